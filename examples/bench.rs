@@ -0,0 +1,28 @@
+//! Run the kernel microbenchmarks. Build with the `benches` feature, e.g.,
+//! `cargo run --release --features benches,qemu --example bench`.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{config, debug::bench, interrupt::declare::handler, task, task::main};
+use stm32f4xx_hal::pac::Interrupt;
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    // The benchmarks need a priority level above the calling task.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY).unwrap();
+
+    bench::run_all(Interrupt::TIM2).unwrap();
+
+    #[cfg(feature = "qemu")]
+    hopter::debug::semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    loop {}
+}
+
+/// The IRQ is only pended by software to measure the IRQ-to-task latency.
+#[handler(TIM2)]
+fn tim2_handler() {
+    bench::irq_handler_allow_isr();
+}
@@ -0,0 +1,109 @@
+//! Tests putting to and getting from a CMSIS-RTOS2 message queue in an ISR.
+//! The ISR must use a zero timeout, and it finds the queue full or empty
+//! instead of blocking.
+
+#![no_main]
+#![no_std]
+#![feature(naked_functions)]
+#![feature(asm_const)]
+
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU32, Ordering},
+};
+use cortex_m::peripheral::NVIC;
+use hopter::{
+    compat::cmsis_rtos2::*,
+    debug::semihosting::{self, dbg_println},
+    interrupt::declare::handler,
+    task::main,
+};
+use stm32f4xx_hal::pac::Interrupt;
+
+const PUT: u32 = 0;
+const PUT_BLOCKING: u32 = 1;
+const GET: u32 = 2;
+
+static QUEUE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+/// What the ISR does when it runs next.
+static ISR_OP: AtomicU32 = AtomicU32::new(PUT);
+/// The value the ISR puts next.
+static NEXT_VALUE: AtomicU32 = AtomicU32::new(1);
+
+#[main]
+fn main(_cp: cortex_m::Peripherals) {
+    unsafe {
+        NVIC::unmask(Interrupt::TIM2);
+    }
+
+    let queue = osMessageQueueNew(2, 4, ptr::null());
+    QUEUE.store(queue, Ordering::SeqCst);
+
+    // The third message finds the queue full.
+    for _ in 0..3 {
+        NVIC::pend(Interrupt::TIM2);
+    }
+    dbg_println!("count: {}", unsafe { osMessageQueueGetCount(queue) });
+    for _ in 0..2 {
+        let mut value = 0u32;
+        let msg = &mut value as *mut u32 as *mut c_void;
+        let status = unsafe { osMessageQueueGet(queue, msg, ptr::null_mut(), osWaitForever) };
+        dbg_println!("got {}: {}", value, status == osOK);
+    }
+
+    // The ISR cannot wait for a free slot.
+    ISR_OP.store(PUT_BLOCKING, Ordering::SeqCst);
+    NVIC::pend(Interrupt::TIM2);
+
+    // The ISR gets a message put by the task, then finds the queue empty.
+    let msg = &42u32 as *const u32 as *const c_void;
+    unsafe { osMessageQueuePut(queue, msg, 0, 0) };
+    ISR_OP.store(GET, Ordering::SeqCst);
+    NVIC::pend(Interrupt::TIM2);
+    NVIC::pend(Interrupt::TIM2);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+#[handler(TIM2)]
+fn tim2_handler() {
+    let queue = QUEUE.load(Ordering::SeqCst);
+    match ISR_OP.load(Ordering::SeqCst) {
+        PUT => {
+            let value = NEXT_VALUE.fetch_add(1, Ordering::SeqCst);
+            let msg = &value as *const u32 as *const c_void;
+            let status = unsafe { osMessageQueuePut(queue, msg, 0, 0) };
+            dbg_println!("ISR puts {}: {}", value, status_name(status));
+        }
+        PUT_BLOCKING => {
+            let msg = &0u32 as *const u32 as *const c_void;
+            let status = unsafe { osMessageQueuePut(queue, msg, 0, 10) };
+            dbg_println!("ISR puts with timeout: {}", status_name(status));
+        }
+        _ => {
+            let mut value = 0u32;
+            let msg = &mut value as *mut u32 as *mut c_void;
+            let status = unsafe { osMessageQueueGet(queue, msg, ptr::null_mut(), 0) };
+            match status == osOK {
+                true => dbg_println!("ISR got {}", value),
+                false => dbg_println!("ISR gets: {}", status_name(status)),
+            }
+        }
+    }
+}
+
+fn status_name(status: osStatus_t) -> &'static str {
+    match status {
+        osOK => "osOK",
+        osErrorResource => "osErrorResource",
+        osErrorParameter => "osErrorParameter",
+        _ => "other",
+    }
+}
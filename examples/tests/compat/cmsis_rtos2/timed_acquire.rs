@@ -0,0 +1,82 @@
+//! Tests that a timed `osSemaphoreAcquire` blocks on the semaphore. It
+//! returns `osErrorTimeout` after the full timeout when nobody releases, and
+//! when the semaphore is released the waiters are woken in the same tick in
+//! priority order.
+
+#![no_main]
+#![no_std]
+
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU32, Ordering},
+};
+use hopter::{
+    compat::cmsis_rtos2::*,
+    debug::semihosting::{self, dbg_println},
+    task::main,
+};
+
+static SEM: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static RELEASE_TICK: AtomicU32 = AtomicU32::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let sem = osSemaphoreNew(1, 0, ptr::null());
+    SEM.store(sem, Ordering::SeqCst);
+
+    let start = osKernelGetTickCount();
+    let status = unsafe { osSemaphoreAcquire(sem, 10) };
+    let waited = osKernelGetTickCount() - start;
+    dbg_println!("acquire timed out: {}", status == osErrorTimeout);
+    dbg_println!("waited full timeout: {}", waited >= 10);
+
+    // Create the lower priority waiter first, so that the wake order cannot
+    // come from the order of waiting.
+    spawn_waiter(osPriorityISR - 11, ptr::null_mut());
+    spawn_waiter(osPriorityISR - 1, 1 as _);
+
+    for _ in 0..2 {
+        osDelay(5);
+        RELEASE_TICK.store(osKernelGetTickCount(), Ordering::SeqCst);
+        unsafe { osSemaphoreRelease(sem) };
+    }
+    osDelay(5);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn spawn_waiter(priority: osPriority_t, arg: *mut c_void) {
+    let attr = osThreadAttr_t {
+        name: ptr::null(),
+        attr_bits: 0,
+        cb_mem: ptr::null_mut(),
+        cb_size: 0,
+        stack_mem: ptr::null_mut(),
+        stack_size: 0,
+        priority,
+        tz_module: 0,
+        reserved: 0,
+    };
+    let id = unsafe { osThreadNew(Some(waiter), arg, &attr) };
+    assert!(!id.is_null());
+}
+
+unsafe extern "C" fn waiter(arg: *mut c_void) {
+    let name = if arg.is_null() { "low" } else { "high" };
+    let sem = SEM.load(Ordering::SeqCst);
+    let status = unsafe { osSemaphoreAcquire(sem, 100) };
+    let same_tick = osKernelGetTickCount() == RELEASE_TICK.load(Ordering::SeqCst);
+    dbg_println!(
+        "{} acquired: {}, in the tick of the release: {}",
+        name,
+        status == osOK,
+        same_tick
+    );
+}
@@ -0,0 +1,123 @@
+//! Tests calling the C interface through `extern "C"` declarations matching
+//! `include/hopter.h`, as a C module would. A task spawned with an argument
+//! sleeps and then releases a semaphore the main task waits on.
+
+#![no_main]
+#![no_std]
+
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU32, Ordering},
+};
+use hopter::{
+    compat::ffi::{
+        HOPTER_ERR_PARAM, HOPTER_ERR_TIMEOUT, HOPTER_ERR_WOULD_BLOCK, HOPTER_OK,
+        HOPTER_WAIT_FOREVER,
+    },
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::main,
+};
+
+type HopterTaskEntry = unsafe extern "C" fn(arg: *mut c_void);
+
+extern "C" {
+    fn hopter_task_spawn(
+        entry: Option<HopterTaskEntry>,
+        arg: *mut c_void,
+        id: u8,
+        priority: u8,
+        stack_size: usize,
+    ) -> i32;
+    fn hopter_task_current_id() -> u8;
+    fn hopter_sleep_ms(ms: u32) -> i32;
+    fn hopter_get_tick() -> u32;
+    fn hopter_semaphore_new(max_count: u32, init_count: u32) -> *mut c_void;
+    fn hopter_semaphore_up(sem: *mut c_void, timeout_ms: u32) -> i32;
+    fn hopter_semaphore_down(sem: *mut c_void, timeout_ms: u32) -> i32;
+    fn hopter_semaphore_count(sem: *mut c_void) -> u32;
+    fn hopter_semaphore_delete(sem: *mut c_void) -> i32;
+}
+
+const WORKER_ID: u8 = 7;
+
+static SEM: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static UP_TICK: AtomicU32 = AtomicU32::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    unsafe {
+        let sem = hopter_semaphore_new(1, 0);
+        SEM.store(sem, Ordering::SeqCst);
+        dbg_println!(
+            "invalid counts rejected: {}",
+            hopter_semaphore_new(1, 2).is_null()
+        );
+
+        // The worker runs first and sleeps.
+        let res = hopter_task_spawn(
+            Some(worker),
+            sem,
+            WORKER_ID,
+            config::DEFAULT_TASK_PRIORITY - 1,
+            0,
+        );
+        dbg_println!("spawned: {}", res == HOPTER_OK);
+        let res = hopter_task_spawn(None, ptr::null_mut(), 0, config::DEFAULT_TASK_PRIORITY, 0);
+        dbg_println!("null entry rejected: {}", res == HOPTER_ERR_PARAM);
+
+        dbg_println!(
+            "down would block: {}",
+            hopter_semaphore_down(sem, 0) == HOPTER_ERR_WOULD_BLOCK
+        );
+        dbg_println!(
+            "down timed out: {}",
+            hopter_semaphore_down(sem, 2) == HOPTER_ERR_TIMEOUT
+        );
+        let res = hopter_semaphore_down(sem, HOPTER_WAIT_FOREVER);
+        let woken = hopter_get_tick();
+        dbg_println!("down succeeded: {}", res == HOPTER_OK);
+        dbg_println!(
+            "woken by the up: {}",
+            woken >= UP_TICK.load(Ordering::SeqCst)
+        );
+        dbg_println!("count: {}", hopter_semaphore_count(sem));
+
+        dbg_println!("up succeeded: {}", hopter_semaphore_up(sem, 0) == HOPTER_OK);
+        dbg_println!(
+            "up would block: {}",
+            hopter_semaphore_up(sem, 0) == HOPTER_ERR_WOULD_BLOCK
+        );
+        dbg_println!("deleted: {}", hopter_semaphore_delete(sem) == HOPTER_OK);
+        dbg_println!(
+            "null deletion rejected: {}",
+            hopter_semaphore_delete(ptr::null_mut()) == HOPTER_ERR_PARAM
+        );
+    }
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+unsafe extern "C" fn worker(arg: *mut c_void) {
+    unsafe {
+        dbg_println!("worker id: {}", hopter_task_current_id());
+        dbg_println!("worker arg: {}", arg == SEM.load(Ordering::SeqCst));
+
+        let start = hopter_get_tick();
+        let res = hopter_sleep_ms(10);
+        dbg_println!(
+            "worker slept: {}",
+            res == HOPTER_OK && hopter_get_tick() - start >= 10
+        );
+
+        UP_TICK.store(hopter_get_tick(), Ordering::SeqCst);
+        dbg_println!("worker up: {}", hopter_semaphore_up(arg, 0) == HOPTER_OK);
+    }
+}
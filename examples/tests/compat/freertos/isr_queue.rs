@@ -0,0 +1,127 @@
+//! Tests sending to and receiving from a FreeRTOS queue in an ISR. The ISR
+//! does not block, it finds the queue full or empty instead, and a task
+//! blocked on the queue is woken up by a message sent from the ISR.
+
+#![no_main]
+#![no_std]
+#![feature(naked_functions)]
+#![feature(asm_const)]
+
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU32, Ordering},
+};
+use cortex_m::peripheral::NVIC;
+use hopter::{
+    compat::freertos::*,
+    debug::semihosting::{self, dbg_println},
+    interrupt::declare::handler,
+    task::main,
+};
+use stm32f4xx_hal::pac::Interrupt;
+
+const SEND: u32 = 0;
+const SEND_BLOCKING: u32 = 1;
+const RECEIVE: u32 = 2;
+
+static QUEUE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+/// What the ISR does when it runs next.
+static ISR_OP: AtomicU32 = AtomicU32::new(SEND);
+/// The value the ISR sends next.
+static NEXT_VALUE: AtomicU32 = AtomicU32::new(1);
+
+#[main]
+fn main(_cp: cortex_m::Peripherals) {
+    unsafe {
+        NVIC::unmask(Interrupt::TIM2);
+    }
+
+    let queue = xQueueCreate(4, 4);
+    QUEUE.store(queue, Ordering::SeqCst);
+
+    // The fifth message finds the queue full.
+    for _ in 0..5 {
+        NVIC::pend(Interrupt::TIM2);
+    }
+    dbg_println!("waiting: {}", unsafe { uxQueueMessagesWaiting(queue) });
+    for _ in 0..4 {
+        dbg_println!("received {}", receive(queue, portMAX_DELAY));
+    }
+
+    // The ISR cannot wait for a free slot.
+    ISR_OP.store(SEND_BLOCKING, Ordering::SeqCst);
+    NVIC::pend(Interrupt::TIM2);
+
+    // A lower priority task triggers the ISR while the main task blocks.
+    ISR_OP.store(SEND, Ordering::SeqCst);
+    unsafe {
+        xTaskCreate(
+            Some(trigger),
+            ptr::null(),
+            0,
+            ptr::null_mut(),
+            0,
+            ptr::null_mut(),
+        )
+    };
+    let start = xTaskGetTickCount();
+    dbg_println!("received {}", receive(queue, 100));
+    dbg_println!(
+        "woken before timeout: {}",
+        xTaskGetTickCount() - start < 100
+    );
+
+    // The ISR receives a message sent by the task, then finds the queue
+    // empty.
+    unsafe { xQueueSend(queue, &42u32 as *const u32 as *const c_void, 0) };
+    ISR_OP.store(RECEIVE, Ordering::SeqCst);
+    NVIC::pend(Interrupt::TIM2);
+    NVIC::pend(Interrupt::TIM2);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn receive(queue: QueueHandle_t, ticks: TickType_t) -> u32 {
+    let mut value = 0u32;
+    let res = unsafe { xQueueReceive(queue, &mut value as *mut u32 as *mut c_void, ticks) };
+    assert_eq!(res, pdTRUE);
+    value
+}
+
+unsafe extern "C" fn trigger(_: *mut c_void) {
+    NVIC::pend(Interrupt::TIM2);
+}
+
+#[handler(TIM2)]
+fn tim2_handler() {
+    let queue = QUEUE.load(Ordering::SeqCst);
+    match ISR_OP.load(Ordering::SeqCst) {
+        SEND => {
+            let value = NEXT_VALUE.fetch_add(1, Ordering::SeqCst);
+            let item = &value as *const u32 as *const c_void;
+            let res = unsafe { xQueueSendFromISR(queue, item, ptr::null_mut()) };
+            dbg_println!("ISR sends {}: {}", value, res == pdTRUE);
+        }
+        SEND_BLOCKING => {
+            let item = &0u32 as *const u32 as *const c_void;
+            let res = unsafe { xQueueSend(queue, item, 10) };
+            dbg_println!("ISR sends with timeout: {}", res == pdTRUE);
+        }
+        _ => {
+            let mut value = 0u32;
+            let buffer = &mut value as *mut u32 as *mut c_void;
+            let res = unsafe { xQueueReceiveFromISR(queue, buffer, ptr::null_mut()) };
+            match res == pdTRUE {
+                true => dbg_println!("ISR received {}", value),
+                false => dbg_println!("ISR found queue empty"),
+            }
+        }
+    }
+}
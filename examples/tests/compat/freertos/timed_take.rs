@@ -0,0 +1,69 @@
+//! Tests that a timed `xSemaphoreTake` blocks on the semaphore. It returns
+//! `pdFALSE` after the full timeout when nobody gives, and when the semaphore
+//! is given the waiters are woken in the same tick in priority order.
+
+#![no_main]
+#![no_std]
+
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU32, Ordering},
+};
+use hopter::{
+    compat::freertos::*,
+    debug::semihosting::{self, dbg_println},
+    task::main,
+};
+
+static SEM: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+static GIVE_TICK: AtomicU32 = AtomicU32::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let sem = xSemaphoreCreateBinary();
+    SEM.store(sem, Ordering::SeqCst);
+
+    let start = xTaskGetTickCount();
+    let res = unsafe { xSemaphoreTake(sem, 10) };
+    let waited = xTaskGetTickCount() - start;
+    dbg_println!("take timed out: {}", res == pdFALSE);
+    dbg_println!("waited full timeout: {}", waited >= 10);
+
+    // Create the lower priority waiter first, so that the wake order cannot
+    // come from the order of waiting.
+    let low = configMAX_PRIORITIES - 2;
+    let high = configMAX_PRIORITIES - 1;
+    unsafe {
+        xTaskCreate(Some(waiter), ptr::null(), 0, 0 as _, low, ptr::null_mut());
+        xTaskCreate(Some(waiter), ptr::null(), 0, 1 as _, high, ptr::null_mut());
+    }
+
+    for _ in 0..2 {
+        vTaskDelay(5);
+        GIVE_TICK.store(xTaskGetTickCount(), Ordering::SeqCst);
+        unsafe { xSemaphoreGive(sem) };
+    }
+    vTaskDelay(5);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+unsafe extern "C" fn waiter(arg: *mut c_void) {
+    let name = if arg.is_null() { "low" } else { "high" };
+    let sem = SEM.load(Ordering::SeqCst);
+    let res = unsafe { xSemaphoreTake(sem, 100) };
+    let same_tick = xTaskGetTickCount() == GIVE_TICK.load(Ordering::SeqCst);
+    dbg_println!(
+        "{} took: {}, in the tick of the give: {}",
+        name,
+        res == pdTRUE,
+        same_tick
+    );
+}
@@ -0,0 +1,76 @@
+//! Tests that changing the tunable log level at runtime takes effect on the
+//! following log messages, and that level 0 disables all of them.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::{string::String, vec::Vec};
+use hopter::{
+    config::tunable,
+    debug::{
+        log::{self, log_debug, log_error, log_info, log_warn, Level, LogSink},
+        semihosting::{self, dbg_println},
+    },
+    sync::Mutex,
+    task::main,
+    time,
+};
+
+/// The lines written by the logger task.
+static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct CollectSink {
+    line: String,
+}
+
+impl LogSink for CollectSink {
+    fn write(&mut self, data: &[u8]) {
+        for &byte in data {
+            if byte == b'\n' {
+                LINES.lock().push(core::mem::take(&mut self.line));
+            } else {
+                self.line.push(byte as char);
+            }
+        }
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    log::start(CollectSink {
+        line: String::new(),
+    })
+    .unwrap();
+
+    tunable::set_log_level(Level::Warn as u32);
+    dbg_println!("log level: {}", tunable::log_level());
+    log_warn!("warn at warn level");
+    log_info!("info at warn level");
+
+    tunable::set_log_level(Level::Info as u32);
+    dbg_println!("log level: {}", tunable::log_level());
+    log_info!("info at info level");
+    log_debug!("debug at info level");
+
+    tunable::set_log_level(0);
+    dbg_println!("log level: {}", tunable::log_level());
+    log_error!("error at level 0");
+
+    tunable::set_log_level(Level::Debug as u32);
+    dbg_println!("log level: {}", tunable::log_level());
+    log_debug!("debug at debug level");
+
+    // Let the logger task write the lines.
+    time::sleep_ms(10).unwrap();
+
+    // The lines look like: "[<tick>] [<level>] task <id>: <message>".
+    for line in LINES.lock().iter() {
+        let (_, rest) = line.split_once("] ").unwrap();
+        let (level, _) = rest.split_once(' ').unwrap();
+        let (_, message) = rest.split_once(": ").unwrap();
+        dbg_println!("{} {}", level, message);
+    }
+
+    semihosting::terminate(true);
+}
@@ -0,0 +1,31 @@
+//! Tests that without a preset feature or overrides, the configuration
+//! parameters covered by the presets take the values of the default preset.
+
+#![no_main]
+#![no_std]
+
+use hopter::{
+    config::{self, preset, tunable},
+    debug::semihosting::{self, dbg_println},
+    task::main,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let selected = &preset::SELECTED;
+    dbg_println!("preset: {}", selected.name);
+
+    let matches = config::LOG_QUEUE_LENGTH == selected.log_queue_length
+        && config::TRACE_BUFFER_LENGTH == selected.trace_buffer_length
+        && config::TRACE_FLUSH_PERIOD_MS == selected.trace_flush_period_ms
+        && config::EVENTS_RING_LENGTH == selected.events_ring_length
+        && config::ENABLE_STACKLET_CACHE == selected.enable_stacklet_cache
+        && config::NET_MAX_POLL_INTERVAL_MS == selected.net_max_poll_interval_ms
+        && config::NET_TCP_BUFFER_SIZE == selected.net_tcp_buffer_size;
+    dbg_println!("parameters match: {}", matches);
+
+    dbg_println!("log level: {}", tunable::log_level());
+    dbg_println!("offload timeout: {} ms", tunable::offload_timeout_ms());
+
+    semihosting::terminate(true);
+}
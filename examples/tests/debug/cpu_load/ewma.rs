@@ -0,0 +1,59 @@
+//! Tests that the load averaged over one second rises quickly when a task
+//! keeps the CPU busy, while the load averaged over ten seconds rises slowly,
+//! and that both fall when the CPU is idle again.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    schedule,
+    task::{self, main},
+    time,
+};
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    // Run above the busy task, so that the main task wakes up while it
+    // spins.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY - 2).unwrap();
+
+    time::sleep_ms(1500).unwrap();
+    let load = schedule::cpu_load();
+    dbg_println!("idle, 1s below 10%: {}", load.last_second() < 100);
+
+    task::build()
+        .set_entry(|| {
+            while !STOP.load(Ordering::SeqCst) {
+                core::hint::spin_loop();
+            }
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    time::sleep_ms(2000).unwrap();
+    let load = schedule::cpu_load();
+    dbg_println!("busy, 1s above 80%: {}", load.last_second() > 800);
+    dbg_println!(
+        "busy, 10s between 10% and 50%: {}",
+        (100..500).contains(&load.last_ten_seconds())
+    );
+
+    STOP.store(true, Ordering::SeqCst);
+    time::sleep_ms(2000).unwrap();
+    let load = schedule::cpu_load();
+    dbg_println!("idle again, 1s below 20%: {}", load.last_second() < 200);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,69 @@
+//! Tests that the crash log persists an entry for the boot and for a
+//! panicking task with its unwound frames on the RAM flash backend, and that
+//! clearing the log erases them.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::vec::Vec;
+use hopter::{
+    debug::{
+        crash_log::{self, CrashEntry, CrashKind},
+        semihosting::{self, dbg_println},
+    },
+    fs::RamFlash,
+    task,
+    task::main,
+    time,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    crash_log::start(RamFlash::new(1024, 2)).unwrap();
+
+    task::build()
+        .set_id(3)
+        .set_entry(|| panic!("boom"))
+        .spawn()
+        .unwrap();
+
+    // Let the task unwind and the writer task persist the entry.
+    time::sleep_ms(50).unwrap();
+
+    let mut entries: Vec<CrashEntry> = Vec::new();
+    crash_log::for_each(|entry| entries.push(entry.clone())).unwrap();
+    dbg_println!("Entry count {}", entries.len());
+    for entry in entries.iter() {
+        match entry.kind() {
+            CrashKind::Reset => dbg_println!("Reset entry #{}", entry.seq()),
+            CrashKind::Panic => {
+                dbg_println!(
+                    "Panic entry #{} task {} in ISR {}",
+                    entry.seq(),
+                    entry.task_id(),
+                    entry.in_isr()
+                );
+                // The text of the panic information depends on the toolchain.
+                dbg_println!("Reason has message: {}", entry.reason().contains("boom"));
+                dbg_println!("Has frames: {}", !entry.frames().is_empty());
+            }
+        }
+    }
+
+    // Starting again is refused, and the entries are kept.
+    dbg_println!("{:?}", crash_log::start(RamFlash::new(1024, 2)));
+
+    crash_log::clear().unwrap();
+    let mut count = 0;
+    crash_log::for_each(|_| count += 1).unwrap();
+    dbg_println!("Entry count after clear {}", count);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,45 @@
+//! Tests that the event ring starts with the boot event, keeps the newest
+//! events in order once full, and overwrites the oldest ones.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::vec::Vec;
+use hopter::{
+    config,
+    debug::{
+        events::{self, BOOT_CODE},
+        semihosting::{self, dbg_println},
+    },
+    task::main,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let mut recorded = Vec::new();
+    events::for_each(|event| recorded.push(event));
+    dbg_println!("{} event(s) after boot", recorded.len());
+    dbg_println!("boot event: {}", recorded[0].code() == BOOT_CODE);
+
+    let total = config::EVENTS_RING_LENGTH as u32 + 6;
+    for code in 1..=total {
+        events::record(code as u16, code * 10);
+    }
+
+    recorded.clear();
+    events::for_each(|event| recorded.push(event));
+    dbg_println!("{} event(s) kept", recorded.len());
+
+    let oldest = recorded.first().unwrap();
+    let newest = recorded.last().unwrap();
+    dbg_println!("oldest: code {} arg {}", oldest.code(), oldest.arg());
+    dbg_println!("newest: code {} arg {}", newest.code(), newest.arg());
+
+    let in_order = recorded
+        .windows(2)
+        .all(|pair| pair[1].seq() == pair[0].seq() + 1 && pair[1].stamp() >= pair[0].stamp());
+    dbg_println!("in order: {}", in_order);
+
+    semihosting::terminate(true);
+}
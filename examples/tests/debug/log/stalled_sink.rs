@@ -0,0 +1,70 @@
+//! Tests that logging does not block while the logger task is stalled on a
+//! full sink, and that messages exceeding the queue are dropped and counted.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::string::String;
+use hopter::{
+    config::{self, tunable},
+    debug::{
+        log::{self, log_info},
+        semihosting::{self, dbg_println},
+    },
+    sync, task,
+    task::main,
+    time,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    tunable::set_log_level(log::Level::Info as u32);
+
+    // The channel is too small to hold a single log line, so the logger task
+    // blocks on the first line until the channel is drained.
+    let (producer, consumer) = sync::create_channel::<u8, 8>();
+    log::start(producer).unwrap();
+
+    task::build()
+        .set_id(2)
+        .set_priority(5)
+        .set_entry(|| log_info!("from task 2"))
+        .spawn()
+        .unwrap();
+
+    // Let the logger task write the first line and stall.
+    time::sleep_ms(10).unwrap();
+
+    task::build()
+        .set_id(1)
+        .set_priority(1)
+        .set_entry(|| {
+            for i in 0..config::LOG_QUEUE_LENGTH + 2 {
+                log_info!("message {}", i);
+            }
+            dbg_println!("task 1 did not block");
+        })
+        .spawn()
+        .unwrap();
+
+    time::sleep_ms(10).unwrap();
+
+    // The first line, the queued lines, and the line reporting the drops.
+    let mut lines = config::LOG_QUEUE_LENGTH + 2;
+    let mut line = String::new();
+    while lines > 0 {
+        let byte = consumer.consume();
+        if byte != b'\n' {
+            line.push(byte as char);
+            continue;
+        }
+        // Strip the tick count which varies between runs.
+        let (_, rest) = line.split_once("] ").unwrap();
+        dbg_println!("{}", rest);
+        line.clear();
+        lines -= 1;
+    }
+
+    semihosting::terminate(true);
+}
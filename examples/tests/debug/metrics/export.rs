@@ -0,0 +1,113 @@
+//! Tests that the registered metrics are exported with their values in both
+//! formats after the kernel metrics, that a name refers to a single metric,
+//! and that an exporter task writes snapshots periodically.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    metrics::{self, Format, MetricsError, MetricsSink},
+    task::main,
+    time,
+};
+
+/// The number of kernel metrics in a snapshot without `irq_stats`.
+const KERNEL_METRICS: u16 = 6;
+
+/// Print the lines of the registered metrics.
+struct PrintSink;
+
+impl MetricsSink for PrintSink {
+    fn write(&mut self, data: &[u8]) {
+        let text = core::str::from_utf8(data).unwrap();
+        let kernel = text
+            .lines()
+            .filter(|line| line.starts_with("kernel."))
+            .count();
+        dbg_println!("kernel lines: {}", kernel);
+        for line in text.lines() {
+            if !line.is_empty() && !line.starts_with("kernel.") {
+                dbg_println!("{}", line);
+            }
+        }
+    }
+}
+
+/// Check the header of binary frames.
+struct BinarySink;
+
+impl MetricsSink for BinarySink {
+    fn write(&mut self, data: &[u8]) {
+        dbg_println!("binary magic: {}", data[..2] == [0xA5, b'M']);
+        let records = u16::from_le_bytes([data[6], data[7]]);
+        dbg_println!("binary records: {}", records - KERNEL_METRICS);
+    }
+}
+
+/// Count the snapshots written by an exporter task.
+struct CountSink(Arc<AtomicU32>);
+
+impl MetricsSink for CountSink {
+    fn write(&mut self, _data: &[u8]) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let count = metrics::counter("test.count").unwrap();
+    for _ in 0..3 {
+        count.increment();
+    }
+    count.add(2);
+    // Registering the name again returns the same counter.
+    dbg_println!(
+        "same counter: {}",
+        metrics::counter("test.count").unwrap().get()
+    );
+    dbg_println!(
+        "name taken: {}",
+        metrics::gauge("test.count").err() == Some(MetricsError::NameTaken)
+    );
+
+    let level = metrics::gauge("test.level").unwrap();
+    level.set(10);
+    level.add(-3);
+
+    let latency = metrics::histogram("test.latency", &[10, 100]).unwrap();
+    for value in [5, 50, 500] {
+        latency.record(value);
+    }
+    dbg_println!(
+        "invalid buckets: {}",
+        metrics::histogram("test.unordered", &[100, 10]).err()
+            == Some(MetricsError::InvalidBuckets)
+    );
+
+    metrics::export(&mut PrintSink, Format::Line);
+    metrics::export(&mut BinarySink, Format::Binary);
+
+    dbg_println!(
+        "invalid period: {}",
+        metrics::start(PrintSink, Format::Line, 0).err() == Some(MetricsError::InvalidPeriod)
+    );
+    let exported = Arc::new(AtomicU32::new(0));
+    metrics::start(CountSink(exported.clone()), Format::Binary, 10).unwrap();
+    time::sleep_ms(45).unwrap();
+    dbg_println!(
+        "exported periodically: {}",
+        exported.load(Ordering::SeqCst) >= 3
+    );
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,50 @@
+//! Tests that a panicking task is reported through the sink in JSON, with
+//! the message escaped.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::string::String;
+use hopter::{
+    debug::{
+        panic_report::{self, Json},
+        semihosting::{self, dbg_println},
+    },
+    sync, task,
+    task::main,
+    time,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let (producer, consumer) = sync::create_channel::<u8, 512>();
+    panic_report::set_sink(producer);
+    panic_report::set_formatter(Json);
+
+    task::build()
+        .set_id(3)
+        .set_entry(|| panic!("boom \"quoted\""))
+        .spawn()
+        .unwrap();
+
+    time::sleep_ms(10).unwrap();
+
+    let mut line = String::new();
+    loop {
+        let byte = consumer.consume();
+        if byte == b'\n' {
+            break;
+        }
+        line.push(byte as char);
+    }
+
+    // Strip the tick count which varies between runs.
+    let (_, rest) = line.split_once(",\"task\"").unwrap();
+    // The text of the panic information depends on the toolchain.
+    let (fields, report) = rest.split_once(",\"report\"").unwrap();
+    dbg_println!("{}", fields);
+    dbg_println!("{}", report.contains("boom \\\"quoted\\\""));
+
+    semihosting::terminate(true);
+}
@@ -0,0 +1,56 @@
+//! Tests that claiming a peripheral held by another task fails with the
+//! owner, and that the peripheral can be claimed again once released.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config::tunable,
+    debug::{
+        log::Level,
+        semihosting::{self, dbg_println},
+    },
+    peripheral,
+    sync::Mailbox,
+    task,
+    task::main,
+};
+
+static CLAIMED: Mailbox = Mailbox::new();
+static RELEASE: Mailbox = Mailbox::new();
+static RELEASED: Mailbox = Mailbox::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    tunable::set_log_level(Level::Error as u32);
+
+    task::build().set_id(1).set_entry(owner).spawn().unwrap();
+
+    CLAIMED.wait();
+    peripheral::for_each_claim(|name, task_id| dbg_println!("{} task {}", name, task_id));
+
+    let result = peripheral::claim("USART1");
+    dbg_println!("{:?}", result.as_ref().err());
+    dbg_println!("{:?}", peripheral::owner("TIM2"));
+
+    RELEASE.notify_allow_isr();
+    RELEASED.wait();
+
+    let _usart = peripheral::claim("USART1").unwrap();
+    dbg_println!("claimed USART1 after release");
+    dbg_println!("{:?}", peripheral::claim("").err());
+
+    semihosting::terminate(true);
+}
+
+fn owner() {
+    let usart = peripheral::claim("USART1").unwrap();
+    let _tim = peripheral::claim("TIM2").unwrap();
+    CLAIMED.notify_allow_isr();
+
+    RELEASE.wait();
+    drop(usart);
+    dbg_println!("task 1 released USART1");
+    RELEASED.notify_allow_isr();
+}
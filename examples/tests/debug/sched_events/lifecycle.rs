@@ -0,0 +1,63 @@
+//! Tests that the scheduler events of a task are recorded in order as it is
+//! spawned, blocks on a mailbox, is woken up, and panics.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::vec::Vec;
+use hopter::{
+    config,
+    debug::{
+        sched_events::{self, SchedEventKind},
+        semihosting::{self, dbg_println},
+    },
+    sync::Mailbox,
+    task::{self, main},
+    time,
+};
+
+static WAKE: Mailbox = Mailbox::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let mut boot_recorded = false;
+    sched_events::for_each(|event| boot_recorded |= event.kind() == SchedEventKind::Boot);
+    dbg_println!("boot recorded: {}", boot_recorded);
+
+    // The worker preempts the main task and blocks.
+    task::build()
+        .set_name("worker")
+        .set_entry(|| {
+            WAKE.wait();
+            panic!("worker panics");
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    let worker = task::find_by_name("worker").unwrap();
+
+    WAKE.notify_allow_isr();
+    time::sleep_ms(5).unwrap();
+
+    let mut kinds = Vec::new();
+    let mut last_seq = None;
+    let mut in_order = true;
+    sched_events::for_each(|event| {
+        in_order &= last_seq.map_or(true, |seq| event.seq() > seq);
+        last_seq = Some(event.seq());
+        if event.task() == Some(worker) {
+            kinds.push(event.kind().name());
+        }
+    });
+    dbg_println!("worker events: {}", kinds.join(" "));
+    dbg_println!("in order: {}", in_order);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,176 @@
+//! Tests that the soft lock statistics count the full and the pend-only
+//! accesses, and the largest batch of pended operations. A nested access
+//! while the full access is held gets pend-only access, and an ISR waking up
+//! a task during a context switch pends the insertion into the ready queue.
+
+#![no_main]
+#![no_std]
+#![feature(naked_functions)]
+#![feature(asm_const)]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::peripheral::NVIC;
+use hopter::{
+    config,
+    debug::{
+        semihosting::{self, dbg_println},
+        soft_lock_stats::{self, SoftLockStats},
+    },
+    interrupt::declare::handler,
+    sync::{Access, AllowPendOp, Mailbox, RefCellSchedSafe, RunPendedOp, SoftLock, Spin},
+    task::{self, main},
+    time,
+};
+use stm32f4xx_hal::pac::Interrupt;
+
+struct Inner {
+    total: Spin<u32>,
+    pended: AtomicU32,
+}
+
+struct FullAccessor<'a>(&'a Inner);
+struct PendAccessor<'a>(&'a AtomicU32);
+
+impl<'a> AllowPendOp<'a> for Inner {
+    type FullAccessor = FullAccessor<'a>;
+    type PendOnlyAccessor = PendAccessor<'a>;
+    fn full_access(&'a self) -> Self::FullAccessor {
+        FullAccessor(self)
+    }
+    fn pend_only_access(&'a self) -> Self::PendOnlyAccessor {
+        PendAccessor(&self.pended)
+    }
+}
+
+impl RunPendedOp for FullAccessor<'_> {
+    fn run_pended_op(&mut self) {
+        *self.0.total.lock() += self.0.pended.swap(0, Ordering::SeqCst);
+    }
+}
+
+static EVENTS: RefCellSchedSafe<SoftLock<Inner>> = RefCellSchedSafe::new(SoftLock::new(Inner {
+    total: Spin::new(0),
+    pended: AtomicU32::new(0),
+}));
+
+static WAKE: Mailbox = Mailbox::new();
+
+/// Record an event, either directly or by pending it.
+fn record() {
+    EVENTS.with_suspended_scheduler(|events, _| {
+        events.with_access(|access| match access {
+            Access::Full { full_access } => *full_access.0.total.lock() += 1,
+            Access::PendOnly { pend_access } => {
+                pend_access.0.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+    });
+}
+
+/// Record events while holding the full access, so that they are pended.
+fn record_nested(count: u32) {
+    EVENTS.with_suspended_scheduler(|events, _| {
+        events.must_with_full_access(|_full_access| {
+            for _ in 0..count {
+                record();
+            }
+        })
+    });
+}
+
+/// Pend the ISR when dropped. The entry closure of a restartable task is
+/// dropped together with the task struct by the context switch that follows
+/// the task's return, while the ready queue is held. The copies of the
+/// closure run by the task are disarmed.
+struct PendOnDrop {
+    armed: bool,
+}
+
+impl Clone for PendOnDrop {
+    fn clone(&self) -> Self {
+        Self { armed: false }
+    }
+}
+
+impl Drop for PendOnDrop {
+    fn drop(&mut self) {
+        if self.armed {
+            NVIC::pend(Interrupt::TIM2);
+        }
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    unsafe {
+        NVIC::unmask(Interrupt::TIM2);
+    }
+
+    record();
+    record_nested(3);
+    record_nested(1);
+    let stats = EVENTS.with_suspended_scheduler(|events, _| events.stats());
+    dbg_println!(
+        "full {}, pend-only {}, max pended {}",
+        stats.full,
+        stats.pend_only,
+        stats.max_pended
+    );
+
+    let merged = stats.merge(SoftLockStats {
+        full: 1,
+        pend_only: 1,
+        max_pended: 5,
+    });
+    dbg_println!(
+        "merged: full {}, pend-only {}, max pended {}",
+        merged.full,
+        merged.pend_only,
+        merged.max_pended
+    );
+
+    // The task blocks on the mailbox. The ISR wakes it up while the context
+    // switch after the short-lived task holds the ready queue.
+    task::build()
+        .set_entry(|| {
+            WAKE.wait();
+            dbg_println!("woken up");
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    let before = soft_lock_stats::ready_queue();
+    let guard = PendOnDrop { armed: true };
+    task::build()
+        .set_entry(move || {
+            let _guard = &guard;
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn_restartable()
+        .unwrap();
+    time::sleep_ms(5).unwrap();
+    let after = soft_lock_stats::ready_queue();
+    dbg_println!(
+        "ready queue pended: {}",
+        after.pend_only > before.pend_only && after.max_pended >= 1
+    );
+
+    let mailbox = WAKE.soft_lock_stats();
+    dbg_println!(
+        "mailbox uncontended: {}",
+        mailbox.full > 0 && mailbox.pend_only == 0
+    );
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+#[handler(TIM2)]
+fn tim2_handler() {
+    WAKE.notify_allow_isr();
+}
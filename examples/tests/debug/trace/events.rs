@@ -0,0 +1,105 @@
+//! Tests that the trace task writes the recorded spans, IRQ scopes, and
+//! context switches to the sink, tagged with the recording task or as
+//! recorded in ISR context.
+
+#![no_main]
+#![no_std]
+#![feature(naked_functions)]
+#![feature(asm_const)]
+
+extern crate alloc;
+use alloc::{string::String, vec::Vec};
+use cortex_m::peripheral::NVIC;
+use hopter::{
+    config,
+    debug::{
+        semihosting::{self, dbg_println},
+        trace::{self, span, TraceError, TraceSink},
+    },
+    interrupt::declare::handler,
+    sync::Mutex,
+    task::{self, main},
+    time,
+};
+use stm32f4xx_hal::pac::Interrupt;
+
+const WORKER_ID: u8 = 42;
+
+/// The lines written by the trace task.
+static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct CollectSink;
+
+impl TraceSink for CollectSink {
+    fn write(&mut self, data: &[u8]) {
+        let line = core::str::from_utf8(data).unwrap();
+        LINES.lock().push(String::from(line.trim_end()));
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    unsafe {
+        NVIC::unmask(Interrupt::TIM2);
+    }
+
+    trace::start(CollectSink).unwrap();
+    dbg_println!(
+        "started twice: {}",
+        trace::start(CollectSink).err() == Some(TraceError::AlreadyStarted)
+    );
+
+    // The worker preempts the main task and records its span first.
+    task::build()
+        .set_id(WORKER_ID)
+        .set_entry(|| {
+            let _span = span!("worker_phase");
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    {
+        let _span = span!("main_phase");
+    }
+    NVIC::pend(Interrupt::TIM2);
+    dbg_println!(
+        "no scope outside IRQ: {}",
+        trace::irq_scope_allow_isr().is_none()
+    );
+
+    time::sleep_ms(config::TRACE_FLUSH_PERIOD_MS * 3).unwrap();
+
+    let lines = LINES.lock();
+    let mut worker_switched = false;
+    for line in lines.iter() {
+        // The lines look like: "trace,<cycles>,<task>,<event>,<arg>".
+        let fields: Vec<&str> = line.split(',').collect();
+        let (task, event, arg) = (fields[2], fields[3], fields[4]);
+        let task_id = task.parse::<u8>().ok();
+        if event == "switch" {
+            worker_switched |= task_id == Some(WORKER_ID);
+            continue;
+        }
+        let task = match task_id {
+            Some(config::MAIN_TASK_ID) => "main",
+            Some(WORKER_ID) => "worker",
+            _ => task,
+        };
+        dbg_println!("{} {} {}", task, event, arg);
+    }
+    dbg_println!("worker switched on: {}", worker_switched);
+    drop(lines);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+#[handler(TIM2)]
+fn tim2_handler() {
+    let _irq = trace::irq_scope_allow_isr();
+}
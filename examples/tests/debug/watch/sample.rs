@@ -0,0 +1,84 @@
+//! Tests that the trace task samples the watched variables and memory words
+//! into the trace stream, and that an unwatched variable is no longer
+//! sampled.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::{string::String, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
+use hopter::{
+    config,
+    debug::{
+        semihosting::{self, dbg_println},
+        trace::{self, TraceSink},
+        watch::{self, WatchError},
+    },
+    sync::Mutex,
+    task::main,
+    time,
+};
+
+static COUNT: AtomicU32 = AtomicU32::new(7);
+static LEVEL: AtomicI32 = AtomicI32::new(-5);
+static READY: AtomicBool = AtomicBool::new(true);
+static WORD: u32 = 0xDEAD_BEEF;
+
+/// The arguments of the `sample` events written by the trace task.
+static SAMPLES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct SampleSink;
+
+impl TraceSink for SampleSink {
+    fn write(&mut self, data: &[u8]) {
+        // The lines look like: "trace,<cycles>,<task>,<event>,<arg>".
+        let line = core::str::from_utf8(data).unwrap().trim_end();
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields[3] == "sample" {
+            SAMPLES.lock().push(String::from(fields[4]));
+        }
+    }
+}
+
+/// Wait for a sampling round and print the distinct samples in order.
+fn print_samples() {
+    time::sleep_ms(config::TRACE_WATCH_PERIOD_MS + config::TRACE_FLUSH_PERIOD_MS * 2).unwrap();
+    let samples = SAMPLES.lock();
+    let mut printed: Vec<&String> = Vec::new();
+    for sample in samples.iter() {
+        if !printed.contains(&sample) {
+            dbg_println!("{}", sample);
+            printed.push(sample);
+        }
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    watch::watch("test.count", &COUNT).unwrap();
+    watch::watch("test.level", &LEVEL).unwrap();
+    watch::watch("test.ready", &READY).unwrap();
+    unsafe { watch::watch_address("test.word", &WORD) }.unwrap();
+    dbg_println!(
+        "name taken: {}",
+        watch::watch("test.count", &LEVEL) == Err(WatchError::NameTaken)
+    );
+
+    trace::start(SampleSink).unwrap();
+    print_samples();
+
+    dbg_println!("unwatched: {}", watch::unwatch("test.level"));
+    dbg_println!("unwatched again: {}", watch::unwatch("test.level"));
+    COUNT.fetch_add(1, Ordering::SeqCst);
+    SAMPLES.lock().clear();
+    print_samples();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,58 @@
+//! Tests that a file written once survives the compaction of the blocks it
+//! is stored in while another file is rewritten many times, both before and
+//! after the filesystem is mounted again.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::format;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    fs::{self, FlashBackend, RamFlash},
+    task::main,
+};
+
+const BLOCK_SIZE: usize = 256;
+const BLOCK_COUNT: usize = 3;
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let mut flash = RamFlash::new(BLOCK_SIZE, BLOCK_COUNT);
+    fs::format(&mut flash).unwrap();
+    fs::mount(flash.clone()).unwrap();
+
+    fs::write("kept", b"written once").unwrap();
+    // Each version takes 32 bytes, so the versions fill all blocks many
+    // times over.
+    for version in 0..60 {
+        fs::write("churn", format!("version {:02}", version).as_bytes()).unwrap();
+    }
+    print_files();
+
+    // The block holding the first records has been erased since.
+    let mut seq = [0; 4];
+    flash.read(4, &mut seq).unwrap();
+    dbg_println!("first block compacted: {}", u32::from_le_bytes(seq) != 0);
+
+    fs::unmount().unwrap();
+    fs::mount(flash).unwrap();
+    print_files();
+    fs::unmount().unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn print_files() {
+    let mut buf = [0; 16];
+    let len = fs::read("kept", &mut buf).unwrap();
+    dbg_println!("kept: {}", core::str::from_utf8(&buf[..len]).unwrap());
+    let len = fs::read("churn", &mut buf).unwrap();
+    dbg_println!("churn: {}", core::str::from_utf8(&buf[..len]).unwrap());
+}
@@ -0,0 +1,52 @@
+//! Tests that removing a file hides all its earlier versions, including one
+//! in an older block, also after the filesystem is mounted again, and that a
+//! version written after the removal is found.
+
+#![no_main]
+#![no_std]
+
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    fs::{self, RamFlash},
+    task::main,
+};
+
+const BLOCK_SIZE: usize = 512;
+const BLOCK_COUNT: usize = 4;
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let mut flash = RamFlash::new(BLOCK_SIZE, BLOCK_COUNT);
+    fs::format(&mut flash).unwrap();
+    fs::mount(flash.clone()).unwrap();
+
+    fs::write("gone", b"version 1").unwrap();
+    // Each filler takes 64 bytes, so the next records go to another block.
+    for _ in 0..8 {
+        fs::write("filler", &[0x5A; 40]).unwrap();
+    }
+    fs::write("gone", b"version 2").unwrap();
+    fs::remove("gone").unwrap();
+    dbg_println!("exists after remove: {}", fs::exists("gone").unwrap());
+
+    fs::unmount().unwrap();
+    fs::mount(flash.clone()).unwrap();
+    dbg_println!("exists after remount: {}", fs::exists("gone").unwrap());
+    let mut buf = [0; 16];
+    dbg_println!("{:?}", fs::read("gone", &mut buf));
+
+    fs::write("gone", b"version 3").unwrap();
+    fs::unmount().unwrap();
+    fs::mount(flash).unwrap();
+    let len = fs::read("gone", &mut buf).unwrap();
+    dbg_println!("gone: {}", core::str::from_utf8(&buf[..len]).unwrap());
+    fs::unmount().unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
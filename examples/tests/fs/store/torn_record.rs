@@ -0,0 +1,93 @@
+//! Tests that a record interrupted before its commit mark was written, or
+//! whose content does not match its CRC, is discarded when the filesystem is
+//! mounted, while the records before it are kept.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::vec;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    fs::{self, FlashBackend, RamFlash},
+    task::main,
+};
+
+const BLOCK_SIZE: usize = 512;
+const BLOCK_COUNT: usize = 4;
+/// The size of a record header preceding the name, see the `fs` module.
+const RECORD_HEADER_SIZE: usize = 12;
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let mut flash = RamFlash::new(BLOCK_SIZE, BLOCK_COUNT);
+    fs::format(&mut flash).unwrap();
+    fs::mount(flash.clone()).unwrap();
+    fs::write("kept", b"committed").unwrap();
+    fs::write("torn", b"interrupted").unwrap();
+    fs::unmount().unwrap();
+
+    // The power was lost before the commit mark of the last record was
+    // written.
+    let torn = edited_copy(&mut flash, |image| {
+        let commit = record_offset(image, "torn") + RECORD_HEADER_SIZE + align4(4 + 11);
+        image[commit..commit + 4].fill(0xFF);
+    });
+    fs::mount(torn).unwrap();
+    print_files();
+
+    // The filesystem is still writable after the discarded record.
+    fs::write("torn", b"rewritten").unwrap();
+    let mut buf = [0; 16];
+    let len = fs::read("torn", &mut buf).unwrap();
+    dbg_println!("torn: {}", core::str::from_utf8(&buf[..len]).unwrap());
+    fs::unmount().unwrap();
+
+    // A bit of the data of the last record flipped.
+    let corrupted = edited_copy(&mut flash, |image| {
+        let data = record_offset(image, "torn") + RECORD_HEADER_SIZE + 4;
+        image[data] ^= 1;
+    });
+    fs::mount(corrupted).unwrap();
+    print_files();
+    fs::unmount().unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn print_files() {
+    dbg_println!("torn exists: {}", fs::exists("torn").unwrap());
+    let mut buf = [0; 16];
+    let len = fs::read("kept", &mut buf).unwrap();
+    dbg_println!("kept: {}", core::str::from_utf8(&buf[..len]).unwrap());
+}
+
+/// Copy the content of the flash into a new one after letting `edit` change
+/// it. Writing can only clear bits, so a damaged record is made on a copy.
+fn edited_copy(flash: &mut RamFlash, edit: impl FnOnce(&mut [u8])) -> RamFlash {
+    let mut image = vec![0; BLOCK_SIZE * BLOCK_COUNT];
+    flash.read(0, &mut image).unwrap();
+    edit(&mut image);
+    let mut copy = RamFlash::new(BLOCK_SIZE, BLOCK_COUNT);
+    copy.write(0, &image).unwrap();
+    copy
+}
+
+/// Return the offset of the latest record with the given name.
+fn record_offset(image: &[u8], name: &str) -> usize {
+    image
+        .windows(name.len())
+        .rposition(|bytes| bytes == name.as_bytes())
+        .unwrap()
+        - RECORD_HEADER_SIZE
+}
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
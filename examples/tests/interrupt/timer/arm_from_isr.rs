@@ -0,0 +1,75 @@
+//! Test arming one-shot kernel timers from an ISR and from a task. The task
+//! waiting on the mailboxes is notified only after the delays expire.
+
+#![no_main]
+#![no_std]
+#![feature(naked_functions)]
+#![feature(asm_const)]
+
+extern crate alloc;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::peripheral::NVIC;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    interrupt::declare::handler,
+    sync::Mailbox,
+    task,
+    task::main,
+    time::{self, timer},
+};
+use stm32f4xx_hal::pac::Interrupt;
+
+static FROM_ISR: Mailbox = Mailbox::new();
+static FROM_TASK: Mailbox = Mailbox::new();
+
+/// The tick when the ISR armed the timer.
+static ARMED_AT: AtomicU32 = AtomicU32::new(0);
+
+#[main]
+fn main(_cp: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(waiter)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the waiter task run and block on the mailbox.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    unsafe {
+        NVIC::unmask(Interrupt::TIM2);
+    }
+    NVIC::pend(Interrupt::TIM2);
+
+    time::sleep_ms(100).unwrap();
+    dbg_println!("Armed timers: {}", timer::armed_count());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn waiter() {
+    FROM_ISR.wait();
+    let elapsed = time::get_tick().wrapping_sub(ARMED_AT.load(Ordering::SeqCst));
+    dbg_println!("Timer armed by ISR fired: {}", elapsed >= 20);
+
+    let armed_at = time::get_tick();
+    timer::arm_allow_isr(10, &FROM_TASK).unwrap();
+    dbg_println!("Timed out early: {}", !FROM_TASK.wait_until_timeout(5));
+    FROM_TASK.wait();
+    let elapsed = time::get_tick().wrapping_sub(armed_at);
+    dbg_println!("Timer armed by task fired: {}", elapsed >= 10);
+}
+
+#[handler(TIM2)]
+fn tim2_handler() {
+    ARMED_AT.store(time::get_tick(), Ordering::SeqCst);
+    timer::arm_allow_isr(20, &FROM_ISR).unwrap();
+}
@@ -0,0 +1,53 @@
+//! Tests that the values set in the key-value store, and the removal of a
+//! key, persist after the store is unmounted and mounted again on the same
+//! RAM flash.
+
+#![no_main]
+#![no_std]
+
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    fs::RamFlash,
+    kv,
+    task::main,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let mut flash = RamFlash::new(1024, 2);
+    kv::format(&mut flash).unwrap();
+    kv::mount(flash.clone()).unwrap();
+
+    kv::set("cal.offset", &(-12i32).to_le_bytes()).unwrap();
+    kv::set("boot.count", &1u32.to_le_bytes()).unwrap();
+    kv::set("stale", b"removed").unwrap();
+    kv::remove("stale").unwrap();
+    kv::unmount().unwrap();
+
+    // Count a boot, as an application would after each reset.
+    for _ in 0..3 {
+        kv::mount(flash.clone()).unwrap();
+        let mut buf = [0; 4];
+        kv::get("boot.count", &mut buf).unwrap();
+        let count = u32::from_le_bytes(buf) + 1;
+        kv::set("boot.count", &count.to_le_bytes()).unwrap();
+        kv::unmount().unwrap();
+    }
+
+    kv::mount(flash).unwrap();
+    let mut buf = [0; 4];
+    kv::get("cal.offset", &mut buf).unwrap();
+    dbg_println!("cal.offset: {}", i32::from_le_bytes(buf));
+    kv::get("boot.count", &mut buf).unwrap();
+    dbg_println!("boot.count: {}", u32::from_le_bytes(buf));
+    dbg_println!("contains stale: {}", kv::contains("stale").unwrap());
+    kv::unmount().unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
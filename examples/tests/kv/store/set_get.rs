@@ -0,0 +1,59 @@
+//! Tests setting, replacing, reading and removing keys in the key-value
+//! store on the RAM flash backend.
+
+#![no_main]
+#![no_std]
+
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    fs::RamFlash,
+    kv,
+    task::main,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let mut flash = RamFlash::new(1024, 2);
+    kv::format(&mut flash).unwrap();
+    kv::mount(flash).unwrap();
+
+    kv::set("cal.offset", &(-12i32).to_le_bytes()).unwrap();
+    kv::set("boot.count", &1u32.to_le_bytes()).unwrap();
+    kv::set("boot.count", &2u32.to_le_bytes()).unwrap();
+
+    let mut buf = [0; 4];
+    kv::get("cal.offset", &mut buf).unwrap();
+    dbg_println!("cal.offset: {}", i32::from_le_bytes(buf));
+    kv::get("boot.count", &mut buf).unwrap();
+    dbg_println!("boot.count: {}", u32::from_le_bytes(buf));
+
+    // The value must fit in the buffer.
+    dbg_println!("{:?}", kv::get("cal.offset", &mut buf[..2]));
+
+    kv::remove("cal.offset").unwrap();
+    dbg_println!(
+        "contains cal.offset: {}",
+        kv::contains("cal.offset").unwrap()
+    );
+    dbg_println!("{:?}", kv::get("cal.offset", &mut buf));
+    // Removing a missing key is not an error.
+    dbg_println!("{:?}", kv::remove("cal.offset"));
+
+    let mut count = 0;
+    kv::for_each_key(|key, len| {
+        dbg_println!("key {} len {}", key, len);
+        count += 1;
+    })
+    .unwrap();
+    dbg_println!("key count: {}", count);
+
+    kv::unmount().unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,70 @@
+//! Tests that a cooperative task is not time sliced: a busy cooperative task
+//! keeps the CPU for several quanta while a task of its priority is ready,
+//! whereas a busy task that did not opt out gives the CPU to the other task
+//! after one quantum. Built with `HOPTER_ROUND_ROBIN_QUANTUM_TICKS=5`.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main},
+    time,
+};
+
+/// Whether the peer task has run.
+static PEER_RAN: AtomicBool = AtomicBool::new(false);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    spawn_busy_and_peer(true);
+    time::sleep_ms(50).unwrap();
+
+    PEER_RAN.store(false, Ordering::SeqCst);
+    spawn_busy_and_peer(false);
+    time::sleep_ms(50).unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+/// Spawn a busy task and then a peer task of the same priority, so that the
+/// busy task runs first.
+fn spawn_busy_and_peer(cooperative: bool) {
+    task::run_uninterrupted(|| {
+        task::build()
+            .set_entry(move || busy(cooperative))
+            .set_priority(config::DEFAULT_TASK_PRIORITY - 2)
+            .set_cooperative(cooperative)
+            .spawn()
+            .unwrap();
+        task::build()
+            .set_entry(peer)
+            .set_priority(config::DEFAULT_TASK_PRIORITY - 2)
+            .spawn()
+            .unwrap();
+    });
+}
+
+/// Spin without yielding for four quanta.
+fn busy(cooperative: bool) {
+    let end = time::get_tick() + 4 * config::ROUND_ROBIN_QUANTUM_TICKS;
+    while time::get_tick() < end {}
+    dbg_println!(
+        "peer ran during {} task: {}",
+        if cooperative { "cooperative" } else { "sliced" },
+        PEER_RAN.load(Ordering::SeqCst)
+    );
+}
+
+fn peer() {
+    PEER_RAN.store(true, Ordering::SeqCst);
+    dbg_println!("peer runs");
+}
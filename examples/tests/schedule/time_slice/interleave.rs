@@ -0,0 +1,79 @@
+//! Tests that two busy tasks of the same priority take turns on the CPU, each
+//! running for one round robin quantum before the other one gets the CPU.
+//! Built with `HOPTER_ROUND_ROBIN_QUANTUM_TICKS=5`.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main},
+    time,
+};
+
+const NO_TASK: usize = usize::MAX;
+const BUSY_TICKS: u32 = 60;
+
+/// The busy tasks stop running when the tick count reaches it.
+static END_TICK: AtomicU32 = AtomicU32::new(0);
+/// The busy task which ran last.
+static LAST: AtomicUsize = AtomicUsize::new(NO_TASK);
+/// The number of times the CPU changed from one busy task to the other.
+static SWITCH_COUNT: AtomicUsize = AtomicUsize::new(0);
+const NO_SWITCH: AtomicU32 = AtomicU32::new(0);
+/// The tick count at each switch.
+static SWITCH_TICKS: [AtomicU32; 32] = [NO_SWITCH; 32];
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    dbg_println!("quantum: {}", config::ROUND_ROBIN_QUANTUM_TICKS);
+
+    task::run_uninterrupted(|| {
+        END_TICK.store(time::get_tick() + BUSY_TICKS, Ordering::SeqCst);
+        for id in 0..2 {
+            task::build()
+                .set_entry(move || busy(id))
+                .set_priority(config::DEFAULT_TASK_PRIORITY - 2)
+                .spawn()
+                .unwrap();
+        }
+    });
+
+    time::sleep_ms(BUSY_TICKS + 20).unwrap();
+
+    let count = SWITCH_COUNT.load(Ordering::SeqCst).min(SWITCH_TICKS.len());
+    let quantum = config::ROUND_ROBIN_QUANTUM_TICKS;
+    dbg_println!("interleaved: {}", count as u32 >= BUSY_TICKS / quantum - 1);
+
+    // The last slice, cut short when the tasks stop running, does not end
+    // with a switch and is not checked. The context switch is performed on
+    // the tick ending the quantum, so each slice lasts exactly the quantum.
+    let slices_match = SWITCH_TICKS[..count].windows(2).all(|ticks| {
+        let slice = ticks[1].load(Ordering::SeqCst) - ticks[0].load(Ordering::SeqCst);
+        slice == quantum
+    });
+    dbg_println!("slices match quantum: {}", slices_match);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+/// Spin without yielding, recording each time this task takes the CPU over
+/// from the other one.
+fn busy(id: usize) {
+    while time::get_tick() < END_TICK.load(Ordering::SeqCst) {
+        if LAST.swap(id, Ordering::SeqCst) != id {
+            let index = SWITCH_COUNT.fetch_add(1, Ordering::SeqCst);
+            if let Some(tick) = SWITCH_TICKS.get(index) {
+                tick.store(time::get_tick(), Ordering::SeqCst);
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Test that cancelling a token wakes up a worker task blocked on a channel,
+//! and that waits on a cancelled token return immediately.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{self, CancellationToken, Consumer, Semaphore},
+    task,
+    task::main,
+};
+
+static SHUTDOWN: CancellationToken = CancellationToken::new();
+static PERMITS: Semaphore = Semaphore::new(1, 0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let (producer, consumer) = sync::create_channel::<usize, 4>();
+
+    task::build()
+        .set_entry(move || worker(consumer))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the worker task run and block on the empty channel.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    dbg_println!("Cancelled before timeout {}", SHUTDOWN.wait_timeout(10));
+
+    for job in 1..=2 {
+        producer.produce(job);
+    }
+
+    SHUTDOWN.cancel_allow_isr();
+    SHUTDOWN.wait();
+    dbg_println!("Cancelled {}", SHUTDOWN.is_cancelled());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn worker(consumer: Consumer<usize, 4>) {
+    while let Ok(job) = consumer.consume_cancellable(&SHUTDOWN) {
+        dbg_println!("Processing job {}", job);
+    }
+    dbg_println!("Worker cancelled");
+
+    // Waiting on a cancelled token returns immediately.
+    dbg_println!("Down {:?}", PERMITS.down_cancellable(&SHUTDOWN));
+}
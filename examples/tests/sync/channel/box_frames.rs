@@ -0,0 +1,77 @@
+//! Tests that a box channel passes heap allocated frames without copying
+//! them, and that the allocations left in the channel are dropped with it.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::{boxed::Box, sync::Arc};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync, task,
+    task::main,
+};
+
+/// The address of the last produced frame.
+static FRAME_ADDR: AtomicUsize = AtomicUsize::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let (producer, consumer) = sync::create_box_channel::<Box<[u8; 512]>, 2>();
+
+    task::build()
+        .set_entry(move || {
+            for i in 0..3 {
+                let frame = consumer.consume();
+                let moved = frame.as_ptr() as usize == FRAME_ADDR.load(Ordering::SeqCst);
+                dbg_println!(
+                    "Frame {} not copied: {}, filled with {}",
+                    i,
+                    moved,
+                    frame[511]
+                );
+            }
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the consumer task run and block on the channel.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    for i in 0..3 {
+        let frame = Box::new([i as u8; 512]);
+        FRAME_ADDR.store(frame.as_ptr() as usize, Ordering::SeqCst);
+        producer.produce(frame);
+    }
+
+    // The channel is full after two allocations. Failing to pass one returns
+    // it to the producer.
+    let (producer, consumer) = sync::create_box_channel::<Arc<u32>, 2>();
+    let shared = Arc::new(42);
+    producer.try_produce_allow_isr(shared.clone()).unwrap();
+    producer.try_produce_allow_isr(shared.clone()).unwrap();
+    let rejected = producer.try_produce_allow_isr(shared.clone()).unwrap_err();
+    drop(rejected);
+    dbg_println!(
+        "References with full channel: {}",
+        Arc::strong_count(&shared)
+    );
+
+    drop(producer);
+    drop(consumer);
+    dbg_println!(
+        "References after dropping channel: {}",
+        Arc::strong_count(&shared)
+    );
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
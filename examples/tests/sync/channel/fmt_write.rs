@@ -0,0 +1,40 @@
+//! Tests formatting text into a byte channel with the blocking writer and
+//! the non-blocking writer.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::string::String;
+use core::fmt::Write;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    sync::{self, Consumer},
+    task::main,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let (mut producer, consumer) = sync::create_channel::<u8, 16>();
+
+    write!(producer, "{} + {} = {}", 1, 2, 1 + 2).unwrap();
+    dbg_println!("{}", drain(&consumer));
+
+    // The channel holds 16 bytes, so the text is cut off.
+    let result = write!(producer.try_writer(), "{:?}", [1000, 2000, 3000, 4000]);
+    dbg_println!("{:?}", result);
+    dbg_println!("{}", drain(&consumer));
+
+    write!(producer.try_writer(), "fits").unwrap();
+    dbg_println!("{}", drain(&consumer));
+
+    semihosting::terminate(true);
+}
+
+fn drain(consumer: &Consumer<u8, 16>) -> String {
+    let mut text = String::new();
+    while let Some(byte) = consumer.try_consume_allow_isr() {
+        text.push(byte as char);
+    }
+    text
+}
@@ -0,0 +1,47 @@
+//! Test that peeking a channel does not consume the element, and that the
+//! occupancy reflects produced and consumed elements.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    sync,
+    task::main,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let (producer, consumer) = sync::create_channel::<usize, 3>();
+
+    dbg_println!("Capacity {}", consumer.capacity());
+    dbg_println!("Empty {} peek {:?}", consumer.is_empty(), consumer.peek());
+
+    producer.produce(1);
+    producer.produce(2);
+    dbg_println!("Len {} peek {:?}", consumer.len(), consumer.peek());
+    dbg_println!("Len {} peek {:?}", consumer.len(), consumer.peek());
+
+    producer.produce(3);
+    dbg_println!("Full {}", consumer.is_full());
+
+    // The peeked element is consumed first.
+    dbg_println!("Consumed {}", consumer.consume());
+    dbg_println!("Len {} peek {:?}", consumer.len(), consumer.peek());
+    dbg_println!("Full {}", consumer.is_full());
+
+    producer.produce(4);
+    while let Some(data) = consumer.try_consume_allow_isr() {
+        dbg_println!("Consumed {}", data);
+    }
+    dbg_println!("Empty {} peek {:?}", consumer.is_empty(), consumer.peek());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
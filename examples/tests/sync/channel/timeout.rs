@@ -0,0 +1,67 @@
+//! Test that consuming from an empty channel and producing into a full
+//! channel time out, and that they succeed if the channel changes before
+//! the timeout.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{self, Consumer},
+    task,
+    task::main,
+    time,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let (producer, consumer) = sync::create_channel::<usize, 2>();
+
+    task::build()
+        .set_entry(move || consume(consumer))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the test task run and block on the empty channel.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    // Let the first consumption time out. Wake up the second one.
+    time::sleep_ms(15).unwrap();
+    producer.produce(7);
+
+    // Fill the channel while the test task is sleeping.
+    producer.produce(1);
+    producer.produce(2);
+    if let Err(value) = producer.produce_timeout(3, 10) {
+        dbg_println!("Produce timed out with {}", value);
+    }
+
+    // The test task wakes up and makes room in time.
+    let produced = producer.produce_timeout(4, 100).is_ok();
+    dbg_println!("Produce with timeout succeeded: {}", produced);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn consume(consumer: Consumer<usize, 2>) {
+    let value = consumer.consume_timeout(10);
+    dbg_println!("Consume timed out: {}", value.is_none());
+
+    if let Some(value) = consumer.consume_timeout(100) {
+        dbg_println!("Consumed {}", value);
+    }
+
+    // Stay busy while the channel is filled.
+    time::sleep_ms(20).unwrap();
+    let values = [consumer.consume(), consumer.consume(), consumer.consume()];
+    dbg_println!("Consumed {:?}", values);
+}
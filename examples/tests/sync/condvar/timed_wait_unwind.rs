@@ -0,0 +1,58 @@
+//! Test that a task unwound while waiting on a condition variable with a
+//! timeout is unregistered, so that a later notification wakes up another
+//! waiting task instead.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{CondVar, Mutex},
+    task,
+    task::main,
+};
+
+static LOCK: Mutex<()> = Mutex::new(());
+static CONDVAR: CondVar = CondVar::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(will_panic)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the test task and its unwinding complete first.
+    task::change_current_priority(config::UNWIND_PRIORITY + 1).unwrap();
+
+    task::build()
+        .set_entry(waiter)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the waiter block on the condition variable.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    CONDVAR.notify_one_allow_isr();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn will_panic() {
+    CONDVAR.wait_without_lock_until_timeout(|| panic!(), 1000);
+}
+
+fn waiter() {
+    let (_guard, notified) = CONDVAR.wait_timeout(LOCK.lock(), 1000);
+    dbg_println!("Notified: {}", notified);
+}
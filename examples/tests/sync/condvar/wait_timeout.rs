@@ -0,0 +1,65 @@
+//! Test waiting on a condition variable with a predicate and with a timeout,
+//! and waking up all waiting tasks.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{CondVar, Mutex},
+    task,
+    task::main,
+    time,
+};
+
+static COUNT: Mutex<u32> = Mutex::new(0);
+static CONDVAR: CondVar = CondVar::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(predicate_waiter)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    task::build()
+        .set_entry(timeout_waiter)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the test tasks run and block on the condition variable.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    // Let the first timed wait expire.
+    time::sleep_ms(50).unwrap();
+
+    for count in 1..=3 {
+        let mut guard = COUNT.lock();
+        *guard = count;
+        CONDVAR.notify_all_allow_isr();
+    }
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn predicate_waiter() {
+    let guard = CONDVAR.wait_while(COUNT.lock(), |count| **count < 3);
+    dbg_println!("Predicate waiter woke, count {}", *guard);
+}
+
+fn timeout_waiter() {
+    let (guard, notified) = CONDVAR.wait_timeout(COUNT.lock(), 10);
+    dbg_println!("Timeout waiter notified: {}", notified);
+
+    let (guard, notified) = CONDVAR.wait_timeout(guard, 1000);
+    dbg_println!("Timeout waiter notified: {}, count {}", notified, *guard);
+}
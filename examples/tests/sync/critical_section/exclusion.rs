@@ -0,0 +1,69 @@
+//! Tests that a task inside `critical_section::with` is not preempted by a
+//! higher priority task woken up in the critical section, which runs once
+//! the outermost critical section exits. IRQs are not masked, so an ISR
+//! still runs inside the critical section.
+
+#![no_main]
+#![no_std]
+#![feature(naked_functions)]
+#![feature(asm_const)]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use cortex_m::peripheral::NVIC;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    interrupt::declare::handler,
+    sync::Mailbox,
+    task::{self, main},
+    time,
+};
+use stm32f4xx_hal::pac::Interrupt;
+
+static WAKE: Mailbox = Mailbox::new();
+static ISR_RAN: AtomicBool = AtomicBool::new(false);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    unsafe {
+        NVIC::unmask(Interrupt::TIM2);
+    }
+
+    // The worker preempts the main task and blocks.
+    task::build()
+        .set_entry(|| {
+            WAKE.wait();
+            dbg_println!("worker runs");
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    critical_section::with(|_| {
+        WAKE.notify_allow_isr();
+        NVIC::pend(Interrupt::TIM2);
+
+        // Keep running across several ticks, each of which would switch to
+        // the worker if the scheduler were not suspended.
+        let start = time::get_tick();
+        while time::get_tick().wrapping_sub(start) < 5 {}
+
+        critical_section::with(|_| dbg_println!("nested section entered"));
+        dbg_println!("isr ran inside: {}", ISR_RAN.load(Ordering::SeqCst));
+        dbg_println!("leaving critical section");
+    });
+    dbg_println!("main task resumes");
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+#[handler(TIM2)]
+fn tim2_handler() {
+    ISR_RAN.store(true, Ordering::SeqCst);
+}
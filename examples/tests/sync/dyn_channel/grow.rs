@@ -0,0 +1,47 @@
+//! Test that the buffer of a heap-backed channel grows when producing into
+//! it, that it is full only at the configured largest capacity, and that
+//! the elements are consumed in order.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync,
+    task::main,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let (producer, consumer) = sync::create_dyn_channel::<usize>(1);
+
+    // Fill the channel up to the largest capacity, which grows the buffer
+    // several times.
+    for i in 0..config::DYN_CHANNEL_MAX_CAPACITY - 1 {
+        producer.produce(i);
+    }
+    let produced = producer
+        .try_produce_allow_isr(config::DYN_CHANNEL_MAX_CAPACITY - 1)
+        .is_ok();
+    dbg_println!("Produced the last element: {}", produced);
+
+    // Now the channel is full.
+    let full = producer.produce_timeout(0, 10).is_err();
+    dbg_println!("Full at the largest capacity: {}", full);
+
+    let in_order = (0..config::DYN_CHANNEL_MAX_CAPACITY).all(|i| consumer.consume() == i);
+    dbg_println!("Consumed in order: {}", in_order);
+
+    let empty = consumer.try_consume_allow_isr().is_none();
+    dbg_println!("Empty after consuming: {}", empty);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,72 @@
+//! Test waiting for any or all of the event flags, with and without a
+//! timeout, and clearing the flags.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::EventFlags,
+    task,
+    task::main,
+    time,
+};
+
+const FLAG_A: u32 = 1 << 0;
+const FLAG_B: u32 = 1 << 1;
+
+static EVENTS: EventFlags = EventFlags::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(any_waiter)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    task::build()
+        .set_entry(all_waiter)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the test tasks run and block on the event flags.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    // Let the timed wait expire.
+    time::sleep_ms(50).unwrap();
+
+    // Wakes up only the task waiting for any flag.
+    EVENTS.set_allow_isr(FLAG_A);
+    time::sleep_ms(10).unwrap();
+
+    // Wakes up the task waiting for all flags.
+    EVENTS.set_allow_isr(FLAG_B);
+    time::sleep_ms(10).unwrap();
+
+    let prev = EVENTS.clear_allow_isr(FLAG_A);
+    dbg_println!("Cleared {:#x}, flags {:#x}", prev, EVENTS.get());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn any_waiter() {
+    let flags = EVENTS.wait_any_timeout(FLAG_A, 10);
+    dbg_println!("Any waiter timed out: {}", flags.is_none());
+
+    let flags = EVENTS.wait_any(FLAG_A | FLAG_B);
+    dbg_println!("Any waiter woke with {:#x}", flags);
+}
+
+fn all_waiter() {
+    let flags = EVENTS.wait_all(FLAG_A | FLAG_B);
+    dbg_println!("All waiter woke with {:#x}", flags);
+}
@@ -0,0 +1,56 @@
+//! Test handing off pool buffers through a channel. The producer blocks when
+//! all buffers are held by the consumer side, and continues when the consumer
+//! drops one.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{self, BufferPool, Consumer, HandoffBuffer},
+    task,
+    task::main,
+};
+
+static POOL: BufferPool<8, 2> = BufferPool::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let (producer, consumer) = sync::create_channel::<HandoffBuffer<8, 2>, 4>();
+
+    task::build()
+        .set_entry(move || consume(consumer))
+        .set_priority(config::DEFAULT_TASK_PRIORITY + 1)
+        .spawn()
+        .unwrap();
+
+    for i in 0..4u8 {
+        let mut buf = POOL.acquire();
+        let msg = [b'a' + i; 3];
+        buf.as_mut_array()[..msg.len()].copy_from_slice(&msg);
+        buf.set_len(msg.len());
+        producer.produce(buf);
+        dbg_println!("Produced {}, available {}", i, POOL.available());
+    }
+
+    // Let the consumer finish.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+    dbg_println!("Available {}", POOL.available());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn consume(consumer: Consumer<HandoffBuffer<8, 2>, 4>) {
+    loop {
+        let buf = consumer.consume();
+        dbg_println!("Consumed {}", core::str::from_utf8(&buf).unwrap());
+    }
+}
@@ -0,0 +1,60 @@
+//! Test sharing a value between a task and an ISR with `IsrShared`. The ISR
+//! preempting the task accessing the value is denied access and runs again
+//! after the task finishes its access.
+
+#![no_main]
+#![no_std]
+#![feature(naked_functions)]
+#![feature(asm_const)]
+
+extern crate alloc;
+
+use cortex_m::peripheral::NVIC;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    interrupt::declare::handler,
+    sync::IsrShared,
+    task::main,
+};
+use stm32f4xx_hal::pac::Interrupt;
+
+static SHARED: IsrShared<u32, Interrupt> = IsrShared::new(Interrupt::TIM2, 0);
+
+#[main]
+fn main(_cp: cortex_m::Peripherals) {
+    unsafe {
+        NVIC::unmask(Interrupt::TIM2);
+    }
+
+    // The ISR runs without preempting an access.
+    NVIC::pend(Interrupt::TIM2);
+
+    SHARED.with_task(|value| {
+        *value += 10;
+        // The ISR preempts the access and is denied access.
+        NVIC::pend(Interrupt::TIM2);
+        dbg_println!("Task writes {}", value);
+    });
+
+    let value = SHARED.with_task(|value| *value);
+    dbg_println!("Task reads {}", value);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+#[handler(TIM2)]
+fn tim2_handler() {
+    let accessed = SHARED.with_isr(|value| {
+        *value += 1;
+        dbg_println!("ISR writes {}", value);
+    });
+    if accessed.is_none() {
+        dbg_println!("ISR deferred");
+    }
+}
@@ -0,0 +1,46 @@
+//! Test that locking a mutex with a priority ceiling immediately raises the
+//! owner to the ceiling priority, so that a task with priority between the
+//! intrinsic and the ceiling priority of the owner runs only after the mutex
+//! is released.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::Mutex,
+    task,
+    task::main,
+};
+
+static MUTEX: Mutex<()> = Mutex::with_ceiling((), config::DEFAULT_TASK_PRIORITY);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    let guard = MUTEX.lock();
+    dbg_println!("Locked with ceiling");
+
+    // The spawned task has higher intrinsic priority than the current task,
+    // but lower priority than the ceiling. It should not preempt.
+    task::build()
+        .set_entry(|| dbg_println!("Medium task running"))
+        .set_priority(config::DEFAULT_TASK_PRIORITY + 1)
+        .spawn()
+        .unwrap();
+    dbg_println!("Medium task spawned");
+
+    drop(guard);
+    dbg_println!("Released");
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
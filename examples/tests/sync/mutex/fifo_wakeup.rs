@@ -0,0 +1,74 @@
+//! Tasks blocking on a mutex created with the FIFO wakeup order should be
+//! woken up in the sequence they started waiting, regardless of their
+//! priority.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{Mutex, WakeupOrder},
+    task,
+    task::main,
+    time,
+};
+
+static MUTEX: Mutex<()> = Mutex::with_wakeup_order((), WakeupOrder::Fifo);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    // Hold the lock at the beginning.
+    let guard = MUTEX.lock();
+
+    // Create test tasks one by one, and let each of them block on the mutex
+    // before creating the next one.
+    task::build()
+        .set_entry(low_task)
+        .set_priority(config::DEFAULT_TASK_PRIORITY + 1)
+        .spawn()
+        .unwrap();
+    time::sleep_ms(10).unwrap();
+    task::build()
+        .set_entry(middle_task)
+        .set_priority(config::DEFAULT_TASK_PRIORITY)
+        .spawn()
+        .unwrap();
+    time::sleep_ms(10).unwrap();
+    task::build()
+        .set_entry(high_task)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    time::sleep_ms(10).unwrap();
+
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    // Release the mutex and the test tasks should be woken up in the order
+    // they started waiting.
+    core::mem::drop(guard);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn high_task() {
+    let _gaurd = MUTEX.lock();
+    dbg_println!("High priority task locking data");
+}
+
+fn middle_task() {
+    let _gaurd = MUTEX.lock();
+    dbg_println!("Middle priority task locking data");
+}
+
+fn low_task() {
+    let _gaurd = MUTEX.lock();
+    dbg_println!("Low priority task locking data");
+}
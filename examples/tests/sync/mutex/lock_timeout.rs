@@ -0,0 +1,54 @@
+//! Test mutex `lock_timeout`, both timing out while another task holds the
+//! mutex and locking it once the holder releases it.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::Mutex,
+    task,
+    task::main,
+    time,
+};
+
+static MUTEX: Mutex<u32> = Mutex::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(holder)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the holder lock the mutex first.
+    time::sleep_ms(5).unwrap();
+
+    if MUTEX.lock_timeout(10).is_none() {
+        dbg_println!("Timed out");
+    }
+
+    match MUTEX.lock_timeout(100) {
+        Some(guard) => dbg_println!("Locked after release, value {}", *guard),
+        None => dbg_println!("Timed out again"),
+    }
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn holder() {
+    let mut guard = MUTEX.lock();
+    time::sleep_ms(50).unwrap();
+    *guard = 42;
+    drop(guard);
+    dbg_println!("Holder released");
+}
@@ -0,0 +1,54 @@
+//! Test that a guard mapped to a field of the protected data keeps the mutex
+//! locked, and that a failed mapping returns the original guard.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    sync::{MappedMutexGuard, Mutex, MutexGuard},
+    task::main,
+};
+
+struct Device {
+    config: [u8; 4],
+    status: Option<u32>,
+}
+
+static DEVICE: Mutex<Device> = Mutex::new(Device {
+    config: [0; 4],
+    status: None,
+});
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let mut config = MutexGuard::map(DEVICE.lock(), |device| &mut device.config);
+    config[0] = 1;
+    dbg_println!("Locked while mapped: {}", DEVICE.try_lock().is_none());
+
+    // Map the mapped guard further.
+    let mut tail = MappedMutexGuard::map(config, |config| &mut config[2..]);
+    tail[1] = 3;
+    dbg_println!("Locked while mapped again: {}", DEVICE.try_lock().is_none());
+    drop(tail);
+
+    match MutexGuard::try_map(DEVICE.lock(), |device| device.status.as_mut()) {
+        Ok(_) => dbg_println!("Mapped to absent status"),
+        Err(device) => dbg_println!("Mapping failed with config {:?}", device.config),
+    }
+
+    DEVICE.lock().status = Some(7);
+    if let Ok(mut status) = MutexGuard::try_map(DEVICE.lock(), |device| device.status.as_mut()) {
+        *status += 1;
+    }
+    dbg_println!("Status {:?}", DEVICE.lock().status);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
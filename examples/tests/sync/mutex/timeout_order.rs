@@ -0,0 +1,63 @@
+//! Test that the tasks waiting on a mutex with a timeout are woken up in the
+//! same priority order as the tasks waiting without one.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::Mutex,
+    task,
+    task::main,
+};
+
+static MUTEX: Mutex<()> = Mutex::new(());
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    // A low priority task waiting with a timeout starts waiting before a high
+    // priority task waiting without one. The latter locks the mutex first.
+    let guard = MUTEX.lock();
+    spawn(timed_waiter, config::DEFAULT_TASK_PRIORITY - 1);
+    spawn(untimed_waiter, config::DEFAULT_TASK_PRIORITY - 2);
+    drop(guard);
+
+    // A low priority task waiting without a timeout starts waiting before a
+    // high priority task waiting with one. The latter locks the mutex first.
+    let guard = MUTEX.lock();
+    spawn(untimed_waiter, config::DEFAULT_TASK_PRIORITY - 1);
+    spawn(timed_waiter, config::DEFAULT_TASK_PRIORITY - 2);
+    drop(guard);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+/// Spawn a task with a priority higher than the main task, so that it runs
+/// and blocks on the mutex right away.
+fn spawn(entry: fn(), priority: u8) {
+    task::build()
+        .set_entry(entry)
+        .set_priority(priority)
+        .spawn()
+        .unwrap();
+}
+
+fn timed_waiter() {
+    match MUTEX.lock_timeout(1000) {
+        Some(_guard) => dbg_println!("Timed waiter locked"),
+        None => dbg_println!("Timed waiter timed out"),
+    }
+}
+
+fn untimed_waiter() {
+    let _guard = MUTEX.lock();
+    dbg_println!("Untimed waiter locked");
+}
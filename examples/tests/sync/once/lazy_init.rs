@@ -0,0 +1,65 @@
+//! Test that a lazily initialized value is initialized only once when tasks
+//! race to access it, and that a task accessing it during the
+//! initialization waits for the initialization to complete.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{Lazy, Once},
+    task,
+    task::main,
+    time,
+};
+
+static INIT_COUNT: AtomicU32 = AtomicU32::new(0);
+static VALUE: Lazy<u32> = Lazy::new(slow_init);
+static ONCE: Once = Once::new();
+
+fn slow_init() -> u32 {
+    INIT_COUNT.fetch_add(1, Ordering::SeqCst);
+    // Let the other task try to access the value in between.
+    time::sleep_ms(20).unwrap();
+    42
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(|| dbg_println!("First task got {}", *VALUE))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    task::build()
+        .set_entry(|| dbg_println!("Second task got {}", *VALUE))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the test tasks run.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+    time::sleep_ms(100).unwrap();
+
+    dbg_println!("Init count: {}", INIT_COUNT.load(Ordering::SeqCst));
+    dbg_println!("Get: {:?}", VALUE.get_allow_isr());
+
+    for _ in 0..2 {
+        ONCE.call_once(|| dbg_println!("Once ran"));
+    }
+    dbg_println!(
+        "Completed: {}",
+        ONCE.call_once_allow_isr(|| dbg_println!("Once ran again"))
+    );
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
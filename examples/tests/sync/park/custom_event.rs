@@ -0,0 +1,77 @@
+//! Test that a task parks until it is unparked, with and without a timeout,
+//! and that unparking a task twice before it parks leaves only one wake-up
+//! token.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use core::sync::atomic::{AtomicBool, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{self, Spin, TaskRef},
+    task,
+    task::main,
+    time,
+};
+
+static READY: AtomicBool = AtomicBool::new(false);
+static WAITER: Spin<Option<TaskRef>> = Spin::new(None);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(waiter)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the test task run and park.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    // Let the timed park expire. The test task then parks without a timeout.
+    time::sleep_ms(15).unwrap();
+
+    READY.store(true, Ordering::SeqCst);
+    unpark_waiter();
+    time::sleep_ms(5).unwrap();
+
+    // The test task is sleeping. Only one token is left for it.
+    unpark_waiter();
+    unpark_waiter();
+    time::sleep_ms(50).unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn unpark_waiter() {
+    if let Some(waiter) = WAITER.lock().as_ref() {
+        sync::unpark(waiter);
+    }
+}
+
+fn waiter() {
+    WAITER.lock().replace(sync::current_task_ref());
+
+    let unparked = sync::park_current_task_with_timeout(10);
+    dbg_println!("Waiter timed out: {}", !unparked);
+
+    while !READY.load(Ordering::SeqCst) {
+        sync::park_current_task();
+    }
+    dbg_println!("Waiter sees ready");
+
+    // Stay busy while being unparked.
+    time::sleep_ms(20).unwrap();
+    let unparked = sync::park_current_task_with_timeout(10);
+    dbg_println!("First park unparked: {}", unparked);
+    let unparked = sync::park_current_task_with_timeout(10);
+    dbg_println!("Second park unparked: {}", unparked);
+}
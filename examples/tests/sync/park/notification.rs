@@ -0,0 +1,77 @@
+//! Test that a task waiting for notification bits receives only the bits in
+//! its mask, that other bits are kept for later waits, and that notifying
+//! the same bits twice delivers them once.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{self, Spin, TaskRef},
+    task,
+    task::main,
+    time,
+};
+
+const RX_DONE: u32 = 1 << 0;
+const TX_DONE: u32 = 1 << 1;
+const ERROR: u32 = 1 << 2;
+
+static WAITER: Spin<Option<TaskRef>> = Spin::new(None);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(waiter)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the first wait time out.
+    time::sleep_ms(15).unwrap();
+
+    // The waiter only waits for `RX_DONE`.
+    notify_waiter(TX_DONE);
+    time::sleep_ms(5).unwrap();
+    notify_waiter(RX_DONE);
+    time::sleep_ms(5).unwrap();
+
+    notify_waiter(ERROR);
+    notify_waiter(ERROR);
+    time::sleep_ms(30).unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn notify_waiter(bits: u32) {
+    if let Some(waiter) = WAITER.lock().as_ref() {
+        sync::notify_value_allow_isr(waiter, bits);
+    }
+}
+
+fn waiter() {
+    WAITER.lock().replace(sync::current_task_ref());
+
+    let bits = sync::wait_notification(RX_DONE, 10);
+    dbg_println!("Timed out: {}", bits == 0);
+
+    let bits = sync::wait_notification(RX_DONE, 100);
+    dbg_println!("Received {:#b}", bits);
+
+    // `TX_DONE` was kept.
+    let bits = sync::wait_notification(RX_DONE | TX_DONE, 0);
+    dbg_println!("Received {:#b}", bits);
+
+    let bits = sync::wait_notification(ERROR, 100);
+    dbg_println!("Received {:#b}", bits);
+    let bits = sync::wait_notification(ERROR, 10);
+    dbg_println!("Received again: {}", bits != 0);
+}
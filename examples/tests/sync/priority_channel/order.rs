@@ -0,0 +1,55 @@
+//! Test that a priority channel delivers the elements in the order of their
+//! priorities, and in the order of production among the same priority.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::PriorityChannel,
+    task,
+    task::main,
+};
+
+static CHANNEL: PriorityChannel<&str, 4> = PriorityChannel::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    CHANNEL.produce("bulk 1", 10);
+    CHANNEL.produce("bulk 2", 10);
+    CHANNEL.produce("control 1", 0);
+    CHANNEL.produce("control 2", 0);
+
+    // The channel is full.
+    dbg_println!(
+        "Full: {}",
+        CHANNEL.try_produce_allow_isr("bulk 3", 10).is_err()
+    );
+
+    task::build()
+        .set_entry(consumer)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the consumer task drain the channel and block on it.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    CHANNEL.produce("control 3", 0);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn consumer() {
+    for _ in 0..5 {
+        dbg_println!("Consumed {}", CHANNEL.consume());
+    }
+}
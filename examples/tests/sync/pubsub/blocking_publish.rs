@@ -0,0 +1,49 @@
+//! Test that blocking publishing waits for a slow subscriber, so that no
+//! message is dropped even though the subscriber queue holds only one.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    sync::{Mailbox, Topic},
+    task::{self, main},
+    time,
+};
+
+static NUMBERS: Topic<u32> = Topic::new("numbers");
+static DONE: Mailbox = Mailbox::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let subscriber = NUMBERS.subscribe::<1>();
+
+    task::build()
+        .set_entry(move || {
+            for _ in 0..5 {
+                // Consume slower than the publisher produces.
+                time::sleep_ms(20).unwrap();
+                dbg_println!("received {}", subscriber.recv());
+            }
+            dbg_println!("dropped {}", subscriber.dropped_count());
+            DONE.notify_allow_isr();
+        })
+        .spawn()
+        .unwrap();
+
+    for i in 0..5 {
+        NUMBERS.publish(i);
+    }
+
+    DONE.wait();
+    dbg_println!("topic dropped {}", NUMBERS.dropped_count());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
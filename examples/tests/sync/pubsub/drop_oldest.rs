@@ -0,0 +1,51 @@
+//! Test that a subscriber with the drop-oldest lag policy keeps the latest
+//! messages and reports its lag, while a subscriber with backpressure keeps
+//! the earliest ones.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::sync::Arc;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    sync::{LagPolicy, Topic},
+    task::main,
+};
+
+static STATES: Topic<Arc<u32>> = Topic::new("states");
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let latest = STATES.subscribe_with_policy::<2>(LagPolicy::DropOldest);
+    let earliest = STATES.subscribe::<2>();
+
+    // Never blocks on the full queue of the drop-oldest subscriber.
+    for i in 0..2 {
+        let delivered = STATES.publish(Arc::new(i));
+        dbg_println!("published {} to {} subscribers", i, delivered);
+    }
+    for i in 2..5 {
+        let delivered = STATES.publish_allow_isr(Arc::new(i));
+        dbg_println!("published {} to {} subscribers", i, delivered);
+    }
+
+    dbg_println!("latest lag {}", latest.take_lag());
+    dbg_println!("latest lag {}", latest.take_lag());
+    while let Some(msg) = latest.try_recv_allow_isr() {
+        dbg_println!("latest received {}", msg);
+    }
+    while let Some(msg) = earliest.try_recv_allow_isr() {
+        dbg_println!("earliest received {}", msg);
+    }
+    dbg_println!("earliest dropped {}", earliest.dropped_count());
+    dbg_println!("topic dropped {}", STATES.dropped_count());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
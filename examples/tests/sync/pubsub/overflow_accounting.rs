@@ -0,0 +1,57 @@
+//! Test that non-blocking publishing delivers to every subscriber with room
+//! in its queue and counts the messages dropped for full queues.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    sync::Topic,
+    task::main,
+};
+
+static NUMBERS: Topic<u32> = Topic::new("numbers");
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let small = NUMBERS.subscribe::<2>();
+    let large = NUMBERS.subscribe::<4>();
+    dbg_println!("subscribers: {}", NUMBERS.subscriber_count());
+
+    for i in 0..3 {
+        let delivered = NUMBERS.publish_allow_isr(i);
+        dbg_println!("published {} to {} subscribers", i, delivered);
+    }
+
+    while let Some(msg) = small.try_recv_allow_isr() {
+        dbg_println!("small received {}", msg);
+    }
+    while let Some(msg) = large.try_recv_allow_isr() {
+        dbg_println!("large received {}", msg);
+    }
+    dbg_println!("small dropped {}", small.dropped_count());
+    dbg_println!("large dropped {}", large.dropped_count());
+
+    drop(small);
+    dbg_println!("subscribers: {}", NUMBERS.subscriber_count());
+
+    let delivered = NUMBERS.publish_allow_isr(3);
+    dbg_println!("published 3 to {} subscribers", delivered);
+    dbg_println!("large received {}", large.recv());
+
+    dbg_println!(
+        "{}: published {}, dropped {}",
+        NUMBERS.name(),
+        NUMBERS.published_count(),
+        NUMBERS.dropped_count()
+    );
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
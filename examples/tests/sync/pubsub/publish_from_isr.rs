@@ -0,0 +1,141 @@
+//! Tests publishing messages to a subscriber from an ISR with
+//! `publish_allow_isr`.
+
+#![no_main]
+#![no_std]
+#![feature(naked_functions)]
+#![feature(asm_const)]
+
+extern crate alloc;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    interrupt::declare::{handler, irq},
+    sync::{Mailbox, SpinIrqSafe, Topic},
+    task,
+    task::main,
+};
+use stm32f4xx_hal::{
+    pac::{Interrupt, Peripherals, TIM2},
+    prelude::*,
+    timer::{CounterUs, Event},
+};
+
+irq!(Tim2Irq, Interrupt::TIM2);
+static TIMER: SpinIrqSafe<Option<CounterUs<TIM2>>, Tim2Irq> = SpinIrqSafe::new(None);
+
+static TICKS: Topic<usize> = Topic::new("ticks");
+
+/// Never notified. Keeps the subscriber task alive.
+static PARK: Mailbox = Mailbox::new();
+
+#[main]
+fn main(_cp: cortex_m::Peripherals) {
+    // Allow the new task below to run first until it blocks.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY).unwrap();
+
+    // The new task should block on the subscriber queue.
+    task::build()
+        .set_entry(subscribe_function)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    let dp = unsafe { Peripherals::steal() };
+
+    // For unknown reason QEMU accepts only the following clock frequency.
+    let rcc = dp.RCC.constrain();
+
+    #[cfg(feature = "qemu")]
+    let clocks = rcc.cfgr.sysclk(16.MHz()).pclk1(8.MHz()).freeze();
+    #[cfg(feature = "stm32f411")]
+    let clocks = rcc
+        .cfgr
+        .use_hse(8.MHz())
+        .sysclk(100.MHz())
+        .pclk1(25.MHz())
+        .pclk2(50.MHz())
+        .freeze();
+    #[cfg(feature = "stm32f407")]
+    let clocks = rcc
+        .cfgr
+        .use_hse(8.MHz())
+        .sysclk(168.MHz())
+        .pclk1(42.MHz())
+        .pclk2(84.MHz())
+        .freeze();
+
+    let mut timer = dp.TIM2.counter(&clocks);
+
+    // Generate an interrupt when the timer expires.
+    timer.listen(Event::Update);
+
+    // Enable TIM2 interrupt.
+    unsafe {
+        cortex_m::peripheral::NVIC::unmask(Interrupt::TIM2);
+    }
+
+    // Set the timer to expire every 1 second.
+    // Empirically when set to 62 seconds the interval is actually
+    // approximately 1 second. Weird QEMU.
+    #[cfg(feature = "qemu")]
+    timer.start(62.secs()).unwrap();
+    #[cfg(not(feature = "qemu"))]
+    timer.start(1.secs()).unwrap();
+
+    // Move the timer into the global storage to prevent it from being dropped.
+    *TIMER.lock() = Some(timer);
+}
+
+fn subscribe_function() {
+    let subscriber = TICKS.subscribe::<2>();
+
+    // Receive the first three messages. The queue should be able to hold two
+    // more messages.
+    for _ in 0..3 {
+        dbg_println!("Received {}", subscriber.recv());
+    }
+
+    // Stay subscribed without receiving.
+    PARK.wait();
+    drop(subscriber);
+}
+
+/// Get invoked approximately every 1 second.
+#[handler(TIM2)]
+fn tim2_handler() {
+    TIMER.lock().as_mut().unwrap().wait().unwrap();
+
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let prev_cnt = COUNT.fetch_add(1, Ordering::SeqCst);
+
+    let delivered = TICKS.publish_allow_isr(prev_cnt);
+
+    // The first five messages should be delivered and the sixth one should
+    // be dropped.
+    match (prev_cnt, delivered) {
+        (0..=4, 1) => {}
+        (5, 0) => {
+            dbg_println!("Dropped {}", TICKS.dropped_count());
+            #[cfg(feature = "qemu")]
+            semihosting::terminate(true);
+            #[cfg(not(feature = "qemu"))]
+            {
+                dbg_println!("test complete!");
+                loop {}
+            }
+        }
+        _ => {
+            dbg_println!("Unexpectedly delivered to {} subscribers", delivered);
+            #[cfg(feature = "qemu")]
+            semihosting::terminate(false);
+            #[cfg(not(feature = "qemu"))]
+            {
+                dbg_println!("test complete!");
+                loop {}
+            }
+        }
+    }
+}
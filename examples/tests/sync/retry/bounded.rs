@@ -0,0 +1,85 @@
+//! Tests that a `RetryCounter` counts each retry of a lock-free loop caused
+//! by an ISR modifying the same value in between, and that the maximum
+//! number of retries is reported by `max_isr_retries`.
+
+#![no_main]
+#![no_std]
+#![feature(naked_functions)]
+#![feature(asm_const)]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::peripheral::NVIC;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    interrupt::declare::handler,
+    sync::{self, RetryCounter},
+    task::main,
+};
+use stm32f4xx_hal::pac::Interrupt;
+
+static COUNTER: AtomicU32 = AtomicU32::new(0);
+/// The number of times the ISR still modifies the counter.
+static INTERFERENCE: AtomicU32 = AtomicU32::new(0);
+
+#[main]
+fn main(_cp: cortex_m::Peripherals) {
+    unsafe {
+        NVIC::unmask(Interrupt::TIM2);
+    }
+
+    // Without interference, the loop does not retry.
+    dbg_println!("retries: {}", increment());
+
+    // Let the ISR modify the counter in as many iterations as the limit
+    // allows, which does not fail the debug assertion.
+    INTERFERENCE.store(config::ISR_RETRY_LIMIT, Ordering::SeqCst);
+    let retries = increment();
+    dbg_println!(
+        "retries reach limit: {}",
+        retries == config::ISR_RETRY_LIMIT
+    );
+    dbg_println!(
+        "max retries reach limit: {}",
+        sync::max_isr_retries() == config::ISR_RETRY_LIMIT
+    );
+    dbg_println!(
+        "counter as expected: {}",
+        COUNTER.load(Ordering::SeqCst) == 2 * config::ISR_RETRY_LIMIT + 2
+    );
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+/// Increment the counter by one with a lock-free loop. Return the number of
+/// retries.
+fn increment() -> u32 {
+    let mut retries = RetryCounter::new();
+    loop {
+        let cur = COUNTER.load(Ordering::SeqCst);
+        if INTERFERENCE.load(Ordering::SeqCst) > 0 {
+            // The ISR modifies the counter before the exchange below.
+            NVIC::pend(Interrupt::TIM2);
+            cortex_m::asm::isb();
+        }
+        if COUNTER
+            .compare_exchange(cur, cur + 1, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            return retries.count();
+        }
+        retries.retry();
+    }
+}
+
+#[handler(TIM2)]
+fn tim2_handler() {
+    INTERFERENCE.fetch_sub(1, Ordering::SeqCst);
+    COUNTER.fetch_add(2, Ordering::SeqCst);
+}
@@ -0,0 +1,60 @@
+//! Tests that calls get the response of the server, fail when the server
+//! drops the responder or does not respond in time, and that requests sent
+//! without waiting discard their response.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    sync::rpc::{self, Server},
+    task,
+    task::main,
+    time,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let (client, server) = rpc::create::<u32, u32, 4>();
+
+    task::build()
+        .set_id(1)
+        .set_priority(5)
+        .set_entry(move || serve(server))
+        .spawn()
+        .unwrap();
+
+    dbg_println!("{:?}", client.call(21));
+    dbg_println!("{:?}", client.call(0));
+    dbg_println!("{:?}", client.call_until_timeout(1000, 10));
+    dbg_println!("{:?}", client.call_until_timeout(5, 100));
+
+    let sent = client.try_send_allow_isr(7);
+    let result = client.call(8);
+    dbg_println!("{:?}", sent);
+    dbg_println!("{:?}", result);
+
+    semihosting::terminate(true);
+}
+
+fn serve(server: Server<u32, u32, 4>) {
+    loop {
+        let (request, responder) = server.recv();
+        match request {
+            // Drop the responder without responding.
+            0 => {}
+            // Respond too late.
+            1000 => {
+                time::sleep_ms(50).unwrap();
+                responder.respond(request);
+            }
+            _ => {
+                if !responder.expects_response() {
+                    dbg_println!("request {} without response", request);
+                }
+                responder.respond(request * 2);
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Test that readers share a reader-writer lock, that a waiting writer is
+//! preferred over new readers, and that the lock is poisoned when a writer is
+//! unwound.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::RwLock,
+    task,
+    task::main,
+};
+
+static LOCK: RwLock<u32> = RwLock::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    // Hold the lock for reading at the beginning.
+    let guard = LOCK.read();
+
+    task::build()
+        .set_entry(writer)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    task::build()
+        .set_entry(reader)
+        .set_priority(config::DEFAULT_TASK_PRIORITY)
+        .spawn()
+        .unwrap();
+
+    // Let the test tasks run. The writer blocks because the lock is held for
+    // reading, and the reader blocks because the writer is waiting.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    // Readers not waiting share the lock.
+    dbg_println!("Try read: {:?}", LOCK.try_read_allow_isr().as_deref());
+    dbg_println!("Try write: {}", LOCK.try_write_allow_isr().is_some());
+
+    // Release the lock. The writer should run before the reader.
+    core::mem::drop(guard);
+
+    // Let a writer panic while holding the lock.
+    task::build().set_entry(will_panic).spawn().unwrap();
+    task::change_current_priority(config::UNWIND_PRIORITY + 1).unwrap();
+    dbg_println!("Poisoned: {}", LOCK.is_poisoned());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn writer() {
+    let mut guard = LOCK.write();
+    *guard = 42;
+    dbg_println!("Writer wrote {}", *guard);
+}
+
+fn reader() {
+    let guard = LOCK.read();
+    dbg_println!("Reader read {}", *guard);
+}
+
+fn will_panic() {
+    let _guard = LOCK.write();
+    panic!();
+}
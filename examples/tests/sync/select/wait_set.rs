@@ -0,0 +1,70 @@
+//! Test waiting on two channels and a mailbox at once with a wait set,
+//! including a timed wait that expires.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{self, Consumer, Mailbox, WaitSet},
+    task,
+    task::main,
+    time,
+};
+
+static MAILBOX: Mailbox = Mailbox::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let (producer1, consumer1) = sync::create_channel::<u32, 4>();
+    let (producer2, consumer2) = sync::create_channel::<u32, 4>();
+
+    task::build()
+        .set_entry(move || selector(consumer1, consumer2))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the test task run and block on the wait set.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    // Let the timed wait expire.
+    time::sleep_ms(50).unwrap();
+
+    // Each of them wakes up the test task.
+    producer2.produce(20);
+    MAILBOX.notify_allow_isr();
+    producer1.produce(10);
+    producer2.produce(21);
+    time::sleep_ms(10).unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn selector(consumer1: Consumer<u32, 4>, consumer2: Consumer<u32, 4>) {
+    let mut set = WaitSet::new();
+    let channel1 = set.add(&consumer1);
+    let channel2 = set.add(&consumer2);
+    let mailbox = set.add(&MAILBOX);
+
+    dbg_println!("Timed out: {}", set.wait_timeout(10).is_none());
+
+    for _ in 0..4 {
+        let ready = set.wait();
+        if ready == channel1 {
+            dbg_println!("Channel 1: {:?}", consumer1.try_consume_allow_isr());
+        } else if ready == channel2 {
+            dbg_println!("Channel 2: {:?}", consumer2.try_consume_allow_isr());
+        } else if ready == mailbox {
+            dbg_println!("Mailbox: {}", MAILBOX.try_wait_allow_isr());
+        }
+    }
+}
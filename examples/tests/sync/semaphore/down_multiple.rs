@@ -0,0 +1,57 @@
+//! Test that `down_multiple` blocks until enough permits are available and
+//! takes them at once, and the non-blocking `try_down_multiple_allow_isr`.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::Semaphore,
+    task,
+    task::main,
+};
+
+static SEM: Semaphore = Semaphore::new(4, 0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(|| {
+            SEM.down_multiple(3).unwrap();
+            dbg_println!("Took 3 permits, count {}", SEM.count());
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    for i in 1..=3 {
+        dbg_println!("Up {}", i);
+        SEM.up();
+    }
+
+    // Let the waiting task complete.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    if SEM.down_multiple(5).is_err() {
+        dbg_println!("Cannot take more than the maximum");
+    }
+
+    if SEM.try_down_multiple_allow_isr(2).is_err() {
+        dbg_println!("Not enough permits");
+    }
+    SEM.try_up_allow_isr().unwrap();
+    SEM.try_up_allow_isr().unwrap();
+    if SEM.try_down_multiple_allow_isr(2).is_ok() {
+        dbg_println!("Took 2 permits, count {}", SEM.count());
+    }
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
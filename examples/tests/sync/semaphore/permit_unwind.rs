@@ -0,0 +1,46 @@
+//! Test that a semaphore permit acquired by a panicking task is released
+//! when the task is unwound, and that a forgotten permit is not released.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::Semaphore,
+    task,
+    task::main,
+};
+
+static SEM: Semaphore = Semaphore::new(1, 1);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(will_panic)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the test task and its unwinding complete first.
+    task::change_current_priority(config::UNWIND_PRIORITY + 1).unwrap();
+    dbg_println!("Count after unwinding: {}", SEM.count());
+
+    SEM.acquire().forget();
+    dbg_println!("Count after forgetting: {}", SEM.count());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn will_panic() {
+    let _permit = SEM.acquire();
+    dbg_println!("Count after acquiring: {}", SEM.count());
+    panic!()
+}
@@ -0,0 +1,70 @@
+//! Tests posting multiple permits and notifications at once with
+//! `up_n_allow_isr` and `notify_n_allow_isr`.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{Mailbox, Semaphore},
+    task,
+    task::main,
+};
+
+static SEMAPHORE: Semaphore = Semaphore::new(4, 0);
+static MAILBOX: Mailbox = Mailbox::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    for id in 0..2 {
+        task::build()
+            .set_entry(move || down_task(id))
+            .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+            .spawn()
+            .unwrap();
+    }
+    task::build()
+        .set_entry(wait_task)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the test tasks run and block.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    // Both blocked tasks should be woken up, leaving one permit.
+    SEMAPHORE.up_n_allow_isr(3).unwrap();
+    dbg_println!("Semaphore count {}", SEMAPHORE.count());
+
+    // Exceeding the maximum should leave the counter unchanged.
+    let result = SEMAPHORE.up_n_allow_isr(4);
+    dbg_println!("Up by 4 {:?}, count {}", result, SEMAPHORE.count());
+    SEMAPHORE.up_n_allow_isr(3).unwrap();
+    dbg_println!("Semaphore count {}", SEMAPHORE.count());
+
+    // The waiting task should receive all three notifications.
+    MAILBOX.notify_n_allow_isr(3);
+    dbg_println!("Mailbox notified");
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn down_task(id: usize) {
+    SEMAPHORE.down();
+    dbg_println!("Task {} took a permit", id);
+}
+
+fn wait_task() {
+    for i in 0..3 {
+        MAILBOX.wait();
+        dbg_println!("Notification {} received", i);
+    }
+}
@@ -0,0 +1,86 @@
+//! Tests building a custom primitive on the public soft lock API. A nested
+//! access while the full access is held gets pend-only access, as an ISR
+//! preempting the owner would, and its pended operation is run when the full
+//! access is released.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    sync::{Access, AllowPendOp, RefCellSchedSafe, RunPendedOp, SoftLock, Spin},
+    task::main,
+};
+
+struct Inner {
+    total: Spin<u32>,
+    pended: AtomicU32,
+}
+
+struct FullAccessor<'a>(&'a Inner);
+struct PendAccessor<'a>(&'a AtomicU32);
+
+impl<'a> AllowPendOp<'a> for Inner {
+    type FullAccessor = FullAccessor<'a>;
+    type PendOnlyAccessor = PendAccessor<'a>;
+    fn full_access(&'a self) -> Self::FullAccessor {
+        FullAccessor(self)
+    }
+    fn pend_only_access(&'a self) -> Self::PendOnlyAccessor {
+        PendAccessor(&self.pended)
+    }
+}
+
+impl RunPendedOp for FullAccessor<'_> {
+    fn run_pended_op(&mut self) {
+        let pended = self.0.pended.swap(0, Ordering::SeqCst);
+        dbg_println!("Running {} pended", pended);
+        *self.0.total.lock() += pended;
+    }
+}
+
+static EVENTS: RefCellSchedSafe<SoftLock<Inner>> = RefCellSchedSafe::new(SoftLock::new(Inner {
+    total: Spin::new(0),
+    pended: AtomicU32::new(0),
+}));
+
+/// Record an event, either directly or by pending it.
+fn record() {
+    EVENTS.with_suspended_scheduler(|events, _| {
+        events.with_access(|access| match access {
+            Access::Full { full_access } => {
+                dbg_println!("Recording with full access");
+                *full_access.0.total.lock() += 1;
+            }
+            Access::PendOnly { pend_access } => {
+                dbg_println!("Pending");
+                pend_access.0.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+    });
+}
+
+fn total() -> u32 {
+    EVENTS.with_suspended_scheduler(|events, _| {
+        events.must_with_full_access(|full_access| *full_access.0.total.lock())
+    })
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    record();
+
+    EVENTS.with_suspended_scheduler(|events, _| {
+        events.must_with_full_access(|_full_access| {
+            record();
+            record();
+            dbg_println!("Releasing full access");
+        })
+    });
+
+    dbg_println!("Total {}", total());
+
+    semihosting::terminate(true);
+}
@@ -0,0 +1,53 @@
+//! Test that a coordinator task waiting on a `WaitGroup` resumes only after
+//! all worker tasks finish their work items.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::WaitGroup,
+    task,
+    task::main,
+    time,
+};
+
+static PENDING: WaitGroup = WaitGroup::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    PENDING.add(3);
+    for id in 1..=3 {
+        task::build()
+            .set_entry(move || worker(id))
+            .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+            .spawn()
+            .unwrap();
+    }
+
+    if !PENDING.wait_timeout(5) {
+        dbg_println!("Not yet");
+    }
+    PENDING.wait();
+    dbg_println!("All done, count {}", PENDING.count());
+
+    if PENDING.done_allow_isr().is_err() {
+        dbg_println!("Extra done rejected");
+    }
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn worker(id: u32) {
+    time::sleep_ms(10 * id).unwrap();
+    dbg_println!("Worker {} done", id);
+    PENDING.done_allow_isr().unwrap();
+}
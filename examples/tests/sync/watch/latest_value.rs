@@ -0,0 +1,68 @@
+//! Test that a watcher blocks until a value is sent, with and without a
+//! timeout, and that it only receives the latest of the values sent while
+//! it is busy.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::Watch,
+    task,
+    task::main,
+    time,
+};
+
+static SENSOR: Watch<u32> = Watch::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(watcher)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Let the test task run and block on the watch.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY + 2).unwrap();
+
+    // Let the timed wait expire. The test task is then sleeping.
+    time::sleep_ms(15).unwrap();
+
+    // Only the last value is received.
+    SENSOR.send_allow_isr(1);
+    SENSOR.send_allow_isr(2);
+    SENSOR.send_allow_isr(3);
+    time::sleep_ms(35).unwrap();
+
+    // Wakes up the test task blocked on the watch.
+    SENSOR.send_allow_isr(4);
+    time::sleep_ms(10).unwrap();
+
+    dbg_println!("Latest value {:?}", SENSOR.get_allow_isr());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn watcher() {
+    let mut watcher = SENSOR.watcher();
+
+    let value = watcher.changed_timeout(10);
+    dbg_println!("Watcher timed out: {}", value.is_none());
+
+    // Stay busy while the values are sent.
+    time::sleep_ms(20).unwrap();
+    dbg_println!("Watcher sees change: {}", watcher.has_changed());
+    dbg_println!("Watcher got {}", watcher.changed());
+
+    dbg_println!("Watcher got {}", watcher.changed());
+    dbg_println!("Watcher sees change: {}", watcher.has_changed());
+}
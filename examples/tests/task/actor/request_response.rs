@@ -0,0 +1,55 @@
+//! Test that an actor handles messages in order and answers requests.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    actor::{self, Actor, ReplyTo},
+    debug::semihosting::{self, dbg_println},
+    task::main,
+};
+
+enum CounterMsg {
+    Add(u32),
+    Get(ReplyTo<u32>),
+}
+
+struct Counter(u32);
+
+impl Actor for Counter {
+    type Message = CounterMsg;
+
+    fn started(&mut self) {
+        dbg_println!("counter started");
+    }
+
+    fn handle(&mut self, msg: CounterMsg) {
+        match msg {
+            CounterMsg::Add(n) => self.0 += n,
+            CounterMsg::Get(reply) => reply.reply(self.0),
+        }
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let counter = actor::build().spawn::<_, _, 4>(|| Counter(0)).unwrap();
+
+    counter.send(CounterMsg::Add(5));
+    counter.send(CounterMsg::Add(7));
+    dbg_println!("total {:?}", counter.ask(CounterMsg::Get));
+
+    let clone = counter.clone();
+    clone.try_send_allow_isr(CounterMsg::Add(1)).ok().unwrap();
+    dbg_println!("total {:?}", clone.ask_until_timeout(CounterMsg::Get, 100));
+    dbg_println!("restarts {}", counter.restart_count());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,59 @@
+//! Test that an actor panicking while handling a request is restarted with
+//! fresh state, and that the request fails instead of blocking forever.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use hopter::{
+    actor::{self, Actor, ReplyTo},
+    debug::semihosting::{self, dbg_println},
+    task::main,
+};
+
+static STARTS: AtomicUsize = AtomicUsize::new(0);
+
+enum CounterMsg {
+    Add(u32),
+    Crash(ReplyTo<u32>),
+    Get(ReplyTo<u32>),
+}
+
+struct Counter(u32);
+
+impl Actor for Counter {
+    type Message = CounterMsg;
+
+    fn started(&mut self) {
+        STARTS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn handle(&mut self, msg: CounterMsg) {
+        match msg {
+            CounterMsg::Add(n) => self.0 += n,
+            CounterMsg::Crash(_reply) => panic!(),
+            CounterMsg::Get(reply) => reply.reply(self.0),
+        }
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let counter = actor::build().spawn::<_, _, 4>(|| Counter(0)).unwrap();
+
+    counter.send(CounterMsg::Add(3));
+    dbg_println!("before crash {:?}", counter.ask(CounterMsg::Get));
+    dbg_println!("crash {:?}", counter.ask(CounterMsg::Crash));
+    dbg_println!("after crash {:?}", counter.ask(CounterMsg::Get));
+    dbg_println!("starts {}", STARTS.load(Ordering::SeqCst));
+    dbg_println!("restarts {}", counter.restart_count());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
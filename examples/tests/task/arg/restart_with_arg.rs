@@ -0,0 +1,50 @@
+//! Tests that a task spawned with an argument gets the same argument when it
+//! is restarted after panicking.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task,
+    task::main,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let runs = Arc::new(AtomicU32::new(0));
+
+    task::build()
+        .set_entry_with_arg(count_runs, runs.clone())
+        .spawn_restartable()
+        .unwrap();
+
+    // Let the test task and its unwinding complete first.
+    task::change_current_priority(config::UNWIND_PRIORITY + 1).unwrap();
+
+    dbg_println!("Runs seen by main: {}", runs.load(Ordering::SeqCst));
+    // All clones held by the task are dropped.
+    assert_eq!(Arc::strong_count(&runs), 1);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn count_runs(runs: Arc<AtomicU32>) {
+    let run = runs.fetch_add(1, Ordering::SeqCst) + 1;
+    dbg_println!("Run {}", run);
+
+    // Deliberate panic on the first run.
+    if run == 1 {
+        panic!()
+    }
+}
@@ -0,0 +1,62 @@
+//! Tests that the concurrency limit of breathing tasks can be changed at
+//! runtime, and that work items are delayed and counted when the memory
+//! pressure reaches the target, while one breathing task keeps working.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    task::{self, breathing, main},
+    time,
+};
+
+const TASK_NUM: usize = 3;
+
+/// The maximum number of breathing tasks observed working at the same time.
+static MAX_INFLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    breathing::set_concurrency_limit(1);
+
+    for _ in 0..TASK_NUM {
+        task::build_breathing()
+            .set_init(|| ())
+            .set_wait(|_| time::sleep_ms(1).unwrap())
+            .set_work(|_, _| {
+                MAX_INFLIGHT.fetch_max(breathing::get_inflight_count(), Ordering::SeqCst);
+                time::sleep_ms(10).unwrap();
+            })
+            .spawn()
+            .unwrap();
+    }
+
+    time::sleep_ms(60).unwrap();
+    dbg_println!("limit 1: {}", MAX_INFLIGHT.swap(0, Ordering::SeqCst));
+
+    breathing::set_concurrency_limit(TASK_NUM);
+    time::sleep_ms(60).unwrap();
+    dbg_println!("limit 3: {}", MAX_INFLIGHT.swap(0, Ordering::SeqCst));
+
+    // Any memory pressure reaches the target.
+    breathing::set_target_memory_pressure(0);
+    time::sleep_ms(20).unwrap();
+    MAX_INFLIGHT.store(0, Ordering::SeqCst);
+    let delayed_before = breathing::get_memory_delay_count();
+    time::sleep_ms(60).unwrap();
+    dbg_println!("under pressure: {}", MAX_INFLIGHT.load(Ordering::SeqCst));
+    dbg_println!(
+        "delays counted: {}",
+        breathing::get_memory_delay_count() > delayed_before
+    );
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,37 @@
+//! Tests that checkpoints record the progress of a long computation and do
+//! not cancel it when the system is not shutting down.
+
+#![no_main]
+#![no_std]
+
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    task::{self, main},
+    time,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let before = task::progress();
+    dbg_println!("count before: {}", before.count);
+
+    // Let the tick advance so that the recorded tick is not zero.
+    time::sleep_ms(5).unwrap();
+
+    let mut sum: u32 = 0;
+    let mut result = Ok(());
+    for i in 0..100 {
+        sum += i;
+        result = task::checkpoint();
+        if result.is_err() {
+            break;
+        }
+    }
+    dbg_println!("sum {}, result {:?}", sum, result);
+
+    let after = task::progress();
+    dbg_println!("count after: {}", after.count);
+    dbg_println!("tick recorded: {}", after.last_tick > 0);
+
+    semihosting::terminate(true);
+}
@@ -0,0 +1,45 @@
+//! Test running futures on the async executor. Two futures sleep for
+//! different durations and should complete in the order of their deadlines.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    sync::Mailbox,
+    task::{self, main},
+    time,
+};
+
+static DONE: Mailbox = Mailbox::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::spawn_async(async {
+        time::sleep_ms_async(200).unwrap().await;
+        dbg_println!("slept 200 ms");
+        DONE.notify_allow_isr();
+    })
+    .unwrap();
+
+    task::spawn_async(async {
+        time::sleep_ms_async(100).unwrap().await;
+        dbg_println!("slept 100 ms");
+        time::sleep_ms_async(50).unwrap().await;
+        dbg_println!("slept 150 ms");
+        DONE.notify_allow_isr();
+    })
+    .unwrap();
+
+    DONE.wait();
+    DONE.wait();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,69 @@
+//! Tests that the exit hooks of a task run in the reverse order of
+//! registration when the task returns, that they run after the unwinding
+//! when the task panics, and that a panicking hook does not prevent the
+//! remaining hooks from running.
+
+#![no_std]
+#![no_main]
+
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main},
+    time,
+};
+
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        dbg_println!("guard dropped");
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(|| {
+            task::at_exit(|| dbg_println!("hook 1"));
+            task::at_exit(|| dbg_println!("hook 2"));
+            task::at_exit(|| dbg_println!("hook 3"));
+            dbg_println!("returning");
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    task::build()
+        .set_entry(|| {
+            let _guard = Guard;
+            task::at_exit(|| dbg_println!("hook after unwinding"));
+            dbg_println!("panicking");
+            panic!();
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Wait for the unwinding to finish.
+    time::sleep_ms(20).unwrap();
+
+    task::build()
+        .set_entry(|| {
+            task::at_exit(|| dbg_println!("remaining hook"));
+            task::at_exit(|| panic!());
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    time::sleep_ms(20).unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,74 @@
+//! Tests that changing the priority of another task takes effect
+//! immediately, both when raising it above the calling task and when
+//! lowering it below.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main, TaskHandle},
+    time,
+};
+
+static COUNT: AtomicU32 = AtomicU32::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_name("background")
+        .set_entry(|| dbg_println!("background runs"))
+        .set_priority(config::DEFAULT_TASK_PRIORITY + 1)
+        .spawn()
+        .unwrap();
+    let background = TaskHandle::from_id(task::find_by_name("background").unwrap());
+    assert_eq!(
+        background.priority(),
+        Some(config::DEFAULT_TASK_PRIORITY + 1)
+    );
+
+    // The background task preempts the main task as soon as it has a higher
+    // priority.
+    dbg_println!("raising priority");
+    background
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .unwrap();
+    dbg_println!("priority raised");
+    assert_eq!(background.priority(), None);
+
+    task::build()
+        .set_name("sensor")
+        .set_entry(|| loop {
+            COUNT.fetch_add(1, Ordering::SeqCst);
+            time::sleep_ms(1).unwrap();
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    let sensor = TaskHandle::from_id(task::find_by_name("sensor").unwrap());
+    time::sleep_ms(5).unwrap();
+
+    // The sensor task does not run while the main task is busy.
+    sensor
+        .set_priority(config::DEFAULT_TASK_PRIORITY + 1)
+        .unwrap();
+    let frozen = COUNT.load(Ordering::SeqCst);
+    let start = time::get_tick();
+    while time::get_tick() - start < 10 {}
+    assert_eq!(COUNT.load(Ordering::SeqCst), frozen);
+    dbg_println!("sensor deprioritized");
+
+    assert_eq!(sensor.set_priority(config::TASK_PRIORITY_LEVELS), Err(()));
+    let idle = TaskHandle::from_id(task::find_by_name("idle").unwrap());
+    assert_eq!(idle.set_priority(config::DEFAULT_TASK_PRIORITY), Err(()));
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
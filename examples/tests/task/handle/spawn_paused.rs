@@ -0,0 +1,45 @@
+//! Tests that a task spawned paused does not run until it is started, and
+//! that tasks started together run in the order of their priorities.
+
+#![no_std]
+#![no_main]
+
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main},
+    time,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let low = task::build()
+        .set_entry(|| dbg_println!("low runs"))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn_paused()
+        .unwrap();
+    let high = task::build()
+        .set_entry(|| dbg_println!("high runs"))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 2)
+        .spawn_paused()
+        .unwrap();
+
+    time::sleep_ms(10).unwrap();
+    dbg_println!("both created, suspended: {}", high.handle().is_suspended());
+
+    task::run_uninterrupted(|| {
+        low.start().unwrap();
+        high.start().unwrap();
+        dbg_println!("started");
+    });
+
+    time::sleep_ms(10).unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,77 @@
+//! Tests that the stack statistics of a task count the stacklets it
+//! currently has and keep the peak stack usage after the stacklets are
+//! freed, and that a task without dynamic stack extension reports its whole
+//! stack.
+
+#![no_std]
+#![no_main]
+
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main, StackStats, TaskHandle},
+};
+
+const FRAME_SIZE: usize = 4096;
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(worker)
+        .set_stack_init_size(2048)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    task::build()
+        .set_name("static")
+        .set_entry(|| {})
+        .disable_dynamic_stack()
+        .set_stack_limit(2048)
+        .set_priority(config::DEFAULT_TASK_PRIORITY + 1)
+        .spawn()
+        .unwrap();
+    let stats = TaskHandle::from_id(task::find_by_name("static").unwrap())
+        .stack_stats()
+        .unwrap();
+    dbg_println!("static: {} {}", stats.peak_usage(), stats.stacklet_count());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn own_stats() -> StackStats {
+    task::current_handle().stack_stats().unwrap()
+}
+
+/// Use a stack frame too large to fit in the initial stacklet, and return
+/// the stack statistics while running with it.
+#[inline(never)]
+fn large_frame(seed: u8) -> StackStats {
+    let buf = core::hint::black_box([seed; FRAME_SIZE]);
+    let stats = own_stats();
+    core::hint::black_box(buf[FRAME_SIZE - 1]);
+    stats
+}
+
+fn worker() {
+    let before = own_stats();
+    let deep = large_frame(0);
+    let after = own_stats();
+
+    dbg_println!(
+        "extended: {}",
+        deep.stacklet_count() > before.stacklet_count()
+    );
+    dbg_println!("usage grown: {}", deep.peak_usage() >= FRAME_SIZE);
+    dbg_println!(
+        "shrunk: {}",
+        after.stacklet_count() == before.stacklet_count()
+    );
+    dbg_println!("peak kept: {}", after.peak_usage() == deep.peak_usage());
+}
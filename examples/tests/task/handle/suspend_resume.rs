@@ -0,0 +1,81 @@
+//! Tests that a suspended task does not run until it is resumed, both when
+//! it is suspended by another task while sleeping and when it suspends
+//! itself.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::Mailbox,
+    task::{self, main, TaskHandle, TaskStatus},
+    time,
+};
+
+static COUNT: AtomicU32 = AtomicU32::new(0);
+static HANDLE_READY: Mailbox = Mailbox::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_name("worker")
+        .set_entry(|| loop {
+            COUNT.fetch_add(1, Ordering::SeqCst);
+            time::sleep_ms(1).unwrap();
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    let worker = TaskHandle::from_id(task::find_by_name("worker").unwrap());
+
+    time::sleep_ms(10).unwrap();
+    worker.suspend().unwrap();
+    assert!(worker.is_suspended());
+    let frozen = COUNT.load(Ordering::SeqCst);
+    time::sleep_ms(10).unwrap();
+    assert_eq!(COUNT.load(Ordering::SeqCst), frozen);
+    task::for_each_task(|info| {
+        if info.id() == worker.id() {
+            assert_eq!(info.status(), TaskStatus::Suspended);
+        }
+    });
+    dbg_println!("worker frozen");
+
+    worker.resume().unwrap();
+    assert!(!worker.is_suspended());
+    time::sleep_ms(10).unwrap();
+    assert!(COUNT.load(Ordering::SeqCst) > frozen);
+    dbg_println!("worker resumed");
+
+    task::build()
+        .set_name("self-suspending")
+        .set_entry(|| {
+            HANDLE_READY.notify_allow_isr();
+            task::suspend_current();
+            dbg_println!("resumed");
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    HANDLE_READY.wait();
+    let suspended = TaskHandle::from_id(task::find_by_name("self-suspending").unwrap());
+    assert!(suspended.is_suspended());
+    dbg_println!("task suspended itself");
+
+    // The task runs to completion once resumed.
+    suspended.resume().unwrap();
+    assert_eq!(suspended.resume(), Err(()));
+
+    let idle = TaskHandle::from_id(task::find_by_name("idle").unwrap());
+    assert_eq!(idle.suspend(), Err(()));
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
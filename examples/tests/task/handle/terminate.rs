@@ -0,0 +1,120 @@
+//! Tests that a terminated task is unwound, running the drop handlers of its
+//! live objects, whether it is sleeping, waiting on a mailbox with a
+//! timeout, suspended or computing, and that a terminated restartable task
+//! is not restarted. The mailbox the terminated task waited on must remain
+//! usable.
+
+#![no_std]
+#![no_main]
+
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::Mailbox,
+    task::{self, main, TaskHandle},
+    time,
+};
+
+static SUSPENDING: Mailbox = Mailbox::new();
+static WAITED: Mailbox = Mailbox::new();
+
+struct Guard(&'static str);
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        dbg_println!("{} dropped", self.0);
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_name("sleeper")
+        .set_entry(|| {
+            let _guard = Guard("sleeper");
+            loop {
+                time::sleep_ms(1).unwrap();
+            }
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    let sleeper = TaskHandle::from_id(task::find_by_name("sleeper").unwrap());
+
+    time::sleep_ms(5).unwrap();
+    sleeper.terminate().unwrap();
+    time::sleep_ms(10).unwrap();
+    assert_eq!(task::find_by_name("sleeper"), None);
+    assert_eq!(sleeper.terminate(), Err(()));
+
+    task::build()
+        .set_name("waiter")
+        .set_entry(|| {
+            let _guard = Guard("waiter");
+            WAITED.wait_until_timeout(10_000);
+            dbg_println!("should not time out");
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    let waiter = TaskHandle::from_id(task::find_by_name("waiter").unwrap());
+
+    time::sleep_ms(5).unwrap();
+    waiter.terminate().unwrap();
+    time::sleep_ms(10).unwrap();
+    assert_eq!(task::find_by_name("waiter"), None);
+    // The mailbox no longer refers to the unwound task.
+    assert!(!WAITED.wait_until_timeout(1));
+    WAITED.notify_allow_isr();
+    assert!(WAITED.wait_until_timeout(1));
+    dbg_println!("mailbox reusable");
+
+    task::build()
+        .set_name("frozen")
+        .set_entry(|| {
+            let _guard = Guard("frozen");
+            SUSPENDING.notify_allow_isr();
+            task::suspend_current();
+            dbg_println!("should not be resumed");
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    SUSPENDING.wait();
+    let frozen = TaskHandle::from_id(task::find_by_name("frozen").unwrap());
+    frozen.terminate().unwrap();
+    time::sleep_ms(10).unwrap();
+    assert_eq!(task::find_by_name("frozen"), None);
+
+    // Runs at the same priority as the main task, so that both get the CPU
+    // when the spinner yields at its checkpoints.
+    task::build()
+        .set_name("spinner")
+        .set_entry(|| {
+            let _guard = Guard("spinner");
+            loop {
+                task::checkpoint().unwrap();
+            }
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY)
+        .spawn_restartable()
+        .unwrap();
+    let spinner = TaskHandle::from_id(task::find_by_name("spinner").unwrap());
+
+    time::sleep_ms(5).unwrap();
+    spinner.terminate().unwrap();
+    time::sleep_ms(20).unwrap();
+    // A restarted instance would keep the name.
+    assert_eq!(task::find_by_name("spinner"), None);
+
+    let idle = TaskHandle::from_id(task::find_by_name("idle").unwrap());
+    assert_eq!(idle.terminate(), Err(()));
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,56 @@
+//! Tests that yielding to a ready task of the same priority runs it ahead of
+//! the other ready tasks of that priority, and that the yielding task runs
+//! again after them.
+
+#![no_std]
+#![no_main]
+
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main},
+    time,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let consumer = task::build()
+        .set_entry(|| dbg_println!("consumer runs"))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn_paused()
+        .unwrap();
+    let consumer_handle = consumer.handle();
+
+    // The tasks become ready in the order: producer, other, consumer.
+    task::run_uninterrupted(|| {
+        task::build()
+            .set_entry(move || {
+                dbg_println!("producer yields to consumer");
+                task::yield_to(consumer_handle).unwrap();
+                dbg_println!("producer runs again");
+            })
+            .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+            .spawn()
+            .unwrap();
+        task::build()
+            .set_entry(|| dbg_println!("other runs"))
+            .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+            .spawn()
+            .unwrap();
+        consumer.start().unwrap();
+    });
+
+    time::sleep_ms(10).unwrap();
+
+    // The consumer has returned.
+    assert_eq!(task::yield_to(consumer_handle), Err(()));
+    dbg_println!("terminated target rejected");
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
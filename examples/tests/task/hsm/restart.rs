@@ -0,0 +1,83 @@
+//! Test that a state machine panicking while handling an event is restarted
+//! in its safe state.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    hsm::{self, Context, Response, StateMachine},
+    task::main,
+    time,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Running,
+    Safe,
+}
+
+enum Event {
+    Crash,
+    Resume,
+}
+
+struct Controller;
+
+impl StateMachine for Controller {
+    type State = State;
+    type Event = Event;
+
+    fn initial_state(&self) -> State {
+        State::Running
+    }
+
+    fn safe_state(&self) -> State {
+        State::Safe
+    }
+
+    fn on_entry(&mut self, state: State, _ctx: &mut Context<State, Event>) {
+        dbg_println!("enter {:?}", state);
+    }
+
+    fn handle(
+        &mut self,
+        state: State,
+        event: &Event,
+        _ctx: &mut Context<State, Event>,
+    ) -> Response<State> {
+        match (state, event) {
+            (State::Running, Event::Crash) => panic!(),
+            (State::Safe, Event::Resume) => Response::Transition(State::Running),
+            _ => Response::Super,
+        }
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let controller = hsm::build()
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn::<_, _, 4>(|| Controller)
+        .unwrap();
+    time::sleep_ms(10).unwrap();
+
+    controller.post(Event::Crash);
+    time::sleep_ms(100).unwrap();
+    dbg_println!("state {:?}", controller.state());
+    dbg_println!("restarts {}", controller.restart_count());
+
+    controller.post(Event::Resume);
+    time::sleep_ms(10).unwrap();
+    dbg_println!("state {:?}", controller.state());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
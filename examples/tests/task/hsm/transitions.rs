@@ -0,0 +1,117 @@
+//! Test entry and exit actions of nested states, events handled by parent
+//! states, and timed transitions cancelled when their state is exited.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    hsm::{self, Context, Response, StateMachine},
+    task::main,
+    time,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Off,
+    On,
+    Idle,
+    Busy,
+}
+
+enum Event {
+    PowerOn,
+    Work,
+    Timeout,
+    PowerOff,
+}
+
+struct Device;
+
+impl StateMachine for Device {
+    type State = State;
+    type Event = Event;
+
+    fn initial_state(&self) -> State {
+        State::Off
+    }
+
+    fn parent(state: State) -> Option<State> {
+        match state {
+            State::Idle | State::Busy => Some(State::On),
+            _ => None,
+        }
+    }
+
+    fn initial_child(state: State) -> Option<State> {
+        match state {
+            State::On => Some(State::Idle),
+            _ => None,
+        }
+    }
+
+    fn on_entry(&mut self, state: State, ctx: &mut Context<State, Event>) {
+        dbg_println!("enter {:?}", state);
+        if state == State::Busy {
+            ctx.start_timer(20, Event::Timeout);
+        }
+    }
+
+    fn on_exit(&mut self, state: State) {
+        dbg_println!("exit {:?}", state);
+    }
+
+    fn handle(
+        &mut self,
+        state: State,
+        event: &Event,
+        _ctx: &mut Context<State, Event>,
+    ) -> Response<State> {
+        match (state, event) {
+            (State::Off, Event::PowerOn) => Response::Transition(State::On),
+            (State::Idle, Event::Work) => Response::Transition(State::Busy),
+            (State::Busy, Event::Timeout) => Response::Transition(State::Idle),
+            (State::On, Event::PowerOff) => Response::Transition(State::Off),
+            _ => Response::Super,
+        }
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let device = hsm::build()
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn::<_, _, 4>(|| Device)
+        .unwrap();
+    time::sleep_ms(10).unwrap();
+
+    device.post(Event::PowerOn);
+    time::sleep_ms(10).unwrap();
+
+    // Leave `Busy` by its timer.
+    device.post(Event::Work);
+    time::sleep_ms(50).unwrap();
+    dbg_println!("state {:?}", device.state());
+
+    // Leave `Busy` before its timer expires, through an event handled by
+    // the parent state.
+    device.post(Event::Work);
+    time::sleep_ms(10).unwrap();
+    device.post(Event::PowerOff);
+    time::sleep_ms(50).unwrap();
+
+    // Not handled by any state.
+    device.post(Event::Timeout);
+    time::sleep_ms(10).unwrap();
+    dbg_println!("state {:?}", device.state());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
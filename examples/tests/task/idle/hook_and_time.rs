@@ -0,0 +1,52 @@
+//! Tests that the idle hook runs when no other task is ready, and that the
+//! idle time increases while all tasks are sleeping but not while a task is
+//! busy.
+
+#![no_main]
+#![no_std]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    task::{self, main},
+    time,
+};
+
+static HOOK_RUNS: AtomicU32 = AtomicU32::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::set_idle_hook(|| {
+        HOOK_RUNS.fetch_add(1, Ordering::SeqCst);
+        cortex_m::asm::wfi();
+    });
+
+    let idle_before = task::get_idle_time_ms();
+    time::sleep_ms(50).unwrap();
+    let idle_delta = task::get_idle_time_ms().wrapping_sub(idle_before);
+
+    if HOOK_RUNS.load(Ordering::SeqCst) > 0 {
+        dbg_println!("idle hook ran");
+    }
+    if (45..=50).contains(&idle_delta) {
+        dbg_println!("idle time measured");
+    }
+
+    // Keep the CPU busy so that the idle task does not run.
+    let idle_before = task::get_idle_time_ms();
+    let start = time::get_tick();
+    while time::get_tick().wrapping_sub(start) < 20 {}
+    let idle_delta = task::get_idle_time_ms().wrapping_sub(idle_before);
+
+    if idle_delta == 0 {
+        dbg_println!("no idle time when busy");
+    }
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,69 @@
+//! Tests that enumerating the tasks reports every live task with its name
+//! and state, and no longer reports a task after it terminates.
+
+#![no_std]
+#![no_main]
+
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::Mailbox,
+    task::{self, main, TaskStatus},
+    time,
+};
+
+static NEVER: Mailbox = Mailbox::new();
+static EXIT: Mailbox = Mailbox::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_name("sleeper")
+        .set_entry(|| time::sleep_ms(100_000).unwrap())
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    task::build()
+        .set_name("waiter")
+        .set_entry(|| NEVER.wait())
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    task::build()
+        .set_name("exiting")
+        .set_entry(|| EXIT.wait())
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    task::build()
+        .set_name("ready")
+        .set_entry(|| {})
+        .set_priority(config::DEFAULT_TASK_PRIORITY + 1)
+        .spawn()
+        .unwrap();
+
+    print_tasks();
+
+    // Let the task terminate.
+    EXIT.notify_allow_isr();
+    print_tasks();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn print_tasks() {
+    task::for_each_task(|info| {
+        if info.status() == TaskStatus::Running {
+            assert_eq!(info.id(), task::current_id());
+            assert!(info.stack_usage() > 0);
+        }
+        dbg_println!("{:?} {:?}", info.name(), info.status());
+    });
+    dbg_println!("---");
+}
@@ -0,0 +1,35 @@
+//! Test that the main function runs in the kernel stage, and that the
+//! application stages are entered only in order.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    init::{self, Stage},
+    task::main,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    dbg_println!("{:?}", init::stage());
+    dbg_println!("{:?}", init::require(Stage::Transports));
+
+    dbg_println!("{:?}", init::advance(Stage::Kernel));
+    dbg_println!("{:?}", init::advance(Stage::Transports));
+    dbg_println!("{:?}", init::require(Stage::Transports));
+
+    // Go without a session.
+    dbg_println!("{:?}", init::advance(Stage::Tasks));
+    dbg_println!("{:?}", init::advance(Stage::Session));
+    dbg_println!("{:?}", init::stage());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,66 @@
+//! Tests that the main task collects the values returned by worker tasks
+//! through their join handles, that joining a panicked worker returns an
+//! error, and that a timed out join gives back the handle.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use alloc::vec::Vec;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main, JoinError},
+    time,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let handles: Vec<_> = (1..=3u32)
+        .map(|n| {
+            task::build()
+                .set_entry(move || (1..=n * 10).sum::<u32>())
+                .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+                .spawn_joinable()
+                .unwrap()
+        })
+        .collect();
+
+    for handle in handles {
+        dbg_println!("result {}", handle.join().unwrap());
+    }
+
+    let panicking = task::build()
+        .set_entry(|| -> u32 { panic!() })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn_joinable()
+        .unwrap();
+    assert_eq!(panicking.join(), Err(JoinError::Panicked));
+    dbg_println!("panicked worker joined");
+
+    let slow = task::build()
+        .set_entry(|| {
+            time::sleep_ms(50).unwrap();
+            42u32
+        })
+        .spawn_joinable()
+        .unwrap();
+    let slow = match slow.join_timeout(10) {
+        Ok(_) => panic!(),
+        Err(handle) => handle,
+    };
+    assert!(!slow.is_finished());
+    dbg_println!("join timed out");
+
+    time::sleep_ms(100).unwrap();
+    assert!(slow.is_finished());
+    dbg_println!("slow result {}", slow.join().unwrap());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,72 @@
+//! Tests that tasks can be named and looked up by name, that every task has
+//! a unique ID, and that a restarted task keeps its name and ID.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::{Mailbox, SpinSchedSafe},
+    task::{self, main, TaskId},
+    time,
+};
+
+/// The ID observed by each instance of the restartable task.
+static OBSERVED_ID: SpinSchedSafe<Option<TaskId>> = SpinSchedSafe::new(None);
+/// Notified by the restarted instance.
+static RESTARTED: Mailbox = Mailbox::new();
+static HAS_PANICKED: AtomicBool = AtomicBool::new(false);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    dbg_println!("main task name {:?}", task::current_name());
+
+    task::build()
+        .set_name("sensor")
+        .set_entry(|| loop {
+            time::sleep_ms(1000).unwrap();
+        })
+        .spawn()
+        .unwrap();
+    task::build()
+        .set_entry(|| dbg_println!("running as {:?}", task::current_name()))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    let sensor = task::find_by_name("sensor").unwrap();
+    assert_ne!(sensor, task::current_id());
+    assert_eq!(task::find_by_name("main"), Some(task::current_id()));
+    assert_eq!(task::find_by_name("missing"), None);
+    dbg_println!("found sensor");
+
+    task::build()
+        .set_name("flaky")
+        .set_entry(flaky)
+        .spawn_restartable()
+        .unwrap();
+    RESTARTED.wait();
+    dbg_println!("restarted task kept its ID");
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn flaky() {
+    assert_eq!(task::current_name(), Some("flaky"));
+    let id = task::current_id();
+    let prev = OBSERVED_ID.lock().replace(id);
+    if !HAS_PANICKED.swap(true, Ordering::SeqCst) {
+        panic!();
+    }
+    assert_eq!(prev, Some(id));
+    assert_eq!(task::find_by_name("flaky"), Some(id));
+    RESTARTED.notify_allow_isr();
+}
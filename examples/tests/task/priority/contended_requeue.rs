@@ -0,0 +1,107 @@
+//! Tests changing the priority of a ready task from an ISR. When the ISR
+//! preempts the context switch holding the ready queue, the task is moved to
+//! the list of its new priority after the context switch finishes. When the
+//! ready queue is free, the task is moved at once and preempts the current
+//! task if it now has a higher priority.
+
+#![no_main]
+#![no_std]
+#![feature(naked_functions)]
+#![feature(asm_const)]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use cortex_m::peripheral::NVIC;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    interrupt::declare::{handler, irq},
+    sync::SpinIrqSafe,
+    task::{self, main, TaskHandle},
+    time,
+};
+use stm32f4xx_hal::pac::Interrupt;
+
+irq!(Tim2Irq, Interrupt::TIM2);
+
+/// The task whose priority the ISR changes and its new priority.
+static TARGET: SpinIrqSafe<Option<(TaskHandle, u8)>, Tim2Irq> = SpinIrqSafe::new(None);
+static ALL_CHANGED: AtomicBool = AtomicBool::new(true);
+
+/// Pend the ISR when dropped. The entry closure of a restartable task is
+/// dropped together with the task struct by the context switch that follows
+/// the task's return, while the ready queue is held. The copies of the
+/// closure run by the task are disarmed.
+struct PendOnDrop {
+    armed: bool,
+}
+
+impl Clone for PendOnDrop {
+    fn clone(&self) -> Self {
+        Self { armed: false }
+    }
+}
+
+impl Drop for PendOnDrop {
+    fn drop(&mut self) {
+        if self.armed {
+            NVIC::pend(Interrupt::TIM2);
+        }
+    }
+}
+
+#[main]
+fn main(_cp: cortex_m::Peripherals) {
+    unsafe {
+        NVIC::unmask(Interrupt::TIM2);
+    }
+
+    // The tasks are ready but do not run until the main task sleeps.
+    let low = config::DEFAULT_TASK_PRIORITY + 2;
+    for name in ["first", "second", "third"] {
+        task::build()
+            .set_name(name)
+            .set_entry(move || dbg_println!("{} runs", name))
+            .set_priority(low)
+            .spawn()
+            .unwrap();
+    }
+    let second = TaskHandle::from_id(task::find_by_name("second").unwrap());
+    let third = TaskHandle::from_id(task::find_by_name("third").unwrap());
+
+    // The ISR moves the second task ahead of the first one while the context
+    // switch after the short-lived task holds the ready queue.
+    *TARGET.lock() = Some((second, config::DEFAULT_TASK_PRIORITY + 1));
+    let guard = PendOnDrop { armed: true };
+    task::build()
+        .set_entry(move || {
+            let _guard = &guard;
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn_restartable()
+        .unwrap();
+
+    // The third task preempts the main task when the ISR returns.
+    *TARGET.lock() = Some((third, config::DEFAULT_TASK_PRIORITY - 1));
+    NVIC::pend(Interrupt::TIM2);
+    dbg_println!("main task resumes");
+
+    time::sleep_ms(10).unwrap();
+    dbg_println!("all changed: {}", ALL_CHANGED.load(Ordering::SeqCst));
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+#[handler(TIM2)]
+fn tim2_handler() {
+    if let Some((handle, prio)) = TARGET.lock().take() {
+        if handle.set_priority_allow_isr(prio).is_err() {
+            ALL_CHANGED.store(false, Ordering::SeqCst);
+        }
+    }
+}
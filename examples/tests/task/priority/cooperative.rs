@@ -0,0 +1,77 @@
+//! Tests that no task runs while the calling task runs a closure with
+//! `run_uninterrupted`, and that a cooperative task preempted by a higher
+//! priority task resumes before the other tasks of its priority, which run
+//! only after it yields.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main},
+    time,
+};
+
+static HIGH_DONE: AtomicBool = AtomicBool::new(false);
+static PEER_RAN: AtomicBool = AtomicBool::new(false);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::run_uninterrupted(|| {
+        task::build()
+            .set_entry(high)
+            .set_priority(config::DEFAULT_TASK_PRIORITY - 3)
+            .spawn()
+            .unwrap();
+        task::build()
+            .set_entry(cooperative)
+            .set_priority(config::DEFAULT_TASK_PRIORITY - 2)
+            .set_cooperative(true)
+            .spawn()
+            .unwrap();
+        task::build()
+            .set_entry(peer)
+            .set_priority(config::DEFAULT_TASK_PRIORITY - 2)
+            .spawn()
+            .unwrap();
+        dbg_println!("spawned");
+    });
+
+    time::sleep_ms(50).unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn high() {
+    dbg_println!("high sleeps");
+    time::sleep_ms(5).unwrap();
+    dbg_println!("high preempts");
+    HIGH_DONE.store(true, Ordering::SeqCst);
+}
+
+fn cooperative() {
+    dbg_println!("cooperative spins");
+    // Spin without yielding until preempted by the high priority task.
+    while !HIGH_DONE.load(Ordering::SeqCst) {
+        core::hint::spin_loop();
+    }
+    dbg_println!(
+        "cooperative resumed before peer: {}",
+        !PEER_RAN.load(Ordering::SeqCst)
+    );
+    task::yield_current();
+    dbg_println!("cooperative done");
+}
+
+fn peer() {
+    dbg_println!("peer runs");
+    PEER_RAN.store(true, Ordering::SeqCst);
+}
@@ -0,0 +1,63 @@
+//! Tests that EDF tasks run in the order of their deadlines, that an EDF
+//! task woken up with an earlier deadline preempts the running EDF task, and
+//! that a task of the EDF priority without a deadline runs after them.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main},
+    time,
+};
+
+static URGENT_DONE: AtomicBool = AtomicBool::new(false);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::run_uninterrupted(|| {
+        task::build()
+            .set_entry(|| dbg_println!("plain runs"))
+            .set_priority(config::EDF_TASK_PRIORITY)
+            .spawn()
+            .unwrap();
+        task::build()
+            .set_entry(relaxed)
+            .set_deadline_ms(50)
+            .spawn()
+            .unwrap();
+        task::build()
+            .set_entry(urgent)
+            .set_deadline_ms(5)
+            .spawn()
+            .unwrap();
+    });
+
+    time::sleep_ms(50).unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn relaxed() {
+    dbg_println!("relaxed starts");
+    // Spin without yielding until preempted by the urgent task.
+    while !URGENT_DONE.load(Ordering::SeqCst) {
+        core::hint::spin_loop();
+    }
+    dbg_println!("relaxed done");
+}
+
+fn urgent() {
+    dbg_println!("urgent sleeps");
+    time::sleep_ms(10).unwrap();
+    dbg_println!("urgent preempts");
+    URGENT_DONE.store(true, Ordering::SeqCst);
+}
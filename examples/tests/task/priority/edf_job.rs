@@ -0,0 +1,70 @@
+//! Tests that an EDF job keeps its deadline while it sleeps in the middle of
+//! the job, so that it preempts a task whose later job was released since,
+//! and that a job is released only by `task::next_job`.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use hopter::{
+    debug::semihosting::{self, dbg_println},
+    task::{self, main},
+    time,
+};
+
+static LONG_DONE: AtomicBool = AtomicBool::new(false);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::run_uninterrupted(|| {
+        // The job of this task is due 20 ticks from now.
+        task::build()
+            .set_entry(long_job)
+            .set_deadline_ms(20)
+            .spawn()
+            .unwrap();
+        task::build()
+            .set_entry(short_jobs)
+            .set_deadline_ms(10)
+            .spawn()
+            .unwrap();
+    });
+
+    time::sleep_ms(60).unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn long_job() {
+    dbg_println!("long job sleeps");
+    // Block in the middle of the job. It wakes up 15 ticks from the start,
+    // still due 20 ticks from the start.
+    time::sleep_ms(15).unwrap();
+    dbg_println!("long job resumes");
+    LONG_DONE.store(true, Ordering::SeqCst);
+}
+
+fn short_jobs() {
+    dbg_println!("short job sleeps");
+    time::sleep_ms(12).unwrap();
+    // The next job is due 22 ticks from the start, after the long job.
+    task::next_job();
+    dbg_println!("short job released");
+
+    // Spin without yielding until preempted by the long job, or give up
+    // after its deadline.
+    let give_up = time::get_tick() + 20;
+    while !LONG_DONE.load(Ordering::SeqCst) && time::get_tick() < give_up {
+        core::hint::spin_loop();
+    }
+    dbg_println!(
+        "preempted by long job: {}",
+        LONG_DONE.load(Ordering::SeqCst)
+    );
+}
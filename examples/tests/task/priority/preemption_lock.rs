@@ -0,0 +1,66 @@
+//! Tests that a task holding the preemption lock is not preempted by a
+//! higher priority task woken up meanwhile, that the tick keeps advancing
+//! while the lock is held, and that the woken task runs once it is released.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    schedule,
+    task::{self, main},
+    time,
+};
+
+static HIGH_RAN: AtomicBool = AtomicBool::new(false);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(high)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 3)
+        .spawn()
+        .unwrap();
+    task::build()
+        .set_entry(locker)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 2)
+        .spawn()
+        .unwrap();
+
+    time::sleep_ms(50).unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn high() {
+    time::sleep_ms(5).unwrap();
+    HIGH_RAN.store(true, Ordering::SeqCst);
+    dbg_println!("high runs");
+}
+
+fn locker() {
+    let lock = schedule::preemption_lock();
+    // The SysTick interrupt is still served, so the tick advances and wakes
+    // up the high priority task.
+    let start = time::get_tick();
+    while time::get_tick().wrapping_sub(start) < 10 {
+        core::hint::spin_loop();
+    }
+    dbg_println!(
+        "leaving region, high ran: {}",
+        HIGH_RAN.load(Ordering::SeqCst)
+    );
+    drop(lock);
+    dbg_println!(
+        "after region, high ran: {}",
+        HIGH_RAN.load(Ordering::SeqCst)
+    );
+}
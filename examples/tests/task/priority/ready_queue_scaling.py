@@ -0,0 +1,21 @@
+import sys
+
+def validate_output(output):
+    # The lines look like: "alone: xxx" and "crowded: xxx". The counts
+    # depend on the host running QEMU, so they are only reported.
+    counts = {}
+    for line in output.splitlines():
+        label, count = line.split(': ')
+        counts[label] = int(count)
+
+    alone = counts.get('alone', 0)
+    crowded = counts.get('crowded', 0)
+    if alone == 0 or crowded == 0:
+        print("Test Failed")
+        sys.exit(1)
+
+    print("Test Passed")
+
+
+if __name__ == "__main__":
+    validate_output(sys.stdin.read())
@@ -0,0 +1,107 @@
+//! Benchmarks context switches with few and with many ready tasks. Two tasks
+//! pass the control back and forth through mailboxes for a fixed number of
+//! ticks, first with no other task ready and then with many lower priority
+//! tasks ready, and the round trips completed in each run are printed.
+//!
+//! Picking the next task takes constant time, so the crowded run should
+//! complete about as many round trips as the first one. When the ready queue
+//! was a single list scanned on every context switch, the crowded run
+//! completed fewer round trips the more tasks were ready. The counts depend
+//! on the host running QEMU, so the test only checks that both runs made
+//! progress.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::Mailbox,
+    task::{self, main},
+    time,
+};
+
+/// The number of ticks each run lasts.
+const RUN_TICKS: u32 = 50;
+
+/// The number of lower priority tasks kept ready during the crowded run,
+/// leaving room for the idle task, the main task, and the two measuring
+/// tasks.
+const CROWD: usize = config::MAX_TASK_NUMBER - 6;
+
+static PING: Mailbox = Mailbox::new();
+static PONG: Mailbox = Mailbox::new();
+static STOP: AtomicBool = AtomicBool::new(false);
+static ROUND_TRIPS: AtomicU32 = AtomicU32::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    // Run above the measuring tasks, so that they only start when the main
+    // task sleeps.
+    task::change_current_priority(config::DEFAULT_TASK_PRIORITY - 3).unwrap();
+
+    dbg_println!("alone: {}", run());
+
+    for _ in 0..CROWD {
+        task::build()
+            .set_entry(|| {})
+            .set_priority(config::DEFAULT_TASK_PRIORITY)
+            .spawn()
+            .unwrap();
+    }
+    dbg_println!("crowded: {}", run());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+/// Let the two measuring tasks run for [`RUN_TICKS`] and return the number
+/// of round trips they completed.
+fn run() -> u32 {
+    STOP.store(false, Ordering::SeqCst);
+    task::build()
+        .set_entry(partner)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 2)
+        .spawn()
+        .unwrap();
+    task::build()
+        .set_entry(measurer)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // The measuring tasks keep the CPU busy, so the lower priority tasks
+    // stay ready until they finish.
+    time::sleep_ms(RUN_TICKS + 20).unwrap();
+    ROUND_TRIPS.load(Ordering::SeqCst)
+}
+
+fn measurer() {
+    let start = time::get_tick();
+    let mut round_trips = 0;
+    while time::get_tick().wrapping_sub(start) < RUN_TICKS {
+        // Switch to the partner, which switches back after notifying.
+        PING.notify_allow_isr();
+        PONG.wait();
+        round_trips += 1;
+    }
+    ROUND_TRIPS.store(round_trips, Ordering::SeqCst);
+    STOP.store(true, Ordering::SeqCst);
+    PING.notify_allow_isr();
+}
+
+fn partner() {
+    loop {
+        PING.wait();
+        if STOP.load(Ordering::SeqCst) {
+            return;
+        }
+        PONG.notify_allow_isr();
+    }
+}
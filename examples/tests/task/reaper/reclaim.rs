@@ -0,0 +1,62 @@
+//! Tests that terminated tasks are handed to the reaper task instead of
+//! being freed by the context switch, and that the reaper task frees them
+//! once it runs.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::sync::Arc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main},
+    time,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    // The tasks preempt the main task and terminate right away. The reaper
+    // task has a lower priority than the main task, so it does not run yet.
+    for _ in 0..3 {
+        task::build()
+            .set_entry(|| {})
+            .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+            .spawn()
+            .unwrap();
+    }
+    dbg_println!("pending: {}", task::get_pending_reclamation_count());
+
+    time::sleep_ms(5).unwrap();
+    dbg_println!("pending: {}", task::get_pending_reclamation_count());
+
+    // The task struct of a restartable task keeps its entry closure, which
+    // holds the marker, until the struct is freed.
+    let marker = Arc::new(());
+    let captured = marker.clone();
+    task::build()
+        .set_entry(move || {
+            let _captured = &captured;
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn_restartable()
+        .unwrap();
+    dbg_println!(
+        "task struct kept: {}",
+        Arc::strong_count(&marker) == 2 && task::get_pending_reclamation_count() == 1
+    );
+
+    time::sleep_ms(5).unwrap();
+    dbg_println!(
+        "task struct freed: {}",
+        Arc::strong_count(&marker) == 1 && task::get_pending_reclamation_count() == 0
+    );
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,60 @@
+//! Tests that a stack reservation is counted until the reserving task uses
+//! it, and that a task cannot be spawned with a reservation exceeding the
+//! heap headroom.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    debug::{
+        segmented_stack,
+        semihosting::{self, dbg_println},
+    },
+    task::{self, main, TaskBuildError},
+};
+
+const RESERVE: usize = 4096;
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    dbg_println!("reserved: {}", segmented_stack::get_reserved_stack_size());
+
+    let too_large = segmented_stack::get_stack_headroom() + 1;
+    let result = task::build()
+        .set_entry(|| {})
+        .set_stack_reserve(too_large)
+        .spawn();
+    dbg_println!(
+        "too large: {}",
+        result == Err(TaskBuildError::NoStackReserve)
+    );
+
+    task::build()
+        .set_id(1)
+        .set_stack_init_size(512)
+        .set_stack_reserve(RESERVE)
+        .set_entry(critical)
+        .spawn()
+        .unwrap();
+}
+
+/// Use a stack frame too large to fit in the initial stacklet, and return
+/// the reserved size while running with it.
+#[inline(never)]
+fn large_frame(seed: u8) -> usize {
+    let buf = core::hint::black_box([seed; 2048]);
+    let reserved = segmented_stack::get_reserved_stack_size();
+    reserved + buf[2047] as usize
+}
+
+fn critical() {
+    dbg_println!("reserved: {}", segmented_stack::get_reserved_stack_size());
+
+    let reserved = large_frame(0);
+    dbg_println!("partly used: {}", reserved < RESERVE && reserved > 0);
+
+    dbg_println!("reserved: {}", segmented_stack::get_reserved_stack_size());
+
+    semihosting::terminate(true);
+}
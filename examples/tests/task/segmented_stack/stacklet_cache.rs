@@ -0,0 +1,60 @@
+//! Tests that a loop repeatedly extending the stack reuses the stacklet
+//! cached by the task, and that the cached stacklet is not counted as an
+//! active one.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    debug::{
+        segmented_stack,
+        semihosting::{self, dbg_println},
+    },
+    task,
+    task::main,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_id(1)
+        .set_stack_init_size(512)
+        .set_entry(looping)
+        .spawn()
+        .unwrap();
+}
+
+/// Use a stack frame too large to fit in the initial stacklet.
+#[inline(never)]
+fn large_frame(seed: u8) -> u8 {
+    let buf = core::hint::black_box([seed; 2048]);
+    buf[2047]
+}
+
+fn looping() {
+    let hits = segmented_stack::get_stacklet_cache_hit_count();
+    let misses = segmented_stack::get_stacklet_cache_miss_count();
+    let stacklets = segmented_stack::get_active_stacklet_count();
+
+    let mut sum = 0u32;
+    for i in 0..10 {
+        sum += large_frame(i) as u32;
+    }
+    dbg_println!("sum {}", sum);
+
+    dbg_println!(
+        "missed: {}",
+        segmented_stack::get_stacklet_cache_miss_count() > misses
+    );
+    dbg_println!(
+        "hit: {}",
+        segmented_stack::get_stacklet_cache_hit_count() > hits
+    );
+    dbg_println!(
+        "stacklets restored: {}",
+        segmented_stack::get_active_stacklet_count() == stacklets
+    );
+
+    semihosting::terminate(true);
+}
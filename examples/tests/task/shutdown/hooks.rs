@@ -0,0 +1,48 @@
+//! Tests that shutting down the system cancels the tokens, rejects new hooks,
+//! and runs the registered hooks in order. The last hook ends the test before
+//! the system is reset.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use hopter::{
+    debug::{
+        breadcrumb::RebootReason,
+        semihosting::{self, dbg_println},
+    },
+    power, task,
+    task::main,
+    time,
+};
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    power::register_hook(|| dbg_println!("hook 1")).unwrap();
+    power::register_hook(|| {
+        dbg_println!("hook 2");
+        semihosting::terminate(true);
+    })
+    .unwrap();
+
+    task::build()
+        .set_id(1)
+        .set_priority(1)
+        .set_entry(worker)
+        .spawn()
+        .unwrap();
+
+    // Let the worker block on its token.
+    time::sleep_ms(10).unwrap();
+
+    dbg_println!("shutting down");
+    hopter::shutdown(RebootReason::Requested);
+}
+
+fn worker() {
+    let token = power::token();
+    dbg_println!("task 1 cancelled: {}", token.is_cancelled());
+    token.wait();
+    dbg_println!("task 1 cancelled: {}", token.is_cancelled());
+    dbg_println!("{:?}", power::register_hook(|| {}));
+}
@@ -0,0 +1,125 @@
+//! Tests that the starvation monitor reports a task blocked on a mutex whose
+//! owner is blocked on a semaphore, listing the chain of lock owners, while
+//! tasks of lower priority keep running. The task is reported once per wait.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+use alloc::{format, string::String, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+use hopter::{
+    config::{self, tunable},
+    debug::{
+        log::{self, LogSink},
+        semihosting::{self, dbg_println},
+    },
+    sync::{Mutex, Semaphore},
+    task::{self, main},
+    time,
+};
+
+static LOCK: Mutex<()> = Mutex::new(());
+static BLOCKER: Semaphore = Semaphore::new(1, 0);
+static HIGH_RAN: AtomicBool = AtomicBool::new(false);
+
+/// The lines written by the logger task.
+static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct CollectSink {
+    line: String,
+}
+
+impl LogSink for CollectSink {
+    fn write(&mut self, data: &[u8]) {
+        for &byte in data {
+            if byte == b'\n' {
+                LINES.lock().push(core::mem::take(&mut self.line));
+            } else {
+                self.line.push(byte as char);
+            }
+        }
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    tunable::set_log_level(log::Level::Warn as u32);
+    log::start(CollectSink {
+        line: String::new(),
+    })
+    .unwrap();
+
+    // The low priority task takes the lock and blocks on the semaphore, so
+    // inheriting the priority of the high priority task does not help.
+    task::build()
+        .set_name("low")
+        .set_entry(|| {
+            let _guard = LOCK.lock();
+            BLOCKER.down();
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY + 1)
+        .spawn()
+        .unwrap();
+    time::sleep_ms(5).unwrap();
+
+    // The high priority task preempts the main task and blocks on the lock.
+    task::build()
+        .set_name("high")
+        .set_entry(|| {
+            let _guard = LOCK.lock();
+            HIGH_RAN.store(true, Ordering::SeqCst);
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    let high = format!("{}", task::find_by_name("high").unwrap());
+    let low = format!("{}", task::find_by_name("low").unwrap());
+
+    // The main task has a lower priority than the high priority task, and
+    // keeps running for several check periods past the threshold.
+    let waited = config::STARVATION_THRESHOLD_MS + config::STARVATION_CHECK_PERIOD_MS * 4;
+    for _ in 0..waited / 10 {
+        time::sleep_ms(10).unwrap();
+    }
+
+    BLOCKER.up();
+    time::sleep_ms(5).unwrap();
+    dbg_println!("high ran: {}", HIGH_RAN.load(Ordering::SeqCst));
+
+    // The lines look like: "[<tick>] [WARN] task <id>: starvation: task <id>
+    // waited <ms> ms while lower priority tasks ran; chain: <chain>".
+    let name_of = |id: &str| {
+        if id == high {
+            "high"
+        } else if id == low {
+            "low"
+        } else {
+            "other"
+        }
+    };
+    let lines = LINES.lock();
+    let mut reports = 0;
+    for line in lines.iter() {
+        let Some((_, report)) = line.split_once("starvation: task ") else {
+            continue;
+        };
+        reports += 1;
+        let (starved, _) = report.split_once(" waited ").unwrap();
+        dbg_println!("starved: {}", name_of(starved));
+        let (_, chain) = report.split_once("chain: ").unwrap();
+        let (ids, end) = chain.rsplit_once(' ').unwrap();
+        let names: Vec<&str> = ids.split(" -> ").map(name_of).collect();
+        dbg_println!("chain: {} {}", names.join(" -> "), end);
+    }
+    dbg_println!("reports: {}", reports);
+    drop(lines);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
@@ -0,0 +1,80 @@
+//! Tests that a task runs in static memory, that the memory cannot be used
+//! by a second task while the first one is alive or by a restartable task,
+//! that spawning fails if the task struct does not fit, and that the memory
+//! can be used again after the first task terminates.
+
+#![no_std]
+#![no_main]
+
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main, StaticTask, TaskBuildError},
+    time,
+};
+
+static TASK: StaticTask<4096> = StaticTask::new();
+static RESTARTABLE_TASK: StaticTask<4096> = StaticTask::new();
+static SMALL_TASK: StaticTask<4096, 64> = StaticTask::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build_static(&TASK)
+        .set_entry(|| {
+            dbg_println!("first sum: {}", sum(32));
+            time::sleep_ms(20).unwrap();
+        })
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    time::sleep_ms(5).unwrap();
+
+    let result = task::build_static(&TASK).set_entry(|| {}).spawn();
+    dbg_println!(
+        "in use: {}",
+        result == Err(TaskBuildError::StaticTaskUnavailable)
+    );
+
+    let result = task::build_static(&RESTARTABLE_TASK)
+        .set_entry(|| {})
+        .spawn_restartable();
+    dbg_println!(
+        "restartable rejected: {}",
+        result == Err(TaskBuildError::StaticTaskUnavailable)
+    );
+
+    let result = task::build_static(&SMALL_TASK).set_entry(|| {}).spawn();
+    dbg_println!(
+        "too small: {}",
+        result == Err(TaskBuildError::StaticTaskTooSmall)
+    );
+
+    time::sleep_ms(40).unwrap();
+
+    let n = 16;
+    task::build_static(&TASK)
+        .set_entry(move || dbg_println!("second sum: {}", sum(n)))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    time::sleep_ms(10).unwrap();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+#[inline(never)]
+fn sum(n: u32) -> u32 {
+    if n == 0 {
+        0
+    } else {
+        n + sum(n - 1)
+    }
+}
@@ -0,0 +1,76 @@
+//! Tests that a one-for-all supervisor restarts all members when one of them
+//! panics, and that it terminates the members and panics itself when the
+//! members are restarted more than the maximum number of times.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main, RestartStrategy, Supervisor},
+    time,
+};
+
+static FLAKY_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        dbg_println!("steady dropped");
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    let supervisor = Supervisor::new(RestartStrategy::OneForAll)
+        .max_restarts(1)
+        .add(
+            task::build()
+                .set_name("steady")
+                .set_entry(steady)
+                .set_priority(config::DEFAULT_TASK_PRIORITY - 1),
+        )
+        .add(
+            task::build()
+                .set_name("flaky")
+                .set_entry(flaky)
+                .set_priority(config::DEFAULT_TASK_PRIORITY - 1),
+        );
+
+    // The supervisor has a higher priority than the members, so that it
+    // reacts to their exits immediately.
+    let handle = task::build()
+        .set_name("supervisor")
+        .set_entry(supervisor.into_entry())
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 2)
+        .spawn_joinable()
+        .unwrap();
+
+    dbg_println!("supervisor panicked: {}", handle.join().is_err());
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn steady() {
+    let _guard = Guard;
+    dbg_println!("steady started");
+    loop {
+        time::sleep_ms(10).unwrap();
+    }
+}
+
+fn flaky() {
+    let run = FLAKY_RUNS.fetch_add(1, Ordering::SeqCst) + 1;
+    dbg_println!("flaky run {}", run);
+    time::sleep_ms(5).unwrap();
+    panic!("flaky failed");
+}
@@ -0,0 +1,67 @@
+//! Tests that two tasks panicking nearly simultaneously are unwound
+//! independently. The second task panics while the first one is still
+//! running a drop handler during its unwinding, and the two unwinding
+//! processes interleave without corrupting each other.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    sync::Mailbox,
+    task,
+    task::main,
+    time,
+};
+
+/// Notified when a drop handler run during unwinding completes.
+static DROPPED: Mailbox = Mailbox::new();
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(|| will_panic("First", 20))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+    task::build()
+        .set_entry(|| will_panic("Second", 5))
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn()
+        .unwrap();
+
+    // Wait until the drop handlers of both tasks complete.
+    DROPPED.wait();
+    DROPPED.wait();
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn will_panic(name: &'static str, sleep_ms: u32) {
+    let _sleep_on_drop = SleepOnDrop { name, sleep_ms };
+    panic!()
+}
+
+/// Sleep in the drop handler, so that the unwinding of the other task
+/// proceeds in between.
+struct SleepOnDrop {
+    name: &'static str,
+    sleep_ms: u32,
+}
+
+impl Drop for SleepOnDrop {
+    fn drop(&mut self) {
+        dbg_println!("{} task dropping", self.name);
+        time::sleep_ms(self.sleep_ms).unwrap();
+        dbg_println!("{} task dropped", self.name);
+        DROPPED.notify_allow_isr();
+    }
+}
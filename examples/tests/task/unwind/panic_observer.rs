@@ -0,0 +1,60 @@
+//! Tests that the panic observer is called with the panic message after a
+//! panicked task is unwound, and that the restart of the task follows the
+//! decision of the observer.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task,
+    task::{main, PanicRecord, TaskInfo},
+};
+
+static RUNS: AtomicU32 = AtomicU32::new(0);
+static CRASHES: AtomicU32 = AtomicU32::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::set_panic_observer(observe);
+
+    task::build()
+        .set_name("flaky")
+        .set_entry(always_panic)
+        .spawn_restartable()
+        .unwrap();
+
+    // Let the test task and its unwinding complete first.
+    task::change_current_priority(config::UNWIND_PRIORITY + 1).unwrap();
+
+    dbg_println!("Runs: {}", RUNS.load(Ordering::SeqCst));
+    assert_eq!(task::find_by_name("flaky"), None);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn always_panic() {
+    RUNS.fetch_add(1, Ordering::SeqCst);
+    panic!("boom")
+}
+
+/// Allow one restart.
+fn observe(info: &TaskInfo, record: &PanicRecord) -> bool {
+    let crashes = CRASHES.fetch_add(1, Ordering::SeqCst) + 1;
+    dbg_println!(
+        "Crash {} of {}: {}",
+        crashes,
+        info.name().unwrap(),
+        record.text().ends_with("boom")
+    );
+    crashes < 2
+}
@@ -0,0 +1,58 @@
+//! Tests that a restartable task is restarted after the configured delay,
+//! which doubles on each further restart up to the configured maximum.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task,
+    task::main,
+    time,
+};
+
+static RUNS: AtomicU32 = AtomicU32::new(0);
+static LAST_PANIC_TICK: AtomicU32 = AtomicU32::new(0);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(flaky)
+        .restart_delay_ms(10)
+        .restart_backoff(25)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn_restartable()
+        .unwrap();
+
+    time::sleep_ms(200).unwrap();
+    assert_eq!(RUNS.load(Ordering::SeqCst), 4);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn flaky() {
+    // The expected delay before each restart.
+    const DELAYS: [u32; 3] = [10, 20, 25];
+
+    let run = RUNS.fetch_add(1, Ordering::SeqCst);
+    if run > 0 {
+        let elapsed = time::get_tick() - LAST_PANIC_TICK.load(Ordering::SeqCst);
+        assert!(elapsed >= DELAYS[run as usize - 1]);
+    }
+    dbg_println!("Run {}", run + 1);
+
+    // Succeed on the fourth run.
+    if run < 3 {
+        LAST_PANIC_TICK.store(time::get_tick(), Ordering::SeqCst);
+        panic!()
+    }
+}
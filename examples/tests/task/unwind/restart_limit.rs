@@ -0,0 +1,55 @@
+//! Tests that a restartable task panicking repeatedly is restarted at most
+//! the configured number of times, after which the callback is run and the
+//! task terminates.
+
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task,
+    task::{main, TaskId},
+};
+
+static RUNS: AtomicU32 = AtomicU32::new(0);
+static EXHAUSTED_ID: AtomicU32 = AtomicU32::new(u32::MAX);
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_name("flaky")
+        .set_entry(always_panic)
+        .max_restarts(2)
+        .on_restart_exhausted(on_exhausted)
+        .spawn_restartable()
+        .unwrap();
+    let id = task::find_by_name("flaky").unwrap();
+
+    // Let the test task and its unwinding complete first.
+    task::change_current_priority(config::UNWIND_PRIORITY + 1).unwrap();
+
+    dbg_println!("Runs: {}", RUNS.load(Ordering::SeqCst));
+    assert_eq!(EXHAUSTED_ID.load(Ordering::SeqCst), id.get());
+    assert_eq!(task::find_by_name("flaky"), None);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn always_panic() {
+    RUNS.fetch_add(1, Ordering::SeqCst);
+    panic!()
+}
+
+fn on_exhausted(id: TaskId) {
+    EXHAUSTED_ID.store(id.get(), Ordering::SeqCst);
+    dbg_println!("Restart exhausted");
+}
@@ -0,0 +1,75 @@
+//! Tests that a task feeding the watchdog in time is left running, that a
+//! task missing its deadline while sleeping is unwound and restarted, and
+//! that an unregistered task is no longer watched. The restarted instance
+//! runs concurrently with the unwinding of the stalled one.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+use hopter::{
+    config,
+    debug::semihosting::{self, dbg_println},
+    task::{self, main, WatchdogAction},
+    time,
+};
+
+const PERIOD_MS: u32 = 20;
+
+static RUNS: AtomicU32 = AtomicU32::new(0);
+
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        dbg_println!("guard dropped");
+    }
+}
+
+#[main]
+fn main(_: cortex_m::Peripherals) {
+    task::build()
+        .set_entry(worker)
+        .set_priority(config::DEFAULT_TASK_PRIORITY - 1)
+        .spawn_restartable()
+        .unwrap();
+
+    time::sleep_ms(200).unwrap();
+    assert_eq!(RUNS.load(Ordering::SeqCst), 2);
+
+    #[cfg(feature = "qemu")]
+    semihosting::terminate(true);
+    #[cfg(not(feature = "qemu"))]
+    {
+        dbg_println!("test complete!");
+        loop {}
+    }
+}
+
+fn worker() {
+    let run = RUNS.fetch_add(1, Ordering::SeqCst) + 1;
+    dbg_println!("run {}", run);
+
+    task::watchdog_register(PERIOD_MS, WatchdogAction::Restart);
+
+    if run > 1 {
+        task::watchdog_unregister();
+        time::sleep_ms(2 * PERIOD_MS).unwrap();
+        dbg_println!("unregistered task not unwound");
+        return;
+    }
+
+    let _guard = Guard;
+
+    // Feeding keeps the task alive for longer than a period.
+    for _ in 0..5 {
+        time::sleep_ms(PERIOD_MS / 2).unwrap();
+        task::watchdog_feed();
+    }
+    dbg_println!("fed task alive");
+
+    // Stall without feeding.
+    loop {
+        time::sleep_ms(1000).unwrap();
+    }
+}
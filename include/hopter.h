@@ -0,0 +1,128 @@
+#ifndef HOPTER_H
+#define HOPTER_H
+
+/* Generated by cbindgen from src/compat/ffi.rs. Do not edit manually. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// The operation succeeded.
+#define HOPTER_OK 0
+
+// An argument is invalid, e.g., a null pointer.
+#define HOPTER_ERR_PARAM -1
+
+// The function is not allowed in ISR context.
+#define HOPTER_ERR_ISR -2
+
+// The resource is not available and the timeout is zero.
+#define HOPTER_ERR_WOULD_BLOCK -3
+
+// The resource did not become available within the timeout.
+#define HOPTER_ERR_TIMEOUT -4
+
+// The maximum number of tasks has been reached.
+#define HOPTER_ERR_NO_MORE_TASK -5
+
+// The task priority is not allowed by the configuration.
+#define HOPTER_ERR_PRIORITY -6
+
+// Block until the operation can complete.
+#define HOPTER_WAIT_FOREVER 4294967295
+
+// Log level for errors.
+#define HOPTER_LOG_ERROR 1
+
+// Log level for warnings.
+#define HOPTER_LOG_WARN 2
+
+// Log level for informational messages.
+#define HOPTER_LOG_INFO 3
+
+// Log level for debugging messages.
+#define HOPTER_LOG_DEBUG 4
+
+// Opaque handle of a channel carrying fixed-size items.
+typedef struct HopterChannel HopterChannel;
+
+// Opaque handle of a mutex.
+typedef struct HopterMutex HopterMutex;
+
+// Opaque handle of a semaphore.
+typedef struct HopterSemaphore HopterSemaphore;
+
+// The entry function of a task spawned from C.
+typedef void (*HopterTaskEntry)(void *arg);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Spawn a task running `entry(arg)`. A `stack_size` of zero selects the
+// default initial stack size.
+int32_t hopter_task_spawn(HopterTaskEntry entry,
+                          void *arg,
+                          uint8_t id,
+                          uint8_t priority,
+                          size_t stack_size);
+
+void hopter_task_yield(void);
+
+uint8_t hopter_task_current_id(void);
+
+int32_t hopter_sleep_ms(uint32_t ms);
+
+uint32_t hopter_get_tick(void);
+
+// Create a mutex with priority inheritance. Return null in ISR context.
+HopterMutex *hopter_mutex_new(void);
+
+int32_t hopter_mutex_lock(HopterMutex *mutex);
+
+int32_t hopter_mutex_try_lock(HopterMutex *mutex);
+
+// Unlock the mutex. Must be called by the task that locked it.
+int32_t hopter_mutex_unlock(HopterMutex *mutex);
+
+// Delete the mutex. It must not be locked.
+int32_t hopter_mutex_delete(HopterMutex *mutex);
+
+// Create a semaphore. Return null in ISR context or if the counts are
+// invalid.
+HopterSemaphore *hopter_semaphore_new(uint32_t max_count, uint32_t init_count);
+
+// Increment the semaphore. In ISR context, the timeout must be zero.
+int32_t hopter_semaphore_up(HopterSemaphore *sem, uint32_t timeout_ms);
+
+// Decrement the semaphore. In ISR context, the timeout must be zero.
+int32_t hopter_semaphore_down(HopterSemaphore *sem, uint32_t timeout_ms);
+
+uint32_t hopter_semaphore_count(HopterSemaphore *sem);
+
+int32_t hopter_semaphore_delete(HopterSemaphore *sem);
+
+// Create a channel holding up to `capacity` items of `item_size` bytes.
+// Return null in ISR context or if either argument is zero.
+HopterChannel *hopter_channel_new(uint32_t capacity, uint32_t item_size);
+
+// Copy an item from `item` into the channel. Not allowed in ISR context,
+// because memory cannot be allocated there.
+int32_t hopter_channel_send(HopterChannel *chan, const void *item, uint32_t timeout_ms);
+
+// Copy an item out of the channel into `item`. Not allowed in ISR context,
+// because memory cannot be freed there.
+int32_t hopter_channel_recv(HopterChannel *chan, void *item, uint32_t timeout_ms);
+
+uint32_t hopter_channel_len(HopterChannel *chan);
+
+int32_t hopter_channel_delete(HopterChannel *chan);
+
+// Print the null-terminated message if `level` does not exceed the tunable
+// log level.
+void hopter_log(uint32_t level, const char *msg);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* HOPTER_H */
@@ -2,8 +2,27 @@ import os
 import sys
 import pathlib
 import subprocess
+import tomllib
 from tqdm import tqdm
 
+def required_features():
+    # Map each test example to the features it requires beyond `qemu`.
+    repo_path = pathlib.Path(__file__).parent.resolve()
+    with open(os.path.join(repo_path, 'Cargo.toml'), 'rb') as f:
+        manifest = tomllib.load(f)
+    return {
+        example['name']: example.get('required-features', [])
+        for example in manifest.get('example', [])
+    }
+
+def test_env():
+    # Map each test example to the environment variables overriding
+    # configuration parameters when it is built.
+    repo_path = pathlib.Path(__file__).parent.resolve()
+    with open(os.path.join(repo_path, 'Cargo.toml'), 'rb') as f:
+        manifest = tomllib.load(f)
+    return manifest['package'].get('metadata', {}).get('test-env', {})
+
 def enumerate_tests():
     repo_path = pathlib.Path(__file__).parent.resolve()
     all_tests_path = os.path.join(repo_path, 'examples/tests')
@@ -48,15 +67,21 @@ def enumerate_tests():
 def main():
     # Get all test cases under ./examples/tests/
     tests = enumerate_tests()
+    features = required_features()
+    envs = test_env()
 
     for (category, subcategory, file_no_ext), answer in tqdm(tests):
+        name = f'test-{category}-{subcategory}-{file_no_ext}'
+        extra_features = ','.join(features.get(name, []))
+        env = {**os.environ, **envs.get(name, {})}
         # Build the test case with `cargo build --example`
         run_result = subprocess.run([
             'cargo', 'build', '--release',
             '--features', 'stm32f405',
             '--features', 'qemu',
-            '--example', f'test-{category}-{subcategory}-{file_no_ext}'
-        ], capture_output=True)
+            *(['--features', extra_features] if extra_features else []),
+            '--example', name
+        ], capture_output=True, env=env)
 
         # Error handling for build error.
         if run_result.returncode != 0:
@@ -76,8 +101,9 @@ def main():
             'cargo', 'run', '--release',
             '--features', 'stm32f405',
             '--features', 'qemu',
-            '--example', f'test-{category}-{subcategory}-{file_no_ext}'
-        ], capture_output=True)
+            *(['--features', extra_features] if extra_features else []),
+            '--example', name
+        ], capture_output=True, env=env)
 
         # If the test execution returns an error, report the error.
         if run_result.returncode != 0:
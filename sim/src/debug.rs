@@ -0,0 +1,31 @@
+//! Debug output, mirroring `hopter::debug`.
+
+pub mod semihosting {
+    //! Semihosting output printed to the standard output of the host.
+
+    #[doc(inline)]
+    pub use crate::__dbg_print as dbg_print;
+    #[doc(inline)]
+    pub use crate::__dbg_println as dbg_println;
+
+    /// End the simulation with a status. Exits the host process.
+    pub fn terminate(success: bool) -> ! {
+        std::process::exit(if success { 0 } else { 1 })
+    }
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __dbg_println {
+    ($($arg:tt)*) => {
+        ::std::println!($($arg)*)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __dbg_print {
+    ($($arg:tt)*) => {
+        ::std::print!($($arg)*)
+    };
+}
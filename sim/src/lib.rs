@@ -0,0 +1,40 @@
+//! A host-side simulation of the Hopter API.
+//!
+//! This crate mirrors the task, time, and synchronization API of Hopter on
+//! top of `std`, so that application logic can be unit-tested on a
+//! development machine without QEMU or hardware. Code written against Hopter
+//! can usually be compiled against this crate by renaming it in tests:
+//!
+//! ```rust
+//! use hopter_sim as hopter;
+//! use hopter::{sync::Mailbox, task, time};
+//!
+//! static DONE: Mailbox = Mailbox::new();
+//!
+//! task::build()
+//!     .set_entry(|| {
+//!         time::sleep_ms(10).unwrap();
+//!         DONE.notify_allow_isr();
+//!     })
+//!     .spawn()
+//!     .unwrap();
+//! DONE.wait();
+//! ```
+//!
+//! Each task is emulated by a thread. Task priorities are recorded but not
+//! enforced, so tests must not rely on priority scheduling. Restartable
+//! tasks are restarted after a panic just like on the target.
+//!
+//! Time is measured by a virtual tick. By default the tick advances once
+//! every real millisecond. Calling [`sim::set_realtime(false)`](sim::set_realtime)
+//! freezes the tick, after which it only advances with
+//! [`sim::advance_ms`], making timeouts deterministic.
+
+// The API mirrors the kernel, which returns `Result<(), ()>` in places.
+#![allow(clippy::result_unit_err)]
+
+pub mod debug;
+pub mod sim;
+pub mod sync;
+pub mod task;
+pub mod time;
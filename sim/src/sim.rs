@@ -0,0 +1,99 @@
+//! Control of the simulation.
+//!
+//! All simulated kernel objects share a single lock and a single condition
+//! variable. Every state change, including an advance of the virtual tick,
+//! wakes up all blocked threads to re-check what they are waiting for. This
+//! is slow but simple, and makes every blocking operation with a timeout
+//! follow the virtual tick.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Condvar, Mutex, MutexGuard, Once,
+    },
+    thread,
+    time::Duration,
+};
+
+/// The lock held while changing the state of any simulated kernel object.
+static KERNEL: Mutex<()> = Mutex::new(());
+
+/// Notified after every state change.
+static EVENT: Condvar = Condvar::new();
+
+/// The virtual tick.
+static TICK: AtomicU32 = AtomicU32::new(0);
+
+/// Set if the tick follows the real time.
+static REALTIME: AtomicBool = AtomicBool::new(true);
+
+static TICKER: Once = Once::new();
+
+fn lock_kernel() -> MutexGuard<'static, ()> {
+    // A panicking task must not bring down the others.
+    KERNEL.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Start the thread advancing the tick in real time.
+fn start_ticker() {
+    TICKER.call_once(|| {
+        thread::spawn(|| loop {
+            thread::sleep(Duration::from_millis(1));
+            if REALTIME.load(Ordering::SeqCst) {
+                advance_ms(1);
+            }
+        });
+    });
+}
+
+/// Choose if the virtual tick advances once every real millisecond, which is
+/// the default. When disabled, the tick only advances with [`advance_ms`].
+pub fn set_realtime(realtime: bool) {
+    REALTIME.store(realtime, Ordering::SeqCst);
+    if realtime {
+        start_ticker();
+    }
+}
+
+/// Advance the virtual tick and wake up the tasks whose sleep or timeout
+/// expires.
+pub fn advance_ms(ms: u32) {
+    let _guard = lock_kernel();
+    TICK.fetch_add(ms, Ordering::SeqCst);
+    EVENT.notify_all();
+}
+
+pub(crate) fn tick() -> u32 {
+    if REALTIME.load(Ordering::SeqCst) {
+        start_ticker();
+    }
+    TICK.load(Ordering::SeqCst)
+}
+
+/// Change kernel object state with `op` and wake up all blocked threads.
+pub(crate) fn update<R>(op: impl FnOnce() -> R) -> R {
+    let _guard = lock_kernel();
+    let res = op();
+    EVENT.notify_all();
+    res
+}
+
+/// Block until `op` succeeds or, if given, the timeout in ticks expires.
+/// `op` runs with the kernel lock held and all blocked threads are woken up
+/// after it succeeds. Return `None` on timeout.
+pub(crate) fn block_on<R>(timeout_ms: Option<u32>, mut op: impl FnMut() -> Option<R>) -> Option<R> {
+    let deadline = timeout_ms.map(|ms| tick().wrapping_add(ms));
+    let mut guard = lock_kernel();
+    loop {
+        if let Some(res) = op() {
+            EVENT.notify_all();
+            return Some(res);
+        }
+        if let Some(deadline) = deadline {
+            if (TICK.load(Ordering::SeqCst).wrapping_sub(deadline) as i32) >= 0 {
+                return None;
+            }
+        }
+        guard = EVENT.wait(guard).unwrap_or_else(|err| err.into_inner());
+    }
+}
@@ -0,0 +1,276 @@
+//! Synchronization primitives, mirroring `hopter::sync`.
+//!
+//! The `_allow_isr` methods never block, as on the target. There are no
+//! interrupts in the simulation, but tests can call them from any thread to
+//! emulate an ISR.
+
+use crate::sim;
+use std::{
+    collections::VecDeque,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// A counting notification primitive that a single task waits on.
+pub struct Mailbox {
+    count: AtomicUsize,
+}
+
+impl Mailbox {
+    /// Create a new mailbox.
+    pub const fn new() -> Self {
+        Self {
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    fn try_take(&self) -> Option<()> {
+        let count = self.count.load(Ordering::SeqCst);
+        if count == 0 {
+            return None;
+        }
+        self.count.store(count - 1, Ordering::SeqCst);
+        Some(())
+    }
+
+    /// Block until notified.
+    pub fn wait(&self) {
+        sim::block_on(None, || self.try_take());
+    }
+
+    /// Block until notified or the timeout expires. Return `true` if
+    /// notified, or `false` on timeout.
+    pub fn wait_until_timeout(&self, timeout_ms: u32) -> bool {
+        sim::block_on(Some(timeout_ms), || self.try_take()).is_some()
+    }
+
+    /// Notify the mailbox.
+    pub fn notify_allow_isr(&self) {
+        sim::update(|| self.count.fetch_add(1, Ordering::SeqCst));
+    }
+}
+
+impl Default for Mailbox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A counting semaphore.
+pub struct Semaphore {
+    count: AtomicUsize,
+    max_count: usize,
+}
+
+impl Semaphore {
+    /// Create a new semaphore with the maximum and the initial count.
+    pub const fn new(max_count: usize, init_count: usize) -> Self {
+        Self {
+            count: AtomicUsize::new(init_count),
+            max_count,
+        }
+    }
+
+    /// The current count.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// The maximum count.
+    pub const fn max_count(&self) -> usize {
+        self.max_count
+    }
+
+    fn try_up(&self) -> Option<()> {
+        let count = self.count.load(Ordering::SeqCst);
+        if count == self.max_count {
+            return None;
+        }
+        self.count.store(count + 1, Ordering::SeqCst);
+        Some(())
+    }
+
+    fn try_down(&self) -> Option<()> {
+        let count = self.count.load(Ordering::SeqCst);
+        if count == 0 {
+            return None;
+        }
+        self.count.store(count - 1, Ordering::SeqCst);
+        Some(())
+    }
+
+    /// Increment the count, blocking while it is at the maximum.
+    pub fn up(&self) {
+        sim::block_on(None, || self.try_up());
+    }
+
+    /// Increment the count if it is below the maximum.
+    pub fn try_up_allow_isr(&self) -> Result<(), ()> {
+        sim::update(|| self.try_up()).ok_or(())
+    }
+
+    /// Decrement the count, blocking while it is zero.
+    pub fn down(&self) {
+        sim::block_on(None, || self.try_down());
+    }
+
+    /// Decrement the count if it is positive.
+    pub fn try_down_allow_isr(&self) -> Result<(), ()> {
+        sim::update(|| self.try_down()).ok_or(())
+    }
+}
+
+/// A mutual exclusion lock. A panic while holding the lock does not poison
+/// it.
+pub struct Mutex<T> {
+    inner: std::sync::Mutex<T>,
+}
+
+/// The guard of a locked [`Mutex`].
+pub struct MutexGuard<'a, T> {
+    inner: std::sync::MutexGuard<'a, T>,
+}
+
+impl<T> Mutex<T> {
+    /// Create a new mutex.
+    pub const fn new(data: T) -> Self {
+        Self {
+            inner: std::sync::Mutex::new(data),
+        }
+    }
+
+    /// Acquire the lock, blocking until it is available.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        MutexGuard {
+            inner: self.inner.lock().unwrap_or_else(|err| err.into_inner()),
+        }
+    }
+
+    /// Acquire the lock if it is available.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        match self.inner.try_lock() {
+            Ok(inner) => Some(MutexGuard { inner }),
+            Err(std::sync::TryLockError::Poisoned(err)) => Some(MutexGuard {
+                inner: err.into_inner(),
+            }),
+            Err(std::sync::TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// Consume the mutex and return the protected data.
+    pub fn into_inner(self) -> T {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+/// A multi-producer multi-consumer channel.
+struct Channel<T, const N: usize> {
+    buffer: std::sync::Mutex<VecDeque<T>>,
+}
+
+impl<T, const N: usize> Channel<T, N> {
+    fn buffer(&self) -> std::sync::MutexGuard<'_, VecDeque<T>> {
+        self.buffer.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn try_push(&self, data: &mut Option<T>) -> Option<()> {
+        let mut buffer = self.buffer();
+        if buffer.len() == N {
+            return None;
+        }
+        buffer.push_back(data.take()?);
+        Some(())
+    }
+
+    fn try_pop(&self) -> Option<T> {
+        self.buffer().pop_front()
+    }
+}
+
+/// A producer of a channel. It can be cloned.
+pub struct Producer<T, const N: usize> {
+    channel: Arc<Channel<T, N>>,
+}
+
+/// The consumer of a channel. It can be cloned.
+pub struct Consumer<T, const N: usize> {
+    channel: Arc<Channel<T, N>>,
+}
+
+impl<T, const N: usize> Clone for Producer<T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T, const N: usize> Clone for Consumer<T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T, const N: usize> Producer<T, N> {
+    /// Push an element into the channel, blocking while it is full.
+    pub fn produce(&self, data: T) {
+        let mut data = Some(data);
+        sim::block_on(None, || self.channel.try_push(&mut data));
+    }
+
+    /// Push an element into the channel. If the channel is full, return the
+    /// element with `Err`.
+    pub fn try_produce_allow_isr(&self, data: T) -> Result<(), T> {
+        let mut data = Some(data);
+        match sim::update(|| self.channel.try_push(&mut data)) {
+            Some(()) => Ok(()),
+            None => Err(data.take().unwrap()),
+        }
+    }
+}
+
+impl<T, const N: usize> Consumer<T, N> {
+    /// Pop an element from the channel, blocking while it is empty.
+    pub fn consume(&self) -> T {
+        sim::block_on(None, || self.channel.try_pop()).unwrap()
+    }
+
+    /// Pop an element from the channel if there is one.
+    pub fn try_consume_allow_isr(&self) -> Option<T> {
+        sim::update(|| self.channel.try_pop())
+    }
+}
+
+/// Create a channel with the given buffering capacity.
+pub fn create_channel<T, const N: usize>() -> (Producer<T, N>, Consumer<T, N>) {
+    let channel = Arc::new(Channel {
+        buffer: std::sync::Mutex::new(VecDeque::with_capacity(N)),
+    });
+    (
+        Producer {
+            channel: channel.clone(),
+        },
+        Consumer { channel },
+    )
+}
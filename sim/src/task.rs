@@ -0,0 +1,158 @@
+//! Tasks emulated by threads, mirroring `hopter::task`.
+
+use std::{
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+    thread,
+};
+
+/// The ID of tasks without an explicitly set ID, and of the thread running
+/// the test.
+pub const DEFAULT_TASK_ID: u8 = 0;
+
+/// The priority of tasks without an explicitly set priority.
+pub const DEFAULT_TASK_PRIORITY: u8 = 8;
+
+/// The number of allowed priority levels.
+pub const TASK_PRIORITY_LEVELS: u8 = 16;
+
+thread_local! {
+    static CURRENT_ID: Cell<u8> = const { Cell::new(DEFAULT_TASK_ID) };
+}
+
+/// Enumeration of errors during task creation.
+#[derive(Debug, PartialEq)]
+pub enum TaskBuildError {
+    /// No entry closure is set for the task.
+    NoEntry,
+    /// The priority level is not an allowed value.
+    PriorityNotAllowed,
+    /// The host failed to create a thread.
+    NoMoreTask,
+}
+
+/// Supporting the builder pattern to create a new task.
+pub struct TaskBuilder<F>
+where
+    F: FnOnce() + Send + 'static,
+{
+    entry_closure: Option<F>,
+    priority: Option<u8>,
+    id: Option<u8>,
+}
+
+/// Build a new task with the task builder.
+pub fn build<F>() -> TaskBuilder<F>
+where
+    F: FnOnce() + Send + 'static,
+{
+    TaskBuilder {
+        entry_closure: None,
+        priority: None,
+        id: None,
+    }
+}
+
+impl<F> TaskBuilder<F>
+where
+    F: FnOnce() + Send + 'static,
+{
+    /// Set a numerical ID for the task.
+    pub fn set_id(mut self, id: u8) -> Self {
+        self.id.replace(id);
+        self
+    }
+
+    /// Set the priority to a task. The priority is validated but does not
+    /// affect scheduling.
+    pub fn set_priority(mut self, prio: u8) -> Self {
+        self.priority.replace(prio);
+        self
+    }
+
+    /// Accepted for compatibility and ignored.
+    pub fn set_stack_limit(self, _limit: usize) -> Self {
+        self
+    }
+
+    /// Accepted for compatibility and ignored.
+    pub fn set_stack_init_size(self, _size: usize) -> Self {
+        self
+    }
+
+    /// Accepted for compatibility and ignored.
+    pub fn disable_dynamic_stack(self) -> Self {
+        self
+    }
+
+    /// Set the entry closure for the task.
+    pub fn set_entry(mut self, closure: F) -> Self {
+        self.entry_closure.replace(closure);
+        self
+    }
+
+    fn prepare(&mut self) -> Result<(F, u8), TaskBuildError> {
+        let entry = self.entry_closure.take().ok_or(TaskBuildError::NoEntry)?;
+        let prio = self.priority.unwrap_or(DEFAULT_TASK_PRIORITY);
+        if prio >= TASK_PRIORITY_LEVELS - 1 {
+            return Err(TaskBuildError::PriorityNotAllowed);
+        }
+        Ok((entry, self.id.unwrap_or(DEFAULT_TASK_ID)))
+    }
+
+    /// Start the task in a new thread. If the task panics, it ends without
+    /// affecting other tasks.
+    pub fn spawn(mut self) -> Result<(), TaskBuildError> {
+        let (entry, id) = self.prepare()?;
+        spawn_thread(id, move || {
+            let _ = panic::catch_unwind(AssertUnwindSafe(entry));
+        })
+    }
+}
+
+impl<F> TaskBuilder<F>
+where
+    F: FnOnce() + Send + Sync + Clone + 'static,
+{
+    /// Start the task in a new thread. If the task panics, it is restarted
+    /// from the entry closure.
+    pub fn spawn_restartable(mut self) -> Result<(), TaskBuildError> {
+        let (entry, id) = self.prepare()?;
+        spawn_thread(id, move || {
+            while panic::catch_unwind(AssertUnwindSafe(entry.clone())).is_err() {}
+        })
+    }
+}
+
+fn spawn_thread<F>(id: u8, body: F) -> Result<(), TaskBuildError>
+where
+    F: FnOnce() + Send + 'static,
+{
+    thread::Builder::new()
+        .name(format!("task {}", id))
+        .spawn(move || {
+            CURRENT_ID.with(|cur| cur.set(id));
+            body();
+        })
+        .map(drop)
+        .map_err(|_| TaskBuildError::NoMoreTask)
+}
+
+/// Return the ID of the current task.
+pub fn get_current_id() -> u8 {
+    CURRENT_ID.with(|cur| cur.get())
+}
+
+/// Change the priority of the current task. The priority is validated but
+/// does not affect scheduling.
+pub fn change_current_priority(prio: u8) -> Result<(), ()> {
+    if prio >= TASK_PRIORITY_LEVELS - 1 {
+        return Err(());
+    }
+    Ok(())
+}
+
+/// Yield the current task to others.
+pub fn yield_current() {
+    thread::yield_now();
+}
@@ -0,0 +1,55 @@
+//! Virtual time, mirroring `hopter::time`.
+
+use crate::sim;
+
+/// Enumeration of errors of sleeping.
+#[derive(Debug, PartialEq)]
+pub enum SleepError {
+    /// The given time to sleep is too long.
+    TooLong,
+}
+
+/// Return the virtual tick counter. The counter gets incremented by 1 every
+/// simulated millisecond, and it wraps around `u32::MAX`.
+pub fn get_tick() -> u32 {
+    sim::tick()
+}
+
+/// Block the task for the given number of simulated milliseconds.
+pub fn sleep_ms(ms: u32) -> Result<(), SleepError> {
+    if ms > i32::MAX as u32 {
+        return Err(SleepError::TooLong);
+    }
+    sim::block_on::<()>(Some(ms), || None);
+    Ok(())
+}
+
+/// Unblock a task periodically, mirroring `hopter::time::IntervalBarrier`.
+pub struct IntervalBarrier {
+    interval_ms: u32,
+    next_tick_to_wake: u32,
+}
+
+impl IntervalBarrier {
+    /// Create a new barrier that will unblock the task at the given interval
+    /// in milliseconds.
+    pub fn new(interval_ms: u32) -> Result<Self, SleepError> {
+        if interval_ms > i32::MAX as u32 {
+            return Err(SleepError::TooLong);
+        }
+        Ok(Self {
+            interval_ms,
+            next_tick_to_wake: get_tick().wrapping_add(interval_ms),
+        })
+    }
+
+    /// Block the current task until the interval is elapsed since the last
+    /// time the task was resumed.
+    pub fn wait(&mut self) {
+        let remaining = self.next_tick_to_wake.wrapping_sub(get_tick());
+        if (remaining as i32) > 0 {
+            let _ = sleep_ms(remaining);
+        }
+        self.next_tick_to_wake = self.next_tick_to_wake.wrapping_add(self.interval_ms);
+    }
+}
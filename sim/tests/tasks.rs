@@ -0,0 +1,49 @@
+use hopter_sim::{
+    sync::{create_channel, Mailbox},
+    task,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[test]
+fn channel_between_tasks() {
+    let (producer, consumer) = create_channel::<u32, 2>();
+    task::build()
+        .set_id(3)
+        .set_entry(move || {
+            assert_eq!(task::get_current_id(), 3);
+            for i in 0..10 {
+                producer.produce(i);
+            }
+        })
+        .spawn()
+        .unwrap();
+
+    let received: Vec<u32> = (0..10).map(|_| consumer.consume()).collect();
+    assert_eq!(received, (0..10).collect::<Vec<_>>());
+    assert_eq!(consumer.try_consume_allow_isr(), None);
+}
+
+#[test]
+fn restartable_task_restarts_after_panic() {
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    static DONE: Mailbox = Mailbox::new();
+
+    task::build()
+        .set_entry(|| {
+            if RUNS.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("first run");
+            }
+            DONE.notify_allow_isr();
+        })
+        .spawn_restartable()
+        .unwrap();
+
+    DONE.wait();
+    assert_eq!(RUNS.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn invalid_priority_is_rejected() {
+    let res = task::build().set_priority(15).set_entry(|| {}).spawn();
+    assert_eq!(res, Err(task::TaskBuildError::PriorityNotAllowed));
+}
@@ -0,0 +1,28 @@
+use hopter_sim::{sim, sync::Mailbox, task, time};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[test]
+fn timeouts_follow_virtual_tick() {
+    static MAILBOX: Mailbox = Mailbox::new();
+    static WOKEN: Mailbox = Mailbox::new();
+    static WAITED: AtomicU32 = AtomicU32::new(0);
+
+    sim::set_realtime(false);
+
+    task::build()
+        .set_entry(|| {
+            let start = time::get_tick();
+            assert!(!MAILBOX.wait_until_timeout(50));
+            WAITED.store(time::get_tick().wrapping_sub(start), Ordering::SeqCst);
+            WOKEN.notify_allow_isr();
+        })
+        .spawn()
+        .unwrap();
+
+    // The waiter is only woken up by advancing the virtual tick.
+    while !WOKEN.wait_until_timeout(0) {
+        sim::advance_ms(1);
+        std::thread::yield_now();
+    }
+    assert!(WAITED.load(Ordering::SeqCst) >= 50);
+}
@@ -0,0 +1,273 @@
+//! Actors built on restartable tasks and channels.
+//!
+//! An actor is a task owning some state and a typed mailbox. It handles the
+//! messages in its mailbox one at a time, so its state needs no locking.
+//! Other tasks and ISRs send messages through an [`Address`], which is cheap
+//! to clone.
+//!
+//! Actors are supervised. If handling a message panics, the actor task is
+//! unwound and restarted with fresh state created by the factory given when
+//! spawning it. The message being handled is lost, but the messages still in
+//! the mailbox are handled by the restarted actor. Restarting requires the
+//! `unwind` feature.
+//!
+//! A message may carry a [`ReplyTo`] for the actor to answer a request, see
+//! [`Address::ask`].
+//!
+//! # Example
+//! ```rust
+//! enum CounterMsg {
+//!     Add(u32),
+//!     Get(ReplyTo<u32>),
+//! }
+//!
+//! struct Counter(u32);
+//!
+//! impl Actor for Counter {
+//!     type Message = CounterMsg;
+//!
+//!     fn handle(&mut self, msg: CounterMsg) {
+//!         match msg {
+//!             CounterMsg::Add(n) => self.0 += n,
+//!             CounterMsg::Get(reply) => reply.reply(self.0),
+//!         }
+//!     }
+//! }
+//!
+//! let counter = actor::build().spawn::<_, _, 8>(|| Counter(0)).unwrap();
+//! counter.send(CounterMsg::Add(5));
+//! let total = counter.ask(CounterMsg::Get).unwrap();
+//! ```
+
+use crate::{
+    config,
+    sync::{create_channel, Mailbox, Producer, SpinSchedSafe},
+    task::{self, TaskBuildError},
+};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// The behavior of an actor.
+pub trait Actor: Send + 'static {
+    /// The type of messages handled by the actor.
+    type Message: Send + 'static;
+
+    /// Called before the first message is handled, both when the actor is
+    /// first started and when it is restarted after a panic.
+    fn started(&mut self) {}
+
+    /// Handle a message.
+    fn handle(&mut self, msg: Self::Message);
+}
+
+/// Enumeration of errors of requests made with [`Address::ask`].
+#[derive(Debug, PartialEq)]
+pub enum AskError {
+    /// The actor dropped the [`ReplyTo`] without replying, e.g., because it
+    /// panicked while handling the request.
+    NoReply,
+    /// No reply arrived within the timeout.
+    Timeout,
+}
+
+/// The sending half of a mailbox with the capacity erased from the type.
+trait Sender<M>: Send + Sync {
+    fn send(&self, msg: M);
+    fn try_send_allow_isr(&self, msg: M) -> Result<(), M>;
+}
+
+impl<M, const N: usize> Sender<M> for Producer<M, N>
+where
+    M: Send,
+{
+    fn send(&self, msg: M) {
+        self.produce(msg)
+    }
+
+    fn try_send_allow_isr(&self, msg: M) -> Result<(), M> {
+        self.try_produce_allow_isr(msg)
+    }
+}
+
+/// A handle to send messages of type `M` to an actor.
+pub struct Address<M> {
+    sender: Arc<dyn Sender<M>>,
+    restarts: Arc<AtomicU32>,
+}
+
+impl<M> Clone for Address<M> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            restarts: self.restarts.clone(),
+        }
+    }
+}
+
+impl<M> Address<M>
+where
+    M: Send + 'static,
+{
+    /// Send a message to the actor. If the mailbox is full, block until there
+    /// is room.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn send(&self, msg: M) {
+        self.sender.send(msg)
+    }
+
+    /// Send a message to the actor without blocking. If the mailbox is full,
+    /// return the message with `Err`.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn try_send_allow_isr(&self, msg: M) -> Result<(), M> {
+        self.sender.try_send_allow_isr(msg)
+    }
+
+    /// Send a request built by `make` around a [`ReplyTo`] and block until
+    /// the actor replies.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn ask<R, F>(&self, make: F) -> Result<R, AskError>
+    where
+        F: FnOnce(ReplyTo<R>) -> M,
+        R: Send,
+    {
+        let slot = Arc::new(ReplySlot::new());
+        self.send(make(ReplyTo { slot: slot.clone() }));
+        slot.done.wait();
+        slot.value.lock().take().ok_or(AskError::NoReply)
+    }
+
+    /// Like [`ask`](Self::ask), but give up if no reply arrives within the
+    /// timeout. Note that the request may still be handled later.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn ask_until_timeout<R, F>(&self, make: F, timeout_ms: u32) -> Result<R, AskError>
+    where
+        F: FnOnce(ReplyTo<R>) -> M,
+        R: Send,
+    {
+        let slot = Arc::new(ReplySlot::new());
+        self.send(make(ReplyTo { slot: slot.clone() }));
+        if !slot.done.wait_until_timeout(timeout_ms) {
+            return Err(AskError::Timeout);
+        }
+        slot.value.lock().take().ok_or(AskError::NoReply)
+    }
+
+    /// The number of times the actor has been restarted after a panic.
+    pub fn restart_count(&self) -> u32 {
+        self.restarts.load(Ordering::SeqCst)
+    }
+}
+
+/// Where the reply to a request is delivered.
+struct ReplySlot<R> {
+    value: SpinSchedSafe<Option<R>>,
+    done: Mailbox,
+}
+
+impl<R> ReplySlot<R> {
+    fn new() -> Self {
+        Self {
+            value: SpinSchedSafe::new(None),
+            done: Mailbox::new(),
+        }
+    }
+}
+
+/// The means for an actor to answer a request sent with [`Address::ask`].
+/// Dropping it without replying, including when the actor panics, makes the
+/// request fail with [`AskError::NoReply`].
+pub struct ReplyTo<R> {
+    slot: Arc<ReplySlot<R>>,
+}
+
+impl<R> ReplyTo<R> {
+    /// Answer the request.
+    pub fn reply(self, value: R) {
+        *self.slot.value.lock() = Some(value);
+        // The asker is notified when `self` is dropped.
+    }
+}
+
+impl<R> Drop for ReplyTo<R> {
+    fn drop(&mut self) {
+        self.slot.done.notify_allow_isr();
+    }
+}
+
+/// Build a new actor with the actor builder.
+pub fn build() -> ActorBuilder {
+    ActorBuilder {
+        priority: None,
+        id: None,
+    }
+}
+
+/// Supporting the builder pattern to spawn a new actor.
+pub struct ActorBuilder {
+    priority: Option<u8>,
+    id: Option<u8>,
+}
+
+impl ActorBuilder {
+    /// Set a numerical ID for the actor task. See
+    /// [`TaskBuilder::set_id`](crate::task::TaskBuilder::set_id).
+    pub fn set_id(mut self, id: u8) -> Self {
+        self.id.replace(id);
+        self
+    }
+
+    /// Set the priority of the actor task. If not explicitly set, the task
+    /// will have the [`DEFAULT_TASK_PRIORITY`](config::DEFAULT_TASK_PRIORITY).
+    pub fn set_priority(mut self, prio: u8) -> Self {
+        self.priority.replace(prio);
+        self
+    }
+
+    /// Spawn the actor with a mailbox holding up to `N` messages. The
+    /// factory creates the state of the actor when it starts and every time
+    /// it restarts.
+    pub fn spawn<A, F, const N: usize>(
+        self,
+        factory: F,
+    ) -> Result<Address<A::Message>, TaskBuildError>
+    where
+        A: Actor,
+        F: Fn() -> A + Send + Sync + Clone + 'static,
+    {
+        let (producer, consumer) = create_channel::<A::Message, N>();
+        let restarts = Arc::new(AtomicU32::new(0));
+        let started = Arc::new(AtomicBool::new(false));
+
+        let entry = {
+            let restarts = restarts.clone();
+            move || {
+                if started.swap(true, Ordering::SeqCst) {
+                    restarts.fetch_add(1, Ordering::SeqCst);
+                }
+                let mut actor = factory();
+                actor.started();
+                loop {
+                    actor.handle(consumer.consume());
+                }
+            }
+        };
+
+        let builder = task::build()
+            .set_id(self.id.unwrap_or(config::DEFAULT_TASK_ID))
+            .set_priority(self.priority.unwrap_or(config::DEFAULT_TASK_PRIORITY))
+            .set_entry(entry);
+        #[cfg(feature = "unwind")]
+        builder.spawn_restartable()?;
+        #[cfg(not(feature = "unwind"))]
+        builder.spawn()?;
+
+        Ok(Address {
+            sender: Arc::new(producer),
+            restarts,
+        })
+    }
+}
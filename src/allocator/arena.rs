@@ -0,0 +1,224 @@
+//! Static memory serving the allocations made while building a task.
+//!
+//! A task spawned from [`build_static`](crate::task::build_static) keeps its
+//! task struct, entry closure and parker in the memory of its
+//! [`StaticTask`](crate::task::StaticTask) rather than on the heap. While
+//! the task is being built inside [`with_arena`], the global allocator
+//! serves the allocations of the spawning task from the arena with a bump
+//! pointer. Allocations made by interrupt handlers are still served from the
+//! heap.
+//!
+//! An arena is added to a list of known arenas when it is first used. Freeing
+//! a pointer outside the heap looks up the arena containing it and
+//! decrements its count of live allocations, so that it can be freed later
+//! from any context knowing only its address. The arena becomes available
+//! again once all allocations made from it have been freed.
+
+use super::heap_start;
+use crate::{config, schedule::scheduler::Scheduler, sync::Spin};
+use core::{
+    alloc::Layout,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+};
+use cortex_m::peripheral::{scb::VectActive, SCB};
+
+/// A region of static memory to serve allocations from.
+#[derive(Clone, Copy)]
+pub(crate) struct Arena {
+    begin: *mut u8,
+    size: usize,
+    state: &'static ArenaState,
+}
+
+/// The bookkeeping of an arena, kept in the same `static` as its memory.
+pub(crate) struct ArenaState {
+    /// The number of allocations from the arena not yet freed.
+    live: AtomicUsize,
+    /// The bounds of the arena memory, set before the arena is listed.
+    begin: AtomicUsize,
+    end: AtomicUsize,
+    /// If the arena is in the list headed by [`LISTED`].
+    listed: AtomicBool,
+    /// The next arena in the list.
+    next: AtomicPtr<ArenaState>,
+}
+
+impl ArenaState {
+    pub(crate) const fn new() -> Self {
+        Self {
+            live: AtomicUsize::new(0),
+            begin: AtomicUsize::new(0),
+            end: AtomicUsize::new(0),
+            listed: AtomicBool::new(false),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+/// The head of the list of arenas that have been used. Arenas are only
+/// added, by tasks with the scheduler suspended, and never removed, so the
+/// list can be walked from any context without locking.
+static LISTED: AtomicPtr<ArenaState> = AtomicPtr::new(ptr::null_mut());
+
+/// The arena only refers to a `static`, which is `Sync`.
+unsafe impl Send for Arena {}
+unsafe impl Sync for Arena {}
+
+impl Arena {
+    /// Create an arena over `size` bytes starting from `begin`.
+    ///
+    /// Safety: The memory must be a `static` outside the heap, and must only
+    /// be accessed through the arena.
+    pub(crate) const unsafe fn new(
+        begin: *mut u8,
+        size: usize,
+        state: &'static ArenaState,
+    ) -> Self {
+        Self { begin, size, state }
+    }
+
+    /// Add the arena to the list of known arenas if it is not listed yet.
+    /// The caller must have suspended the scheduler.
+    fn list(&self) {
+        let state = self.state;
+        if state.listed.load(Ordering::SeqCst) {
+            return;
+        }
+        state.begin.store(self.begin as usize, Ordering::SeqCst);
+        state
+            .end
+            .store(self.begin as usize + self.size, Ordering::SeqCst);
+        state
+            .next
+            .store(LISTED.load(Ordering::SeqCst), Ordering::SeqCst);
+        LISTED.store(state as *const _ as *mut _, Ordering::SeqCst);
+        state.listed.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Errors of serving allocations from an arena.
+pub(crate) enum ArenaError {
+    /// Some allocations from the previous use of the arena are not freed.
+    InUse,
+    /// The allocations did not fit in the arena.
+    TooSmall,
+}
+
+/// The arena currently serving allocations.
+struct Cursor {
+    arena: Arena,
+    /// The offset of the next free byte.
+    next: usize,
+    /// If an allocation did not fit and was served from the heap.
+    overflowed: bool,
+}
+
+/// The spin lock around it is only for sanity check. This field should not
+/// be accessed concurrently, because it is only accessed by the task holding
+/// the arena while the scheduler is suspended.
+static ACTIVE: Spin<Option<Cursor>> = Spin::new(None);
+
+/// If [`ACTIVE`] holds an arena. Other tasks never observe it set, so that
+/// they never touch [`ACTIVE`].
+static IS_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Deactivate the arena and drop the count held while it was active, when
+/// [`with_arena`] returns or unwinds.
+struct ActiveGuard<'a>(&'a Arena);
+
+impl Drop for ActiveGuard<'_> {
+    fn drop(&mut self) {
+        IS_ACTIVE.store(false, Ordering::SeqCst);
+        ACTIVE.lock_now_or_die().take();
+        self.0.state.live.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Run `op` with the allocations it makes served from `arena`. The
+/// scheduler is suspended meanwhile so that no other task allocates from the
+/// arena.
+///
+/// Return [`ArenaError::InUse`] if some allocations from the previous use of
+/// the arena are not freed yet. Return [`ArenaError::TooSmall`] if some
+/// allocations did not fit in the arena, in which case the value returned by
+/// `op` is dropped.
+pub(crate) fn with_arena<R>(arena: &Arena, op: impl FnOnce() -> R) -> Result<R, ArenaError> {
+    // Hold a count while the arena is active, so that freeing all
+    // allocations made so far does not make it available.
+    arena
+        .state
+        .live
+        .compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst)
+        .map_err(|_| ArenaError::InUse)?;
+
+    let _sched_guard = Scheduler::suspend();
+    let guard = ActiveGuard(arena);
+    arena.list();
+    *ACTIVE.lock_now_or_die() = Some(Cursor {
+        arena: *arena,
+        next: 0,
+        overflowed: false,
+    });
+    IS_ACTIVE.store(true, Ordering::SeqCst);
+
+    let ret = op();
+
+    IS_ACTIVE.store(false, Ordering::SeqCst);
+    let overflowed = ACTIVE
+        .lock_now_or_die()
+        .take()
+        .is_some_and(|cursor| cursor.overflowed);
+    drop(guard);
+
+    if overflowed {
+        drop(ret);
+        return Err(ArenaError::TooSmall);
+    }
+    Ok(ret)
+}
+
+/// Serve the allocation from the active arena if the calling task holds
+/// one. Return `None` if the allocation should be served from the heap.
+pub(super) fn alloc(layout: Layout) -> Option<*mut u8> {
+    if !IS_ACTIVE.load(Ordering::SeqCst) || SCB::vect_active() != VectActive::ThreadMode {
+        return None;
+    }
+
+    let mut active = ACTIVE.lock_now_or_die();
+    let cursor = active.as_mut()?;
+    let begin = cursor.arena.begin as usize;
+    let ptr = (begin + cursor.next).next_multiple_of(layout.align());
+    // A zero-sized allocation still takes a byte, so that its address lies
+    // within the arena.
+    let end = ptr + layout.size().max(1);
+    if end > begin + cursor.arena.size {
+        cursor.overflowed = true;
+        return None;
+    }
+    cursor.next = end - begin;
+    cursor.arena.state.live.fetch_add(1, Ordering::SeqCst);
+    Some(ptr as *mut u8)
+}
+
+/// Free the allocation if it was served from an arena. Return `false` if it
+/// was served from the heap, or if it lies in no known arena.
+pub(super) fn free(ptr: *mut u8) -> bool {
+    let addr = ptr as usize;
+    if (heap_start()..config::RAM_END_ADDR).contains(&(addr as u32)) {
+        return false;
+    }
+
+    let mut node = LISTED.load(Ordering::SeqCst);
+    // Safety: The listed arenas are `static`s and never unlisted.
+    while let Some(state) = unsafe { node.as_ref() } {
+        let begin = state.begin.load(Ordering::SeqCst);
+        let end = state.end.load(Ordering::SeqCst);
+        if (begin..end).contains(&addr) {
+            state.live.fetch_sub(1, Ordering::SeqCst);
+            return true;
+        }
+        node = state.next.load(Ordering::SeqCst);
+    }
+    false
+}
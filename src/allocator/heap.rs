@@ -73,6 +73,7 @@ use crate::{
 };
 use core::{
     arch::asm,
+    ptr::read_volatile,
     sync::atomic::{AtomicPtr, Ordering},
 };
 use static_assertions::const_assert_eq;
@@ -500,6 +501,16 @@ fn search_first_fit(size: u32) -> *mut Header {
     core::ptr::null_mut()
 }
 
+/// Return the payload size of an allocated chunk, which is no less than the
+/// size requested when it was allocated.
+///
+/// Safety:
+/// - `payload` must be a pointer previously returned by [`mcu_malloc`] and
+///   not yet freed.
+pub(crate) unsafe fn mcu_usable_size(payload: *mut u8) -> u32 {
+    get_hdr_chunk_size(payload_to_hdr(payload)) - HDR_SIZE
+}
+
 /// Return an allocated chunk back to the heap free lists.
 ///
 /// Safety:
@@ -606,6 +617,17 @@ pub(crate) unsafe fn mcu_malloc(mut size: u32) -> *mut u8 {
     hdr_to_payload(hdr)
 }
 
+/// Return the sum of currently allocated size and its historical maximum.
+/// The values may be momentarily stale if an allocation is in progress.
+pub(crate) fn alloc_size() -> (u32, u32) {
+    unsafe {
+        (
+            read_volatile(&raw const CUR_ALLOC_SIZE),
+            read_volatile(&raw const MAX_ALLOC_SIZE),
+        )
+    }
+}
+
 // Initialize the heap structure.
 pub(crate) unsafe fn mcu_heap_init(mut data_end: u32) {
     // Round up to a multiple of 4.
@@ -5,14 +5,18 @@ use core::{
 };
 
 use super::{
+    config,
     interrupt::{svc, trap_frame::TrapFrame},
     schedule::scheduler::Scheduler,
     task,
     unrecoverable::{self, Lethal},
 };
 
+mod arena;
 mod heap;
 
+pub(crate) use arena::{with_arena, Arena, ArenaError, ArenaState};
+
 #[no_mangle]
 static mut ADJUSTED_HIGH_WATER_MARK: u32 = 0;
 
@@ -142,10 +146,16 @@ static GLOBAL_ALLOC: Allocator = unsafe { Allocator::new() };
 
 unsafe impl GlobalAlloc for Allocator {
     unsafe fn alloc(&self, layout: core::alloc::Layout) -> *mut u8 {
+        if let Some(ptr) = arena::alloc(layout) {
+            return ptr;
+        }
         self.alloc_impl(layout.size())
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, _layout: core::alloc::Layout) {
+        if arena::free(ptr) {
+            return;
+        }
         self.free_impl(ptr)
     }
 }
@@ -219,6 +229,25 @@ pub(super) fn task_free(tf: &TrapFrame) {
     unsafe { alloc::alloc::dealloc(tf.gp_regs.r0 as *mut u8, Layout::new::<u8>()) }
 }
 
+/// Returns the number of usable bytes in the allocated memory chunk, which is
+/// no less than the size requested when it was allocated.
+///
+/// Safety: `ptr` must have been returned by the allocator and not yet freed.
+pub(crate) unsafe fn usable_size(ptr: *mut u8) -> usize {
+    heap::mcu_usable_size(ptr) as usize
+}
+
+/// Returns the total size of the heap in bytes.
+pub(crate) fn heap_size() -> u32 {
+    config::RAM_END_ADDR - heap_start()
+}
+
+/// Returns the number of bytes currently allocated from the heap and the
+/// historical maximum.
+pub(crate) fn heap_usage() -> (u32, u32) {
+    heap::alloc_size()
+}
+
 /// Returns a pointer to the start of the heap.
 /// The returned pointer is guaranteed to be 4-byte aligned.
 #[inline]
@@ -1,3 +1,5 @@
+#[cfg(feature = "boot_report")]
+mod report;
 pub(crate) mod reset;
 mod system_init;
 mod vector_table;
@@ -0,0 +1,57 @@
+//! The boot report printed when the kernel starts, enabled by the
+//! `boot_report` feature. It anchors every captured log session with the
+//! exact kernel build and the environment it boots in.
+
+use crate::{
+    allocator, config,
+    debug::{breadcrumb, semihosting::dbg_println},
+};
+
+/// A FNV-1a hash over the configuration values that affect kernel behavior.
+/// Two builds with the same hash run with the same configuration.
+fn config_hash() -> u32 {
+    let params: [u32; 12] = [
+        config::SYSTICK_FREQUENCY_HZ,
+        config::ALLOW_DYNAMIC_STACK as u32,
+        config::STACKLET_ADDITION_ALLOC_SIZE as u32,
+        config::HOT_SPLIT_PREVENTION_CACHE_SIZE as u32,
+        config::HOT_SPLIT_DETECTION_THRESHOLD as u32,
+        config::MAIN_TASK_INITIAL_STACK_SIZE as u32,
+        config::MAX_TASK_NUMBER as u32,
+        config::ALLOW_TASK_PREEMPTION as u32,
+        config::BREATHING_CONCURRENCY as u32,
+        config::TASK_PRIORITY_LEVELS as u32,
+        config::FP_CONTEXT_SAVE as u32,
+        config::RAM_END_ADDR,
+    ];
+
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in params.iter().flat_map(|param| param.to_le_bytes()) {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
+/// Print the boot report.
+pub(super) fn print() {
+    dbg_println!("=== Hopter v{} ===", env!("CARGO_PKG_VERSION"));
+    dbg_println!("config hash:      {:#010x}", config_hash());
+    dbg_println!("config preset:    {}", config::preset::SELECTED.name);
+    dbg_println!("systick clock:    {} Hz", config::SYSTICK_FREQUENCY_HZ);
+    dbg_println!("heap size:        {} bytes", allocator::heap_size());
+    dbg_println!(
+        "stacklet:         main {} bytes, extra alloc {} bytes, dynamic {}",
+        config::MAIN_TASK_INITIAL_STACK_SIZE,
+        config::STACKLET_ADDITION_ALLOC_SIZE,
+        config::ALLOW_DYNAMIC_STACK
+    );
+    dbg_println!("fp context:       {}", config::FP_CONTEXT_SAVE);
+    dbg_println!("reset cause:      {}", breadcrumb::last_reset_cause());
+    dbg_println!("boot count:       {}", breadcrumb::boot_count());
+    dbg_println!("reboot reason:    {:?}", breadcrumb::last_reboot_reason());
+    match breadcrumb::last_panic_hash() {
+        Some(hash) => dbg_println!("last panic hash:  {:#010x}", hash),
+        None => dbg_println!("last panic hash:  none"),
+    }
+}
@@ -1,15 +1,37 @@
 //! The module performs the initialization before running the user defined main
 //! function.
 
-use crate::{allocator, config, schedule::scheduler::Scheduler, task, unrecoverable::Lethal};
+use crate::{
+    allocator, config, debug::events, init, schedule::scheduler::Scheduler, task,
+    unrecoverable::Lethal,
+};
 use alloc::boxed::Box;
 use core::sync::atomic::AtomicPtr;
 use cortex_m::peripheral::scb::SystemHandler;
 
+/// The automatic state preservation enable bit of the FPCCR register.
+const FPCCR_ASPEN: u32 = 1 << 31;
+/// The lazy state preservation enable bit of the FPCCR register.
+const FPCCR_LSPEN: u32 = 1 << 30;
+
 /// The very first Rust function executed.
 pub(super) extern "C" fn system_start() -> ! {
     allocator::initialize();
 
+    // Snapshot the breadcrumbs left by the previous boot before anything
+    // else gets a chance to overwrite them.
+    #[cfg(feature = "breadcrumb")]
+    {
+        crate::debug::breadcrumb::init();
+        crate::power::enter_dfu_if_requested();
+    }
+    events::init();
+    #[cfg(feature = "sched_events")]
+    crate::debug::sched_events::init();
+
+    // Pick up the configuration values that may have been patched by the host.
+    config::tunable::load();
+
     let mut cp = unsafe { cortex_m::Peripherals::steal() };
 
     // Configure system call and context switch exception priority.
@@ -21,18 +43,41 @@ pub(super) extern "C" fn system_start() -> ! {
         cortex_m::register::basepri::write(config::IRQ_ENABLE_BASEPRI_PRIORITY);
     }
 
-    cp.SCB.enable_fpu();
+    // Leave the FPU disabled if its context is not preserved, so that any
+    // stray floating point instruction faults instead of corrupting other
+    // tasks' registers.
+    if config::FP_CONTEXT_SAVE {
+        cp.SCB.enable_fpu();
+
+        // Stack the floating point registers `s0-s15` and `fpscr` either
+        // lazily or eagerly on exception entry. See `FP_LAZY_STACKING`.
+        let fpccr = if config::FP_LAZY_STACKING {
+            FPCCR_ASPEN | FPCCR_LSPEN
+        } else {
+            FPCCR_ASPEN
+        };
+        unsafe {
+            cp.FPU.fpccr.write(fpccr);
+        }
+    }
 
     // Spawn the main task. The task will not be executed until we start the
     // scheduler.
     task::build()
         .set_id(config::MAIN_TASK_ID)
+        .set_name("main")
         .set_entry(move || main_task(cp))
         .set_stack_init_size(config::MAIN_TASK_INITIAL_STACK_SIZE)
         .set_priority(config::MAIN_TASK_PRIORITY)
         .spawn()
         .unwrap_or_die();
 
+    #[cfg(feature = "reaper")]
+    task::reaper::spawn().unwrap_or_die();
+
+    #[cfg(feature = "starvation_monitor")]
+    task::starvation::spawn().unwrap_or_die();
+
     // Start the scheduler. It will transform the current bootstrap thread into
     // the idle task context and then perform a context switch to run the main
     // task.
@@ -80,6 +125,11 @@ extern "Rust" {
 fn main_task(mut cp: cortex_m::Peripherals) {
     enable_systick(&mut cp);
 
+    #[cfg(feature = "boot_report")]
+    super::report::print();
+
+    init::enter_kernel_stage();
+
     let boxed_cp = Box::new(cp);
     let raw_cp = AtomicPtr::new(Box::into_raw(boxed_cp) as *mut u8);
     unsafe { __main_trampoline(raw_cp) }
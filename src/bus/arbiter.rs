@@ -0,0 +1,91 @@
+use crate::{
+    schedule::current,
+    sync::{Mailbox, SpinSchedSafe},
+};
+use alloc::{sync::Arc, vec::Vec};
+
+/// A task waiting for the bus.
+struct Waiter {
+    /// The effective priority of the task when it started waiting.
+    prio: u8,
+    /// Notified when the bus is handed over to the task.
+    grant: Arc<Mailbox>,
+}
+
+struct ArbiterState {
+    /// Set while a task owns the bus.
+    busy: bool,
+    /// Tasks waiting for the bus in the order of arrival.
+    waiters: Vec<Waiter>,
+}
+
+/// Grant exclusive use of a bus to one task at a time. Waiting tasks get the
+/// bus in the order of their priority, and tasks with the same priority in
+/// the order of arrival.
+pub(super) struct Arbiter {
+    state: SpinSchedSafe<ArbiterState>,
+}
+
+impl Arbiter {
+    pub(super) const fn new() -> Self {
+        Self {
+            state: SpinSchedSafe::new(ArbiterState {
+                busy: false,
+                waiters: Vec::new(),
+            }),
+        }
+    }
+
+    /// Block until the bus is granted to the calling task. The bus is
+    /// released when the returned guard is dropped.
+    pub(super) fn acquire(&self) -> ArbiterGuard<'_> {
+        let grant = {
+            let mut state = self.state.lock();
+            if !state.busy {
+                state.busy = true;
+                return ArbiterGuard { arbiter: self };
+            }
+            let prio =
+                current::with_cur_task(|cur_task| cur_task.get_priority().effective_priority());
+            let grant = Arc::new(Mailbox::new());
+            state.waiters.push(Waiter {
+                prio,
+                grant: grant.clone(),
+            });
+            grant
+        };
+
+        // The bus stays busy when handed over, so it is ours once notified.
+        grant.wait();
+        ArbiterGuard { arbiter: self }
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock();
+
+        // Smaller numbers are higher priorities. Take the first one among
+        // the highest to keep the order of arrival.
+        let next = state
+            .waiters
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, waiter)| waiter.prio)
+            .map(|(idx, _)| idx);
+
+        match next {
+            Some(idx) => state.waiters.remove(idx).grant.notify_allow_isr(),
+            None => state.busy = false,
+        }
+    }
+}
+
+/// Release the bus when dropped.
+pub(super) struct ArbiterGuard<'a> {
+    arbiter: &'a Arbiter,
+}
+
+impl Drop for ArbiterGuard<'_> {
+    fn drop(&mut self) {
+        self.arbiter.release();
+    }
+}
@@ -0,0 +1,135 @@
+use super::{arbiter::Arbiter, BusError, Completion};
+use crate::sync::Mutex;
+use embedded_hal::i2c::{self, ErrorType, Operation, SevenBitAddress};
+
+/// A driver performing transactions on an I2C peripheral.
+///
+/// A driver may complete a transaction by busy waiting, or it may start the
+/// transfer with interrupts or DMA and block on the given [`Completion`],
+/// which its IRQ handler notifies through [`I2cBus::notify_allow_isr`].
+pub trait I2cDriver: Send {
+    /// The error type of the driver.
+    type Error: i2c::Error;
+
+    /// Perform the operations as one I2C transaction with the device at the
+    /// given address, as specified by `embedded_hal::i2c::I2c::transaction`.
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+        completion: &Completion,
+    ) -> Result<(), Self::Error>;
+}
+
+/// An I2C peripheral shared by tasks. The bus is meant to live in a `static`
+/// so that the IRQ handlers of the peripheral can reach it.
+///
+/// # Example
+/// ```rust
+/// static I2C1_BUS: I2cBus<Blocking<I2c<I2C1>>> = I2cBus::new();
+///
+/// I2C1_BUS.init(Blocking(dp.I2C1.i2c((scl, sda), 400.kHz(), &clocks))).unwrap();
+/// let sensor = Bmp280::new(I2C1_BUS.device());
+/// ```
+pub struct I2cBus<D> {
+    arbiter: Arbiter,
+    driver: Mutex<Option<D>>,
+    completion: Completion,
+}
+
+impl<D> I2cBus<D>
+where
+    D: I2cDriver,
+{
+    /// Create a bus without a driver. Call [`init`](Self::init) before use.
+    pub const fn new() -> Self {
+        Self {
+            arbiter: Arbiter::new(),
+            driver: Mutex::new(None),
+            completion: Completion::new(),
+        }
+    }
+
+    /// Take over the peripheral with the given driver.
+    pub fn init(&self, driver: D) -> Result<(), BusError<D::Error>> {
+        let mut slot = self.driver.lock();
+        if slot.is_some() {
+            return Err(BusError::AlreadyInitialized);
+        }
+        *slot = Some(driver);
+        Ok(())
+    }
+
+    /// Notify the driver waiting for a transfer to complete. Drivers should
+    /// call this function from their IRQ handlers.
+    pub fn notify_allow_isr(&self) {
+        self.completion.notify_allow_isr();
+    }
+
+    /// Perform the operations as one I2C transaction with the device at the
+    /// given address. If the bus is in use, block until it is granted to the
+    /// calling task.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn transaction(
+        &self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), BusError<D::Error>> {
+        let _guard = self.arbiter.acquire();
+        let mut driver = self.driver.lock();
+        let driver = driver.as_mut().ok_or(BusError::NotInitialized)?;
+        driver
+            .transaction(address, operations, &self.completion)
+            .map_err(BusError::Driver)
+    }
+
+    /// Get a handle implementing `embedded_hal::i2c::I2c` to pass to device
+    /// driver crates.
+    pub fn device(&self) -> I2cDevice<'_, D> {
+        I2cDevice { bus: self }
+    }
+}
+
+impl<D> Default for I2cBus<D>
+where
+    D: I2cDriver,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A handle to an [`I2cBus`] implementing `embedded_hal::i2c::I2c`. Handles
+/// are cheap to copy, so each device driver can own one.
+pub struct I2cDevice<'a, D> {
+    bus: &'a I2cBus<D>,
+}
+
+impl<D> Clone for I2cDevice<'_, D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<D> Copy for I2cDevice<'_, D> {}
+
+impl<D> ErrorType for I2cDevice<'_, D>
+where
+    D: I2cDriver,
+{
+    type Error = BusError<D::Error>;
+}
+
+impl<D> i2c::I2c<SevenBitAddress> for I2cDevice<'_, D>
+where
+    D: I2cDriver,
+{
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.bus.transaction(address, operations)
+    }
+}
@@ -0,0 +1,168 @@
+//! Shared I2C and SPI buses, enabled by the `bus` feature.
+//!
+//! An [`I2cBus`] or an [`SpiBus`] owns a peripheral through a driver and
+//! serializes the transactions of all tasks using it. A task starting a
+//! transaction while the bus is in use blocks until the bus is granted to
+//! it. Waiting tasks are granted the bus in the order of their priority, and
+//! tasks with the same priority in the order of arrival. A transaction is
+//! never interrupted by another one, so device drivers can rely on
+//! multi-operation transactions being atomic.
+//!
+//! Drivers implement [`I2cDriver`] or [`SpiDriver`]. A driver may start a
+//! transfer with interrupts or DMA and block on the [`Completion`] of the
+//! bus, which lets other tasks run until its IRQ handler notifies the
+//! completion. Any blocking `embedded-hal` bus implementation, e.g., from a
+//! HAL crate, can be used as a driver by wrapping it in [`Blocking`].
+//!
+//! Handles to the bus implement the `embedded-hal` traits
+//! `embedded_hal::i2c::I2c` and `embedded_hal::spi::SpiDevice`, so that
+//! device driver crates work on the shared bus unchanged.
+//!
+//! # Example
+//! ```rust
+//! static I2C1_BUS: I2cBus<Blocking<I2c<I2C1>>> = I2cBus::new();
+//!
+//! I2C1_BUS.init(Blocking(dp.I2C1.i2c((scl, sda), 400.kHz(), &clocks))).unwrap();
+//!
+//! // Both drivers can be used from different tasks.
+//! let mut sensor = Bmp280::new(I2C1_BUS.device());
+//! let mut eeprom = At24c32::new(I2C1_BUS.device());
+//! ```
+
+mod arbiter;
+mod i2c;
+mod spi;
+
+pub use i2c::{I2cBus, I2cDevice, I2cDriver};
+pub use spi::{SpiBus, SpiDevice, SpiDriver};
+
+use crate::{sync::Mailbox, time::Delay};
+use embedded_hal::{
+    delay::DelayNs,
+    i2c::{self as hal_i2c, Operation as I2cOperation},
+    spi::{self as hal_spi, Operation as SpiOperation},
+};
+
+/// Enumeration of errors of the bus API.
+#[derive(Debug, PartialEq)]
+pub enum BusError<E> {
+    /// The bus has no driver yet.
+    NotInitialized,
+    /// The bus already has a driver.
+    AlreadyInitialized,
+    /// The chip select pin could not be set.
+    ChipSelect,
+    /// The driver failed.
+    Driver(E),
+}
+
+impl<E> hal_i2c::Error for BusError<E>
+where
+    E: hal_i2c::Error,
+{
+    fn kind(&self) -> hal_i2c::ErrorKind {
+        match self {
+            Self::Driver(err) => err.kind(),
+            _ => hal_i2c::ErrorKind::Other,
+        }
+    }
+}
+
+impl<E> hal_spi::Error for BusError<E>
+where
+    E: hal_spi::Error,
+{
+    fn kind(&self) -> hal_spi::ErrorKind {
+        match self {
+            Self::Driver(err) => err.kind(),
+            Self::ChipSelect => hal_spi::ErrorKind::ChipSelectFault,
+            _ => hal_spi::ErrorKind::Other,
+        }
+    }
+}
+
+/// The notification of a transfer completing, waited on by the driver of a
+/// bus and notified by its IRQ handler.
+pub struct Completion {
+    mailbox: Mailbox,
+}
+
+impl Completion {
+    const fn new() -> Self {
+        Self {
+            mailbox: Mailbox::new(),
+        }
+    }
+
+    /// Block until notified. Each call consumes one notification, so the
+    /// driver should arrange exactly one notification per transfer it waits
+    /// for.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn wait(&self) {
+        self.mailbox.wait();
+    }
+
+    /// Block until notified or the timeout expires. Return `true` if
+    /// notified, or `false` on timeout, after which the driver should abort
+    /// the transfer before a late notification can arrive.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn wait_until_timeout(&self, timeout_ms: u32) -> bool {
+        self.mailbox.wait_until_timeout(timeout_ms)
+    }
+
+    /// Notify the waiting driver.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn notify_allow_isr(&self) {
+        self.mailbox.notify_allow_isr();
+    }
+}
+
+/// A driver over a blocking `embedded-hal` bus implementation. It busy waits
+/// for the transfers and never waits on the [`Completion`].
+pub struct Blocking<T>(pub T);
+
+impl<T> I2cDriver for Blocking<T>
+where
+    T: hal_i2c::I2c + Send,
+{
+    type Error = T::Error;
+
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [I2cOperation<'_>],
+        _completion: &Completion,
+    ) -> Result<(), Self::Error> {
+        self.0.transaction(address, operations)
+    }
+}
+
+impl<T> SpiDriver for Blocking<T>
+where
+    T: hal_spi::SpiBus + Send,
+{
+    type Error = T::Error;
+
+    fn transaction(
+        &mut self,
+        operations: &mut [SpiOperation<'_, u8>],
+        _completion: &Completion,
+    ) -> Result<(), Self::Error> {
+        for op in operations {
+            match op {
+                SpiOperation::Read(buf) => self.0.read(buf)?,
+                SpiOperation::Write(buf) => self.0.write(buf)?,
+                SpiOperation::Transfer(read, write) => self.0.transfer(read, write)?,
+                SpiOperation::TransferInPlace(buf) => self.0.transfer_in_place(buf)?,
+                SpiOperation::DelayNs(ns) => {
+                    self.0.flush()?;
+                    Delay::new().delay_ns(*ns);
+                }
+            }
+        }
+        self.0.flush()
+    }
+}
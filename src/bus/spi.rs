@@ -0,0 +1,140 @@
+use super::{arbiter::Arbiter, BusError, Completion};
+use crate::sync::Mutex;
+use embedded_hal::{
+    digital::OutputPin,
+    spi::{self, ErrorType, Operation},
+};
+
+/// A driver performing transfers on an SPI peripheral. Chip select is
+/// handled by the [`SpiDevice`] handles.
+///
+/// A driver may complete the transfers by busy waiting, or it may start them
+/// with interrupts or DMA and block on the given [`Completion`], which its
+/// IRQ handler notifies through [`SpiBus::notify_allow_isr`].
+pub trait SpiDriver: Send {
+    /// The error type of the driver.
+    type Error: spi::Error;
+
+    /// Perform the operations in order, as specified by
+    /// `embedded_hal::spi::SpiDevice::transaction`, and return after the
+    /// last bit is on the wire.
+    fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+        completion: &Completion,
+    ) -> Result<(), Self::Error>;
+}
+
+/// An SPI peripheral shared by tasks. The bus is meant to live in a `static`
+/// so that the IRQ handlers of the peripheral can reach it.
+///
+/// # Example
+/// ```rust
+/// static SPI1_BUS: SpiBus<Blocking<Spi<SPI1>>> = SpiBus::new();
+///
+/// SPI1_BUS.init(Blocking(dp.SPI1.spi((sck, miso, mosi), MODE_0, 1.MHz(), &clocks))).unwrap();
+/// let flash = SpiNorFlash::new(SPI1_BUS.device(flash_cs));
+/// let display = St7789::new(SPI1_BUS.device(display_cs));
+/// ```
+pub struct SpiBus<D> {
+    arbiter: Arbiter,
+    driver: Mutex<Option<D>>,
+    completion: Completion,
+}
+
+impl<D> SpiBus<D>
+where
+    D: SpiDriver,
+{
+    /// Create a bus without a driver. Call [`init`](Self::init) before use.
+    pub const fn new() -> Self {
+        Self {
+            arbiter: Arbiter::new(),
+            driver: Mutex::new(None),
+            completion: Completion::new(),
+        }
+    }
+
+    /// Take over the peripheral with the given driver.
+    pub fn init(&self, driver: D) -> Result<(), BusError<D::Error>> {
+        let mut slot = self.driver.lock();
+        if slot.is_some() {
+            return Err(BusError::AlreadyInitialized);
+        }
+        *slot = Some(driver);
+        Ok(())
+    }
+
+    /// Notify the driver waiting for a transfer to complete. Drivers should
+    /// call this function from their IRQ handlers.
+    pub fn notify_allow_isr(&self) {
+        self.completion.notify_allow_isr();
+    }
+
+    /// Assert the chip select, perform the operations, and deassert the chip
+    /// select. If the bus is in use, block until it is granted to the
+    /// calling task.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn transaction<CS>(
+        &self,
+        cs: &mut CS,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), BusError<D::Error>>
+    where
+        CS: OutputPin,
+    {
+        let _guard = self.arbiter.acquire();
+        let mut driver = self.driver.lock();
+        let driver = driver.as_mut().ok_or(BusError::NotInitialized)?;
+
+        cs.set_low().map_err(|_| BusError::ChipSelect)?;
+        let result = driver.transaction(operations, &self.completion);
+        let deassert = cs.set_high();
+
+        result.map_err(BusError::Driver)?;
+        deassert.map_err(|_| BusError::ChipSelect)
+    }
+
+    /// Get a handle implementing `embedded_hal::spi::SpiDevice` for the
+    /// device selected by the given pin, to pass to device driver crates.
+    pub fn device<CS>(&self, cs: CS) -> SpiDevice<'_, D, CS>
+    where
+        CS: OutputPin,
+    {
+        SpiDevice { bus: self, cs }
+    }
+}
+
+impl<D> Default for SpiBus<D>
+where
+    D: SpiDriver,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A handle to an [`SpiBus`] with the chip select pin of one device,
+/// implementing `embedded_hal::spi::SpiDevice`.
+pub struct SpiDevice<'a, D, CS> {
+    bus: &'a SpiBus<D>,
+    cs: CS,
+}
+
+impl<D, CS> ErrorType for SpiDevice<'_, D, CS>
+where
+    D: SpiDriver,
+{
+    type Error = BusError<D::Error>;
+}
+
+impl<D, CS> spi::SpiDevice<u8> for SpiDevice<'_, D, CS>
+where
+    D: SpiDriver,
+    CS: OutputPin,
+{
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        self.bus.transaction(&mut self.cs, operations)
+    }
+}
@@ -0,0 +1,242 @@
+use super::{CanDriver, CanError, CanId, Frame};
+use core::ptr::{read_volatile, write_volatile};
+
+/// Address of the `RCC_APB1ENR` register.
+const RCC_APB1ENR: *mut u32 = 0x4002_3840 as *mut u32;
+
+/// The `CAN1EN` bit in `RCC_APB1ENR`.
+const RCC_APB1ENR_CAN1EN: u32 = 1 << 25;
+
+/// The `CAN2EN` bit in `RCC_APB1ENR`.
+const RCC_APB1ENR_CAN2EN: u32 = 1 << 26;
+
+/// Base address of CAN1, which also holds the filter banks of both
+/// controllers.
+const CAN1_BASE: usize = 0x4000_6400;
+
+/// Base address of CAN2.
+const CAN2_BASE: usize = 0x4000_6800;
+
+/* Register offsets. */
+const MCR: usize = 0x000;
+const MSR: usize = 0x004;
+const TSR: usize = 0x008;
+const RF0R: usize = 0x00C;
+const IER: usize = 0x014;
+const BTR: usize = 0x01C;
+const TI0R: usize = 0x180;
+const RI0R: usize = 0x1B0;
+const FMR: usize = 0x200;
+const FM1R: usize = 0x204;
+const FS1R: usize = 0x20C;
+const FFA1R: usize = 0x214;
+const FA1R: usize = 0x21C;
+const F0R1: usize = 0x240;
+
+/* Register bits. */
+const MCR_INRQ: u32 = 1 << 0;
+const MCR_SLEEP: u32 = 1 << 1;
+const MCR_ABOM: u32 = 1 << 6;
+const MSR_INAK: u32 = 1 << 0;
+const TSR_RQCP_ALL: u32 = (1 << 0) | (1 << 8) | (1 << 16);
+const TSR_TME_ALL: u32 = 0b111 << 26;
+const TSR_CODE_SHIFT: u32 = 24;
+const RF0R_FMP0: u32 = 0b11;
+const RF0R_RFOM0: u32 = 1 << 5;
+const IER_TMEIE: u32 = 1 << 0;
+const IER_FMPIE0: u32 = 1 << 1;
+const FMR_FINIT: u32 = 1 << 0;
+const IR_TXRQ: u32 = 1 << 0;
+const IR_RTR: u32 = 1 << 1;
+const IR_IDE: u32 = 1 << 2;
+
+/// The number of status polls before giving up on a mode change.
+const POLL_LIMIT: u32 = 100_000;
+
+/// A bxCAN controller on STM32F4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BxCanInstance {
+    /// The first controller.
+    Can1,
+    /// The second controller, present on STM32F405/407 and larger parts.
+    Can2,
+}
+
+impl BxCanInstance {
+    fn base(self) -> usize {
+        match self {
+            Self::Can1 => CAN1_BASE,
+            Self::Can2 => CAN2_BASE,
+        }
+    }
+
+    /// The filter bank used by the controller. CAN2 starts at bank 14 after
+    /// reset.
+    fn filter_bank(self) -> usize {
+        match self {
+            Self::Can1 => 0,
+            Self::Can2 => 14,
+        }
+    }
+}
+
+fn read_reg(base: usize, offset: usize) -> u32 {
+    unsafe { read_volatile((base + offset) as *const u32) }
+}
+
+fn write_reg(base: usize, offset: usize, val: u32) {
+    unsafe { write_volatile((base + offset) as *mut u32, val) }
+}
+
+fn modify_reg(base: usize, offset: usize, op: impl FnOnce(u32) -> u32) {
+    write_reg(base, offset, op(read_reg(base, offset)))
+}
+
+/// Wait until the `INAK` bit becomes `set`.
+fn wait_inak(base: usize, set: bool) -> Result<(), CanError> {
+    for _ in 0..POLL_LIMIT {
+        if (read_reg(base, MSR) & MSR_INAK != 0) == set {
+            return Ok(());
+        }
+    }
+    Err(CanError::Hardware)
+}
+
+/// A [`CanDriver`] for the bxCAN controllers of STM32F4.
+///
+/// The CAN pins must be configured to their alternate function before
+/// creating the driver. The driver receives through FIFO 0 with all frames
+/// accepted by the hardware filter, leaving filtering to the receivers.
+/// The IRQ handlers of both the RX0 and the TX IRQ of the controller must
+/// call [`BxCan::irq_handler_allow_isr`].
+///
+/// # Example
+/// ```rust
+/// // 500 kbit/s with a 42 MHz APB1 clock.
+/// let driver = BxCan::new(BxCanInstance::Can1, 0x001c_0004).unwrap();
+/// can::start(driver).unwrap();
+///
+/// #[handler(CAN1_RX0)]
+/// fn can1_rx0_handler() {
+///     BxCan::irq_handler_allow_isr(BxCanInstance::Can1);
+/// }
+///
+/// #[handler(CAN1_TX)]
+/// fn can1_tx_handler() {
+///     BxCan::irq_handler_allow_isr(BxCanInstance::Can1);
+/// }
+/// ```
+pub struct BxCan {
+    base: usize,
+}
+
+impl BxCan {
+    /// Initialize the controller and join the bus. `btr` is the value of the
+    /// `CAN_BTR` register, which sets the bit timing.
+    ///
+    /// Return [`CanError::Hardware`] if the controller does not enter or
+    /// leave the initialization mode, e.g., because the pins are not
+    /// configured.
+    pub fn new(instance: BxCanInstance, btr: u32) -> Result<Self, CanError> {
+        // CAN2 needs the clock of CAN1 to access the filter banks.
+        let clocks = match instance {
+            BxCanInstance::Can1 => RCC_APB1ENR_CAN1EN,
+            BxCanInstance::Can2 => RCC_APB1ENR_CAN1EN | RCC_APB1ENR_CAN2EN,
+        };
+        unsafe { write_volatile(RCC_APB1ENR, read_volatile(RCC_APB1ENR) | clocks) };
+
+        let base = instance.base();
+        modify_reg(base, MCR, |mcr| (mcr | MCR_INRQ) & !MCR_SLEEP);
+        wait_inak(base, true)?;
+        modify_reg(base, MCR, |mcr| mcr | MCR_ABOM);
+        write_reg(base, BTR, btr);
+
+        // Route all frames to FIFO 0 with a 32-bit mask filter that masks
+        // nothing.
+        let bank = instance.filter_bank();
+        let bit = 1 << bank;
+        modify_reg(CAN1_BASE, FMR, |fmr| fmr | FMR_FINIT);
+        modify_reg(CAN1_BASE, FA1R, |fa1r| fa1r & !bit);
+        modify_reg(CAN1_BASE, FM1R, |fm1r| fm1r & !bit);
+        modify_reg(CAN1_BASE, FS1R, |fs1r| fs1r | bit);
+        modify_reg(CAN1_BASE, FFA1R, |ffa1r| ffa1r & !bit);
+        write_reg(CAN1_BASE, F0R1 + bank * 8, 0);
+        write_reg(CAN1_BASE, F0R1 + bank * 8 + 4, 0);
+        modify_reg(CAN1_BASE, FA1R, |fa1r| fa1r | bit);
+        modify_reg(CAN1_BASE, FMR, |fmr| fmr & !FMR_FINIT);
+
+        modify_reg(base, MCR, |mcr| mcr & !MCR_INRQ);
+        wait_inak(base, false)?;
+
+        let mut driver = Self { base };
+        driver.enable_interrupts();
+        Ok(driver)
+    }
+
+    /// Disable the IRQs of the controller and notify the CAN task. Must be
+    /// called from the RX0 and the TX IRQ handlers of the controller.
+    pub fn irq_handler_allow_isr(instance: BxCanInstance) {
+        modify_reg(instance.base(), IER, |ier| ier & !(IER_FMPIE0 | IER_TMEIE));
+        super::notify_allow_isr();
+    }
+}
+
+impl CanDriver for BxCan {
+    fn receive(&mut self) -> Option<Frame> {
+        if read_reg(self.base, RF0R) & RF0R_FMP0 == 0 {
+            return None;
+        }
+        let rir = read_reg(self.base, RI0R);
+        let rdtr = read_reg(self.base, RI0R + 4);
+        let mut data = [0; 8];
+        data[..4].copy_from_slice(&read_reg(self.base, RI0R + 8).to_le_bytes());
+        data[4..].copy_from_slice(&read_reg(self.base, RI0R + 12).to_le_bytes());
+        write_reg(self.base, RF0R, RF0R_RFOM0);
+
+        // Remote frames are not supported and are discarded.
+        if rir & IR_RTR != 0 {
+            return self.receive();
+        }
+        let id = if rir & IR_IDE != 0 {
+            CanId::Extended(rir >> 3)
+        } else {
+            CanId::Standard((rir >> 21) as u16)
+        };
+        let len = ((rdtr & 0xF) as usize).min(8);
+        Frame::new(id, &data[..len])
+    }
+
+    fn can_transmit(&self) -> bool {
+        read_reg(self.base, TSR) & TSR_TME_ALL != 0
+    }
+
+    fn transmit(&mut self, frame: &Frame) {
+        let mailbox = ((read_reg(self.base, TSR) >> TSR_CODE_SHIFT) & 0b11) as usize;
+        let tir = TI0R + mailbox * 0x10;
+        let data = frame.data();
+        let mut bytes = [0; 8];
+        bytes[..data.len()].copy_from_slice(data);
+        write_reg(self.base, tir + 4, data.len() as u32);
+        write_reg(
+            self.base,
+            tir + 8,
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        );
+        write_reg(
+            self.base,
+            tir + 12,
+            u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        );
+        let id_bits = match frame.id() {
+            CanId::Standard(id) => (id as u32) << 21,
+            CanId::Extended(id) => (id << 3) | IR_IDE,
+        };
+        write_reg(self.base, tir, id_bits | IR_TXRQ);
+    }
+
+    fn enable_interrupts(&mut self) {
+        // Clear the completion flags, which keep the TX IRQ pending.
+        write_reg(self.base, TSR, TSR_RQCP_ALL);
+        modify_reg(self.base, IER, |ier| ier | IER_FMPIE0 | IER_TMEIE);
+    }
+}
@@ -0,0 +1,25 @@
+use super::Frame;
+
+/// A CAN controller driver that the CAN task can use.
+///
+/// The driver should call [`notify_allow_isr`](super::notify_allow_isr) from
+/// its IRQ handlers whenever a frame is received or a transmit mailbox
+/// becomes empty. Since the frames are only drained later by the CAN task, a
+/// level-triggered IRQ should be disabled before notifying and re-enabled in
+/// [`enable_interrupts`](Self::enable_interrupts).
+pub trait CanDriver: Send {
+    /// Take the next received frame. Return `None` if no frame is pending.
+    fn receive(&mut self) -> Option<Frame>;
+
+    /// Return if a frame can be transmitted now.
+    fn can_transmit(&self) -> bool;
+
+    /// Transmit the frame. Only called after
+    /// [`can_transmit`](Self::can_transmit) returned `true`.
+    fn transmit(&mut self, frame: &Frame);
+
+    /// Re-enable the IRQs disabled by the IRQ handlers. Called by the CAN task
+    /// after it has drained the received frames and filled the transmit
+    /// mailboxes.
+    fn enable_interrupts(&mut self) {}
+}
@@ -0,0 +1,131 @@
+/// The identifier of a CAN frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanId {
+    /// An 11-bit standard identifier.
+    Standard(u16),
+    /// A 29-bit extended identifier.
+    Extended(u32),
+}
+
+impl CanId {
+    /// The largest standard identifier.
+    pub const MAX_STANDARD: u16 = 0x7FF;
+
+    /// The largest extended identifier.
+    pub const MAX_EXTENDED: u32 = 0x1FFF_FFFF;
+
+    /// The identifier as a number.
+    pub fn raw(&self) -> u32 {
+        match self {
+            Self::Standard(id) => *id as u32,
+            Self::Extended(id) => *id,
+        }
+    }
+
+    /// Return if the identifier is an extended one.
+    pub fn is_extended(&self) -> bool {
+        matches!(self, Self::Extended(_))
+    }
+
+    fn is_valid(&self) -> bool {
+        match self {
+            Self::Standard(id) => *id <= Self::MAX_STANDARD,
+            Self::Extended(id) => *id <= Self::MAX_EXTENDED,
+        }
+    }
+
+    /// A key ordering frames as the bus arbitration does. A smaller key wins
+    /// the arbitration. A standard identifier is compared with the 11 most
+    /// significant bits of an extended one and wins a tie.
+    pub(super) fn arbitration_key(&self) -> u32 {
+        match self {
+            Self::Standard(id) => (*id as u32) << 19,
+            Self::Extended(id) => ((id >> 18) << 19) | (1 << 18) | (id & 0x3_FFFF),
+        }
+    }
+}
+
+/// A classic CAN data frame carrying up to 8 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    id: CanId,
+    len: u8,
+    data: [u8; 8],
+}
+
+impl Frame {
+    /// Create a frame. Return `None` if the identifier is out of range or
+    /// the data is longer than 8 bytes.
+    pub fn new(id: CanId, data: &[u8]) -> Option<Self> {
+        if !id.is_valid() || data.len() > 8 {
+            return None;
+        }
+        let mut frame = Self {
+            id,
+            len: data.len() as u8,
+            data: [0; 8],
+        };
+        frame.data[..data.len()].copy_from_slice(data);
+        Some(frame)
+    }
+
+    /// The identifier of the frame.
+    pub fn id(&self) -> CanId {
+        self.id
+    }
+
+    /// The data carried by the frame.
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+}
+
+/// Select received frames by identifier. A frame is accepted if its
+/// identifier has the same kind as the filter and equals the filter
+/// identifier in all bits set in the mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Filter {
+    /// `None` if both kinds of identifiers are accepted.
+    extended: Option<bool>,
+    id: u32,
+    mask: u32,
+}
+
+impl Filter {
+    /// Accept all frames.
+    pub const fn accept_all() -> Self {
+        Self {
+            extended: None,
+            id: 0,
+            mask: 0,
+        }
+    }
+
+    /// Accept frames with a standard identifier matching `id` under `mask`.
+    pub const fn standard(id: u16, mask: u16) -> Self {
+        Self {
+            extended: Some(false),
+            id: id as u32,
+            mask: mask as u32,
+        }
+    }
+
+    /// Accept frames with an extended identifier matching `id` under `mask`.
+    pub const fn extended(id: u32, mask: u32) -> Self {
+        Self {
+            extended: Some(true),
+            id,
+            mask,
+        }
+    }
+
+    /// Return if the filter accepts the frame.
+    pub fn matches(&self, frame: &Frame) -> bool {
+        if let Some(extended) = self.extended {
+            if extended != frame.id.is_extended() {
+                return false;
+            }
+        }
+        (frame.id.raw() ^ self.id) & self.mask == 0
+    }
+}
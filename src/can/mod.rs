@@ -0,0 +1,254 @@
+//! CAN bus support, enabled by the `can` feature.
+//!
+//! The CAN controller is driven by a dedicated kernel task started by
+//! [`start`] with a [`CanDriver`], e.g., [`BxCan`] for the bxCAN controllers
+//! of STM32F4. The IRQ handlers of the controller only disable its IRQs and
+//! notify the task, which then drains the received frames and refills the
+//! transmit mailboxes. Each received frame is delivered to every
+//! [`Receiver`] whose [`Filter`] accepts it, through a channel with a
+//! capacity chosen by the receiver. Frames passed to [`send`] are queued and
+//! transmitted in the order of their bus arbitration priority, i.e., a frame
+//! with a smaller identifier is transmitted first.
+//!
+//! # Example
+//! ```rust
+//! can::start(BxCan::new(BxCanInstance::Can1, 0x001c_0004).unwrap()).unwrap();
+//!
+//! let rx = can::open::<8>(Filter::standard(0x100, 0x7F0));
+//! let frame = rx.recv();
+//! can::send(Frame::new(CanId::Standard(0x200), frame.data()).unwrap()).unwrap();
+//! ```
+
+#[cfg(not(any(feature = "stm32f401", feature = "stm32f410", feature = "stm32f411")))]
+mod bxcan;
+mod driver;
+mod frame;
+
+#[cfg(not(any(feature = "stm32f401", feature = "stm32f410", feature = "stm32f411")))]
+pub use bxcan::{BxCan, BxCanInstance};
+pub use driver::CanDriver;
+pub use frame::*;
+
+use crate::{
+    config,
+    sync::{create_channel, Consumer, Mailbox, Mutex, Producer},
+    task::{self, TaskBuildError},
+};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// Enumeration of errors of the CAN API.
+#[derive(Debug, PartialEq)]
+pub enum CanError {
+    /// [`start`] has not been called.
+    NotStarted,
+    /// [`start`] has already been called.
+    AlreadyStarted,
+    /// The CAN task cannot be spawned.
+    Task(TaskBuildError),
+    /// The transmit queue is full.
+    QueueFull,
+    /// The controller did not respond.
+    Hardware,
+}
+
+/// The sending half of a receiver channel with the capacity erased from the
+/// type.
+trait Sink: Send {
+    fn try_send_allow_isr(&self, frame: Frame) -> bool;
+}
+
+impl<const N: usize> Sink for Producer<Frame, N> {
+    fn try_send_allow_isr(&self, frame: Frame) -> bool {
+        self.try_produce_allow_isr(frame).is_ok()
+    }
+}
+
+/// A receiver as seen by the CAN task.
+struct Entry {
+    id: usize,
+    filter: Filter,
+    sink: Box<dyn Sink>,
+    dropped: Arc<AtomicU32>,
+}
+
+/// The state owned by the CAN task and borrowed by [`send`].
+struct CanBus {
+    driver: Box<dyn CanDriver>,
+    /// Frames waiting for a transmit mailbox, ordered by arbitration
+    /// priority.
+    tx_queue: Vec<Frame>,
+}
+
+impl CanBus {
+    /// Deliver received frames and transmit queued frames. Return if the
+    /// task should run again right away.
+    fn service(&mut self) -> bool {
+        while let Some(frame) = self.driver.receive() {
+            for entry in RECEIVERS.lock().iter() {
+                if entry.filter.matches(&frame) && !entry.sink.try_send_allow_isr(frame) {
+                    entry.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        while !self.tx_queue.is_empty() && self.driver.can_transmit() {
+            let frame = self.tx_queue.remove(0);
+            self.driver.transmit(&frame);
+        }
+        self.driver.enable_interrupts();
+
+        // A mailbox may have become empty before the IRQs were re-enabled.
+        !self.tx_queue.is_empty() && self.driver.can_transmit()
+    }
+}
+
+static BUS: Mutex<Option<CanBus>> = Mutex::new(None);
+
+static RECEIVERS: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+static NEXT_RECEIVER_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Notified to request the CAN task to service the controller.
+static CAN_MAILBOX: Mailbox = Mailbox::new();
+
+/// Request the CAN task to service the controller. Drivers should call this
+/// function from their IRQ handlers.
+pub fn notify_allow_isr() {
+    CAN_MAILBOX.notify_allow_isr();
+}
+
+/// Take over the controller with the given driver and spawn the CAN task.
+pub fn start<D>(driver: D) -> Result<(), CanError>
+where
+    D: CanDriver + 'static,
+{
+    {
+        let mut bus = BUS.lock();
+        if bus.is_some() {
+            return Err(CanError::AlreadyStarted);
+        }
+        *bus = Some(CanBus {
+            driver: Box::new(driver),
+            tx_queue: Vec::with_capacity(config::CAN_TX_QUEUE_LENGTH),
+        });
+    }
+
+    task::build()
+        .set_id(config::CAN_TASK_ID)
+        .set_name("can")
+        .set_priority(config::CAN_TASK_PRIORITY)
+        .set_entry(can_task)
+        .spawn()
+        .map_err(CanError::Task)
+}
+
+/// The body of the CAN task.
+fn can_task() {
+    loop {
+        let again = match BUS.lock().as_mut() {
+            Some(bus) => bus.service(),
+            None => false,
+        };
+        if !again {
+            CAN_MAILBOX.wait();
+        }
+    }
+}
+
+/// Queue the frame for transmission. Return [`CanError::QueueFull`] if
+/// [`CAN_TX_QUEUE_LENGTH`](config::CAN_TX_QUEUE_LENGTH) frames are already
+/// waiting.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn send(frame: Frame) -> Result<(), CanError> {
+    let mut bus = BUS.lock();
+    let bus = bus.as_mut().ok_or(CanError::NotStarted)?;
+    if bus.tx_queue.len() >= config::CAN_TX_QUEUE_LENGTH {
+        return Err(CanError::QueueFull);
+    }
+
+    // Queue after frames of the same priority to keep their order.
+    let key = frame.id().arbitration_key();
+    let pos = bus
+        .tx_queue
+        .iter()
+        .position(|queued| queued.id().arbitration_key() > key)
+        .unwrap_or(bus.tx_queue.len());
+    bus.tx_queue.insert(pos, frame);
+    CAN_MAILBOX.notify_allow_isr();
+    Ok(())
+}
+
+/// Receive frames accepted by the filter through a queue holding up to `N`
+/// frames. Receiving ends when the returned [`Receiver`] is dropped.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn open<const N: usize>(filter: Filter) -> Receiver<N> {
+    let (producer, consumer) = create_channel::<Frame, N>();
+    let id = NEXT_RECEIVER_ID.fetch_add(1, Ordering::Relaxed);
+    let dropped = Arc::new(AtomicU32::new(0));
+
+    RECEIVERS.lock().push(Entry {
+        id,
+        filter,
+        sink: Box::new(producer),
+        dropped: dropped.clone(),
+    });
+
+    Receiver {
+        id,
+        filter,
+        consumer,
+        dropped,
+    }
+}
+
+/// A receiver of frames accepted by a [`Filter`], with a queue holding up to
+/// `N` frames. Dropping the receiver stops the delivery.
+pub struct Receiver<const N: usize> {
+    id: usize,
+    filter: Filter,
+    consumer: Consumer<Frame, N>,
+    dropped: Arc<AtomicU32>,
+}
+
+impl<const N: usize> Receiver<N> {
+    /// Receive the next frame. If the queue is empty, block until a frame
+    /// arrives.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn recv(&self) -> Frame {
+        self.consumer.consume()
+    }
+
+    /// Receive the next frame if there is one, without blocking.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn try_recv_allow_isr(&self) -> Option<Frame> {
+        self.consumer.try_consume_allow_isr()
+    }
+
+    /// The filter of the receiver.
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    /// The number of accepted frames dropped because the queue was full.
+    pub fn dropped_count(&self) -> u32 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> Drop for Receiver<N> {
+    fn drop(&mut self) {
+        // Drop the entry after releasing the lock.
+        let entry = {
+            let mut receivers = RECEIVERS.lock();
+            receivers
+                .iter()
+                .position(|entry| entry.id == self.id)
+                .map(|idx| receivers.swap_remove(idx))
+        };
+        drop(entry);
+    }
+}
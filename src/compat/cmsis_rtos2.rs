@@ -0,0 +1,387 @@
+//! A subset of the CMSIS-RTOS2 C API implemented over Hopter's tasks and
+//! synchronization primitives, enabled by the `cmsis_rtos2` feature.
+//!
+//! The functions are exported with their CMSIS-RTOS2 names and C calling
+//! convention, so that middleware written against `cmsis_os2.h` can be linked
+//! into the same image without modification. The supported subset is:
+//!
+//! - Kernel: `osKernelInitialize`, `osKernelStart`, `osKernelGetTickCount`,
+//!   `osKernelGetTickFreq`.
+//! - Threads: `osThreadNew`, `osThreadYield`.
+//! - Delays: `osDelay`, `osDelayUntil`.
+//! - Semaphores: `osSemaphoreNew`, `osSemaphoreAcquire`, `osSemaphoreRelease`,
+//!   `osSemaphoreGetCount`, `osSemaphoreDelete`.
+//! - Mutexes: `osMutexNew`, `osMutexAcquire`, `osMutexRelease`,
+//!   `osMutexDelete`.
+//! - Message queues: `osMessageQueueNew`, `osMessageQueuePut`,
+//!   `osMessageQueueGet`, `osMessageQueueGetCount`, `osMessageQueueDelete`.
+//!
+//! Semantic differences from the specification:
+//!
+//! - The kernel is already running when the C code gets control, so
+//!   `osKernelInitialize` and `osKernelStart` do nothing.
+//! - Threads are always detached. Static control block and stack memory
+//!   given through the attributes are ignored, and the stack grows on demand.
+//! - Mutexes are neither recursive nor priority inheriting, and ownership is
+//!   not checked on release.
+//! - Message priorities are ignored, so messages are received in the order
+//!   they were put.
+//! - A message put from an ISR that preempts a thread accessing the same
+//!   queue becomes visible when the thread finishes the access. A get from
+//!   such an ISR finds the queue empty.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use super::common::{self, ByteQueue, WaitError, WAIT_FOREVER};
+use crate::{config, schedule::current, sync::Semaphore, task, time};
+use alloc::boxed::Box;
+use core::{
+    ffi::{c_char, c_void},
+    sync::atomic::{AtomicPtr, AtomicUsize, Ordering},
+};
+
+pub type osStatus_t = i32;
+pub const osOK: osStatus_t = 0;
+pub const osError: osStatus_t = -1;
+pub const osErrorTimeout: osStatus_t = -2;
+pub const osErrorResource: osStatus_t = -3;
+pub const osErrorParameter: osStatus_t = -4;
+/// Never returned, because Hopter treats memory exhaustion as fatal.
+pub const osErrorNoMemory: osStatus_t = -5;
+pub const osErrorISR: osStatus_t = -6;
+
+pub const osWaitForever: u32 = WAIT_FOREVER;
+
+pub type osPriority_t = i32;
+pub const osPriorityNone: osPriority_t = 0;
+pub const osPriorityIdle: osPriority_t = 1;
+pub const osPriorityNormal: osPriority_t = 24;
+pub const osPriorityISR: osPriority_t = 56;
+
+pub type osThreadFunc_t = unsafe extern "C" fn(argument: *mut c_void);
+pub type osThreadId_t = *mut c_void;
+pub type osSemaphoreId_t = *mut c_void;
+pub type osMutexId_t = *mut c_void;
+pub type osMessageQueueId_t = *mut c_void;
+
+#[repr(C)]
+pub struct osThreadAttr_t {
+    pub name: *const c_char,
+    pub attr_bits: u32,
+    pub cb_mem: *mut c_void,
+    pub cb_size: u32,
+    pub stack_mem: *mut c_void,
+    pub stack_size: u32,
+    pub priority: osPriority_t,
+    pub tz_module: u32,
+    pub reserved: u32,
+}
+
+/// Shared layout of the semaphore, mutex, and message queue attributes.
+#[repr(C)]
+pub struct osObjectAttr_t {
+    pub name: *const c_char,
+    pub attr_bits: u32,
+    pub cb_mem: *mut c_void,
+    pub cb_size: u32,
+}
+
+pub type osSemaphoreAttr_t = osObjectAttr_t;
+pub type osMutexAttr_t = osObjectAttr_t;
+
+#[repr(C)]
+pub struct osMessageQueueAttr_t {
+    pub name: *const c_char,
+    pub attr_bits: u32,
+    pub cb_mem: *mut c_void,
+    pub cb_size: u32,
+    pub mq_mem: *mut c_void,
+    pub mq_size: u32,
+}
+
+/// The C function pointer and its argument. They are bundled so that the
+/// entry closure can be sent to the new task.
+struct ThreadEntry {
+    func: osThreadFunc_t,
+    arg: AtomicPtr<c_void>,
+}
+
+/// Source of the opaque `osThreadId_t` values. Each spawned thread gets a
+/// distinct non-null value.
+static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// Map a CMSIS-RTOS2 priority, where a larger value means higher priority, to
+/// a Hopter priority, where a smaller value means higher priority. The idle
+/// priority level of Hopter is never returned.
+fn map_priority(prio: osPriority_t) -> Option<u8> {
+    let prio = match prio {
+        osPriorityNone => osPriorityNormal,
+        p if (osPriorityIdle..osPriorityISR).contains(&p) => p,
+        _ => return None,
+    };
+    let lowest = (config::TASK_PRIORITY_LEVELS - 2) as i32;
+    let span = osPriorityISR - 1 - osPriorityIdle;
+    let mapped = lowest - (prio - osPriorityIdle) * lowest / span;
+    Some(mapped as u8)
+}
+
+/// Convert the result of a wait into a CMSIS-RTOS2 status code.
+fn to_status(result: Result<(), WaitError>) -> osStatus_t {
+    match result {
+        Ok(()) => osOK,
+        Err(WaitError::WouldBlock) => osErrorResource,
+        Err(WaitError::Timeout) => osErrorTimeout,
+        Err(WaitError::BlockInIsr) => osErrorParameter,
+    }
+}
+
+/* ### Kernel ### */
+
+#[no_mangle]
+pub extern "C" fn osKernelInitialize() -> osStatus_t {
+    osOK
+}
+
+#[no_mangle]
+pub extern "C" fn osKernelStart() -> osStatus_t {
+    osOK
+}
+
+#[no_mangle]
+pub extern "C" fn osKernelGetTickCount() -> u32 {
+    time::get_tick()
+}
+
+#[no_mangle]
+pub extern "C" fn osKernelGetTickFreq() -> u32 {
+    1000
+}
+
+/* ### Threads ### */
+
+#[no_mangle]
+pub unsafe extern "C" fn osThreadNew(
+    func: Option<osThreadFunc_t>,
+    argument: *mut c_void,
+    attr: *const osThreadAttr_t,
+) -> osThreadId_t {
+    if current::is_in_isr_context() {
+        return core::ptr::null_mut();
+    }
+    let Some(func) = func else {
+        return core::ptr::null_mut();
+    };
+
+    let attr = unsafe { attr.as_ref() };
+    let prio = attr.map_or(osPriorityNormal, |attr| attr.priority);
+    let Some(prio) = map_priority(prio) else {
+        return core::ptr::null_mut();
+    };
+
+    let entry = ThreadEntry {
+        func,
+        arg: AtomicPtr::new(argument),
+    };
+    let mut builder = task::build()
+        .set_entry(move || unsafe { (entry.func)(entry.arg.load(Ordering::SeqCst)) })
+        .set_priority(prio);
+    if let Some(size) = attr.map(|attr| attr.stack_size).filter(|size| *size > 0) {
+        builder = builder.set_stack_init_size(size as usize);
+    }
+
+    match builder.spawn() {
+        Ok(()) => NEXT_THREAD_ID.fetch_add(1, Ordering::SeqCst) as osThreadId_t,
+        Err(_) => core::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn osThreadYield() -> osStatus_t {
+    if current::is_in_isr_context() {
+        return osErrorISR;
+    }
+    task::yield_current();
+    osOK
+}
+
+/* ### Delays ### */
+
+#[no_mangle]
+pub extern "C" fn osDelay(ticks: u32) -> osStatus_t {
+    if current::is_in_isr_context() {
+        return osErrorISR;
+    }
+    match time::sleep_ms(ticks) {
+        Ok(()) => osOK,
+        Err(_) => osErrorParameter,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn osDelayUntil(ticks: u32) -> osStatus_t {
+    if current::is_in_isr_context() {
+        return osErrorISR;
+    }
+    let delta = ticks.wrapping_sub(time::get_tick());
+    match time::sleep_ms(delta) {
+        Ok(()) => osOK,
+        Err(_) => osErrorParameter,
+    }
+}
+
+/* ### Semaphores ### */
+
+#[no_mangle]
+pub extern "C" fn osSemaphoreNew(
+    max_count: u32,
+    initial_count: u32,
+    _attr: *const osSemaphoreAttr_t,
+) -> osSemaphoreId_t {
+    if current::is_in_isr_context() || max_count == 0 || initial_count > max_count {
+        return core::ptr::null_mut();
+    }
+    let sem = Semaphore::new(max_count as usize, initial_count as usize);
+    Box::into_raw(Box::new(sem)) as osSemaphoreId_t
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn osSemaphoreAcquire(id: osSemaphoreId_t, timeout: u32) -> osStatus_t {
+    match unsafe { (id as *const Semaphore).as_ref() } {
+        Some(sem) => to_status(common::down_with_timeout(sem, timeout)),
+        None => osErrorParameter,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn osSemaphoreRelease(id: osSemaphoreId_t) -> osStatus_t {
+    match unsafe { (id as *const Semaphore).as_ref() } {
+        Some(sem) => to_status(common::up_with_timeout(sem, 0)),
+        None => osErrorParameter,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn osSemaphoreGetCount(id: osSemaphoreId_t) -> u32 {
+    match unsafe { (id as *const Semaphore).as_ref() } {
+        Some(sem) => sem.count() as u32,
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn osSemaphoreDelete(id: osSemaphoreId_t) -> osStatus_t {
+    if current::is_in_isr_context() {
+        return osErrorISR;
+    }
+    if id.is_null() {
+        return osErrorParameter;
+    }
+    drop(unsafe { Box::from_raw(id as *mut Semaphore) });
+    osOK
+}
+
+/* ### Mutexes ### */
+
+#[no_mangle]
+pub extern "C" fn osMutexNew(attr: *const osMutexAttr_t) -> osMutexId_t {
+    osSemaphoreNew(1, 1, attr)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn osMutexAcquire(id: osMutexId_t, timeout: u32) -> osStatus_t {
+    if current::is_in_isr_context() {
+        return osErrorISR;
+    }
+    unsafe { osSemaphoreAcquire(id, timeout) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn osMutexRelease(id: osMutexId_t) -> osStatus_t {
+    if current::is_in_isr_context() {
+        return osErrorISR;
+    }
+    unsafe { osSemaphoreRelease(id) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn osMutexDelete(id: osMutexId_t) -> osStatus_t {
+    unsafe { osSemaphoreDelete(id) }
+}
+
+/* ### Message queues ### */
+
+#[no_mangle]
+pub extern "C" fn osMessageQueueNew(
+    msg_count: u32,
+    msg_size: u32,
+    _attr: *const osMessageQueueAttr_t,
+) -> osMessageQueueId_t {
+    if current::is_in_isr_context() || msg_count == 0 || msg_size == 0 {
+        return core::ptr::null_mut();
+    }
+    match ByteQueue::new(msg_count as usize, msg_size as usize) {
+        Some(queue) => Box::into_raw(Box::new(queue)) as osMessageQueueId_t,
+        None => core::ptr::null_mut(),
+    }
+}
+
+/// In ISR context, the timeout must be zero.
+#[no_mangle]
+pub unsafe extern "C" fn osMessageQueuePut(
+    id: osMessageQueueId_t,
+    msg_ptr: *const c_void,
+    _msg_prio: u8,
+    timeout: u32,
+) -> osStatus_t {
+    let Some(queue) = (unsafe { (id as *const ByteQueue).as_ref() }) else {
+        return osErrorParameter;
+    };
+    if msg_ptr.is_null() {
+        return osErrorParameter;
+    }
+    to_status(unsafe { queue.send(msg_ptr as *const u8, timeout) })
+}
+
+/// In ISR context, the timeout must be zero.
+#[no_mangle]
+pub unsafe extern "C" fn osMessageQueueGet(
+    id: osMessageQueueId_t,
+    msg_ptr: *mut c_void,
+    msg_prio: *mut u8,
+    timeout: u32,
+) -> osStatus_t {
+    let Some(queue) = (unsafe { (id as *const ByteQueue).as_ref() }) else {
+        return osErrorParameter;
+    };
+    if msg_ptr.is_null() {
+        return osErrorParameter;
+    }
+
+    let status = to_status(unsafe { queue.recv(msg_ptr as *mut u8, timeout) });
+    if status == osOK {
+        if let Some(prio) = unsafe { msg_prio.as_mut() } {
+            *prio = 0;
+        }
+    }
+    status
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn osMessageQueueGetCount(id: osMessageQueueId_t) -> u32 {
+    match unsafe { (id as *const ByteQueue).as_ref() } {
+        Some(queue) => queue.len() as u32,
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn osMessageQueueDelete(id: osMessageQueueId_t) -> osStatus_t {
+    if current::is_in_isr_context() {
+        return osErrorISR;
+    }
+    if id.is_null() {
+        return osErrorParameter;
+    }
+    drop(unsafe { Box::from_raw(id as *mut ByteQueue) });
+    osOK
+}
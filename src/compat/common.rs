@@ -0,0 +1,352 @@
+//! Building blocks shared by the compatibility layers.
+
+use crate::{
+    schedule::current,
+    sync::{
+        Access, AllowPendOp, RefCellSchedSafe, RetryCounter, RunPendedOp, Semaphore, SoftLock, Spin,
+    },
+    unrecoverable::Lethal,
+};
+use alloc::{boxed::Box, sync::Arc};
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// The timeout value meaning to block indefinitely. Both CMSIS-RTOS2 and
+/// FreeRTOS use the same value.
+pub(super) const WAIT_FOREVER: u32 = 0xFFFF_FFFF;
+
+/// Reasons why a wait with timeout did not succeed.
+#[derive(Debug, PartialEq)]
+pub(super) enum WaitError {
+    /// The timeout is zero and the resource is not available.
+    WouldBlock,
+    /// The resource did not become available within the timeout.
+    Timeout,
+    /// A non-zero timeout was given in ISR context.
+    BlockInIsr,
+}
+
+/// Acquire the semaphore within the timeout given in ticks. A task waiting
+/// with a finite timeout blocks on the semaphore like any other waiter, and
+/// is woken up in the same order.
+pub(super) fn down_with_timeout(sem: &Semaphore, timeout: u32) -> Result<(), WaitError> {
+    match timeout {
+        0 => sem.try_down_allow_isr().map_err(|_| WaitError::WouldBlock),
+        _ if current::is_in_isr_context() => Err(WaitError::BlockInIsr),
+        WAIT_FOREVER => {
+            sem.down();
+            Ok(())
+        }
+        _ => sem.down_timeout(timeout).map_err(|_| WaitError::Timeout),
+    }
+}
+
+/// Release the semaphore within the timeout given in ticks.
+pub(super) fn up_with_timeout(sem: &Semaphore, timeout: u32) -> Result<(), WaitError> {
+    match timeout {
+        0 => sem.try_up_allow_isr().map_err(|_| WaitError::WouldBlock),
+        _ if current::is_in_isr_context() => Err(WaitError::BlockInIsr),
+        WAIT_FOREVER => {
+            sem.up();
+            Ok(())
+        }
+        _ => sem.up_timeout(timeout).map_err(|_| WaitError::Timeout),
+    }
+}
+
+/// The maximum number of messages a [`ByteQueue`] can hold. The tail index
+/// and the number of reserved slots are packed in 16 bits each.
+const MAX_QUEUE_LENGTH: usize = u16::MAX as usize;
+
+/// A bounded queue of fixed-size messages copied in and out as raw bytes.
+///
+/// The messages are stored in a ring buffer allocated once at creation, so
+/// sending and receiving never allocate and are allowed in ISR context with
+/// a zero timeout. An ISR preempting a context that accesses the queue
+/// still copies its message into a slot, but the message becomes visible to
+/// receivers only when the preempted context finishes the access. An ISR
+/// receiving while the queue is being accessed finds it empty.
+pub(super) struct ByteQueue {
+    /// ISRs may preempt a context accessing the ring buffer, in which case
+    /// the preempted context publishes the messages they sent.
+    inner: RefCellSchedSafe<SoftLock<Inner>>,
+    /// The semaphore counting on the empty slots.
+    sem_empty: Semaphore,
+    /// The semaphore counting on the published messages. It is shared with
+    /// the ring buffer, which publishes the messages.
+    sem_occupied: Arc<Semaphore>,
+}
+
+/// The message storage and the slots reserved in it by senders.
+struct Slots {
+    buf: Box<[UnsafeCell<u8>]>,
+    msg_size: usize,
+    msg_count: usize,
+    /// The index of the slot after the last published message in the upper
+    /// 16 bits, and the number of slots reserved after it in the lower 16
+    /// bits. Packing them lets an ISR reserve a slot with a single atomic
+    /// increment.
+    state: AtomicU32,
+}
+
+struct Inner {
+    slots: Slots,
+    /// The index of the oldest published message. The spin lock around it is
+    /// only for sanity check. This field should not be accessed concurrently.
+    head: Spin<usize>,
+    sem_occupied: Arc<Semaphore>,
+}
+
+/// Representing full access to all fields of the [`ByteQueue`] ring buffer.
+struct InnerFullAccessor<'a> {
+    slots: &'a Slots,
+    head: &'a Spin<usize>,
+    sem_occupied: &'a Semaphore,
+}
+
+/// Representing pend-only access to the [`ByteQueue`] ring buffer. Only a
+/// slot can be reserved and written, which is published later.
+struct InnerPendAccessor<'a> {
+    slots: &'a Slots,
+}
+
+/// Bind the accessor types.
+impl<'a> AllowPendOp<'a> for Inner {
+    type FullAccessor = InnerFullAccessor<'a>;
+    type PendOnlyAccessor = InnerPendAccessor<'a>;
+    fn full_access(&'a self) -> InnerFullAccessor<'a> {
+        InnerFullAccessor {
+            slots: &self.slots,
+            head: &self.head,
+            sem_occupied: &self.sem_occupied,
+        }
+    }
+    fn pend_only_access(&'a self) -> InnerPendAccessor<'a> {
+        InnerPendAccessor { slots: &self.slots }
+    }
+}
+
+/// A pended operation is always sending a message. Publish the messages
+/// written by ISRs.
+impl<'a> RunPendedOp for InnerFullAccessor<'a> {
+    fn run_pended_op(&mut self) {
+        self.publish();
+    }
+}
+
+impl Slots {
+    /// Reserve the slot after the last reserved one and copy the message
+    /// from `src` into it. The caller must have taken an empty slot.
+    ///
+    /// # Safety
+    /// `src` must be valid for reading the message size.
+    unsafe fn write(&self, src: *const u8) {
+        // Any context can preempt another one between reserving and writing
+        // its slot, but each writes only the slot it reserved.
+        let (tail, reserved) = unpack(self.state.fetch_add(1, Ordering::SeqCst));
+        let index = (tail + reserved) % self.msg_count;
+        unsafe { core::ptr::copy_nonoverlapping(src, self.slot_ptr(index), self.msg_size) };
+    }
+
+    /// Return the pointer to the first byte of the slot at `index`.
+    fn slot_ptr(&self, index: usize) -> *mut u8 {
+        let base = UnsafeCell::raw_get(self.buf.as_ptr());
+        unsafe { base.add(index * self.msg_size) }
+    }
+}
+
+impl<'a> InnerFullAccessor<'a> {
+    /// Publish all reserved slots. The contexts that reserved them have
+    /// finished writing them, because they either are the owner of the full
+    /// access or have preempted it and returned.
+    fn publish(&self) {
+        let slots = self.slots;
+        let mut retries = RetryCounter::new();
+        loop {
+            let state = slots.state.load(Ordering::SeqCst);
+            let (tail, reserved) = unpack(state);
+            if reserved == 0 {
+                return;
+            }
+
+            let new_state = pack((tail + reserved) % slots.msg_count, 0);
+            if slots
+                .state
+                .compare_exchange(state, new_state, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                // Each reserved slot holds an empty slot permit, so the
+                // semaphore cannot exceed its maximum.
+                self.sem_occupied.up_n_allow_isr(reserved).unwrap_or_die();
+                return;
+            }
+
+            // A higher priority ISR reserved a slot in between.
+            retries.retry();
+        }
+    }
+
+    /// Copy the oldest published message into `dst` and free its slot. The
+    /// caller must have taken an occupied slot.
+    ///
+    /// # Safety
+    /// `dst` must be valid for writing the message size.
+    unsafe fn take(&self, dst: *mut u8) {
+        let slots = self.slots;
+        let mut head = self.head.lock_now_or_die();
+        let src = slots.slot_ptr(*head);
+        unsafe { core::ptr::copy_nonoverlapping(src, dst, slots.msg_size) };
+        *head = (*head + 1) % slots.msg_count;
+    }
+}
+
+fn pack(tail: usize, reserved: usize) -> u32 {
+    ((tail as u32) << 16) | reserved as u32
+}
+
+fn unpack(state: u32) -> (usize, usize) {
+    ((state >> 16) as usize, (state & 0xFFFF) as usize)
+}
+
+impl ByteQueue {
+    /// Create a queue holding up to `msg_count` messages of `msg_size`
+    /// bytes. Return `None` if `msg_count` exceeds [`MAX_QUEUE_LENGTH`].
+    pub(super) fn new(msg_count: usize, msg_size: usize) -> Option<Self> {
+        if msg_count > MAX_QUEUE_LENGTH {
+            return None;
+        }
+
+        let buf = core::iter::repeat_with(|| UnsafeCell::new(0))
+            .take(msg_count * msg_size)
+            .collect();
+        let sem_occupied = Arc::new(Semaphore::new(msg_count, 0));
+        let inner = Inner {
+            slots: Slots {
+                buf,
+                msg_size,
+                msg_count,
+                state: AtomicU32::new(0),
+            },
+            head: Spin::new(0),
+            sem_occupied: sem_occupied.clone(),
+        };
+        Some(Self {
+            inner: RefCellSchedSafe::new(SoftLock::new(inner)),
+            sem_empty: Semaphore::new(msg_count, msg_count),
+            sem_occupied,
+        })
+    }
+
+    /// Copy a message from `src` into the back of the queue, waiting for a
+    /// free slot within the timeout given in ticks.
+    ///
+    /// Calling this method in ISR context is allowed with a zero timeout.
+    ///
+    /// # Safety
+    /// `src` must be valid for reading the message size of the queue.
+    pub(super) unsafe fn send(&self, src: *const u8, timeout: u32) -> Result<(), WaitError> {
+        if timeout == 0 {
+            return unsafe { self.try_send_allow_isr(src) };
+        }
+        down_with_timeout(&self.sem_empty, timeout)?;
+        unsafe { self.put(src) };
+        Ok(())
+    }
+
+    /// Copy a message from `src` into the back of the queue if there is a
+    /// free slot. Otherwise, return `WouldBlock`.
+    ///
+    /// Calling this method in ISR context is allowed.
+    ///
+    /// # Safety
+    /// `src` must be valid for reading the message size of the queue.
+    pub(super) unsafe fn try_send_allow_isr(&self, src: *const u8) -> Result<(), WaitError> {
+        self.sem_empty
+            .try_down_allow_isr()
+            .map_err(|_| WaitError::WouldBlock)?;
+        unsafe { self.put(src) };
+        Ok(())
+    }
+
+    /// Copy the message at the front of the queue into `dst`, waiting for a
+    /// message within the timeout given in ticks.
+    ///
+    /// Calling this method in ISR context is allowed with a zero timeout.
+    ///
+    /// # Safety
+    /// `dst` must be valid for writing the message size of the queue.
+    pub(super) unsafe fn recv(&self, dst: *mut u8, timeout: u32) -> Result<(), WaitError> {
+        if timeout == 0 {
+            return unsafe { self.try_recv_allow_isr(dst) };
+        }
+        down_with_timeout(&self.sem_occupied, timeout)?;
+
+        // Should always grant full access to a task.
+        self.inner.with_suspended_scheduler(|inner, _| {
+            inner.must_with_full_access(|full_access| unsafe { full_access.take(dst) })
+        });
+        self.sem_empty.try_up_allow_isr().unwrap_or_die();
+        Ok(())
+    }
+
+    /// Copy the message at the front of the queue into `dst` if there is
+    /// one. Otherwise, return `WouldBlock`.
+    ///
+    /// Calling this method in ISR context is allowed. If it preempts a
+    /// context accessing the queue, it returns `WouldBlock`.
+    ///
+    /// # Safety
+    /// `dst` must be valid for writing the message size of the queue.
+    pub(super) unsafe fn try_recv_allow_isr(&self, dst: *mut u8) -> Result<(), WaitError> {
+        self.sem_occupied
+            .try_down_allow_isr()
+            .map_err(|_| WaitError::WouldBlock)?;
+
+        let taken = self.inner.with_suspended_scheduler(|inner, _| {
+            inner.with_access(|access| match access {
+                Access::Full { full_access } => {
+                    unsafe { full_access.take(dst) };
+                    true
+                }
+                Access::PendOnly { .. } => false,
+            })
+        });
+        if !taken {
+            // Give back the message for the preempted context or a later
+            // receiver.
+            self.sem_occupied.try_up_allow_isr().unwrap_or_die();
+            return Err(WaitError::WouldBlock);
+        }
+        self.sem_empty.try_up_allow_isr().unwrap_or_die();
+        Ok(())
+    }
+
+    /// Copy the message into a slot and publish it, or let the owner of the
+    /// full access publish it. The caller must have taken an empty slot.
+    ///
+    /// # Safety
+    /// `src` must be valid for reading the message size of the queue.
+    unsafe fn put(&self, src: *const u8) {
+        self.inner.with_suspended_scheduler(|inner, _| {
+            inner.with_access(|access| match access {
+                Access::Full { full_access } => {
+                    unsafe { full_access.slots.write(src) };
+                    full_access.publish();
+                }
+                Access::PendOnly { pend_access } => unsafe { pend_access.slots.write(src) },
+            })
+        })
+    }
+
+    /// The number of messages in the queue.
+    pub(super) fn len(&self) -> usize {
+        self.sem_occupied.count()
+    }
+
+    /// The number of free slots in the queue.
+    pub(super) fn free_slots(&self) -> usize {
+        self.sem_empty.count()
+    }
+}
@@ -0,0 +1,389 @@
+//! A stable C interface to the core kernel services, enabled by the `ffi`
+//! feature.
+//!
+//! The interface lets C modules compiled into the same image as Hopter spawn
+//! tasks, sleep, and use mutexes, semaphores, channels, and logging. The C
+//! declarations are in `include/hopter.h`, which is generated from this file
+//! with `cbindgen --config cbindgen.toml --output include/hopter.h`.
+//!
+//! All functions returning `int32_t` return [`HOPTER_OK`] on success or a
+//! negative error code. Kernel objects are handed out as opaque pointers and
+//! must be released with the corresponding `_delete` function. Functions
+//! that create or delete objects, or that may block, must not be called from
+//! ISRs. Timeouts are given in milliseconds, where zero means not to block
+//! and [`HOPTER_WAIT_FOREVER`] means to block indefinitely.
+
+use super::common::{self, ByteQueue, WaitError, WAIT_FOREVER};
+use crate::{
+    debug::log::{self, Level},
+    schedule::current,
+    sync::{Mutex, MutexGuard, Semaphore},
+    task::{self, TaskBuildError},
+    time,
+};
+use alloc::boxed::Box;
+use core::{
+    cell::UnsafeCell,
+    ffi::{c_char, c_void, CStr},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+/// The operation succeeded.
+pub const HOPTER_OK: i32 = 0;
+/// An argument is invalid, e.g., a null pointer.
+pub const HOPTER_ERR_PARAM: i32 = -1;
+/// The function is not allowed in ISR context.
+pub const HOPTER_ERR_ISR: i32 = -2;
+/// The resource is not available and the timeout is zero.
+pub const HOPTER_ERR_WOULD_BLOCK: i32 = -3;
+/// The resource did not become available within the timeout.
+pub const HOPTER_ERR_TIMEOUT: i32 = -4;
+/// The maximum number of tasks has been reached.
+pub const HOPTER_ERR_NO_MORE_TASK: i32 = -5;
+/// The task priority is not allowed by the configuration.
+pub const HOPTER_ERR_PRIORITY: i32 = -6;
+
+/// Block until the operation can complete.
+pub const HOPTER_WAIT_FOREVER: u32 = WAIT_FOREVER;
+
+/// Log level for errors.
+pub const HOPTER_LOG_ERROR: u32 = 1;
+/// Log level for warnings.
+pub const HOPTER_LOG_WARN: u32 = 2;
+/// Log level for informational messages.
+pub const HOPTER_LOG_INFO: u32 = 3;
+/// Log level for debugging messages.
+pub const HOPTER_LOG_DEBUG: u32 = 4;
+
+/// The entry function of a task spawned from C.
+pub type HopterTaskEntry = unsafe extern "C" fn(arg: *mut c_void);
+
+/// Opaque handle of a mutex.
+pub struct HopterMutex {
+    /// The guard of the owning task. Only the owner accesses the slot. It is
+    /// declared first so that it is dropped before the mutex.
+    guard: UnsafeCell<Option<MutexGuard<'static, ()>>>,
+    mutex: Mutex<()>,
+}
+
+/// Opaque handle of a semaphore.
+pub struct HopterSemaphore {
+    sem: Semaphore,
+}
+
+/// Opaque handle of a channel carrying fixed-size items.
+pub struct HopterChannel {
+    queue: ByteQueue,
+}
+
+fn wait_result_to_code(result: Result<(), WaitError>) -> i32 {
+    match result {
+        Ok(()) => HOPTER_OK,
+        Err(WaitError::WouldBlock) => HOPTER_ERR_WOULD_BLOCK,
+        Err(WaitError::Timeout) => HOPTER_ERR_TIMEOUT,
+        Err(WaitError::BlockInIsr) => HOPTER_ERR_ISR,
+    }
+}
+
+/* ### Tasks ### */
+
+/// The C function pointer and its argument. They are bundled so that the
+/// entry closure can be sent to the new task.
+struct TaskEntry {
+    func: HopterTaskEntry,
+    arg: AtomicPtr<c_void>,
+}
+
+/// Spawn a task running `entry(arg)`. A `stack_size` of zero selects the
+/// default initial stack size.
+#[no_mangle]
+pub extern "C" fn hopter_task_spawn(
+    entry: Option<HopterTaskEntry>,
+    arg: *mut c_void,
+    id: u8,
+    priority: u8,
+    stack_size: usize,
+) -> i32 {
+    if current::is_in_isr_context() {
+        return HOPTER_ERR_ISR;
+    }
+    let Some(func) = entry else {
+        return HOPTER_ERR_PARAM;
+    };
+
+    let entry = TaskEntry {
+        func,
+        arg: AtomicPtr::new(arg),
+    };
+    let mut builder = task::build()
+        .set_entry(move || unsafe { (entry.func)(entry.arg.load(Ordering::SeqCst)) })
+        .set_id(id)
+        .set_priority(priority);
+    if stack_size > 0 {
+        builder = builder.set_stack_init_size(stack_size);
+    }
+
+    match builder.spawn() {
+        Ok(()) => HOPTER_OK,
+        Err(TaskBuildError::NoMoreTask) => HOPTER_ERR_NO_MORE_TASK,
+        Err(TaskBuildError::PriorityNotAllowed) => HOPTER_ERR_PRIORITY,
+        Err(_) => HOPTER_ERR_PARAM,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn hopter_task_yield() {
+    task::yield_current();
+}
+
+#[no_mangle]
+pub extern "C" fn hopter_task_current_id() -> u8 {
+    task::get_current_id()
+}
+
+/* ### Time ### */
+
+#[no_mangle]
+pub extern "C" fn hopter_sleep_ms(ms: u32) -> i32 {
+    match time::sleep_ms(ms) {
+        Ok(()) => HOPTER_OK,
+        Err(_) => HOPTER_ERR_ISR,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn hopter_get_tick() -> u32 {
+    time::get_tick()
+}
+
+/* ### Mutexes ### */
+
+/// Create a mutex with priority inheritance. Return null in ISR context.
+#[no_mangle]
+pub extern "C" fn hopter_mutex_new() -> *mut HopterMutex {
+    if current::is_in_isr_context() {
+        return core::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(HopterMutex {
+        guard: UnsafeCell::new(None),
+        mutex: Mutex::new(()),
+    }))
+}
+
+/// Store the guard of a freshly acquired mutex in its slot.
+///
+/// Safety: The mutex must have been created by [`hopter_mutex_new`] and not
+/// been deleted, which keeps the mutex at a fixed address while it is locked.
+unsafe fn mutex_store_guard(mutex: &HopterMutex, guard: MutexGuard<'_, ()>) {
+    let guard: MutexGuard<'static, ()> = unsafe { core::mem::transmute(guard) };
+    unsafe { *mutex.guard.get() = Some(guard) };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hopter_mutex_lock(mutex: *mut HopterMutex) -> i32 {
+    if current::is_in_isr_context() {
+        return HOPTER_ERR_ISR;
+    }
+    let Some(mutex) = (unsafe { mutex.as_ref() }) else {
+        return HOPTER_ERR_PARAM;
+    };
+    let guard = mutex.mutex.lock();
+    unsafe { mutex_store_guard(mutex, guard) };
+    HOPTER_OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hopter_mutex_try_lock(mutex: *mut HopterMutex) -> i32 {
+    if current::is_in_isr_context() {
+        return HOPTER_ERR_ISR;
+    }
+    let Some(mutex) = (unsafe { mutex.as_ref() }) else {
+        return HOPTER_ERR_PARAM;
+    };
+    match mutex.mutex.try_lock() {
+        Some(guard) => {
+            unsafe { mutex_store_guard(mutex, guard) };
+            HOPTER_OK
+        }
+        None => HOPTER_ERR_WOULD_BLOCK,
+    }
+}
+
+/// Unlock the mutex. Must be called by the task that locked it.
+#[no_mangle]
+pub unsafe extern "C" fn hopter_mutex_unlock(mutex: *mut HopterMutex) -> i32 {
+    if current::is_in_isr_context() {
+        return HOPTER_ERR_ISR;
+    }
+    let Some(mutex) = (unsafe { mutex.as_ref() }) else {
+        return HOPTER_ERR_PARAM;
+    };
+    match unsafe { (*mutex.guard.get()).take() } {
+        Some(guard) => {
+            drop(guard);
+            HOPTER_OK
+        }
+        None => HOPTER_ERR_PARAM,
+    }
+}
+
+/// Delete the mutex. It must not be locked.
+#[no_mangle]
+pub unsafe extern "C" fn hopter_mutex_delete(mutex: *mut HopterMutex) -> i32 {
+    if current::is_in_isr_context() {
+        return HOPTER_ERR_ISR;
+    }
+    if mutex.is_null() {
+        return HOPTER_ERR_PARAM;
+    }
+    drop(unsafe { Box::from_raw(mutex) });
+    HOPTER_OK
+}
+
+/* ### Semaphores ### */
+
+/// Create a semaphore. Return null in ISR context or if the counts are
+/// invalid.
+#[no_mangle]
+pub extern "C" fn hopter_semaphore_new(max_count: u32, init_count: u32) -> *mut HopterSemaphore {
+    if current::is_in_isr_context() || max_count == 0 || init_count > max_count {
+        return core::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(HopterSemaphore {
+        sem: Semaphore::new(max_count as usize, init_count as usize),
+    }))
+}
+
+/// Increment the semaphore. In ISR context, the timeout must be zero.
+#[no_mangle]
+pub unsafe extern "C" fn hopter_semaphore_up(sem: *mut HopterSemaphore, timeout_ms: u32) -> i32 {
+    match unsafe { sem.as_ref() } {
+        Some(sem) => wait_result_to_code(common::up_with_timeout(&sem.sem, timeout_ms)),
+        None => HOPTER_ERR_PARAM,
+    }
+}
+
+/// Decrement the semaphore. In ISR context, the timeout must be zero.
+#[no_mangle]
+pub unsafe extern "C" fn hopter_semaphore_down(sem: *mut HopterSemaphore, timeout_ms: u32) -> i32 {
+    match unsafe { sem.as_ref() } {
+        Some(sem) => wait_result_to_code(common::down_with_timeout(&sem.sem, timeout_ms)),
+        None => HOPTER_ERR_PARAM,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hopter_semaphore_count(sem: *mut HopterSemaphore) -> u32 {
+    match unsafe { sem.as_ref() } {
+        Some(sem) => sem.sem.count() as u32,
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hopter_semaphore_delete(sem: *mut HopterSemaphore) -> i32 {
+    if current::is_in_isr_context() {
+        return HOPTER_ERR_ISR;
+    }
+    if sem.is_null() {
+        return HOPTER_ERR_PARAM;
+    }
+    drop(unsafe { Box::from_raw(sem) });
+    HOPTER_OK
+}
+
+/* ### Channels ### */
+
+/// Create a channel holding up to `capacity` items of `item_size` bytes.
+/// Return null in ISR context or if either argument is zero.
+#[no_mangle]
+pub extern "C" fn hopter_channel_new(capacity: u32, item_size: u32) -> *mut HopterChannel {
+    if current::is_in_isr_context() || capacity == 0 || item_size == 0 {
+        return core::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(HopterChannel {
+        queue: ByteQueue::new(capacity as usize, item_size as usize),
+    }))
+}
+
+/// Copy an item from `item` into the channel. Not allowed in ISR context,
+/// because memory cannot be allocated there.
+#[no_mangle]
+pub unsafe extern "C" fn hopter_channel_send(
+    chan: *mut HopterChannel,
+    item: *const c_void,
+    timeout_ms: u32,
+) -> i32 {
+    if current::is_in_isr_context() {
+        return HOPTER_ERR_ISR;
+    }
+    let Some(chan) = (unsafe { chan.as_ref() }) else {
+        return HOPTER_ERR_PARAM;
+    };
+    if item.is_null() {
+        return HOPTER_ERR_PARAM;
+    }
+    wait_result_to_code(unsafe { chan.queue.send(item as *const u8, timeout_ms) })
+}
+
+/// Copy an item out of the channel into `item`. Not allowed in ISR context,
+/// because memory cannot be freed there.
+#[no_mangle]
+pub unsafe extern "C" fn hopter_channel_recv(
+    chan: *mut HopterChannel,
+    item: *mut c_void,
+    timeout_ms: u32,
+) -> i32 {
+    if current::is_in_isr_context() {
+        return HOPTER_ERR_ISR;
+    }
+    let Some(chan) = (unsafe { chan.as_ref() }) else {
+        return HOPTER_ERR_PARAM;
+    };
+    if item.is_null() {
+        return HOPTER_ERR_PARAM;
+    }
+    wait_result_to_code(unsafe { chan.queue.recv(item as *mut u8, timeout_ms) })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hopter_channel_len(chan: *mut HopterChannel) -> u32 {
+    match unsafe { chan.as_ref() } {
+        Some(chan) => chan.queue.len() as u32,
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hopter_channel_delete(chan: *mut HopterChannel) -> i32 {
+    if current::is_in_isr_context() {
+        return HOPTER_ERR_ISR;
+    }
+    if chan.is_null() {
+        return HOPTER_ERR_PARAM;
+    }
+    drop(unsafe { Box::from_raw(chan) });
+    HOPTER_OK
+}
+
+/* ### Logging ### */
+
+/// Log the null-terminated message through [`log`] if `level` does not
+/// exceed the tunable log level.
+#[no_mangle]
+pub unsafe extern "C" fn hopter_log(level: u32, msg: *const c_char) {
+    if msg.is_null() {
+        return;
+    }
+    let level = match level {
+        HOPTER_LOG_ERROR => Level::Error,
+        HOPTER_LOG_WARN => Level::Warn,
+        HOPTER_LOG_INFO => Level::Info,
+        _ => Level::Debug,
+    };
+    let msg = unsafe { CStr::from_ptr(msg) };
+    log::log(
+        level,
+        format_args!("{}", msg.to_str().unwrap_or("<invalid utf-8>")),
+    );
+}
@@ -0,0 +1,515 @@
+//! A subset of the FreeRTOS C API implemented over Hopter's tasks and
+//! synchronization primitives, enabled by the `freertos` feature.
+//!
+//! Many FreeRTOS APIs are macros expanding to generic kernel functions, e.g.,
+//! `xQueueSend` expands to `xQueueGenericSend`. Instead of mimicking those
+//! internals, the functions here are exported under the public API names with
+//! C calling convention. A migrating code base should include a small header
+//! declaring these functions in place of `FreeRTOS.h`. The supported subset
+//! is:
+//!
+//! - Tasks: `xTaskCreate`, `xTaskGetCurrentTaskHandle`, `vTaskDelay`,
+//!   `vTaskDelayUntil`, `xTaskGetTickCount`, `xTaskGetTickCountFromISR`.
+//! - Queues: `xQueueCreate`, `xQueueSend`, `xQueueSendToBack`,
+//!   `xQueueSendFromISR`, `xQueueSendToBackFromISR`, `xQueueReceive`,
+//!   `xQueueReceiveFromISR`, `uxQueueMessagesWaiting`,
+//!   `uxQueueSpacesAvailable`, `vQueueDelete`.
+//! - Semaphores: `xSemaphoreCreateBinary`, `xSemaphoreCreateCounting`,
+//!   `xSemaphoreCreateMutex`, `xSemaphoreTake`, `xSemaphoreTakeFromISR`,
+//!   `xSemaphoreGive`, `xSemaphoreGiveFromISR`, `uxSemaphoreGetCount`,
+//!   `vSemaphoreDelete`.
+//! - Task notifications: `xTaskNotifyGive`, `vTaskNotifyGiveFromISR`,
+//!   `ulTaskNotifyTake`.
+//!
+//! Semantic differences from FreeRTOS:
+//!
+//! - The scheduler is already running when the C code gets control, so there
+//!   is no `vTaskStartScheduler`.
+//! - A tick is one millisecond.
+//! - FreeRTOS priorities range from 0 to `configMAX_PRIORITIES - 1` with a
+//!   larger value meaning higher priority. They are mapped onto the non-idle
+//!   Hopter priorities in reverse order.
+//! - The stack depth given to `xTaskCreate` only sets the initial stack size.
+//!   The stack grows on demand.
+//! - A task ends when its function returns. `vTaskDelete` is not supported.
+//! - Mutexes are neither recursive nor priority inheriting, and ownership is
+//!   not checked on give.
+//! - A message sent from an ISR that preempts a task accessing the same
+//!   queue becomes visible when the task finishes the access. A receive from
+//!   such an ISR finds the queue empty.
+//! - The `FromISR` variants never request a context switch through
+//!   `pxHigherPriorityTaskWoken`, because Hopter reschedules on ISR exit by
+//!   itself.
+//! - A task notification is a counter, i.e., only the `xTaskNotifyGive` and
+//!   `ulTaskNotifyTake` style is supported. Tasks not created through
+//!   `xTaskCreate` get their notification state allocated on first use, which
+//!   is never reclaimed.
+
+#![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
+
+use super::common::{self, ByteQueue, WaitError, WAIT_FOREVER};
+use crate::{
+    config,
+    schedule::current,
+    sync::{Mailbox, Semaphore, SpinSchedSafe},
+    task, time,
+};
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    ffi::{c_char, c_void},
+    sync::atomic::{AtomicPtr, AtomicU32, Ordering},
+};
+
+pub type BaseType_t = i32;
+pub type UBaseType_t = u32;
+pub type TickType_t = u32;
+
+pub const pdFALSE: BaseType_t = 0;
+pub const pdTRUE: BaseType_t = 1;
+pub const pdFAIL: BaseType_t = pdFALSE;
+pub const pdPASS: BaseType_t = pdTRUE;
+pub const errQUEUE_EMPTY: BaseType_t = pdFALSE;
+pub const errQUEUE_FULL: BaseType_t = pdFALSE;
+pub const errCOULD_NOT_ALLOCATE_REQUIRED_MEMORY: BaseType_t = -1;
+
+pub const portMAX_DELAY: TickType_t = WAIT_FOREVER;
+
+/// The number of FreeRTOS priority levels. The idle priority level of Hopter
+/// is not exposed.
+pub const configMAX_PRIORITIES: UBaseType_t = (config::TASK_PRIORITY_LEVELS - 1) as UBaseType_t;
+
+pub type TaskFunction_t = unsafe extern "C" fn(parameters: *mut c_void);
+pub type TaskHandle_t = *mut c_void;
+pub type QueueHandle_t = *mut c_void;
+pub type SemaphoreHandle_t = *mut c_void;
+
+/// The object backing a `TaskHandle_t`.
+struct TaskCb {
+    /// The notification value of the task.
+    notify_value: AtomicU32,
+    /// Notified whenever the notification value is incremented.
+    mailbox: Mailbox,
+}
+
+impl TaskCb {
+    fn new_leaked() -> &'static TaskCb {
+        Box::leak(Box::new(TaskCb {
+            notify_value: AtomicU32::new(0),
+            mailbox: Mailbox::new(),
+        }))
+    }
+
+    fn notify_give(&self) {
+        self.notify_value.fetch_add(1, Ordering::SeqCst);
+        self.mailbox.notify_allow_isr();
+    }
+
+    /// Take the notification value if it is non-zero, either by clearing it
+    /// or by decrementing it. Return the value before taking.
+    fn try_take(&self, clear: bool) -> u32 {
+        if clear {
+            self.notify_value.swap(0, Ordering::SeqCst)
+        } else {
+            self.notify_value
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |val| val.checked_sub(1))
+                .unwrap_or(0)
+        }
+    }
+}
+
+/// Control blocks of the running tasks keyed by the address of their Hopter
+/// task struct.
+static TASK_CBS: SpinSchedSafe<Vec<(usize, &'static TaskCb)>> = SpinSchedSafe::new(Vec::new());
+
+/// An identifier of the current task that stays the same while the task runs.
+fn current_task_key() -> usize {
+    current::with_cur_task(|task| task as *const _ as usize)
+}
+
+/// Get the control block of the current task, allocating one if the task was
+/// not created through `xTaskCreate`.
+fn current_task_cb() -> &'static TaskCb {
+    let key = current_task_key();
+    let mut task_cbs = TASK_CBS.lock();
+    if let Some((_, tcb)) = task_cbs.iter().find(|(k, _)| *k == key) {
+        return tcb;
+    }
+    let tcb = TaskCb::new_leaked();
+    task_cbs.push((key, tcb));
+    tcb
+}
+
+/// Map a FreeRTOS priority to a Hopter priority.
+fn map_priority(prio: UBaseType_t) -> Option<u8> {
+    if prio >= configMAX_PRIORITIES {
+        return None;
+    }
+    Some((configMAX_PRIORITIES - 1 - prio) as u8)
+}
+
+/// Convert the result of a wait into `pdTRUE` or `pdFALSE`.
+fn to_base_type(result: Result<(), WaitError>) -> BaseType_t {
+    match result {
+        Ok(()) => pdTRUE,
+        Err(_) => pdFALSE,
+    }
+}
+
+/// The C function pointer and its argument. They are bundled so that the
+/// entry closure can be sent to the new task.
+struct TaskEntry {
+    func: TaskFunction_t,
+    arg: AtomicPtr<c_void>,
+    tcb: &'static TaskCb,
+}
+
+impl TaskEntry {
+    fn run(self) {
+        let key = current_task_key();
+        TASK_CBS.lock().push((key, self.tcb));
+
+        unsafe { (self.func)(self.arg.load(Ordering::SeqCst)) };
+
+        // Another task struct may later be allocated at the same address.
+        TASK_CBS.lock().retain(|(k, _)| *k != key);
+    }
+}
+
+/* ### Tasks ### */
+
+/// `usStackDepth` is given in words as in FreeRTOS. The handle written to
+/// `pxCreatedTask` remains valid after the task ends.
+#[no_mangle]
+pub unsafe extern "C" fn xTaskCreate(
+    pxTaskCode: Option<TaskFunction_t>,
+    _pcName: *const c_char,
+    usStackDepth: u16,
+    pvParameters: *mut c_void,
+    uxPriority: UBaseType_t,
+    pxCreatedTask: *mut TaskHandle_t,
+) -> BaseType_t {
+    if current::is_in_isr_context() {
+        return pdFAIL;
+    }
+    let Some(func) = pxTaskCode else {
+        return pdFAIL;
+    };
+    let Some(prio) = map_priority(uxPriority) else {
+        return pdFAIL;
+    };
+
+    let tcb = TaskCb::new_leaked();
+    let entry = TaskEntry {
+        func,
+        arg: AtomicPtr::new(pvParameters),
+        tcb,
+    };
+    let mut builder = task::build()
+        .set_entry(move || entry.run())
+        .set_priority(prio);
+    if usStackDepth > 0 {
+        builder = builder.set_stack_init_size(usStackDepth as usize * 4);
+    }
+
+    match builder.spawn() {
+        Ok(()) => {
+            if let Some(handle) = unsafe { pxCreatedTask.as_mut() } {
+                *handle = tcb as *const TaskCb as TaskHandle_t;
+            }
+            pdPASS
+        }
+        Err(_) => errCOULD_NOT_ALLOCATE_REQUIRED_MEMORY,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn xTaskGetCurrentTaskHandle() -> TaskHandle_t {
+    current_task_cb() as *const TaskCb as TaskHandle_t
+}
+
+#[no_mangle]
+pub extern "C" fn vTaskDelay(xTicksToDelay: TickType_t) {
+    let _ = time::sleep_ms(xTicksToDelay);
+}
+
+/// Sleep until `*pxPreviousWakeTime + xTimeIncrement` and advance
+/// `*pxPreviousWakeTime` by the increment, for periodic execution.
+#[no_mangle]
+pub unsafe extern "C" fn vTaskDelayUntil(
+    pxPreviousWakeTime: *mut TickType_t,
+    xTimeIncrement: TickType_t,
+) {
+    let Some(prev) = (unsafe { pxPreviousWakeTime.as_mut() }) else {
+        return;
+    };
+    let wake = prev.wrapping_add(xTimeIncrement);
+    *prev = wake;
+
+    // Do not sleep if the wake up time has already passed.
+    let delta = wake.wrapping_sub(time::get_tick()) as i32;
+    if delta > 0 {
+        let _ = time::sleep_ms(delta as u32);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn xTaskGetTickCount() -> TickType_t {
+    time::get_tick()
+}
+
+#[no_mangle]
+pub extern "C" fn xTaskGetTickCountFromISR() -> TickType_t {
+    time::get_tick()
+}
+
+/* ### Queues ### */
+
+#[no_mangle]
+pub extern "C" fn xQueueCreate(
+    uxQueueLength: UBaseType_t,
+    uxItemSize: UBaseType_t,
+) -> QueueHandle_t {
+    if current::is_in_isr_context() || uxQueueLength == 0 || uxItemSize == 0 {
+        return core::ptr::null_mut();
+    }
+    match ByteQueue::new(uxQueueLength as usize, uxItemSize as usize) {
+        Some(queue) => Box::into_raw(Box::new(queue)) as QueueHandle_t,
+        None => core::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn xQueueSend(
+    xQueue: QueueHandle_t,
+    pvItemToQueue: *const c_void,
+    xTicksToWait: TickType_t,
+) -> BaseType_t {
+    unsafe { xQueueSendToBack(xQueue, pvItemToQueue, xTicksToWait) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn xQueueSendToBack(
+    xQueue: QueueHandle_t,
+    pvItemToQueue: *const c_void,
+    xTicksToWait: TickType_t,
+) -> BaseType_t {
+    if pvItemToQueue.is_null() {
+        return errQUEUE_FULL;
+    }
+    match unsafe { (xQueue as *const ByteQueue).as_ref() } {
+        Some(queue) => {
+            to_base_type(unsafe { queue.send(pvItemToQueue as *const u8, xTicksToWait) })
+        }
+        None => errQUEUE_FULL,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn xQueueSendFromISR(
+    xQueue: QueueHandle_t,
+    pvItemToQueue: *const c_void,
+    pxHigherPriorityTaskWoken: *mut BaseType_t,
+) -> BaseType_t {
+    unsafe { xQueueSendToBackFromISR(xQueue, pvItemToQueue, pxHigherPriorityTaskWoken) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn xQueueSendToBackFromISR(
+    xQueue: QueueHandle_t,
+    pvItemToQueue: *const c_void,
+    pxHigherPriorityTaskWoken: *mut BaseType_t,
+) -> BaseType_t {
+    if let Some(woken) = unsafe { pxHigherPriorityTaskWoken.as_mut() } {
+        *woken = pdFALSE;
+    }
+    unsafe { xQueueSendToBack(xQueue, pvItemToQueue, 0) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn xQueueReceive(
+    xQueue: QueueHandle_t,
+    pvBuffer: *mut c_void,
+    xTicksToWait: TickType_t,
+) -> BaseType_t {
+    if pvBuffer.is_null() {
+        return errQUEUE_EMPTY;
+    }
+    match unsafe { (xQueue as *const ByteQueue).as_ref() } {
+        Some(queue) => to_base_type(unsafe { queue.recv(pvBuffer as *mut u8, xTicksToWait) }),
+        None => errQUEUE_EMPTY,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn xQueueReceiveFromISR(
+    xQueue: QueueHandle_t,
+    pvBuffer: *mut c_void,
+    pxHigherPriorityTaskWoken: *mut BaseType_t,
+) -> BaseType_t {
+    if let Some(woken) = unsafe { pxHigherPriorityTaskWoken.as_mut() } {
+        *woken = pdFALSE;
+    }
+    unsafe { xQueueReceive(xQueue, pvBuffer, 0) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn uxQueueMessagesWaiting(xQueue: QueueHandle_t) -> UBaseType_t {
+    match unsafe { (xQueue as *const ByteQueue).as_ref() } {
+        Some(queue) => queue.len() as UBaseType_t,
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn uxQueueSpacesAvailable(xQueue: QueueHandle_t) -> UBaseType_t {
+    match unsafe { (xQueue as *const ByteQueue).as_ref() } {
+        Some(queue) => queue.free_slots() as UBaseType_t,
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vQueueDelete(xQueue: QueueHandle_t) {
+    if current::is_in_isr_context() || xQueue.is_null() {
+        return;
+    }
+    drop(unsafe { Box::from_raw(xQueue as *mut ByteQueue) });
+}
+
+/* ### Semaphores ### */
+
+fn new_semaphore(max_count: UBaseType_t, init_count: UBaseType_t) -> SemaphoreHandle_t {
+    if current::is_in_isr_context() || max_count == 0 || init_count > max_count {
+        return core::ptr::null_mut();
+    }
+    let sem = Semaphore::new(max_count as usize, init_count as usize);
+    Box::into_raw(Box::new(sem)) as SemaphoreHandle_t
+}
+
+/// The semaphore is created empty, as in FreeRTOS.
+#[no_mangle]
+pub extern "C" fn xSemaphoreCreateBinary() -> SemaphoreHandle_t {
+    new_semaphore(1, 0)
+}
+
+#[no_mangle]
+pub extern "C" fn xSemaphoreCreateCounting(
+    uxMaxCount: UBaseType_t,
+    uxInitialCount: UBaseType_t,
+) -> SemaphoreHandle_t {
+    new_semaphore(uxMaxCount, uxInitialCount)
+}
+
+#[no_mangle]
+pub extern "C" fn xSemaphoreCreateMutex() -> SemaphoreHandle_t {
+    new_semaphore(1, 1)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn xSemaphoreTake(
+    xSemaphore: SemaphoreHandle_t,
+    xTicksToWait: TickType_t,
+) -> BaseType_t {
+    match unsafe { (xSemaphore as *const Semaphore).as_ref() } {
+        Some(sem) => to_base_type(common::down_with_timeout(sem, xTicksToWait)),
+        None => pdFALSE,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn xSemaphoreTakeFromISR(
+    xSemaphore: SemaphoreHandle_t,
+    pxHigherPriorityTaskWoken: *mut BaseType_t,
+) -> BaseType_t {
+    if let Some(woken) = unsafe { pxHigherPriorityTaskWoken.as_mut() } {
+        *woken = pdFALSE;
+    }
+    unsafe { xSemaphoreTake(xSemaphore, 0) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn xSemaphoreGive(xSemaphore: SemaphoreHandle_t) -> BaseType_t {
+    match unsafe { (xSemaphore as *const Semaphore).as_ref() } {
+        Some(sem) => to_base_type(common::up_with_timeout(sem, 0)),
+        None => pdFALSE,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn xSemaphoreGiveFromISR(
+    xSemaphore: SemaphoreHandle_t,
+    pxHigherPriorityTaskWoken: *mut BaseType_t,
+) -> BaseType_t {
+    if let Some(woken) = unsafe { pxHigherPriorityTaskWoken.as_mut() } {
+        *woken = pdFALSE;
+    }
+    unsafe { xSemaphoreGive(xSemaphore) }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn uxSemaphoreGetCount(xSemaphore: SemaphoreHandle_t) -> UBaseType_t {
+    match unsafe { (xSemaphore as *const Semaphore).as_ref() } {
+        Some(sem) => sem.count() as UBaseType_t,
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vSemaphoreDelete(xSemaphore: SemaphoreHandle_t) {
+    if current::is_in_isr_context() || xSemaphore.is_null() {
+        return;
+    }
+    drop(unsafe { Box::from_raw(xSemaphore as *mut Semaphore) });
+}
+
+/* ### Task notifications ### */
+
+#[no_mangle]
+pub unsafe extern "C" fn xTaskNotifyGive(xTaskToNotify: TaskHandle_t) -> BaseType_t {
+    if let Some(tcb) = unsafe { (xTaskToNotify as *const TaskCb).as_ref() } {
+        tcb.notify_give();
+    }
+    pdPASS
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vTaskNotifyGiveFromISR(
+    xTaskToNotify: TaskHandle_t,
+    pxHigherPriorityTaskWoken: *mut BaseType_t,
+) {
+    if let Some(woken) = unsafe { pxHigherPriorityTaskWoken.as_mut() } {
+        *woken = pdFALSE;
+    }
+    unsafe { xTaskNotifyGive(xTaskToNotify) };
+}
+
+/// Wait for the notification value of the current task to become non-zero.
+/// Return the value before it is cleared or decremented, or zero if the
+/// timeout expires.
+#[no_mangle]
+pub extern "C" fn ulTaskNotifyTake(xClearCountOnExit: BaseType_t, xTicksToWait: TickType_t) -> u32 {
+    if current::is_in_isr_context() {
+        return 0;
+    }
+
+    let tcb = current_task_cb();
+    let clear = xClearCountOnExit != pdFALSE;
+    let start = time::get_tick();
+    loop {
+        let val = tcb.try_take(clear);
+        if val != 0 {
+            return val;
+        }
+        if xTicksToWait == portMAX_DELAY {
+            tcb.mailbox.wait();
+            continue;
+        }
+        let elapsed = time::get_tick().wrapping_sub(start);
+        if elapsed >= xTicksToWait {
+            return 0;
+        }
+        // The mailbox may carry stale notifications whose value has already
+        // been taken, so the value is checked again after every wake up.
+        tcb.mailbox.wait_until_timeout(xTicksToWait - elapsed);
+    }
+}
@@ -0,0 +1,14 @@
+//! Compatibility layers that let code written against other RTOS APIs run on
+//! Hopter, and the C interface to Hopter's own kernel services.
+
+#[cfg(any(feature = "cmsis_rtos2", feature = "freertos", feature = "ffi"))]
+mod common;
+
+#[cfg(feature = "cmsis_rtos2")]
+pub mod cmsis_rtos2;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "freertos")]
+pub mod freertos;
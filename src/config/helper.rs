@@ -42,6 +42,60 @@ pub(super) const fn is_power_of_2(x: u32) -> bool {
     x.leading_zeros() + x.trailing_zeros() == 31
 }
 
+/// Parse a decimal build-time override, e.g., `option_env!("HOPTER_X")`, or
+/// return `default` if it is not set. Compilation will fail if the value is
+/// not a decimal number fitting in `u32`.
+pub(super) const fn override_u32(var: Option<&str>, default: u32) -> u32 {
+    let bytes = match var {
+        Some(var) => var.as_bytes(),
+        None => return default,
+    };
+    assert!(!bytes.is_empty(), "empty configuration override");
+
+    let mut val: u32 = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        assert!(
+            bytes[i].is_ascii_digit(),
+            "configuration override is not a decimal number"
+        );
+        val = match val.checked_mul(10) {
+            Some(val) => match val.checked_add((bytes[i] - b'0') as u32) {
+                Some(val) => val,
+                None => panic!("configuration override is too large"),
+            },
+            None => panic!("configuration override is too large"),
+        };
+        i += 1;
+    }
+    val
+}
+
+/// Like [`override_u32`], but for `u8` parameters.
+pub(super) const fn override_u8(var: Option<&str>, default: u8) -> u8 {
+    let val = override_u32(var, default as u32);
+    assert!(val <= u8::MAX as u32, "configuration override is too large");
+    val as u8
+}
+
+/// Like [`override_u32`], but for `usize` parameters.
+pub(super) const fn override_usize(var: Option<&str>, default: usize) -> usize {
+    override_u32(var, default as u32) as usize
+}
+
+/// Parse a boolean build-time override, either `true` or `false`, or return
+/// `default` if it is not set. Compilation will fail for other values.
+pub(super) const fn override_bool(var: Option<&str>, default: bool) -> bool {
+    match var {
+        None => default,
+        Some(var) => match var.as_bytes() {
+            b"true" => true,
+            b"false" => false,
+            _ => panic!("configuration override is neither true nor false"),
+        },
+    }
+}
+
 /// Assert that a given value has a given type. Compilation will fail if the
 /// types mismatch, but the diagnostic message might be obscure.
 macro_rules! assert_value_type {
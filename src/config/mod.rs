@@ -2,6 +2,17 @@ use static_assertions::{const_assert, const_assert_eq};
 
 #[macro_use]
 mod helper;
+pub mod preset;
+pub mod tunable;
+
+use helper::{override_bool, override_u32, override_u8, override_usize};
+
+/* ############################# */
+/* ### Preset Configurations ### */
+/* ############################# */
+
+// At most one `preset_*` feature can be enabled.
+const_assert!(preset::SELECTED_COUNT <= 1);
 
 /* ############################ */
 /* ### Clock Configurations ### */
@@ -38,6 +49,16 @@ assert_value_type!(HOT_SPLIT_PREVENTION_CACHE_SIZE, usize);
 pub use hopter_conf_params::HOT_SPLIT_DETECTION_THRESHOLD;
 assert_value_type!(HOT_SPLIT_DETECTION_THRESHOLD, usize);
 
+/// Whether each task with dynamic stack extension keeps its most recently
+/// freed stacklet and reuses it for the next stack extension that fits,
+/// instead of going back to the heap. It makes a loop repeatedly calling a
+/// function across a stacklet boundary cheaper, at the cost of holding up
+/// to one idle stacklet per task.
+pub const ENABLE_STACKLET_CACHE: bool = override_bool(
+    option_env!("HOPTER_ENABLE_STACKLET_CACHE"),
+    preset::SELECTED.enable_stacklet_cache,
+);
+
 #[doc(inline)]
 pub use hopter_conf_params::MAIN_TASK_INITIAL_STACK_SIZE;
 assert_value_type!(MAIN_TASK_INITIAL_STACK_SIZE, usize);
@@ -111,6 +132,21 @@ assert_value_type!(IRQ_MIN_PRIORITY, u8);
 // Smaller numerical value represents higher priority.
 const_assert!(IRQ_MIN_PRIORITY > IRQ_MAX_PRIORITY);
 
+/// The maximum number of times a lock-free loop on the path of an
+/// `_allow_isr` operation may retry. A retry happens only when an ISR of
+/// higher priority than the retrying context modifies the same primitive in
+/// between. By default, the limit allows one such ISR per IRQ priority
+/// level. Exceeding it means that ISRs modify the primitive faster than the
+/// operation completes, e.g., during an IRQ storm, which fails a debug
+/// assertion.
+pub const ISR_RETRY_LIMIT: u32 = override_u32(
+    option_env!("HOPTER_ISR_RETRY_LIMIT"),
+    ((IRQ_MIN_PRIORITY - IRQ_MAX_PRIORITY) / IRQ_PRIORITY_GRANULARITY) as u32 + 1,
+);
+
+// Must allow the retry caused by at least one preempting ISR.
+const_assert!(ISR_RETRY_LIMIT >= 1);
+
 #[doc(inline)]
 pub use hopter_conf_params::SYSTICK_PRIORITY;
 assert_value_type!(SYSTICK_PRIORITY, u8);
@@ -157,6 +193,34 @@ assert_value_type!(PENDSV_PRIORITY, u8);
 // PendSV's priority must be lower than SVC.
 const_assert!(PENDSV_PRIORITY > SVC_NORMAL_PRIORITY);
 
+/* ########################## */
+/* ### FPU Configurations ### */
+/* ########################## */
+
+/// Whether the floating point context of tasks is preserved. Controlled by
+/// the `fp_context` cargo feature. When disabled, the FPU is not enabled, and
+/// context switches neither save nor restore floating point registers, which
+/// saves 34 words of stacking per exception.
+pub const FP_CONTEXT_SAVE: bool = cfg!(feature = "fp_context");
+
+/// Whether the floating point registers `s0-s15` and `fpscr` are stacked
+/// lazily on exception entry, which is the hardware default. The hardware
+/// then only reserves their space in the trap frame, and fills it when the
+/// handler first executes a floating point instruction, so that ISRs not
+/// using the FPU do not pay for the stacking. When disabled, the registers
+/// are always stacked on exception entry from a task that used the FPU.
+/// Only relevant if [`FP_CONTEXT_SAVE`] is enabled.
+pub const FP_LAZY_STACKING: bool = override_bool(option_env!("HOPTER_FP_LAZY_STACKING"), true);
+
+/// The exception return value when returning to thread mode using the
+/// process stack pointer. With floating point context preserved, the
+/// hardware pushes an extended trap frame.
+pub(crate) const __EXC_RETURN_THREAD_PSP: u32 = if FP_CONTEXT_SAVE {
+    0xffffffed
+} else {
+    0xfffffffd
+};
+
 /* ########################### */
 /* ### Task Configurations ### */
 /* ########################### */
@@ -232,3 +296,382 @@ const_assert_eq!(__TLS_MEM_ADDR, 0x2000_0000);
 // The memory address should be encodable as a thumb2 instruction constant,
 // so that we can use a `mov.w` instruction to load the address into a register.
 const_assert!(helper::is_thumb2_allowed_constant(__TLS_MEM_ADDR));
+
+/// The minimum number of milliseconds between two yields of a task at its
+/// checkpoints. A long computation calling `task::checkpoint` lets other
+/// ready tasks of the same priority run at most this often. Zero makes every
+/// checkpoint yield.
+pub const CHECKPOINT_YIELD_PERIOD_MS: u32 = 10;
+
+/// The priority of the tasks scheduled earliest deadline first (EDF), i.e.,
+/// spawned with `TaskBuilder::set_deadline_ms`. Among the ready EDF tasks,
+/// the one with the earliest deadline runs. Tasks of higher priority
+/// preempt EDF tasks, and tasks of lower priority run only when no EDF task
+/// is ready. A task of this priority without a deadline runs after the EDF
+/// tasks. By default, it is one level above the default task priority.
+pub const EDF_TASK_PRIORITY: u8 = override_u8(
+    option_env!("HOPTER_EDF_TASK_PRIORITY"),
+    DEFAULT_TASK_PRIORITY - 1,
+);
+
+// The EDF priority should be one of the priority levels allowed for tasks.
+const_assert!(EDF_TASK_PRIORITY < TASK_PRIORITY_LEVELS - 1);
+
+/// The number of ticks a task runs before the other ready tasks of its
+/// priority get the CPU, i.e., the quantum of round robin time slicing. Zero
+/// disables time slicing, so that a task gives up the CPU to tasks of its
+/// priority only when it yields, blocks or sleeps. A task made cooperative
+/// with `TaskBuilder::set_cooperative` is never time sliced. Time slicing is
+/// also disabled if task preemption is not allowed.
+///
+/// Time slicing does not add interrupt latency. The SysTick handler only
+/// counts the ticks and pends the context switch, which is performed by the
+/// lowest priority PendSV handler after all other pending interrupts are
+/// served. While the scheduler is suspended, e.g., inside
+/// `task::run_uninterrupted`, the switch is deferred until the scheduler is
+/// resumed, so a time slice can overrun the quantum.
+pub const ROUND_ROBIN_QUANTUM_TICKS: u32 =
+    override_u32(option_env!("HOPTER_ROUND_ROBIN_QUANTUM_TICKS"), 0);
+
+/// The number of milliseconds between two checks of the memory pressure by
+/// a breathing task whose work is delayed because of the memory pressure.
+/// See `task::breathing::set_target_memory_pressure`.
+pub const BREATHING_PRESSURE_POLL_MS: u32 = 5;
+
+/* ############################ */
+/* ### Timer Configurations ### */
+/* ############################ */
+
+/// The maximum number of one-shot timers armed with `timer::arm_allow_isr`
+/// at the same time.
+pub const TIMER_QUEUE_LENGTH: usize = 16;
+
+// Required by the lock-free queue buffering timers armed from ISRs.
+const_assert!(helper::is_power_of_2(TIMER_QUEUE_LENGTH as u32));
+
+/* ############################## */
+/* ### Channel Configurations ### */
+/* ############################## */
+
+/// The largest number of elements buffered by a channel created with
+/// [`create_dyn_channel`](crate::sync::create_dyn_channel). Its buffer grows
+/// on demand up to this capacity.
+pub const DYN_CHANNEL_MAX_CAPACITY: usize = 256;
+
+/* ############################ */
+/* ### Async Configurations ### */
+/* ############################ */
+
+/// The number of worker tasks polling futures spawned with
+/// [`spawn_async`](crate::task::spawn_async).
+pub const ASYNC_WORKER_NUMBER: usize = 2;
+
+// Should have at least one worker.
+const_assert!(ASYNC_WORKER_NUMBER > 0);
+
+/// The maximum number of unfinished futures assigned to each worker task.
+pub const ASYNC_RUN_QUEUE_CAPACITY: usize = 16;
+
+// Required by the lock-free queue backing the run queue.
+const_assert!(helper::is_power_of_2(ASYNC_RUN_QUEUE_CAPACITY as u32));
+
+/// The priority of the worker tasks.
+pub const ASYNC_WORKER_PRIORITY: u8 = DEFAULT_TASK_PRIORITY;
+
+/// The ID of the worker tasks.
+pub const ASYNC_WORKER_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/* ############################## */
+/* ### Network Configurations ### */
+/* ############################## */
+
+/// The priority of the network task started by `net::start`.
+pub const NET_TASK_PRIORITY: u8 = DEFAULT_TASK_PRIORITY;
+
+/// The ID of the network task.
+pub const NET_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/// The longest interval in milliseconds between two polls of the network
+/// stack, which bounds the latency if a driver does not notify the network
+/// task from its IRQ handler.
+pub const NET_MAX_POLL_INTERVAL_MS: u32 = override_u32(
+    option_env!("HOPTER_NET_MAX_POLL_INTERVAL_MS"),
+    preset::SELECTED.net_max_poll_interval_ms,
+);
+
+// Must poll at least once in a while.
+const_assert!(NET_MAX_POLL_INTERVAL_MS > 0);
+
+/// The size in bytes of the receive and the transmit buffer of each TCP
+/// socket.
+pub const NET_TCP_BUFFER_SIZE: usize = override_usize(
+    option_env!("HOPTER_NET_TCP_BUFFER_SIZE"),
+    preset::SELECTED.net_tcp_buffer_size,
+);
+
+/// The size in bytes of the receive and the transmit payload buffer of each
+/// UDP socket.
+pub const NET_UDP_BUFFER_SIZE: usize = 1024;
+
+/// The maximum number of datagrams queued in each direction of a UDP socket.
+pub const NET_UDP_PACKET_NUMBER: usize = 4;
+
+/* ############################ */
+/* ### Shell Configurations ### */
+/* ############################ */
+
+/// The priority of the shell tasks started by `shell::start`.
+pub const SHELL_TASK_PRIORITY: u8 = DEFAULT_TASK_PRIORITY;
+
+/// The ID of the shell tasks.
+pub const SHELL_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/// The maximum number of characters in a command line.
+pub const SHELL_LINE_LENGTH: usize = 128;
+
+// Must at least hold a command name.
+const_assert!(SHELL_LINE_LENGTH > 0);
+
+/// The prompt printed before reading each command line.
+pub const SHELL_PROMPT: &str = "> ";
+
+/* ################################ */
+/* ### Crash Log Configurations ### */
+/* ################################ */
+
+/// The priority of the task writing crash log entries to flash. It should be
+/// low enough to let a panicked task finish unwinding before its entry is
+/// written.
+pub const CRASH_LOG_TASK_PRIORITY: u8 = IDLE_TASK_PRIORITY - 1;
+
+// Must be a priority allowed for a task other than the idle task.
+const_assert!(CRASH_LOG_TASK_PRIORITY < IDLE_TASK_PRIORITY);
+
+/// The ID of the crash log writer task.
+pub const CRASH_LOG_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/// The maximum number of panic entries waiting to be written to flash. Panics
+/// occurring while the queue is full are not logged.
+pub const CRASH_LOG_PENDING_NUMBER: usize = 4;
+
+// Must queue at least one entry, and be a power of two as required by the
+// lock-free queue of entries pended by ISRs.
+const_assert!(CRASH_LOG_PENDING_NUMBER > 0);
+const_assert!(CRASH_LOG_PENDING_NUMBER.is_power_of_two());
+
+/* ########################## */
+/* ### CAN Configurations ### */
+/* ########################## */
+
+/// The priority of the CAN task started by `can::start`.
+pub const CAN_TASK_PRIORITY: u8 = DEFAULT_TASK_PRIORITY;
+
+/// The ID of the CAN task.
+pub const CAN_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/// The maximum number of frames waiting for transmission.
+pub const CAN_TX_QUEUE_LENGTH: usize = 16;
+
+// Must queue at least one frame.
+const_assert!(CAN_TX_QUEUE_LENGTH > 0);
+
+/* ############################## */
+/* ### Metrics Configurations ### */
+/* ############################## */
+
+/// The priority of the exporter tasks started by `metrics::start`.
+pub const METRICS_TASK_PRIORITY: u8 = IDLE_TASK_PRIORITY - 1;
+
+// Must be a priority allowed for a task other than the idle task.
+const_assert!(METRICS_TASK_PRIORITY < IDLE_TASK_PRIORITY);
+
+/// The ID of the exporter tasks.
+pub const METRICS_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/* ########################## */
+/* ### Log Configurations ### */
+/* ########################## */
+
+/// The priority of the logger task started by `log::start`. Logging tasks
+/// never wait for the logger task, so it can be low.
+pub const LOG_TASK_PRIORITY: u8 = IDLE_TASK_PRIORITY - 1;
+
+// Must be a priority allowed for a task other than the idle task.
+const_assert!(LOG_TASK_PRIORITY < IDLE_TASK_PRIORITY);
+
+/// The ID of the logger task.
+pub const LOG_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/// The maximum number of log records waiting for the logger task. Messages
+/// logged while the queue is full are dropped and counted.
+pub const LOG_QUEUE_LENGTH: usize = override_usize(
+    option_env!("HOPTER_LOG_QUEUE_LENGTH"),
+    preset::SELECTED.log_queue_length,
+);
+
+// Must queue at least one record, and be a power of two as required by the
+// lock-free queue.
+const_assert!(LOG_QUEUE_LENGTH > 0);
+const_assert!(LOG_QUEUE_LENGTH.is_power_of_two());
+
+/// The maximum length of a log message in bytes. Longer messages are
+/// truncated. It bounds the time spent with IRQs masked to queue a record.
+pub const LOG_RECORD_LENGTH: usize = 96;
+
+// Must hold at least one character.
+const_assert!(LOG_RECORD_LENGTH > 0);
+
+/* ############################# */
+/* ### Reaper Configurations ### */
+/* ############################# */
+
+/// The priority of the reaper task freeing terminated tasks, with the
+/// `reaper` feature.
+pub const REAPER_TASK_PRIORITY: u8 = IDLE_TASK_PRIORITY - 1;
+
+// Must be a priority allowed for a task other than the idle task.
+const_assert!(REAPER_TASK_PRIORITY < IDLE_TASK_PRIORITY);
+
+/// The ID of the reaper task.
+pub const REAPER_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/* ############################ */
+/* ### Trace Configurations ### */
+/* ############################ */
+
+/// The priority of the trace task started by `trace::start`.
+pub const TRACE_TASK_PRIORITY: u8 = IDLE_TASK_PRIORITY - 1;
+
+// Must be a priority allowed for a task other than the idle task.
+const_assert!(TRACE_TASK_PRIORITY < IDLE_TASK_PRIORITY);
+
+/// The ID of the trace task.
+pub const TRACE_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/// The maximum number of events buffered between two flushes. Events
+/// recorded while the buffer is full are dropped and counted.
+pub const TRACE_BUFFER_LENGTH: usize = override_usize(
+    option_env!("HOPTER_TRACE_BUFFER_LENGTH"),
+    preset::SELECTED.trace_buffer_length,
+);
+
+// Must buffer at least one event, and be a power of two as required by the
+// lock-free ring.
+const_assert!(TRACE_BUFFER_LENGTH > 0);
+const_assert!(TRACE_BUFFER_LENGTH.is_power_of_two());
+
+/// The number of milliseconds between two flushes of the buffered events.
+pub const TRACE_FLUSH_PERIOD_MS: u32 = override_u32(
+    option_env!("HOPTER_TRACE_FLUSH_PERIOD_MS"),
+    preset::SELECTED.trace_flush_period_ms,
+);
+
+// Must be a positive sleep duration.
+const_assert!(TRACE_FLUSH_PERIOD_MS > 0);
+
+/// The number of milliseconds between two samplings of the variables watched
+/// with `watch::watch`. Sampling happens when the trace task flushes, so the
+/// actual period is rounded up to a multiple of
+/// [`TRACE_FLUSH_PERIOD_MS`].
+pub const TRACE_WATCH_PERIOD_MS: u32 = 100;
+
+// Must sample at most once per flush.
+const_assert!(TRACE_WATCH_PERIOD_MS >= TRACE_FLUSH_PERIOD_MS);
+
+/* ################################ */
+/* ### Self-test Configurations ### */
+/* ################################ */
+
+/// The priority of the self-test task started by `selftest::start`. The
+/// self-tests only run when no task of higher priority is ready.
+pub const SELFTEST_TASK_PRIORITY: u8 = IDLE_TASK_PRIORITY - 1;
+
+// Must be a priority allowed for a task other than the idle task.
+const_assert!(SELFTEST_TASK_PRIORITY < IDLE_TASK_PRIORITY);
+
+/// The ID of the self-test task.
+pub const SELFTEST_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/// The number of milliseconds between the end of a run of all self-tests
+/// and the start of the next run.
+pub const SELFTEST_PERIOD_MS: u32 = 1000;
+
+// Must be a valid sleep duration.
+const_assert!(SELFTEST_PERIOD_MS <= i32::MAX as u32);
+
+/// The number of bytes checked by each step of the flash CRC self-test. It
+/// bounds the time the self-test task runs before yielding.
+pub const SELFTEST_STEP_BYTES: usize = 1024;
+
+// Must make progress in each step.
+const_assert!(SELFTEST_STEP_BYTES > 0);
+
+/* ############################# */
+/* ### Events Configurations ### */
+/* ############################# */
+
+/// The number of entries in the event ring recorded by `events::record`.
+/// When the ring is full, the oldest entry is overwritten.
+pub const EVENTS_RING_LENGTH: usize = override_usize(
+    option_env!("HOPTER_EVENTS_RING_LENGTH"),
+    preset::SELECTED.events_ring_length,
+);
+
+// Must be a power of two so that the ring index stays consistent when the
+// 32-bit entry counter wraps around.
+const_assert!(EVENTS_RING_LENGTH.is_power_of_two());
+
+/// The number of entries in the ring of scheduler events recorded with the
+/// `sched_events` feature. When the ring is full, the oldest entry is
+/// overwritten.
+pub const SCHED_EVENTS_RING_LENGTH: usize =
+    override_usize(option_env!("HOPTER_SCHED_EVENTS_RING_LENGTH"), 64);
+
+// Must be a power of two for the same reason as above.
+const_assert!(SCHED_EVENTS_RING_LENGTH.is_power_of_two());
+
+/* ######################################### */
+/* ### Starvation Monitor Configurations ### */
+/* ######################################### */
+
+/// The priority of the task checking for starved tasks with the
+/// `starvation_monitor` feature. It runs at the highest priority, so that it
+/// keeps checking while the lower priority tasks are starved.
+pub const STARVATION_MONITOR_PRIORITY: u8 = 0;
+
+// Must be a priority allowed for a task other than the idle task.
+const_assert!(STARVATION_MONITOR_PRIORITY < IDLE_TASK_PRIORITY);
+
+/// The ID of the starvation monitor task.
+pub const STARVATION_MONITOR_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/// The number of milliseconds a task may wait for the CPU while lower
+/// priority tasks run before the monitor reports it.
+pub const STARVATION_THRESHOLD_MS: u32 =
+    override_u32(option_env!("HOPTER_STARVATION_THRESHOLD_MS"), 100);
+
+/// The number of milliseconds between two checks of the monitor.
+pub const STARVATION_CHECK_PERIOD_MS: u32 =
+    override_u32(option_env!("HOPTER_STARVATION_CHECK_PERIOD_MS"), 50);
+
+// The monitor must not sleep without a tick in between.
+const_assert!(STARVATION_CHECK_PERIOD_MS > 0);
+
+/* ############################### */
+
+/// The priority of the task running the shutdown hooks.
+pub const SHUTDOWN_TASK_PRIORITY: u8 = DEFAULT_TASK_PRIORITY;
+
+// Must be a priority allowed for a task other than the idle task.
+const_assert!(SHUTDOWN_TASK_PRIORITY < IDLE_TASK_PRIORITY);
+
+/// The ID of the task running the shutdown hooks.
+pub const SHUTDOWN_TASK_ID: u8 = DEFAULT_TASK_ID;
+
+/// The maximum number of milliseconds to wait for all shutdown hooks to
+/// return. The system is reset anyway when the time is up.
+pub const SHUTDOWN_HOOK_TIMEOUT_MS: u32 = 1000;
+
+/// The maximum number of milliseconds to wait for the logger task to write
+/// the queued log records before the system is reset.
+pub const SHUTDOWN_FLUSH_TIMEOUT_MS: u32 = 100;
@@ -0,0 +1,134 @@
+//! Curated sets of configuration values selected with a Cargo feature.
+//!
+//! Each preset picks consistent values for the logging, tracing, stack, and
+//! networking parameters defined by the kernel, and the Cargo feature of a
+//! preset may also enable other features, e.g., the statistics features for
+//! debugging. At most one of the following features can be enabled:
+//!
+//! - `preset_minimal`: small buffers and only errors and warnings logged, for
+//!   parts with little RAM.
+//! - `preset_debug`: large buffers, all messages logged, and the `irq_stats`
+//!   and `boot_report` features enabled.
+//! - `preset_low_power`: infrequent polling and flushing, so that the CPU
+//!   sleeps longer between wake-ups.
+//! - `preset_throughput`: large network buffers, frequent polling, and the
+//!   stacklet cache enabled, at the cost of RAM.
+//!
+//! Without a preset feature, [`DEFAULT`] is used.
+//!
+//! Each parameter taken from the preset can be overridden individually by
+//! setting an environment variable named after the parameter with the
+//! `HOPTER_` prefix when building, e.g., `HOPTER_LOG_QUEUE_LENGTH=32`. An
+//! invalid value fails the build.
+//!
+//! The tick rate, the stacklet sizes, and the other parameters re-exported
+//! from the `hopter_conf_params` crate are not covered by the presets. They
+//! are configured by patching that crate.
+
+/// A set of configuration values. See the identically named parameters in
+/// [`config`](super) for their meaning.
+pub struct Preset {
+    /// The name of the preset, printed in the boot report.
+    pub name: &'static str,
+    /// The default of [`tunable::log_level`](super::tunable::log_level).
+    pub log_level: u32,
+    pub log_queue_length: usize,
+    pub trace_buffer_length: usize,
+    pub trace_flush_period_ms: u32,
+    pub events_ring_length: usize,
+    pub enable_stacklet_cache: bool,
+    pub net_max_poll_interval_ms: u32,
+    pub net_tcp_buffer_size: usize,
+    /// The default of
+    /// [`tunable::offload_timeout_ms`](super::tunable::offload_timeout_ms).
+    pub offload_timeout_ms: u32,
+}
+
+/// The values used without a preset feature.
+pub const DEFAULT: Preset = Preset {
+    name: "default",
+    log_level: 3,
+    log_queue_length: 16,
+    trace_buffer_length: 128,
+    trace_flush_period_ms: 10,
+    events_ring_length: 64,
+    enable_stacklet_cache: true,
+    net_max_poll_interval_ms: 100,
+    net_tcp_buffer_size: 1024,
+    offload_timeout_ms: 1000,
+};
+
+/// The values selected by the `preset_minimal` feature.
+pub const MINIMAL: Preset = Preset {
+    name: "minimal",
+    log_level: 2,
+    log_queue_length: 4,
+    trace_buffer_length: 16,
+    trace_flush_period_ms: 10,
+    events_ring_length: 16,
+    enable_stacklet_cache: false,
+    net_max_poll_interval_ms: 100,
+    net_tcp_buffer_size: 512,
+    offload_timeout_ms: 1000,
+};
+
+/// The values selected by the `preset_debug` feature.
+pub const DEBUG: Preset = Preset {
+    name: "debug",
+    log_level: 4,
+    log_queue_length: 64,
+    trace_buffer_length: 512,
+    trace_flush_period_ms: 10,
+    events_ring_length: 256,
+    enable_stacklet_cache: true,
+    net_max_poll_interval_ms: 100,
+    net_tcp_buffer_size: 1024,
+    offload_timeout_ms: 5000,
+};
+
+/// The values selected by the `preset_low_power` feature.
+pub const LOW_POWER: Preset = Preset {
+    name: "low_power",
+    log_level: 2,
+    log_queue_length: 16,
+    trace_buffer_length: 256,
+    trace_flush_period_ms: 100,
+    events_ring_length: 64,
+    enable_stacklet_cache: true,
+    net_max_poll_interval_ms: 1000,
+    net_tcp_buffer_size: 1024,
+    offload_timeout_ms: 2000,
+};
+
+/// The values selected by the `preset_throughput` feature.
+pub const THROUGHPUT: Preset = Preset {
+    name: "throughput",
+    log_level: 1,
+    log_queue_length: 16,
+    trace_buffer_length: 128,
+    trace_flush_period_ms: 10,
+    events_ring_length: 64,
+    enable_stacklet_cache: true,
+    net_max_poll_interval_ms: 10,
+    net_tcp_buffer_size: 4096,
+    offload_timeout_ms: 250,
+};
+
+/// The number of enabled preset features.
+pub(super) const SELECTED_COUNT: usize = cfg!(feature = "preset_minimal") as usize
+    + cfg!(feature = "preset_debug") as usize
+    + cfg!(feature = "preset_low_power") as usize
+    + cfg!(feature = "preset_throughput") as usize;
+
+/// The preset selected by the Cargo features.
+pub const SELECTED: Preset = if cfg!(feature = "preset_minimal") {
+    MINIMAL
+} else if cfg!(feature = "preset_debug") {
+    DEBUG
+} else if cfg!(feature = "preset_low_power") {
+    LOW_POWER
+} else if cfg!(feature = "preset_throughput") {
+    THROUGHPUT
+} else {
+    DEFAULT
+};
@@ -0,0 +1,124 @@
+//! Configuration values that can be changed without recompiling.
+//!
+//! Unlike the other configuration parameters which are compile-time
+//! constants, the values in this module are stored in a dedicated flash
+//! section `.hopter_tunables` under the symbol `HOPTER_TUNABLES`. The host
+//! may patch the section in the ELF image or directly in flash, e.g., with a
+//! debugger, and the kernel will pick up the new values on the next boot.
+//! If the section does not contain a valid record, e.g., the flash page has
+//! been erased, the default values are used instead.
+
+use core::{
+    ptr::read_volatile,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use static_assertions::const_assert;
+
+/// Marks a valid tunable parameter record.
+const TUNABLES_MAGIC: u32 = 0x4870_5475;
+
+/// Incremented whenever the layout of [`TunableParams`] changes, so that a
+/// host tool never patches a record it does not understand.
+const TUNABLES_VERSION: u32 = 1;
+
+/// The layout of the tunable parameter record in flash. The layout is part
+/// of the contract with host tools and fields must only be appended.
+#[repr(C)]
+pub struct TunableParams {
+    /// Must be [`TUNABLES_MAGIC`] for the record to be considered valid.
+    magic: u32,
+    /// Must be [`TUNABLES_VERSION`] for the record to be considered valid.
+    version: u32,
+    /// The maximum verbosity of kernel log messages.
+    pub log_level: u32,
+    /// The number of milliseconds to wait for the host to acknowledge an
+    /// offloaded request.
+    pub offload_timeout_ms: u32,
+    /// The default watchdog period in milliseconds.
+    pub watchdog_period_ms: u32,
+}
+
+/// Default maximum log verbosity.
+pub const DEFAULT_LOG_LEVEL: u32 = super::override_u32(
+    option_env!("HOPTER_LOG_LEVEL"),
+    super::preset::SELECTED.log_level,
+);
+
+// Level 0 disables all log messages.
+const_assert!(DEFAULT_LOG_LEVEL <= crate::debug::log::Level::Debug as u32);
+
+/// Default offload acknowledgement timeout in milliseconds.
+pub const DEFAULT_OFFLOAD_TIMEOUT_MS: u32 = super::override_u32(
+    option_env!("HOPTER_OFFLOAD_TIMEOUT_MS"),
+    super::preset::SELECTED.offload_timeout_ms,
+);
+
+const_assert!(DEFAULT_OFFLOAD_TIMEOUT_MS > 0);
+
+/// Default watchdog period in milliseconds.
+pub const DEFAULT_WATCHDOG_PERIOD_MS: u32 = 5000;
+
+const_assert!(DEFAULT_WATCHDOG_PERIOD_MS > 0);
+
+/// The record that host tools patch.
+#[no_mangle]
+#[used]
+#[link_section = ".hopter_tunables"]
+pub static HOPTER_TUNABLES: TunableParams = TunableParams {
+    magic: TUNABLES_MAGIC,
+    version: TUNABLES_VERSION,
+    log_level: DEFAULT_LOG_LEVEL,
+    offload_timeout_ms: DEFAULT_OFFLOAD_TIMEOUT_MS,
+    watchdog_period_ms: DEFAULT_WATCHDOG_PERIOD_MS,
+};
+
+static LOG_LEVEL: AtomicU32 = AtomicU32::new(DEFAULT_LOG_LEVEL);
+static OFFLOAD_TIMEOUT_MS: AtomicU32 = AtomicU32::new(DEFAULT_OFFLOAD_TIMEOUT_MS);
+static WATCHDOG_PERIOD_MS: AtomicU32 = AtomicU32::new(DEFAULT_WATCHDOG_PERIOD_MS);
+
+/// Load the tunable parameters from flash. Called once by the kernel during
+/// boot. The reads are volatile so that the compiler never folds the values
+/// in [`HOPTER_TUNABLES`] as constants.
+pub(crate) fn load() {
+    let params = &HOPTER_TUNABLES;
+
+    let magic = unsafe { read_volatile(&params.magic) };
+    let version = unsafe { read_volatile(&params.version) };
+    if magic != TUNABLES_MAGIC || version != TUNABLES_VERSION {
+        return;
+    }
+
+    LOG_LEVEL.store(
+        unsafe { read_volatile(&params.log_level) },
+        Ordering::SeqCst,
+    );
+    OFFLOAD_TIMEOUT_MS.store(
+        unsafe { read_volatile(&params.offload_timeout_ms) },
+        Ordering::SeqCst,
+    );
+    WATCHDOG_PERIOD_MS.store(
+        unsafe { read_volatile(&params.watchdog_period_ms) },
+        Ordering::SeqCst,
+    );
+}
+
+/// Get the maximum verbosity of kernel log messages.
+pub fn log_level() -> u32 {
+    LOG_LEVEL.load(Ordering::SeqCst)
+}
+
+/// Change the maximum verbosity of kernel log messages until the next boot.
+pub fn set_log_level(level: u32) {
+    LOG_LEVEL.store(level, Ordering::SeqCst);
+}
+
+/// Get the number of milliseconds to wait for the host to acknowledge an
+/// offloaded request.
+pub fn offload_timeout_ms() -> u32 {
+    OFFLOAD_TIMEOUT_MS.load(Ordering::SeqCst)
+}
+
+/// Get the default watchdog period in milliseconds.
+pub fn watchdog_period_ms() -> u32 {
+    WATCHDOG_PERIOD_MS.load(Ordering::SeqCst)
+}
@@ -0,0 +1,519 @@
+//! On-target microbenchmarks of kernel services, enabled by the `benches`
+//! feature.
+//!
+//! [`run_all`] measures the following and prints the results through
+//! semihosting:
+//!
+//! - `timer_overhead`: taking two timestamps, which is subtracted from all
+//!   other results.
+//! - `context_switch`: one switch between two tasks, measured as half of a
+//!   round trip through two mailboxes.
+//! - `context_switch_crowded`: the same as `context_switch`, but with half
+//!   of [`MAX_TASK_NUMBER`](config::MAX_TASK_NUMBER) lower priority tasks
+//!   ready. It should cost the same as `context_switch`, since picking the
+//!   next task takes constant time. Only measured when the calling task has
+//!   a priority at least two levels above the idle task.
+//! - `irq_to_task`: from pending an IRQ until a higher priority task blocked
+//!   on a mailbox notified by the IRQ handler starts running.
+//! - `mutex_lock_unlock`: locking and unlocking an uncontended mutex.
+//! - `channel_transfer`: passing one element through a channel to a higher
+//!   priority consumer task.
+//! - `stacklet_alloc`: calling and returning from a function that needs a new
+//!   stacklet. Only measured when dynamic stack extension is enabled.
+//! - `unwind_local`: unwinding eight frames with drop handlers after a panic,
+//!   including the panic handler. Only measured with the `unwind` feature.
+//!   The panics are visible to panic records such as the crash log.
+//! - `isr_mailbox_wake`: notifying a mailbox with a waiting task.
+//! - `isr_semaphore_up_down`: incrementing and decrementing a semaphore.
+//! - `isr_channel_round_trip`: producing and consuming a channel element.
+//!
+//! Times are CPU cycles derived from the SysTick counter, so they need no
+//! timer peripheral. The `isr_*` benchmarks instead run the `_allow_isr`
+//! operations in the IRQ handler and count cycles with the DWT cycle counter,
+//! which has no timestamp overhead, so that their maximum documents the
+//! worst case observed in ISR context. They are skipped on cores without the
+//! counter, and QEMU does not count cycles with it. The output is one comma separated record per line, so
+//! it can be collected from logs of different releases and configurations
+//! and compared with a script:
+//!
+//! ```text
+//! bench-meta,version=0.2.4,core_hz=168000000,dynamic_stack=true
+//! bench,context_switch,64,412,430,517
+//! bench-end
+//! ```
+//!
+//! A `bench` record lists the benchmark name, the number of samples, and the
+//! minimum, mean, and maximum cycles of a sample.
+//!
+//! # Example
+//! ```rust
+//! #[main]
+//! fn main(_cp: cortex_m::Peripherals) {
+//!     bench::run_all(Interrupt::TIM2).unwrap();
+//! }
+//!
+//! #[handler(TIM2)]
+//! fn tim2_handler() {
+//!     bench::irq_handler_allow_isr();
+//! }
+//! ```
+
+use crate::{
+    config,
+    debug::semihosting::dbg_println,
+    interrupt::mask::AllIrqExceptSvc,
+    schedule::current,
+    sync::{create_channel, Consumer, Mailbox, Mutex, Producer, Semaphore, SpinIrqSafe},
+    task::{self, TaskBuildError},
+    time,
+};
+use core::{
+    hint::black_box,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+use cortex_m::{
+    interrupt::InterruptNumber,
+    peripheral::{DWT, NVIC},
+};
+
+/// The number of samples taken by each benchmark.
+const ITERATIONS: u32 = 64;
+
+/// The number of lower priority tasks kept ready by the crowded context
+/// switch benchmark.
+const CROWD: usize = config::MAX_TASK_NUMBER / 2;
+
+/// The number of elements passed through the channel per sample.
+const CHANNEL_BATCH: u32 = 32;
+
+/// The number of frames unwound by the unwind benchmark.
+#[cfg(feature = "unwind")]
+const UNWIND_DEPTH: u32 = 8;
+
+/// The timestamp taken by the benchmark task before triggering the measured
+/// event.
+static START: AtomicU32 = AtomicU32::new(0);
+
+/// The cycles measured by a partner task.
+static SAMPLE: AtomicU32 = AtomicU32::new(0);
+
+/// Notified by a partner task when a sample is ready.
+static SAMPLE_READY: Mailbox = Mailbox::new();
+
+/// Notified by [`irq_handler_allow_isr`].
+static IRQ_MAILBOX: Mailbox = Mailbox::new();
+
+/// Set while [`irq_handler_allow_isr`] should measure the `_allow_isr`
+/// operations rather than notify [`IRQ_MAILBOX`].
+static ISR_MEASURE: AtomicBool = AtomicBool::new(false);
+
+/// The cycles of each `_allow_isr` benchmark measured by the IRQ handler, in
+/// the order of [`ISR_BENCHES`].
+static ISR_CYCLES: [AtomicU32; 3] = [AtomicU32::new(0), AtomicU32::new(0), AtomicU32::new(0)];
+
+/// The name and the number of operations of each `_allow_isr` benchmark.
+const ISR_BENCHES: [(&str, u32); 3] = [
+    ("isr_mailbox_wake", 1),
+    ("isr_semaphore_up_down", 2),
+    ("isr_channel_round_trip", 2),
+];
+
+/// Notified by the IRQ handler while a partner task waits on it.
+static ISR_WAKE: Mailbox = Mailbox::new();
+
+static ISR_SEMAPHORE: Semaphore = Semaphore::new(1, 0);
+
+static ISR_CHANNEL: SpinIrqSafe<Option<(Producer<u32, 4>, Consumer<u32, 4>)>, AllIrqExceptSvc> =
+    SpinIrqSafe::new(None);
+
+/// Return the cycles elapsed since the `start` timestamp.
+fn cycles_since(start: u32) -> u32 {
+    time::cycle_stamp().wrapping_sub(start)
+}
+
+/// The statistics of the samples of a benchmark.
+struct Stats {
+    count: u32,
+    min: u32,
+    max: u32,
+    total: u64,
+    /// Subtracted from every measurement.
+    overhead: u32,
+    /// The number of operations in every measurement.
+    ops: u32,
+}
+
+impl Stats {
+    fn new(overhead: u32, ops: u32) -> Self {
+        Self {
+            count: 0,
+            min: u32::MAX,
+            max: 0,
+            total: 0,
+            overhead,
+            ops,
+        }
+    }
+
+    /// Record the cycles of a measurement as a sample of the cycles per
+    /// operation.
+    fn record(&mut self, cycles: u32) {
+        let cycles = cycles.saturating_sub(self.overhead) / self.ops;
+        self.count += 1;
+        self.min = self.min.min(cycles);
+        self.max = self.max.max(cycles);
+        self.total += cycles as u64;
+    }
+
+    fn report(&self, name: &str) {
+        if self.count == 0 {
+            return;
+        }
+        dbg_println!(
+            "bench,{},{},{},{},{}",
+            name,
+            self.count,
+            self.min,
+            self.total / self.count as u64,
+            self.max
+        );
+    }
+}
+
+/// Run all benchmarks and print the results.
+///
+/// The benchmarks spawn partner tasks with a priority one level higher than
+/// the calling task, so the calling task must not have the highest priority.
+/// `irq` must be an IRQ otherwise unused by the application whose handler
+/// calls [`irq_handler_allow_isr`]. The IRQ is unmasked during the run and
+/// masked afterwards.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn run_all<I>(irq: I) -> Result<(), TaskBuildError>
+where
+    I: InterruptNumber,
+{
+    let prio = current::with_cur_task(|cur_task| cur_task.get_priority().intrinsic_priority());
+    let partner_prio = prio
+        .checked_sub(1)
+        .ok_or(TaskBuildError::PriorityNotAllowed)?;
+
+    dbg_println!(
+        "bench-meta,version={},core_hz={},dynamic_stack={}",
+        env!("CARGO_PKG_VERSION"),
+        config::SYSTICK_FREQUENCY_HZ,
+        config::ALLOW_DYNAMIC_STACK
+    );
+
+    let overhead = bench_timer_overhead();
+    bench_context_switch(partner_prio, overhead, "context_switch")?;
+    if prio + 1 < config::IDLE_TASK_PRIORITY {
+        bench_context_switch_crowded(prio, partner_prio, overhead)?;
+    }
+    bench_irq_to_task(irq, partner_prio, overhead)?;
+    bench_mutex(overhead);
+    bench_channel(partner_prio, overhead)?;
+    if config::ALLOW_DYNAMIC_STACK {
+        bench_stacklet_alloc(partner_prio, overhead)?;
+    }
+    #[cfg(feature = "unwind")]
+    bench_unwind(partner_prio, overhead)?;
+    if DWT::has_cycle_counter() {
+        bench_isr_ops(irq, partner_prio)?;
+    }
+
+    dbg_println!("bench-end");
+    Ok(())
+}
+
+/// Notify the task measuring the IRQ-to-task latency, or measure the
+/// `_allow_isr` operations. Must be called from the handler of the IRQ given
+/// to [`run_all`].
+pub fn irq_handler_allow_isr() {
+    if ISR_MEASURE.load(Ordering::SeqCst) {
+        measure_isr_ops();
+        SAMPLE_READY.notify_allow_isr();
+    } else {
+        IRQ_MAILBOX.notify_allow_isr();
+    }
+}
+
+/// Measure the cycles of taking the timestamps and return the minimum.
+fn bench_timer_overhead() -> u32 {
+    let mut stats = Stats::new(0, 1);
+    for _ in 0..ITERATIONS {
+        let start = time::cycle_stamp();
+        stats.record(cycles_since(start));
+    }
+    stats.report("timer_overhead");
+    stats.min
+}
+
+fn bench_context_switch(partner_prio: u8, overhead: u32, name: &str) -> Result<(), TaskBuildError> {
+    static PING: Mailbox = Mailbox::new();
+    static PONG: Mailbox = Mailbox::new();
+
+    task::build()
+        .set_priority(partner_prio)
+        .set_entry(|| {
+            for _ in 0..ITERATIONS {
+                PING.wait();
+                PONG.notify_allow_isr();
+            }
+        })
+        .spawn()?;
+
+    // A round trip has two context switches.
+    let mut stats = Stats::new(overhead, 2);
+    for _ in 0..ITERATIONS {
+        let start = time::cycle_stamp();
+        // Switch to the partner, which switches back after notifying.
+        PING.notify_allow_isr();
+        PONG.wait();
+        stats.record(cycles_since(start));
+    }
+    stats.report(name);
+    Ok(())
+}
+
+fn bench_context_switch_crowded(
+    prio: u8,
+    partner_prio: u8,
+    overhead: u32,
+) -> Result<(), TaskBuildError> {
+    // The crowd stays ready while the calling task or its partner runs.
+    for _ in 0..CROWD {
+        task::build()
+            .set_priority(prio + 1)
+            .set_entry(|| {})
+            .spawn()?;
+    }
+
+    bench_context_switch(partner_prio, overhead, "context_switch_crowded")?;
+
+    // Let the crowd run and exit, so that it neither disturbs the other
+    // benchmarks nor holds their task quota.
+    let _ = time::sleep_ms(10);
+    Ok(())
+}
+
+fn bench_irq_to_task<I>(irq: I, partner_prio: u8, overhead: u32) -> Result<(), TaskBuildError>
+where
+    I: InterruptNumber,
+{
+    task::build()
+        .set_priority(partner_prio)
+        .set_entry(|| {
+            for _ in 0..ITERATIONS {
+                IRQ_MAILBOX.wait();
+                SAMPLE.store(cycles_since(START.load(Ordering::SeqCst)), Ordering::SeqCst);
+                SAMPLE_READY.notify_allow_isr();
+            }
+        })
+        .spawn()?;
+
+    unsafe { NVIC::unmask(irq) };
+
+    let mut stats = Stats::new(overhead, 1);
+    for _ in 0..ITERATIONS {
+        START.store(time::cycle_stamp(), Ordering::SeqCst);
+        NVIC::pend(irq);
+        SAMPLE_READY.wait();
+        stats.record(SAMPLE.load(Ordering::SeqCst));
+    }
+
+    NVIC::mask(irq);
+    stats.report("irq_to_task");
+    Ok(())
+}
+
+fn bench_isr_ops<I>(irq: I, partner_prio: u8) -> Result<(), TaskBuildError>
+where
+    I: InterruptNumber,
+{
+    let mut cp = unsafe { cortex_m::Peripherals::steal() };
+    cp.DCB.enable_trace();
+    cp.DWT.enable_cycle_counter();
+
+    // Keep a task waiting on the mailbox, so that notifying it takes the
+    // path that wakes a task.
+    task::build()
+        .set_priority(partner_prio)
+        .set_entry(|| {
+            for _ in 0..ITERATIONS {
+                ISR_WAKE.wait();
+            }
+        })
+        .spawn()?;
+
+    *ISR_CHANNEL.lock() = Some(create_channel());
+    ISR_MEASURE.store(true, Ordering::SeqCst);
+    unsafe { NVIC::unmask(irq) };
+
+    let mut stats = ISR_BENCHES.map(|(_, ops)| Stats::new(0, ops));
+    for _ in 0..ITERATIONS {
+        NVIC::pend(irq);
+        SAMPLE_READY.wait();
+        for (stats, cycles) in stats.iter_mut().zip(ISR_CYCLES.iter()) {
+            stats.record(cycles.load(Ordering::SeqCst));
+        }
+    }
+
+    NVIC::mask(irq);
+    ISR_MEASURE.store(false, Ordering::SeqCst);
+    *ISR_CHANNEL.lock() = None;
+
+    for (stats, (name, _)) in stats.iter().zip(ISR_BENCHES.iter()) {
+        stats.report(name);
+    }
+    Ok(())
+}
+
+/// Run each `_allow_isr` benchmark once in ISR context and store the cycles
+/// in [`ISR_CYCLES`], less the cycles of reading the counter.
+fn measure_isr_ops() {
+    let start = DWT::cycle_count();
+    let overhead = DWT::cycle_count().wrapping_sub(start);
+    let cycles_since = |start: u32| {
+        DWT::cycle_count()
+            .wrapping_sub(start)
+            .saturating_sub(overhead)
+    };
+
+    let start = DWT::cycle_count();
+    ISR_WAKE.notify_allow_isr();
+    ISR_CYCLES[0].store(cycles_since(start), Ordering::SeqCst);
+
+    let start = DWT::cycle_count();
+    let _ = ISR_SEMAPHORE.try_up_allow_isr();
+    let _ = ISR_SEMAPHORE.try_down_allow_isr();
+    ISR_CYCLES[1].store(cycles_since(start), Ordering::SeqCst);
+
+    if let Some((producer, consumer)) = ISR_CHANNEL.lock().as_ref() {
+        let start = DWT::cycle_count();
+        let _ = producer.try_produce_allow_isr(0);
+        black_box(consumer.try_consume_allow_isr());
+        ISR_CYCLES[2].store(cycles_since(start), Ordering::SeqCst);
+    }
+}
+
+fn bench_mutex(overhead: u32) {
+    let mutex = Mutex::new(0u32);
+
+    let mut stats = Stats::new(overhead, 1);
+    for _ in 0..ITERATIONS {
+        let start = time::cycle_stamp();
+        *mutex.lock() += 1;
+        stats.record(cycles_since(start));
+    }
+    stats.report("mutex_lock_unlock");
+}
+
+fn bench_channel(partner_prio: u8, overhead: u32) -> Result<(), TaskBuildError> {
+    let (producer, consumer) = create_channel::<u32, 8>();
+
+    task::build()
+        .set_priority(partner_prio)
+        .set_entry(move || {
+            for _ in 0..ITERATIONS * CHANNEL_BATCH {
+                black_box(consumer.consume());
+            }
+        })
+        .spawn()?;
+
+    let mut stats = Stats::new(overhead, CHANNEL_BATCH);
+    for _ in 0..ITERATIONS {
+        let start = time::cycle_stamp();
+        for i in 0..CHANNEL_BATCH {
+            producer.produce(i);
+        }
+        stats.record(cycles_since(start));
+    }
+    stats.report("channel_transfer");
+    Ok(())
+}
+
+/// A function whose frame does not fit in the initial stacklet of the
+/// partner task.
+#[inline(never)]
+fn large_frame() {
+    let buf = [0u8; 2048];
+    black_box(&buf);
+}
+
+fn bench_stacklet_alloc(partner_prio: u8, overhead: u32) -> Result<(), TaskBuildError> {
+    let mut stats = Stats::new(overhead, 1);
+    for _ in 0..ITERATIONS {
+        // Use a new task for each sample, so that the hot-split prevention
+        // never enlarges the stack in advance.
+        task::build()
+            .set_priority(partner_prio)
+            .set_stack_init_size(1024)
+            .set_entry(|| {
+                let start = time::cycle_stamp();
+                large_frame();
+                SAMPLE.store(cycles_since(start), Ordering::SeqCst);
+                SAMPLE_READY.notify_allow_isr();
+            })
+            .spawn()?;
+
+        SAMPLE_READY.wait();
+        stats.record(SAMPLE.load(Ordering::SeqCst));
+    }
+    stats.report("stacklet_alloc");
+    Ok(())
+}
+
+/// Take the end timestamp of the unwind benchmark when dropped by the
+/// outermost frame.
+#[cfg(feature = "unwind")]
+struct StopWatch;
+
+#[cfg(feature = "unwind")]
+impl Drop for StopWatch {
+    fn drop(&mut self) {
+        SAMPLE.store(cycles_since(START.load(Ordering::SeqCst)), Ordering::SeqCst);
+        SAMPLE_READY.notify_allow_isr();
+    }
+}
+
+/// Give each unwound frame a drop handler to run.
+#[cfg(feature = "unwind")]
+struct Guard(u32);
+
+#[cfg(feature = "unwind")]
+impl Drop for Guard {
+    fn drop(&mut self) {
+        black_box(self.0);
+    }
+}
+
+#[cfg(feature = "unwind")]
+#[inline(never)]
+fn nested_panic(depth: u32) {
+    let _guard = Guard(depth);
+    if depth == 0 {
+        START.store(time::cycle_stamp(), Ordering::SeqCst);
+        panic!("benchmark");
+    }
+    nested_panic(black_box(depth - 1));
+}
+
+#[cfg(feature = "unwind")]
+fn bench_unwind(partner_prio: u8, overhead: u32) -> Result<(), TaskBuildError> {
+    let mut stats = Stats::new(overhead, 1);
+    for _ in 0..ITERATIONS {
+        task::build()
+            .set_priority(partner_prio)
+            .set_entry(|| {
+                let _watch = StopWatch;
+                nested_panic(UNWIND_DEPTH - 1);
+            })
+            .spawn()?;
+
+        SAMPLE_READY.wait();
+        stats.record(SAMPLE.load(Ordering::SeqCst));
+    }
+    stats.report("unwind_local");
+    Ok(())
+}
@@ -0,0 +1,278 @@
+//! Breadcrumbs that survive a system reset.
+//!
+//! The STM32F4 RTC peripheral has a set of backup registers residing in the
+//! backup power domain. Their content is preserved across system resets,
+//! including watchdog resets, as long as the backup domain stays powered.
+//! This module uses a few of them to record why the system rebooted, a hash
+//! of the location of the most recent panic, and how many times the system
+//! has booted. The values recorded during the previous boot are snapshotted
+//! when the kernel boots and can be read back with [`last_reboot_reason`],
+//! [`last_panic_hash`], and [`boot_count`]. The hardware reset flags are
+//! snapshotted and cleared at the same time and can be read back with
+//! [`last_reset_cause`].
+//!
+//! Only a panic that the system cannot recover from, e.g., a panic while
+//! unwinding, is recorded. A task panic recovered by unwinding leaves no
+//! breadcrumb.
+//!
+//! The kernel touches the backup domain and the reset flags only with the
+//! `breadcrumb` feature. Without it, nothing is recorded and the getters
+//! return the values of a board that was just powered on, with a boot count
+//! of zero.
+
+#[cfg(feature = "breadcrumb")]
+use core::ptr::{read_volatile, write_volatile};
+use core::{
+    panic::PanicInfo,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use int_enum::IntEnum;
+
+/// Address of the `RCC_APB1ENR` register.
+#[cfg(feature = "breadcrumb")]
+const RCC_APB1ENR: *mut u32 = 0x4002_3840 as *mut u32;
+
+/// The `PWREN` bit in `RCC_APB1ENR`.
+#[cfg(feature = "breadcrumb")]
+const RCC_APB1ENR_PWREN: u32 = 1 << 28;
+
+/// Address of the `PWR_CR` register.
+#[cfg(feature = "breadcrumb")]
+const PWR_CR: *mut u32 = 0x4000_7000 as *mut u32;
+
+/// The `DBP` bit in `PWR_CR`. Setting it disables the write protection of the
+/// backup domain.
+#[cfg(feature = "breadcrumb")]
+const PWR_CR_DBP: u32 = 1 << 8;
+
+/// Address of the `RCC_CSR` register.
+#[cfg(feature = "breadcrumb")]
+const RCC_CSR: *mut u32 = 0x4002_3874 as *mut u32;
+
+/// The `RMVF` bit in `RCC_CSR`. Writing 1 clears all reset flags.
+#[cfg(feature = "breadcrumb")]
+const RCC_CSR_RMVF: u32 = 1 << 24;
+
+/// Reset flags in `RCC_CSR` paired with their names, from the most specific
+/// to the least specific.
+const RESET_FLAGS: [(u32, &str); 7] = [
+    (1 << 31, "low-power"),
+    (1 << 30, "window watchdog"),
+    (1 << 29, "independent watchdog"),
+    (1 << 28, "software"),
+    (1 << 27, "power-on"),
+    (1 << 25, "brownout"),
+    (1 << 26, "pin"),
+];
+
+/// Address of the `RTC_BKP0R` register. There are 20 backup registers laid
+/// out consecutively.
+#[cfg(feature = "breadcrumb")]
+const RTC_BKP0R: *mut u32 = 0x4000_2850 as *mut u32;
+
+/// Index of the backup register holding [`BREADCRUMB_MAGIC`].
+#[cfg(feature = "breadcrumb")]
+const MAGIC_IDX: usize = 0;
+/// Index of the backup register holding the boot counter.
+#[cfg(feature = "breadcrumb")]
+const BOOT_COUNT_IDX: usize = 1;
+/// Index of the backup register holding the reboot reason.
+const REBOOT_REASON_IDX: usize = 2;
+/// Index of the backup register holding the last panic hash.
+const PANIC_HASH_IDX: usize = 3;
+/// Index of the backup register holding [`DFU_MAGIC`] when a reboot into the
+/// system bootloader is requested.
+#[cfg(feature = "breadcrumb")]
+const DFU_REQUEST_IDX: usize = 4;
+
+/// A pattern indicating that the backup registers hold valid breadcrumbs
+/// rather than garbage left from a backup domain power loss.
+#[cfg(feature = "breadcrumb")]
+const BREADCRUMB_MAGIC: u32 = 0x4870_4263;
+
+/// A pattern requesting the next boot to enter the system bootloader.
+#[cfg(feature = "breadcrumb")]
+const DFU_MAGIC: u32 = 0x4466_5521;
+
+/// The reason why the system rebooted last time.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntEnum)]
+pub enum RebootReason {
+    /// No reason was recorded, e.g., the board was just powered on or reset
+    /// externally.
+    Unknown = 0,
+    /// The application requested the reboot.
+    Requested = 1,
+    /// An unrecoverable error occurred in the kernel.
+    KernelFault = 2,
+    /// The system was reset by a watchdog.
+    Watchdog = 3,
+    /// A task or an IRQ handler panicked and the system could not recover
+    /// from it.
+    Panic = 4,
+    /// The system rebooted into the bootloader to install an update.
+    Update = 5,
+}
+
+/// The reboot reason recorded during the previous boot.
+static LAST_REBOOT_REASON: AtomicU32 = AtomicU32::new(RebootReason::Unknown as u32);
+
+/// The panic hash recorded during the previous boot. Zero means no panic.
+static LAST_PANIC_HASH: AtomicU32 = AtomicU32::new(0);
+
+/// The number of boots including the current one.
+static BOOT_COUNT: AtomicU32 = AtomicU32::new(0);
+
+/// The content of `RCC_CSR` when the kernel booted.
+static LAST_RESET_FLAGS: AtomicU32 = AtomicU32::new(0);
+
+/// The hash of the location of the most recent panic during the current
+/// boot, written to the backup registers only if the panic cannot be
+/// recovered. Zero means no panic.
+static PANIC_HASH: AtomicU32 = AtomicU32::new(0);
+
+#[cfg(feature = "breadcrumb")]
+fn read_bkp(idx: usize) -> u32 {
+    unsafe { read_volatile(RTC_BKP0R.add(idx)) }
+}
+
+#[cfg(feature = "breadcrumb")]
+fn write_bkp(idx: usize, val: u32) {
+    unsafe { write_volatile(RTC_BKP0R.add(idx), val) }
+}
+
+/// The backup registers are left untouched without the `breadcrumb` feature.
+#[cfg(not(feature = "breadcrumb"))]
+fn write_bkp(_idx: usize, _val: u32) {}
+
+/// Enable write access to the backup domain, snapshot the breadcrumbs left by
+/// the previous boot, and prepare the registers for the current boot. Called
+/// once by the kernel during boot.
+#[cfg(feature = "breadcrumb")]
+pub(crate) fn init() {
+    unsafe {
+        write_volatile(RCC_APB1ENR, read_volatile(RCC_APB1ENR) | RCC_APB1ENR_PWREN);
+        write_volatile(PWR_CR, read_volatile(PWR_CR) | PWR_CR_DBP);
+    }
+
+    // Clear the reset flags so that the next reset reports its own cause.
+    let csr = unsafe { read_volatile(RCC_CSR) };
+    unsafe { write_volatile(RCC_CSR, csr | RCC_CSR_RMVF) };
+    LAST_RESET_FLAGS.store(csr, Ordering::SeqCst);
+
+    // The backup domain lost its power, so the registers hold no meaningful
+    // value. Start from a clean slate.
+    if read_bkp(MAGIC_IDX) != BREADCRUMB_MAGIC {
+        write_bkp(BOOT_COUNT_IDX, 0);
+        write_bkp(REBOOT_REASON_IDX, RebootReason::Unknown as u32);
+        write_bkp(PANIC_HASH_IDX, 0);
+        write_bkp(DFU_REQUEST_IDX, 0);
+        write_bkp(MAGIC_IDX, BREADCRUMB_MAGIC);
+    }
+
+    LAST_REBOOT_REASON.store(read_bkp(REBOOT_REASON_IDX), Ordering::SeqCst);
+    LAST_PANIC_HASH.store(read_bkp(PANIC_HASH_IDX), Ordering::SeqCst);
+
+    let boot_count = read_bkp(BOOT_COUNT_IDX).wrapping_add(1);
+    write_bkp(BOOT_COUNT_IDX, boot_count);
+    BOOT_COUNT.store(boot_count, Ordering::SeqCst);
+
+    // Clear the breadcrumbs for the current boot.
+    write_bkp(REBOOT_REASON_IDX, RebootReason::Unknown as u32);
+    write_bkp(PANIC_HASH_IDX, 0);
+}
+
+/// Record the reason for the upcoming reboot. The value will be available
+/// through [`last_reboot_reason`] after the system reboots.
+pub fn set_reboot_reason(reason: RebootReason) {
+    write_bkp(REBOOT_REASON_IDX, reason as u32);
+}
+
+/// Request the next boot to enter the system bootloader.
+#[cfg(feature = "breadcrumb")]
+pub(crate) fn request_dfu() {
+    write_bkp(DFU_REQUEST_IDX, DFU_MAGIC);
+}
+
+/// Return if the previous boot requested entering the system bootloader, and
+/// clear the request so that the boot after the update proceeds normally.
+#[cfg(feature = "breadcrumb")]
+pub(crate) fn take_dfu_request() -> bool {
+    let requested = read_bkp(DFU_REQUEST_IDX) == DFU_MAGIC;
+    write_bkp(DFU_REQUEST_IDX, 0);
+    requested
+}
+
+/// Get the reboot reason recorded before the current boot.
+pub fn last_reboot_reason() -> RebootReason {
+    RebootReason::try_from(LAST_REBOOT_REASON.load(Ordering::SeqCst))
+        .unwrap_or(RebootReason::Unknown)
+}
+
+/// Get the hash of the location of the most recent panic that occurred before
+/// the current boot. Return `None` if no panic occurred.
+pub fn last_panic_hash() -> Option<u32> {
+    match LAST_PANIC_HASH.load(Ordering::SeqCst) {
+        0 => None,
+        hash => Some(hash),
+    }
+}
+
+/// Get the number of times the system has booted since the backup domain was
+/// last powered on, including the current boot.
+pub fn boot_count() -> u32 {
+    BOOT_COUNT.load(Ordering::SeqCst)
+}
+
+/// Get the hardware cause of the last reset, e.g., `"power-on"` or
+/// `"independent watchdog"`.
+pub fn last_reset_cause() -> &'static str {
+    let csr = LAST_RESET_FLAGS.load(Ordering::SeqCst);
+    RESET_FLAGS
+        .iter()
+        .find(|(mask, _)| csr & mask != 0)
+        .map(|(_, name)| *name)
+        .unwrap_or("unknown")
+}
+
+/// Remember a hash of the panic location. Called from the panic handler.
+/// The hash is written to the backup registers by [`commit_panic`] only if
+/// the system cannot recover from the panic.
+pub(crate) fn record_panic(info: &PanicInfo) {
+    let hash = match info.location() {
+        Some(loc) => panic_location_hash(loc.file(), loc.line(), loc.column()),
+        None => panic_location_hash("", 0, 0),
+    };
+    PANIC_HASH.store(hash, Ordering::SeqCst);
+}
+
+/// Write the hash of the most recent panic to the backup registers and set
+/// the reboot reason to [`RebootReason::Panic`], so that when the system is
+/// eventually reset, e.g., by a watchdog, the cause can be identified.
+/// Called right before the system dies of a panic.
+pub(crate) fn commit_panic() {
+    let hash = PANIC_HASH.load(Ordering::SeqCst);
+    if hash != 0 {
+        write_bkp(PANIC_HASH_IDX, hash);
+        write_bkp(REBOOT_REASON_IDX, RebootReason::Panic as u32);
+    }
+}
+
+/// Compute the FNV-1a hash of a panic location. The result is never zero
+/// because zero is reserved for "no panic".
+fn panic_location_hash(file: &str, line: u32, column: u32) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    let bytes = file
+        .bytes()
+        .chain(line.to_le_bytes())
+        .chain(column.to_le_bytes());
+    for byte in bytes {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    if hash == 0 {
+        1
+    } else {
+        hash
+    }
+}
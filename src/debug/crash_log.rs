@@ -0,0 +1,600 @@
+//! A crash log in flash, enabled by the `crash_log` feature.
+//!
+//! The log is a ring of fixed-size entries over a [`FlashBackend`] reserved
+//! for it, e.g., a couple of internal flash sectors. Each panic appends an
+//! entry with the tick, the ID of the panicked task, the beginning of the
+//! panic message, and the first few program counters unwound from its stack.
+//! Each boot appends an entry with the hardware reset cause and the recorded
+//! [reboot reason](super::breadcrumb::last_reboot_reason). Every entry is
+//! protected by a CRC, so an entry torn by a power loss is ignored when the
+//! log is read back. When the log is full, the oldest erase block is erased
+//! to make room for new entries.
+//!
+//! Flash cannot be written from the panic handler, so a panic only queues its
+//! entry in RAM and a low priority writer task persists it later. The writer
+//! usually runs after the panicked task has been unwound, so that the entry
+//! carries the unwound program counters. A panic that immediately kills the
+//! system, e.g., inside an ISR or without the `unwind` feature, is thus not
+//! persisted. The [breadcrumbs](super::breadcrumb) still record it.
+//!
+//! With the `shell` feature, [`start`] also registers a `crashlog` command
+//! that dumps or clears the log.
+//!
+//! # Example
+//! ```rust
+//! let flash = InternalFlash::new(6, 2).unwrap();
+//! crash_log::start(flash).unwrap();
+//! crash_log::for_each(|entry| {
+//!     dbg_println!("#{} at tick {}: {}", entry.seq(), entry.tick(), entry.reason());
+//! })
+//! .unwrap();
+//! ```
+
+use crate::{
+    config,
+    crc::crc32_update,
+    debug::breadcrumb,
+    fs::{FlashBackend, FlashError},
+    schedule::current,
+    sync::{Access, AllowPendOp, Mailbox, Mutex, RefCellSchedSafe, RunPendedOp, SoftLock, Spin},
+    task::{self, TaskBuildError},
+    time,
+};
+use alloc::boxed::Box;
+use core::{
+    fmt::{self, Write},
+    panic::PanicInfo,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use heapless::{mpmc::MpMcQueue, Deque};
+
+/// The size in bytes of an entry in flash.
+const ENTRY_SIZE: usize = 128;
+
+/// The maximum number of program counters recorded in an entry.
+pub const MAX_FRAMES: usize = 8;
+
+/// The maximum length in bytes of the reason text of an entry.
+pub const MAX_REASON_LEN: usize = 76;
+
+/// A pattern marking a written entry.
+const ENTRY_MAGIC: u16 = 0xC4A5;
+
+/// Enumeration of errors of the crash log API.
+#[derive(Debug, PartialEq)]
+pub enum CrashLogError {
+    /// The crash log has not been started.
+    NotStarted,
+    /// The crash log has already been started.
+    AlreadyStarted,
+    /// The backend has fewer than two blocks or too small blocks.
+    InvalidGeometry,
+    /// Failed to spawn the writer task.
+    Task(TaskBuildError),
+    /// The crash log must not be accessed in ISR context.
+    InIsr,
+    /// The backend reported an error.
+    Flash(FlashError),
+}
+
+impl From<FlashError> for CrashLogError {
+    fn from(err: FlashError) -> Self {
+        Self::Flash(err)
+    }
+}
+
+/// The event recorded by an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrashKind {
+    /// A task or an ISR panicked.
+    Panic,
+    /// The system booted after a reset.
+    Reset,
+}
+
+/// An entry of the crash log.
+#[derive(Clone)]
+pub struct CrashEntry {
+    kind: CrashKind,
+    seq: u32,
+    tick: u32,
+    task_id: u8,
+    in_isr: bool,
+    frame_count: u8,
+    frames: [u32; MAX_FRAMES],
+    reason_len: u8,
+    reason: [u8; MAX_REASON_LEN],
+}
+
+impl CrashEntry {
+    fn new(kind: CrashKind) -> Self {
+        Self {
+            kind,
+            seq: 0,
+            tick: time::get_tick(),
+            task_id: 0,
+            in_isr: false,
+            frame_count: 0,
+            frames: [0; MAX_FRAMES],
+            reason_len: 0,
+            reason: [0; MAX_REASON_LEN],
+        }
+    }
+
+    /// The event recorded by the entry.
+    pub fn kind(&self) -> CrashKind {
+        self.kind
+    }
+
+    /// The sequence number of the entry, which increases with every entry
+    /// appended to the log.
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    /// The tick when the event happened. The tick restarts from zero on every
+    /// boot.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// The ID of the panicked task. Meaningless for [`CrashKind::Reset`].
+    pub fn task_id(&self) -> u8 {
+        self.task_id
+    }
+
+    /// Return if the panic happened in ISR context.
+    pub fn in_isr(&self) -> bool {
+        self.in_isr
+    }
+
+    /// The program counters unwound from the panicked stack, innermost first.
+    pub fn frames(&self) -> &[u32] {
+        &self.frames[..self.frame_count as usize]
+    }
+
+    /// The panic message or the reset cause, truncated to
+    /// [`MAX_REASON_LEN`] bytes.
+    pub fn reason(&self) -> &str {
+        let bytes = &self.reason[..self.reason_len as usize];
+        match core::str::from_utf8(bytes) {
+            Ok(reason) => reason,
+            // The truncation may have split a character.
+            Err(err) => unsafe { core::str::from_utf8_unchecked(&bytes[..err.valid_up_to()]) },
+        }
+    }
+
+    /// Serialize the entry for the given sequence number.
+    fn encode(&self, seq: u32) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        let kind: u8 = match self.kind {
+            CrashKind::Panic => 1,
+            CrashKind::Reset => 2,
+        };
+        bytes[0..2].copy_from_slice(&ENTRY_MAGIC.to_le_bytes());
+        bytes[2] = kind;
+        bytes[3] = self.frame_count;
+        bytes[4..8].copy_from_slice(&seq.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.tick.to_le_bytes());
+        bytes[12] = self.task_id;
+        bytes[13] = self.in_isr as u8;
+        bytes[14] = self.reason_len;
+        for (i, frame) in self.frames.iter().enumerate() {
+            bytes[16 + i * 4..20 + i * 4].copy_from_slice(&frame.to_le_bytes());
+        }
+        bytes[48..48 + MAX_REASON_LEN].copy_from_slice(&self.reason);
+        let crc = !crc32_update(!0, &bytes[..ENTRY_SIZE - 4]);
+        bytes[ENTRY_SIZE - 4..].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    /// Deserialize an entry. Return `None` if the bytes do not hold an intact
+    /// entry.
+    fn decode(bytes: &[u8; ENTRY_SIZE]) -> Option<Self> {
+        let word = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        if u16::from_le_bytes([bytes[0], bytes[1]]) != ENTRY_MAGIC {
+            return None;
+        }
+        if word(ENTRY_SIZE - 4) != !crc32_update(!0, &bytes[..ENTRY_SIZE - 4]) {
+            return None;
+        }
+        let kind = match bytes[2] {
+            1 => CrashKind::Panic,
+            2 => CrashKind::Reset,
+            _ => return None,
+        };
+        let mut entry = Self {
+            kind,
+            seq: word(4),
+            tick: word(8),
+            task_id: bytes[12],
+            in_isr: bytes[13] != 0,
+            frame_count: bytes[3].min(MAX_FRAMES as u8),
+            frames: [0; MAX_FRAMES],
+            reason_len: bytes[14].min(MAX_REASON_LEN as u8),
+            reason: [0; MAX_REASON_LEN],
+        };
+        for (i, frame) in entry.frames.iter_mut().enumerate() {
+            *frame = word(16 + i * 4);
+        }
+        entry
+            .reason
+            .copy_from_slice(&bytes[48..48 + MAX_REASON_LEN]);
+        Some(entry)
+    }
+}
+
+/// Write formatted text into the reason of an entry, silently truncating
+/// whatever does not fit.
+struct ReasonWriter<'a>(&'a mut CrashEntry);
+
+impl Write for ReasonWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let entry = &mut *self.0;
+        let start = entry.reason_len as usize;
+        let len = s.len().min(MAX_REASON_LEN - start);
+        entry.reason[start..start + len].copy_from_slice(&s.as_bytes()[..len]);
+        entry.reason_len += len as u8;
+        Ok(())
+    }
+}
+
+/// The ring of entries over the backend.
+struct Log {
+    backend: Box<dyn FlashBackend>,
+    /// The number of entry slots in an erase block.
+    slots_per_block: usize,
+    /// The slot to try first for the next entry.
+    head: usize,
+    /// The sequence number of the next entry.
+    next_seq: u32,
+}
+
+impl Log {
+    /// Scan the backend for the most recent entry.
+    fn mount(backend: Box<dyn FlashBackend>) -> Result<Self, CrashLogError> {
+        let slots_per_block = backend.block_size() / ENTRY_SIZE;
+        if backend.block_count() < 2 || slots_per_block == 0 {
+            return Err(CrashLogError::InvalidGeometry);
+        }
+        let mut log = Self {
+            backend,
+            slots_per_block,
+            head: 0,
+            next_seq: 0,
+        };
+        let mut latest: Option<(u32, usize)> = None;
+        for slot in 0..log.slot_count() {
+            if let Some(entry) = log.read(slot)? {
+                if latest.map_or(true, |(seq, _)| entry.seq.wrapping_sub(seq) as i32 > 0) {
+                    latest = Some((entry.seq, slot));
+                }
+            }
+        }
+        if let Some((seq, slot)) = latest {
+            log.head = (slot + 1) % log.slot_count();
+            log.next_seq = seq.wrapping_add(1);
+        }
+        Ok(log)
+    }
+
+    fn slot_count(&self) -> usize {
+        self.slots_per_block * self.backend.block_count()
+    }
+
+    fn read_raw(&mut self, slot: usize) -> Result<[u8; ENTRY_SIZE], FlashError> {
+        let mut bytes = [0; ENTRY_SIZE];
+        self.backend.read(slot * ENTRY_SIZE, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn read(&mut self, slot: usize) -> Result<Option<CrashEntry>, FlashError> {
+        Ok(CrashEntry::decode(&self.read_raw(slot)?))
+    }
+
+    fn is_erased(&mut self, slot: usize) -> Result<bool, FlashError> {
+        Ok(self.read_raw(slot)?.iter().all(|byte| *byte == 0xFF))
+    }
+
+    /// Append the entry after the most recent one. Slots torn by a power loss
+    /// are skipped. The block holding the oldest entries is erased when the
+    /// head enters it.
+    fn append(&mut self, entry: &CrashEntry) -> Result<(), FlashError> {
+        for _ in 0..self.slot_count() {
+            let slot = self.head;
+            self.head = (self.head + 1) % self.slot_count();
+            if slot % self.slots_per_block == 0 && !self.is_erased(slot)? {
+                self.backend.erase(slot / self.slots_per_block)?;
+            }
+            if self.is_erased(slot)? {
+                let bytes = entry.encode(self.next_seq);
+                self.next_seq = self.next_seq.wrapping_add(1);
+                return self.backend.write(slot * ENTRY_SIZE, &bytes);
+            }
+        }
+        Err(FlashError::Hardware)
+    }
+
+    /// Call the closure with every entry, from the oldest to the newest.
+    fn for_each<F: FnMut(&CrashEntry)>(&mut self, mut op: F) -> Result<(), FlashError> {
+        let count = self.slot_count();
+        for i in 0..count {
+            if let Some(entry) = self.read((self.head + i) % count)? {
+                op(&entry);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), FlashError> {
+        for block in 0..self.backend.block_count() {
+            self.backend.erase(block)?;
+        }
+        self.head = 0;
+        Ok(())
+    }
+}
+
+static LOG: Mutex<Option<Log>> = Mutex::new(None);
+
+/// Set when the log has been started, so that panics start being queued.
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// The queue of entries recorded by panics but not yet written to flash.
+struct Pending {
+    /// The spin lock around it is only for sanity check. This field should
+    /// not be accessed concurrently.
+    entries: Spin<Deque<CrashEntry, { config::CRASH_LOG_PENDING_NUMBER }>>,
+    /// The entries recorded by panicking ISRs preempting the owner of the
+    /// full access, moved to `entries` when the access is released.
+    pended: MpMcQueue<CrashEntry, { config::CRASH_LOG_PENDING_NUMBER }>,
+}
+
+/// Representing full access to all fields of the [`Pending`] queue.
+struct PendingFullAccessor<'a> {
+    entries: &'a Spin<Deque<CrashEntry, { config::CRASH_LOG_PENDING_NUMBER }>>,
+    pended: &'a MpMcQueue<CrashEntry, { config::CRASH_LOG_PENDING_NUMBER }>,
+}
+
+/// Representing pend-only access to the [`Pending`] queue. Only an entry to
+/// be queued later can be pended.
+struct PendingPendAccessor<'a> {
+    pended: &'a MpMcQueue<CrashEntry, { config::CRASH_LOG_PENDING_NUMBER }>,
+}
+
+/// Bind the accessor types.
+impl<'a> AllowPendOp<'a> for Pending {
+    type FullAccessor = PendingFullAccessor<'a>;
+    type PendOnlyAccessor = PendingPendAccessor<'a>;
+    fn full_access(&'a self) -> Self::FullAccessor {
+        Self::FullAccessor {
+            entries: &self.entries,
+            pended: &self.pended,
+        }
+    }
+
+    fn pend_only_access(&'a self) -> Self::PendOnlyAccessor {
+        Self::PendOnlyAccessor {
+            pended: &self.pended,
+        }
+    }
+}
+
+/// A pended operation is always queuing an entry. Move the pended entries to
+/// the queue, dropping them if the queue is full.
+impl<'a> RunPendedOp for PendingFullAccessor<'a> {
+    fn run_pended_op(&mut self) {
+        let mut entries = self.entries.lock_now_or_die();
+        while let Some(entry) = self.pended.dequeue() {
+            let _ = entries.push_back(entry);
+        }
+    }
+}
+
+/// Entries recorded by panics but not yet written to flash. A panicking ISR
+/// preempting a task accessing the queue pends its entry, which the task
+/// queues when it finishes the access.
+static PENDING: RefCellSchedSafe<SoftLock<Pending>> =
+    RefCellSchedSafe::new(SoftLock::new(Pending {
+        entries: Spin::new(Deque::new()),
+        pended: MpMcQueue::new(),
+    }));
+
+/// Notified when an entry is queued.
+static WRITER: Mailbox = Mailbox::new();
+
+/// Run the closure with the started log locked.
+fn with_log<F, R>(op: F) -> Result<R, CrashLogError>
+where
+    F: FnOnce(&mut Log) -> Result<R, FlashError>,
+{
+    if current::is_in_isr_context() {
+        return Err(CrashLogError::InIsr);
+    }
+    match LOG.lock().as_mut() {
+        Some(log) => Ok(op(log)?),
+        None => Err(CrashLogError::NotStarted),
+    }
+}
+
+/// Start the crash log on the backend, which must be reserved for the log.
+/// An unrecognized backend is treated as an empty log. An entry for the
+/// current boot is appended and the writer task is spawned.
+pub fn start<B>(backend: B) -> Result<(), CrashLogError>
+where
+    B: FlashBackend + 'static,
+{
+    if current::is_in_isr_context() {
+        return Err(CrashLogError::InIsr);
+    }
+    let mut log = LOG.lock();
+    if log.is_some() {
+        return Err(CrashLogError::AlreadyStarted);
+    }
+    let mut new_log = Log::mount(Box::new(backend))?;
+
+    let mut entry = CrashEntry::new(CrashKind::Reset);
+    let _ = write!(
+        ReasonWriter(&mut entry),
+        "{} reset, {:?}",
+        breadcrumb::last_reset_cause(),
+        breadcrumb::last_reboot_reason()
+    );
+    new_log.append(&entry)?;
+
+    task::build()
+        .set_id(config::CRASH_LOG_TASK_ID)
+        .set_name("crash_log")
+        .set_priority(config::CRASH_LOG_TASK_PRIORITY)
+        .set_entry(writer)
+        .spawn()
+        .map_err(CrashLogError::Task)?;
+
+    *log = Some(new_log);
+    STARTED.store(true, Ordering::SeqCst);
+
+    #[cfg(feature = "shell")]
+    let _ = crate::shell::register(
+        "crashlog",
+        "[clear]",
+        "dump or clear the crash log",
+        command,
+    );
+
+    Ok(())
+}
+
+/// Call the closure with every entry in the log, from the oldest to the
+/// newest. The closure runs with the log locked, so it must not access the
+/// log.
+pub fn for_each<F>(op: F) -> Result<(), CrashLogError>
+where
+    F: FnMut(&CrashEntry),
+{
+    with_log(|log| log.for_each(op))
+}
+
+/// Erase all entries in the log.
+pub fn clear() -> Result<(), CrashLogError> {
+    with_log(|log| log.clear())
+}
+
+/// The body of the writer task.
+fn writer() {
+    loop {
+        WRITER.wait();
+        flush();
+    }
+}
+
+/// Write the queued entries to flash in the calling task. Called by the
+/// writer task and when the system shuts down.
+pub(crate) fn flush() {
+    loop {
+        // Should always grant full access to a task.
+        let entry = PENDING.with_suspended_scheduler(|pending, _| {
+            pending.must_with_full_access(|full_access| {
+                full_access.entries.lock_now_or_die().pop_front()
+            })
+        });
+        let Some(entry) = entry else {
+            break;
+        };
+        // Nothing can be done if the flash fails.
+        let _ = with_log(|log| log.append(&entry));
+    }
+}
+
+/// Queue an entry for the panic. Called from the panic handler.
+pub(crate) fn record_panic(info: &PanicInfo) {
+    if !STARTED.load(Ordering::SeqCst) {
+        return;
+    }
+    let mut entry = CrashEntry::new(CrashKind::Panic);
+    entry.in_isr = current::is_in_isr_context();
+    if !entry.in_isr {
+        entry.task_id = task::get_current_id();
+    }
+    let _ = write!(ReasonWriter(&mut entry), "{}", info);
+
+    // Drop the entry if the queue is full.
+    PENDING.with_suspended_scheduler(|pending, _| {
+        pending.with_access(|access| match access {
+            Access::Full { full_access } => {
+                let _ = full_access.entries.lock_now_or_die().push_back(entry);
+            }
+            Access::PendOnly { pend_access } => {
+                let _ = pend_access.pended.enqueue(entry);
+            }
+        })
+    });
+    WRITER.notify_allow_isr();
+}
+
+/// Append an unwound program counter to the queued entry of the panic of the
+/// current task. Called by the unwinder for every frame.
+pub(crate) fn record_frame(pc: u32) {
+    if !STARTED.load(Ordering::SeqCst) || current::is_in_isr_context() {
+        return;
+    }
+    let task_id = task::get_current_id();
+    // Should always grant full access to a task.
+    PENDING.with_suspended_scheduler(|pending, _| {
+        pending.must_with_full_access(|full_access| {
+            let mut entries = full_access.entries.lock_now_or_die();
+            let entry = entries
+                .iter_mut()
+                .rev()
+                .find(|entry| entry.kind == CrashKind::Panic && entry.task_id == task_id);
+            if let Some(entry) = entry {
+                if (entry.frame_count as usize) < MAX_FRAMES {
+                    entry.frames[entry.frame_count as usize] = pc;
+                    entry.frame_count += 1;
+                }
+            }
+        })
+    });
+}
+
+#[cfg(feature = "shell")]
+fn command(
+    args: &crate::shell::Args,
+    out: &mut crate::shell::Output,
+) -> Result<(), crate::shell::CommandError> {
+    use crate::shell::CommandError;
+    use alloc::vec::Vec;
+
+    let failed = |_| CommandError::Failed("crash log unavailable");
+    match (args.len(), args.get(0)) {
+        (0, _) => {
+            // Collect the entries first because printing may block.
+            let mut entries = Vec::new();
+            for_each(|entry| entries.push(entry.clone())).map_err(failed)?;
+            for entry in entries.iter() {
+                write!(out, "#{} tick {} ", entry.seq(), entry.tick())?;
+                match entry.kind() {
+                    CrashKind::Reset => writeln!(out, "reset: {}", entry.reason())?,
+                    CrashKind::Panic if entry.in_isr() => {
+                        writeln!(out, "panic in ISR: {}", entry.reason())?
+                    }
+                    CrashKind::Panic => {
+                        writeln!(out, "panic in task {}: {}", entry.task_id(), entry.reason())?
+                    }
+                }
+                for pc in entry.frames() {
+                    writeln!(out, "    at {:#010x}", pc)?;
+                }
+            }
+            Ok(())
+        }
+        (1, Some("clear")) => clear().map_err(failed),
+        _ => Err(CommandError::Usage),
+    }
+}
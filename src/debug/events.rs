@@ -0,0 +1,232 @@
+//! A flight recorder of timestamped events that survives a system reset.
+//!
+//! [`record`] appends an event with an application defined code and argument
+//! to a fixed-size ring of [`EVENTS_RING_LENGTH`](config::EVENTS_RING_LENGTH)
+//! entries, overwriting the oldest entry when the ring is full. Recording
+//! takes no lock and never blocks, so it can be called from any context,
+//! including ISRs, the scheduler, and code holding a spin lock, with a small
+//! and constant overhead. Each event is stamped with the CPU cycle count
+//! since boot, wrapping around `u32::MAX`, which resolves the order and the
+//! distance of events even within a single tick.
+//!
+//! The ring is placed in a RAM section that is not initialized on boot, so
+//! its content is preserved across a reset that keeps the RAM powered, e.g.,
+//! a watchdog reset or a reset requested after a panic. On boot, the ring is
+//! cleared only if it does not hold valid entries, and an event with the code
+//! [`BOOT_CODE`] is recorded with the boot count as its argument. Events
+//! following the last boot event were recorded during the current boot.
+//!
+//! The events can be read back with [`for_each`] or printed through
+//! semihosting with [`dump`]. With the `shell` feature, the `events` command
+//! prints them too.
+//!
+//! # Example
+//! ```rust
+//! const RX_START: u16 = 1;
+//! const RX_DONE: u16 = 2;
+//!
+//! events::record(RX_START, dma_stream as u32);
+//! // ...
+//! events::record(RX_DONE, received_len as u32);
+//!
+//! events::for_each(|event| {
+//!     dbg_println!("{} {} {}", event.stamp(), event.code(), event.arg());
+//! });
+//! ```
+
+use crate::{
+    config,
+    debug::{breadcrumb, semihosting::dbg_println},
+    time,
+};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// The code of the event recorded on boot. Applications should not record
+/// events with this code.
+pub const BOOT_CODE: u16 = u16::MAX;
+
+/// A pattern indicating that the ring holds valid entries rather than
+/// garbage left from a power loss.
+const EVENTS_MAGIC: u32 = 0x4576_5274;
+
+/// A recorded event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    seq: u32,
+    stamp: u32,
+    code: u16,
+    arg: u32,
+}
+
+impl Event {
+    /// The sequence number of the event, which increases with every recorded
+    /// event, including those recorded before a reset.
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    /// The CPU cycle count when the event was recorded. The count restarts
+    /// from zero on every boot.
+    pub fn stamp(&self) -> u32 {
+        self.stamp
+    }
+
+    /// The code of the event.
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    /// The argument of the event.
+    pub fn arg(&self) -> u32 {
+        self.arg
+    }
+}
+
+/// An entry of the ring.
+struct Slot {
+    /// One more than the sequence number of the event in the slot, or zero if
+    /// the slot is empty or being written.
+    seq: AtomicU32,
+    stamp: AtomicU32,
+    code: AtomicU32,
+    arg: AtomicU32,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+            stamp: AtomicU32::new(0),
+            code: AtomicU32::new(0),
+            arg: AtomicU32::new(0),
+        }
+    }
+}
+
+/// A ring of events and its state. All fields are atomic integers, for
+/// which any bit pattern left in RAM is a valid value.
+pub(crate) struct Ring<const N: usize> {
+    magic: AtomicU32,
+    /// The sequence number of the next event.
+    next: AtomicU32,
+    slots: [Slot; N],
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const SLOT_INIT: Slot = Slot::new();
+
+impl<const N: usize> Ring<N> {
+    /// Create an empty ring. `N` must be a power of two so that the ring
+    /// index stays consistent when the 32-bit entry counter wraps around.
+    pub(crate) const fn new() -> Self {
+        assert!(N.is_power_of_two());
+        Self {
+            magic: AtomicU32::new(0),
+            next: AtomicU32::new(0),
+            slots: [SLOT_INIT; N],
+        }
+    }
+
+    /// Clear the ring if it does not hold valid entries, i.e., if it is not
+    /// marked with the given magic pattern.
+    pub(crate) fn init(&self, magic: u32) {
+        if self.magic.load(Ordering::SeqCst) != magic {
+            for slot in self.slots.iter() {
+                slot.seq.store(0, Ordering::Relaxed);
+            }
+            self.next.store(0, Ordering::Relaxed);
+            self.magic.store(magic, Ordering::SeqCst);
+        }
+    }
+
+    /// Record an event, overwriting the oldest event if the ring is full.
+    pub(crate) fn record(&self, code: u16, arg: u32) {
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let slot = &self.slots[seq as usize % N];
+
+        // Invalidate the slot while it is being written, so that a reader
+        // never sees a mix of two events.
+        slot.seq.store(0, Ordering::Relaxed);
+        slot.stamp.store(time::cycle_stamp(), Ordering::Relaxed);
+        slot.code.store(code as u32, Ordering::Relaxed);
+        slot.arg.store(arg, Ordering::Relaxed);
+        slot.seq.store(seq.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Read the event with the sequence number. Return `None` if it has been
+    /// overwritten or is being written.
+    fn read(&self, seq: u32) -> Option<Event> {
+        let slot = &self.slots[seq as usize % N];
+        let tag = slot.seq.load(Ordering::Acquire);
+        let event = Event {
+            seq,
+            stamp: slot.stamp.load(Ordering::Relaxed),
+            code: slot.code.load(Ordering::Relaxed) as u16,
+            arg: slot.arg.load(Ordering::Relaxed),
+        };
+        // The slot is rewritten if the tag changed while reading.
+        let valid = tag == seq.wrapping_add(1) && slot.seq.load(Ordering::Acquire) == tag;
+        valid.then_some(event)
+    }
+
+    /// Call the closure with each event in the ring, from the oldest to the
+    /// newest. Events overwritten while iterating are skipped.
+    pub(crate) fn for_each<F>(&self, mut op: F)
+    where
+        F: FnMut(Event),
+    {
+        let next = self.next.load(Ordering::Acquire);
+        let count = (N as u32).min(next);
+        for offset in (1..=count).rev() {
+            if let Some(event) = self.read(next.wrapping_sub(offset)) {
+                op(event);
+            }
+        }
+    }
+}
+
+/// The ring, in a section not initialized on boot. The initializer below is
+/// ignored.
+#[link_section = ".hopter_uninit"]
+static RING: Ring<{ config::EVENTS_RING_LENGTH }> = Ring::new();
+
+/// Clear the ring if it does not hold valid entries and record the boot
+/// event. Called once by the kernel during boot after the breadcrumbs are
+/// initialized.
+pub(crate) fn init() {
+    RING.init(EVENTS_MAGIC);
+    record(BOOT_CODE, breadcrumb::boot_count());
+}
+
+/// Record an event with the code and the argument, overwriting the oldest
+/// event if the ring is full.
+///
+/// Calling this function in ISR context is allowed.
+pub fn record(code: u16, arg: u32) {
+    RING.record(code, arg);
+}
+
+/// Call the closure with each event in the ring, from the oldest to the
+/// newest. Events overwritten while iterating are skipped.
+///
+/// Calling this function in ISR context is allowed.
+pub fn for_each<F>(op: F)
+where
+    F: FnMut(Event),
+{
+    RING.for_each(op);
+}
+
+/// Print each event in the ring through semihosting, from the oldest to the
+/// newest.
+pub fn dump() {
+    for_each(|event| {
+        dbg_println!(
+            "event #{} at {}: code {} arg {}",
+            event.seq(),
+            event.stamp(),
+            event.code(),
+            event.arg()
+        );
+    });
+}
@@ -0,0 +1,281 @@
+//! Leveled log messages written to a sink by a logger task.
+//!
+//! Log messages are formatted by the logging task or ISR into a fixed-size
+//! record and queued. The logger task started by [`start`] drains the queue
+//! and writes the records to a [`LogSink`], e.g., a channel drained by a
+//! UART. Before the logger task is started, messages are printed through
+//! semihosting instead, as `[LEVEL] text`. The logger task writes them as
+//! `[tick] [LEVEL] task <id>: text`, or `isr: text` if logged by an ISR.
+//! Messages more verbose than the tunable log level, see
+//! [`tunable::log_level`], are discarded.
+//!
+//! # Arbitration
+//!
+//! Writing to a sink may block for a long time, e.g., while a UART drains.
+//! If tasks wrote to the sink directly, a high priority task logging while a
+//! low priority task holds the sink would wait for the low priority task,
+//! which in turn may be preempted by any task in between. Instead, only the
+//! logger task ever touches the sink, and logging never waits for it.
+//!
+//! The queue is lock-free, so logging never masks IRQs and never waits for
+//! another logging task or ISR. The worst-case latency added to a logging
+//! task or ISR is formatting the message into its own stack plus copying
+//! one record of [`LOG_RECORD_LENGTH`](config::LOG_RECORD_LENGTH) bytes into
+//! the queue, independent of the sink and of other logging tasks. The price
+//! is that messages are dropped when the queue is full, which the logger
+//! task reports, and that messages are truncated to the record length.
+//!
+//! # Example
+//! ```rust
+//! let (tx_producer, tx_consumer) = sync::create_channel::<u8, 256>();
+//! // The UART IRQ handler drains `tx_consumer`.
+//! log::start(tx_producer).unwrap();
+//!
+//! log_info!("sensor ready after {} ms", time::get_tick());
+//! ```
+
+use crate::{
+    config::{self, tunable},
+    debug::semihosting::dbg_println,
+    schedule::current,
+    sync::{Mailbox, Producer},
+    task::{self, TaskBuildError},
+    time,
+};
+use core::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+};
+use heapless::{mpmc::MpMcQueue, String};
+
+/// The verbosity of a log message. The numerical values are compared with
+/// the tunable log level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+}
+
+impl Level {
+    fn tag(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+/// Enumeration of errors of the log API.
+#[derive(Debug, PartialEq)]
+pub enum LogError {
+    /// [`start`] has already been called.
+    AlreadyStarted,
+    /// The logger task cannot be spawned.
+    Task(TaskBuildError),
+}
+
+/// A destination of log records.
+pub trait LogSink: Send {
+    /// Write the bytes, blocking until all of them are accepted.
+    fn write(&mut self, data: &[u8]);
+}
+
+/// A channel carrying the bytes to be transmitted.
+impl<const N: usize> LogSink for Producer<u8, N> {
+    fn write(&mut self, data: &[u8]) {
+        for byte in data {
+            self.produce(*byte);
+        }
+    }
+}
+
+/// A queued log message.
+struct Record {
+    level: Level,
+    tick: u32,
+    /// The ID of the logging task, or `None` if logged by an ISR.
+    task_id: Option<u8>,
+    text: String<{ config::LOG_RECORD_LENGTH }>,
+}
+
+/// Append characters until the record is full and drop the rest.
+struct Truncate<'a>(&'a mut String<{ config::LOG_RECORD_LENGTH }>);
+
+impl Write for Truncate<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for ch in s.chars() {
+            if self.0.push(ch).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The records waiting for the logger task.
+static PENDING: MpMcQueue<Record, { config::LOG_QUEUE_LENGTH }> = MpMcQueue::new();
+
+/// The number of records in the queue or being queued, since the queue
+/// cannot tell.
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+
+/// Set when the logger task is started.
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// The number of records dropped because the queue was full, not yet
+/// reported by the logger task.
+static DROPPED: AtomicU32 = AtomicU32::new(0);
+
+/// Set while the logger task is writing records to the sink.
+static WRITING: AtomicBool = AtomicBool::new(false);
+
+/// Notified when a record is queued.
+static LOGGER: Mailbox = Mailbox::new();
+
+/// Spawn the logger task writing records to the sink.
+pub fn start<S>(mut sink: S) -> Result<(), LogError>
+where
+    S: LogSink + 'static,
+{
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return Err(LogError::AlreadyStarted);
+    }
+    task::build()
+        .set_id(config::LOG_TASK_ID)
+        .set_name("log")
+        .set_priority(config::LOG_TASK_PRIORITY)
+        .set_entry(move || logger(&mut sink))
+        .spawn()
+        .map_err(|err| {
+            STARTED.store(false, Ordering::SeqCst);
+            LogError::Task(err)
+        })
+}
+
+/// Log a message at the given level. Prefer the `log_error!`, `log_warn!`,
+/// `log_info!`, and `log_debug!` macros.
+///
+/// Calling this function in ISR context is allowed.
+pub fn log(level: Level, args: fmt::Arguments) {
+    if level as u32 > tunable::log_level() {
+        return;
+    }
+
+    if !STARTED.load(Ordering::SeqCst) {
+        dbg_println!("[{}] {}", level.tag(), args);
+        return;
+    }
+
+    let mut record = Record {
+        level,
+        tick: time::get_tick(),
+        task_id: if current::is_in_isr_context() {
+            None
+        } else {
+            Some(task::get_current_id())
+        },
+        text: String::new(),
+    };
+    let _ = Truncate(&mut record.text).write_fmt(args);
+
+    // Count the record first, so that the logger task never takes it before
+    // it is counted.
+    QUEUED.fetch_add(1, Ordering::SeqCst);
+    if PENDING.enqueue(record).is_err() {
+        QUEUED.fetch_sub(1, Ordering::SeqCst);
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+    LOGGER.notify_allow_isr();
+}
+
+/// The body of the logger task.
+fn logger(sink: &mut dyn LogSink) {
+    let mut line: String<{ config::LOG_RECORD_LENGTH + 32 }> = String::new();
+    loop {
+        LOGGER.wait();
+        WRITING.store(true, Ordering::SeqCst);
+
+        let dropped = DROPPED.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            line.clear();
+            let _ = writeln!(
+                line,
+                "[{}] [WARN] log: {} dropped",
+                time::get_tick(),
+                dropped
+            );
+            sink.write(line.as_bytes());
+        }
+
+        while let Some(record) = PENDING.dequeue() {
+            QUEUED.fetch_sub(1, Ordering::SeqCst);
+            line.clear();
+            let _ = write!(line, "[{}] [{}] ", record.tick, record.level.tag());
+            let _ = match record.task_id {
+                Some(id) => write!(line, "task {}: ", id),
+                None => write!(line, "isr: "),
+            };
+            let _ = line.push_str(&record.text);
+            let _ = line.push('\n');
+            sink.write(line.as_bytes());
+        }
+        WRITING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Return if all queued records have been written to the sink. Always true
+/// before the logger task is started. Used to flush the log when the system
+/// shuts down.
+pub(crate) fn is_drained() -> bool {
+    !STARTED.load(Ordering::SeqCst)
+        || (!WRITING.load(Ordering::SeqCst) && QUEUED.load(Ordering::SeqCst) == 0)
+}
+
+/// Log a message at the error level.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __macro_impl_log_error {
+    ($($tt:tt)*) => {
+        $crate::debug::log::log($crate::debug::log::Level::Error, format_args!($($tt)*))
+    };
+}
+
+/// Log a message at the warning level.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __macro_impl_log_warn {
+    ($($tt:tt)*) => {
+        $crate::debug::log::log($crate::debug::log::Level::Warn, format_args!($($tt)*))
+    };
+}
+
+/// Log a message at the informational level.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __macro_impl_log_info {
+    ($($tt:tt)*) => {
+        $crate::debug::log::log($crate::debug::log::Level::Info, format_args!($($tt)*))
+    };
+}
+
+/// Log a message at the debugging level.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __macro_impl_log_debug {
+    ($($tt:tt)*) => {
+        $crate::debug::log::log($crate::debug::log::Level::Debug, format_args!($($tt)*))
+    };
+}
+
+#[doc(inline)]
+pub use __macro_impl_log_debug as log_debug;
+#[doc(inline)]
+pub use __macro_impl_log_error as log_error;
+#[doc(inline)]
+pub use __macro_impl_log_info as log_info;
+#[doc(inline)]
+pub use __macro_impl_log_warn as log_warn;
@@ -1,3 +1,19 @@
+#[cfg(feature = "benches")]
+pub mod bench;
+pub mod breadcrumb;
 pub mod cpu_load;
+#[cfg(feature = "crash_log")]
+pub mod crash_log;
+pub mod events;
+pub mod log;
+pub mod panic_report;
+#[cfg(feature = "sched_events")]
+pub mod sched_events;
 pub mod segmented_stack;
 pub mod semihosting;
+#[cfg(feature = "soft_lock_stats")]
+pub mod soft_lock_stats;
+#[cfg(feature = "trace")]
+pub mod trace;
+#[cfg(feature = "trace")]
+pub mod watch;
@@ -0,0 +1,311 @@
+//! Panic reports written to a sink in an application chosen format.
+//!
+//! When a task or an ISR panics, a [`PanicReport`] describing the panic is
+//! formatted by the registered [`PanicFormatter`] and written to the
+//! registered [`PanicSink`], e.g., a channel drained by a UART towards a
+//! fleet-management backend. Without a sink, no report is written. Without a
+//! formatter, the report is written by [`Text`]. [`Json`] writes a single
+//! JSON object per report, and applications can implement the trait for
+//! other formats, e.g., CBOR or defmt.
+//!
+//! The report is written by the panic handler before the panicked task is
+//! unwound, so the formatter and the sink must not block and must not
+//! panic. A panic inside the formatter or the sink is not reported, and
+//! neither is a panic in an ISR preempting a task while it sets the sink or
+//! the formatter.
+//!
+//! # Example
+//! ```rust
+//! let (tx_producer, tx_consumer) = sync::create_channel::<u8, 512>();
+//! // The UART IRQ handler drains `tx_consumer`.
+//! panic_report::set_sink(tx_producer);
+//! panic_report::set_formatter(Json);
+//! ```
+
+use crate::{
+    schedule::current,
+    sync::{Access, AllowPendOp, Producer, RefCellSchedSafe, RunPendedOp, SoftLock, Spin},
+    task, time,
+};
+use alloc::boxed::Box;
+use core::{
+    fmt::{self, Write},
+    panic::{Location, PanicInfo},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A panic being reported.
+pub struct PanicReport<'a> {
+    info: &'a PanicInfo<'a>,
+    task_id: Option<u8>,
+    task_name: Option<&'static str>,
+    tick: u32,
+}
+
+impl<'a> PanicReport<'a> {
+    /// The information passed to the panic handler. Its `Display`
+    /// implementation writes the location and the message.
+    pub fn info(&self) -> &PanicInfo<'a> {
+        self.info
+    }
+
+    /// The location of the panic in the source code, if known.
+    pub fn location(&self) -> Option<&Location<'a>> {
+        self.info.location()
+    }
+
+    /// The ID of the panicked task, or `None` if an ISR panicked.
+    pub fn task_id(&self) -> Option<u8> {
+        self.task_id
+    }
+
+    /// The name of the panicked task, or `None` if an ISR panicked or the
+    /// task is not named.
+    pub fn task_name(&self) -> Option<&'static str> {
+        self.task_name
+    }
+
+    /// The tick when the panic occurred.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+}
+
+/// Turn a [`PanicReport`] into the wire format of the application.
+pub trait PanicFormatter: Send {
+    /// Write the report to `out`. Errors returned by `out` are never
+    /// caused by the sink and can be ignored.
+    fn format(&self, report: &PanicReport, out: &mut dyn Write) -> fmt::Result;
+}
+
+/// A destination of formatted panic reports.
+pub trait PanicSink: Send {
+    /// Write the bytes without blocking, dropping those that do not fit.
+    fn write(&mut self, data: &[u8]);
+}
+
+/// A channel carrying the bytes to be transmitted. Bytes are dropped when
+/// the channel is full.
+impl<const N: usize> PanicSink for Producer<u8, N> {
+    fn write(&mut self, data: &[u8]) {
+        for byte in data {
+            if self.try_produce_allow_isr(*byte).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// The default formatter, writing one line per report, e.g.,
+/// `panic in task 3 (sensor) at tick 1200: panicked at src/main.rs:10:5: boom`.
+/// The name in parentheses is omitted if the task is not named.
+pub struct Text;
+
+impl PanicFormatter for Text {
+    fn format(&self, report: &PanicReport, out: &mut dyn Write) -> fmt::Result {
+        match report.task_id() {
+            Some(id) => write!(out, "panic in task {}", id)?,
+            None => write!(out, "panic in ISR")?,
+        }
+        if let Some(name) = report.task_name() {
+            write!(out, " ({})", name)?;
+        }
+        writeln!(out, " at tick {}: {}", report.tick(), report.info())
+    }
+}
+
+/// A formatter writing one JSON object per line, e.g.,
+/// `{"tick":1200,"task":3,"name":"sensor","file":"src/main.rs","line":10,"column":5,"report":"..."}`.
+/// The `task` field is `null` if an ISR panicked, the `name` field is
+/// omitted if the task is not named, and the location fields are omitted if
+/// the location is unknown.
+pub struct Json;
+
+impl PanicFormatter for Json {
+    fn format(&self, report: &PanicReport, out: &mut dyn Write) -> fmt::Result {
+        write!(out, "{{\"tick\":{},\"task\":", report.tick())?;
+        match report.task_id() {
+            Some(id) => write!(out, "{}", id)?,
+            None => write!(out, "null")?,
+        }
+        if let Some(name) = report.task_name() {
+            write!(out, ",\"name\":\"")?;
+            write!(JsonEscape(out), "{}", name)?;
+            write!(out, "\"")?;
+        }
+        if let Some(loc) = report.location() {
+            write!(out, ",\"file\":\"")?;
+            write!(JsonEscape(out), "{}", loc.file())?;
+            write!(
+                out,
+                "\",\"line\":{},\"column\":{}",
+                loc.line(),
+                loc.column()
+            )?;
+        }
+        write!(out, ",\"report\":\"")?;
+        write!(JsonEscape(out), "{}", report.info())?;
+        writeln!(out, "\"}}")
+    }
+}
+
+/// Escape the written text for a JSON string.
+struct JsonEscape<'a>(&'a mut dyn Write);
+
+impl Write for JsonEscape<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            match c {
+                '"' => self.0.write_str("\\\"")?,
+                '\\' => self.0.write_str("\\\\")?,
+                '\n' => self.0.write_str("\\n")?,
+                c if (c as u32) < 0x20 => write!(self.0, "\\u{:04x}", c as u32)?,
+                c => self.0.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Pass the formatted text to the sink.
+struct SinkWriter<'a>(&'a mut dyn PanicSink);
+
+impl Write for SinkWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+struct Config {
+    /// The spin locks around the fields are only for sanity check. The
+    /// fields should not be accessed concurrently.
+    sink: Spin<Option<Box<dyn PanicSink>>>,
+    formatter: Spin<Option<Box<dyn PanicFormatter>>>,
+}
+
+/// Representing full access to all fields of the [`Config`].
+struct ConfigFullAccessor<'a> {
+    sink: &'a Spin<Option<Box<dyn PanicSink>>>,
+    formatter: &'a Spin<Option<Box<dyn PanicFormatter>>>,
+}
+
+/// Representing pend-only access to the [`Config`]. A report cannot be
+/// written later, so nothing is granted.
+struct ConfigPendAccessor;
+
+/// Bind the accessor types.
+impl<'a> AllowPendOp<'a> for Config {
+    type FullAccessor = ConfigFullAccessor<'a>;
+    type PendOnlyAccessor = ConfigPendAccessor;
+    fn full_access(&'a self) -> Self::FullAccessor {
+        Self::FullAccessor {
+            sink: &self.sink,
+            formatter: &self.formatter,
+        }
+    }
+
+    fn pend_only_access(&'a self) -> Self::PendOnlyAccessor {
+        ConfigPendAccessor
+    }
+}
+
+/// No operation is ever pended.
+impl<'a> RunPendedOp for ConfigFullAccessor<'a> {
+    fn run_pended_op(&mut self) {}
+}
+
+/// A panic in an ISR preempting a task that accesses the configuration is
+/// not reported, so that it never waits for the preempted context.
+static CONFIG: RefCellSchedSafe<SoftLock<Config>> = RefCellSchedSafe::new(SoftLock::new(Config {
+    sink: Spin::new(None),
+    formatter: Spin::new(None),
+}));
+
+/// Whether a report is being written. A panic while writing a report is
+/// not reported.
+static REPORTING: AtomicBool = AtomicBool::new(false);
+
+/// Clear [`REPORTING`] when the report is written or the writing is
+/// unwound.
+struct ReportingGuard;
+
+impl Drop for ReportingGuard {
+    fn drop(&mut self) {
+        REPORTING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Write the panic reports to the sink, replacing the previous sink.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn set_sink<S>(sink: S)
+where
+    S: PanicSink + 'static,
+{
+    // Drop the previous sink after releasing the access. Should always grant
+    // full access to a task.
+    let prev = CONFIG.with_suspended_scheduler(|config, _| {
+        config.must_with_full_access(|full_access| {
+            full_access.sink.lock_now_or_die().replace(Box::new(sink))
+        })
+    });
+    drop(prev);
+}
+
+/// Format the panic reports with the formatter, replacing the previous
+/// formatter.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn set_formatter<F>(formatter: F)
+where
+    F: PanicFormatter + 'static,
+{
+    let prev = CONFIG.with_suspended_scheduler(|config, _| {
+        config.must_with_full_access(|full_access| {
+            full_access
+                .formatter
+                .lock_now_or_die()
+                .replace(Box::new(formatter))
+        })
+    });
+    drop(prev);
+}
+
+/// Write the report of the panic to the sink. Called from the panic
+/// handler.
+pub(crate) fn report(info: &PanicInfo) {
+    if REPORTING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let _guard = ReportingGuard;
+
+    let (task_id, task_name) = if current::is_in_isr_context() {
+        (None, None)
+    } else {
+        (Some(task::get_current_id()), task::current_name())
+    };
+    let report = PanicReport {
+        info,
+        task_id,
+        task_name,
+        tick: time::get_tick(),
+    };
+
+    // The access may be held by the context preempted by the panicked ISR.
+    CONFIG.with_suspended_scheduler(|config, _| {
+        config.with_access(|access| {
+            let Access::Full { full_access } = access else {
+                return;
+            };
+            let mut sink = full_access.sink.lock_now_or_die();
+            let Some(sink) = sink.as_mut() else {
+                return;
+            };
+            let formatter = full_access.formatter.lock_now_or_die();
+            let formatter = formatter.as_deref().unwrap_or(&Text);
+            // Nothing can be done if formatting fails.
+            let _ = formatter.format(&report, &mut SinkWriter(sink.as_mut()));
+        })
+    });
+}
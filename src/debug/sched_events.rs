@@ -0,0 +1,188 @@
+//! A ring of recent scheduler events for post-mortem analysis, enabled by
+//! the `sched_events` feature.
+//!
+//! The kernel records the following events into a ring of
+//! [`SCHED_EVENTS_RING_LENGTH`](config::SCHED_EVENTS_RING_LENGTH) entries,
+//! overwriting the oldest entry when the ring is full:
+//!
+//! - `switch`: a task is switched on to the CPU.
+//! - `block`: a task is switched out of the CPU because it blocked, e.g., on
+//!   a mailbox, a semaphore, or by sleeping.
+//! - `wakeup`: a task becomes ready, including when it is spawned or
+//!   restarted.
+//! - `unwind`: a task starts unwinding, because it panicked or is forcefully
+//!   unwound. Unwinding in ISR context is recorded without a task.
+//!
+//! Like the [flight recorder](super::events), the ring is lock-free, stamps
+//! each event with the CPU cycle count since boot, and is placed in a RAM
+//! section that is not initialized on boot. The events leading to a panic
+//! can thus be read back after the panicked task is unwound, or after a
+//! reset that keeps the RAM powered. On boot, a `boot` event is recorded, so
+//! events preceding the last `boot` event were recorded before the reset.
+//!
+//! The events can be read back with [`for_each`], printed through
+//! semihosting with [`dump`], or, with the `shell` feature, printed by the
+//! `events sched` command, e.g., over a UART.
+//!
+//! # Example
+//! ```rust
+//! sched_events::for_each(|event| {
+//!     if event.kind() == SchedEventKind::Unwind {
+//!         log::warn!("task {:?} unwound at {}", event.task(), event.stamp());
+//!     }
+//! });
+//! ```
+
+use super::{
+    events::{Event, Ring},
+    semihosting::dbg_println,
+};
+use crate::{config, task::TaskId};
+
+/// A pattern indicating that the ring holds valid entries rather than
+/// garbage left from a power loss.
+const SCHED_EVENTS_MAGIC: u32 = 0x5363_4576;
+
+/// The argument of an event without a task.
+const NO_TASK: u32 = u32::MAX;
+
+/// The kind of a scheduler event. See the [module-level
+/// documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedEventKind {
+    /// The system booted.
+    Boot,
+    /// A task was switched on to the CPU.
+    Switch,
+    /// A task was switched out of the CPU because it blocked.
+    Block,
+    /// A task became ready.
+    Wakeup,
+    /// A task started unwinding.
+    Unwind,
+}
+
+impl SchedEventKind {
+    fn code(self) -> u16 {
+        self as u16
+    }
+
+    fn from_code(code: u16) -> Option<Self> {
+        [
+            Self::Boot,
+            Self::Switch,
+            Self::Block,
+            Self::Wakeup,
+            Self::Unwind,
+        ]
+        .into_iter()
+        .find(|kind| kind.code() == code)
+    }
+
+    /// The name of the event kind in lower case.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Boot => "boot",
+            Self::Switch => "switch",
+            Self::Block => "block",
+            Self::Wakeup => "wakeup",
+            Self::Unwind => "unwind",
+        }
+    }
+}
+
+/// A recorded scheduler event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchedEvent {
+    seq: u32,
+    stamp: u32,
+    kind: SchedEventKind,
+    task: Option<TaskId>,
+}
+
+impl SchedEvent {
+    fn from_event(event: Event) -> Option<Self> {
+        Some(Self {
+            seq: event.seq(),
+            stamp: event.stamp(),
+            kind: SchedEventKind::from_code(event.code())?,
+            task: (event.arg() != NO_TASK).then(|| TaskId::from_raw(event.arg())),
+        })
+    }
+
+    /// The sequence number of the event, which increases with every recorded
+    /// event, including those recorded before a reset.
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    /// The CPU cycle count when the event was recorded. The count restarts
+    /// from zero on every boot.
+    pub fn stamp(&self) -> u32 {
+        self.stamp
+    }
+
+    /// The kind of the event.
+    pub fn kind(&self) -> SchedEventKind {
+        self.kind
+    }
+
+    /// The task of the event, or `None` for a `boot` event or unwinding in
+    /// ISR context.
+    pub fn task(&self) -> Option<TaskId> {
+        self.task
+    }
+}
+
+/// The ring, in a section not initialized on boot. The initializer below is
+/// ignored.
+#[link_section = ".hopter_uninit"]
+static RING: Ring<{ config::SCHED_EVENTS_RING_LENGTH }> = Ring::new();
+
+/// Clear the ring if it does not hold valid entries and record the boot
+/// event. Called once by the kernel during boot.
+pub(crate) fn init() {
+    RING.init(SCHED_EVENTS_MAGIC);
+    RING.record(SchedEventKind::Boot.code(), NO_TASK);
+}
+
+/// Record an event of the task, or of no task if `None`. Called by the
+/// kernel in any context.
+pub(crate) fn record(kind: SchedEventKind, task: Option<TaskId>) {
+    RING.record(kind.code(), task.map_or(NO_TASK, TaskId::get));
+}
+
+/// Call the closure with each event in the ring, from the oldest to the
+/// newest. Events overwritten while iterating are skipped.
+///
+/// Calling this function in ISR context is allowed.
+pub fn for_each<F>(mut op: F)
+where
+    F: FnMut(SchedEvent),
+{
+    RING.for_each(|event| {
+        if let Some(event) = SchedEvent::from_event(event) {
+            op(event);
+        }
+    });
+}
+
+/// Print each event in the ring through semihosting, from the oldest to the
+/// newest.
+pub fn dump() {
+    for_each(|event| match event.task() {
+        Some(task) => dbg_println!(
+            "sched #{} at {}: {} task {}",
+            event.seq(),
+            event.stamp(),
+            event.kind().name(),
+            task
+        ),
+        None => dbg_println!(
+            "sched #{} at {}: {}",
+            event.seq(),
+            event.stamp(),
+            event.kind().name()
+        ),
+    });
+}
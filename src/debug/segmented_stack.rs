@@ -1,2 +1,5 @@
 #[doc(inline)]
-pub use crate::task::segmented_stack::{get_active_stacklet_count, get_stack_extend_count};
+pub use crate::task::segmented_stack::{
+    get_active_stacklet_count, get_reserved_stack_size, get_stack_extend_count, get_stack_headroom,
+    get_stacklet_cache_hit_count, get_stacklet_cache_miss_count,
+};
@@ -0,0 +1,64 @@
+//! Contention statistics of soft locks, enabled by the `soft_lock_stats`
+//! feature.
+//!
+//! Kernel queues and synchronization primitives are protected by soft locks
+//! that never mask IRQs. When an ISR or a higher priority context preempts
+//! the owner of the full access, it is granted pend-only access and its
+//! operation is pended and later run by the owner. Frequent pend-only
+//! accesses or long batches of pended operations indicate contention, e.g.,
+//! an ISR firing while tasks are modifying a queue.
+//!
+//! The statistics of a primitive instance are read with its
+//! `soft_lock_stats` method, e.g., [`Mailbox::soft_lock_stats`]. The
+//! statistics of the kernel queues are read with the functions below.
+//!
+//! [`Mailbox::soft_lock_stats`]: crate::sync::Mailbox::soft_lock_stats
+//!
+//! # Example
+//! ```rust
+//! let stats = debug::soft_lock_stats::ready_queue();
+//! dbg_println!("{} pended of {}", stats.pend_only, stats.full + stats.pend_only);
+//! ```
+
+use crate::{schedule::scheduler, time};
+
+/// The contention statistics of a soft lock.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SoftLockStats {
+    /// The number of times the full access was granted.
+    pub full: u32,
+    /// The number of times only the pend-only access was granted, i.e., the
+    /// operation was pended.
+    pub pend_only: u32,
+    /// The largest number of pended operations that the owner of the full
+    /// access ran at once before releasing it.
+    pub max_pended: u32,
+}
+
+impl SoftLockStats {
+    /// Combine the statistics of two soft locks, e.g., those of a primitive
+    /// built from several soft locks. The counts are summed, and the larger
+    /// `max_pended` is kept.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            full: self.full.wrapping_add(other.full),
+            pend_only: self.pend_only.wrapping_add(other.pend_only),
+            max_pended: self.max_pended.max(other.max_pended),
+        }
+    }
+}
+
+/// Return the contention statistics of the ready task queue.
+pub fn ready_queue() -> SoftLockStats {
+    scheduler::ready_queue_soft_lock_stats()
+}
+
+/// Return the contention statistics of the sleeping task queue.
+pub fn sleep_queue() -> SoftLockStats {
+    time::sleep_queue_soft_lock_stats()
+}
+
+/// Return the contention statistics of the software timer queue.
+pub fn timer_queue() -> SoftLockStats {
+    time::timer::timer_queue_soft_lock_stats()
+}
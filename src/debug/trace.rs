@@ -0,0 +1,271 @@
+//! A stream of timestamped scheduler and application events for host-side
+//! timeline tools, enabled by the `trace` feature.
+//!
+//! Once the trace task is started by [`start`], the kernel records a
+//! `switch` event whenever a task is switched on to the CPU, and
+//! applications record:
+//!
+//! - application phases with [`span!`], as a `span_begin` event and a
+//!   `span_end` event when the returned guard is dropped, including when the
+//!   task is unwound.
+//! - IRQ handlers with [`irq_scope_allow_isr`], as an `irq_enter` event and
+//!   an `irq_exit` event when the returned guard is dropped.
+//!
+//! The trace task also records a `sample` event for each variable watched
+//! with [`watch`](super::watch).
+//!
+//! Events are buffered in RAM and written to a [`TraceSink`] by the trace
+//! task every [`TRACE_FLUSH_PERIOD_MS`](config::TRACE_FLUSH_PERIOD_MS)
+//! milliseconds. Events recorded while the buffer is full are dropped and
+//! reported with a `dropped` event. Each event is written as one line:
+//!
+//! ```text
+//! trace,<cycles>,<task>,<event>,<arg>
+//! ```
+//!
+//! `cycles` is the CPU cycle count since boot, wrapping around `u32::MAX`.
+//! `task` is the ID of the task switched on or recording the event, or `isr`
+//! for events recorded in ISR context. `arg` is the span name, the IRQ
+//! number, the number of dropped events, `<name>=<value>` for `sample`, or
+//! empty for `switch`.
+//!
+//! # Example
+//! ```rust
+//! let (tx_producer, tx_consumer) = sync::create_channel::<u8, 1024>();
+//! // The UART IRQ handler drains `tx_consumer`.
+//! trace::start(tx_producer).unwrap();
+//!
+//! let _span = trace::span!("load_config");
+//! ```
+
+use super::watch::{self, WatchValue};
+use crate::{
+    config,
+    schedule::current,
+    sync::Producer,
+    task::{self, TaskBuildError},
+    time,
+};
+use alloc::format;
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+use cortex_m::peripheral::{scb::VectActive, SCB};
+use heapless::mpmc::MpMcQueue;
+
+/// Enumeration of errors of the trace API.
+#[derive(Debug, PartialEq)]
+pub enum TraceError {
+    /// [`start`] has already been called.
+    AlreadyStarted,
+    /// The trace task cannot be spawned.
+    Task(TaskBuildError),
+}
+
+/// A destination of trace events.
+pub trait TraceSink: Send {
+    /// Write the bytes, blocking until all of them are accepted.
+    fn write(&mut self, data: &[u8]);
+}
+
+/// A channel carrying the bytes to be transmitted.
+impl<const N: usize> TraceSink for Producer<u8, N> {
+    fn write(&mut self, data: &[u8]) {
+        for byte in data {
+            self.produce(*byte);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Event {
+    Switch,
+    SpanBegin(&'static str),
+    SpanEnd(&'static str),
+    IrqEnter(u16),
+    IrqExit(u16),
+    Sample(&'static str, WatchValue),
+}
+
+struct Record {
+    stamp: u32,
+    /// The ID of the task, or `None` if recorded in ISR context.
+    task_id: Option<u8>,
+    event: Event,
+}
+
+/// The events waiting for the trace task. The ring is lock-free, so that
+/// recording an event never masks IRQs.
+static BUFFER: MpMcQueue<Record, { config::TRACE_BUFFER_LENGTH }> = MpMcQueue::new();
+
+/// Set when the trace task is started. No event is recorded before.
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// The number of events dropped because the buffer was full, not yet
+/// reported by the trace task.
+static DROPPED: AtomicU32 = AtomicU32::new(0);
+
+/// Spawn the trace task writing events to the sink.
+pub fn start<S>(mut sink: S) -> Result<(), TraceError>
+where
+    S: TraceSink + 'static,
+{
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return Err(TraceError::AlreadyStarted);
+    }
+    task::build()
+        .set_id(config::TRACE_TASK_ID)
+        .set_name("trace")
+        .set_priority(config::TRACE_TASK_PRIORITY)
+        .set_entry(move || {
+            let mut last_sample_tick = time::get_tick();
+            loop {
+                let _ = time::sleep_ms(config::TRACE_FLUSH_PERIOD_MS);
+                watch::sample_if_due(&mut last_sample_tick);
+                flush(&mut sink);
+            }
+        })
+        .spawn()
+        .map_err(|err| {
+            STARTED.store(false, Ordering::SeqCst);
+            TraceError::Task(err)
+        })
+}
+
+/// Buffer the event if the trace task is started.
+fn record(task_id: Option<u8>, event: Event) {
+    if !STARTED.load(Ordering::Relaxed) {
+        return;
+    }
+    let record = Record {
+        stamp: time::cycle_stamp(),
+        task_id,
+        event,
+    };
+    if BUFFER.enqueue(record).is_err() {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Record an event tagged with the current task, or as recorded in ISR
+/// context.
+fn record_current(event: Event) {
+    let task_id = if current::is_in_isr_context() {
+        None
+    } else {
+        Some(task::get_current_id())
+    };
+    record(task_id, event);
+}
+
+/// Record switching the task with the ID on to the CPU. Called by the
+/// scheduler.
+pub(crate) fn record_switch(task_id: u8) {
+    record(Some(task_id), Event::Switch);
+}
+
+/// Record a sample of the watched variable with the name.
+pub(super) fn record_sample(name: &'static str, value: WatchValue) {
+    record_current(Event::Sample(name, value));
+}
+
+/// Write the buffered events to the sink.
+fn flush(sink: &mut dyn TraceSink) {
+    let dropped = DROPPED.swap(0, Ordering::Relaxed);
+    if dropped > 0 {
+        let line = format!("trace,{},isr,dropped,{}\n", time::cycle_stamp(), dropped);
+        sink.write(line.as_bytes());
+    }
+
+    while let Some(record) = BUFFER.dequeue() {
+        let sample;
+        let (name, arg): (&str, &dyn fmt::Display) = match &record.event {
+            Event::Switch => ("switch", &""),
+            Event::SpanBegin(name) => ("span_begin", name),
+            Event::SpanEnd(name) => ("span_end", name),
+            Event::IrqEnter(irq) => ("irq_enter", irq),
+            Event::IrqExit(irq) => ("irq_exit", irq),
+            Event::Sample(var, value) => {
+                sample = format!("{}={}", var, value);
+                ("sample", &sample)
+            }
+        };
+        let line = match record.task_id {
+            Some(id) => format!("trace,{},{},{},{}\n", record.stamp, id, name, arg),
+            None => format!("trace,{},isr,{},{}\n", record.stamp, name, arg),
+        };
+        sink.write(line.as_bytes());
+    }
+}
+
+/// A span of an application phase, created by [`span!`]. The end of the span
+/// is recorded when dropped.
+#[must_use = "the span ends when dropped"]
+pub struct Span {
+    name: &'static str,
+}
+
+impl Span {
+    /// Record the beginning of the span with the name. Prefer [`span!`].
+    ///
+    /// Calling this function in ISR context is allowed.
+    pub fn begin(name: &'static str) -> Self {
+        record_current(Event::SpanBegin(name));
+        Self { name }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        record_current(Event::SpanEnd(self.name));
+    }
+}
+
+/// The execution of an IRQ handler, created by [`irq_scope_allow_isr`]. The
+/// exit of the handler is recorded when dropped.
+#[must_use = "the IRQ exit is recorded when dropped"]
+pub struct IrqScope {
+    irq: u16,
+}
+
+impl Drop for IrqScope {
+    fn drop(&mut self) {
+        record(None, Event::IrqExit(self.irq));
+    }
+}
+
+/// Record entering the running IRQ handler, and exiting it when the returned
+/// guard is dropped. Return `None` if not called by an IRQ handler.
+///
+/// # Example
+/// ```rust
+/// #[handler(TIM2)]
+/// fn tim2_handler() {
+///     let _irq = trace::irq_scope_allow_isr();
+///     // ...
+/// }
+/// ```
+pub fn irq_scope_allow_isr() -> Option<IrqScope> {
+    match SCB::vect_active() {
+        VectActive::Interrupt { irqn } => {
+            let irq = irqn as u16;
+            record(None, Event::IrqEnter(irq));
+            Some(IrqScope { irq })
+        }
+        _ => None,
+    }
+}
+
+/// Begin a span with the given name, returning a guard that ends the span
+/// when dropped.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __macro_impl_span {
+    ($name:expr) => {
+        $crate::debug::trace::Span::begin($name)
+    };
+}
+
+#[doc(inline)]
+pub use __macro_impl_span as span;
@@ -0,0 +1,163 @@
+//! Watch expressions sampled periodically into the trace stream, enabled by
+//! the `trace` feature.
+//!
+//! Applications register named variables with [`watch`], or raw memory
+//! addresses with [`watch_address`]. Once the trace task is started by
+//! [`trace::start`], it samples every registered variable each
+//! [`TRACE_WATCH_PERIOD_MS`](config::TRACE_WATCH_PERIOD_MS) milliseconds and
+//! writes the values to the trace sink as `sample` events, giving a
+//! continuous view of the firmware state without halting the CPU:
+//!
+//! ```text
+//! trace,<cycles>,<task>,sample,<name>=<value>
+//! ```
+//!
+//! Variables are read without synchronizing with their writers, so a
+//! variable that is not an atomic integer may be sampled in the middle of
+//! an update.
+//!
+//! # Example
+//! ```rust
+//! static RX_BYTES: AtomicU32 = AtomicU32::new(0);
+//!
+//! watch::watch("uart.rx_bytes", &RX_BYTES).unwrap();
+//! unsafe { watch::watch_address("tim2.cnt", 0x4000_0024 as *const u32) }.unwrap();
+//! trace::start(tx_producer).unwrap();
+//! ```
+
+use super::trace;
+use crate::{config, sync::Mutex, time};
+use alloc::vec::Vec;
+use core::{
+    fmt,
+    ptr::read_volatile,
+    sync::atomic::{
+        AtomicBool, AtomicI16, AtomicI32, AtomicI8, AtomicU16, AtomicU32, AtomicU8, AtomicUsize,
+        Ordering,
+    },
+};
+
+/// Enumeration of errors of the watch API.
+#[derive(Debug, PartialEq)]
+pub enum WatchError {
+    /// Another variable is watched with the same name.
+    NameTaken,
+}
+
+/// A sampled value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchValue {
+    Unsigned(u32),
+    Signed(i32),
+    Bool(bool),
+}
+
+impl fmt::Display for WatchValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsigned(val) => write!(f, "{}", val),
+            Self::Signed(val) => write!(f, "{}", val),
+            Self::Bool(val) => write!(f, "{}", val),
+        }
+    }
+}
+
+/// A variable that can be watched.
+pub trait Watchable: Sync {
+    /// Read the current value of the variable.
+    fn sample(&self) -> WatchValue;
+}
+
+macro_rules! impl_watchable {
+    ($($atomic:ty => $variant:ident as $ty:ty),* $(,)?) => {
+        $(
+            impl Watchable for $atomic {
+                fn sample(&self) -> WatchValue {
+                    WatchValue::$variant(self.load(Ordering::Relaxed) as $ty)
+                }
+            }
+        )*
+    };
+}
+
+impl_watchable!(
+    AtomicU8 => Unsigned as u32,
+    AtomicU16 => Unsigned as u32,
+    AtomicU32 => Unsigned as u32,
+    AtomicUsize => Unsigned as u32,
+    AtomicI8 => Signed as i32,
+    AtomicI16 => Signed as i32,
+    AtomicI32 => Signed as i32,
+    AtomicBool => Bool as bool,
+);
+
+/// A registered variable.
+enum Source {
+    Variable(&'static dyn Watchable),
+    /// The address of a word in memory.
+    Address(usize),
+}
+
+impl Source {
+    fn sample(&self) -> WatchValue {
+        match self {
+            Self::Variable(var) => var.sample(),
+            Self::Address(addr) => {
+                WatchValue::Unsigned(unsafe { read_volatile(*addr as *const u32) })
+            }
+        }
+    }
+}
+
+static WATCHES: Mutex<Vec<(&'static str, Source)>> = Mutex::new(Vec::new());
+
+/// Watch the variable under the name.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn watch(name: &'static str, var: &'static dyn Watchable) -> Result<(), WatchError> {
+    register(name, Source::Variable(var))
+}
+
+/// Watch the 32-bit word at the address under the name, e.g., a peripheral
+/// register or a variable located by the linker map.
+///
+/// # Safety
+/// The address must be word aligned and remain readable, without side
+/// effects, for as long as it is watched.
+///
+/// Important: *must not* call this function in ISR context.
+pub unsafe fn watch_address(name: &'static str, addr: *const u32) -> Result<(), WatchError> {
+    register(name, Source::Address(addr as usize))
+}
+
+fn register(name: &'static str, source: Source) -> Result<(), WatchError> {
+    let mut watches = WATCHES.lock();
+    if watches.iter().any(|(watched, _)| *watched == name) {
+        return Err(WatchError::NameTaken);
+    }
+    watches.push((name, source));
+    Ok(())
+}
+
+/// Stop watching the variable with the name. Return whether it was watched.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn unwatch(name: &str) -> bool {
+    let mut watches = WATCHES.lock();
+    let len = watches.len();
+    watches.retain(|(watched, _)| *watched != name);
+    watches.len() != len
+}
+
+/// Sample all watched variables into the trace buffer if the watch period
+/// has elapsed since the last sampling. Called by the trace task.
+pub(super) fn sample_if_due(last_tick: &mut u32) {
+    let now = time::get_tick();
+    if now.wrapping_sub(*last_tick) < config::TRACE_WATCH_PERIOD_MS {
+        return;
+    }
+    *last_tick = now;
+    for (name, source) in WATCHES.lock().iter() {
+        trace::record_sample(name, source.sample());
+    }
+}
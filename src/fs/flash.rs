@@ -0,0 +1,282 @@
+use crate::sync::Mutex;
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::ptr::{read_volatile, write_volatile};
+
+/// Enumeration of errors of flash operations.
+#[derive(Debug, PartialEq)]
+pub enum FlashError {
+    /// The access goes beyond the flash region.
+    OutOfBounds,
+    /// The offset or length of a write is not a multiple of 4.
+    Misaligned,
+    /// The flash controller reported an error.
+    Hardware,
+}
+
+/// A flash region organized as equally sized erase blocks.
+///
+/// Erasing a block sets all its bytes to `0xFF`. Writing can only clear bits,
+/// so a byte must be erased before it can be written with an arbitrary value.
+/// Writes always start at an offset that is a multiple of 4 and have a length
+/// that is a multiple of 4. Reads may be of any offset and length.
+pub trait FlashBackend: Send {
+    /// The size of an erase block in bytes.
+    fn block_size(&self) -> usize;
+
+    /// The number of erase blocks in the region.
+    fn block_count(&self) -> usize;
+
+    /// Read bytes starting at the byte offset from the start of the region.
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), FlashError>;
+
+    /// Write bytes starting at the byte offset from the start of the region.
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError>;
+
+    /// Erase the block with the given index.
+    fn erase(&mut self, block: usize) -> Result<(), FlashError>;
+}
+
+/// Validate the range of an access to a region of `size` bytes.
+fn check_range(offset: usize, len: usize, size: usize) -> Result<(), FlashError> {
+    match offset.checked_add(len) {
+        Some(end) if end <= size => Ok(()),
+        _ => Err(FlashError::OutOfBounds),
+    }
+}
+
+/// A flash backend simulated in RAM. Its content is lost on reset, so it is
+/// only useful for testing code written against [`FlashBackend`].
+///
+/// Clones share the same memory, so that a test can keep a clone to inspect
+/// the content, or to mount it again after the filesystem is unmounted.
+#[derive(Clone)]
+pub struct RamFlash {
+    block_size: usize,
+    block_count: usize,
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl RamFlash {
+    /// Create an erased region with the given geometry.
+    pub fn new(block_size: usize, block_count: usize) -> Self {
+        Self {
+            block_size,
+            block_count,
+            data: Arc::new(Mutex::new(vec![0xFF; block_size * block_count])),
+        }
+    }
+}
+
+impl FlashBackend for RamFlash {
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> usize {
+        self.block_count
+    }
+
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), FlashError> {
+        let mem = self.data.lock();
+        check_range(offset, buf.len(), mem.len())?;
+        buf.copy_from_slice(&mem[offset..offset + buf.len()]);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError> {
+        if offset % 4 != 0 || data.len() % 4 != 0 {
+            return Err(FlashError::Misaligned);
+        }
+        let mut mem = self.data.lock();
+        check_range(offset, data.len(), mem.len())?;
+        // Like real flash, writing can only clear bits.
+        for (dst, src) in mem[offset..].iter_mut().zip(data) {
+            *dst &= *src;
+        }
+        Ok(())
+    }
+
+    fn erase(&mut self, block: usize) -> Result<(), FlashError> {
+        if block >= self.block_count {
+            return Err(FlashError::OutOfBounds);
+        }
+        let start = block * self.block_size;
+        self.data.lock()[start..start + self.block_size].fill(0xFF);
+        Ok(())
+    }
+}
+
+/// Base address of the internal flash memory.
+const FLASH_BASE: usize = 0x0800_0000;
+
+/// Address of the `FLASH_ACR` register.
+const FLASH_ACR: *mut u32 = 0x4002_3C00 as *mut u32;
+/// Address of the `FLASH_KEYR` register.
+const FLASH_KEYR: *mut u32 = 0x4002_3C04 as *mut u32;
+/// Address of the `FLASH_SR` register.
+const FLASH_SR: *mut u32 = 0x4002_3C0C as *mut u32;
+/// Address of the `FLASH_CR` register.
+const FLASH_CR: *mut u32 = 0x4002_3C10 as *mut u32;
+
+/// The keys to write into `FLASH_KEYR` in order to unlock `FLASH_CR`.
+const FLASH_KEYS: [u32; 2] = [0x4567_0123, 0xCDEF_89AB];
+
+const FLASH_ACR_DCEN: u32 = 1 << 10;
+const FLASH_ACR_DCRST: u32 = 1 << 12;
+const FLASH_SR_BSY: u32 = 1 << 16;
+/// The `PGSERR`, `PGPERR`, `PGAERR`, `WRPERR`, and `OPERR` bits.
+const FLASH_SR_ERRORS: u32 = 0b1111_0010;
+const FLASH_CR_PG: u32 = 1 << 0;
+const FLASH_CR_SER: u32 = 1 << 1;
+const FLASH_CR_SNB_SHIFT: u32 = 3;
+/// Program 32 bits at a time.
+const FLASH_CR_PSIZE_X32: u32 = 0b10 << 8;
+const FLASH_CR_STRT: u32 = 1 << 16;
+const FLASH_CR_LOCK: u32 = 1 << 31;
+
+/// The first sector of the 128 KiB sectors.
+const FIRST_LARGE_SECTOR: usize = 5;
+/// The last sector of the devices with 1 MiB flash.
+const LAST_SECTOR: usize = 11;
+/// The size of sectors from [`FIRST_LARGE_SECTOR`].
+const LARGE_SECTOR_SIZE: usize = 128 * 1024;
+
+/// Serializes access to the flash controller among all [`InternalFlash`]
+/// instances. A mutex rather than a spin lock is used because erasing a
+/// sector takes up to seconds.
+static FLASH_CTRL: Mutex<()> = Mutex::new(());
+
+/// A range of the 128 KiB sectors of the STM32F4 internal flash. The sectors
+/// must not overlap with the program image.
+///
+/// The CPU stalls when fetching instructions from flash while the flash is
+/// being erased or written.
+pub struct InternalFlash {
+    first_sector: usize,
+    sector_count: usize,
+}
+
+impl InternalFlash {
+    /// Use `sector_count` sectors starting from `first_sector`. Return `None`
+    /// if the range includes sectors other than the 128 KiB sectors 5 to 11.
+    pub fn new(first_sector: usize, sector_count: usize) -> Option<Self> {
+        if first_sector < FIRST_LARGE_SECTOR
+            || sector_count == 0
+            || first_sector + sector_count - 1 > LAST_SECTOR
+        {
+            return None;
+        }
+        Some(Self {
+            first_sector,
+            sector_count,
+        })
+    }
+
+    fn base_addr(&self) -> usize {
+        FLASH_BASE + LARGE_SECTOR_SIZE * (self.first_sector - 4)
+    }
+
+    fn size(&self) -> usize {
+        LARGE_SECTOR_SIZE * self.sector_count
+    }
+
+    /// Run the operation with the flash controller unlocked and locked again
+    /// afterwards.
+    fn with_unlocked<F>(op: F) -> Result<(), FlashError>
+    where
+        F: FnOnce() -> Result<(), FlashError>,
+    {
+        let _guard = FLASH_CTRL.lock();
+        unsafe {
+            wait_not_busy();
+            write_volatile(FLASH_SR, FLASH_SR_ERRORS);
+            if read_volatile(FLASH_CR) & FLASH_CR_LOCK != 0 {
+                write_volatile(FLASH_KEYR, FLASH_KEYS[0]);
+                write_volatile(FLASH_KEYR, FLASH_KEYS[1]);
+            }
+        }
+        let res = op();
+        unsafe { write_volatile(FLASH_CR, FLASH_CR_LOCK) };
+        res
+    }
+}
+
+/// Spin until the flash controller finishes the ongoing operation.
+unsafe fn wait_not_busy() {
+    while unsafe { read_volatile(FLASH_SR) } & FLASH_SR_BSY != 0 {}
+}
+
+/// Check and clear the error flags of the last operation.
+unsafe fn take_error() -> Result<(), FlashError> {
+    let sr = unsafe { read_volatile(FLASH_SR) };
+    if sr & FLASH_SR_ERRORS != 0 {
+        unsafe { write_volatile(FLASH_SR, FLASH_SR_ERRORS) };
+        return Err(FlashError::Hardware);
+    }
+    Ok(())
+}
+
+impl FlashBackend for InternalFlash {
+    fn block_size(&self) -> usize {
+        LARGE_SECTOR_SIZE
+    }
+
+    fn block_count(&self) -> usize {
+        self.sector_count
+    }
+
+    fn read(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), FlashError> {
+        check_range(offset, buf.len(), self.size())?;
+        // The flash is memory mapped.
+        let src = (self.base_addr() + offset) as *const u8;
+        for (idx, byte) in buf.iter_mut().enumerate() {
+            *byte = unsafe { read_volatile(src.add(idx)) };
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: usize, data: &[u8]) -> Result<(), FlashError> {
+        if offset % 4 != 0 || data.len() % 4 != 0 {
+            return Err(FlashError::Misaligned);
+        }
+        check_range(offset, data.len(), self.size())?;
+
+        let dst = (self.base_addr() + offset) as *mut u32;
+        Self::with_unlocked(|| {
+            unsafe { write_volatile(FLASH_CR, FLASH_CR_PSIZE_X32 | FLASH_CR_PG) };
+            for (idx, word) in data.chunks_exact(4).enumerate() {
+                let word = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+                unsafe {
+                    write_volatile(dst.add(idx), word);
+                    wait_not_busy();
+                    take_error()?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn erase(&mut self, block: usize) -> Result<(), FlashError> {
+        if block >= self.sector_count {
+            return Err(FlashError::OutOfBounds);
+        }
+
+        let sector = (self.first_sector + block) as u32;
+        Self::with_unlocked(|| unsafe {
+            write_volatile(
+                FLASH_CR,
+                FLASH_CR_PSIZE_X32 | FLASH_CR_SER | (sector << FLASH_CR_SNB_SHIFT),
+            );
+            write_volatile(FLASH_CR, read_volatile(FLASH_CR) | FLASH_CR_STRT);
+            wait_not_busy();
+            take_error()?;
+
+            // The data cache may hold the content before erasure.
+            let acr = read_volatile(FLASH_ACR);
+            write_volatile(FLASH_ACR, acr & !FLASH_ACR_DCEN);
+            write_volatile(FLASH_ACR, (acr & !FLASH_ACR_DCEN) | FLASH_ACR_DCRST);
+            write_volatile(FLASH_ACR, acr);
+            Ok(())
+        })
+    }
+}
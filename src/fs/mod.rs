@@ -0,0 +1,299 @@
+//! A power-loss-safe flash filesystem, enabled by the `fs` feature.
+//!
+//! The filesystem is mounted once on a [`FlashBackend`], e.g., a range of the
+//! internal flash sectors with [`InternalFlash`], and then shared by all
+//! tasks. The mount state is protected by a mutex, so every operation is
+//! atomic with respect to other tasks. Files are identified by their path,
+//! which is an arbitrary string without any directory semantics.
+//!
+//! The filesystem is log-structured and spreads erasures over all blocks of
+//! the backend. A file is always rewritten as a whole, and either the old or
+//! the new content survives a power loss. Consequently, a file must fit in a
+//! single erase block, and a writable [`File`] buffers its content in RAM
+//! until it is flushed. The filesystem suits configuration data and crash
+//! logs rather than bulk storage.
+//!
+//! # Example
+//! ```rust
+//! let flash = InternalFlash::new(10, 2).unwrap();
+//! if fs::mount(flash).is_err() {
+//!     let mut flash = InternalFlash::new(10, 2).unwrap();
+//!     fs::format(&mut flash).unwrap();
+//!     fs::mount(flash).unwrap();
+//! }
+//!
+//! fs::write("config", b"baud=115200").unwrap();
+//! let mut buf = [0; 32];
+//! let len = fs::read("config", &mut buf).unwrap();
+//! ```
+
+mod flash;
+pub(crate) mod store;
+
+pub use flash::*;
+
+use crate::{schedule::current, sync::Mutex};
+use alloc::{boxed::Box, string::String, vec::Vec};
+use store::Store;
+
+/// Enumeration of errors of the filesystem API.
+#[derive(Debug, PartialEq)]
+pub enum FsError {
+    /// The filesystem is not mounted.
+    NotMounted,
+    /// The filesystem is already mounted.
+    AlreadyMounted,
+    /// The backend does not hold a formatted filesystem.
+    NotFormatted,
+    /// The backend has fewer than two blocks or too small blocks.
+    InvalidGeometry,
+    /// The file does not exist.
+    NotFound,
+    /// The file does not fit in an erase block.
+    TooLarge,
+    /// The backend has no more free space.
+    NoSpace,
+    /// The operation is not allowed on a file opened in the current mode.
+    BadMode,
+    /// The filesystem must not be used in ISR context.
+    InIsr,
+    /// The backend reported an error.
+    Flash(FlashError),
+}
+
+impl From<FlashError> for FsError {
+    fn from(err: FlashError) -> Self {
+        Self::Flash(err)
+    }
+}
+
+static FS: Mutex<Option<Store>> = Mutex::new(None);
+
+/// Run the closure with the mounted filesystem locked.
+fn with_fs<F, R>(op: F) -> Result<R, FsError>
+where
+    F: FnOnce(&mut Store) -> Result<R, FsError>,
+{
+    if current::is_in_isr_context() {
+        return Err(FsError::InIsr);
+    }
+    match FS.lock().as_mut() {
+        Some(store) => op(store),
+        None => Err(FsError::NotMounted),
+    }
+}
+
+/// Erase the backend and create an empty filesystem on it.
+pub fn format(backend: &mut dyn FlashBackend) -> Result<(), FsError> {
+    if current::is_in_isr_context() {
+        return Err(FsError::InIsr);
+    }
+    Store::format(backend)
+}
+
+/// Mount the filesystem on the backend. Return [`FsError::NotFormatted`] if
+/// the backend has never been formatted.
+pub fn mount<B>(backend: B) -> Result<(), FsError>
+where
+    B: FlashBackend + 'static,
+{
+    if current::is_in_isr_context() {
+        return Err(FsError::InIsr);
+    }
+    let mut fs = FS.lock();
+    if fs.is_some() {
+        return Err(FsError::AlreadyMounted);
+    }
+    *fs = Some(Store::mount(Box::new(backend))?);
+    Ok(())
+}
+
+/// Unmount the filesystem. Open [`File`]s become unusable.
+pub fn unmount() -> Result<(), FsError> {
+    if current::is_in_isr_context() {
+        return Err(FsError::InIsr);
+    }
+    FS.lock().take().map(drop).ok_or(FsError::NotMounted)
+}
+
+/// Read the beginning of the file into `buf`. Return the number of bytes
+/// read.
+pub fn read(path: &str, buf: &mut [u8]) -> Result<usize, FsError> {
+    with_fs(|store| store.read(path.as_bytes(), 0, buf))
+}
+
+/// Replace the content of the file, creating it if it does not exist.
+pub fn write(path: &str, data: &[u8]) -> Result<(), FsError> {
+    with_fs(|store| store.write(path.as_bytes(), data))
+}
+
+/// Remove the file. Removing a file that does not exist is not an error.
+pub fn remove(path: &str) -> Result<(), FsError> {
+    with_fs(|store| store.remove(path.as_bytes()))
+}
+
+/// Get the length of the file in bytes.
+pub fn file_len(path: &str) -> Result<usize, FsError> {
+    with_fs(|store| store.len(path.as_bytes()).ok_or(FsError::NotFound))
+}
+
+/// Return if the file exists.
+pub fn exists(path: &str) -> Result<bool, FsError> {
+    with_fs(|store| Ok(store.len(path.as_bytes()).is_some()))
+}
+
+/// Call the closure with the path and the length of every file. The closure
+/// runs with the filesystem locked, so it must not access the filesystem.
+pub fn for_each_file<F>(mut op: F) -> Result<(), FsError>
+where
+    F: FnMut(&str, usize),
+{
+    with_fs(|store| {
+        store.for_each(|name, len| {
+            if let Ok(path) = core::str::from_utf8(name) {
+                op(path, len);
+            }
+        });
+        Ok(())
+    })
+}
+
+/// The largest file size in bytes allowed for the path.
+pub fn max_file_len(path: &str) -> Result<usize, FsError> {
+    with_fs(|store| Ok(store.max_data_len(path.len())))
+}
+
+/// A handle to a file that can be sent to and used by other tasks.
+///
+/// A file opened with [`File::open`] reads directly from flash. If another
+/// handle rewrites the file in between two reads, the second read sees the
+/// new content. A file opened with [`File::create`] or [`File::append`]
+/// buffers the content in RAM and writes it to flash as a whole when
+/// [`flush`](File::flush) is called or the handle is dropped.
+pub struct File {
+    path: String,
+    pos: usize,
+    /// The content to be written, or `None` if the file is read-only.
+    buffer: Option<Vec<u8>>,
+    dirty: bool,
+}
+
+impl File {
+    /// Open an existing file for reading.
+    pub fn open(path: &str) -> Result<Self, FsError> {
+        with_fs(|store| store.len(path.as_bytes()).ok_or(FsError::NotFound))?;
+        Ok(Self {
+            path: String::from(path),
+            pos: 0,
+            buffer: None,
+            dirty: false,
+        })
+    }
+
+    /// Open a file for writing, discarding any existing content. The file is
+    /// created when the handle is first flushed.
+    pub fn create(path: &str) -> Result<Self, FsError> {
+        with_fs(|_| Ok(()))?;
+        Ok(Self {
+            path: String::from(path),
+            pos: 0,
+            buffer: Some(Vec::new()),
+            dirty: true,
+        })
+    }
+
+    /// Open a file for writing at its end, creating it if it does not exist.
+    pub fn append(path: &str) -> Result<Self, FsError> {
+        let content = with_fs(|store| {
+            let len = store.len(path.as_bytes()).unwrap_or(0);
+            let mut content = alloc::vec![0; len];
+            if len > 0 {
+                store.read(path.as_bytes(), 0, &mut content)?;
+            }
+            Ok(content)
+        })?;
+        Ok(Self {
+            path: String::from(path),
+            pos: content.len(),
+            buffer: Some(content),
+            dirty: false,
+        })
+    }
+
+    /// The path of the file.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The current length of the file, including unflushed writes.
+    pub fn len(&self) -> Result<usize, FsError> {
+        match &self.buffer {
+            Some(buffer) => Ok(buffer.len()),
+            None => file_len(&self.path),
+        }
+    }
+
+    /// Return if the file is empty, including unflushed writes.
+    pub fn is_empty(&self) -> Result<bool, FsError> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Move the position for the next read or write. The position is clamped
+    /// to the length of the file.
+    pub fn seek(&mut self, pos: usize) -> Result<(), FsError> {
+        self.pos = pos.min(self.len()?);
+        Ok(())
+    }
+
+    /// Read from the current position into `buf`. Return the number of bytes
+    /// read, which is zero at the end of the file.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, FsError> {
+        let len = match &self.buffer {
+            Some(buffer) => {
+                let src = &buffer[self.pos.min(buffer.len())..];
+                let len = src.len().min(buf.len());
+                buf[..len].copy_from_slice(&src[..len]);
+                len
+            }
+            None => with_fs(|store| store.read(self.path.as_bytes(), self.pos, buf))?,
+        };
+        self.pos += len;
+        Ok(len)
+    }
+
+    /// Write `data` at the current position, overwriting or extending the
+    /// content. The data is only buffered until the file is flushed.
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, FsError> {
+        let max_len = max_file_len(&self.path)?;
+        let buffer = self.buffer.as_mut().ok_or(FsError::BadMode)?;
+        let end = self.pos + data.len();
+        if end > max_len {
+            return Err(FsError::TooLarge);
+        }
+        if end > buffer.len() {
+            buffer.resize(end, 0);
+        }
+        buffer[self.pos..end].copy_from_slice(data);
+        self.pos = end;
+        self.dirty = true;
+        Ok(data.len())
+    }
+
+    /// Write the buffered content to flash, replacing the previous content.
+    pub fn flush(&mut self) -> Result<(), FsError> {
+        let Some(buffer) = &self.buffer else {
+            return Ok(());
+        };
+        if self.dirty {
+            write(&self.path, buffer)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
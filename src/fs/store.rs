@@ -0,0 +1,493 @@
+//! A log-structured store of named records on a [`FlashBackend`].
+//!
+//! Each erase block starts with a header holding a magic number and a
+//! sequence number telling the order in which blocks were put into use.
+//! Records are appended to the block with the highest sequence number, the
+//! head block. A record consists of a header, the name, the data, padding to
+//! a multiple of 4 bytes, and a trailing commit mark written last. A record
+//! without the commit mark or with a mismatching CRC was interrupted by a
+//! power loss and is ignored together with everything after it in the block.
+//! Replacing a record appends a new version, and removing a record appends a
+//! tombstone. The latest version in log order wins.
+//!
+//! One erased block is always kept in reserve. When the head block is full
+//! and no other erased block is available, the oldest block is compacted: the
+//! latest versions of its records are copied to the head and the block is
+//! erased. Because blocks are put into use in round-robin order, erasures are
+//! spread over all blocks.
+
+use super::{FlashBackend, FsError};
+use crate::crc::crc32_update;
+use alloc::{boxed::Box, vec, vec::Vec};
+
+/// Marks an initialized block.
+const BLOCK_MAGIC: u32 = 0x4846_5331;
+/// The magic number and the sequence number.
+const BLOCK_HEADER_SIZE: usize = 8;
+/// The name length, the flags, the data length, and the CRC.
+const RECORD_HEADER_SIZE: usize = 12;
+/// Written after the rest of the record to mark it as complete.
+const COMMIT_MARK: u32 = 0x600D_F00D;
+const COMMIT_SIZE: usize = 4;
+/// The record is a tombstone of a removed record.
+const FLAG_TOMBSTONE: u16 = 1;
+/// The size of the buffer used to move data between RAM and flash.
+const CHUNK_SIZE: usize = 64;
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn record_size(name_len: usize, data_len: usize) -> usize {
+    RECORD_HEADER_SIZE + align4(name_len + data_len) + COMMIT_SIZE
+}
+
+/// The fields of a record header.
+struct RecordHeader {
+    name_len: usize,
+    flags: u16,
+    data_len: usize,
+    crc: u32,
+}
+
+impl RecordHeader {
+    fn to_bytes(&self) -> [u8; RECORD_HEADER_SIZE] {
+        let mut bytes = [0; RECORD_HEADER_SIZE];
+        bytes[0..2].copy_from_slice(&(self.name_len as u16).to_le_bytes());
+        bytes[2..4].copy_from_slice(&self.flags.to_le_bytes());
+        bytes[4..8].copy_from_slice(&(self.data_len as u32).to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.crc.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; RECORD_HEADER_SIZE]) -> Self {
+        Self {
+            name_len: u16::from_le_bytes([bytes[0], bytes[1]]) as usize,
+            flags: u16::from_le_bytes([bytes[2], bytes[3]]),
+            data_len: u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize,
+            crc: u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+        }
+    }
+
+    /// The CRC of the header fields other than the CRC itself. The name and
+    /// the data are fed in afterwards.
+    fn crc_init(name_len: usize, flags: u16, data_len: usize) -> u32 {
+        let mut crc = !0;
+        crc = crc32_update(crc, &(name_len as u16).to_le_bytes());
+        crc = crc32_update(crc, &flags.to_le_bytes());
+        crc32_update(crc, &(data_len as u32).to_le_bytes())
+    }
+}
+
+/// Where the data of a record being written comes from.
+#[derive(Clone, Copy)]
+enum DataSource<'a> {
+    Ram(&'a [u8]),
+    /// The data of an existing record at the given offset in flash.
+    Flash(usize),
+}
+
+/// The location of the latest version of a record.
+struct Located {
+    name: Box<[u8]>,
+    block: usize,
+    /// The offset of the record from the start of the region.
+    offset: usize,
+    data_len: usize,
+}
+
+impl Located {
+    fn data_offset(&self) -> usize {
+        self.offset + RECORD_HEADER_SIZE + self.name.len()
+    }
+}
+
+/// A mounted store.
+pub(crate) struct Store {
+    backend: Box<dyn FlashBackend>,
+    block_size: usize,
+    block_count: usize,
+    /// The sequence number of each block, or `None` if the block is free.
+    seqs: Vec<Option<u32>>,
+    head: usize,
+    /// The offset of the next record in the head block, relative to the
+    /// start of the block.
+    head_offset: usize,
+    index: Vec<Located>,
+}
+
+impl Store {
+    /// Erase the whole region and initialize the first block.
+    pub(crate) fn format(backend: &mut dyn FlashBackend) -> Result<(), FsError> {
+        check_geometry(backend)?;
+        for block in 0..backend.block_count() {
+            backend.erase(block)?;
+        }
+        write_block_header(backend, 0, 0)
+    }
+
+    /// Mount the store on a formatted region and rebuild the index.
+    pub(crate) fn mount(mut backend: Box<dyn FlashBackend>) -> Result<Self, FsError> {
+        check_geometry(&*backend)?;
+        let block_size = backend.block_size();
+        let block_count = backend.block_count();
+
+        let mut seqs = vec![None; block_count];
+        for (block, seq) in seqs.iter_mut().enumerate() {
+            let mut header = [0; BLOCK_HEADER_SIZE];
+            backend.read(block * block_size, &mut header)?;
+            if u32::from_le_bytes([header[0], header[1], header[2], header[3]]) == BLOCK_MAGIC {
+                *seq = Some(u32::from_le_bytes([
+                    header[4], header[5], header[6], header[7],
+                ]));
+            }
+        }
+
+        let mut order: Vec<usize> = (0..block_count).filter(|b| seqs[*b].is_some()).collect();
+        order.sort_by_key(|b| seqs[*b]);
+        let Some(&head) = order.last() else {
+            return Err(FsError::NotFormatted);
+        };
+
+        let mut store = Self {
+            backend,
+            block_size,
+            block_count,
+            seqs,
+            head,
+            head_offset: block_size,
+            index: Vec::new(),
+        };
+        for block in order {
+            let end = store.replay_block(block)?;
+            if block == head {
+                store.head_offset = end;
+            }
+        }
+        Ok(store)
+    }
+
+    /// Apply the records in the block to the index. Return the offset in the
+    /// block where the next record can be appended, which is the block size
+    /// if the block cannot take more records.
+    fn replay_block(&mut self, block: usize) -> Result<usize, FsError> {
+        let base = block * self.block_size;
+        let mut offset = BLOCK_HEADER_SIZE;
+
+        while offset + RECORD_HEADER_SIZE + COMMIT_SIZE <= self.block_size {
+            let mut bytes = [0; RECORD_HEADER_SIZE];
+            self.backend.read(base + offset, &mut bytes)?;
+            if bytes.iter().all(|byte| *byte == 0xFF) {
+                // The rest of the block is erased.
+                return Ok(offset);
+            }
+
+            let header = RecordHeader::from_bytes(&bytes);
+            if header.name_len > self.block_size || header.data_len > self.block_size {
+                // An interrupted write. Nothing can be appended after it.
+                return Ok(self.block_size);
+            }
+            let size = record_size(header.name_len, header.data_len);
+            if offset + size > self.block_size
+                || self.read_u32(base + offset + size - COMMIT_SIZE)? != COMMIT_MARK
+                || self.record_crc(base + offset, &header)? != header.crc
+            {
+                // An interrupted write. Nothing can be appended after it.
+                return Ok(self.block_size);
+            }
+
+            let mut name = vec![0; header.name_len].into_boxed_slice();
+            self.backend
+                .read(base + offset + RECORD_HEADER_SIZE, &mut name)?;
+            self.index.retain(|loc| loc.name != name);
+            if header.flags & FLAG_TOMBSTONE == 0 {
+                self.index.push(Located {
+                    name,
+                    block,
+                    offset: base + offset,
+                    data_len: header.data_len,
+                });
+            }
+
+            offset += size;
+        }
+
+        Ok(self.block_size)
+    }
+
+    fn read_u32(&mut self, offset: usize) -> Result<u32, FsError> {
+        let mut bytes = [0; 4];
+        self.backend.read(offset, &mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Compute the CRC of the record at the offset from its content in flash.
+    fn record_crc(&mut self, offset: usize, header: &RecordHeader) -> Result<u32, FsError> {
+        let mut crc = RecordHeader::crc_init(header.name_len, header.flags, header.data_len);
+        let mut chunk = [0; CHUNK_SIZE];
+        let mut pos = offset + RECORD_HEADER_SIZE;
+        let mut remaining = header.name_len + header.data_len;
+        while remaining > 0 {
+            let len = remaining.min(CHUNK_SIZE);
+            self.backend.read(pos, &mut chunk[..len])?;
+            crc = crc32_update(crc, &chunk[..len]);
+            pos += len;
+            remaining -= len;
+        }
+        Ok(crc)
+    }
+
+    fn find(&self, name: &[u8]) -> Option<&Located> {
+        self.index.iter().find(|loc| &*loc.name == name)
+    }
+
+    /// The length of the record's data, or `None` if the record does not
+    /// exist.
+    pub(crate) fn len(&self, name: &[u8]) -> Option<usize> {
+        self.find(name).map(|loc| loc.data_len)
+    }
+
+    /// Read the record's data starting at `pos` into `buf`. Return the number
+    /// of bytes read.
+    pub(crate) fn read(
+        &mut self,
+        name: &[u8],
+        pos: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, FsError> {
+        let loc = self.find(name).ok_or(FsError::NotFound)?;
+        let len = loc.data_len.saturating_sub(pos).min(buf.len());
+        let offset = loc.data_offset() + pos;
+        self.backend.read(offset, &mut buf[..len])?;
+        Ok(len)
+    }
+
+    /// Call the closure with the name and data length of every record.
+    pub(crate) fn for_each<F>(&self, mut op: F)
+    where
+        F: FnMut(&[u8], usize),
+    {
+        for loc in self.index.iter() {
+            op(&loc.name, loc.data_len);
+        }
+    }
+
+    /// The largest data length of a record with the given name length.
+    pub(crate) fn max_data_len(&self, name_len: usize) -> usize {
+        (self.block_size - BLOCK_HEADER_SIZE - RECORD_HEADER_SIZE - COMMIT_SIZE)
+            .saturating_sub(name_len)
+            & !3
+    }
+
+    /// Write a new version of the record.
+    pub(crate) fn write(&mut self, name: &[u8], data: &[u8]) -> Result<(), FsError> {
+        if data.len() > self.max_data_len(name.len()) {
+            return Err(FsError::TooLarge);
+        }
+        let mut crc = RecordHeader::crc_init(name.len(), 0, data.len());
+        crc = crc32_update(crc, name);
+        crc = crc32_update(crc, data);
+        let header = RecordHeader {
+            name_len: name.len(),
+            flags: 0,
+            data_len: data.len(),
+            crc,
+        };
+        self.append(name, &header, DataSource::Ram(data))
+    }
+
+    /// Remove the record. Removing a record that does not exist is not an
+    /// error.
+    pub(crate) fn remove(&mut self, name: &[u8]) -> Result<(), FsError> {
+        if self.find(name).is_none() {
+            return Ok(());
+        }
+        let crc = crc32_update(RecordHeader::crc_init(name.len(), FLAG_TOMBSTONE, 0), name);
+        let header = RecordHeader {
+            name_len: name.len(),
+            flags: FLAG_TOMBSTONE,
+            data_len: 0,
+            crc,
+        };
+        self.append(name, &header, DataSource::Ram(&[]))
+    }
+
+    /// Append a record to the head block and update the index.
+    fn append(
+        &mut self,
+        name: &[u8],
+        header: &RecordHeader,
+        data: DataSource,
+    ) -> Result<(), FsError> {
+        let size = record_size(header.name_len, header.data_len);
+        self.ensure_space(size)?;
+        self.append_in_head(name, header, data)
+    }
+
+    /// Append a record to the head block which must have enough space.
+    fn append_in_head(
+        &mut self,
+        name: &[u8],
+        header: &RecordHeader,
+        data: DataSource,
+    ) -> Result<(), FsError> {
+        let size = record_size(header.name_len, header.data_len);
+        let offset = self.head * self.block_size + self.head_offset;
+        // Advance first, so that a failed write never gets overwritten.
+        self.head_offset += size;
+
+        self.backend.write(offset, &header.to_bytes())?;
+        self.write_body(offset + RECORD_HEADER_SIZE, name, data, header.data_len)?;
+        self.backend
+            .write(offset + size - COMMIT_SIZE, &COMMIT_MARK.to_le_bytes())?;
+
+        self.index.retain(|loc| &*loc.name != name);
+        if header.flags & FLAG_TOMBSTONE == 0 {
+            self.index.push(Located {
+                name: name.into(),
+                block: self.head,
+                offset,
+                data_len: header.data_len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Write the name followed by the data and the padding in chunks.
+    fn write_body(
+        &mut self,
+        mut dst: usize,
+        name: &[u8],
+        data: DataSource,
+        data_len: usize,
+    ) -> Result<(), FsError> {
+        let total = name.len() + data_len;
+        let padded = align4(total);
+        let mut chunk = [0xFF; CHUNK_SIZE];
+        let mut pos = 0;
+
+        while pos < padded {
+            let len = (padded - pos).min(CHUNK_SIZE);
+            chunk.fill(0xFF);
+
+            // The part of the chunk covered by the name.
+            if pos < name.len() {
+                let end = name.len().min(pos + len);
+                chunk[..end - pos].copy_from_slice(&name[pos..end]);
+            }
+
+            // The part of the chunk covered by the data.
+            let data_start = pos.max(name.len());
+            let data_end = (pos + len).min(total);
+            if data_start < data_end {
+                let dst_chunk = &mut chunk[data_start - pos..data_end - pos];
+                let src_pos = data_start - name.len();
+                match data {
+                    DataSource::Ram(data) => {
+                        dst_chunk.copy_from_slice(&data[src_pos..src_pos + dst_chunk.len()])
+                    }
+                    DataSource::Flash(src) => self.backend.read(src + src_pos, dst_chunk)?,
+                }
+            }
+
+            self.backend.write(dst, &chunk[..len])?;
+            dst += len;
+            pos += len;
+        }
+        Ok(())
+    }
+
+    fn free_blocks(&self) -> usize {
+        self.seqs.iter().filter(|seq| seq.is_none()).count()
+    }
+
+    /// Make the head block have at least `size` bytes of space, compacting
+    /// blocks if necessary.
+    fn ensure_space(&mut self, size: usize) -> Result<(), FsError> {
+        for _ in 0..=self.block_count {
+            if self.head_offset + size <= self.block_size {
+                return Ok(());
+            }
+            // Keep one free block in reserve for compaction.
+            if self.free_blocks() >= 2 {
+                self.claim_head()?;
+            } else {
+                self.compact_oldest()?;
+            }
+        }
+        Err(FsError::NoSpace)
+    }
+
+    /// Put the next free block in round-robin order into use as the head.
+    fn claim_head(&mut self) -> Result<(), FsError> {
+        let block = (1..=self.block_count)
+            .map(|step| (self.head + step) % self.block_count)
+            .find(|block| self.seqs[*block].is_none())
+            .ok_or(FsError::NoSpace)?;
+        let seq = self.seqs.iter().flatten().max().map_or(0, |seq| seq + 1);
+
+        self.backend.erase(block)?;
+        write_block_header(&mut *self.backend, block, seq)?;
+        self.seqs[block] = Some(seq);
+        self.head = block;
+        self.head_offset = BLOCK_HEADER_SIZE;
+        Ok(())
+    }
+
+    /// Copy the live records of the oldest block to the head and erase it.
+    fn compact_oldest(&mut self) -> Result<(), FsError> {
+        let oldest = (0..self.block_count)
+            .filter(|block| self.seqs[*block].is_some())
+            .min_by_key(|block| self.seqs[*block])
+            .ok_or(FsError::NoSpace)?;
+
+        if oldest == self.head {
+            self.claim_head()?;
+        }
+
+        let live: Vec<(Box<[u8]>, usize)> = self
+            .index
+            .iter()
+            .filter(|loc| loc.block == oldest)
+            .map(|loc| (loc.name.clone(), loc.offset))
+            .collect();
+
+        for (name, offset) in live {
+            let mut bytes = [0; RECORD_HEADER_SIZE];
+            self.backend.read(offset, &mut bytes)?;
+            let header = RecordHeader::from_bytes(&bytes);
+            let size = record_size(header.name_len, header.data_len);
+            if self.head_offset + size > self.block_size {
+                self.claim_head()?;
+            }
+            let data = DataSource::Flash(offset + RECORD_HEADER_SIZE + header.name_len);
+            self.append_in_head(&name, &header, data)?;
+        }
+
+        self.backend.erase(oldest)?;
+        self.seqs[oldest] = None;
+        Ok(())
+    }
+}
+
+/// The store needs at least two blocks, one of which is kept in reserve, and
+/// blocks large enough to hold a record.
+fn check_geometry(backend: &dyn FlashBackend) -> Result<(), FsError> {
+    let block_size = backend.block_size();
+    if backend.block_count() < 2 || block_size < 64 || block_size % 4 != 0 {
+        return Err(FsError::InvalidGeometry);
+    }
+    Ok(())
+}
+
+fn write_block_header(
+    backend: &mut dyn FlashBackend,
+    block: usize,
+    seq: u32,
+) -> Result<(), FsError> {
+    let mut header = [0; BLOCK_HEADER_SIZE];
+    header[0..4].copy_from_slice(&BLOCK_MAGIC.to_le_bytes());
+    header[4..8].copy_from_slice(&seq.to_le_bytes());
+    backend.write(block * backend.block_size(), &header)?;
+    Ok(())
+}
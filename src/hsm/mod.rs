@@ -0,0 +1,485 @@
+//! Hierarchical state machines running in their own tasks.
+//!
+//! A state machine is described by implementing [`StateMachine`]. States may
+//! be nested by naming a parent with [`StateMachine::parent`]. An event is
+//! first offered to the current leaf state and travels up to its ancestors
+//! until a state handles it. A transition exits states from the current leaf
+//! up to the closest common ancestor with the target, then enters states
+//! down to the target and further into the initial children of the target.
+//! A transition to the current state or one of its ancestors exits and
+//! re-enters that state.
+//!
+//! A state may start a timer when it is entered or while handling an event,
+//! which delivers a timed event unless the state is exited first. This makes
+//! timed transitions, e.g., a timeout in a connecting state, a one-liner.
+//!
+//! Events are delivered through a channel and can be posted by tasks and
+//! ISRs through an [`EventSender`]. The machine runs in a restartable task.
+//! If an action or a handler panics, the task is restarted with fresh data
+//! created by the factory and enters [`StateMachine::safe_state`] instead of
+//! the initial state. The event being handled is lost, but the events still
+//! queued are handled after the restart. Restarting requires the `unwind`
+//! feature.
+//!
+//! # Example
+//! ```rust
+//! #[derive(Clone, Copy, PartialEq, Debug)]
+//! enum State { Idle, Active, Connecting, Connected }
+//!
+//! enum Event { Connect, Connected, Timeout, Stop }
+//!
+//! struct Link;
+//!
+//! impl StateMachine for Link {
+//!     type State = State;
+//!     type Event = Event;
+//!
+//!     fn initial_state(&self) -> State { State::Idle }
+//!
+//!     fn parent(state: State) -> Option<State> {
+//!         match state {
+//!             State::Connecting | State::Connected => Some(State::Active),
+//!             _ => None,
+//!         }
+//!     }
+//!
+//!     fn on_entry(&mut self, state: State, ctx: &mut Context<State, Event>) {
+//!         if state == State::Connecting {
+//!             ctx.start_timer(500, Event::Timeout);
+//!         }
+//!     }
+//!
+//!     fn handle(&mut self, state: State, event: &Event, _: &mut Context<State, Event>) -> Response<State> {
+//!         match (state, event) {
+//!             (State::Idle, Event::Connect) => Response::Transition(State::Connecting),
+//!             (State::Connecting, Event::Connected) => Response::Transition(State::Connected),
+//!             (State::Connecting, Event::Timeout) => Response::Transition(State::Idle),
+//!             (State::Active, Event::Stop) => Response::Transition(State::Idle),
+//!             _ => Response::Super,
+//!         }
+//!     }
+//! }
+//!
+//! let link = hsm::build().spawn::<_, _, 8>(|| Link).unwrap();
+//! link.post(Event::Connect);
+//! ```
+
+use crate::{
+    config,
+    sync::{create_channel, Consumer, Mailbox, Producer, SpinSchedSafe},
+    task::{self, TaskBuildError},
+    time,
+};
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    cmp::Ordering as CmpOrdering,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+/// The description of a state machine and the data it owns.
+pub trait StateMachine: Send + 'static {
+    /// The type naming the states.
+    type State: Copy + PartialEq + Send + 'static;
+    /// The type of events handled by the machine.
+    type Event: Send + 'static;
+
+    /// The state entered when the machine first starts.
+    fn initial_state(&self) -> Self::State;
+
+    /// The state entered when the machine restarts after a panic. Defaults
+    /// to the initial state.
+    fn safe_state(&self) -> Self::State {
+        self.initial_state()
+    }
+
+    /// The parent of the state, or `None` for a top-level state.
+    fn parent(_state: Self::State) -> Option<Self::State> {
+        None
+    }
+
+    /// The child state entered right after the state is entered as the
+    /// target of a transition, or `None` if the state has no children.
+    fn initial_child(_state: Self::State) -> Option<Self::State> {
+        None
+    }
+
+    /// The entry action of the state.
+    fn on_entry(&mut self, _state: Self::State, _ctx: &mut Context<Self::State, Self::Event>) {}
+
+    /// The exit action of the state.
+    fn on_exit(&mut self, _state: Self::State) {}
+
+    /// Handle the event in the given state, which is the current leaf state
+    /// or one of its ancestors.
+    fn handle(
+        &mut self,
+        state: Self::State,
+        event: &Self::Event,
+        ctx: &mut Context<Self::State, Self::Event>,
+    ) -> Response<Self::State>;
+}
+
+/// The response of a state to an event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Response<S> {
+    /// The event is handled without changing the state.
+    Handled,
+    /// The event is handled by transitioning to the given state.
+    Transition(S),
+    /// The event is not handled and is offered to the parent state. An
+    /// event not handled by any state is dropped.
+    Super,
+}
+
+/// A pending timed event.
+struct Timer<S, E> {
+    /// The state that started the timer. The timer is cancelled when the
+    /// state is exited.
+    owner: S,
+    expire_at_tick: u32,
+    event: E,
+}
+
+/// The means for actions and handlers to start and cancel the timer of the
+/// machine.
+pub struct Context<S, E> {
+    /// The state whose action or handler is running.
+    state: S,
+    timer: Option<Timer<S, E>>,
+}
+
+impl<S, E> Context<S, E>
+where
+    S: Copy + PartialEq,
+{
+    /// Deliver the event after the given number of milliseconds, unless the
+    /// state whose action or handler calls this method is exited first. The
+    /// machine has a single timer, so a timer started earlier is cancelled.
+    pub fn start_timer(&mut self, ms: u32, event: E) {
+        self.timer = Some(Timer {
+            owner: self.state,
+            expire_at_tick: time::get_tick().wrapping_add(ms),
+            event,
+        });
+    }
+
+    /// Cancel the timer if it is running.
+    pub fn cancel_timer(&mut self) {
+        self.timer = None;
+    }
+
+    /// Cancel the timer if it was started by the state.
+    fn exit(&mut self, state: S) {
+        if matches!(&self.timer, Some(timer) if timer.owner == state) {
+            self.timer = None;
+        }
+    }
+
+    /// Return the number of milliseconds until the timer expires, or `None`
+    /// if no timer is running.
+    fn remaining_ms(&self) -> Option<u32> {
+        let timer = self.timer.as_ref()?;
+        let now = time::get_tick();
+        match time::tick_cmp(timer.expire_at_tick, now) {
+            CmpOrdering::Greater => Some(timer.expire_at_tick.wrapping_sub(now)),
+            _ => Some(0),
+        }
+    }
+}
+
+/// A running machine as seen by its task.
+struct Runner<M>
+where
+    M: StateMachine,
+{
+    machine: M,
+    leaf: M::State,
+    ctx: Context<M::State, M::Event>,
+}
+
+impl<M> Runner<M>
+where
+    M: StateMachine,
+{
+    /// Start the machine by entering the state and its ancestors.
+    fn start(machine: M, state: M::State) -> Self {
+        let mut runner = Self {
+            machine,
+            leaf: state,
+            ctx: Context { state, timer: None },
+        };
+        let mut path = path_to_root::<M>(state);
+        path.reverse();
+        runner.enter(&path);
+        runner
+    }
+
+    /// Run the entry actions of the states in order, then descend into the
+    /// initial children of the last one.
+    fn enter(&mut self, states: &[M::State]) {
+        for &state in states {
+            self.enter_one(state);
+        }
+        while let Some(child) = M::initial_child(self.leaf) {
+            self.enter_one(child);
+        }
+    }
+
+    fn enter_one(&mut self, state: M::State) {
+        self.leaf = state;
+        self.ctx.state = state;
+        self.machine.on_entry(state, &mut self.ctx);
+    }
+
+    fn dispatch(&mut self, event: &M::Event) {
+        let mut state = Some(self.leaf);
+        while let Some(cur) = state {
+            self.ctx.state = cur;
+            match self.machine.handle(cur, event, &mut self.ctx) {
+                Response::Handled => return,
+                Response::Transition(target) => {
+                    self.transition(target);
+                    return;
+                }
+                Response::Super => state = M::parent(cur),
+            }
+        }
+    }
+
+    fn transition(&mut self, target: M::State) {
+        let leaf_path = path_to_root::<M>(self.leaf);
+        let target_path = path_to_root::<M>(target);
+
+        // The closest common ancestor is not exited. A transition to an
+        // ancestor of the leaf exits and re-enters the target.
+        let mut common = leaf_path
+            .iter()
+            .copied()
+            .find(|state| target_path.contains(state));
+        if common == Some(target) {
+            common = M::parent(target);
+        }
+
+        for &state in leaf_path.iter().take_while(|&&state| Some(state) != common) {
+            self.machine.on_exit(state);
+            self.ctx.exit(state);
+        }
+
+        let mut entries: Vec<M::State> = target_path
+            .iter()
+            .copied()
+            .take_while(|&state| Some(state) != common)
+            .collect();
+        entries.reverse();
+        self.enter(&entries);
+    }
+
+    /// Deliver the timed event if the timer has expired.
+    fn fire_timer(&mut self) {
+        if self.ctx.remaining_ms() != Some(0) {
+            return;
+        }
+        if let Some(timer) = self.ctx.timer.take() {
+            self.dispatch(&timer.event);
+        }
+    }
+}
+
+/// Return the state followed by its ancestors.
+fn path_to_root<M>(state: M::State) -> Vec<M::State>
+where
+    M: StateMachine,
+{
+    let mut path = Vec::new();
+    let mut cur = Some(state);
+    while let Some(state) = cur {
+        path.push(state);
+        cur = M::parent(state);
+    }
+    path
+}
+
+/// The channel carrying events to the machine task, with the capacity
+/// erased from the type.
+trait Queue<E>: Send + Sync {
+    fn post(&self, event: E);
+    fn try_post_allow_isr(&self, event: E) -> Result<(), E>;
+}
+
+impl<E, const N: usize> Queue<E> for Producer<E, N>
+where
+    E: Send,
+{
+    fn post(&self, event: E) {
+        self.produce(event)
+    }
+
+    fn try_post_allow_isr(&self, event: E) -> Result<(), E> {
+        self.try_produce_allow_isr(event)
+    }
+}
+
+/// The state shared by the machine task and the senders.
+struct Shared<S> {
+    /// Notified for every posted event.
+    wakeup: Mailbox,
+    /// The current leaf state, or `None` before the machine starts.
+    state: SpinSchedSafe<Option<S>>,
+    restarts: AtomicU32,
+}
+
+/// A handle to post events to a state machine.
+pub struct EventSender<S, E> {
+    queue: Arc<dyn Queue<E>>,
+    shared: Arc<Shared<S>>,
+}
+
+impl<S, E> Clone for EventSender<S, E> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<S, E> EventSender<S, E>
+where
+    S: Copy + Send,
+    E: Send + 'static,
+{
+    /// Post an event to the machine. If the queue is full, block until there
+    /// is room.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn post(&self, event: E) {
+        self.queue.post(event);
+        self.shared.wakeup.notify_allow_isr();
+    }
+
+    /// Post an event to the machine without blocking. If the queue is full,
+    /// return the event with `Err`.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn try_post_allow_isr(&self, event: E) -> Result<(), E> {
+        self.queue.try_post_allow_isr(event)?;
+        self.shared.wakeup.notify_allow_isr();
+        Ok(())
+    }
+
+    /// The current leaf state, or `None` if the machine has not started yet.
+    pub fn state(&self) -> Option<S> {
+        *self.shared.state.lock()
+    }
+
+    /// The number of times the machine has been restarted after a panic.
+    pub fn restart_count(&self) -> u32 {
+        self.shared.restarts.load(Ordering::SeqCst)
+    }
+}
+
+/// Build a new state machine with the state machine builder.
+pub fn build() -> StateMachineBuilder {
+    StateMachineBuilder {
+        priority: None,
+        id: None,
+    }
+}
+
+/// Supporting the builder pattern to spawn a new state machine.
+pub struct StateMachineBuilder {
+    priority: Option<u8>,
+    id: Option<u8>,
+}
+
+impl StateMachineBuilder {
+    /// Set a numerical ID for the machine task. See
+    /// [`TaskBuilder::set_id`](crate::task::TaskBuilder::set_id).
+    pub fn set_id(mut self, id: u8) -> Self {
+        self.id.replace(id);
+        self
+    }
+
+    /// Set the priority of the machine task. If not explicitly set, the task
+    /// will have the [`DEFAULT_TASK_PRIORITY`](config::DEFAULT_TASK_PRIORITY).
+    pub fn set_priority(mut self, prio: u8) -> Self {
+        self.priority.replace(prio);
+        self
+    }
+
+    /// Spawn the machine with an event queue holding up to `N` events. The
+    /// factory creates the data of the machine when it starts and every time
+    /// it restarts.
+    pub fn spawn<M, F, const N: usize>(
+        self,
+        factory: F,
+    ) -> Result<EventSender<M::State, M::Event>, TaskBuildError>
+    where
+        M: StateMachine,
+        F: Fn() -> M + Send + Sync + Clone + 'static,
+    {
+        let (producer, consumer) = create_channel::<M::Event, N>();
+        let shared = Arc::new(Shared {
+            wakeup: Mailbox::new(),
+            state: SpinSchedSafe::new(None),
+            restarts: AtomicU32::new(0),
+        });
+        let started = Arc::new(AtomicBool::new(false));
+
+        let entry = {
+            let shared = shared.clone();
+            move || run(factory(), &consumer, &shared, &started)
+        };
+
+        let builder = task::build()
+            .set_id(self.id.unwrap_or(config::DEFAULT_TASK_ID))
+            .set_priority(self.priority.unwrap_or(config::DEFAULT_TASK_PRIORITY))
+            .set_entry(entry);
+        #[cfg(feature = "unwind")]
+        builder.spawn_restartable()?;
+        #[cfg(not(feature = "unwind"))]
+        builder.spawn()?;
+
+        Ok(EventSender {
+            queue: Arc::new(producer),
+            shared,
+        })
+    }
+}
+
+/// The body of the machine task.
+fn run<M, const N: usize>(
+    machine: M,
+    consumer: &Consumer<M::Event, N>,
+    shared: &Shared<M::State>,
+    started: &AtomicBool,
+) where
+    M: StateMachine,
+{
+    let state = if started.swap(true, Ordering::SeqCst) {
+        shared.restarts.fetch_add(1, Ordering::SeqCst);
+        machine.safe_state()
+    } else {
+        machine.initial_state()
+    };
+
+    let mut runner = Runner::start(machine, state);
+    *shared.state.lock() = Some(runner.leaf);
+
+    loop {
+        match runner.ctx.remaining_ms() {
+            None => shared.wakeup.wait(),
+            Some(0) => {}
+            Some(ms) => {
+                shared.wakeup.wait_until_timeout(ms);
+            }
+        }
+
+        runner.fire_timer();
+        while let Some(event) = consumer.try_consume_allow_isr() {
+            runner.dispatch(&event);
+            *shared.state.lock() = Some(runner.leaf);
+        }
+        *shared.state.lock() = Some(runner.leaf);
+    }
+}
@@ -0,0 +1,137 @@
+//! The initialization sequence of the system.
+//!
+//! The system comes up in the [`Stage`]s below, in this order:
+//!
+//! 1. [`Stage::Boot`]: the kernel is initializing the heap, the breadcrumbs,
+//!    and the scheduler.
+//! 2. [`Stage::Kernel`]: the scheduler and SysTick are running, and the
+//!    [`#[main]`](crate::task::main) function has been called.
+//! 3. [`Stage::Transports`]: the application has started the drivers that
+//!    carry data off the device, e.g., the network stack, CAN, or a UART
+//!    console.
+//! 4. [`Stage::Session`]: the application has established the session with
+//!    the host over the transports, e.g., the one serving offloaded requests.
+//! 5. [`Stage::Tasks`]: the application tasks are running.
+//!
+//! The kernel enters the first two stages by itself. The application enters
+//! the later ones with [`advance`] once the corresponding services are up.
+//! An application without a session may go from [`Stage::Transports`]
+//! straight to [`Stage::Tasks`], but no stage can be entered twice or after a
+//! later one.
+//!
+//! A service that depends on an earlier stage checks it with [`require`],
+//! which returns [`InitError::NotReached`] when called too early, instead of
+//! finding its state missing.
+//!
+//! # Example
+//! ```rust
+//! #[main]
+//! fn main(_: cortex_m::Peripherals) {
+//!     net::start(driver, net_config).unwrap();
+//!     init::advance(Stage::Transports).unwrap();
+//!
+//!     session::connect().unwrap();
+//!     init::advance(Stage::Session).unwrap();
+//!
+//!     spawn_app_tasks();
+//!     init::advance(Stage::Tasks).unwrap();
+//! }
+//!
+//! fn send_report() -> Result<(), init::InitError> {
+//!     init::require(Stage::Session)?;
+//!     // ...
+//! }
+//! ```
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// A stage of the initialization sequence. Later stages compare greater.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Stage {
+    /// The kernel is initializing.
+    Boot = 0,
+    /// The kernel is running the main function.
+    Kernel = 1,
+    /// The transports are started.
+    Transports = 2,
+    /// The session with the host is established.
+    Session = 3,
+    /// The application tasks are running.
+    Tasks = 4,
+}
+
+impl Stage {
+    fn from_u8(val: u8) -> Self {
+        match val {
+            0 => Self::Boot,
+            1 => Self::Kernel,
+            2 => Self::Transports,
+            3 => Self::Session,
+            _ => Self::Tasks,
+        }
+    }
+}
+
+/// Enumeration of errors of the initialization API.
+#[derive(Debug, PartialEq)]
+pub enum InitError {
+    /// The required stage has not been reached yet.
+    NotReached { required: Stage, current: Stage },
+    /// The requested stage is not after the current one, or is entered by
+    /// the kernel.
+    OutOfOrder { requested: Stage, current: Stage },
+}
+
+/// The current stage.
+static STAGE: AtomicU8 = AtomicU8::new(Stage::Boot as u8);
+
+/// Return the current stage.
+///
+/// Calling this function in ISR context is allowed.
+pub fn stage() -> Stage {
+    Stage::from_u8(STAGE.load(Ordering::SeqCst))
+}
+
+/// Return `Ok` if the system has reached the stage, or
+/// [`InitError::NotReached`] otherwise.
+///
+/// Calling this function in ISR context is allowed.
+pub fn require(stage: Stage) -> Result<(), InitError> {
+    let current = self::stage();
+    if current < stage {
+        return Err(InitError::NotReached {
+            required: stage,
+            current,
+        });
+    }
+    Ok(())
+}
+
+/// Enter the application stage. The stage must be after the current one.
+/// Stages in between are skipped.
+///
+/// Calling this function in ISR context is allowed.
+pub fn advance(stage: Stage) -> Result<(), InitError> {
+    if stage <= Stage::Kernel {
+        return Err(InitError::OutOfOrder {
+            requested: stage,
+            current: self::stage(),
+        });
+    }
+    STAGE
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+            (current < stage as u8).then_some(stage as u8)
+        })
+        .map(|_| ())
+        .map_err(|current| InitError::OutOfOrder {
+            requested: stage,
+            current: Stage::from_u8(current),
+        })
+}
+
+/// Enter [`Stage::Kernel`]. Called once by the kernel right before calling
+/// the main function.
+pub(crate) fn enter_kernel_stage() {
+    STAGE.store(Stage::Kernel as u8, Ordering::SeqCst);
+}
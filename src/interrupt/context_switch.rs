@@ -17,7 +17,12 @@ use core::arch::asm;
 ///
 /// Safety: This function should only be invoked directly by hardware PendSV exception.
 ///
-/// FIXME: make sure lazy stacking of floating point registers is properly done.
+/// The floating point registers are only preserved if the `fp_context` feature
+/// is enabled. Otherwise, the assembler skips the instructions wrapped between
+/// `.if` and `.endif`. If the hardware stacked `s0-s15` and `fpscr` lazily,
+/// storing `s16-s31` is the first floating point instruction of the handler,
+/// which makes the hardware write them into the trap frame on the task's
+/// stack before the handler switches to another task's stack.
 #[export_name = "PendSV"]
 #[allow(unused)]
 #[naked]
@@ -40,7 +45,9 @@ unsafe extern "C" fn pendsv_entry() {
         "stmia  r12!, {{r0-r3, r4-r11}}",
         // Preserve the floating point registers `s16-s31`.
         // Register `s0-s15` and `fpscr` are pushed by hardware onto the task's stack.
+        ".if    {fp_ctxt}",
         "vstmia r12!, {{s16-s31}}",
+        ".endif",
         // Update the stacklet boundary to the kernel's boundary and zero out
         // other fields in the TLS.
         "ldr    r0, ={kern_stk_boundary}",
@@ -68,7 +75,9 @@ unsafe extern "C" fn pendsv_entry() {
         "ldr    r3, ={tls_mem_addr}",
         "stmia  r3, {{r0-r2}}",
         // Restore the task's floating point registers s16-s31.
+        ".if    {fp_ctxt}",
         "vldmia r12!, {{s16-s31}}",
+        ".endif",
         // Sanity check that the kernel stack pointer is at the bottom.
         "mrs    r3, msp",
         "ldr    r2, ={kern_stk_bottom}",
@@ -76,9 +85,9 @@ unsafe extern "C" fn pendsv_entry() {
         // Call `unrecoverable::die` if the check fails.
         "bne    0f",
         // Perform exception return, assuming that the task has floating
-        // point context. Register r0-r3, r12, lr, s0-s15, and fpscr will
-        // be restored from the trap frame on the task's stack.
-        "ldr    lr, ={ex_ret_to_psp_with_fp}",
+        // point context if it is preserved. Register r0-r3, r12, lr, s0-s15,
+        // and fpscr will be restored from the trap frame on the task's stack.
+        "ldr    lr, ={ex_ret_to_psp}",
         "bx     lr",
         // Call `unrecoverable::die`.
         "0:",
@@ -89,16 +98,18 @@ unsafe extern "C" fn pendsv_entry() {
         kern_stk_boundary = const config::__CONTIGUOUS_STACK_BOUNDARY,
         pendsv_handler = sym pendsv_handler,
         kern_stk_bottom = const config::_CONTIGUOUS_STACK_BOTTOM,
-        ex_ret_to_psp_with_fp = const 0xffffffedu32,
+        ex_ret_to_psp = const config::__EXC_RETURN_THREAD_PSP,
+        fp_ctxt = const config::FP_CONTEXT_SAVE as u32,
         die = sym unrecoverable::die,
         options(noreturn)
     )
 }
 
 /// Make sure PendVS is invoked from thread mode, was using process stack pointer,
-/// and the floating point registers s0-s15 were pushed in the trap frame.
+/// and the floating point registers s0-s15 were pushed in the trap frame if the
+/// floating point context is preserved.
 fn die_if_unexpected_pendsv(ex_ret_lr: u32) {
-    if ex_ret_lr != 0xffffffed {
+    if ex_ret_lr != config::__EXC_RETURN_THREAD_PSP {
         unrecoverable::die();
     }
 }
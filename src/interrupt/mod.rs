@@ -8,3 +8,5 @@ pub(crate) mod trap_frame;
 
 pub mod declare;
 pub mod mask;
+#[cfg(feature = "irq_stats")]
+pub mod stats;
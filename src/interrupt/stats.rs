@@ -0,0 +1,82 @@
+//! Invocation counters of exceptions and IRQs, enabled by the `irq_stats`
+//! feature.
+//!
+//! The kernel counts its own SysTick exceptions. IRQ handlers defined by
+//! applications should call [`record_allow_isr`] to be counted.
+//!
+//! # Example
+//! ```rust
+//! #[handler(TIM2)]
+//! fn tim2_handler() {
+//!     interrupt::stats::record_allow_isr();
+//!     // ...
+//! }
+//! ```
+
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// The 16 system exceptions followed by the largest number of IRQs among the
+/// supported STM32F4 devices.
+const VECTOR_NUMBER: usize = 16 + 102;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU32 = AtomicU32::new(0);
+
+/// The invocation counter of each exception, indexed by exception number.
+static COUNTS: [AtomicU32; VECTOR_NUMBER] = [ZERO; VECTOR_NUMBER];
+
+/// Count one invocation of the currently running exception or IRQ handler.
+/// Does nothing when called outside of ISR context.
+pub fn record_allow_isr() {
+    let ipsr: u32;
+
+    unsafe {
+        asm!(
+            "mrs {}, ipsr",
+            out(reg) ipsr,
+            options(nomem, nostack)
+        );
+    }
+
+    let exception = (ipsr & 0x1FF) as usize;
+    if exception != 0 && exception < VECTOR_NUMBER {
+        COUNTS[exception].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Get the number of invocations of the IRQ. Return `None` if the IRQ number
+/// is beyond the largest one supported.
+pub fn irq_count(irq: u16) -> Option<u32> {
+    COUNTS
+        .get(16 + irq as usize)
+        .map(|count| count.load(Ordering::Relaxed))
+}
+
+/// Get the number of SysTick exceptions.
+pub fn systick_count() -> u32 {
+    COUNTS[15].load(Ordering::Relaxed)
+}
+
+/// Call the closure with the IRQ number and the invocation count of every
+/// IRQ that has been counted at least once.
+pub fn for_each_irq<F>(mut op: F)
+where
+    F: FnMut(u16, u32),
+{
+    for (irq, count) in COUNTS[16..].iter().enumerate() {
+        let count = count.load(Ordering::Relaxed);
+        if count > 0 {
+            op(irq as u16, count);
+        }
+    }
+}
+
+/// Reset all counters to zero.
+pub fn reset() {
+    for count in COUNTS.iter() {
+        count.store(0, Ordering::Relaxed);
+    }
+}
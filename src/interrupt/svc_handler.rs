@@ -78,14 +78,17 @@ unsafe extern "C" fn svc_entry() {
     asm!(
         // Make sure SVC is invoked from thread mode, was using process stack
         // pointer, and the floating point registers s0-s15 were pushed in the
-        // trap frame.
-        "cmp      lr, #0xffffffed",
+        // trap frame if the floating point context is preserved.
+        "cmp      lr, #{ex_ret_to_psp}",
         "it       ne",
         "blne     {die}",
         // Execute a floating point instruction, so that the CPU will push the
-        // floating point registers into the trap frame. See the "lazy stacking"
-        // feature of Cortex-M4 for details.
+        // floating point registers into the trap frame if it stacked them
+        // lazily. The handler may copy the trap frame to a new stacklet.
+        // See the "lazy stacking" feature of Cortex-M4 for details.
+        ".if      {fp_lazy}",
         "vmov.f32 s0, s0",
+        ".endif",
         // Read task's stack pointer into `r0`, which is pointing the trap
         // frame, and which will become the first argument to the SVC handler.
         "mrs      r0, psp",
@@ -116,6 +119,8 @@ unsafe extern "C" fn svc_entry() {
         tls_mem_addr = const config::__TLS_MEM_ADDR,
         kern_stk_boundary = const config::__CONTIGUOUS_STACK_BOUNDARY,
         svc_handler = sym svc_handler,
+        ex_ret_to_psp = const config::__EXC_RETURN_THREAD_PSP,
+        fp_lazy = const (config::FP_CONTEXT_SAVE && config::FP_LAZY_STACKING) as u32,
         die = sym unrecoverable::die,
         options(noreturn)
     )
@@ -1,4 +1,8 @@
-use crate::{config, time};
+use crate::{
+    config,
+    schedule::{load, scheduler::Scheduler},
+    task, time,
+};
 use core::arch::asm;
 
 #[naked]
@@ -29,8 +33,15 @@ unsafe extern "C" fn systick_entry() {
     )
 }
 
-/// We simply need to advance the tick count when SysTick fires.
+/// Advance the tick count, wake up the tasks whose sleep or timers expired,
+/// account the time slice of the current task, and sample the CPU load.
 unsafe extern "C" fn systick_handler() {
+    #[cfg(feature = "irq_stats")]
+    super::stats::record_allow_isr();
     time::advance_tick();
     time::wake_sleeping_tasks();
+    time::timer::fire_expired_timers();
+    task::check_deadlines_allow_isr();
+    Scheduler::tick_time_slice_allow_isr();
+    load::sample_allow_isr();
 }
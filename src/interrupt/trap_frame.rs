@@ -3,6 +3,7 @@
 #[derive(Clone, Default)]
 pub(crate) struct TrapFrame {
     pub gp_regs: TrapFrameGPRegs,
+    #[cfg(feature = "fp_context")]
     pub fp_regs: TrapFrameFPRegs,
 }
 
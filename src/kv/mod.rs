@@ -0,0 +1,133 @@
+//! A persistent key-value store in flash, enabled by the `kv` feature.
+//!
+//! The store keeps small values, e.g., calibration data and counters, that
+//! must survive a reboot. It uses the same log-structured format as the
+//! [filesystem](crate::fs), but is mounted on its own [`FlashBackend`]
+//! independently of the filesystem. Setting a key either fully replaces its
+//! value or leaves the previous value intact when power is lost in between.
+//! Erasures are spread over all blocks of the backend. All operations are
+//! atomic with respect to other tasks.
+//!
+//! # Example
+//! ```rust
+//! let flash = InternalFlash::new(8, 2).unwrap();
+//! if kv::mount(flash).is_err() {
+//!     let mut flash = InternalFlash::new(8, 2).unwrap();
+//!     kv::format(&mut flash).unwrap();
+//!     kv::mount(flash).unwrap();
+//! }
+//!
+//! kv::set("cal.offset", &(-12i32).to_le_bytes()).unwrap();
+//! let mut buf = [0; 4];
+//! kv::get("cal.offset", &mut buf).unwrap();
+//! let offset = i32::from_le_bytes(buf);
+//! ```
+
+use crate::{
+    fs::{store::Store, FlashBackend, FsError},
+    schedule::current,
+    sync::Mutex,
+};
+use alloc::boxed::Box;
+
+static KV: Mutex<Option<Store>> = Mutex::new(None);
+
+/// Run the closure with the mounted store locked.
+fn with_kv<F, R>(op: F) -> Result<R, FsError>
+where
+    F: FnOnce(&mut Store) -> Result<R, FsError>,
+{
+    if current::is_in_isr_context() {
+        return Err(FsError::InIsr);
+    }
+    match KV.lock().as_mut() {
+        Some(store) => op(store),
+        None => Err(FsError::NotMounted),
+    }
+}
+
+/// Erase the backend and create an empty store on it.
+pub fn format(backend: &mut dyn FlashBackend) -> Result<(), FsError> {
+    if current::is_in_isr_context() {
+        return Err(FsError::InIsr);
+    }
+    Store::format(backend)
+}
+
+/// Mount the store on the backend. Return [`FsError::NotFormatted`] if the
+/// backend has never been formatted.
+pub fn mount<B>(backend: B) -> Result<(), FsError>
+where
+    B: FlashBackend + 'static,
+{
+    if current::is_in_isr_context() {
+        return Err(FsError::InIsr);
+    }
+    let mut kv = KV.lock();
+    if kv.is_some() {
+        return Err(FsError::AlreadyMounted);
+    }
+    *kv = Some(Store::mount(Box::new(backend))?);
+    Ok(())
+}
+
+/// Unmount the store.
+pub fn unmount() -> Result<(), FsError> {
+    if current::is_in_isr_context() {
+        return Err(FsError::InIsr);
+    }
+    KV.lock().take().map(drop).ok_or(FsError::NotMounted)
+}
+
+/// Set the value of the key, replacing any previous value.
+pub fn set(key: &str, value: &[u8]) -> Result<(), FsError> {
+    with_kv(|store| store.write(key.as_bytes(), value))
+}
+
+/// Read the value of the key into `buf`. Return the length of the value, or
+/// [`FsError::TooLarge`] if it does not fit in `buf`.
+pub fn get(key: &str, buf: &mut [u8]) -> Result<usize, FsError> {
+    with_kv(|store| {
+        let len = store.len(key.as_bytes()).ok_or(FsError::NotFound)?;
+        if len > buf.len() {
+            return Err(FsError::TooLarge);
+        }
+        store.read(key.as_bytes(), 0, buf)
+    })
+}
+
+/// Remove the key. Removing a key that does not exist is not an error.
+pub fn remove(key: &str) -> Result<(), FsError> {
+    with_kv(|store| store.remove(key.as_bytes()))
+}
+
+/// Get the length of the value of the key in bytes.
+pub fn value_len(key: &str) -> Result<usize, FsError> {
+    with_kv(|store| store.len(key.as_bytes()).ok_or(FsError::NotFound))
+}
+
+/// Return if the key exists.
+pub fn contains(key: &str) -> Result<bool, FsError> {
+    with_kv(|store| Ok(store.len(key.as_bytes()).is_some()))
+}
+
+/// Call the closure with every key and the length of its value. The closure
+/// runs with the store locked, so it must not access the store.
+pub fn for_each_key<F>(mut op: F) -> Result<(), FsError>
+where
+    F: FnMut(&str, usize),
+{
+    with_kv(|store| {
+        store.for_each(|name, len| {
+            if let Ok(key) = core::str::from_utf8(name) {
+                op(key, len);
+            }
+        });
+        Ok(())
+    })
+}
+
+/// The largest value size in bytes allowed for the key.
+pub fn max_value_len(key: &str) -> Result<usize, FsError> {
+    with_kv(|store| Ok(store.max_data_len(key.len())))
+}
@@ -71,18 +71,58 @@
 
 extern crate alloc;
 
+// Without floating point context preservation, tasks must not be compiled
+// into floating point instructions, otherwise their floating point registers
+// would be silently corrupted by context switches.
+#[cfg(all(not(feature = "fp_context"), target_abi = "eabihf"))]
+compile_error!(
+    "Feature `fp_context` is disabled but the target uses hardware floating \
+    point. Build for `thumbv7em-none-eabi` instead."
+);
+
 mod allocator;
 mod assembly;
 mod boot;
-mod schedule;
+#[cfg(any(feature = "fs", feature = "selftest"))]
+mod crc;
 mod unrecoverable;
 
+pub mod actor;
+#[cfg(feature = "bus")]
+pub mod bus;
+#[cfg(feature = "can")]
+pub mod can;
+pub mod compat;
 pub mod config;
 pub mod debug;
+#[cfg(feature = "fs")]
+pub mod fs;
+pub mod hsm;
+pub mod init;
 pub mod interrupt;
+#[cfg(feature = "kv")]
+pub mod kv;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod peripheral;
+pub mod power;
+pub mod rand;
+pub mod schedule;
+#[cfg(feature = "selftest")]
+pub mod selftest;
+#[cfg(feature = "shell")]
+pub mod shell;
 pub mod sync;
 pub mod task;
 pub mod time;
 
 #[doc(hidden)]
 pub mod unwind;
+
+#[cfg(feature = "breadcrumb")]
+#[doc(inline)]
+pub use power::reboot_into_dfu;
+#[doc(inline)]
+pub use power::shutdown;
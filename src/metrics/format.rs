@@ -0,0 +1,76 @@
+use super::Value;
+use alloc::{format, vec::Vec};
+
+/// Encode the snapshot as described by [`Format::Line`](super::Format::Line).
+pub(super) fn encode_line(values: &[(&str, Value)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (name, value) in values {
+        match value {
+            Value::Counter(value) => push_line(&mut out, name, "", value),
+            Value::Gauge(value) => push_line(&mut out, name, "", value),
+            Value::Histogram {
+                bounds,
+                buckets,
+                count,
+                sum,
+            } => {
+                push_line(&mut out, name, ".count", count);
+                push_line(&mut out, name, ".sum", sum);
+                let mut cumulative = 0u32;
+                for (idx, bucket) in buckets.iter().enumerate() {
+                    cumulative = cumulative.wrapping_add(*bucket);
+                    match bounds.get(idx) {
+                        Some(bound) => {
+                            push_line(&mut out, name, &format!(".le.{}", bound), &cumulative)
+                        }
+                        None => push_line(&mut out, name, ".le.inf", &cumulative),
+                    }
+                }
+            }
+        }
+    }
+    out.push(b'\n');
+    out
+}
+
+fn push_line(out: &mut Vec<u8>, name: &str, suffix: &str, value: &dyn core::fmt::Display) {
+    out.extend_from_slice(format!("{}{} {}\n", name, suffix, value).as_bytes());
+}
+
+/// Encode the snapshot as described by
+/// [`Format::Binary`](super::Format::Binary).
+pub(super) fn encode_binary(tick: u32, values: &[(&str, Value)]) -> Vec<u8> {
+    let mut out = Vec::from([0xA5, b'M']);
+    out.extend_from_slice(&tick.to_le_bytes());
+    out.extend_from_slice(&(values.len() as u16).to_le_bytes());
+
+    for (name, value) in values {
+        let kind = match value {
+            Value::Counter(..) => 0,
+            Value::Gauge(..) => 1,
+            Value::Histogram { .. } => 2,
+        };
+        // Names longer than 255 bytes are truncated.
+        let name = &name.as_bytes()[..name.len().min(u8::MAX as usize)];
+        out.push(kind);
+        out.push(name.len() as u8);
+        out.extend_from_slice(name);
+
+        match value {
+            Value::Counter(value) => out.extend_from_slice(&value.to_le_bytes()),
+            Value::Gauge(value) => out.extend_from_slice(&value.to_le_bytes()),
+            Value::Histogram {
+                bounds,
+                buckets,
+                count,
+                sum,
+            } => {
+                out.push(bounds.len() as u8);
+                for word in bounds.iter().chain(buckets).chain([count, sum]) {
+                    out.extend_from_slice(&word.to_le_bytes());
+                }
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,356 @@
+//! Metrics exported periodically to a sink, enabled by the `metrics`
+//! feature.
+//!
+//! Tasks register named [`Counter`]s, [`Gauge`]s, and [`Histogram`]s, and
+//! update them from task or ISR context without blocking. An exporter task
+//! started by [`start`] periodically takes a snapshot of all metrics and
+//! writes it to a [`MetricsSink`], e.g., a channel drained by a UART, a
+//! network socket, or a file in flash. Each snapshot also includes kernel
+//! metrics named with the `kernel.` prefix:
+//!
+//! - `kernel.uptime_ms`: the tick count.
+//! - `kernel.tasks`: the number of existing tasks.
+//! - `kernel.stacklets`: the number of existing stacklets.
+//! - `kernel.stack_extends`: the number of stack extensions since boot.
+//! - `kernel.heap_used` and `kernel.heap_peak`: the heap usage in bytes.
+//! - `kernel.systicks`: the number of SysTick interrupts, with the
+//!   `irq_stats` feature.
+//!
+//! Snapshots are encoded in one of the [`Format`]s, see [`Format::Line`] and
+//! [`Format::Binary`] for the layouts.
+//!
+//! # Example
+//! ```rust
+//! let rx_frames = metrics::counter("can.rx_frames").unwrap();
+//! let latency = metrics::histogram("can.latency_us", &[10, 100, 1000]).unwrap();
+//!
+//! rx_frames.increment();
+//! latency.record(42);
+//!
+//! let (tx_producer, tx_consumer) = sync::create_channel::<u8, 256>();
+//! // The UART IRQ handler drains `tx_consumer`.
+//! metrics::start(tx_producer, Format::Line, 1000).unwrap();
+//! ```
+
+mod format;
+
+use crate::{
+    allocator, config,
+    schedule::scheduler::Scheduler,
+    sync::{Mutex, Producer},
+    task::{self, TaskBuildError},
+    time::{self, IntervalBarrier},
+};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+
+/// Enumeration of errors of the metrics API.
+#[derive(Debug, PartialEq)]
+pub enum MetricsError {
+    /// A metric of a different type is registered with the same name.
+    NameTaken,
+    /// The histogram bucket bounds are not strictly increasing.
+    InvalidBuckets,
+    /// The export period is zero or too long.
+    InvalidPeriod,
+    /// The exporter task cannot be spawned.
+    Task(TaskBuildError),
+}
+
+/// A destination of encoded snapshots.
+pub trait MetricsSink: Send {
+    /// Write the bytes, blocking until all of them are accepted.
+    fn write(&mut self, data: &[u8]);
+}
+
+/// A channel carrying the bytes to be transmitted.
+impl<const N: usize> MetricsSink for Producer<u8, N> {
+    fn write(&mut self, data: &[u8]) {
+        for byte in data {
+            self.produce(*byte);
+        }
+    }
+}
+
+/// The encoding of snapshots.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// One `name value` line per value followed by an empty line. A
+    /// histogram is written as `name.count`, `name.sum`, and one cumulative
+    /// `name.le.<bound>` line per bucket including `name.le.inf`.
+    Line,
+    /// A frame of little-endian fields:
+    ///
+    /// - header: `0xA5`, `b'M'`, tick `u32`, record count `u16`.
+    /// - each record: kind `u8` (0 counter, 1 gauge, 2 histogram), name
+    ///   length `u8`, name, then the payload.
+    /// - counter payload: value `u32`.
+    /// - gauge payload: value `i32`.
+    /// - histogram payload: bound count `n: u8`, `n` bounds `u32`, `n + 1`
+    ///   bucket counts `u32`, count `u32`, sum `u32`.
+    Binary,
+}
+
+/// A monotonically increasing count. It wraps around after `u32::MAX`.
+pub struct Counter {
+    value: AtomicU32,
+}
+
+impl Counter {
+    /// Increment the count by one.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    /// Increment the count by the given amount.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn add(&self, n: u32) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// The current count.
+    pub fn get(&self) -> u32 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// A value that can go up and down.
+pub struct Gauge {
+    value: AtomicI32,
+}
+
+impl Gauge {
+    /// Set the value.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn set(&self, value: i32) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    /// Add to the value, which may be negative.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn add(&self, delta: i32) {
+        self.value.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    /// The current value.
+    pub fn get(&self) -> i32 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// The distribution of recorded values over buckets with fixed upper
+/// bounds, plus a bucket for values above the largest bound.
+pub struct Histogram {
+    bounds: &'static [u32],
+    buckets: Box<[AtomicU32]>,
+    count: AtomicU32,
+    sum: AtomicU32,
+}
+
+impl Histogram {
+    /// Record a value in the first bucket whose bound is not less than it.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn record(&self, value: u32) {
+        let idx = self.bounds.partition_point(|&bound| bound < value);
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// The number of recorded values.
+    pub fn count(&self) -> u32 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+/// A registered metric.
+enum Metric {
+    Counter(Arc<Counter>),
+    Gauge(Arc<Gauge>),
+    Histogram(Arc<Histogram>),
+}
+
+/// The value of a metric in a snapshot.
+enum Value {
+    Counter(u32),
+    Gauge(i32),
+    Histogram {
+        bounds: &'static [u32],
+        buckets: Vec<u32>,
+        count: u32,
+        sum: u32,
+    },
+}
+
+static REGISTRY: Mutex<Vec<(&'static str, Metric)>> = Mutex::new(Vec::new());
+
+/// Find the metric with the name, or register the one created by `create`.
+/// Return the metric extracted by `extract`, or `None` if the registered
+/// metric has a different type.
+fn get_or_register<T>(
+    name: &'static str,
+    create: impl FnOnce() -> Metric,
+    extract: impl Fn(&Metric) -> Option<Arc<T>>,
+) -> Result<Arc<T>, MetricsError> {
+    let mut registry = REGISTRY.lock();
+    if let Some((_, metric)) = registry.iter().find(|(key, _)| *key == name) {
+        return extract(metric).ok_or(MetricsError::NameTaken);
+    }
+    let metric = create();
+    let found = extract(&metric).ok_or(MetricsError::NameTaken);
+    registry.push((name, metric));
+    found
+}
+
+/// Get the counter with the name, registering it if it does not exist yet.
+/// Registering again with the same name, e.g., from a restarted task, returns
+/// the same counter.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn counter(name: &'static str) -> Result<Arc<Counter>, MetricsError> {
+    get_or_register(
+        name,
+        || {
+            Metric::Counter(Arc::new(Counter {
+                value: AtomicU32::new(0),
+            }))
+        },
+        |metric| match metric {
+            Metric::Counter(counter) => Some(counter.clone()),
+            _ => None,
+        },
+    )
+}
+
+/// Get the gauge with the name, registering it if it does not exist yet.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn gauge(name: &'static str) -> Result<Arc<Gauge>, MetricsError> {
+    get_or_register(
+        name,
+        || {
+            Metric::Gauge(Arc::new(Gauge {
+                value: AtomicI32::new(0),
+            }))
+        },
+        |metric| match metric {
+            Metric::Gauge(gauge) => Some(gauge.clone()),
+            _ => None,
+        },
+    )
+}
+
+/// Get the histogram with the name, registering it with the given bucket
+/// bounds if it does not exist yet. The bounds must be strictly increasing
+/// and there can be at most 255 of them.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn histogram(
+    name: &'static str,
+    bounds: &'static [u32],
+) -> Result<Arc<Histogram>, MetricsError> {
+    if bounds.len() > u8::MAX as usize || bounds.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(MetricsError::InvalidBuckets);
+    }
+    get_or_register(
+        name,
+        || {
+            Metric::Histogram(Arc::new(Histogram {
+                bounds,
+                buckets: (0..=bounds.len()).map(|_| AtomicU32::new(0)).collect(),
+                count: AtomicU32::new(0),
+                sum: AtomicU32::new(0),
+            }))
+        },
+        |metric| match metric {
+            Metric::Histogram(histogram) => Some(histogram.clone()),
+            _ => None,
+        },
+    )
+}
+
+/// Take a snapshot of the kernel metrics followed by the registered ones.
+fn snapshot() -> Vec<(&'static str, Value)> {
+    let (heap_used, heap_peak) = allocator::heap_usage();
+    let mut values = Vec::from([
+        ("kernel.uptime_ms", Value::Counter(time::get_tick())),
+        ("kernel.tasks", Value::Gauge(Scheduler::task_count() as i32)),
+        (
+            "kernel.stacklets",
+            Value::Gauge(task::get_active_stacklet_count() as i32),
+        ),
+        (
+            "kernel.stack_extends",
+            Value::Counter(task::get_stack_extend_count() as u32),
+        ),
+        ("kernel.heap_used", Value::Gauge(heap_used as i32)),
+        ("kernel.heap_peak", Value::Gauge(heap_peak as i32)),
+    ]);
+    #[cfg(feature = "irq_stats")]
+    values.push((
+        "kernel.systicks",
+        Value::Counter(crate::interrupt::stats::systick_count()),
+    ));
+
+    for (name, metric) in REGISTRY.lock().iter() {
+        let value = match metric {
+            Metric::Counter(counter) => Value::Counter(counter.get()),
+            Metric::Gauge(gauge) => Value::Gauge(gauge.get()),
+            Metric::Histogram(histogram) => Value::Histogram {
+                bounds: histogram.bounds,
+                buckets: histogram
+                    .buckets
+                    .iter()
+                    .map(|bucket| bucket.load(Ordering::Relaxed))
+                    .collect(),
+                count: histogram.count(),
+                sum: histogram.sum.load(Ordering::Relaxed),
+            },
+        };
+        values.push((*name, value));
+    }
+    values
+}
+
+/// Take a snapshot of all metrics and write it to the sink.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn export(sink: &mut dyn MetricsSink, format: Format) {
+    // Encode before writing because writing may block.
+    let values = snapshot();
+    let data = match format {
+        Format::Line => format::encode_line(&values),
+        Format::Binary => format::encode_binary(time::get_tick(), &values),
+    };
+    sink.write(&data);
+}
+
+/// Spawn an exporter task writing a snapshot to the sink every `period_ms`
+/// milliseconds. Multiple exporters with different sinks may run at the
+/// same time.
+pub fn start<S>(mut sink: S, format: Format, period_ms: u32) -> Result<(), MetricsError>
+where
+    S: MetricsSink + 'static,
+{
+    if period_ms == 0 {
+        return Err(MetricsError::InvalidPeriod);
+    }
+    let mut barrier = IntervalBarrier::new(period_ms).map_err(|_| MetricsError::InvalidPeriod)?;
+
+    task::build()
+        .set_id(config::METRICS_TASK_ID)
+        .set_name("metrics")
+        .set_priority(config::METRICS_TASK_PRIORITY)
+        .set_entry(move || loop {
+            barrier.wait();
+            export(&mut sink, format);
+        })
+        .spawn()
+        .map_err(MetricsError::Task)
+}
@@ -0,0 +1,118 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+use smoltcp::{
+    phy::{self, DeviceCapabilities, Medium},
+    time::Instant,
+};
+
+/// An Ethernet controller driver that the network stack can use.
+///
+/// The driver should call [`notify_allow_isr`](super::notify_allow_isr) from
+/// its IRQ handler whenever a frame is received or a transmit buffer becomes
+/// available, so that the network task polls the driver promptly instead of
+/// waiting for the next timer deadline.
+pub trait NetDriver: Send {
+    /// The MAC address of the controller.
+    fn mac_address(&self) -> [u8; 6];
+
+    /// The maximum Ethernet frame length in bytes, excluding the FCS.
+    fn mtu(&self) -> usize {
+        1514
+    }
+
+    /// Copy the next received frame into `buf` and return its length. Return
+    /// `None` if no frame is pending. `buf` is at least [`mtu`](Self::mtu)
+    /// bytes long.
+    fn receive(&mut self, buf: &mut [u8]) -> Option<usize>;
+
+    /// Return if a frame can be transmitted now.
+    fn can_transmit(&self) -> bool;
+
+    /// Transmit the frame. Only called after [`can_transmit`](Self::can_transmit)
+    /// returned `true`.
+    fn transmit(&mut self, frame: &[u8]);
+}
+
+/// Adapts a [`NetDriver`] to the smoltcp device interface.
+pub(super) struct DriverDevice {
+    driver: Box<dyn NetDriver>,
+    rx_buf: Vec<u8>,
+}
+
+impl DriverDevice {
+    pub(super) fn new(driver: Box<dyn NetDriver>) -> Self {
+        let mtu = driver.mtu();
+        Self {
+            driver,
+            rx_buf: vec![0; mtu],
+        }
+    }
+
+    pub(super) fn mac_address(&self) -> [u8; 6] {
+        self.driver.mac_address()
+    }
+}
+
+impl phy::Device for DriverDevice {
+    type RxToken<'a> = RxToken<'a>;
+    type TxToken<'a> = TxToken<'a>;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(RxToken<'_>, TxToken<'_>)> {
+        if !self.driver.can_transmit() {
+            return None;
+        }
+        let len = self.driver.receive(&mut self.rx_buf)?;
+        Some((
+            RxToken {
+                frame: &mut self.rx_buf[..len],
+            },
+            TxToken {
+                driver: &mut *self.driver,
+            },
+        ))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<TxToken<'_>> {
+        if !self.driver.can_transmit() {
+            return None;
+        }
+        Some(TxToken {
+            driver: &mut *self.driver,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = self.driver.mtu();
+        caps
+    }
+}
+
+pub(super) struct RxToken<'a> {
+    frame: &'a mut [u8],
+}
+
+impl<'a> phy::RxToken for RxToken<'a> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(self.frame)
+    }
+}
+
+pub(super) struct TxToken<'a> {
+    driver: &'a mut dyn NetDriver,
+}
+
+impl<'a> phy::TxToken for TxToken<'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = vec![0; len];
+        let res = f(&mut frame);
+        self.driver.transmit(&frame);
+        res
+    }
+}
@@ -0,0 +1,208 @@
+//! TCP/IP networking based on [smoltcp](https://docs.rs/smoltcp), enabled by
+//! the `net` feature.
+//!
+//! The network stack runs in a dedicated kernel task started by [`start`]
+//! with a [`NetDriver`] for the Ethernet controller. The task polls the
+//! stack whenever the driver or a socket notifies it, or when a protocol
+//! timer expires, and blocks on a [`Mailbox`] otherwise. Other tasks use the
+//! stack through [`TcpSocket`] and [`UdpSocket`], whose blocking operations
+//! wait on a per-socket mailbox notified by the network task. Received UDP
+//! datagrams can also be forwarded into a channel with
+//! [`UdpSocket::into_channel`].
+//!
+//! # Example
+//! ```rust
+//! net::start(
+//!     MyEthDriver::new(),
+//!     NetConfig {
+//!         ipv4_addr: [192, 168, 1, 2],
+//!         prefix_len: 24,
+//!         gateway: Some([192, 168, 1, 1]),
+//!     },
+//! )
+//! .unwrap();
+//!
+//! let socket = TcpSocket::new().unwrap();
+//! socket.connect(([192, 168, 1, 10], 8000), 49152, 1000).unwrap();
+//! socket.send(b"hello", 1000).unwrap();
+//! ```
+
+mod driver;
+mod socket;
+
+pub use driver::NetDriver;
+pub use socket::*;
+
+use crate::{
+    config,
+    sync::{Mailbox, Mutex},
+    task::{self, TaskBuildError},
+    time,
+};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use driver::DriverDevice;
+use smoltcp::{
+    iface::{Config, Interface, SocketHandle, SocketSet},
+    time::Instant,
+    wire::{EthernetAddress, HardwareAddress, IpCidr, Ipv4Address},
+};
+
+/// Static IPv4 configuration of the network interface.
+pub struct NetConfig {
+    /// The address of the interface.
+    pub ipv4_addr: [u8; 4],
+    /// The length of the subnet prefix, e.g., 24 for `255.255.255.0`.
+    pub prefix_len: u8,
+    /// The default gateway, if any.
+    pub gateway: Option<[u8; 4]>,
+}
+
+/// Enumeration of errors of the networking API.
+#[derive(Debug, PartialEq)]
+pub enum NetError {
+    /// [`start`] has not been called.
+    NotStarted,
+    /// [`start`] has already been called.
+    AlreadyStarted,
+    /// The network task cannot be spawned.
+    Task(TaskBuildError),
+    /// The operation did not complete within the timeout.
+    Timeout,
+    /// The socket is not in a state allowing the operation, e.g., sending on
+    /// an unconnected TCP socket.
+    InvalidState,
+    /// The address or port is not usable, e.g., port 0.
+    Unaddressable,
+    /// The connection was closed or reset by the peer.
+    Closed,
+    /// The datagram does not fit in the socket buffer.
+    TooLarge,
+}
+
+/// The state of the network stack, owned by the network task and borrowed by
+/// socket operations.
+struct NetStack {
+    iface: Interface,
+    device: DriverDevice,
+    sockets: SocketSet<'static>,
+    /// Mailboxes of live sockets to be notified after the stack makes
+    /// progress.
+    waiters: Vec<(SocketHandle, Arc<Mailbox>)>,
+    /// The tick count when the clock was last read.
+    last_tick: u32,
+    /// Milliseconds elapsed since the stack started. Unlike the tick count,
+    /// it does not wrap around.
+    millis: i64,
+}
+
+impl NetStack {
+    fn now(&mut self) -> Instant {
+        let tick = time::get_tick();
+        self.millis += tick.wrapping_sub(self.last_tick) as i64;
+        self.last_tick = tick;
+        Instant::from_millis(self.millis)
+    }
+
+    /// Poll the interface once and notify the sockets if anything changed.
+    /// Return the number of milliseconds until the stack should be polled
+    /// again.
+    fn poll(&mut self) -> Option<u32> {
+        let now = self.now();
+        if self.iface.poll(now, &mut self.device, &mut self.sockets) {
+            for (_, mailbox) in self.waiters.iter() {
+                mailbox.notify_allow_isr();
+            }
+        }
+        self.iface
+            .poll_delay(now, &self.sockets)
+            .map(|delay| delay.total_millis().min(u32::MAX as u64) as u32)
+    }
+}
+
+static STACK: Mutex<Option<NetStack>> = Mutex::new(None);
+
+/// Notified to request the network task to poll the stack.
+static NET_MAILBOX: Mailbox = Mailbox::new();
+
+/// Request the network task to poll the stack. Drivers should call this
+/// function from their IRQ handlers when a frame is received or transmitted.
+pub fn notify_allow_isr() {
+    NET_MAILBOX.notify_allow_isr();
+}
+
+/// Bring up the network interface on the given driver and spawn the network
+/// task.
+pub fn start<D>(driver: D, net_config: NetConfig) -> Result<(), NetError>
+where
+    D: NetDriver + 'static,
+{
+    {
+        let mut stack = STACK.lock();
+        if stack.is_some() {
+            return Err(NetError::AlreadyStarted);
+        }
+
+        let mut device = DriverDevice::new(Box::new(driver));
+        let hw_addr = HardwareAddress::Ethernet(EthernetAddress(device.mac_address()));
+        let last_tick = time::get_tick();
+        let mut iface = Interface::new(Config::new(hw_addr), &mut device, Instant::from_millis(0));
+
+        let [a, b, c, d] = net_config.ipv4_addr;
+        iface.update_ip_addrs(|addrs| {
+            let _ = addrs.push(IpCidr::new(
+                Ipv4Address::new(a, b, c, d).into(),
+                net_config.prefix_len,
+            ));
+        });
+        if let Some([a, b, c, d]) = net_config.gateway {
+            let _ = iface
+                .routes_mut()
+                .add_default_ipv4_route(Ipv4Address::new(a, b, c, d));
+        }
+
+        *stack = Some(NetStack {
+            iface,
+            device,
+            sockets: SocketSet::new(Vec::new()),
+            waiters: Vec::new(),
+            last_tick,
+            millis: 0,
+        });
+    }
+
+    task::build()
+        .set_id(config::NET_TASK_ID)
+        .set_name("net")
+        .set_priority(config::NET_TASK_PRIORITY)
+        .set_entry(net_task)
+        .spawn()
+        .map_err(NetError::Task)
+}
+
+/// The body of the network task.
+fn net_task() {
+    loop {
+        let delay_ms = match STACK.lock().as_mut() {
+            Some(stack) => stack.poll(),
+            None => None,
+        };
+
+        let delay_ms = delay_ms.map_or(config::NET_MAX_POLL_INTERVAL_MS, |delay_ms| {
+            delay_ms.min(config::NET_MAX_POLL_INTERVAL_MS)
+        });
+        if delay_ms > 0 {
+            NET_MAILBOX.wait_until_timeout(delay_ms);
+        }
+    }
+}
+
+/// Run the closure with the network stack locked.
+fn with_stack<F, R>(op: F) -> Result<R, NetError>
+where
+    F: FnOnce(&mut NetStack) -> R,
+{
+    match STACK.lock().as_mut() {
+        Some(stack) => Ok(op(stack)),
+        None => Err(NetError::NotStarted),
+    }
+}
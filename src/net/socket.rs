@@ -0,0 +1,326 @@
+use super::{notify_allow_isr, with_stack, NetError, NetStack};
+use crate::{
+    config,
+    sync::{self, Consumer, Mailbox},
+    task, time,
+};
+use alloc::{sync::Arc, vec, vec::Vec};
+use smoltcp::{
+    iface::SocketHandle,
+    socket::{tcp, udp, AnySocket},
+    wire::{IpAddress, IpEndpoint, Ipv4Address},
+};
+
+/// Pass as the timeout to block until the operation completes.
+pub const WAIT_FOREVER: u32 = u32::MAX;
+
+/// Add the socket to the stack and allocate the mailbox that the network
+/// task notifies when the socket may have become ready.
+fn register<S>(socket: S) -> Result<(SocketHandle, Arc<Mailbox>), NetError>
+where
+    S: AnySocket<'static>,
+{
+    with_stack(|stack| {
+        let handle = stack.sockets.add(socket);
+        let mailbox = Arc::new(Mailbox::new());
+        stack.waiters.push((handle, mailbox.clone()));
+        (handle, mailbox)
+    })
+}
+
+/// Remove the socket and its mailbox from the stack.
+fn unregister(handle: SocketHandle) {
+    let _ = with_stack(|stack| {
+        stack.waiters.retain(|(h, _)| *h != handle);
+        stack.sockets.remove(handle);
+    });
+}
+
+/// Repeatedly run `op` with the stack locked until it returns `Some`. Between
+/// attempts, wait on the mailbox of the socket, which is notified when the
+/// network task makes progress.
+fn block_on<F, R>(mailbox: &Mailbox, timeout_ms: u32, mut op: F) -> Result<R, NetError>
+where
+    F: FnMut(&mut NetStack) -> Option<Result<R, NetError>>,
+{
+    let start = time::get_tick();
+    loop {
+        if let Some(res) = with_stack(&mut op)? {
+            // The operation may have queued data or changed the socket state.
+            notify_allow_isr();
+            return res;
+        }
+
+        if timeout_ms == WAIT_FOREVER {
+            mailbox.wait();
+            continue;
+        }
+        let elapsed = time::get_tick().wrapping_sub(start);
+        if elapsed >= timeout_ms {
+            return Err(NetError::Timeout);
+        }
+        mailbox.wait_until_timeout(timeout_ms - elapsed);
+    }
+}
+
+/// A TCP socket. Dropping the socket removes it from the stack immediately,
+/// so [`close`](Self::close) should be called first for a graceful shutdown.
+pub struct TcpSocket {
+    handle: SocketHandle,
+    mailbox: Arc<Mailbox>,
+}
+
+impl TcpSocket {
+    /// Create a socket with [`NET_TCP_BUFFER_SIZE`](config::NET_TCP_BUFFER_SIZE)
+    /// bytes of buffer in each direction.
+    pub fn new() -> Result<Self, NetError> {
+        let socket = tcp::Socket::new(
+            tcp::SocketBuffer::new(vec![0; config::NET_TCP_BUFFER_SIZE]),
+            tcp::SocketBuffer::new(vec![0; config::NET_TCP_BUFFER_SIZE]),
+        );
+        let (handle, mailbox) = register(socket)?;
+        Ok(Self { handle, mailbox })
+    }
+
+    /// Connect to the remote address and port from the given local port.
+    /// Block until the connection is established.
+    pub fn connect(
+        &self,
+        remote: ([u8; 4], u16),
+        local_port: u16,
+        timeout_ms: u32,
+    ) -> Result<(), NetError> {
+        let (addr, port) = remote;
+        with_stack(|stack| {
+            let socket = stack.sockets.get_mut::<tcp::Socket>(self.handle);
+            socket
+                .connect(
+                    stack.iface.context(),
+                    (IpAddress::from(Ipv4Address::from_bytes(&addr)), port),
+                    local_port,
+                )
+                .map_err(|err| match err {
+                    tcp::ConnectError::InvalidState => NetError::InvalidState,
+                    tcp::ConnectError::Unaddressable => NetError::Unaddressable,
+                })
+        })??;
+
+        block_on(&self.mailbox, timeout_ms, |stack| {
+            let socket = stack.sockets.get_mut::<tcp::Socket>(self.handle);
+            if socket.may_send() {
+                Some(Ok(()))
+            } else if !socket.is_active() {
+                Some(Err(NetError::Closed))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Listen for an incoming connection on the local port.
+    pub fn listen(&self, port: u16) -> Result<(), NetError> {
+        with_stack(|stack| {
+            stack
+                .sockets
+                .get_mut::<tcp::Socket>(self.handle)
+                .listen(port)
+                .map_err(|err| match err {
+                    tcp::ListenError::InvalidState => NetError::InvalidState,
+                    tcp::ListenError::Unaddressable => NetError::Unaddressable,
+                })
+        })?
+    }
+
+    /// Block until a listening socket has established a connection.
+    pub fn accept(&self, timeout_ms: u32) -> Result<(), NetError> {
+        block_on(&self.mailbox, timeout_ms, |stack| {
+            let socket = stack.sockets.get_mut::<tcp::Socket>(self.handle);
+            match socket.state() {
+                tcp::State::Listen | tcp::State::SynReceived => None,
+                _ if socket.is_active() => Some(Ok(())),
+                _ => Some(Err(NetError::Closed)),
+            }
+        })
+    }
+
+    /// Send as much of `data` as fits in the transmit buffer, blocking until
+    /// there is room for at least one byte. Return the number of bytes sent.
+    pub fn send(&self, data: &[u8], timeout_ms: u32) -> Result<usize, NetError> {
+        block_on(&self.mailbox, timeout_ms, |stack| {
+            let socket = stack.sockets.get_mut::<tcp::Socket>(self.handle);
+            if !socket.may_send() {
+                return Some(Err(NetError::Closed));
+            }
+            if !socket.can_send() {
+                return None;
+            }
+            Some(socket.send_slice(data).map_err(|_| NetError::InvalidState))
+        })
+    }
+
+    /// Receive into `buf`, blocking until at least one byte is available.
+    /// Return the number of bytes received, which is zero once the peer has
+    /// closed the connection and all data has been received.
+    pub fn recv(&self, buf: &mut [u8], timeout_ms: u32) -> Result<usize, NetError> {
+        block_on(&self.mailbox, timeout_ms, |stack| {
+            let socket = stack.sockets.get_mut::<tcp::Socket>(self.handle);
+            if socket.can_recv() {
+                return Some(socket.recv_slice(buf).map_err(|_| NetError::Closed));
+            }
+            if !socket.may_recv() {
+                return Some(Ok(0));
+            }
+            None
+        })
+    }
+
+    /// Close the transmit half of the connection. Data already in the
+    /// transmit buffer is still sent.
+    pub fn close(&self) {
+        let _ = with_stack(|stack| stack.sockets.get_mut::<tcp::Socket>(self.handle).close());
+        notify_allow_isr();
+    }
+}
+
+impl Drop for TcpSocket {
+    fn drop(&mut self) {
+        unregister(self.handle);
+    }
+}
+
+/// A datagram received by a [`UdpSocket`] forwarded through a channel.
+pub struct UdpDatagram {
+    /// The payload.
+    pub data: Vec<u8>,
+    /// The IPv4 address of the sender.
+    pub addr: [u8; 4],
+    /// The port of the sender.
+    pub port: u16,
+}
+
+/// A UDP socket.
+pub struct UdpSocket {
+    handle: SocketHandle,
+    mailbox: Arc<Mailbox>,
+}
+
+impl UdpSocket {
+    /// Create a socket buffering up to
+    /// [`NET_UDP_PACKET_NUMBER`](config::NET_UDP_PACKET_NUMBER) datagrams and
+    /// [`NET_UDP_BUFFER_SIZE`](config::NET_UDP_BUFFER_SIZE) bytes of payload
+    /// in each direction.
+    pub fn new() -> Result<Self, NetError> {
+        let new_buffer = || {
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; config::NET_UDP_PACKET_NUMBER],
+                vec![0; config::NET_UDP_BUFFER_SIZE],
+            )
+        };
+        let socket = udp::Socket::new(new_buffer(), new_buffer());
+        let (handle, mailbox) = register(socket)?;
+        Ok(Self { handle, mailbox })
+    }
+
+    /// Bind the socket to the local port.
+    pub fn bind(&self, port: u16) -> Result<(), NetError> {
+        with_stack(|stack| {
+            stack
+                .sockets
+                .get_mut::<udp::Socket>(self.handle)
+                .bind(port)
+                .map_err(|err| match err {
+                    udp::BindError::InvalidState => NetError::InvalidState,
+                    udp::BindError::Unaddressable => NetError::Unaddressable,
+                })
+        })?
+    }
+
+    /// Send a datagram to the remote address and port, blocking until there
+    /// is room in the transmit buffer.
+    pub fn send_to(
+        &self,
+        data: &[u8],
+        remote: ([u8; 4], u16),
+        timeout_ms: u32,
+    ) -> Result<(), NetError> {
+        let (addr, port) = remote;
+        let endpoint = IpEndpoint::new(Ipv4Address::from_bytes(&addr).into(), port);
+        block_on(&self.mailbox, timeout_ms, |stack| {
+            let socket = stack.sockets.get_mut::<udp::Socket>(self.handle);
+            match socket.send_slice(data, endpoint) {
+                Ok(()) => Some(Ok(())),
+                // The datagram can never fit if the buffer is empty.
+                Err(udp::SendError::BufferFull) if socket.send_queue() == 0 => {
+                    Some(Err(NetError::TooLarge))
+                }
+                Err(udp::SendError::BufferFull) => None,
+                Err(udp::SendError::Unaddressable) => Some(Err(NetError::Unaddressable)),
+            }
+        })
+    }
+
+    /// Receive a datagram into `buf`, blocking until one is available. Return
+    /// the length of the datagram and the address and port of the sender.
+    pub fn recv_from(
+        &self,
+        buf: &mut [u8],
+        timeout_ms: u32,
+    ) -> Result<(usize, ([u8; 4], u16)), NetError> {
+        block_on(&self.mailbox, timeout_ms, |stack| {
+            let socket = stack.sockets.get_mut::<udp::Socket>(self.handle);
+            if !socket.can_recv() {
+                return None;
+            }
+            Some(match socket.recv_slice(buf) {
+                Ok((len, meta)) => Ok((len, (ipv4_bytes(meta.endpoint.addr), meta.endpoint.port))),
+                Err(udp::RecvError::Truncated) => Err(NetError::TooLarge),
+                Err(udp::RecvError::Exhausted) => Err(NetError::InvalidState),
+            })
+        })
+    }
+
+    /// Forward received datagrams into a channel of capacity `N`. A task is
+    /// spawned that owns the socket and keeps receiving from it. When the
+    /// channel is full, the task blocks and further datagrams queue up in the
+    /// socket buffer. Datagrams larger than
+    /// [`NET_UDP_BUFFER_SIZE`](config::NET_UDP_BUFFER_SIZE) are dropped.
+    pub fn into_channel<const N: usize>(self) -> Result<Consumer<UdpDatagram, N>, NetError> {
+        let (producer, consumer) = sync::create_channel::<UdpDatagram, N>();
+        task::build()
+            .set_id(config::NET_TASK_ID)
+            .set_priority(config::NET_TASK_PRIORITY)
+            .set_entry(move || {
+                let mut buf = vec![0; config::NET_UDP_BUFFER_SIZE];
+                loop {
+                    match self.recv_from(&mut buf, WAIT_FOREVER) {
+                        Ok((len, (addr, port))) => producer.produce(UdpDatagram {
+                            data: Vec::from(&buf[..len]),
+                            addr,
+                            port,
+                        }),
+                        Err(NetError::TooLarge) => continue,
+                        Err(_) => break,
+                    }
+                }
+            })
+            .spawn()
+            .map_err(NetError::Task)?;
+        Ok(consumer)
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        unregister(self.handle);
+    }
+}
+
+/// Get the bytes of an IPv4 address. Other kinds of addresses never reach
+/// the sockets because the interface only has an IPv4 address.
+#[allow(unreachable_patterns)]
+fn ipv4_bytes(addr: IpAddress) -> [u8; 4] {
+    match addr {
+        IpAddress::Ipv4(addr) => addr.0,
+        _ => [0; 4],
+    }
+}
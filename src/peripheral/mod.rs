@@ -0,0 +1,100 @@
+//! Ownership of peripherals among drivers.
+//!
+//! Peripherals are usually obtained with `Peripherals::steal()`, which lets
+//! two drivers silently program the same peripheral. Drivers should instead
+//! [`claim`] each peripheral they use by name, e.g., `"USART1"`,
+//! `"DMA2_Stream7"`, or `"TIM2"`, when they are initialized. Claiming a
+//! peripheral already claimed fails right away with the task that holds it,
+//! and is logged as an error. A peripheral is released when its [`Claim`] is
+//! dropped, e.g., when the owning task is unwound, so that a restarted
+//! driver can claim it again.
+//!
+//! The names are compared literally, so all drivers should use the names in
+//! the reference manual of the MCU. [`for_each_claim`] lists the claimed
+//! peripherals, which the `claims` shell command prints.
+//!
+//! # Example
+//! ```rust
+//! let _usart = peripheral::claim("USART1").unwrap();
+//! let _dma = peripheral::claim("DMA2_Stream7").unwrap();
+//! let dp = unsafe { pac::Peripherals::steal() };
+//! ```
+
+use crate::{debug::log::log_error, sync::Mutex, task};
+use alloc::vec::Vec;
+
+/// Enumeration of errors of the peripheral API.
+#[derive(Debug, PartialEq)]
+pub enum ClaimError {
+    /// The peripheral is already claimed by the task with the ID.
+    Claimed { task_id: u8 },
+    /// The name is empty.
+    InvalidName,
+}
+
+/// The ownership of a peripheral. The peripheral is released when dropped.
+#[must_use = "the peripheral is released when the claim is dropped"]
+pub struct Claim {
+    name: &'static str,
+}
+
+impl Claim {
+    /// The name of the claimed peripheral.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        CLAIMS.lock().retain(|(name, _)| *name != self.name);
+    }
+}
+
+/// The claimed peripherals and the ID of the claiming task.
+static CLAIMS: Mutex<Vec<(&'static str, u8)>> = Mutex::new(Vec::new());
+
+/// Claim the peripheral with the name for the current task.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn claim(name: &'static str) -> Result<Claim, ClaimError> {
+    if name.is_empty() {
+        return Err(ClaimError::InvalidName);
+    }
+    let task_id = task::get_current_id();
+    let mut claims = CLAIMS.lock();
+    if let Some((_, owner)) = claims.iter().find(|(claimed, _)| *claimed == name) {
+        let owner = *owner;
+        drop(claims);
+        log_error!("{} already claimed by task {}", name, owner);
+        return Err(ClaimError::Claimed { task_id: owner });
+    }
+    claims.push((name, task_id));
+    Ok(Claim { name })
+}
+
+/// Return the ID of the task that claimed the peripheral with the name, or
+/// `None` if it is not claimed.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn owner(name: &str) -> Option<u8> {
+    CLAIMS
+        .lock()
+        .iter()
+        .find(|(claimed, _)| *claimed == name)
+        .map(|(_, task_id)| *task_id)
+}
+
+/// Call the closure with the name and the claiming task ID of each claimed
+/// peripheral, in the order they were claimed. The closure runs with the
+/// claims locked, so it must not claim or release a peripheral.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn for_each_claim<F>(mut op: F)
+where
+    F: FnMut(&'static str, u8),
+{
+    for (name, task_id) in CLAIMS.lock().iter() {
+        op(name, *task_id);
+    }
+}
@@ -0,0 +1,266 @@
+//! Orderly system shutdown and reboot.
+//!
+//! [`shutdown`] brings the system down in the following steps before
+//! resetting it:
+//!
+//! 1. Every [`ShutdownToken`] is cancelled, waking up the tasks waiting on
+//!    them, so that long-running tasks can wind down.
+//! 2. The hooks registered with [`register_hook`] run in registration order
+//!    in a dedicated task, for at most
+//!    [`SHUTDOWN_HOOK_TIMEOUT_MS`](config::SHUTDOWN_HOOK_TIMEOUT_MS)
+//!    milliseconds in total.
+//! 3. The queued crash log entries are written to flash, with the
+//!    `crash_log` feature, and the logger task is given at most
+//!    [`SHUTDOWN_FLUSH_TIMEOUT_MS`](config::SHUTDOWN_FLUSH_TIMEOUT_MS)
+//!    milliseconds to write the queued log records to its sink.
+//! 4. The reboot reason is recorded in the
+//!    [breadcrumbs](crate::debug::breadcrumb), with the `breadcrumb`
+//!    feature, and the system is reset through `SCB::AIRCR`.
+//!
+//! Bytes already handed to a sink, e.g., those waiting in a channel drained
+//! by a UART, are not waited for.
+//!
+//! With the `breadcrumb` feature, [`reboot_into_dfu`] shuts down the system
+//! the same way, and the next boot jumps to the system bootloader in ROM
+//! before the kernel initializes, where the firmware can be updated over USB
+//! DFU or UART. The request is passed to the next boot in the breadcrumbs.
+//!
+//! # Example
+//! ```rust
+//! power::register_hook(|| motor.stop()).unwrap();
+//!
+//! let token = power::token();
+//! while !token.is_cancelled() {
+//!     // ...
+//! }
+//!
+//! hopter::shutdown(RebootReason::Requested);
+//! ```
+
+use crate::{
+    config,
+    debug::{
+        breadcrumb::{self, RebootReason},
+        log::{self, log_warn},
+    },
+    schedule::current,
+    sync::{Mailbox, Mutex},
+    task, time,
+};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+#[cfg(feature = "breadcrumb")]
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicBool, Ordering};
+use cortex_m::peripheral::SCB;
+
+/// Address of the system memory holding the bootloader in ROM.
+#[cfg(feature = "breadcrumb")]
+const SYSTEM_MEMORY: *const u32 = 0x1FFF_0000 as *const u32;
+
+/// Address of the `RCC_APB2ENR` register.
+#[cfg(feature = "breadcrumb")]
+const RCC_APB2ENR: *mut u32 = 0x4002_3844 as *mut u32;
+
+/// The `SYSCFGEN` bit in `RCC_APB2ENR`.
+#[cfg(feature = "breadcrumb")]
+const RCC_APB2ENR_SYSCFGEN: u32 = 1 << 14;
+
+/// Address of the `SYSCFG_MEMRMP` register.
+#[cfg(feature = "breadcrumb")]
+const SYSCFG_MEMRMP: *mut u32 = 0x4001_3800 as *mut u32;
+
+/// The `MEM_MODE` value mapping the system memory at address zero.
+#[cfg(feature = "breadcrumb")]
+const SYSCFG_MEMRMP_SYSTEM_FLASH: u32 = 0b01;
+
+/// Enumeration of errors of the power API.
+#[derive(Debug, PartialEq)]
+pub enum ShutdownError {
+    /// The system is already shutting down.
+    InProgress,
+}
+
+/// Set when [`shutdown`] is called.
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// The mailboxes of the existing tokens.
+static TOKENS: Mutex<Vec<Arc<Mailbox>>> = Mutex::new(Vec::new());
+
+/// The registered shutdown hooks.
+static HOOKS: Mutex<Vec<Box<dyn FnOnce() + Send>>> = Mutex::new(Vec::new());
+
+/// Notified when all shutdown hooks have returned.
+static HOOKS_DONE: Mailbox = Mailbox::new();
+
+/// Never notified. Blocks the callers of [`shutdown`] other than the first.
+static NEVER: Mailbox = Mailbox::new();
+
+/// A token cancelled when the system starts shutting down.
+pub struct ShutdownToken {
+    mailbox: Arc<Mailbox>,
+}
+
+impl ShutdownToken {
+    /// Return if the system is shutting down.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn is_cancelled(&self) -> bool {
+        SHUTTING_DOWN.load(Ordering::SeqCst)
+    }
+
+    /// Block until the system starts shutting down.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn wait(&self) {
+        while !self.is_cancelled() {
+            self.mailbox.wait();
+        }
+    }
+
+    /// Block until the system starts shutting down or the timeout elapses.
+    /// Return if the system is shutting down.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn wait_until_timeout(&self, timeout_ms: u32) -> bool {
+        if !self.is_cancelled() {
+            self.mailbox.wait_until_timeout(timeout_ms);
+        }
+        self.is_cancelled()
+    }
+}
+
+impl Drop for ShutdownToken {
+    fn drop(&mut self) {
+        TOKENS
+            .lock()
+            .retain(|mailbox| !Arc::ptr_eq(mailbox, &self.mailbox));
+    }
+}
+
+/// Return if the system is shutting down.
+pub(crate) fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// Create a token cancelled when the system starts shutting down. A token
+/// created after that is already cancelled.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn token() -> ShutdownToken {
+    let mailbox = Arc::new(Mailbox::new());
+    TOKENS.lock().push(mailbox.clone());
+    ShutdownToken { mailbox }
+}
+
+/// Register a hook to run when the system shuts down, e.g., to bring
+/// actuators into a safe state or to close files.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn register_hook<F>(hook: F) -> Result<(), ShutdownError>
+where
+    F: FnOnce() + Send + 'static,
+{
+    if SHUTTING_DOWN.load(Ordering::SeqCst) {
+        return Err(ShutdownError::InProgress);
+    }
+    HOOKS.lock().push(Box::new(hook));
+    Ok(())
+}
+
+/// Shut down and reset the system, recording the reason for the next boot.
+/// See the [module-level documentation](self) for the steps. If another task
+/// is already shutting down the system, block until the reset.
+///
+/// Calling this function in ISR context is allowed, but the system is then
+/// reset right away without running the hooks or flushing the logs.
+pub fn shutdown(reason: RebootReason) -> ! {
+    if current::is_in_isr_context() {
+        reset(reason);
+    }
+    if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+        loop {
+            NEVER.wait();
+        }
+    }
+
+    for mailbox in TOKENS.lock().iter() {
+        mailbox.notify_allow_isr();
+    }
+
+    run_hooks();
+
+    #[cfg(feature = "crash_log")]
+    crate::debug::crash_log::flush();
+    for _ in 0..config::SHUTDOWN_FLUSH_TIMEOUT_MS {
+        if log::is_drained() {
+            break;
+        }
+        let _ = time::sleep_ms(1);
+    }
+
+    reset(reason)
+}
+
+/// Shut down the system and reboot into the system bootloader to install a
+/// firmware update.
+///
+/// Important: *must not* call this function in ISR context.
+#[cfg(feature = "breadcrumb")]
+pub fn reboot_into_dfu() -> ! {
+    breadcrumb::request_dfu();
+    shutdown(RebootReason::Update)
+}
+
+/// Run the registered hooks in a dedicated task and wait until they return
+/// or the timeout elapses.
+fn run_hooks() {
+    let hooks = core::mem::take(&mut *HOOKS.lock());
+    if hooks.is_empty() {
+        return;
+    }
+
+    let result = task::build()
+        .set_id(config::SHUTDOWN_TASK_ID)
+        .set_name("shutdown")
+        .set_priority(config::SHUTDOWN_TASK_PRIORITY)
+        .set_entry(move || {
+            for hook in hooks {
+                hook();
+            }
+            HOOKS_DONE.notify_allow_isr();
+        })
+        .spawn();
+
+    match result {
+        Ok(()) => {
+            if !HOOKS_DONE.wait_until_timeout(config::SHUTDOWN_HOOK_TIMEOUT_MS) {
+                log_warn!("shutdown hooks timed out");
+            }
+        }
+        Err(err) => log_warn!("shutdown hooks not run: {:?}", err),
+    }
+}
+
+/// Record the reboot reason and reset the system.
+pub(crate) fn reset(reason: RebootReason) -> ! {
+    breadcrumb::set_reboot_reason(reason);
+    SCB::sys_reset()
+}
+
+/// Jump to the system bootloader if the previous boot requested so. Called
+/// once by the kernel during boot after the breadcrumbs are initialized, when
+/// the device is still in its reset state.
+#[cfg(feature = "breadcrumb")]
+pub(crate) fn enter_dfu_if_requested() {
+    if !breadcrumb::take_dfu_request() {
+        return;
+    }
+    unsafe {
+        write_volatile(
+            RCC_APB2ENR,
+            read_volatile(RCC_APB2ENR) | RCC_APB2ENR_SYSCFGEN,
+        );
+        write_volatile(SYSCFG_MEMRMP, SYSCFG_MEMRMP_SYSTEM_FLASH);
+        cortex_m::asm::bootload(SYSTEM_MEMORY)
+    }
+}
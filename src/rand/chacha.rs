@@ -0,0 +1,71 @@
+/// The ChaCha20 stream cipher used as a random number generator. The output
+/// is the keystream under the seed as the key and a zero nonce.
+pub(super) struct ChaCha {
+    key: [u32; 8],
+    counter: u64,
+    block: [u32; 16],
+    /// Index of the next unused word in `block`.
+    idx: usize,
+}
+
+/// "expand 32-byte k"
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+#[inline]
+fn quarter_round(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+impl ChaCha {
+    pub(super) fn new(seed: [u32; 8]) -> Self {
+        Self {
+            key: seed,
+            counter: 0,
+            block: [0; 16],
+            idx: 16,
+        }
+    }
+
+    /// Compute the next keystream block.
+    fn refill(&mut self) {
+        let mut input = [0; 16];
+        input[..4].copy_from_slice(&CONSTANTS);
+        input[4..12].copy_from_slice(&self.key);
+        input[12] = self.counter as u32;
+        input[13] = (self.counter >> 32) as u32;
+
+        let mut state = input;
+        for _ in 0..10 {
+            quarter_round(&mut state, 0, 4, 8, 12);
+            quarter_round(&mut state, 1, 5, 9, 13);
+            quarter_round(&mut state, 2, 6, 10, 14);
+            quarter_round(&mut state, 3, 7, 11, 15);
+            quarter_round(&mut state, 0, 5, 10, 15);
+            quarter_round(&mut state, 1, 6, 11, 12);
+            quarter_round(&mut state, 2, 7, 8, 13);
+            quarter_round(&mut state, 3, 4, 9, 14);
+        }
+        for (out, (s, i)) in self.block.iter_mut().zip(state.iter().zip(input.iter())) {
+            *out = s.wrapping_add(*i);
+        }
+
+        self.counter = self.counter.wrapping_add(1);
+        self.idx = 0;
+    }
+
+    pub(super) fn next_u32(&mut self) -> u32 {
+        if self.idx == self.block.len() {
+            self.refill();
+        }
+        let word = self.block[self.idx];
+        self.idx += 1;
+        word
+    }
+}
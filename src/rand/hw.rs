@@ -0,0 +1,56 @@
+//! The hardware random number generator, absent on STM32F401 and STM32F411.
+
+use core::ptr::{read_volatile, write_volatile};
+
+/// Address of the `RCC_AHB2ENR` register.
+const RCC_AHB2ENR: *mut u32 = 0x4002_3834 as *mut u32;
+
+/// The `RNGEN` bit in `RCC_AHB2ENR`.
+const RCC_AHB2ENR_RNGEN: u32 = 1 << 6;
+
+/// Address of the `RNG_CR` register.
+const RNG_CR: *mut u32 = 0x5006_0800 as *mut u32;
+
+/// Address of the `RNG_SR` register.
+const RNG_SR: *const u32 = 0x5006_0804 as *const u32;
+
+/// Address of the `RNG_DR` register.
+const RNG_DR: *const u32 = 0x5006_0808 as *const u32;
+
+/// The `RNGEN` bit in `RNG_CR`.
+const RNG_CR_RNGEN: u32 = 1 << 2;
+
+/// The `DRDY` bit in `RNG_SR`, set when a new word can be read.
+const RNG_SR_DRDY: u32 = 1 << 0;
+
+/// The `CECS` and `SECS` bits in `RNG_SR`, set when the RNG clock is too
+/// slow or the entropy source misbehaves.
+const RNG_SR_ERRORS: u32 = (1 << 1) | (1 << 2);
+
+/// The number of status polls before giving up on a word. A word is produced
+/// every 40 RNG clock cycles, so the limit is only reached if the RNG clock
+/// is not running.
+const POLL_LIMIT: u32 = 1000;
+
+/// Enable the peripheral clock and the generator.
+pub(super) fn enable() {
+    unsafe {
+        write_volatile(RCC_AHB2ENR, read_volatile(RCC_AHB2ENR) | RCC_AHB2ENR_RNGEN);
+        write_volatile(RNG_CR, read_volatile(RNG_CR) | RNG_CR_RNGEN);
+    }
+}
+
+/// Read a random word. Return `None` if the generator reports an error or
+/// does not produce a word in time.
+pub(super) fn read() -> Option<u32> {
+    for _ in 0..POLL_LIMIT {
+        let sr = unsafe { read_volatile(RNG_SR) };
+        if sr & RNG_SR_ERRORS != 0 {
+            return None;
+        }
+        if sr & RNG_SR_DRDY != 0 {
+            return Some(unsafe { read_volatile(RNG_DR) });
+        }
+    }
+    None
+}
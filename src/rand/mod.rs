@@ -0,0 +1,125 @@
+//! Random numbers for tasks.
+//!
+//! Numbers come from the hardware random number generator of the MCU if it
+//! is present and its clock is running. Otherwise, e.g., on STM32F401 and
+//! STM32F411, when the 48 MHz clock is not configured, or after the
+//! generator reports a fault, numbers come from a ChaCha20 generator seeded
+//! from the unique device ID, the boot count, and the timing jitter of the
+//! SysTick counter. The fallback is unpredictable only as far as its seed is,
+//! so code needing secret keys should check [`is_hardware_backed`].
+//!
+//! # Example
+//! ```rust
+//! let mut nonce = [0; 12];
+//! rand::fill_bytes(&mut nonce);
+//! let backoff_ms = 100 + rand::u32() % 100;
+//! ```
+
+mod chacha;
+#[cfg(not(any(feature = "stm32f401", feature = "stm32f411")))]
+mod hw;
+
+use crate::{debug::breadcrumb, sync::SpinSchedSafe, time};
+use chacha::ChaCha;
+use core::ptr::read_volatile;
+use cortex_m::peripheral::SYST;
+
+/// Address of the 96-bit unique device ID.
+const UID: *const u32 = 0x1FFF_7A10 as *const u32;
+
+struct Rng {
+    /// Set when the hardware generator has been enabled.
+    initialized: bool,
+    /// Cleared when the hardware generator fails, after which the software
+    /// generator is used.
+    hw_usable: bool,
+    /// The software generator, seeded when first needed.
+    soft: Option<ChaCha>,
+}
+
+impl Rng {
+    fn init(&mut self) {
+        if self.initialized {
+            return;
+        }
+        self.initialized = true;
+        #[cfg(not(any(feature = "stm32f401", feature = "stm32f411")))]
+        {
+            hw::enable();
+            self.hw_usable = true;
+        }
+    }
+
+    fn read_hw(&mut self) -> Option<u32> {
+        self.init();
+        if !self.hw_usable {
+            return None;
+        }
+        #[cfg(not(any(feature = "stm32f401", feature = "stm32f411")))]
+        if let Some(word) = hw::read() {
+            return Some(word);
+        }
+        self.hw_usable = false;
+        None
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        if let Some(word) = self.read_hw() {
+            return word;
+        }
+        self.soft
+            .get_or_insert_with(|| ChaCha::new(jitter_seed()))
+            .next_u32()
+    }
+}
+
+static RNG: SpinSchedSafe<Rng> = SpinSchedSafe::new(Rng {
+    initialized: false,
+    hw_usable: false,
+    soft: None,
+});
+
+/// Collect a seed for the software generator.
+fn jitter_seed() -> [u32; 8] {
+    let mut seed = [0; 8];
+
+    // The unique device ID differs between chips, and the boot count and the
+    // tick differ between boots of the same chip.
+    for (i, word) in seed.iter_mut().take(3).enumerate() {
+        *word = unsafe { read_volatile(UID.add(i)) };
+    }
+    seed[3] = breadcrumb::boot_count();
+    seed[4] = time::get_tick();
+
+    // Sample the SysTick counter after busy loops of varying length. The
+    // samples vary with interrupts, flash wait states, and bus contention.
+    let mut acc = 0u32;
+    for i in 0..64 {
+        for _ in 0..(acc & 0xF) {
+            core::hint::spin_loop();
+        }
+        acc = acc.rotate_left(5) ^ SYST::get_current();
+        seed[5 + i % 3] ^= acc;
+    }
+
+    seed
+}
+
+/// Fill the buffer with random bytes.
+pub fn fill_bytes(buf: &mut [u8]) {
+    let mut rng = RNG.lock();
+    for chunk in buf.chunks_mut(4) {
+        let bytes = rng.next_u32().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+/// Get a random `u32`.
+pub fn u32() -> u32 {
+    RNG.lock().next_u32()
+}
+
+/// Return if the numbers currently come from the hardware generator.
+pub fn is_hardware_backed() -> bool {
+    RNG.lock().read_hw().is_some()
+}
@@ -27,7 +27,7 @@ static CUR_TASK: RwSpin<Option<Arc<Task>>> = RwSpin::new(None);
 
 /// Set another task to be the current task. The current task will lock its
 /// context field. See [`Task`] for the context lock invariant.
-pub(super) fn update_cur_task(task: Arc<Task>) {
+pub(super) fn update_cur_task(task: Arc<Task>) -> Option<Arc<Task>> {
     let mut write_guard: RwSpinWriteGuard<_> = CUR_TASK.write();
 
     // Unlock the context struct for the task being context switched out of
@@ -50,7 +50,7 @@ pub(super) fn update_cur_task(task: Arc<Task>) {
     CUR_TASK_CTXT_PTR.store(task_ctxt_ptr, Ordering::SeqCst);
 
     // Update the global `Arc` current task reference.
-    write_guard.replace(task);
+    write_guard.replace(task)
 }
 
 /// Point to the struct that preserves the task's callee-saved registers upon
@@ -145,6 +145,12 @@ where
 /// Return if the code is currently executing in an interrupt service routine
 /// (ISR), in contrast to in a task.
 pub(crate) fn is_in_isr_context() -> bool {
+    exception_number() != 0
+}
+
+/// Return the number of the exception currently being handled, or 0 if the
+/// code is executing in a task.
+pub(crate) fn exception_number() -> u32 {
     let ipsr: u32;
 
     unsafe {
@@ -155,7 +161,7 @@ pub(crate) fn is_in_isr_context() -> bool {
         );
     }
 
-    ipsr != 0
+    ipsr
 }
 
 /// Return if the code is currently executing in a task, in contrast to in an
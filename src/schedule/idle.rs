@@ -1,8 +1,10 @@
 use crate::{
     interrupt::context_switch,
-    sync::{SpinSchedSafe, SpinSchedSafeGuard},
+    sync::{AtomicCell, SpinSchedSafe, SpinSchedSafeGuard},
+    time,
 };
 use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 pub(crate) trait IdleCallback: Send + Sync {
     /// Invoked every time the idle task is switched on to the CPU.
@@ -22,6 +24,100 @@ pub(super) fn lock_idle_callbacks() -> SpinSchedSafeGuard<'static, Vec<Arc<dyn I
     IDLE_CALLBACKS.lock_now_or_die()
 }
 
+/// The hook run by the idle task in its loop. See [`set_idle_hook`].
+static IDLE_HOOK: AtomicCell<Option<fn()>> = AtomicCell::new(None);
+
+/// Register a hook run repeatedly by the idle task whenever no other task is
+/// ready, replacing the previous hook, e.g., to feed a watchdog or to enter
+/// a low power state. Without a hook, the idle task waits for an event with
+/// `wfe`. With a hook, the idle task does not wait by itself, so the hook
+/// should wait for an interrupt, e.g., with `wfi`, unless busy looping is
+/// intended.
+///
+/// The hook runs in the context of the idle task, so it *must not* block,
+/// e.g., by sleeping or locking a mutex, and *must not* panic.
+///
+/// # Example
+/// ```rust
+/// task::set_idle_hook(|| {
+///     WATCHDOG.feed();
+///     cortex_m::asm::wfi();
+/// });
+/// ```
+pub fn set_idle_hook(hook: fn()) {
+    IDLE_HOOK.store(Some(hook));
+}
+
+/// The tick and the cycles elapsed in that tick when the idle task was last
+/// switched on to the CPU.
+static IDLE_BEGIN_TICK: AtomicU32 = AtomicU32::new(0);
+static IDLE_BEGIN_CYCLES: AtomicU32 = AtomicU32::new(0);
+
+/// The time the idle task has run, in milliseconds and the remaining CPU
+/// cycles less than a tick.
+static IDLE_MS: AtomicU32 = AtomicU32::new(0);
+static IDLE_REMAINING_CYCLES: AtomicU32 = AtomicU32::new(0);
+
+/// Whether the idle task is on the CPU.
+static IDLE_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Record the time when the idle task is switched on to the CPU. Called by
+/// the scheduler.
+pub(super) fn record_idle_begin() {
+    let (tick, cycles) = time::tick_and_cycles();
+    IDLE_BEGIN_TICK.store(tick, Ordering::Relaxed);
+    IDLE_BEGIN_CYCLES.store(cycles, Ordering::Relaxed);
+    IDLE_RUNNING.store(true, Ordering::Relaxed);
+}
+
+/// Add the time since the idle task was switched on to the CPU to the idle
+/// time. Called by the scheduler when the idle task is switched out.
+pub(super) fn record_idle_end() {
+    let (tick, cycles) = time::tick_and_cycles();
+    let period = time::cycles_per_tick();
+
+    // The idle time is `(tick - begin_tick) * period + cycles - begin_cycles`.
+    // Carry the cycles so that the remaining cycles stay less than a tick.
+    let mut ms = tick.wrapping_sub(IDLE_BEGIN_TICK.load(Ordering::Relaxed));
+    let mut remaining = IDLE_REMAINING_CYCLES.load(Ordering::Relaxed) + cycles;
+    let begin_cycles = IDLE_BEGIN_CYCLES.load(Ordering::Relaxed);
+    if remaining < begin_cycles {
+        remaining += period;
+        ms = ms.wrapping_sub(1);
+    }
+    remaining -= begin_cycles;
+    if remaining >= period {
+        remaining -= period;
+        ms = ms.wrapping_add(1);
+    }
+
+    IDLE_REMAINING_CYCLES.store(remaining, Ordering::Relaxed);
+    IDLE_MS.fetch_add(ms, Ordering::Relaxed);
+    IDLE_RUNNING.store(false, Ordering::Relaxed);
+}
+
+/// Return the number of milliseconds the idle task has run since the
+/// scheduler started, wrapping around `u32::MAX`. The ongoing run of the
+/// idle task is counted only after it is switched out.
+///
+/// The CPU utilization over a period is one minus the ratio between the
+/// increase of the idle time and the increase of the
+/// [tick count](crate::time::get_tick).
+pub fn get_idle_time_ms() -> u32 {
+    IDLE_MS.load(Ordering::Relaxed)
+}
+
+/// Return the number of milliseconds the idle task has run, including the
+/// ongoing run if the idle task is on the CPU.
+pub(super) fn get_idle_time_ms_until_now() -> u32 {
+    let idle_ms = IDLE_MS.load(Ordering::Relaxed);
+    if !IDLE_RUNNING.load(Ordering::Relaxed) {
+        return idle_ms;
+    }
+    let ongoing_ms = time::get_tick().wrapping_sub(IDLE_BEGIN_TICK.load(Ordering::Relaxed));
+    idle_ms.wrapping_add(ongoing_ms)
+}
+
 /// The idle task. Just endlessly yield itself so that whenever a task becomes
 /// ready, that task will be chosen by the scheduler to run.
 pub(super) unsafe extern "C" fn idle_task() -> ! {
@@ -30,8 +126,11 @@ pub(super) unsafe extern "C" fn idle_task() -> ! {
     // a context switch to let the main task run.
     context_switch::yield_current_task();
 
-    // If nothing to do, enter low power state.
+    // If nothing to do, run the hook or enter low power state.
     loop {
-        cortex_m::asm::wfe();
+        match IDLE_HOOK.load() {
+            Some(hook) => hook(),
+            None => cortex_m::asm::wfe(),
+        }
     }
 }
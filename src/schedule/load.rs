@@ -0,0 +1,103 @@
+//! System-wide CPU load derived from the time the idle task runs.
+
+use super::{idle, scheduler::Scheduler};
+use crate::time;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// The number of ticks between two samples of the load.
+const SAMPLE_TICKS: u32 = 100;
+
+/// The number of samples in the short and the long averaging window,
+/// i.e., one and ten seconds.
+const SHORT_WINDOW_SAMPLES: i32 = (1000 / SAMPLE_TICKS) as i32;
+const LONG_WINDOW_SAMPLES: i32 = (10_000 / SAMPLE_TICKS) as i32;
+
+/// The averages are kept in permille scaled up by 256, so that the long
+/// average still moves when each sample changes it by less than a permille.
+const SCALE_SHIFT: u32 = 8;
+
+/// The tick and the idle time when the load was last sampled.
+static LAST_SAMPLE_TICK: AtomicU32 = AtomicU32::new(0);
+static LAST_IDLE_MS: AtomicU32 = AtomicU32::new(0);
+
+/// The exponentially weighted averages of the load over the short and the
+/// long window.
+static SHORT_AVG: AtomicU32 = AtomicU32::new(0);
+static LONG_AVG: AtomicU32 = AtomicU32::new(0);
+
+/// The recent CPU utilization, returned by [`cpu_load`]. The load is given in
+/// permille, i.e., from 0 when the CPU is idle to 1000 when it is fully
+/// busy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuLoad {
+    short: u16,
+    long: u16,
+}
+
+impl CpuLoad {
+    /// The load averaged over about the last second.
+    pub fn last_second(&self) -> u16 {
+        self.short
+    }
+
+    /// The load averaged over about the last ten seconds.
+    pub fn last_ten_seconds(&self) -> u16 {
+        self.long
+    }
+}
+
+/// Return the recent CPU utilization, e.g., to defer optional work while the
+/// load is above a threshold. Each average is exponentially weighted, so
+/// recent load counts more than the load earlier in the window. The time not
+/// spent in the idle task counts as load, including the time spent in
+/// interrupt handlers. The averages are updated every 100 ticks and start at
+/// zero when the scheduler starts.
+///
+/// Unlike [`LoadInspector`](crate::debug::cpu_load::LoadInspector), it needs
+/// no microsecond clock, and accounts the idle time with the precision of a
+/// millisecond.
+///
+/// # Example
+/// ```rust
+/// if schedule::cpu_load().last_second() < 800 {
+///     refresh_statistics();
+/// }
+/// ```
+pub fn cpu_load() -> CpuLoad {
+    CpuLoad {
+        short: (SHORT_AVG.load(Ordering::Relaxed) >> SCALE_SHIFT) as u16,
+        long: (LONG_AVG.load(Ordering::Relaxed) >> SCALE_SHIFT) as u16,
+    }
+}
+
+/// Fold the load since the last sample into the averages once a sample
+/// period has passed. Called by the SysTick handler.
+pub(crate) fn sample_allow_isr() {
+    if !Scheduler::has_started() {
+        return;
+    }
+
+    let tick = time::get_tick();
+    let elapsed = tick.wrapping_sub(LAST_SAMPLE_TICK.load(Ordering::Relaxed));
+    if elapsed < SAMPLE_TICKS {
+        return;
+    }
+
+    let idle_ms = idle::get_idle_time_ms_until_now();
+    let idle_delta = idle_ms.wrapping_sub(LAST_IDLE_MS.load(Ordering::Relaxed));
+    LAST_SAMPLE_TICK.store(tick, Ordering::Relaxed);
+    LAST_IDLE_MS.store(idle_ms, Ordering::Relaxed);
+
+    let busy = 1000u32.saturating_sub(idle_delta.saturating_mul(1000) / elapsed);
+    let sample = (busy << SCALE_SHIFT) as i32;
+    update_average(&SHORT_AVG, sample, SHORT_WINDOW_SAMPLES);
+    update_average(&LONG_AVG, sample, LONG_WINDOW_SAMPLES);
+}
+
+/// Move the average towards the sample by the reciprocal of the number of
+/// samples in the window.
+fn update_average(avg: &AtomicU32, sample: i32, window_samples: i32) {
+    let prev = avg.load(Ordering::Relaxed) as i32;
+    let next = prev + (sample - prev) / window_samples;
+    avg.store(next as u32, Ordering::Relaxed);
+}
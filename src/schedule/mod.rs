@@ -1,3 +1,57 @@
+//! Control over task scheduling.
+//!
+//! [`preemption_lock`] creates a region where the calling task is not
+//! switched out of the CPU. Unlike masking interrupts, interrupts are still
+//! served inside the region, so it does not add interrupt latency. A task
+//! woken up by an interrupt handler meanwhile runs after the region ends if
+//! it has a higher priority. This is the mechanism the kernel itself uses to
+//! protect its data structures shared among tasks, see the soft locks in
+//! [`sync`](crate::sync).
+//!
+//! [`cpu_load`] reports the recent utilization of the CPU, measured by the
+//! time not spent in the idle task.
+//!
+//! # Example
+//! ```rust
+//! {
+//!     let _lock = schedule::preemption_lock();
+//!     // No other task observes the two updates separately.
+//!     CONFIG_A.store(a, Ordering::SeqCst);
+//!     CONFIG_B.store(b, Ordering::SeqCst);
+//! }
+//! ```
+
 pub(crate) mod current;
 pub(crate) mod idle;
+pub(crate) mod load;
+mod ready_lists;
 pub(crate) mod scheduler;
+
+pub use load::{cpu_load, CpuLoad};
+
+use crate::unrecoverable;
+use scheduler::{SchedSuspendGuard, Scheduler};
+
+/// The guard returned by [`preemption_lock`]. The calling task can be
+/// switched out again when the guard is dropped.
+pub struct PreemptionLock {
+    _guard: SchedSuspendGuard,
+}
+
+/// Prevent the calling task from being switched out of the CPU until the
+/// returned guard is dropped. The locks can be nested. See the [module-level
+/// documentation](self).
+///
+/// The region should be short, since it delays all other tasks. See also
+/// [`task::run_uninterrupted`](crate::task::run_uninterrupted) for the same
+/// region in closure form.
+///
+/// NOTE: *must not* block, sleep or yield while holding the lock. *Must not*
+/// call this function in ISR context.
+pub fn preemption_lock() -> PreemptionLock {
+    unrecoverable::die_if_in_isr();
+
+    PreemptionLock {
+        _guard: Scheduler::suspend(),
+    }
+}
@@ -0,0 +1,166 @@
+//! Ready tasks kept in one FIFO list per priority level.
+//!
+//! A bitmap records which levels have ready tasks. The highest priority level
+//! with ready tasks is found by counting the leading zeros of the bitmap,
+//! which is a single `CLZ` instruction on Cortex-M, so picking the next task
+//! takes constant time regardless of the number of ready tasks.
+//!
+//! Each task records the level of the list it is linked in, so that it can
+//! be removed from the list, e.g., when suspended, without searching for it.
+//! A task whose priority changes while it is ready is moved to the list of
+//! its new priority, see [`Scheduler::requeue_task`](super::scheduler::Scheduler::requeue_task).
+//! If the move is pended, all ready tasks are checked at once when the
+//! pended operations run.
+
+use crate::{
+    config,
+    task::{Task, TaskListAdapter, TaskListInterfaces},
+};
+use alloc::sync::Arc;
+use intrusive_collections::LinkedList;
+use static_assertions::const_assert;
+
+// Each priority level needs a bit in the bitmap.
+const_assert!(config::TASK_PRIORITY_LEVELS <= 32);
+
+const EMPTY_LIST: LinkedList<TaskListAdapter> = LinkedList::new(TaskListAdapter::NEW);
+
+/// Ready tasks linked into the list of their priority level.
+pub(super) struct ReadyLists {
+    /// The ready tasks of each priority level, in the order they became
+    /// ready.
+    lists: [LinkedList<TaskListAdapter>; config::TASK_PRIORITY_LEVELS as usize],
+    /// Bit `31 - level` is set if and only if the list of the level is not
+    /// empty, so that the number of leading zeros is the highest priority
+    /// level with ready tasks.
+    bitmap: u32,
+}
+
+/// Return the bit representing the level in the bitmap.
+const fn level_bit(level: u8) -> u32 {
+    1 << (31 - level as u32)
+}
+
+impl ReadyLists {
+    pub(super) const fn new() -> Self {
+        Self {
+            lists: [EMPTY_LIST; config::TASK_PRIORITY_LEVELS as usize],
+            bitmap: 0,
+        }
+    }
+
+    /// Put the task behind the other ready tasks of its priority.
+    pub(super) fn push_back(&mut self, task: Arc<Task>) {
+        let level = task.get_priority().effective_priority();
+        task.set_ready_level(Some(level));
+        self.lists[level as usize].push_back(task);
+        self.bitmap |= level_bit(level);
+    }
+
+    /// Put the task before the other ready tasks of its priority.
+    pub(super) fn push_front(&mut self, task: Arc<Task>) {
+        let level = task.get_priority().effective_priority();
+        task.set_ready_level(Some(level));
+        self.lists[level as usize].push_front(task);
+        self.bitmap |= level_bit(level);
+    }
+
+    /// Pop out the first task of the highest priority level with ready tasks.
+    /// At the [EDF priority](config::EDF_TASK_PRIORITY), the task with the
+    /// earliest deadline is chosen instead, which takes a scan of that level
+    /// only. Return `None` if no task is ready.
+    pub(super) fn pop_highest_priority(&mut self) -> Option<Arc<Task>> {
+        if self.bitmap == 0 {
+            return None;
+        }
+
+        let level = self.bitmap.leading_zeros() as u8;
+        let list = &mut self.lists[level as usize];
+        let task = if level == config::EDF_TASK_PRIORITY {
+            list.pop_highest_priority()
+        } else {
+            list.pop_front()
+        }?;
+
+        if list.is_empty() {
+            self.bitmap &= !level_bit(level);
+        }
+        task.set_ready_level(None);
+        Some(task)
+    }
+
+    /// Remove the given task from its ready list. If the task is ready,
+    /// return `Some`, otherwise `None`.
+    pub(super) fn remove_task(&mut self, task: &Task) -> Option<Arc<Task>> {
+        let level = task.get_ready_level()?;
+        let list = &mut self.lists[level as usize];
+
+        // Safety: The task is linked in the list of the level it recorded,
+        // which is cleared whenever it is unlinked.
+        let task = unsafe { list.cursor_mut_from_ptr(task) }.remove()?;
+
+        if list.is_empty() {
+            self.bitmap &= !level_bit(level);
+        }
+        task.set_ready_level(None);
+        Some(task)
+    }
+
+    /// Move the task to the back of the list of its current priority if it
+    /// is ready and its priority has changed since it was linked.
+    pub(super) fn requeue(&mut self, task: &Task) {
+        let Some(level) = task.get_ready_level() else {
+            return;
+        };
+        if level == task.get_priority().effective_priority() {
+            return;
+        }
+        if let Some(task) = self.remove_task(task) {
+            self.push_back(task);
+        }
+    }
+
+    /// Move each task whose priority has changed since it was linked to the
+    /// back of the list of its current priority, keeping their order.
+    pub(super) fn requeue_all(&mut self) {
+        let mut moved = LinkedList::new(TaskListAdapter::NEW);
+        for level in 0..config::TASK_PRIORITY_LEVELS {
+            let list = &mut self.lists[level as usize];
+            let mut cursor = list.front_mut();
+            while let Some(task) = cursor.get() {
+                if task.get_priority().effective_priority() == level {
+                    cursor.move_next();
+                } else if let Some(task) = cursor.remove() {
+                    task.set_ready_level(None);
+                    moved.push_back(task);
+                }
+            }
+            if list.is_empty() {
+                self.bitmap &= !level_bit(level);
+            }
+        }
+        while let Some(task) = moved.pop_front() {
+            self.push_back(task);
+        }
+    }
+
+    /// Move the task to the front of the list of its priority if it is
+    /// ready.
+    pub(super) fn move_to_front(&mut self, task: &Task) {
+        if let Some(task) = self.remove_task(task) {
+            self.push_front(task);
+        }
+    }
+
+    /// Return if any ready task should run before the given task.
+    pub(super) fn has_higher_priority_than(&self, task: &Task) -> bool {
+        let level = task.get_priority().effective_priority();
+        if self.bitmap & !(u32::MAX >> level) != 0 {
+            return true;
+        }
+        level == config::EDF_TASK_PRIORITY
+            && self.lists[level as usize]
+                .iter()
+                .any(|candidate_task| candidate_task.precedes(task))
+    }
+}
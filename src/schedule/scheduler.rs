@@ -1,19 +1,22 @@
-use super::{current, idle};
+use super::{current, idle, ready_lists::ReadyLists};
 use crate::{
     config,
     interrupt::context_switch,
     sync::{Access, AllowPendOp, Holdable, RefCellSchedSafe, RunPendedOp, SoftLock, Spin},
-    task::{Task, TaskListAdapter, TaskListInterfaces, TaskState},
+    task::{self, Task, TaskListAdapter, TaskListInterfaces, TaskState},
     unrecoverable::{self, Lethal},
 };
 use alloc::sync::Arc;
 use core::{
     arch::asm,
-    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
 };
 use heapless::mpmc::MpMcQueue;
 use intrusive_collections::LinkedList;
 
+#[cfg(feature = "sched_events")]
+use crate::debug::sched_events::SchedEventKind;
+
 /// A ready task queue. Ready tasks will be popped out with respect to
 /// their priorities.
 type ReadyQueue = RefCellSchedSafe<SoftLock<Inner>>;
@@ -21,23 +24,32 @@ type ReadyQueue = RefCellSchedSafe<SoftLock<Inner>>;
 /// The inner content of a ready task queue.
 struct Inner {
     /// The lock-free circular buffer holding `Arc<Task>`s which are not yet
-    /// linked into the ready linked list.
+    /// linked into the ready lists.
     insert_buffer: InsertBuffer,
-    /// Ready tasks linked together as a linked list, allowing us to remove the
-    /// one with the highest priority. This linked list is *not* sorted.
-    ready_linked_list: Spin<LinkedList<TaskListAdapter>>,
+    /// Ready tasks linked into one list per priority level, allowing us to
+    /// remove the one with the highest priority in constant time.
+    ready_lists: Spin<ReadyLists>,
+    /// Suspended tasks that would otherwise be ready, linked back into the
+    /// ready lists when resumed.
+    suspended_linked_list: Spin<LinkedList<TaskListAdapter>>,
+    /// Whether the priority of a ready task has changed while the queue was
+    /// under contention, so that the task is not yet moved to the ready list
+    /// of its new priority.
+    requeue_pending: AtomicBool,
 }
 
 /// A lock-free circular buffer holding `Arc<Task>`. When the ready queue is
 /// under contention, new ready tasks will be placed into this buffer and will
-/// be linked into the ready linked list at a later time.
+/// be linked into the ready lists at a later time.
 type InsertBuffer = MpMcQueue<Arc<Task>, { config::MAX_TASK_NUMBER }>;
 
 impl Inner {
     const fn new() -> Self {
         Self {
             insert_buffer: InsertBuffer::new(),
-            ready_linked_list: Spin::new(LinkedList::new(TaskListAdapter::NEW)),
+            ready_lists: Spin::new(ReadyLists::new()),
+            suspended_linked_list: Spin::new(LinkedList::new(TaskListAdapter::NEW)),
+            requeue_pending: AtomicBool::new(false),
         }
     }
 }
@@ -45,33 +57,57 @@ impl Inner {
 /// Representing full access to the queue.
 struct InnerFullAccessor<'a> {
     insert_buffer: &'a InsertBuffer,
-    ready_linked_list: &'a Spin<LinkedList<TaskListAdapter>>,
+    ready_lists: &'a Spin<ReadyLists>,
+    suspended_linked_list: &'a Spin<LinkedList<TaskListAdapter>>,
+    requeue_pending: &'a AtomicBool,
 }
 
 /// Representing pend-only access to the queue. Using this accessor one can only
-/// enqueue a task struct `Arc` into the circular buffer. Later the buffer will
-/// be consumed and get the tasks linked into the linked list.
+/// enqueue a task struct `Arc` into the circular buffer, or request ready tasks
+/// to be moved after their priority changed. Later the buffer will be consumed
+/// and get the tasks linked into the ready lists.
 struct InnerPendAccessor<'a> {
     insert_buffer: &'a InsertBuffer,
+    requeue_pending: &'a AtomicBool,
 }
 
 /// If the insert buffer is not empty, we should pop these tasks out and get
-/// them linked.
+/// them linked. If a requeue is pending, move the ready tasks whose priority
+/// has changed to the lists of their new priority.
 impl<'a> RunPendedOp for InnerFullAccessor<'a> {
     fn run_pended_op(&mut self) {
         current::with_cur_task(|cur_task| {
-            let mut locked_list = self.ready_linked_list.lock_now_or_die();
+            let mut locked_lists = self.ready_lists.lock_now_or_die();
+            if self.requeue_pending.swap(false, Ordering::SeqCst) {
+                locked_lists.requeue_all();
+                if config::ALLOW_TASK_PREEMPTION && locked_lists.has_higher_priority_than(cur_task)
+                {
+                    PENDING_CTXT_SWITCH.store(true, Ordering::SeqCst);
+                }
+            }
             while let Some(task) = self.insert_buffer.dequeue() {
+                if task.is_suspended() {
+                    self.link_suspended(task);
+                    continue;
+                }
                 if task.should_preempt(cur_task) {
                     PENDING_CTXT_SWITCH.store(true, Ordering::SeqCst);
                 }
                 task.set_state(TaskState::Ready);
-                locked_list.push_back(task);
+                locked_lists.push_back(task);
             }
         })
     }
 }
 
+impl<'a> InnerFullAccessor<'a> {
+    /// Keep a task that would otherwise be ready off the ready lists.
+    fn link_suspended(&self, task: Arc<Task>) {
+        task.set_state(TaskState::Suspended);
+        self.suspended_linked_list.lock_now_or_die().push_back(task);
+    }
+}
+
 /// Bind the accessor types.
 impl<'a> AllowPendOp<'a> for Inner {
     type FullAccessor = InnerFullAccessor<'a>;
@@ -79,12 +115,15 @@ impl<'a> AllowPendOp<'a> for Inner {
     fn full_access(&'a self) -> Self::FullAccessor {
         InnerFullAccessor {
             insert_buffer: &self.insert_buffer,
-            ready_linked_list: &self.ready_linked_list,
+            ready_lists: &self.ready_lists,
+            suspended_linked_list: &self.suspended_linked_list,
+            requeue_pending: &self.requeue_pending,
         }
     }
     fn pend_only_access(&'a self) -> Self::PendOnlyAccessor {
         InnerPendAccessor {
             insert_buffer: &self.insert_buffer,
+            requeue_pending: &self.requeue_pending,
         }
     }
 }
@@ -92,6 +131,12 @@ impl<'a> AllowPendOp<'a> for Inner {
 /// The ready task queue.
 static READY_TASK_QUEUE: ReadyQueue = RefCellSchedSafe::new(SoftLock::new(Inner::new()));
 
+/// Return the contention statistics of the ready task queue.
+#[cfg(feature = "soft_lock_stats")]
+pub(crate) fn ready_queue_soft_lock_stats() -> crate::debug::soft_lock_stats::SoftLockStats {
+    READY_TASK_QUEUE.with_suspended_scheduler(|queue, _| queue.stats())
+}
+
 /// The number of existing tasks.
 static EXIST_TASK_NUM: AtomicUsize = AtomicUsize::new(0);
 
@@ -108,6 +153,10 @@ static SUSPEND_CNT: AtomicUsize = AtomicUsize::new(0);
 /// Whether a context switch should be performed after the scheduler is resumed.
 static PENDING_CTXT_SWITCH: AtomicBool = AtomicBool::new(false);
 
+/// The number of ticks the current task has run since it was switched on to
+/// the CPU. Only counted with round robin time slicing enabled.
+static SLICE_TICKS: AtomicU32 = AtomicU32::new(0);
+
 /// The scheduler is a singleton in the system. Logically, the components of
 /// the scheduler are defined by the static variables in the
 /// [scheduler](crate::schedule::scheduler) module.
@@ -126,7 +175,9 @@ impl Scheduler {
         let idle_stk_bound = idle_task.get_stk_bound();
 
         // Set the idle task as the currently running task.
-        current::update_cur_task(Arc::new(idle_task));
+        let idle_task = Arc::new(idle_task);
+        task::register(&idle_task);
+        current::update_cur_task(idle_task);
 
         CUR_TASK_IDLE.store(true, Ordering::SeqCst);
 
@@ -157,13 +208,16 @@ impl Scheduler {
                 // upon SVC the CPU will push a trap frame with floating point
                 // registers. Just enabling FPU is NOT enough for the CPU to
                 // push floating point registers upon exception.
+                ".if {fp_ctxt}",
                 "vmov.f32 s0, s0",
+                ".endif",
                 // With the stack pointer and boundary updated, now the code
                 // runs in the idle task's context. Jump to the idle task entry.
                 "b {idle_task}",
                 idle_task = sym idle::idle_task,
                 tls_mem_addr = const config::__TLS_MEM_ADDR,
                 kern_stk_bottom = const config::_CONTIGUOUS_STACK_BOTTOM,
+                fp_ctxt = const config::FP_CONTEXT_SAVE as u32,
                 in("r0") stack_bottom,
                 in("r1") idle_stk_bound,
                 options(noreturn)
@@ -184,16 +238,32 @@ impl Scheduler {
 
         READY_TASK_QUEUE.with_suspended_scheduler(|queue, sched_guard| {
             queue.must_with_full_access(|full_access| {
-                let mut locked_list = full_access.ready_linked_list.lock_now_or_die();
+                let mut locked_lists = full_access.ready_lists.lock_now_or_die();
 
                 // Clean up for the current task.
                 current::with_cur_task_arc_explicit_sched_suspend(sched_guard, |cur_task| {
+                    #[cfg(feature = "starvation_monitor")]
+                    crate::task::starvation::record_switch_out(&cur_task);
+
                     match cur_task.get_state() {
                         // Put the current task back to the ready queue only if the
                         // task is in `Running` state.
+                        TaskState::Running if cur_task.is_suspended() => {
+                            full_access.link_suspended(cur_task);
+                        }
+                        // A cooperative task preempted by a higher priority task
+                        // goes to the front, so that it is not overtaken by the
+                        // other tasks of its priority.
+                        TaskState::Running
+                            if cur_task.is_cooperative()
+                                && locked_lists.has_higher_priority_than(&cur_task) =>
+                        {
+                            cur_task.set_state(TaskState::Ready);
+                            locked_lists.push_front(cur_task);
+                        }
                         TaskState::Running => {
                             cur_task.set_state(TaskState::Ready);
-                            locked_list.push_back(cur_task);
+                            locked_lists.push_back(cur_task);
                         }
                         // A `Blocked` task should have been put to a waiting queue and
                         // maintain a positive `Arc` reference count there.
@@ -205,7 +275,16 @@ impl Scheduler {
                         // In both cases, we will not put the task back to the ready
                         // queue and will later use `current::set_cur_task` to overwrite
                         // the current task reference maintained by the `current` module.
-                        TaskState::Blocked | TaskState::Destructing => {}
+                        //
+                        // A `Suspended` task is already linked in the suspended list.
+                        TaskState::Blocked => {
+                            #[cfg(feature = "sched_events")]
+                            crate::debug::sched_events::record(
+                                SchedEventKind::Block,
+                                Some(cur_task.get_uid()),
+                            );
+                        }
+                        TaskState::Suspended | TaskState::Destructing => {}
                         // The current task can be set into the `Ready` state under a
                         // rare circumstance: The task was first set to `Blocked` state
                         // and was pushed to a sleeping or waiting queue. But before the
@@ -224,11 +303,19 @@ impl Scheduler {
 
                 // Pick the next task based on the priority. An idle task
                 // guarantees that the ready queue will always be non-empty.
-                let next_task = locked_list.pop_highest_priority().unwrap_or_die();
+                let next_task = locked_lists.pop_highest_priority().unwrap_or_die();
                 next_task.set_state(TaskState::Running);
 
                 let next_idle = next_task.is_idle();
 
+                #[cfg(feature = "trace")]
+                crate::debug::trace::record_switch(next_task.get_id());
+                #[cfg(feature = "sched_events")]
+                crate::debug::sched_events::record(
+                    SchedEventKind::Switch,
+                    Some(next_task.get_uid()),
+                );
+
                 // Load if the current task is the idle task and also set it to
                 // the new value.
                 let was_idle = CUR_TASK_IDLE.swap(next_idle, Ordering::SeqCst);
@@ -239,6 +326,7 @@ impl Scheduler {
 
                     // When the idle task is switched out of CPU.
                     if was_idle {
+                        idle::record_idle_end();
                         for callback in locked_callbacks.iter() {
                             callback.idle_end();
                         }
@@ -246,6 +334,7 @@ impl Scheduler {
 
                     // When the idle task is switched on to the CPU.
                     if next_idle {
+                        idle::record_idle_begin();
                         for callback in locked_callbacks.iter() {
                             callback.idle_begin();
                         }
@@ -253,20 +342,72 @@ impl Scheduler {
                 }
 
                 // Set the chosen task to be current.
-                current::update_cur_task(next_task);
+                let prev_task = current::update_cur_task(next_task);
+
+                // Let the reaper task free a terminated task rather than
+                // freeing it here on the context switch path.
+                #[cfg(feature = "reaper")]
+                if let Some(prev_task) = prev_task {
+                    if prev_task.get_state() == TaskState::Destructing {
+                        crate::task::reaper::defer_reclamation(prev_task);
+                    }
+                }
+                #[cfg(not(feature = "reaper"))]
+                drop(prev_task);
 
                 // Clear the context switch request flag because we just
                 // performed one.
                 PENDING_CTXT_SWITCH.store(false, Ordering::SeqCst);
+
+                // The chosen task starts a new time slice.
+                SLICE_TICKS.store(0, Ordering::SeqCst);
             })
         })
     }
 
+    /// Count a tick against the time slice of the current task. When the task
+    /// has run for the [quantum](config::ROUND_ROBIN_QUANTUM_TICKS), request a
+    /// context switch, which puts the task behind the other ready tasks of its
+    /// priority. Called by the SysTick handler.
+    pub(crate) fn tick_time_slice_allow_isr() {
+        if config::ROUND_ROBIN_QUANTUM_TICKS == 0
+            || !config::ALLOW_TASK_PREEMPTION
+            || !Self::has_started()
+            || CUR_TASK_IDLE.load(Ordering::SeqCst)
+        {
+            return;
+        }
+
+        let ticks = SLICE_TICKS.fetch_add(1, Ordering::SeqCst) + 1;
+        if ticks < config::ROUND_ROBIN_QUANTUM_TICKS {
+            return;
+        }
+
+        // Cooperative tasks are not time sliced.
+        if current::with_cur_task(|cur_task| cur_task.is_cooperative()) {
+            return;
+        }
+
+        PENDING_CTXT_SWITCH.store(true, Ordering::SeqCst);
+
+        // Tail chain a PendSV so that the next task runs from this tick on.
+        // If the scheduler is suspended, the context switch is instead
+        // performed when it is resumed.
+        if !Self::is_suspended() {
+            cortex_m::peripheral::SCB::set_pendsv()
+        }
+    }
+
     /// Return if the scheduler has been started.
     pub(crate) fn has_started() -> bool {
         STARTED.load(Ordering::SeqCst)
     }
 
+    /// Return the number of existing tasks, including the idle task.
+    pub(crate) fn task_count() -> usize {
+        EXIST_TASK_NUM.load(Ordering::SeqCst)
+    }
+
     /// Check whether the existing task number has already reached the allowed
     /// maximum ([`MAX_TASK_NUMBER`](config::MAX_TASK_NUMBER)). If not, return
     /// a [`TaskQuota`] which is a token allowing new task creation.
@@ -276,6 +417,10 @@ impl Scheduler {
 
     /// Insert a task to the scheduler's ready queue.
     pub(crate) fn accept_task(task: Arc<Task>) {
+        #[cfg(feature = "sched_events")]
+        crate::debug::sched_events::record(SchedEventKind::Wakeup, Some(task.get_uid()));
+        #[cfg(feature = "starvation_monitor")]
+        task.start_waiting();
         Self::insert_task_to_ready_queue(task)
     }
 
@@ -284,8 +429,13 @@ impl Scheduler {
         READY_TASK_QUEUE.with_suspended_scheduler(|queue, sched_guard| {
             queue.with_access(|access| match access {
                 // The queue is not under contention. Directly put the task to the
-                // linked list.
+                // ready lists.
                 Access::Full { full_access } => {
+                    if task.is_suspended() {
+                        full_access.link_suspended(task);
+                        return;
+                    }
+
                     // Request a context switch if the incoming ready task has a
                     // higher priority than the current task. Check it only when
                     // the scheduler has started otherwise there will be no current
@@ -298,15 +448,15 @@ impl Scheduler {
                         });
                     }
 
-                    // Put the ready task to the linked list.
+                    // Put the ready task to the ready lists.
                     task.set_state(TaskState::Ready);
-                    let mut locked_list = full_access.ready_linked_list.lock_now_or_die();
-                    locked_list.push_back(task);
+                    let mut locked_lists = full_access.ready_lists.lock_now_or_die();
+                    locked_lists.push_back(task);
                 }
                 // The queue is under contention. The current execution context, which
                 // must be an ISR, preempted another context that is holding the full
                 // access. Place the task in the lock-free buffer. The full access
-                // holder will later put it back to the linked list.
+                // holder will later put it to the ready lists.
                 Access::PendOnly { pend_access } => {
                     pend_access.insert_buffer.enqueue(task).unwrap_or_die();
                 }
@@ -314,10 +464,106 @@ impl Scheduler {
         });
     }
 
+    /// Suspend a task other than the idle task. The task is taken off the
+    /// ready queue if it is ready, or kept off it when it is woken up if it
+    /// is blocked. A context switch is requested if the current task is
+    /// suspended.
+    pub(crate) fn suspend_task(task: &Task) {
+        READY_TASK_QUEUE.with_suspended_scheduler(|queue, _| {
+            queue.must_with_full_access(|full_access| {
+                task.set_suspended(true);
+                match task.get_state() {
+                    TaskState::Ready => {
+                        let mut locked_lists = full_access.ready_lists.lock_now_or_die();
+                        if let Some(task) = locked_lists.remove_task(task) {
+                            drop(locked_lists);
+                            full_access.link_suspended(task);
+                        }
+                    }
+                    // The current task is linked into the suspended list
+                    // when it is switched out.
+                    TaskState::Running => PENDING_CTXT_SWITCH.store(true, Ordering::SeqCst),
+                    _ => {}
+                }
+            })
+        });
+    }
+
+    /// Resume a suspended task. The task is put back to the ready queue if it
+    /// was ready when or after it was suspended.
+    pub(crate) fn resume_task(task: &Task) {
+        READY_TASK_QUEUE.with_suspended_scheduler(|queue, sched_guard| {
+            queue.must_with_full_access(|full_access| {
+                task.set_suspended(false);
+                let mut locked_suspended = full_access.suspended_linked_list.lock_now_or_die();
+                if let Some(task) = locked_suspended.remove_task(task) {
+                    drop(locked_suspended);
+                    current::with_cur_task_explicit_sched_suspend(sched_guard, |cur_task| {
+                        if task.should_preempt(cur_task) {
+                            PENDING_CTXT_SWITCH.store(true, Ordering::SeqCst);
+                        }
+                    });
+                    task.set_state(TaskState::Ready);
+                    full_access.ready_lists.lock_now_or_die().push_back(task);
+                }
+            })
+        });
+    }
+
+    /// Move a ready task to the ready list of its new priority after its
+    /// priority has changed. A task not in the ready lists is linked into the
+    /// list of its priority at the time it becomes ready.
+    ///
+    /// If the queue is under contention, e.g., when called from an ISR or
+    /// while the ready lists are being accessed, the move is pended. All
+    /// ready tasks whose priority has changed are moved when the holder of
+    /// the full access finishes.
+    pub(crate) fn requeue_task(task: &Task) {
+        READY_TASK_QUEUE.with_suspended_scheduler(|queue, sched_guard| {
+            queue.with_access(|access| match access {
+                Access::Full { full_access } => {
+                    full_access.ready_lists.lock_now_or_die().requeue(task);
+
+                    // Request a context switch if the task now has a higher
+                    // priority than the current task, e.g., when an ISR
+                    // raised its priority.
+                    current::with_cur_task_explicit_sched_suspend(sched_guard, |cur_task| {
+                        if task.should_preempt(cur_task) {
+                            PENDING_CTXT_SWITCH.store(true, Ordering::SeqCst);
+                        }
+                    });
+                }
+                Access::PendOnly { pend_access } => {
+                    pend_access.requeue_pending.store(true, Ordering::SeqCst);
+                }
+            })
+        });
+    }
+
+    /// Move a ready task of the same priority as the current task to the
+    /// front of its ready list, so that it runs next when the current task
+    /// yields. Do nothing for other tasks.
+    pub(crate) fn move_to_front_if_peer(task: &Task) {
+        READY_TASK_QUEUE.with_suspended_scheduler(|queue, sched_guard| {
+            queue.must_with_full_access(|full_access| {
+                let is_peer =
+                    current::with_cur_task_explicit_sched_suspend(sched_guard, |cur_task| {
+                        task.get_priority() == cur_task.get_priority()
+                    });
+                if is_peer {
+                    full_access
+                        .ready_lists
+                        .lock_now_or_die()
+                        .move_to_front(task);
+                }
+            })
+        });
+    }
+
     /// Prevent any context switch while the returned guard type is not dropped.
     pub(crate) fn suspend() -> SchedSuspendGuard {
         SUSPEND_CNT.fetch_add(1, Ordering::SeqCst);
-        SchedSuspendGuard
+        SchedSuspendGuard(())
     }
 
     /// Resume context switch if all suspension guard have been dropped, and
@@ -353,6 +599,9 @@ impl Scheduler {
         // the task struct upon a later context switch.
         current::with_cur_task(|cur_task| cur_task.set_state(TaskState::Destructing));
 
+        #[cfg(feature = "reaper")]
+        crate::task::reaper::notify_termination();
+
         // Tail chain a PendSV to perform a context switch.
         cortex_m::peripheral::SCB::set_pendsv()
     }
@@ -360,7 +609,11 @@ impl Scheduler {
 
 /// The guard type returned when suspending the scheduler. The scheduler will
 /// be resumed when the guard is dropped.
-pub(crate) struct SchedSuspendGuard;
+///
+/// It is passed to the closure run by
+/// [`RefCellSchedSafe::with_suspended_scheduler`](crate::sync::RefCellSchedSafe::with_suspended_scheduler)
+/// and cannot be constructed outside of the kernel.
+pub struct SchedSuspendGuard(());
 
 impl Drop for SchedSuspendGuard {
     fn drop(&mut self) {
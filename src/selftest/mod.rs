@@ -0,0 +1,290 @@
+//! Periodic integrity self-tests run in the background, enabled by the
+//! `selftest` feature.
+//!
+//! Products following IEC 60730 class B or similar standards must check the
+//! integrity of their RAM, flash, and peripherals periodically while the
+//! application runs. Applications [`register`] [`SelfTest`]s, and the
+//! self-test task started by [`start`] runs them one after another at the
+//! lowest priority above the idle task, so they only consume idle time. Each
+//! test is run incrementally in bounded [`step`](SelfTest::step)s, and the
+//! task yields between steps, so a long test never delays the tasks sharing
+//! its priority.
+//!
+//! After all tests have run, the task sleeps for
+//! [`SELFTEST_PERIOD_MS`](config::SELFTEST_PERIOD_MS) before running them
+//! again. Each failure is reported as a [`Failure`] through the channel
+//! given to [`start`], which a supervising task drains to react, e.g., by
+//! entering a safe state.
+//!
+//! The following tests are provided:
+//!
+//! - [`RamPattern`]: writes and verifies bit patterns in a free heap region.
+//! - [`FlashCrc`]: checks the CRC-32 of the `.text` section.
+//! - [`Ping`]: runs a function checking a peripheral.
+//!
+//! # Example
+//! ```rust
+//! selftest::register(Box::new(RamPattern::new(256)));
+//! selftest::register(Box::new(FlashCrc::new(None)));
+//! selftest::register(Box::new(Ping::new("imu", || imu_who_am_i() == 0x6A)));
+//!
+//! let (producer, consumer) = sync::create_channel::<Failure, 4>();
+//! selftest::start(producer).unwrap();
+//!
+//! // In the supervising task.
+//! let failure = consumer.consume();
+//! enter_safe_state(failure.name);
+//! ```
+
+use crate::{
+    config,
+    crc::crc32_update,
+    sync::{Mutex, Producer},
+    task::{self, TaskBuildError},
+    time,
+};
+use alloc::{boxed::Box, vec::Vec};
+
+/// The outcome of a step of a self-test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    /// The test has more steps to run.
+    Pending,
+    /// The test has completed and passed. It will start from the beginning
+    /// in the next period.
+    Passed,
+    /// The test has completed and failed with the test specific code. It
+    /// will start from the beginning in the next period.
+    Failed(u32),
+}
+
+/// A self-test run incrementally by the self-test task.
+pub trait SelfTest: Send {
+    /// The name identifying the test in a [`Failure`].
+    fn name(&self) -> &'static str;
+
+    /// Run the next step of the test. A step should take bounded and short
+    /// time, e.g., no more than one tick.
+    fn step(&mut self) -> Step;
+}
+
+/// A failure of a self-test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Failure {
+    /// The name of the failed test.
+    pub name: &'static str,
+    /// The test specific code, see [`Step::Failed`].
+    pub code: u32,
+}
+
+static REGISTRY: Mutex<Vec<Box<dyn SelfTest>>> = Mutex::new(Vec::new());
+
+/// Register a self-test. It runs from the next period on.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn register(test: Box<dyn SelfTest>) {
+    REGISTRY.lock().push(test);
+}
+
+/// Start the self-test task, which reports failures through the channel.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn start<const N: usize>(failures: Producer<Failure, N>) -> Result<(), TaskBuildError> {
+    task::build()
+        .set_id(config::SELFTEST_TASK_ID)
+        .set_name("selftest")
+        .set_priority(config::SELFTEST_TASK_PRIORITY)
+        .set_entry(move || loop {
+            run_all(&failures);
+            // The period is checked at compile time.
+            let _ = time::sleep_ms(config::SELFTEST_PERIOD_MS);
+        })
+        .spawn()
+}
+
+/// Run each registered test to completion.
+fn run_all<const N: usize>(failures: &Producer<Failure, N>) {
+    let mut index = 0;
+    loop {
+        // Do not hold the lock across steps, so that registering a test is
+        // not delayed by a running test.
+        let (name, step) = match REGISTRY.lock().get_mut(index) {
+            Some(test) => (test.name(), test.step()),
+            None => return,
+        };
+        match step {
+            Step::Pending => task::yield_current(),
+            Step::Passed => index += 1,
+            Step::Failed(code) => {
+                failures.produce(Failure { name, code });
+                index += 1;
+            }
+        }
+    }
+}
+
+/// The patterns written by [`RamPattern`], including checkerboards to
+/// detect coupling between neighbouring bits.
+const RAM_PATTERNS: [u32; 4] = [0x0000_0000, 0xFFFF_FFFF, 0x5555_5555, 0xAAAA_AAAA];
+
+/// A test allocating a block of free heap memory and checking that each
+/// word holds the patterns written to it. The block is freed afterwards, so
+/// each run likely checks a different region.
+///
+/// A failure code is the offset of the faulty word in bytes.
+pub struct RamPattern {
+    words: usize,
+    /// The index of the next pattern to check.
+    pattern: usize,
+    block: Vec<u32>,
+}
+
+impl RamPattern {
+    /// Create a test checking a block of the given number of bytes, rounded
+    /// up to a multiple of 4.
+    pub fn new(bytes: usize) -> Self {
+        Self {
+            words: bytes.div_ceil(4),
+            pattern: 0,
+            block: Vec::new(),
+        }
+    }
+}
+
+impl SelfTest for RamPattern {
+    fn name(&self) -> &'static str {
+        "ram"
+    }
+
+    /// Write and verify one pattern per step.
+    fn step(&mut self) -> Step {
+        if self.pattern == 0 {
+            self.block = Vec::with_capacity(self.words);
+        }
+        let pattern = RAM_PATTERNS[self.pattern];
+        let ptr = self.block.as_mut_ptr();
+
+        // Access the memory with volatile operations so that the compiler
+        // cannot assume the read value equals the written one.
+        for i in 0..self.words {
+            unsafe { ptr.add(i).write_volatile(pattern) };
+        }
+        let faulty = (0..self.words).find(|&i| unsafe { ptr.add(i).read_volatile() } != pattern);
+
+        self.pattern += 1;
+        if faulty.is_some() || self.pattern == RAM_PATTERNS.len() {
+            self.pattern = 0;
+            self.block = Vec::new();
+        }
+        match faulty {
+            Some(i) => Step::Failed((i * 4) as u32),
+            None if self.pattern == 0 => Step::Passed,
+            None => Step::Pending,
+        }
+    }
+}
+
+/// A test computing the CRC-32 of the `.text` section and comparing it with
+/// the expected value.
+///
+/// The failure code is the computed CRC.
+pub struct FlashCrc {
+    /// The expected CRC. When not given at creation, the CRC computed in the
+    /// first run is expected in later runs.
+    expected: Option<u32>,
+    /// The offset of the next byte to check.
+    offset: usize,
+    crc: u32,
+}
+
+impl FlashCrc {
+    /// Create a test expecting the given CRC, e.g., computed on the host from
+    /// the built image. If `None`, the test checks that the `.text` section
+    /// is not changed after the first run.
+    pub const fn new(expected: Option<u32>) -> Self {
+        Self {
+            expected,
+            offset: 0,
+            crc: !0,
+        }
+    }
+
+    /// Return the `.text` section.
+    fn text() -> &'static [u8] {
+        extern "C" {
+            static _stext: u8;
+            static __etext: u8;
+        }
+        unsafe {
+            let start = &_stext as *const u8;
+            let end = &__etext as *const u8;
+            core::slice::from_raw_parts(start, end as usize - start as usize)
+        }
+    }
+}
+
+impl SelfTest for FlashCrc {
+    fn name(&self) -> &'static str {
+        "flash"
+    }
+
+    /// Check [`SELFTEST_STEP_BYTES`](config::SELFTEST_STEP_BYTES) bytes per
+    /// step.
+    fn step(&mut self) -> Step {
+        let text = Self::text();
+        let end = (self.offset + config::SELFTEST_STEP_BYTES).min(text.len());
+        self.crc = crc32_update(self.crc, &text[self.offset..end]);
+        self.offset = end;
+        if self.offset < text.len() {
+            return Step::Pending;
+        }
+
+        let crc = !self.crc;
+        self.offset = 0;
+        self.crc = !0;
+        match self.expected {
+            Some(expected) if expected != crc => Step::Failed(crc),
+            Some(_) => Step::Passed,
+            None => {
+                self.expected = Some(crc);
+                Step::Passed
+            }
+        }
+    }
+}
+
+/// A test running a function that returns if a peripheral works, e.g., by
+/// reading an identification register.
+///
+/// The failure code is always zero.
+pub struct Ping<F> {
+    name: &'static str,
+    check: F,
+}
+
+impl<F> Ping<F>
+where
+    F: FnMut() -> bool + Send,
+{
+    /// Create a test with the given name running `check` in a single step.
+    pub const fn new(name: &'static str, check: F) -> Self {
+        Self { name, check }
+    }
+}
+
+impl<F> SelfTest for Ping<F>
+where
+    F: FnMut() -> bool + Send,
+{
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn step(&mut self) -> Step {
+        if (self.check)() {
+            Step::Passed
+        } else {
+            Step::Failed(0)
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use super::CommandError;
+use alloc::{string::String, vec::Vec};
+use core::str::FromStr;
+
+/// The arguments following the command name.
+pub struct Args<'a> {
+    args: &'a [String],
+}
+
+impl<'a> Args<'a> {
+    pub(super) fn new(args: &'a [String]) -> Self {
+        Self { args }
+    }
+
+    /// The number of arguments.
+    pub fn len(&self) -> usize {
+        self.args.len()
+    }
+
+    /// Return if there is no argument.
+    pub fn is_empty(&self) -> bool {
+        self.args.is_empty()
+    }
+
+    /// Get the argument at the index.
+    pub fn get(&self, idx: usize) -> Option<&'a str> {
+        self.args.get(idx).map(String::as_str)
+    }
+
+    /// Parse the argument at the index. Return [`CommandError::Usage`] if the
+    /// argument is missing, or [`CommandError::InvalidArg`] if it cannot be
+    /// parsed.
+    pub fn parse<T: FromStr>(&self, idx: usize) -> Result<T, CommandError> {
+        self.get(idx)
+            .ok_or(CommandError::Usage)?
+            .parse()
+            .map_err(|_| CommandError::InvalidArg(idx))
+    }
+
+    /// Iterate over the arguments.
+    pub fn iter(&self) -> impl Iterator<Item = &'a str> {
+        self.args.iter().map(String::as_str)
+    }
+}
+
+/// Split the line into words separated by whitespace. Characters between a
+/// pair of double quotes form a single word, which may be empty. A missing
+/// closing quote is implied at the end of the line.
+pub(super) fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut in_token = false;
+    let mut in_quotes = false;
+
+    for ch in line.chars() {
+        match ch {
+            '"' => {
+                in_quotes = !in_quotes;
+                in_token = true;
+            }
+            _ if ch.is_whitespace() && !in_quotes => {
+                if in_token {
+                    tokens.push(core::mem::take(&mut token));
+                    in_token = false;
+                }
+            }
+            _ => {
+                token.push(ch);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        tokens.push(token);
+    }
+
+    tokens
+}
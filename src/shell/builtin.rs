@@ -0,0 +1,197 @@
+use super::{Args, CommandError, Output};
+use crate::{
+    allocator,
+    config::{self, tunable},
+    debug::{breadcrumb::RebootReason, events},
+    interrupt::stats,
+    peripheral, power,
+    schedule::scheduler::Scheduler,
+    task, time,
+};
+use alloc::{format, vec::Vec};
+use core::fmt::Write;
+
+type BuiltinFn = fn(&Args, &mut Output) -> Result<(), CommandError>;
+
+/// The name, argument synopsis, help text, and handler of each built-in
+/// command.
+pub(super) static BUILTINS: [(&str, &str, &str, BuiltinFn); 8] = [
+    ("help", "", "list all commands", help),
+    ("ps", "", "list the tasks and stacklets", ps),
+    ("free", "", "show the heap usage", free),
+    (
+        "irqstats",
+        "",
+        "show the invocation count of IRQs",
+        irqstats,
+    ),
+    ("claims", "", "list the claimed peripherals", claims),
+    (
+        "events",
+        "[sched]",
+        "print the recorded or the scheduler events",
+        events,
+    ),
+    ("reboot", "", "shut down and reset the system", reboot),
+    (
+        "loglevel",
+        "[level]",
+        "show or change the kernel log level",
+        loglevel,
+    ),
+];
+
+fn help(args: &Args, out: &mut Output) -> Result<(), CommandError> {
+    if !args.is_empty() {
+        return Err(CommandError::Usage);
+    }
+    for (name, args, help) in super::command_list() {
+        writeln!(out, "{:<20} {}", format!("{} {}", name, args), help)?;
+    }
+    Ok(())
+}
+
+fn ps(args: &Args, out: &mut Output) -> Result<(), CommandError> {
+    if !args.is_empty() {
+        return Err(CommandError::Usage);
+    }
+    writeln!(
+        out,
+        "tasks:     {} of {}",
+        Scheduler::task_count(),
+        config::MAX_TASK_NUMBER
+    )?;
+    writeln!(out, "stacklets: {}", task::get_active_stacklet_count())?;
+    writeln!(out, "current:   task {}", task::get_current_id())?;
+
+    // Collect the snapshots first to return early on write errors.
+    let mut infos = Vec::new();
+    task::for_each_task(|info| infos.push(info.clone()));
+
+    writeln!(
+        out,
+        "{:>4} {:<16} {:>4} {:<8} {:>6}",
+        "ID", "NAME", "PRIO", "STATE", "STACK"
+    )?;
+    for info in infos {
+        writeln!(
+            out,
+            "{:>4} {:<16} {:>4} {:<8} {:>6}",
+            info.id(),
+            info.name().unwrap_or("-"),
+            info.priority(),
+            format!("{:?}", info.status()),
+            info.stack_usage()
+        )?;
+    }
+    Ok(())
+}
+
+fn free(args: &Args, out: &mut Output) -> Result<(), CommandError> {
+    if !args.is_empty() {
+        return Err(CommandError::Usage);
+    }
+    let total = allocator::heap_size();
+    let (used, peak) = allocator::heap_usage();
+    writeln!(out, "total: {} bytes", total)?;
+    writeln!(out, "used:  {} bytes", used)?;
+    writeln!(out, "free:  {} bytes", total.saturating_sub(used))?;
+    writeln!(out, "peak:  {} bytes", peak)?;
+    Ok(())
+}
+
+fn irqstats(args: &Args, out: &mut Output) -> Result<(), CommandError> {
+    if !args.is_empty() {
+        return Err(CommandError::Usage);
+    }
+    // Collect the counts first because printing may block.
+    let mut counts = Vec::new();
+    stats::for_each_irq(|irq, count| counts.push((irq, count)));
+
+    writeln!(out, "SysTick: {}", stats::systick_count())?;
+    for (irq, count) in counts {
+        writeln!(out, "IRQ {:>3}: {}", irq, count)?;
+    }
+    Ok(())
+}
+
+fn claims(args: &Args, out: &mut Output) -> Result<(), CommandError> {
+    if !args.is_empty() {
+        return Err(CommandError::Usage);
+    }
+    // Collect the claims first because printing may block.
+    let mut claims = Vec::new();
+    peripheral::for_each_claim(|name, task_id| claims.push((name, task_id)));
+
+    for (name, task_id) in claims {
+        writeln!(out, "{:<16} task {}", name, task_id)?;
+    }
+    Ok(())
+}
+
+fn events(args: &Args, out: &mut Output) -> Result<(), CommandError> {
+    match (args.len(), args.get(0)) {
+        (0, _) => {}
+        #[cfg(feature = "sched_events")]
+        (1, Some("sched")) => return sched_events(out),
+        _ => return Err(CommandError::Usage),
+    }
+    // Collect the events first because printing may block.
+    let mut recorded = Vec::new();
+    events::for_each(|event| recorded.push(event));
+
+    for event in recorded {
+        writeln!(
+            out,
+            "#{:<6} {:>10} code {:>5} arg {}",
+            event.seq(),
+            event.stamp(),
+            event.code(),
+            event.arg()
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "sched_events")]
+fn sched_events(out: &mut Output) -> Result<(), CommandError> {
+    use crate::debug::sched_events;
+
+    // Collect the events first because printing may block.
+    let mut recorded = Vec::new();
+    sched_events::for_each(|event| recorded.push(event));
+
+    for event in recorded {
+        write!(
+            out,
+            "#{:<6} {:>10} {:<6}",
+            event.seq(),
+            event.stamp(),
+            event.kind().name()
+        )?;
+        match event.task() {
+            Some(task) => writeln!(out, " task {}", task)?,
+            None => writeln!(out)?,
+        }
+    }
+    Ok(())
+}
+
+fn reboot(args: &Args, out: &mut Output) -> Result<(), CommandError> {
+    if !args.is_empty() {
+        return Err(CommandError::Usage);
+    }
+    writeln!(out, "rebooting")?;
+    // Give the transport a chance to send the message.
+    let _ = time::sleep_ms(10);
+    power::shutdown(RebootReason::Requested)
+}
+
+fn loglevel(args: &Args, out: &mut Output) -> Result<(), CommandError> {
+    match args.len() {
+        0 => writeln!(out, "{}", tunable::log_level())?,
+        1 => tunable::set_log_level(args.parse(0)?),
+        _ => return Err(CommandError::Usage),
+    }
+    Ok(())
+}
@@ -0,0 +1,303 @@
+//! An interactive command shell, enabled by the `shell` feature.
+//!
+//! A shell runs in its own task started by [`start`] over any
+//! [`ShellTransport`], e.g., a UART whose IRQ handler feeds a channel. The
+//! shell echoes the input, handles backspace, discards the line on `Ctrl-C`,
+//! and runs the command named by the first word of each line. The built-in
+//! commands are:
+//!
+//! - `help`: list all commands.
+//! - `ps`: show the number of tasks and stacklets.
+//! - `free`: show the heap usage.
+//! - `irqstats`: show the invocation count of each counted IRQ.
+//! - `claims`: list the claimed peripherals and their owner tasks.
+//! - `events [sched]`: print the events in the flight recorder ring, or
+//!   with `sched`, the scheduler events recorded with the `sched_events`
+//!   feature.
+//! - `reboot`: shut down and reset the system, see [`crate::power`].
+//! - `loglevel [level]`: show or change the kernel log level.
+//!
+//! Applications add their own commands with [`register`]. Arguments are
+//! separated by whitespace, and double quotes group words into a single
+//! argument. All shells share the same set of commands.
+//!
+//! # Example
+//! ```rust
+//! let (rx_producer, rx_consumer) = sync::create_channel::<u8, 64>();
+//! let (tx_producer, tx_consumer) = sync::create_channel::<u8, 64>();
+//! // The UART IRQ handler feeds `rx_producer` and drains `tx_consumer`.
+//!
+//! shell::register("led", "<on|off>", "switch the LED", |args, out| {
+//!     match args.get(0) {
+//!         Some("on") => led_on(),
+//!         Some("off") => led_off(),
+//!         _ => return Err(CommandError::Usage),
+//!     }
+//!     writeln!(out, "ok")?;
+//!     Ok(())
+//! })
+//! .unwrap();
+//!
+//! shell::start((rx_consumer, tx_producer)).unwrap();
+//! ```
+
+mod args;
+mod builtin;
+
+pub use args::Args;
+
+use crate::{
+    config,
+    sync::{Consumer, Mutex, Producer},
+    task::{self, TaskBuildError},
+};
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use core::fmt::{self, Write};
+
+/// A bidirectional byte stream connecting a shell to the user.
+pub trait ShellTransport: Send {
+    /// Block until a byte is received and return it.
+    fn read_byte(&mut self) -> u8;
+
+    /// Write the bytes, blocking until all of them are accepted.
+    fn write(&mut self, data: &[u8]);
+}
+
+/// A pair of channels, where the first one carries the received bytes and
+/// the second one carries the bytes to be transmitted.
+impl<const N: usize, const M: usize> ShellTransport for (Consumer<u8, N>, Producer<u8, M>) {
+    fn read_byte(&mut self) -> u8 {
+        self.0.consume()
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        for byte in data {
+            self.1.produce(*byte);
+        }
+    }
+}
+
+/// Enumeration of errors of the shell API.
+#[derive(Debug, PartialEq)]
+pub enum ShellError {
+    /// The shell task cannot be spawned.
+    Task(TaskBuildError),
+    /// The command name is empty or contains whitespace.
+    InvalidName,
+    /// A command with the same name already exists.
+    DuplicateName,
+}
+
+/// Enumeration of errors returned by command handlers. The shell prints a
+/// message for each error.
+#[derive(Debug, PartialEq)]
+pub enum CommandError {
+    /// The number or the form of the arguments is wrong. The shell prints
+    /// the usage of the command.
+    Usage,
+    /// The argument at the index cannot be parsed.
+    InvalidArg(usize),
+    /// The command failed for the given reason.
+    Failed(&'static str),
+}
+
+impl From<fmt::Error> for CommandError {
+    fn from(_: fmt::Error) -> Self {
+        Self::Failed("output error")
+    }
+}
+
+/// The output of a command. Writing `'\n'` produces `"\r\n"` so that
+/// terminals return to the first column.
+pub struct Output<'a> {
+    transport: &'a mut dyn ShellTransport,
+}
+
+impl Output<'_> {
+    /// Write raw bytes without newline translation.
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        self.transport.write(data);
+    }
+}
+
+impl Write for Output<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (idx, part) in s.split('\n').enumerate() {
+            if idx > 0 {
+                self.transport.write(b"\r\n");
+            }
+            self.transport.write(part.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+type Handler = dyn Fn(&Args, &mut Output) -> Result<(), CommandError> + Send + Sync;
+
+struct Command {
+    name: &'static str,
+    /// The synopsis of the arguments, e.g., `"<pin> [on|off]"`.
+    args: &'static str,
+    help: &'static str,
+    handler: Box<Handler>,
+}
+
+/// All commands. The built-in ones are added on first access.
+static COMMANDS: Mutex<Vec<Arc<Command>>> = Mutex::new(Vec::new());
+
+/// Run the closure with the command list locked.
+fn with_commands<F, R>(op: F) -> R
+where
+    F: FnOnce(&mut Vec<Arc<Command>>) -> R,
+{
+    let mut commands = COMMANDS.lock();
+    if commands.is_empty() {
+        for &(name, args, help, handler) in builtin::BUILTINS.iter() {
+            commands.push(Arc::new(Command {
+                name,
+                args,
+                help,
+                handler: Box::new(handler),
+            }));
+        }
+    }
+    op(&mut commands)
+}
+
+/// Add a command to all shells. The handler is called with the arguments
+/// following the command name and the output to print to. The `args`
+/// synopsis and the `help` text are printed by the `help` command.
+pub fn register<F>(
+    name: &'static str,
+    args: &'static str,
+    help: &'static str,
+    handler: F,
+) -> Result<(), ShellError>
+where
+    F: Fn(&Args, &mut Output) -> Result<(), CommandError> + Send + Sync + 'static,
+{
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(ShellError::InvalidName);
+    }
+    with_commands(|commands| {
+        if commands.iter().any(|command| command.name == name) {
+            return Err(ShellError::DuplicateName);
+        }
+        commands.push(Arc::new(Command {
+            name,
+            args,
+            help,
+            handler: Box::new(handler),
+        }));
+        Ok(())
+    })
+}
+
+/// Spawn a shell task over the transport.
+pub fn start<T>(transport: T) -> Result<(), ShellError>
+where
+    T: ShellTransport + 'static,
+{
+    task::build()
+        .set_id(config::SHELL_TASK_ID)
+        .set_name("shell")
+        .set_priority(config::SHELL_TASK_PRIORITY)
+        .set_entry(move || run(transport))
+        .spawn()
+        .map_err(ShellError::Task)
+}
+
+/// The body of a shell task.
+fn run<T: ShellTransport>(mut transport: T) {
+    let mut line = String::with_capacity(config::SHELL_LINE_LENGTH);
+    let mut last_cr = false;
+
+    transport.write(config::SHELL_PROMPT.as_bytes());
+    loop {
+        let byte = transport.read_byte();
+
+        // Treat "\r\n" as a single line ending.
+        if byte == b'\n' && last_cr {
+            last_cr = false;
+            continue;
+        }
+        last_cr = byte == b'\r';
+
+        match byte {
+            b'\r' | b'\n' => {
+                transport.write(b"\r\n");
+                execute(
+                    &line,
+                    &mut Output {
+                        transport: &mut transport,
+                    },
+                );
+                line.clear();
+                transport.write(config::SHELL_PROMPT.as_bytes());
+            }
+            // Backspace or delete.
+            0x08 | 0x7F => {
+                if line.pop().is_some() {
+                    transport.write(b"\x08 \x08");
+                }
+            }
+            // Ctrl-C.
+            0x03 => {
+                line.clear();
+                transport.write(b"^C\r\n");
+                transport.write(config::SHELL_PROMPT.as_bytes());
+            }
+            b' '..=b'~' if line.len() < config::SHELL_LINE_LENGTH => {
+                line.push(byte as char);
+                transport.write(&[byte]);
+            }
+            // Ring the bell when the line is full.
+            b' '..=b'~' => transport.write(b"\x07"),
+            // Ignore other control characters.
+            _ => {}
+        }
+    }
+}
+
+/// Parse the command line and run the command.
+fn execute(line: &str, out: &mut Output) {
+    let tokens = args::tokenize(line);
+    let Some((name, args)) = tokens.split_first() else {
+        return;
+    };
+
+    // Do not hold the lock while the command runs, so that commands can
+    // register other commands.
+    let command = with_commands(|commands| {
+        commands
+            .iter()
+            .find(|command| command.name == name.as_str())
+            .cloned()
+    });
+    let Some(command) = command else {
+        let _ = writeln!(out, "{}: command not found", name);
+        return;
+    };
+
+    let _ = match (command.handler)(&Args::new(args), out) {
+        Ok(()) => Ok(()),
+        Err(CommandError::Usage) => writeln!(out, "usage: {} {}", command.name, command.args),
+        Err(CommandError::InvalidArg(idx)) => writeln!(
+            out,
+            "{}: invalid argument '{}'",
+            command.name,
+            args.get(idx).map_or("", |arg| arg.as_str())
+        ),
+        Err(CommandError::Failed(reason)) => writeln!(out, "{}: {}", command.name, reason),
+    };
+}
+
+/// Get the name, argument synopsis, and help text of every command.
+fn command_list() -> Vec<(&'static str, &'static str, &'static str)> {
+    with_commands(|commands| {
+        commands
+            .iter()
+            .map(|command| (command.name, command.args, command.help))
+            .collect()
+    })
+}
@@ -0,0 +1,132 @@
+use super::Channel;
+use alloc::{boxed::Box, sync::Arc};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// An owning pointer to a heap allocation, i.e., [`Box<T>`] or [`Arc<T>`],
+/// that can be passed through a box channel. See [`create_box_channel`].
+pub trait HeapPtr: private::Sealed {}
+
+impl<T> private::Sealed for Box<T> {}
+impl<T> HeapPtr for Box<T> {}
+impl<T> private::Sealed for Arc<T> {}
+impl<T> HeapPtr for Arc<T> {}
+
+/// A producer of a box channel. It can be cloned.
+pub struct BoxProducer<P: HeapPtr, const N: usize> {
+    channel: Arc<Channel<P, N>>,
+}
+
+/// The consumer of a box channel. It can be cloned.
+pub struct BoxConsumer<P: HeapPtr, const N: usize> {
+    channel: Arc<Channel<P, N>>,
+}
+
+/// Cloning the producer does not require cloning the pointed data.
+impl<P: HeapPtr, const N: usize> Clone for BoxProducer<P, N> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+/// Cloning the consumer does not require cloning the pointed data.
+impl<P: HeapPtr, const N: usize> Clone for BoxConsumer<P, N> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<P: HeapPtr, const N: usize> BoxProducer<P, N> {
+    /// Pass the allocation into the corresponding channel. If the channel is
+    /// already full, block until there is an empty slot and it can proceed.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn produce(&self, data: P) {
+        self.channel.push(data)
+    }
+
+    /// Pass the allocation into the corresponding channel. If the channel is
+    /// already full, block until there is an empty slot or the elapsed
+    /// waiting time reaches timeout. Return `Ok` if the allocation is passed
+    /// in, or the allocation with `Err` if timed out.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn produce_timeout(&self, data: P, timeout_ms: u32) -> Result<(), P> {
+        self.channel.push_timeout(data, timeout_ms)
+    }
+
+    /// Pass the allocation into the corresponding channel. If the channel is
+    /// already full, return the allocation with `Err`. Otherwise, pass in
+    /// the allocation and return `Ok`.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn try_produce_allow_isr(&self, data: P) -> Result<(), P> {
+        self.channel.try_push_allow_isr(data)
+    }
+}
+
+impl<P: HeapPtr, const N: usize> BoxConsumer<P, N> {
+    /// Take an allocation from the corresponding channel. If the channel is
+    /// empty, block until there is an allocation and it can proceed.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn consume(&self) -> P {
+        self.channel.pop()
+    }
+
+    /// Take an allocation from the corresponding channel. If the channel is
+    /// empty, block until there is an allocation or the elapsed waiting time
+    /// reaches timeout. Return the allocation with `Some`, or `None` if timed
+    /// out.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn consume_timeout(&self, timeout_ms: u32) -> Option<P> {
+        self.channel.pop_timeout(timeout_ms)
+    }
+
+    /// Try to take an allocation from the corresponding channel. If the
+    /// channel is empty, return `None`. Otherwise, return the allocation with
+    /// `Some`.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn try_consume_allow_isr(&self) -> Option<P> {
+        self.channel.try_pop_allow_isr()
+    }
+}
+
+/// Create a channel passing the ownership of heap allocations, e.g.,
+/// `Box<[u8; 512]>` frames, with the given buffering capacity. Return a
+/// producer and a consumer corresponding with the channel.
+///
+/// Only the pointers are moved through the channel, so each slot of the
+/// buffer takes a single word and the pointed data is never copied,
+/// regardless of its size. In contrast, a [`create_channel`](super::create_channel)
+/// of `[u8; 512]` copies each frame into and out of the buffer. The
+/// allocations left in the channel are dropped with it.
+///
+/// # Example
+/// ```rust
+/// let (producer, consumer) = sync::create_box_channel::<Box<[u8; 512]>, 4>();
+///
+/// // In an IRQ handler, with a frame allocated beforehand.
+/// if let Err(frame) = producer.try_produce_allow_isr(frame) {
+///     recycle(frame);
+/// }
+///
+/// // In a task.
+/// let frame = consumer.consume();
+/// ```
+pub fn create_box_channel<P: HeapPtr, const N: usize>() -> (BoxProducer<P, N>, BoxConsumer<P, N>) {
+    let chan = Arc::new(Channel::new());
+    let producer = BoxProducer {
+        channel: chan.clone(),
+    };
+    let consumer = BoxConsumer { channel: chan };
+    (producer, consumer)
+}
@@ -0,0 +1,123 @@
+use super::{Observers, Selectable, WaitSet};
+use crate::unrecoverable;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A flag telling tasks to stop what they are doing, e.g., to shut down
+/// long-running worker tasks cleanly. Once a token is
+/// [`cancel_allow_isr`](Self::cancel_allow_isr)ed, it stays cancelled.
+///
+/// A task can poll the token with [`is_cancelled`](Self::is_cancelled) or
+/// block until it is cancelled. A task blocking on another primitive can
+/// also be woken up early by the cancellation, in which case the wait
+/// returns [`Cancelled`]. See [`wait_for`](Self::wait_for). The token can
+/// also be added to a [`WaitSet`].
+///
+/// # Example
+/// ```rust
+/// static SHUTDOWN: CancellationToken = CancellationToken::new();
+///
+/// // In the worker task.
+/// while let Ok(job) = consumer.consume_cancellable(&SHUTDOWN) {
+///     process(job);
+/// }
+/// release_resources();
+///
+/// // In another task or an ISR.
+/// SHUTDOWN.cancel_allow_isr();
+/// ```
+pub struct CancellationToken {
+    cancelled: AtomicBool,
+    /// The wait sets to notify on the cancellation.
+    pub(super) observers: Observers,
+}
+
+/// The error returned by a wait that ends because the
+/// [`CancellationToken`] is cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl CancellationToken {
+    /// Create a new token that is not cancelled.
+    pub const fn new() -> Self {
+        Self {
+            cancelled: AtomicBool::new(false),
+            observers: Observers::new(),
+        }
+    }
+
+    /// Cancel the token and wake up the tasks waiting on it. Cancelling a
+    /// token that is already cancelled has no effect.
+    ///
+    /// This method is allowed in ISR context.
+    pub fn cancel_allow_isr(&self) {
+        if !self.cancelled.swap(true, Ordering::SeqCst) {
+            self.observers.notify_allow_isr();
+        }
+    }
+
+    /// Return if the token is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Block the calling task until the token is cancelled. Return
+    /// immediately if it is already cancelled.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn wait(&self) {
+        unrecoverable::die_if_in_isr();
+
+        let mut set = WaitSet::new();
+        set.add(self);
+        set.wait();
+    }
+
+    /// Block the calling task until the token is cancelled or the elapsed
+    /// waiting time reaches timeout. Return `true` if the token is
+    /// cancelled, or `false` if timed out.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn wait_timeout(&self, timeout_ms: u32) -> bool {
+        unrecoverable::die_if_in_isr();
+
+        let mut set = WaitSet::new();
+        set.add(self);
+        set.wait_timeout(timeout_ms).is_some()
+    }
+
+    /// Block the calling task until the operation `op` on the primitive
+    /// `source` succeeds or the token is cancelled. Return the result of the
+    /// operation within `Ok`, or `Err(Cancelled)` if cancelled.
+    ///
+    /// The operation should be the non-blocking counterpart of the wait,
+    /// e.g., [`Consumer::try_consume_allow_isr`], returning `None` if it
+    /// cannot proceed yet. It is retried each time the primitive becomes
+    /// ready. The cancellation is checked before the operation, so
+    /// the operation is not performed once the token is cancelled.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    ///
+    /// [`Consumer::try_consume_allow_isr`]: super::Consumer::try_consume_allow_isr
+    pub fn wait_for<R, F>(&self, source: &dyn Selectable, mut op: F) -> Result<R, Cancelled>
+    where
+        F: FnMut() -> Option<R>,
+    {
+        unrecoverable::die_if_in_isr();
+
+        let mut set = WaitSet::new();
+        set.add(self);
+        set.add(source);
+        loop {
+            if self.is_cancelled() {
+                return Err(Cancelled);
+            }
+            if let Some(ret) = op() {
+                return Ok(ret);
+            }
+            // The cancellation or the primitive becoming ready after the
+            // checks above notifies the wait set, so the task does not miss
+            // it.
+            set.wait();
+        }
+    }
+}
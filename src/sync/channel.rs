@@ -1,12 +1,16 @@
-use super::Semaphore;
-use crate::unrecoverable::Lethal;
+use super::{CancellationToken, Cancelled, Semaphore, SpinSchedSafe};
+use crate::unrecoverable::{self, Lethal};
 use alloc::sync::Arc;
+use core::fmt;
 use heapless::mpmc::MpMcQueue;
 
 /// A multi-producer multi-consumer channel.
-struct Channel<T, const N: usize> {
+pub(super) struct Channel<T, const N: usize> {
     /// The underlying lock-free ring buffer.
     buffer: MpMcQueue<T, N>,
+    /// The element moved out of the ring buffer by a peek. It is older than
+    /// all elements in the ring buffer, and it is still counted as occupied.
+    peeked: SpinSchedSafe<Option<T>>,
     /// The semaphore counting on the empty slots.
     sem_empty: Semaphore,
     /// The semaphore counting on the occupied slots.
@@ -14,34 +18,94 @@ struct Channel<T, const N: usize> {
 }
 
 /// A producer of a channel. It can be cloned.
-#[derive(Clone)]
 pub struct Producer<T, const N: usize> {
     channel: Arc<Channel<T, N>>,
 }
 
 /// The consumer of a channel. It can be cloned.
-#[derive(Clone)]
 pub struct Consumer<T, const N: usize> {
     channel: Arc<Channel<T, N>>,
 }
 
+/// Cloning the producer does not require cloning the elements.
+impl<T, const N: usize> Clone for Producer<T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+/// Cloning the consumer does not require cloning the elements.
+impl<T, const N: usize> Clone for Consumer<T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
 impl<T, const N: usize> Channel<T, N> {
     /// Create a new channel with the given buffering capacity.
-    fn new() -> Self {
+    pub(super) fn new() -> Self {
         Self {
             buffer: MpMcQueue::new(),
+            peeked: SpinSchedSafe::new(None),
             sem_empty: Semaphore::new(N, N),
             sem_occupied: Semaphore::new(N, 0),
         }
     }
 
+    /// Remove the oldest element, either the peeked one or the front one in
+    /// the ring buffer. The caller must have taken an occupied slot.
+    fn take(&self) -> T {
+        // Only an ISR can find the peeked element locked, when it preempts a
+        // task peeking or taking the element. The task has taken an occupied
+        // slot for that element, so the ring buffer holds one for the ISR.
+        if let Some(mut peeked) = self.peeked.try_lock() {
+            if let Some(data) = peeked.take() {
+                return data;
+            }
+        }
+        self.buffer.dequeue().unwrap_or_die()
+    }
+
+    /// Return a clone of the oldest element without removing it, or `None`
+    /// if the channel buffer is empty.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub(super) fn peek(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        unrecoverable::die_if_in_isr();
+
+        // Take an occupied slot, so that the element is not consumed while
+        // being moved out of the ring buffer or cloned.
+        self.sem_occupied.try_down_allow_isr().ok()?;
+        let slot = self.sem_occupied.give_back_on_unwind();
+        let data = {
+            // Tasks lock the peeked element only with the scheduler suspended,
+            // so there is no contention in task context.
+            let mut peeked = self.peeked.lock_now_or_die();
+            peeked
+                .get_or_insert_with(|| self.buffer.dequeue().unwrap_or_die())
+                .clone()
+        };
+        slot.disarm();
+        self.sem_occupied.up();
+        Some(data)
+    }
+
     /// Push an element into the channel. If the channel buffer is already full,
     /// the task will be blocked until there is an empty slot.
     ///
     /// Important: *must not* call this method in ISR context.
-    fn push(&self, data: T) {
+    pub(super) fn push(&self, data: T) {
         self.sem_empty.down();
+        let slot = self.sem_empty.give_back_on_unwind();
         self.buffer.enqueue(data).ok().unwrap_or_die();
+        slot.disarm();
         self.sem_occupied.up();
     }
 
@@ -49,23 +113,58 @@ impl<T, const N: usize> Channel<T, N> {
     /// the task will be blocked until there is an element.
     ///
     /// Important: *must not* call this method in ISR context.
-    fn pop(&self) -> T {
+    pub(super) fn pop(&self) -> T {
         self.sem_occupied.down();
-        let data = self.buffer.dequeue().unwrap_or_die();
+        let slot = self.sem_occupied.give_back_on_unwind();
+        let data = self.take();
+        slot.disarm();
         self.sem_empty.up();
         data
     }
 
+    /// Push an element into the channel. If the channel buffer is already full,
+    /// the task will be blocked until there is an empty slot or the elapsed
+    /// waiting time reaches timeout. Return the element with `Err` if timed
+    /// out.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub(super) fn push_timeout(&self, data: T, timeout_ms: u32) -> Result<(), T> {
+        if self.sem_empty.down_timeout(timeout_ms).is_err() {
+            return Err(data);
+        }
+        let slot = self.sem_empty.give_back_on_unwind();
+        self.buffer.enqueue(data).ok().unwrap_or_die();
+        slot.disarm();
+        self.sem_occupied.up();
+        Ok(())
+    }
+
+    /// Pop out an element from the channel. If the channel buffer is empty,
+    /// the task will be blocked until there is an element or the elapsed
+    /// waiting time reaches timeout. Return `None` if timed out.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub(super) fn pop_timeout(&self, timeout_ms: u32) -> Option<T> {
+        self.sem_occupied.down_timeout(timeout_ms).ok()?;
+        let slot = self.sem_occupied.give_back_on_unwind();
+        let data = self.take();
+        slot.disarm();
+        self.sem_empty.up();
+        Some(data)
+    }
+
     /// Try to pop an element from the buffer. If there is no element, return
     /// `None`. Otherwise, return the element in `Some`.
     ///
     /// Calling this method in ISR context is allowed.
-    fn try_pop_allow_isr(&self) -> Option<T> {
+    pub(super) fn try_pop_allow_isr(&self) -> Option<T> {
         if self.sem_occupied.try_down_allow_isr().is_err() {
             return None;
         }
 
-        let data = self.buffer.dequeue().unwrap_or_die();
+        let slot = self.sem_occupied.give_back_on_unwind();
+        let data = self.take();
+        slot.disarm();
         self.sem_empty.try_up_allow_isr().unwrap_or_die();
 
         Some(data)
@@ -76,10 +175,12 @@ impl<T, const N: usize> Channel<T, N> {
     /// return `Ok`.
     ///
     /// Calling this method in ISR context is allowed.
-    fn try_push_allow_isr(&self, data: T) -> Result<(), T> {
+    pub(super) fn try_push_allow_isr(&self, data: T) -> Result<(), T> {
         // If there is empty space in the buffer, simply push it.
         if self.sem_empty.try_down_allow_isr().is_ok() {
+            let slot = self.sem_empty.give_back_on_unwind();
             self.buffer.enqueue(data).ok().unwrap_or_die();
+            slot.disarm();
             self.sem_occupied.try_up_allow_isr().unwrap_or_die();
             return Ok(());
         }
@@ -89,6 +190,15 @@ impl<T, const N: usize> Channel<T, N> {
     }
 }
 
+/// The ring buffer does not drop the elements left in it, so drop them here.
+/// Otherwise, e.g., the heap allocations owned by the elements would leak.
+/// The peeked element, if any, is dropped with its field.
+impl<T, const N: usize> Drop for Channel<T, N> {
+    fn drop(&mut self) {
+        while self.buffer.dequeue().is_some() {}
+    }
+}
+
 impl<T, const N: usize> Producer<T, N> {
     /// Create a new producer for the given channel.
     fn new(chan: Arc<Channel<T, N>>) -> Self {
@@ -103,6 +213,16 @@ impl<T, const N: usize> Producer<T, N> {
         self.channel.push(data)
     }
 
+    /// Push an element into the corresponding channel. If the channel is
+    /// already full, block until there is an empty slot or the elapsed
+    /// waiting time reaches timeout. Return `Ok` if the element is pushed, or
+    /// the element with `Err` if timed out.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn produce_timeout(&self, data: T, timeout_ms: u32) -> Result<(), T> {
+        self.channel.push_timeout(data, timeout_ms)
+    }
+
     /// Push an element into the corresponding channel. If the channel is
     /// already full, return the element with `Err`. Otherwise, push in the
     /// element and return `Ok`.
@@ -127,6 +247,25 @@ impl<T, const N: usize> Consumer<T, N> {
         self.channel.pop()
     }
 
+    /// Pop an element from the corresponding channel. If the channel is
+    /// empty, block until there is an element or the elapsed waiting time
+    /// reaches timeout. Return the element with `Some`, or `None` if timed
+    /// out.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn consume_timeout(&self, timeout_ms: u32) -> Option<T> {
+        self.channel.pop_timeout(timeout_ms)
+    }
+
+    /// Pop an element from the corresponding channel. If the channel is
+    /// empty, block until there is an element or the token is cancelled.
+    /// Return the element with `Ok`, or `Err(Cancelled)` if cancelled.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn consume_cancellable(&self, token: &CancellationToken) -> Result<T, Cancelled> {
+        token.wait_for(self, || self.try_consume_allow_isr())
+    }
+
     /// Try to pop an element from the corresponding channel. If the channel
     /// is empty, return `None`. Otherwise, return the element with `Some`.
     ///
@@ -134,6 +273,94 @@ impl<T, const N: usize> Consumer<T, N> {
     pub fn try_consume_allow_isr(&self) -> Option<T> {
         self.channel.try_pop_allow_isr()
     }
+
+    /// Return a clone of the oldest element in the corresponding channel
+    /// without consuming it, or `None` if the channel is empty.
+    ///
+    /// If another consumer consumes concurrently, it may consume the peeked
+    /// element after a newer one. A consumer trying to consume without
+    /// blocking while the element is being cloned may find the channel empty.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn peek(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.channel.peek()
+    }
+
+    /// Return the number of elements in the corresponding channel. Note that
+    /// the read value may become stale immediately after it is read.
+    pub fn len(&self) -> usize {
+        self.channel.sem_occupied.count()
+    }
+
+    /// Return if the corresponding channel is empty. Note that the read value
+    /// may become stale immediately after it is read.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return if the corresponding channel is full, i.e., producing into it
+    /// would block. Note that the read value may become stale immediately
+    /// after it is read.
+    pub fn is_full(&self) -> bool {
+        self.channel.sem_empty.count() == 0
+    }
+
+    /// Return the maximum number of elements the corresponding channel can
+    /// hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Return the semaphore counting on the occupied slots.
+    pub(super) fn occupied_semaphore(&self) -> &Semaphore {
+        &self.channel.sem_occupied
+    }
+}
+
+impl<const N: usize> Producer<u8, N> {
+    /// Return a writer that formats text into the channel without blocking.
+    /// See [`TryWriter`].
+    pub fn try_writer(&self) -> TryWriter<'_, N> {
+        TryWriter { producer: self }
+    }
+}
+
+/// Format text into a byte channel, e.g., one drained by a UART, with
+/// `write!`. Writing blocks until all bytes are accepted.
+///
+/// Important: *must not* write in ISR context. Use
+/// [`try_writer`](Producer::try_writer) instead.
+impl<const N: usize> fmt::Write for Producer<u8, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.produce(byte);
+        }
+        Ok(())
+    }
+}
+
+/// A writer formatting text into a byte channel without blocking, created by
+/// [`Producer::try_writer`]. When the channel is full, the remaining bytes
+/// are dropped and the write fails with [`fmt::Error`], so the text may be
+/// cut off at any byte.
+///
+/// Writing in ISR context is allowed.
+pub struct TryWriter<'a, const N: usize> {
+    producer: &'a Producer<u8, N>,
+}
+
+impl<const N: usize> fmt::Write for TryWriter<'_, N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.producer
+                .try_produce_allow_isr(byte)
+                .map_err(|_| fmt::Error)?;
+        }
+        Ok(())
+    }
 }
 
 /// Create a channel with the given buffering capacity. Return a producer and
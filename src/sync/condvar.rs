@@ -1,9 +1,15 @@
 use super::{
     lock_traits::{Lockable, UnlockableGuard},
-    WaitQueue,
+    WaitQueue, WakeupOrder,
 };
 
 /// Condition variable, similar to `std::sync::Condvar`.
+///
+/// Tasks waiting with a timeout sleep on a [`Mailbox`](super::Mailbox) of
+/// their own, which puts them in the sleeping queue of the scheduler like
+/// [`Mailbox::wait_until_timeout`](super::Mailbox::wait_until_timeout).
+/// They are notified in the same order as the tasks waiting without a
+/// timeout.
 pub struct CondVar {
     wait_queue: WaitQueue,
 }
@@ -11,11 +17,70 @@ pub struct CondVar {
 impl CondVar {
     /// Create a new condition variable.
     pub const fn new() -> Self {
+        Self::with_wakeup_order(WakeupOrder::Priority)
+    }
+
+    /// Create a new condition variable notifying the waiting tasks in the
+    /// given order.
+    pub const fn with_wakeup_order(order: WakeupOrder) -> Self {
         Self {
-            wait_queue: WaitQueue::new(),
+            wait_queue: WaitQueue::with_order(order),
         }
     }
 
+    /// Wait on the condition variable until notified. The task calling this
+    /// method should pass in a lock guard, which will be atomically unlocked
+    /// when the task is blocked and re-locked when the task is resumed. When
+    /// the task resumes, it will get back the lock guard from the method's
+    /// return value.
+    ///
+    /// Like `std::sync::Condvar`, the task should check its condition again
+    /// after it resumes, or use [`wait_while`](Self::wait_while) instead.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn wait<'a, G, L>(&self, guard: G) -> G
+    where
+        G: UnlockableGuard<'a, LockType = L>,
+        L: Lockable<GuardType<'a> = G> + 'a,
+    {
+        // Block on the first evaluation and resume on the notification.
+        let mut blocked = false;
+        self.wait_until(guard, |_| core::mem::replace(&mut blocked, true))
+    }
+
+    /// Wait on the condition variable while the condition holds. The
+    /// condition predicate function can access the data protected through
+    /// the lock guard and should return true when the task should keep
+    /// waiting. It is checked before blocking and each time the task is
+    /// notified. When the task resumes, it will get back the lock guard from
+    /// the method's return value.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn wait_while<'a, G, L, F>(&self, guard: G, mut condition: F) -> G
+    where
+        F: FnMut(&mut G) -> bool,
+        G: UnlockableGuard<'a, LockType = L>,
+        L: Lockable<GuardType<'a> = G> + 'a,
+    {
+        self.wait_until(guard, |guard| !condition(guard))
+    }
+
+    /// Wait on the condition variable until notified or the timeout elapses.
+    /// The lock guard is handled in the same way as with
+    /// [`wait`](Self::wait).
+    ///
+    /// Return the lock guard and `true` if the task is woken up by
+    /// notification, or `false` if by timeout.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn wait_timeout<'a, G, L>(&self, guard: G, timeout_ms: u32) -> (G, bool)
+    where
+        G: UnlockableGuard<'a, LockType = L>,
+        L: Lockable<GuardType<'a> = G> + 'a,
+    {
+        self.wait_queue.wait_with_lock_timeout(guard, timeout_ms)
+    }
+
     /// Wait on the condition variable until notified and the condition is met.
     ///
     /// Important: *must not* call this method in ISR context.
@@ -31,6 +96,25 @@ impl CondVar {
         self.wait_queue.wait_until(|| condition().then(|| ()))
     }
 
+    /// Wait on the condition variable until notified and the condition is met,
+    /// or until the timeout elapses. Return `true` if the condition is met,
+    /// or `false` if timed out.
+    ///
+    /// The condition is checked before blocking and each time the task is
+    /// notified. Like with [`wait_without_lock_until`](Self::wait_without_lock_until),
+    /// a notification is discarded if the notified task does not have its
+    /// condition met.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn wait_without_lock_until_timeout<F>(&self, mut condition: F, timeout_ms: u32) -> bool
+    where
+        F: FnMut() -> bool,
+    {
+        self.wait_queue
+            .wait_until_timeout(|| condition().then_some(()), timeout_ms)
+            .is_some()
+    }
+
     /// Wait on the condition variable until notified and the condition is met.
     /// The task calling this method should pass in a lock guard, which will be
     /// atomically unlocked when the task is blocked and re-locked when the task
@@ -66,4 +150,18 @@ impl CondVar {
     pub fn notify_one_allow_isr(&self) {
         self.wait_queue.notify_one_allow_isr()
     }
+
+    /// Wake up all waiting tasks. Allowed to be invoked in ISR context.
+    pub fn notify_all_allow_isr(&self) {
+        self.wait_queue.notify_all_allow_isr();
+    }
+}
+
+#[cfg(feature = "soft_lock_stats")]
+impl CondVar {
+    /// Return the contention statistics of the condition variable. See
+    /// [`soft_lock_stats`](crate::debug::soft_lock_stats).
+    pub fn soft_lock_stats(&self) -> crate::debug::soft_lock_stats::SoftLockStats {
+        self.wait_queue.soft_lock_stats()
+    }
 }
@@ -0,0 +1,81 @@
+//! The implementation of the `critical-section` crate, enabled by the
+//! `critical-section` feature.
+//!
+//! Crates in the embedded ecosystem, e.g., `portable-atomic` and `heapless`,
+//! protect their shared state with `critical_section::with`. The usual
+//! implementation for Cortex-M disables IRQs, which would break Hopter's
+//! zero-latency IRQ handling. Instead, a critical section entered by a task
+//! suspends the scheduler, so that no other task can run until it exits.
+//! IRQs are never masked.
+//!
+//! Like the full access of a [`SoftLock`](super::SoftLock), the critical
+//! section is owned by one context at a time, and the owner may enter it
+//! again in a nested way. Unlike a soft lock, the closure run in a critical
+//! section cannot be pended, so a context entering the critical section
+//! while another context owns it is a fatal error. This happens only when
+//! an ISR preempts a task or a lower priority ISR inside a critical section.
+//! Data accessed in critical sections should thus be shared only among
+//! tasks, or only within a single ISR. Data shared between tasks and ISRs
+//! should be protected with a soft lock, or with a [`SpinIrqSafe`] that
+//! explicitly masks the involved IRQs.
+//!
+//! NOTE: *must not* block inside a critical section, e.g., by sleeping or
+//! waiting for a [`Mailbox`](super::Mailbox).
+//!
+//! [`SpinIrqSafe`]: super::SpinIrqSafe
+
+use super::Holdable;
+use crate::{
+    schedule::{current, scheduler::Scheduler},
+    unrecoverable,
+};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// No context owns the critical section.
+const NO_OWNER: u32 = u32::MAX;
+
+/// The exception number of the context owning the critical section, or 0 if
+/// it is owned by a task. Because the scheduler is suspended while a task
+/// owns it, the owning task is always the currently running task.
+static OWNER: AtomicU32 = AtomicU32::new(NO_OWNER);
+
+struct HopterCriticalSection;
+
+critical_section::set_impl!(HopterCriticalSection);
+
+unsafe impl critical_section::Impl for HopterCriticalSection {
+    /// Enter the critical section. Return `true` if the current context
+    /// becomes the owner, or `false` if it is already the owner, i.e., the
+    /// critical section is nested.
+    unsafe fn acquire() -> bool {
+        let context = current::exception_number();
+
+        // Keep other tasks from running. The scheduler stays suspended until
+        // the matching release.
+        if context == 0 {
+            core::mem::forget(Scheduler::hold());
+        }
+
+        match OWNER.compare_exchange(NO_OWNER, context, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => true,
+            Err(owner) if owner == context => false,
+            // We preempted the owner. We cannot wait for it to exit the
+            // critical section.
+            Err(_) => unrecoverable::die(),
+        }
+    }
+
+    /// Exit the critical section. Give up the ownership if the matching
+    /// acquire became the owner.
+    unsafe fn release(became_owner: bool) {
+        if became_owner {
+            OWNER.store(NO_OWNER, Ordering::SeqCst);
+        }
+
+        // Resume the scheduler suspended by the matching acquire. A context
+        // switch requested in the critical section happens now.
+        if current::exception_number() == 0 {
+            Scheduler::force_unhold();
+        }
+    }
+}
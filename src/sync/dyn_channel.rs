@@ -0,0 +1,309 @@
+use super::{Semaphore, SpinSchedSafe, SpinSchedSafeGuard};
+use crate::{config, schedule::current, unrecoverable::Lethal};
+use alloc::{collections::VecDeque, sync::Arc};
+
+/// A multi-producer multi-consumer channel whose buffer is allocated on the
+/// heap and grows on demand.
+struct DynChannel<T> {
+    /// The buffered elements. The scheduler is suspended while an element is
+    /// being inserted or removed, and while the elements are moved into a
+    /// grown buffer, so tasks never contend for the lock. An ISR preempting
+    /// the lock owner gives up the operation instead of waiting.
+    buffer: SpinSchedSafe<VecDeque<T>>,
+    /// The largest capacity the buffer may grow to.
+    max_capacity: usize,
+    /// The semaphore counting on the empty slots, up to the largest
+    /// capacity.
+    sem_empty: Semaphore,
+    /// The semaphore counting on the occupied slots.
+    sem_occupied: Semaphore,
+}
+
+/// A producer of a channel created by [`create_dyn_channel`]. It can be
+/// cloned.
+pub struct DynProducer<T> {
+    channel: Arc<DynChannel<T>>,
+}
+
+/// The consumer of a channel created by [`create_dyn_channel`]. It can be
+/// cloned.
+pub struct DynConsumer<T> {
+    channel: Arc<DynChannel<T>>,
+}
+
+/// Cloning the producer does not require cloning the elements.
+impl<T> Clone for DynProducer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+/// Cloning the consumer does not require cloning the elements.
+impl<T> Clone for DynConsumer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> DynChannel<T> {
+    fn new(initial_capacity: usize, max_capacity: usize) -> Self {
+        Self {
+            buffer: SpinSchedSafe::new(VecDeque::with_capacity(initial_capacity.min(max_capacity))),
+            max_capacity,
+            sem_empty: Semaphore::new(max_capacity, max_capacity),
+            sem_occupied: Semaphore::new(max_capacity, 0),
+        }
+    }
+
+    /// Lock the buffer. Return `None` if the current context is an ISR
+    /// preempting the lock owner.
+    fn lock_buffer(&self) -> Option<SpinSchedSafeGuard<'_, VecDeque<T>>> {
+        if current::is_in_isr_context() {
+            self.buffer.try_lock()
+        } else {
+            Some(self.buffer.lock_now_or_die())
+        }
+    }
+
+    /// Insert an element into the buffer if there is allocated room for it
+    /// and the buffer can be locked. Otherwise, return the element with
+    /// `Err`. The caller must have taken an empty slot.
+    fn try_insert(&self, data: T) -> Result<(), T> {
+        let Some(mut buffer) = self.lock_buffer() else {
+            return Err(data);
+        };
+        if buffer.len() == buffer.capacity() {
+            return Err(data);
+        }
+        buffer.push_back(data);
+        Ok(())
+    }
+
+    /// Insert an element into the buffer, growing the buffer if necessary.
+    /// The caller must have taken an empty slot, so the buffer never needs
+    /// to grow beyond the largest capacity.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    fn insert(&self, mut data: T) {
+        loop {
+            match self.try_insert(data) {
+                Ok(()) => return,
+                Err(rejected) => data = rejected,
+            }
+            self.grow();
+        }
+    }
+
+    /// Double the capacity of the buffer, up to the largest capacity. The
+    /// new buffer is allocated before locking the buffer, and the old one is
+    /// freed after unlocking it.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    fn grow(&self) {
+        let capacity = self.buffer.lock_now_or_die().capacity();
+        let new_capacity = capacity.saturating_mul(2).max(1).min(self.max_capacity);
+        let mut grown = VecDeque::with_capacity(new_capacity);
+
+        let old = {
+            let mut buffer = self.buffer.lock_now_or_die();
+            // Another task may have grown the buffer in between.
+            if buffer.capacity() >= new_capacity {
+                return;
+            }
+            grown.extend(buffer.drain(..));
+            core::mem::replace(&mut *buffer, grown)
+        };
+        drop(old);
+    }
+
+    /// Remove the front element from the buffer. Return `None` if the buffer
+    /// cannot be locked. The caller must have taken an occupied slot.
+    fn try_remove(&self) -> Option<T> {
+        Some(self.lock_buffer()?.pop_front().unwrap_or_die())
+    }
+
+    /// Remove the front element from the buffer. The caller must have taken
+    /// an occupied slot.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    fn remove(&self) -> T {
+        self.buffer.lock_now_or_die().pop_front().unwrap_or_die()
+    }
+
+    fn push(&self, data: T) {
+        self.sem_empty.down();
+        let slot = self.sem_empty.give_back_on_unwind();
+        self.insert(data);
+        slot.disarm();
+        self.sem_occupied.up();
+    }
+
+    fn push_timeout(&self, data: T, timeout_ms: u32) -> Result<(), T> {
+        if self.sem_empty.down_timeout(timeout_ms).is_err() {
+            return Err(data);
+        }
+        let slot = self.sem_empty.give_back_on_unwind();
+        self.insert(data);
+        slot.disarm();
+        self.sem_occupied.up();
+        Ok(())
+    }
+
+    fn try_push_allow_isr(&self, data: T) -> Result<(), T> {
+        if self.sem_empty.try_down_allow_isr().is_err() {
+            return Err(data);
+        }
+
+        let slot = self.sem_empty.give_back_on_unwind();
+        if current::is_in_isr_context() {
+            // Growing the buffer requires allocation, which is not allowed
+            // in ISR context. Give back the empty slot if there is no room or
+            // the ISR preempts the lock owner.
+            if let Err(data) = self.try_insert(data) {
+                slot.disarm();
+                self.sem_empty.try_up_allow_isr().unwrap_or_die();
+                return Err(data);
+            }
+        } else {
+            self.insert(data);
+        }
+        slot.disarm();
+
+        self.sem_occupied.try_up_allow_isr().unwrap_or_die();
+        Ok(())
+    }
+
+    fn pop(&self) -> T {
+        self.sem_occupied.down();
+        let slot = self.sem_occupied.give_back_on_unwind();
+        let data = self.remove();
+        slot.disarm();
+        self.sem_empty.up();
+        data
+    }
+
+    fn pop_timeout(&self, timeout_ms: u32) -> Option<T> {
+        self.sem_occupied.down_timeout(timeout_ms).ok()?;
+        let slot = self.sem_occupied.give_back_on_unwind();
+        let data = self.remove();
+        slot.disarm();
+        self.sem_empty.up();
+        Some(data)
+    }
+
+    fn try_pop_allow_isr(&self) -> Option<T> {
+        if self.sem_occupied.try_down_allow_isr().is_err() {
+            return None;
+        }
+        let slot = self.sem_occupied.give_back_on_unwind();
+        let data = self.try_remove();
+        slot.disarm();
+        let Some(data) = data else {
+            // The ISR preempts the lock owner. Give back the occupied slot.
+            self.sem_occupied.try_up_allow_isr().unwrap_or_die();
+            return None;
+        };
+        self.sem_empty.try_up_allow_isr().unwrap_or_die();
+        Some(data)
+    }
+}
+
+impl<T> DynProducer<T> {
+    /// Push an element into the corresponding channel. If the channel is
+    /// already full, block until there is an empty slot and it can proceed.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn produce(&self, data: T) {
+        self.channel.push(data)
+    }
+
+    /// Push an element into the corresponding channel. If the channel is
+    /// already full, block until there is an empty slot or the elapsed
+    /// waiting time reaches timeout. Return `Ok` if the element is pushed, or
+    /// the element with `Err` if timed out.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn produce_timeout(&self, data: T, timeout_ms: u32) -> Result<(), T> {
+        self.channel.push_timeout(data, timeout_ms)
+    }
+
+    /// Push an element into the corresponding channel. If the channel is
+    /// already full, return the element with `Err`. Otherwise, push in the
+    /// element and return `Ok`.
+    ///
+    /// Calling this method in ISR context is allowed. However, an ISR cannot
+    /// grow the buffer, so it also gets `Err` when the buffer allocated so
+    /// far is full. It also gets `Err` when it preempts a task inserting or
+    /// removing an element.
+    pub fn try_produce_allow_isr(&self, data: T) -> Result<(), T> {
+        self.channel.try_push_allow_isr(data)
+    }
+}
+
+impl<T> DynConsumer<T> {
+    /// Pop an element from the corresponding channel. If the channel is
+    /// empty, block until there is an element and it can proceed.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn consume(&self) -> T {
+        self.channel.pop()
+    }
+
+    /// Pop an element from the corresponding channel. If the channel is
+    /// empty, block until there is an element or the elapsed waiting time
+    /// reaches timeout. Return the element with `Some`, or `None` if timed
+    /// out.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn consume_timeout(&self, timeout_ms: u32) -> Option<T> {
+        self.channel.pop_timeout(timeout_ms)
+    }
+
+    /// Try to pop an element from the corresponding channel. If the channel
+    /// is empty, return `None`. Otherwise, return the element with `Some`.
+    ///
+    /// Calling this method in ISR context is allowed. However, an ISR also
+    /// gets `None` when it preempts a task inserting or removing an element.
+    pub fn try_consume_allow_isr(&self) -> Option<T> {
+        self.channel.try_pop_allow_isr()
+    }
+}
+
+/// Create a channel whose buffer is allocated on the heap with the given
+/// initial capacity. Return a producer and a consumer corresponding with the
+/// channel.
+///
+/// When a task produces into a channel whose buffer is full, the buffer
+/// doubles its capacity, up to
+/// [`DYN_CHANNEL_MAX_CAPACITY`](config::DYN_CHANNEL_MAX_CAPACITY) elements.
+/// Only then is the channel considered full. In contrast, the buffer of a
+/// channel created by [`create_channel`](super::create_channel) is sized for
+/// the worst case upfront. The buffer never shrinks.
+///
+/// # Example
+/// ```rust
+/// let (producer, consumer) = sync::create_dyn_channel::<Event>(4);
+///
+/// // Bursts of events grow the buffer instead of blocking the producer.
+/// for event in burst {
+///     producer.produce(event);
+/// }
+///
+/// // In another task.
+/// let event = consumer.consume();
+/// ```
+pub fn create_dyn_channel<T>(initial_capacity: usize) -> (DynProducer<T>, DynConsumer<T>) {
+    let chan = Arc::new(DynChannel::new(
+        initial_capacity,
+        config::DYN_CHANNEL_MAX_CAPACITY,
+    ));
+    let producer = DynProducer {
+        channel: chan.clone(),
+    };
+    let consumer = DynConsumer { channel: chan };
+    (producer, consumer)
+}
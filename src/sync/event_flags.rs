@@ -0,0 +1,304 @@
+use super::{
+    Access, AllowPendOp, Mailbox, RefCellSchedSafe, RunPendedOp, SoftLock, Spin, UnwindGuard,
+};
+use crate::unrecoverable;
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// A group of 32 event flags, similar to FreeRTOS event groups. Tasks wait
+/// until any or all of the flags in a mask are set, and tasks or ISRs set
+/// and clear the flags.
+///
+/// Setting flags does not consume them. A flag stays set until it is
+/// explicitly cleared with [`clear_allow_isr`](Self::clear_allow_isr), so
+/// every task waiting for it is woken up.
+///
+/// Like the [`Mailbox`], an [`EventFlags`] never masks IRQs. If an ISR sets
+/// the flags while a task is modifying the list of waiting tasks, the task
+/// wakes up the tasks waiting for the flags on behalf of the ISR.
+///
+/// # Example
+/// ```rust
+/// const RX_DONE: u32 = 1 << 0;
+/// const TX_DONE: u32 = 1 << 1;
+/// static EVENTS: EventFlags = EventFlags::new();
+///
+/// // In an IRQ handler.
+/// EVENTS.set_allow_isr(RX_DONE);
+///
+/// // In a task.
+/// if let Some(flags) = EVENTS.wait_any_timeout(RX_DONE | TX_DONE, 100) {
+///     EVENTS.clear_allow_isr(flags & (RX_DONE | TX_DONE));
+/// }
+/// ```
+pub struct EventFlags {
+    inner: RefCellSchedSafe<SoftLock<Inner>>,
+}
+
+struct Inner {
+    /// The flags. They are updated without the full access, so that an ISR
+    /// never needs to pend an update of the flags.
+    flags: AtomicU32,
+    /// The tasks waiting for the flags. The spin lock around it is only for
+    /// sanity check. This field should not be accessed concurrently.
+    waiters: Spin<Vec<Arc<Waiter>>>,
+}
+
+/// A task waiting for the flags.
+struct Waiter {
+    /// The flags that the task waits for.
+    mask: u32,
+    /// Whether all flags in the mask must be set, or only any of them.
+    wait_all: bool,
+    /// The flags when the task's condition is met, returned to the task.
+    flags: AtomicU32,
+    /// The task blocks on the mailbox, which also handles the timeout.
+    mailbox: Mailbox,
+}
+
+/// Representing full access to all fields of the [`EventFlags`].
+struct InnerFullAccessor<'a> {
+    flags: &'a AtomicU32,
+    waiters: &'a Spin<Vec<Arc<Waiter>>>,
+}
+
+/// Representing pend-only access to the [`EventFlags`]. Only the flags can
+/// be updated, and the full access owner wakes up the waiting tasks later.
+struct InnerPendAccessor<'a> {
+    flags: &'a AtomicU32,
+}
+
+/// Bind the accessor types.
+impl<'a> AllowPendOp<'a> for Inner {
+    type FullAccessor = InnerFullAccessor<'a>;
+    type PendOnlyAccessor = InnerPendAccessor<'a>;
+    fn full_access(&'a self) -> Self::FullAccessor {
+        Self::FullAccessor {
+            flags: &self.flags,
+            waiters: &self.waiters,
+        }
+    }
+
+    fn pend_only_access(&'a self) -> Self::PendOnlyAccessor {
+        Self::PendOnlyAccessor { flags: &self.flags }
+    }
+}
+
+/// A pended operation is always setting flags. Wake up the tasks whose
+/// conditions are met by the new flags.
+impl<'a> RunPendedOp for InnerFullAccessor<'a> {
+    fn run_pended_op(&mut self) {
+        self.wake_satisfied();
+    }
+}
+
+impl<'a> InnerFullAccessor<'a> {
+    /// Wake up and remove the waiting tasks whose conditions are met by the
+    /// current flags.
+    fn wake_satisfied(&self) {
+        let flags = self.flags.load(Ordering::SeqCst);
+        self.waiters.lock_now_or_die().retain(|waiter| {
+            if !waiter.is_satisfied(flags) {
+                return true;
+            }
+            waiter.flags.store(flags, Ordering::SeqCst);
+            waiter.mailbox.notify_allow_isr();
+            false
+        });
+    }
+}
+
+impl Inner {
+    const fn new() -> Self {
+        Self {
+            flags: AtomicU32::new(0),
+            waiters: Spin::new(Vec::new()),
+        }
+    }
+}
+
+impl Waiter {
+    /// Return if the condition of the waiting task is met by the flags.
+    fn is_satisfied(&self, flags: u32) -> bool {
+        if self.wait_all {
+            flags & self.mask == self.mask
+        } else {
+            flags & self.mask != 0
+        }
+    }
+}
+
+impl EventFlags {
+    /// Create a new [`EventFlags`] with all flags cleared.
+    pub const fn new() -> Self {
+        Self {
+            inner: RefCellSchedSafe::new(SoftLock::new(Inner::new())),
+        }
+    }
+
+    /// Return the current flags. Note that the read value may become stale
+    /// immediately after it is read.
+    pub fn get(&self) -> u32 {
+        self.inner.with_suspended_scheduler(|event_flags, _| {
+            event_flags.with_access(|access| match access {
+                Access::Full { full_access } => full_access.flags.load(Ordering::SeqCst),
+                Access::PendOnly { pend_access } => pend_access.flags.load(Ordering::SeqCst),
+            })
+        })
+    }
+
+    /// Set the flags in the mask, and wake up the tasks whose conditions are
+    /// met.
+    ///
+    /// This method is allowed in ISR context.
+    pub fn set_allow_isr(&self, mask: u32) {
+        self.inner.with_suspended_scheduler(|event_flags, _| {
+            event_flags.with_access(|access| match access {
+                // If we have full access to the inner fields, we directly wake
+                // up the waiting tasks.
+                Access::Full { full_access } => {
+                    full_access.flags.fetch_or(mask, Ordering::SeqCst);
+                    full_access.wake_satisfied();
+                }
+                // If other context is running with the full access and we
+                // preempt it, we get pend-only access. We only set the flags,
+                // and the full access owner will wake up the waiting tasks on
+                // our behalf.
+                Access::PendOnly { pend_access } => {
+                    pend_access.flags.fetch_or(mask, Ordering::SeqCst);
+                }
+            })
+        });
+    }
+
+    /// Clear the flags in the mask. Return the flags before clearing.
+    ///
+    /// This method is allowed in ISR context.
+    pub fn clear_allow_isr(&self, mask: u32) -> u32 {
+        self.inner.with_suspended_scheduler(|event_flags, _| {
+            event_flags.with_access(|access| match access {
+                Access::Full { full_access } => {
+                    full_access.flags.fetch_and(!mask, Ordering::SeqCst)
+                }
+                Access::PendOnly { pend_access } => {
+                    pend_access.flags.fetch_and(!mask, Ordering::SeqCst)
+                }
+            })
+        })
+    }
+
+    /// Block the calling task until any flag in the mask is set. Return the
+    /// flags when the task's condition is met.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn wait_any(&self, mask: u32) -> u32 {
+        // Cannot time out without a timeout.
+        self.wait(mask, false, None).unwrap_or_default()
+    }
+
+    /// Block the calling task until all flags in the mask are set. Return the
+    /// flags when the task's condition is met.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn wait_all(&self, mask: u32) -> u32 {
+        // Cannot time out without a timeout.
+        self.wait(mask, true, None).unwrap_or_default()
+    }
+
+    /// Block the calling task until any flag in the mask is set or the
+    /// elapsed waiting time reaches timeout.
+    ///
+    /// Return the flags within `Some` when the task's condition is met, or
+    /// `None` if timed out.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn wait_any_timeout(&self, mask: u32, timeout_ms: u32) -> Option<u32> {
+        self.wait(mask, false, Some(timeout_ms))
+    }
+
+    /// Block the calling task until all flags in the mask are set or the
+    /// elapsed waiting time reaches timeout.
+    ///
+    /// Return the flags within `Some` when the task's condition is met, or
+    /// `None` if timed out.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn wait_all_timeout(&self, mask: u32, timeout_ms: u32) -> Option<u32> {
+        self.wait(mask, true, Some(timeout_ms))
+    }
+
+    /// Block the calling task until its condition is met or it times out.
+    fn wait(&self, mask: u32, wait_all: bool, timeout_ms: Option<u32>) -> Option<u32> {
+        unrecoverable::die_if_in_isr();
+
+        let waiter = Arc::new(Waiter {
+            mask,
+            wait_all,
+            flags: AtomicU32::new(0),
+            mailbox: Mailbox::new(),
+        });
+
+        // Suspend scheduling and acquire full access to the fields. If the
+        // condition is not met yet, register the waiter.
+        let satisfied = self.inner.with_suspended_scheduler(|event_flags, _| {
+            event_flags.must_with_full_access(|full_access| {
+                let flags = full_access.flags.load(Ordering::SeqCst);
+                if waiter.is_satisfied(flags) {
+                    return Some(flags);
+                }
+                full_access.waiters.lock_now_or_die().push(waiter.clone());
+                None
+            })
+        });
+        if satisfied.is_some() {
+            return satisfied;
+        }
+
+        // If the task is unwound while blocked, e.g., when it is terminated,
+        // do not leave the waiter registered.
+        let registered = UnwindGuard::new(|| {
+            self.remove_waiter(&waiter);
+        });
+
+        // The mailbox counts the notification if the flags are set before the
+        // task blocks on it.
+        let notified = match timeout_ms {
+            Some(timeout_ms) => waiter.mailbox.wait_until_timeout(timeout_ms),
+            None => {
+                waiter.mailbox.wait();
+                true
+            }
+        };
+        registered.disarm();
+        if notified {
+            return Some(waiter.flags.load(Ordering::SeqCst));
+        }
+
+        // Timed out. The flags may have been set right after the timeout. If
+        // the waiter is no longer registered, its condition was met.
+        (!self.remove_waiter(&waiter)).then(|| waiter.flags.load(Ordering::SeqCst))
+    }
+
+    /// Remove the waiter from the list of waiting tasks. Return whether it
+    /// was still registered.
+    fn remove_waiter(&self, waiter: &Arc<Waiter>) -> bool {
+        self.inner.with_suspended_scheduler(|event_flags, _| {
+            event_flags.must_with_full_access(|full_access| {
+                let mut waiters = full_access.waiters.lock_now_or_die();
+                let len = waiters.len();
+                waiters.retain(|registered| !Arc::ptr_eq(registered, waiter));
+                waiters.len() != len
+            })
+        })
+    }
+}
+
+#[cfg(feature = "soft_lock_stats")]
+impl EventFlags {
+    /// Return the contention statistics of the event flags. See
+    /// [`soft_lock_stats`](crate::debug::soft_lock_stats).
+    pub fn soft_lock_stats(&self) -> crate::debug::soft_lock_stats::SoftLockStats {
+        self.inner
+            .with_suspended_scheduler(|inner, _| inner.stats())
+    }
+}
@@ -0,0 +1,193 @@
+use super::{RetryCounter, Semaphore};
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// The memory of a buffer, word aligned for DMA transfers.
+#[repr(align(4))]
+struct Storage<const SIZE: usize>(UnsafeCell<[u8; SIZE]>);
+
+/// A fixed pool of `COUNT` buffers of `SIZE` bytes each, for handing off
+/// data between tasks and ISRs without copying it. `COUNT` must not exceed
+/// 32.
+///
+/// A producer task or ISR acquires a [`HandoffBuffer`] from the pool, fills
+/// it, e.g., by a DMA transfer into [`as_mut_ptr`](HandoffBuffer::as_mut_ptr),
+/// and sends it through a channel to a consumer, which owns the buffer
+/// exclusively from then on. The buffer returns to the pool when the consumer
+/// drops it, including when the consumer task is unwound.
+///
+/// The pool should be a `static` so that the buffers stay at fixed addresses
+/// outside any task stack, which DMA controllers require.
+///
+/// # Example
+/// ```rust
+/// static POOL: BufferPool<64, 4> = BufferPool::new();
+///
+/// // In the DMA transfer complete IRQ handler.
+/// if let Some(mut buf) = POOL.try_acquire_allow_isr() {
+///     // Copy or transfer the received bytes into `buf`.
+///     buf.set_len(received_len);
+///     let _ = producer.try_produce_allow_isr(buf);
+/// }
+///
+/// // In the consumer task.
+/// let buf = consumer.consume();
+/// parse(&buf);
+/// ```
+pub struct BufferPool<const SIZE: usize, const COUNT: usize> {
+    buffers: [Storage<SIZE>; COUNT],
+    /// Bit `i` is set if the `i`-th buffer is handed out.
+    in_use: AtomicU32,
+    /// Count the buffers not handed out.
+    free: Semaphore,
+}
+
+unsafe impl<const SIZE: usize, const COUNT: usize> Sync for BufferPool<SIZE, COUNT> {}
+
+/// A buffer acquired from a [`BufferPool`], owned exclusively by its holder.
+/// It dereferences to the filled part of the buffer, whose length is set with
+/// [`set_len`](Self::set_len). The buffer returns to the pool when dropped.
+pub struct HandoffBuffer<const SIZE: usize, const COUNT: usize> {
+    pool: &'static BufferPool<SIZE, COUNT>,
+    index: usize,
+    len: usize,
+}
+
+unsafe impl<const SIZE: usize, const COUNT: usize> Send for HandoffBuffer<SIZE, COUNT> {}
+
+impl<const SIZE: usize, const COUNT: usize> BufferPool<SIZE, COUNT> {
+    const VALID_COUNT: () = assert!(COUNT <= 32, "a buffer pool has at most 32 buffers");
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const STORAGE_INIT: Storage<SIZE> = Storage(UnsafeCell::new([0; SIZE]));
+
+    /// Create a new pool with all buffers free.
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::VALID_COUNT;
+        Self {
+            buffers: [Self::STORAGE_INIT; COUNT],
+            in_use: AtomicU32::new(0),
+            free: Semaphore::new(COUNT, COUNT),
+        }
+    }
+
+    /// Return the number of buffers not handed out. Note that the read value
+    /// may become stale immediately after it is read.
+    pub fn available(&self) -> usize {
+        self.free.count()
+    }
+
+    /// Acquire a buffer. If all buffers are handed out, block until one is
+    /// returned to the pool.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn acquire(&'static self) -> HandoffBuffer<SIZE, COUNT> {
+        self.free.down();
+        self.take_free_buffer()
+    }
+
+    /// Acquire a buffer. If all buffers are handed out, return `None`.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn try_acquire_allow_isr(&'static self) -> Option<HandoffBuffer<SIZE, COUNT>> {
+        self.free.try_down_allow_isr().ok()?;
+        Some(self.take_free_buffer())
+    }
+
+    /// Mark a free buffer as in use and return it. The caller must have
+    /// decremented the semaphore, so a free buffer exists.
+    fn take_free_buffer(&'static self) -> HandoffBuffer<SIZE, COUNT> {
+        let mut retries = RetryCounter::new();
+        loop {
+            let in_use = self.in_use.load(Ordering::SeqCst);
+            let index = in_use.trailing_ones() as usize;
+            if self
+                .in_use
+                .compare_exchange(
+                    in_use,
+                    in_use | (1 << index),
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                )
+                .is_ok()
+            {
+                return HandoffBuffer {
+                    pool: self,
+                    index,
+                    len: 0,
+                };
+            }
+
+            // Another context took or returned a buffer in between.
+            retries.retry();
+        }
+    }
+}
+
+impl<const SIZE: usize, const COUNT: usize> HandoffBuffer<SIZE, COUNT> {
+    /// Return the length of the filled part of the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return if no byte of the buffer is filled.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the size of the buffer.
+    pub const fn capacity(&self) -> usize {
+        SIZE
+    }
+
+    /// Set the length of the filled part of the buffer, capped at its
+    /// capacity.
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len.min(SIZE);
+    }
+
+    /// Return the whole buffer regardless of the filled length, for filling
+    /// it.
+    pub fn as_mut_array(&mut self) -> &mut [u8; SIZE] {
+        unsafe { &mut *self.pool.buffers[self.index].0.get() }
+    }
+
+    /// Return the start address of the buffer, for programming a DMA
+    /// transfer. The buffer is word aligned and stays at the address until it
+    /// returns to the pool.
+    ///
+    /// The holder must keep the buffer until the transfer completes.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.pool.buffers[self.index].0.get() as *mut u8
+    }
+}
+
+impl<const SIZE: usize, const COUNT: usize> Deref for HandoffBuffer<SIZE, COUNT> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        let buffer = unsafe { &*self.pool.buffers[self.index].0.get() };
+        &buffer[..self.len]
+    }
+}
+
+impl<const SIZE: usize, const COUNT: usize> DerefMut for HandoffBuffer<SIZE, COUNT> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        let len = self.len;
+        &mut self.as_mut_array()[..len]
+    }
+}
+
+impl<const SIZE: usize, const COUNT: usize> Drop for HandoffBuffer<SIZE, COUNT> {
+    fn drop(&mut self) {
+        self.pool
+            .in_use
+            .fetch_and(!(1 << self.index), Ordering::SeqCst);
+        // Cannot fail because the buffer was counted out of the semaphore.
+        let _ = self.pool.free.try_up_allow_isr();
+    }
+}
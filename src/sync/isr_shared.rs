@@ -0,0 +1,151 @@
+use crate::{schedule::current, unrecoverable};
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+use cortex_m::{
+    interrupt::InterruptNumber,
+    peripheral::{scb::VectActive, NVIC, SCB},
+};
+
+/// The ID of the owner task before any task accesses the value.
+const NO_OWNER: u8 = u8::MAX;
+
+/// A container sharing a value, e.g., a driver object, between one task and
+/// the handler of one IRQ, replacing a `static mut` global accessed with
+/// `unsafe` code on both sides.
+///
+/// The first task accessing the value with [`with_task`](Self::with_task)
+/// becomes its owner, and the handler of the IRQ given at construction
+/// accesses it with [`with_isr`](Self::with_isr). Access from any other
+/// task panics, and access from any other ISR is unrecoverable. Because
+/// only these two contexts ever touch the value, it does not need to be
+/// `Send`.
+///
+/// The IRQ is never masked. If the IRQ preempts the owner task while the
+/// task is accessing the value, the handler is granted no access, similar
+/// to pend-only access to other primitives. The IRQ is then pended again
+/// when the task finishes its access, so the handler runs again and gets
+/// access.
+///
+/// # Example
+/// ```rust
+/// static UART_RX: IsrShared<Option<Rx<USART1>>, Interrupt> =
+///     IsrShared::new(Interrupt::USART1, None);
+///
+/// // In the task initializing and reconfiguring the UART.
+/// UART_RX.with_task(|rx| *rx = Some(serial.split().1));
+///
+/// #[handler(USART1)]
+/// fn usart1_handler() {
+///     // `None` if the handler preempted the task accessing the receiver.
+///     // The handler will run again once the task finishes.
+///     UART_RX.with_isr(|rx| {
+///         if let Some(byte) = rx.as_mut().and_then(|rx| rx.read().ok()) {
+///             // ...
+///         }
+///     });
+/// }
+/// ```
+pub struct IsrShared<T, I> {
+    value: UnsafeCell<T>,
+    /// The IRQ whose handler may access the value.
+    irq: I,
+    /// The ID of the owner task, or [`NO_OWNER`].
+    owner: AtomicU8,
+    /// Whether the owner task is accessing the value.
+    busy: AtomicBool,
+    /// Whether the handler was denied access while the owner task was
+    /// accessing the value.
+    pended: AtomicBool,
+}
+
+/// The value is accessed only by the owner task and the handler of the IRQ,
+/// and never by both at the same time.
+unsafe impl<T, I: Sync> Sync for IsrShared<T, I> {}
+
+/// Mark the end of the access of the owner task when its access returns or
+/// is unwound, and pend the IRQ again if the handler was denied access.
+struct TaskAccess<'a, T, I: InterruptNumber> {
+    shared: &'a IsrShared<T, I>,
+}
+
+impl<T, I: InterruptNumber> Drop for TaskAccess<'_, T, I> {
+    fn drop(&mut self) {
+        self.shared.busy.store(false, Ordering::SeqCst);
+        if self.shared.pended.swap(false, Ordering::SeqCst) {
+            NVIC::pend(self.shared.irq);
+        }
+    }
+}
+
+impl<T, I: InterruptNumber> IsrShared<T, I> {
+    /// Create a new container shared between a task and the handler of
+    /// `irq`.
+    pub const fn new(irq: I, value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+            irq,
+            owner: AtomicU8::new(NO_OWNER),
+            busy: AtomicBool::new(false),
+            pended: AtomicBool::new(false),
+        }
+    }
+
+    /// Run `op` with exclusive access to the value. The calling task becomes
+    /// the owner if there is no owner yet.
+    ///
+    /// Panic if another task owns the value or the call is nested in `op`.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn with_task<F, R>(&self, op: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        unrecoverable::die_if_in_isr();
+
+        let id = current::with_cur_task(|task| task.get_id());
+        match self
+            .owner
+            .compare_exchange(NO_OWNER, id, Ordering::SeqCst, Ordering::SeqCst)
+        {
+            Ok(_) => {}
+            Err(owner) if owner == id => {}
+            Err(_) => panic!("Accessing a value owned by another task."),
+        }
+
+        if self.busy.swap(true, Ordering::SeqCst) {
+            panic!("Nested access to an ISR shared value.");
+        }
+        let _access = TaskAccess { shared: self };
+
+        // Safety: The handler does not access the value while `busy` is set,
+        // and it runs to completion before the task resumes.
+        op(unsafe { &mut *self.value.get() })
+    }
+
+    /// Run `op` with exclusive access to the value and return its result
+    /// within `Some`. Return `None` without running `op` if the handler
+    /// preempted the owner task accessing the value, in which case the IRQ
+    /// is pended again when the task finishes its access.
+    ///
+    /// Calling this method outside the handler of the IRQ is unrecoverable.
+    pub fn with_isr<F, R>(&self, op: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        match SCB::vect_active() {
+            VectActive::Interrupt { irqn } if irqn as u16 == self.irq.number() => {}
+            _ => unrecoverable::die(),
+        }
+
+        if self.busy.load(Ordering::SeqCst) {
+            self.pended.store(true, Ordering::SeqCst);
+            return None;
+        }
+
+        // Safety: The owner task is not accessing the value, and it cannot
+        // resume until the handler returns.
+        Some(op(unsafe { &mut *self.value.get() }))
+    }
+}
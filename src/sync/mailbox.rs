@@ -1,4 +1,7 @@
-use super::{Access, AllowPendOp, RefCellSchedSafe, RunPendedOp, SoftLock, Spin};
+use super::{
+    Access, AllowPendOp, CancellationToken, Cancelled, Observers, RefCellSchedSafe, RunPendedOp,
+    SoftLock, Spin,
+};
 use crate::{
     interrupt::context_switch,
     schedule::{current, scheduler::Scheduler},
@@ -29,6 +32,8 @@ use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 /// the notification counter is zero.
 pub struct Mailbox {
     inner: RefCellSchedSafe<SoftLock<Inner>>,
+    /// The wait sets to notify on a notification.
+    pub(super) observers: Observers,
 }
 
 struct Inner {
@@ -166,6 +171,7 @@ impl Mailbox {
     pub const fn new() -> Self {
         Self {
             inner: RefCellSchedSafe::new(SoftLock::new(Inner::new())),
+            observers: Observers::new(),
         }
     }
 
@@ -210,6 +216,11 @@ impl Mailbox {
 
         if should_block {
             context_switch::yield_current_task();
+
+            // The notifier has taken the task off the mailbox, so the task
+            // can be unwound if it has been terminated meanwhile.
+            #[cfg(feature = "unwind")]
+            crate::unwind::forced::unwind_if_requested();
         }
     }
 
@@ -276,7 +287,7 @@ impl Mailbox {
             // waiting time reaches timeout.
 
             // Suspend scheduling and acquire full access to the mailbox fields.
-            self.inner.with_suspended_scheduler(|mailbox, _| {
+            let notified = self.inner.with_suspended_scheduler(|mailbox, _| {
                 mailbox.must_with_full_access(|full_access| {
                     // Clear the waiting task field. This field was not cleared if
                     // the task wakes up because of the timeout.
@@ -285,7 +296,15 @@ impl Mailbox {
                     // Return whether the task wakes up because of notification.
                     full_access.task_notified.load(Ordering::SeqCst)
                 })
-            })
+            });
+
+            // A terminated task is woken up like on a timeout. Unwind it only
+            // after the waiting task field is cleared, so that the mailbox
+            // does not keep referring to the unwound task.
+            #[cfg(feature = "unwind")]
+            crate::unwind::forced::unwind_if_requested();
+
+            notified
         } else {
             // If the task need not block, it consumed a notification count and
             // is considered to be notified.
@@ -293,19 +312,44 @@ impl Mailbox {
         }
     }
 
+    /// Block the calling task if the notification counter is currently zero,
+    /// until the mailbox is notified or the token is cancelled. Return
+    /// `Ok(())` if a notification is received, or `Err(Cancelled)` if
+    /// cancelled.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn wait_cancellable(&self, token: &CancellationToken) -> Result<(), Cancelled> {
+        token.wait_for(self, || self.try_wait_allow_isr().then_some(()))
+    }
+
     /// Make the waiting task ready to run if there is a waiting task on the
     /// [`Mailbox`], or otherwise increment the counter if there is not current
     /// waiting task.
     ///
     /// This method is allowed in ISR context.
     pub fn notify_allow_isr(&self) {
+        self.notify_n_allow_isr(1);
+    }
+
+    /// Post `n` notifications at once. Equivalent to calling
+    /// [`notify_allow_isr`](Self::notify_allow_isr) `n` times, but the
+    /// mailbox fields are accessed only once, e.g., when a DMA-complete ISR
+    /// posts a notification for each transferred byte.
+    ///
+    /// This method is allowed in ISR context.
+    pub fn notify_n_allow_isr(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+
         // Suspend scheduling and get access to the mailbox fields.
         self.inner.with_suspended_scheduler(|mailbox, _| {
             mailbox.with_access(|access| match access {
                 // If we have full access to the inner fields, we directly wake up
-                // the waiting task or increment the counter.
+                // the waiting task with the first notification and add the rest
+                // to the counter.
                 Access::Full { full_access } => {
-                    match full_access.wait_task.lock_now_or_die().take() {
+                    let remaining = match full_access.wait_task.lock_now_or_die().take() {
                         // If there is a waiting task with timeout, wake it up. The
                         // task's ownership is moved from the sleeping queue to the
                         // scheduler's ready queue. See the documentation of
@@ -313,6 +357,7 @@ impl Mailbox {
                         WaitTask::WithTimeout(wait_task) => {
                             time::remove_task_from_sleep_queue_allow_isr(wait_task);
                             full_access.task_notified.store(true, Ordering::SeqCst);
+                            n - 1
                         }
                         // If there is a waiting task without timeout, wake it up. The
                         // task's ownership is moved from this enum variant to the
@@ -320,22 +365,63 @@ impl Mailbox {
                         // `WithoutTimeout` for details.
                         WaitTask::WithoutTimeout(wait_task) => {
                             Scheduler::accept_task(wait_task);
+                            n - 1
                         }
-                        // If there is not a waiting task, increment the counter.
-                        WaitTask::NoTask => {
-                            full_access.count.fetch_add(1, Ordering::SeqCst);
-                        }
-                    }
+                        // If there is not a waiting task, all notifications go to
+                        // the counter.
+                        WaitTask::NoTask => n,
+                    };
+                    full_access.count.fetch_add(remaining, Ordering::SeqCst);
                 }
                 // If other context is running with the full access and we preempt
                 // it, we get pend-only access. We increment the `pending_count` so
                 // that the full access owner can later help us update the counter
                 // or notify the waiting task on behalf.
                 Access::PendOnly { pend_access } => {
-                    pend_access.pending_count.fetch_add(1, Ordering::SeqCst);
+                    pend_access.pending_count.fetch_add(n, Ordering::SeqCst);
                 }
             })
         });
+
+        self.observers.notify_allow_isr();
+    }
+
+    /// If the notification counter is positive, decrement it and return
+    /// `true`. Otherwise, return `false` without blocking.
+    ///
+    /// This method is allowed in ISR context.
+    pub fn try_wait_allow_isr(&self) -> bool {
+        self.inner.with_suspended_scheduler(|mailbox, _| {
+            mailbox.with_access(|access| match access {
+                Access::Full { full_access } => {
+                    if full_access.count.load(Ordering::SeqCst) == 0 {
+                        return false;
+                    }
+                    full_access.count.fetch_sub(1, Ordering::SeqCst);
+                    true
+                }
+                // The preempted context is updating the counter. Treat the
+                // mailbox as having no notification.
+                Access::PendOnly { .. } => false,
+            })
+        })
+    }
+
+    /// Return if the mailbox has received a notification not yet taken by a
+    /// waiting task. Note that the read value may become stale immediately
+    /// after it is read.
+    pub(super) fn has_notification(&self) -> bool {
+        self.inner.with_suspended_scheduler(|mailbox, _| {
+            mailbox.with_access(|access| match access {
+                Access::Full { full_access } => {
+                    full_access.count.load(Ordering::SeqCst) > 0
+                        || full_access.pending_count.load(Ordering::SeqCst) > 0
+                }
+                Access::PendOnly { pend_access } => {
+                    pend_access.pending_count.load(Ordering::SeqCst) > 0
+                }
+            })
+        })
     }
 }
 
@@ -355,3 +441,13 @@ impl WaitTask {
         }
     }
 }
+
+#[cfg(feature = "soft_lock_stats")]
+impl Mailbox {
+    /// Return the contention statistics of the mailbox. See
+    /// [`soft_lock_stats`](crate::debug::soft_lock_stats).
+    pub fn soft_lock_stats(&self) -> crate::debug::soft_lock_stats::SoftLockStats {
+        self.inner
+            .with_suspended_scheduler(|inner, _| inner.stats())
+    }
+}
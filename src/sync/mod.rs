@@ -1,23 +1,180 @@
+//! Synchronization primitives for tasks and ISRs.
+//!
+//! # Bounded ISR operations
+//!
+//! Every method whose name ends with `_allow_isr` completes in bounded time
+//! and never waits for another context, so an ISR calling it is never
+//! delayed by the task or ISR it preempted. When the preempted context is in
+//! the middle of modifying the same primitive, the operation is pended
+//! instead, and the preempted context runs it when it finishes the
+//! modification. The work done by each operation is bounded as follows:
+//!
+//! - [`Mailbox::notify_allow_isr`] and [`Mailbox::notify_n_allow_isr`]:
+//!   constant, plus a scan of the sleeping tasks if the waiting task waits
+//!   with a timeout.
+//! - [`Semaphore::try_up_allow_isr`], [`Semaphore::up_n_allow_isr`],
+//!   [`Semaphore::try_down_allow_isr`], and
+//!   [`Semaphore::try_down_multiple_allow_isr`]: constant, plus a scan of
+//!   the tasks blocked on the semaphore to wake the one with the highest
+//!   priority, or each of them if any task waits for multiple permits or
+//!   the counter changes by more than 1, plus one mailbox notification per
+//!   woken task waiting with a timeout.
+//! - [`Producer::try_produce_allow_isr`],
+//!   [`Consumer::try_consume_allow_isr`], and their counterparts of
+//!   [`BoxProducer`] and [`BoxConsumer`]: two semaphore operations and one
+//!   lock-free queue operation, plus a check of the peeked element when
+//!   consuming.
+//! - [`DynProducer::try_produce_allow_isr`] and
+//!   [`DynConsumer::try_consume_allow_isr`]: two semaphore operations and
+//!   one queue operation, or three semaphore operations if the ISR preempts
+//!   a task modifying the queue. The buffer never grows in ISR context.
+//! - [`PriorityChannel::try_produce_allow_isr`] and
+//!   [`PriorityChannel::try_consume_allow_isr`]: two semaphore operations
+//!   and one binary heap operation, or three semaphore operations if the ISR
+//!   preempts a task modifying the heap.
+//! - [`CondVar::notify_one_allow_isr`] and [`CondVar::notify_all_allow_isr`]:
+//!   a scan of the waiting tasks for each woken task, plus one mailbox
+//!   notification per woken task waiting with a timeout.
+//! - [`EventFlags::set_allow_isr`] and [`EventFlags::clear_allow_isr`]: one
+//!   atomic update of the flags, plus a scan of the waiting tasks and one
+//!   mailbox notification per woken task when setting the flags.
+//! - [`BufferPool::try_acquire_allow_isr`] and dropping a [`HandoffBuffer`]:
+//!   one semaphore operation and one lock-free update of the buffer bitmap.
+//! - [`Once::call_once_allow_isr`] and [`Lazy::get_allow_isr`]: one
+//!   lock-free update of the state, plus the initialization itself and a
+//!   scan of the waiting tasks if the ISR runs the initialization.
+//! - [`RwLock::try_read_allow_isr`] and [`RwLock::try_write_allow_isr`]: one
+//!   lock-free update of the lock state, plus a scan of the tasks blocked on
+//!   the lock when the returned guard is dropped.
+//! - [`Mailbox::try_wait_allow_isr`]: constant.
+//! - [`Watch::send_allow_isr`] and [`Watch::get_allow_isr`]: one clone or
+//!   replacement of the value, plus one mailbox notification per waiting
+//!   task when sending. If the ISR preempts a task accessing the value,
+//!   sending only queues the value for that task to store, and getting
+//!   returns `None`.
+//! - [`SpscRing::push_allow_isr`] and [`SpscRing::try_pop_allow_isr`]: one
+//!   lock-free update of the ring, plus one mailbox notification when
+//!   pushing into an empty ring.
+//! - [`WaitGroup::done_allow_isr`]: one lock-free update of the count,
+//!   plus one mailbox notification per waiting task when finishing the last
+//!   work item.
+//! - [`unpark`]: one mailbox notification.
+//! - [`notify_value_allow_isr`]: one atomic update of the notification
+//!   word, plus one mailbox notification.
+//! - [`CancellationToken::cancel_allow_isr`]: one mailbox notification per
+//!   wait set containing the token, or none if it preempts a task adding
+//!   the token to or removing it from a wait set, which notifies them on its
+//!   behalf.
+//! - [`Topic::publish_allow_isr`]: one channel operation per subscriber, or
+//!   three for a full queue of a subscriber dropping its oldest message.
+//!   If it preempts a task accessing the subscribers, it only queues the
+//!   message for that task to deliver.
+//!
+//! Each scan visits at most [`MAX_TASK_NUMBER`](crate::config::MAX_TASK_NUMBER)
+//! tasks. The lock-free loops inside these operations retry only when an
+//! ISR of higher priority modifies the same primitive in between, so they
+//! retry at most [`ISR_RETRY_LIMIT`](crate::config::ISR_RETRY_LIMIT) times
+//! unless ISRs modify the primitive at a higher rate, which debug builds
+//! assert. [`max_isr_retries`] reports the most retries seen so far. The
+//! `isr_*` benchmarks of the `benches` feature measure the cycles of these
+//! operations in ISR context.
+//!
+//! # Custom ISR-safe primitives
+//!
+//! The primitives above are built on the [`SoftLock`], which drivers can
+//! also use to build their own data structures shared with ISRs without
+//! masking IRQs. The content protected by a soft lock implements
+//! [`AllowPendOp`] to provide a full accessor and a pend-only accessor. An
+//! ISR preempting the owner of the full access gets the pend-only accessor
+//! and pends its operation, which the owner runs through [`RunPendedOp`]
+//! when releasing the full access. The soft lock should be wrapped with a
+//! [`RefCellSchedSafe`] and accessed only with the scheduler suspended.
+//! Lock-free loops of such data structures should count their retries with
+//! a [`RetryCounter`] to get the same bound.
+//!
+//! # Unwind safety
+//!
+//! A task may be unwound in the middle of an operation on a primitive,
+//! e.g., when a function call would overflow its stack. The primitives
+//! restore their state as follows, so that other tasks can keep using them:
+//!
+//! - [`Mutex`] and [`RwLock`] are released and marked poisoned.
+//! - A [`SemaphorePermit`] increments the counter back.
+//! - The channels give back the slot taken from the semaphore if the task
+//!   is unwound before inserting or removing the element.
+//! - [`Mutex::lock_timeout`] and the timed waits of [`CondVar`], including
+//!   the timed semaphore and channel operations, unregister the waiting
+//!   task, or pass on the notification it has taken to another waiting
+//!   task.
+//! - [`Semaphore::down_multiple`] and [`RwLock::write`] stop counting the
+//!   task as a waiter.
+//!
+//! Registrations left by an unwound task in the other primitives, e.g., a
+//! [`WaitGroup`] or an [`EventFlags`], only receive notifications that all
+//! waiting tasks also receive, so they are harmless. A semaphore counter
+//! decremented without a permit is not restored, since the primitive
+//! cannot tell whether the task intended to increment it back.
+
+mod box_channel;
+mod cancellation;
 mod channel;
 mod condvar;
+#[cfg(feature = "critical-section")]
+mod critical_section_impl;
+mod dyn_channel;
+mod event_flags;
+mod handoff;
 mod imported;
+mod isr_shared;
 mod lock_traits;
 mod mailbox;
 mod mutex;
+mod notification;
+mod once;
+mod park;
+mod priority_channel;
+mod pubsub;
 mod refcell_sched_safe;
+mod retry;
+pub mod rpc;
+mod rwlock;
+mod select;
 mod semaphore;
 mod soft_lock;
 mod spin_lock;
+mod spsc_ring;
+mod unwind_guard;
+mod wait_group;
 mod wait_queue;
+mod watch;
 
+pub use crate::schedule::scheduler::SchedSuspendGuard;
+pub use box_channel::*;
+pub use cancellation::*;
 pub use channel::*;
 pub use condvar::*;
+pub use dyn_channel::*;
+pub use event_flags::*;
+pub use handoff::*;
 pub(crate) use imported::*;
+pub use isr_shared::*;
 pub use lock_traits::*;
 pub use mailbox::*;
 pub use mutex::*;
-pub(crate) use refcell_sched_safe::*;
+pub use notification::*;
+pub use once::*;
+pub use park::*;
+pub use priority_channel::*;
+pub use pubsub::*;
+pub use refcell_sched_safe::*;
+pub use retry::*;
+pub use rwlock::*;
+pub use select::*;
 pub use semaphore::*;
-pub(crate) use soft_lock::*;
+pub use soft_lock::*;
 pub use spin_lock::*;
-use wait_queue::*;
+pub use spsc_ring::*;
+use unwind_guard::*;
+pub use wait_group::*;
+pub use wait_queue::*;
+pub use watch::*;
@@ -1,9 +1,12 @@
 use super::{
     CompoundHoldable, GenericSpin, GenericSpinGuard, Holdable, Lockable, SpinSchedSafe,
-    UnlockableGuard, WaitQueue,
+    UnlockableGuard, WaitQueue, WakeupOrder,
 };
 use crate::{
-    interrupt::mask::{HeldInterrupt, RecursivelyMaskable},
+    interrupt::{
+        context_switch,
+        mask::{HeldInterrupt, RecursivelyMaskable},
+    },
     schedule::{
         current,
         scheduler::{SchedSuspendGuard, Scheduler},
@@ -34,6 +37,9 @@ where
     queue: WaitQueue,
     /// The task that is currently holding this mutex.
     owner: SpinSchedSafe<Option<Arc<Task>>>,
+    /// If set, the task locking the mutex is immediately raised to this
+    /// priority until it releases the mutex.
+    ceiling: Option<u8>,
     /// If the mutex is released in an unwinding path, this variable will
     /// be set to `true`. This is just additional information to application
     /// code.
@@ -69,9 +75,26 @@ where
 {
     /// Create a new mutex instance.
     pub const fn new(data: T) -> Self {
+        Self::new_with_options(data, None, WakeupOrder::Priority)
+    }
+
+    /// Create a new mutex instance following the immediate priority ceiling
+    /// protocol with the given ceiling priority.
+    pub const fn with_ceiling(data: T, ceiling: u8) -> Self {
+        Self::new_with_options(data, Some(ceiling), WakeupOrder::Priority)
+    }
+
+    /// Create a new mutex instance waking up the blocked tasks in the given
+    /// order.
+    pub const fn with_wakeup_order(data: T, order: WakeupOrder) -> Self {
+        Self::new_with_options(data, None, order)
+    }
+
+    const fn new_with_options(data: T, ceiling: Option<u8>, order: WakeupOrder) -> Self {
         GenericMutex {
-            queue: WaitQueue::new(),
+            queue: WaitQueue::with_order(order),
             owner: SpinSchedSafe::new(None),
+            ceiling,
             poisoned: AtomicBool::new(false),
             spin_lock: GenericSpin::new(data),
         }
@@ -119,10 +142,16 @@ where
             // switch, so the ISR will always release the lock before any other code can
             // later acquire it. Second, mechanically, reading the current task pointer
             // from an ISR can cause deadlock when the scheduler is setting the current
-            // task pointer.
+            // task pointer. With a ceiling, the owner is also raised to the ceiling
+            // priority.
             .and_then(|guard| {
                 if !current::is_in_isr_context() {
                     current::with_cur_task_arc(|cur_task| {
+                        if let Some(ceiling) = self.ceiling {
+                            cur_task.ceil_priority_to(ceiling);
+                        }
+                        #[cfg(feature = "starvation_monitor")]
+                        cur_task.set_waits_for(None);
                         self.owner.lock_now_or_die().replace(cur_task)
                     });
                 }
@@ -152,17 +181,51 @@ where
             return guard;
         }
 
-        // Priority inheritance.
+        self.inherit_priority();
+
+        // Otherwise, wait on the wait queue until the current task can lock it.
+        // The called method will return the mutex guard upon return.
+        self.queue.wait_until(|| self.try_lock())
+    }
+
+    /// Lock the mutex. If the mutex has already been locked, block until the
+    /// current task can lock it or the elapsed waiting time reaches timeout.
+    /// Return the guard within `Some`, or `None` if timed out.
+    pub fn lock_timeout(&self, timeout_ms: u32) -> Option<GenericMutexGuard<T, H, G>> {
+        // Fast path for no contention.
+        if let Some(guard) = self.try_lock() {
+            return Some(guard);
+        }
+
+        // The owner keeps the inherited priority until it releases the mutex,
+        // even if the current task times out before that.
+        self.inherit_priority();
+
+        // Otherwise, wait on the wait queue until the current task can lock it
+        // or the timeout is reached.
+        let guard = self.queue.wait_until_timeout(|| self.try_lock(), timeout_ms);
+        #[cfg(feature = "starvation_monitor")]
+        if guard.is_none() {
+            current::with_cur_task(|cur_task| cur_task.set_waits_for(None));
+        }
+        guard
+    }
+
+    /// Raise the priority of the owner task to that of the current task if
+    /// the latter is higher.
+    fn inherit_priority(&self) {
         current::with_cur_task(|cur_task| {
             let locked_owner = self.owner.lock_now_or_die();
             if let Some(owner) = locked_owner.as_ref() {
                 owner.ceil_priority_from(cur_task);
+                // Let the starvation monitor follow the chain of lock owners.
+                #[cfg(feature = "starvation_monitor")]
+                {
+                    cur_task.set_waits_for(Some(owner.get_uid()));
+                    cur_task.start_waiting();
+                }
             }
         });
-
-        // Otherwise, wait on the wait queue until the current task can lock it.
-        // The called method will return the mutex guard upon return.
-        self.queue.wait_until(|| self.try_lock())
     }
 }
 
@@ -212,6 +275,13 @@ where
         // Notify a task on the waitqueue that the lock is released, which occurs
         // automatically when the inner `guard` is dropped after this method executes.
         self.mutex.queue.notify_one_allow_isr();
+
+        // Tasks with priority between the intrinsic and the ceiling priority of
+        // the owner may have become ready while the mutex was held. Let them run
+        // now that the owner is back to its intrinsic priority.
+        if self.mutex.ceiling.is_some() && !current::is_in_isr_context() {
+            context_switch::yield_current_task();
+        }
     }
 }
 
@@ -268,6 +338,8 @@ macro_rules! define_mutex {
         // The documentation of the mutex guard struct in addition to the
         // common documentation.
         $guard_extra_doc:expr,
+        // The type name of the mapped mutex guard struct to be generated.
+        $mapped_guard_ty:ident,
         // Generic type parameters to the mutex type and their constraints.
         <$($gen:ident $(: $bound:path)?),*>,
         // Concrete type providing the implementation of the mutex.
@@ -295,6 +367,20 @@ macro_rules! define_mutex {
             mutex: &'a $mutex_ty<$($gen),*>,
         }
 
+        /// The guard type that provides mutable access to a component of the
+        /// data being protected, e.g., a field of a struct. It is returned by
+        #[doc = concat!("[`", stringify!($guard_ty), "::map`] and [`", stringify!($guard_ty), "::try_map`].")]
+        /// The mutex stays locked until the guard is dropped.
+        ///
+        #[doc = $guard_extra_doc]
+        pub struct $mapped_guard_ty<'a, $($gen $(: $bound)?,)* U: ?Sized> {
+            guard: $guard_ty<'a, $($gen),*>,
+            /// Pointing into the data protected by the mutex. It stays valid
+            /// while `guard` is held, even if the guard is moved, since the
+            /// data is stored in the mutex rather than in the guard.
+            data: *mut U,
+        }
+
         impl<$($gen $(: $bound)?),*> $mutex_ty<$($gen),*> {
             /// Create a new mutex instance protecting the given `data`.
             pub const fn new(data: T) -> Self {
@@ -303,6 +389,43 @@ macro_rules! define_mutex {
                 }
             }
 
+            /// Create a new mutex instance protecting the given `data`,
+            /// following the immediate priority ceiling protocol instead of
+            /// priority inheritance.
+            ///
+            /// A task locking the mutex is immediately raised to the
+            /// `ceiling` priority until it releases the mutex, so no task
+            /// with priority at or below the ceiling can preempt it and
+            /// contend for the mutex. The ceiling should be the highest
+            /// priority among the tasks that may lock the mutex, i.e., the
+            /// smallest numerical value. A task with even higher priority
+            /// locking the mutex keeps its own priority, and it still lends
+            /// its priority to the owner if it blocks on the mutex.
+            ///
+            /// Note: the owner returns to its intrinsic priority when it
+            /// releases the mutex, so a task should not hold another
+            /// priority-raising mutex across the release.
+            pub const fn with_ceiling(data: T, ceiling: u8) -> Self {
+                Self {
+                    generic_mutex: GenericMutex::with_ceiling(data, ceiling),
+                }
+            }
+
+            /// Create a new mutex instance protecting the given `data`,
+            /// waking up the tasks blocked on it in the given `order`.
+            ///
+            /// With [`WakeupOrder::Fifo`], the mutex is handed over in the
+            /// order the tasks started waiting, regardless of priority. The
+            /// owner still inherits the priority of the waiting tasks. Note
+            /// that a running task may lock the mutex right after it is
+            /// released, before the woken task resumes. The woken task then
+            /// keeps its place and is woken again on the next release.
+            pub const fn with_wakeup_order(data: T, order: WakeupOrder) -> Self {
+                Self {
+                    generic_mutex: GenericMutex::with_wakeup_order(data, order),
+                }
+            }
+
             /// Discard the mutex and get back the contained data.
             pub fn into_inner(self) -> T {
                 self.generic_mutex.into_inner()
@@ -341,6 +464,111 @@ macro_rules! define_mutex {
                     mutex: self,
                 }
             }
+
+            /// Lock the mutex. If the mutex has already been locked, block
+            /// until the current task can lock it or the elapsed waiting time
+            /// reaches timeout. Return the guard within `Some`, or `None` if
+            /// timed out.
+            ///
+            /// The tasks waiting with a timeout are woken up in the same
+            /// order as those waiting without one, see [`WakeupOrder`].
+            pub fn lock_timeout<'a>(
+                &'a self,
+                timeout_ms: u32,
+            ) -> Option<$guard_ty<'a, $($gen),*>> {
+                self.generic_mutex
+                    .lock_timeout(timeout_ms)
+                    .map(|generic_guard| $guard_ty {
+                        generic_guard,
+                        mutex: self,
+                    })
+            }
+
+            /// Return the contention statistics of the queue of the tasks
+            /// blocked on the mutex. See
+            /// [`soft_lock_stats`](crate::debug::soft_lock_stats).
+            #[cfg(feature = "soft_lock_stats")]
+            pub fn soft_lock_stats(&self) -> crate::debug::soft_lock_stats::SoftLockStats {
+                self.generic_mutex.queue.soft_lock_stats()
+            }
+        }
+
+        impl<'a, $($gen $(: $bound)?),*> $guard_ty<'a, $($gen),*> {
+            /// Make a guard providing access only to a component of the
+            /// protected data, e.g., a field of a struct, so that a driver can
+            /// hand out access to part of a shared state. The mutex stays
+            /// locked until the returned guard is dropped.
+            ///
+            /// This is an associated function to be called as
+            #[doc = concat!("`", stringify!($guard_ty), "::map(guard, ...)`,")]
+            /// so that it does not conflict with a method of the protected
+            /// data.
+            pub fn map<U, F>(mut guard: Self, f: F) -> $mapped_guard_ty<'a, $($gen,)* U>
+            where
+                U: ?Sized,
+                F: FnOnce(&mut T) -> &mut U,
+            {
+                let data: *mut U = f(&mut *guard);
+                $mapped_guard_ty { guard, data }
+            }
+
+            /// Make a guard providing access only to a component of the
+            /// protected data if the closure returns it within `Some`.
+            /// Otherwise, return the original guard within `Err`. The mutex
+            /// stays locked in either case.
+            ///
+            /// This is an associated function to be called as
+            #[doc = concat!("`", stringify!($guard_ty), "::try_map(guard, ...)`,")]
+            /// so that it does not conflict with a method of the protected
+            /// data.
+            pub fn try_map<U, F>(
+                mut guard: Self,
+                f: F,
+            ) -> Result<$mapped_guard_ty<'a, $($gen,)* U>, Self>
+            where
+                U: ?Sized,
+                F: FnOnce(&mut T) -> Option<&mut U>,
+            {
+                let data = f(&mut *guard).map(|data| data as *mut U);
+                match data {
+                    Some(data) => Ok($mapped_guard_ty { guard, data }),
+                    None => Err(guard),
+                }
+            }
+        }
+
+        impl<'a, $($gen $(: $bound)?,)* U: ?Sized> $mapped_guard_ty<'a, $($gen,)* U> {
+            /// Make a guard providing access only to a component of the
+            /// already mapped data. See
+            #[doc = concat!("[`", stringify!($guard_ty), "::map`].")]
+            pub fn map<V, F>(mut guard: Self, f: F) -> $mapped_guard_ty<'a, $($gen,)* V>
+            where
+                V: ?Sized,
+                F: FnOnce(&mut U) -> &mut V,
+            {
+                let data: *mut V = f(&mut *guard);
+                $mapped_guard_ty {
+                    guard: guard.guard,
+                    data,
+                }
+            }
+        }
+
+        impl<'a, $($gen $(: $bound)?,)* U: ?Sized> Deref for $mapped_guard_ty<'a, $($gen,)* U> {
+            type Target = U;
+
+            fn deref(&self) -> &U {
+                // Safety: the pointed data is protected by the held mutex.
+                unsafe { &*self.data }
+            }
+        }
+
+        impl<'a, $($gen $(: $bound)?,)* U: ?Sized> DerefMut for $mapped_guard_ty<'a, $($gen,)* U> {
+            fn deref_mut(&mut self) -> &mut U {
+                // Safety: the pointed data is protected by the held mutex, and
+                // the guard is borrowed mutably.
+                unsafe { &mut *self.data }
+            }
         }
 
         impl<'a, $($gen $(: $bound)?),*> Deref for $guard_ty<'a, $($gen),*> {
@@ -382,6 +610,7 @@ define_mutex!(
     "",
     MutexGuard,
     "",
+    MappedMutexGuard,
     <T>,
     GenericMutex<T, (), ()>,
     GenericMutexGuard<'a, T, (), ()>
@@ -392,6 +621,7 @@ define_mutex!(
     "When the mutex is acquired, the associated IRQ will also be masked.",
     MutexIrqSafeGuard,
     "The associated IRQ will be masked until the guard is dropped.",
+    MappedMutexIrqSafeGuard,
     <T, I: RecursivelyMaskable>,
     GenericMutex<T, I, HeldInterrupt<I>>,
     GenericMutexGuard<'a, T, I, HeldInterrupt<I>>
@@ -0,0 +1,68 @@
+use super::TaskRef;
+use crate::{schedule::current, time, unrecoverable};
+use core::sync::atomic::Ordering;
+
+/// Set the `bits` in the notification word of the task, and wake it up if it
+/// is waiting in [`wait_notification`] for any of them.
+///
+/// Unlike a [`Mailbox`](super::Mailbox), the notification word is part of
+/// each task, so an ISR signaling a single task needs no separate primitive.
+/// Bits already set stay set until the task receives them, so notifying the
+/// same bits twice before the task waits delivers them once.
+///
+/// Since the notification word shares the wake-up token with parking, a
+/// notification may also make the task return from
+/// [`park_current_task`](super::park_current_task) once, which parking
+/// allows.
+///
+/// # Example
+/// ```rust
+/// const RX_DONE: u32 = 1 << 0;
+/// const TX_DONE: u32 = 1 << 1;
+///
+/// // In the UART ISR.
+/// if let Some(driver) = DRIVER_TASK.lock().as_ref() {
+///     sync::notify_value_allow_isr(driver, RX_DONE);
+/// }
+///
+/// // In the driver task.
+/// let bits = sync::wait_notification(RX_DONE | TX_DONE, 100);
+/// if bits & RX_DONE != 0 {
+///     handle_rx();
+/// }
+/// ```
+///
+/// This function is allowed in ISR context.
+pub fn notify_value_allow_isr(task: &TaskRef, bits: u32) {
+    task.parker.notification.fetch_or(bits, Ordering::SeqCst);
+    task.parker.unpark();
+}
+
+/// Block the calling task until any bit in `mask` is set in its notification
+/// word, or the elapsed waiting time reaches timeout. Return the bits in
+/// `mask` that are set, which are cleared from the notification word, or
+/// zero if timed out. Bits outside `mask` are left for later waits.
+///
+/// If any bit in `mask` is already set, return immediately.
+///
+/// NOTE: *must not* call this function in ISR context.
+pub fn wait_notification(mask: u32, timeout_ms: u32) -> u32 {
+    unrecoverable::die_if_in_isr();
+
+    let parker = current::with_cur_task(|cur_task| cur_task.get_parker());
+    let start = time::get_tick();
+    loop {
+        let bits = parker.notification.fetch_and(!mask, Ordering::SeqCst) & mask;
+        if bits != 0 {
+            return bits;
+        }
+
+        // The task may be woken up by an unpark or a notification of the bits
+        // outside `mask`, so check the bits again after being woken up.
+        let elapsed = time::get_tick().wrapping_sub(start);
+        if elapsed >= timeout_ms {
+            return 0;
+        }
+        parker.mailbox.wait_until_timeout(timeout_ms - elapsed);
+    }
+}
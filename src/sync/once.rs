@@ -0,0 +1,205 @@
+use super::WaitQueue;
+use crate::{schedule::current, unrecoverable};
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    ops::Deref,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+/// No context has completed the initialization, and no context is running it.
+const INCOMPLETE: u8 = 0;
+/// A context is running the initialization.
+const RUNNING: u8 = 1;
+/// The initialization has completed.
+const COMPLETE: u8 = 2;
+
+/// A synchronization primitive running a one-time initialization, similar to
+/// `std::sync::Once`, that tasks and ISRs may race to run.
+///
+/// The first context to call [`call_once`](Self::call_once) or
+/// [`call_once_allow_isr`](Self::call_once_allow_isr) runs the
+/// initialization. A task calling in meanwhile blocks until the
+/// initialization completes. An ISR cannot wait for the context it
+/// preempted, so it returns without running the initialization instead,
+/// in the same way as an ISR granted pend-only access to other primitives.
+///
+/// If the initializing task panics and is unwound, the initialization is
+/// considered not run, and the next caller runs it again.
+pub struct Once {
+    /// One of [`INCOMPLETE`], [`RUNNING`], and [`COMPLETE`].
+    state: AtomicU8,
+    /// The tasks waiting for the running initialization.
+    wait_queue: WaitQueue,
+}
+
+/// Set the state of a [`Once`] when the initialization returns or is
+/// unwound, and wake up the waiting tasks.
+struct Completion<'a> {
+    once: &'a Once,
+    state: u8,
+}
+
+impl Drop for Completion<'_> {
+    fn drop(&mut self) {
+        self.once.state.store(self.state, Ordering::SeqCst);
+        self.once.wait_queue.notify_all_allow_isr();
+    }
+}
+
+impl Once {
+    /// Create a new [`Once`] whose initialization has not run.
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(INCOMPLETE),
+            wait_queue: WaitQueue::new(),
+        }
+    }
+
+    /// Return if the initialization has completed.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == COMPLETE
+    }
+
+    /// Run the initialization if no context has run it. Return `Ok` if it
+    /// has completed, or give `f` back if another context is running it.
+    fn try_call_once<F: FnOnce()>(&self, f: F) -> Result<(), F> {
+        match self
+            .state
+            .compare_exchange(INCOMPLETE, RUNNING, Ordering::SeqCst, Ordering::SeqCst)
+        {
+            Ok(_) => {
+                // Restore the state if `f` unwinds.
+                let mut completion = Completion {
+                    once: self,
+                    state: INCOMPLETE,
+                };
+                f();
+                completion.state = COMPLETE;
+                Ok(())
+            }
+            Err(COMPLETE) => Ok(()),
+            Err(_) => Err(f),
+        }
+    }
+
+    /// Run the initialization `f` if no context has run it. If another task
+    /// is running the initialization, block until it completes. When this
+    /// method returns, the initialization has completed.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn call_once<F: FnOnce()>(&self, mut f: F) {
+        unrecoverable::die_if_in_isr();
+
+        loop {
+            f = match self.try_call_once(f) {
+                Ok(()) => return,
+                Err(f) => f,
+            };
+
+            // Wait until the running initialization completes or is unwound.
+            self.wait_queue
+                .wait_until(|| (self.state.load(Ordering::SeqCst) != RUNNING).then_some(()));
+        }
+    }
+
+    /// Run the initialization `f` if no context has run it. Return `true` if
+    /// the initialization has completed, or `false` if it is being run by the
+    /// context preempted by the calling ISR, in which case `f` is not run.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn call_once_allow_isr<F: FnOnce()>(&self, f: F) -> bool {
+        self.try_call_once(f).is_ok()
+    }
+}
+
+/// A value initialized on first access, similar to `std::sync::LazyLock`,
+/// that tasks and ISRs may race to initialize. The initialization follows
+/// the rules of [`Once`].
+///
+/// The initialization function must be callable more than once, so that it
+/// runs again if the initializing task is unwound.
+///
+/// # Example
+/// ```rust
+/// static TABLE: Lazy<[u16; 256]> = Lazy::new(build_crc_table);
+///
+/// // In a task, blocking if another task is building the table.
+/// let crc = TABLE[byte as usize];
+///
+/// // In an ISR.
+/// if let Some(table) = TABLE.get_allow_isr() {
+///     let crc = table[byte as usize];
+/// }
+/// ```
+pub struct Lazy<T, F = fn() -> T> {
+    once: Once,
+    init: F,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send + Sync, F: Send + Sync> Sync for Lazy<T, F> {}
+
+impl<T, F: Fn() -> T> Lazy<T, F> {
+    /// Create a new lazily initialized value, initialized by calling `init`.
+    pub const fn new(init: F) -> Self {
+        Self {
+            once: Once::new(),
+            init,
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Initialize the value if needed and return a reference to it. If
+    /// another task is initializing it, block until the initialization
+    /// completes.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn force(this: &Self) -> &T {
+        this.once.call_once(|| this.write_init());
+        unsafe { (*this.value.get()).assume_init_ref() }
+    }
+
+    /// Initialize the value if needed and return a reference to it within
+    /// `Some`. Return `None` if the context preempted by the calling ISR is
+    /// initializing the value.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn get_allow_isr(&self) -> Option<&T> {
+        if current::is_in_isr_context() {
+            if !self.once.call_once_allow_isr(|| self.write_init()) {
+                return None;
+            }
+        } else {
+            self.once.call_once(|| self.write_init());
+        }
+        Some(unsafe { (*self.value.get()).assume_init_ref() })
+    }
+
+    /// Run the initialization function and store the value. Called only by
+    /// the context running the initialization of `once`.
+    fn write_init(&self) {
+        let value = (self.init)();
+        unsafe { (*self.value.get()).write(value) };
+    }
+}
+
+/// Dereferencing follows [`Lazy::force`] and thus *must not* happen in ISR
+/// context.
+impl<T, F: Fn() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Self::force(self)
+    }
+}
+
+impl<T, F> Drop for Lazy<T, F> {
+    fn drop(&mut self) {
+        if self.once.is_completed() {
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use super::Mailbox;
+use crate::{schedule::current, unrecoverable};
+use alloc::sync::Arc;
+use core::sync::atomic::AtomicU32;
+
+/// A handle to a task that can be [`unpark`]ed or
+/// [notified](super::notify_value_allow_isr). It is obtained by the task
+/// itself with [`current_task_ref`] and handed to the contexts that will
+/// wake it up.
+///
+/// The handle refers to one instance of a task. If the task panics and is
+/// restarted, the restarted instance has a new handle, and unparking the
+/// old one has no effect on it.
+#[derive(Clone)]
+pub struct TaskRef {
+    pub(super) parker: Arc<Parker>,
+}
+
+/// The per-task state shared by a task and its [`TaskRef`]s.
+pub(crate) struct Parker {
+    /// The task blocks on the mailbox when parked or waiting for a
+    /// notification.
+    pub(super) mailbox: Mailbox,
+    /// The notification word. See
+    /// [`notify_value_allow_isr`](super::notify_value_allow_isr).
+    pub(super) notification: AtomicU32,
+}
+
+impl Parker {
+    pub(crate) const fn new() -> Self {
+        Self {
+            mailbox: Mailbox::new(),
+            notification: AtomicU32::new(0),
+        }
+    }
+
+    /// Make the wake-up token available if it is not.
+    pub(super) fn unpark(&self) {
+        // Keep at most one token. The check may race with another unpark, in
+        // which case the task returns from parking once more without a
+        // matching unpark, which parking allows.
+        if !self.mailbox.has_notification() {
+            self.mailbox.notify_allow_isr();
+        }
+    }
+}
+
+/// Return a handle to the calling task.
+///
+/// NOTE: *must not* call this function in ISR context.
+pub fn current_task_ref() -> TaskRef {
+    unrecoverable::die_if_in_isr();
+
+    TaskRef {
+        parker: current::with_cur_task(|cur_task| cur_task.get_parker()),
+    }
+}
+
+/// Block the calling task until it is [`unpark`]ed. If the task has been
+/// unparked since it was last parked, return immediately.
+///
+/// Each task has a single wake-up token. Unparking a task makes the token
+/// available, and parking consumes it. Like a futex, a task may also return
+/// from parking without a matching unpark, e.g., when it was unparked twice
+/// before parking once. A custom blocking primitive should thus park in a
+/// loop until its condition holds, and unpark the waiting task after
+/// updating the condition.
+///
+/// # Example
+/// ```rust
+/// // In the waiting task.
+/// WAITER.lock().replace(sync::current_task_ref());
+/// while !READY.load(Ordering::SeqCst) {
+///     sync::park_current_task();
+/// }
+///
+/// // In another task or an ISR.
+/// READY.store(true, Ordering::SeqCst);
+/// if let Some(waiter) = WAITER.lock().as_ref() {
+///     sync::unpark(waiter);
+/// }
+/// ```
+///
+/// NOTE: *must not* call this function in ISR context.
+pub fn park_current_task() {
+    unrecoverable::die_if_in_isr();
+
+    current::with_cur_task(|cur_task| cur_task.get_parker())
+        .mailbox
+        .wait();
+}
+
+/// Block the calling task until it is [`unpark`]ed or the elapsed waiting
+/// time reaches timeout. If the task has been unparked since it was last
+/// parked, return immediately. Return `true` if the task consumes the
+/// wake-up token, or `false` if timed out.
+///
+/// See [`park_current_task`] for the semantics of the wake-up token.
+///
+/// NOTE: *must not* call this function in ISR context.
+pub fn park_current_task_with_timeout(timeout_ms: u32) -> bool {
+    unrecoverable::die_if_in_isr();
+
+    current::with_cur_task(|cur_task| cur_task.get_parker())
+        .mailbox
+        .wait_until_timeout(timeout_ms)
+}
+
+/// Make the wake-up token of the task available, and wake it up if it is
+/// parked. Unparking a task whose token is already available has no effect.
+///
+/// This function is allowed in ISR context.
+pub fn unpark(task: &TaskRef) {
+    task.parker.unpark();
+}
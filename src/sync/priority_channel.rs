@@ -0,0 +1,193 @@
+use super::{Semaphore, SpinSchedSafe, SpinSchedSafeGuard};
+use crate::{schedule::current, unrecoverable::Lethal};
+use core::{
+    cmp::Ordering,
+    sync::atomic::{self, AtomicU32},
+};
+use heapless::binary_heap::{BinaryHeap, Max};
+
+/// A multi-producer multi-consumer channel delivering the elements in the
+/// order of their priorities. Among the elements of the same priority, the
+/// one produced first is consumed first.
+///
+/// Like the task priority, a smaller numerical value means a higher
+/// priority.
+///
+/// Unlike the channel created by [`create_channel`](super::create_channel),
+/// the buffer is protected by a spin lock suspending the scheduler while an
+/// element is being inserted or removed, which takes `O(log N)` time. An ISR
+/// preempting the lock owner gives up the operation instead of waiting.
+///
+/// # Example
+/// ```rust
+/// static FRAMES: PriorityChannel<Frame, 16> = PriorityChannel::new();
+///
+/// // Control frames jump ahead of the bulk data already queued.
+/// FRAMES.produce(data_frame, 10);
+/// FRAMES.produce(control_frame, 0);
+///
+/// // Get the control frame.
+/// let frame = FRAMES.consume();
+/// ```
+pub struct PriorityChannel<T, const N: usize> {
+    /// The buffered elements, with the highest priority one at the top.
+    buffer: SpinSchedSafe<BinaryHeap<Entry<T>, Max, N>>,
+    /// The sequence number of the next produced element.
+    next_seq: AtomicU32,
+    /// The semaphore counting on the empty slots.
+    sem_empty: Semaphore,
+    /// The semaphore counting on the occupied slots.
+    sem_occupied: Semaphore,
+}
+
+/// An element in the buffer.
+struct Entry<T> {
+    priority: u8,
+    /// Ordering the elements of the same priority.
+    seq: u32,
+    data: T,
+}
+
+/// An entry is greater if it should be consumed earlier.
+impl<T> Ord for Entry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // At most `N` elements are buffered, so comparing the difference
+        // handles the wrap-around of the sequence numbers.
+        let seq_diff = other.seq.wrapping_sub(self.seq) as i32;
+        other
+            .priority
+            .cmp(&self.priority)
+            .then_with(|| seq_diff.cmp(&0))
+    }
+}
+
+impl<T> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Entry<T> {}
+
+impl<T, const N: usize> PriorityChannel<T, N> {
+    /// Create a new channel with the given buffering capacity.
+    pub const fn new() -> Self {
+        Self {
+            buffer: SpinSchedSafe::new(BinaryHeap::new()),
+            next_seq: AtomicU32::new(0),
+            sem_empty: Semaphore::new(N, N),
+            sem_occupied: Semaphore::new(N, 0),
+        }
+    }
+
+    /// Lock the buffer. Tasks never contend for the lock, since the scheduler
+    /// is suspended while it is held. Return `None` if the current context is
+    /// an ISR preempting the lock owner.
+    fn lock_buffer(&self) -> Option<SpinSchedSafeGuard<'_, BinaryHeap<Entry<T>, Max, N>>> {
+        if current::is_in_isr_context() {
+            self.buffer.try_lock()
+        } else {
+            Some(self.buffer.lock_now_or_die())
+        }
+    }
+
+    /// Insert an element into the buffer. Return the element with `Err` if
+    /// the buffer cannot be locked. The caller must have taken an empty slot.
+    fn insert(&self, data: T, priority: u8) -> Result<(), T> {
+        let Some(mut buffer) = self.lock_buffer() else {
+            return Err(data);
+        };
+        // Take the sequence number with the lock held, so that the elements
+        // are ordered as they are inserted.
+        let seq = self.next_seq.fetch_add(1, atomic::Ordering::SeqCst);
+        buffer
+            .push(Entry {
+                priority,
+                seq,
+                data,
+            })
+            .ok()
+            .unwrap_or_die();
+        Ok(())
+    }
+
+    /// Remove the element with the highest priority from the buffer. Return
+    /// `None` if the buffer cannot be locked. The caller must have taken an
+    /// occupied slot.
+    fn remove(&self) -> Option<T> {
+        Some(self.lock_buffer()?.pop().unwrap_or_die().data)
+    }
+
+    /// Push an element with the given priority into the channel. If the
+    /// channel is already full, block until there is an empty slot.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn produce(&self, data: T, priority: u8) {
+        self.sem_empty.down();
+        let slot = self.sem_empty.give_back_on_unwind();
+        self.insert(data, priority).ok().unwrap_or_die();
+        slot.disarm();
+        self.sem_occupied.up();
+    }
+
+    /// Push an element with the given priority into the channel. If the
+    /// channel is already full, return the element with `Err`.
+    ///
+    /// Calling this method in ISR context is allowed. However, an ISR also
+    /// gets `Err` when it preempts a task inserting or removing an element.
+    pub fn try_produce_allow_isr(&self, data: T, priority: u8) -> Result<(), T> {
+        if self.sem_empty.try_down_allow_isr().is_err() {
+            return Err(data);
+        }
+        let slot = self.sem_empty.give_back_on_unwind();
+        let res = self.insert(data, priority);
+        slot.disarm();
+        if res.is_err() {
+            // The ISR preempts the lock owner. Give back the empty slot.
+            self.sem_empty.try_up_allow_isr().unwrap_or_die();
+            return res;
+        }
+        self.sem_occupied.try_up_allow_isr().unwrap_or_die();
+        Ok(())
+    }
+
+    /// Pop out the element with the highest priority from the channel. If
+    /// the channel is empty, block until there is an element.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn consume(&self) -> T {
+        self.sem_occupied.down();
+        let slot = self.sem_occupied.give_back_on_unwind();
+        let data = self.remove().unwrap_or_die();
+        slot.disarm();
+        self.sem_empty.up();
+        data
+    }
+
+    /// Pop out the element with the highest priority from the channel. If
+    /// the channel is empty, return `None`.
+    ///
+    /// Calling this method in ISR context is allowed. However, an ISR also
+    /// gets `None` when it preempts a task inserting or removing an element.
+    pub fn try_consume_allow_isr(&self) -> Option<T> {
+        if self.sem_occupied.try_down_allow_isr().is_err() {
+            return None;
+        }
+        let slot = self.sem_occupied.give_back_on_unwind();
+        let data = self.remove();
+        slot.disarm();
+        let Some(data) = data else {
+            // The ISR preempts the lock owner. Give back the occupied slot.
+            self.sem_occupied.try_up_allow_isr().unwrap_or_die();
+            return None;
+        };
+        self.sem_empty.try_up_allow_isr().unwrap_or_die();
+        Some(data)
+    }
+}
@@ -0,0 +1,460 @@
+use super::{
+    create_channel, Access, AllowPendOp, Consumer, Producer, RefCellSchedSafe, RunPendedOp,
+    SoftLock, Spin,
+};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use heapless::mpmc::MpMcQueue;
+
+/// The number of messages published by ISRs that can wait for the preempted
+/// context to deliver them.
+const PENDING_CAPACITY: usize = 4;
+
+/// A subscriber queue as seen by the topic, with the capacity erased from
+/// the type.
+trait Sink<T>: Send {
+    fn try_send_allow_isr(&self, msg: T) -> Result<(), T>;
+    fn send(&self, msg: T);
+    /// Drop the oldest message in the queue. Return if there was one.
+    fn evict_allow_isr(&self) -> bool;
+    fn boxed_clone(&self) -> Box<dyn Sink<T>>;
+}
+
+/// Both halves of a subscriber queue. The consumer is needed to evict
+/// messages from the queue.
+struct Queue<T, const N: usize> {
+    producer: Producer<T, N>,
+    consumer: Consumer<T, N>,
+}
+
+impl<T, const N: usize> Clone for Queue<T, N> {
+    fn clone(&self) -> Self {
+        Self {
+            producer: self.producer.clone(),
+            consumer: self.consumer.clone(),
+        }
+    }
+}
+
+impl<T, const N: usize> Sink<T> for Queue<T, N>
+where
+    T: Send + 'static,
+{
+    fn try_send_allow_isr(&self, msg: T) -> Result<(), T> {
+        self.producer.try_produce_allow_isr(msg)
+    }
+
+    fn send(&self, msg: T) {
+        self.producer.produce(msg)
+    }
+
+    fn evict_allow_isr(&self) -> bool {
+        self.consumer.try_consume_allow_isr().is_some()
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Sink<T>> {
+        Box::new(self.clone())
+    }
+}
+
+/// How a message is delivered to a subscriber whose queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// [`Topic::publish`] blocks until the subscriber makes room, and
+    /// [`Topic::publish_allow_isr`] drops the new message. The subscriber
+    /// sees every message up to the point it falls behind.
+    Backpressure,
+    /// The oldest message in the queue is dropped to make room for the new
+    /// one, and neither way of publishing blocks. The subscriber sees the
+    /// latest messages, which suits state updates where only the recent
+    /// values matter.
+    DropOldest,
+}
+
+/// A subscriber as seen by the topic.
+struct Entry<T> {
+    id: usize,
+    sink: Box<dyn Sink<T>>,
+    policy: LagPolicy,
+    dropped: Arc<AtomicU32>,
+}
+
+impl<T> Entry<T> {
+    /// Deliver the message without blocking, following the lag policy.
+    /// Return if the message is enqueued, and count the dropped message
+    /// otherwise or if an older message is evicted.
+    fn deliver_allow_isr(&self, msg: T, topic_dropped: &AtomicU32) -> bool {
+        let msg = match self.sink.try_send_allow_isr(msg) {
+            Ok(()) => return true,
+            Err(msg) => msg,
+        };
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        topic_dropped.fetch_add(1, Ordering::Relaxed);
+
+        // Another context may fill the queue again after the eviction, in
+        // which case the new message is dropped after all.
+        self.policy == LagPolicy::DropOldest
+            && self.sink.evict_allow_isr()
+            && self.sink.try_send_allow_isr(msg).is_ok()
+    }
+}
+
+/// The content of a [`Topic`] protected by the soft lock.
+struct Inner<T> {
+    /// The subscribers. The spin lock around it is only for sanity check.
+    /// This field should not be accessed concurrently.
+    subscribers: Spin<Vec<Entry<T>>>,
+    /// The messages published by ISRs preempting the owner of the full
+    /// access. The owner delivers them when it releases the access.
+    pending: MpMcQueue<T, PENDING_CAPACITY>,
+    /// See [`Topic::dropped_count`].
+    dropped: AtomicU32,
+}
+
+/// Representing full access to all fields of the [`Topic`].
+struct InnerFullAccessor<'a, T> {
+    subscribers: &'a Spin<Vec<Entry<T>>>,
+    pending: &'a MpMcQueue<T, PENDING_CAPACITY>,
+    dropped: &'a AtomicU32,
+}
+
+/// Representing pend-only access to the [`Topic`]. Only a message to be
+/// delivered later can be queued.
+struct InnerPendAccessor<'a, T> {
+    pending: &'a MpMcQueue<T, PENDING_CAPACITY>,
+    dropped: &'a AtomicU32,
+}
+
+/// Bind the accessor types.
+impl<'a, T> AllowPendOp<'a> for Inner<T>
+where
+    T: Clone + Send + 'static,
+{
+    type FullAccessor = InnerFullAccessor<'a, T>;
+    type PendOnlyAccessor = InnerPendAccessor<'a, T>;
+    fn full_access(&'a self) -> Self::FullAccessor {
+        Self::FullAccessor {
+            subscribers: &self.subscribers,
+            pending: &self.pending,
+            dropped: &self.dropped,
+        }
+    }
+
+    fn pend_only_access(&'a self) -> Self::PendOnlyAccessor {
+        Self::PendOnlyAccessor {
+            pending: &self.pending,
+            dropped: &self.dropped,
+        }
+    }
+}
+
+/// A pended operation is always publishing a message. Deliver the messages
+/// to all subscribers without blocking.
+impl<'a, T> RunPendedOp for InnerFullAccessor<'a, T>
+where
+    T: Clone + Send + 'static,
+{
+    fn run_pended_op(&mut self) {
+        while let Some(msg) = self.pending.dequeue() {
+            self.deliver_allow_isr(msg);
+        }
+    }
+}
+
+impl<'a, T> InnerFullAccessor<'a, T>
+where
+    T: Clone + Send + 'static,
+{
+    /// Deliver the message to all subscribers without blocking. Return the
+    /// number of subscribers that received the message.
+    fn deliver_allow_isr(&self, msg: T) -> usize {
+        self.subscribers
+            .lock_now_or_die()
+            .iter()
+            .filter(|entry| entry.deliver_allow_isr(msg.clone(), self.dropped))
+            .count()
+    }
+}
+
+/// The ring buffer does not drop the messages left in it, so drop them here.
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        while self.pending.dequeue().is_some() {}
+    }
+}
+
+/// A topic of a publish/subscribe message bus. Every message published to
+/// the topic is delivered to all subscribers present at the time, each of
+/// which has its own queue with a capacity and a [`LagPolicy`] chosen when
+/// subscribing. To avoid copying large messages, publish them as `Arc`s.
+///
+/// Topics are meant to be declared as `static` items, so that publishers and
+/// subscribers can find them by name in code.
+///
+/// # Example
+/// ```rust
+/// static TEMPERATURE: Topic<i16> = Topic::new("temperature");
+///
+/// // In a consumer task.
+/// let subscriber = TEMPERATURE.subscribe::<4>();
+/// let celsius = subscriber.recv();
+///
+/// // In an IRQ handler.
+/// TEMPERATURE.publish_allow_isr(read_sensor());
+/// ```
+pub struct Topic<T>
+where
+    T: Clone + Send + 'static,
+{
+    name: &'static str,
+    /// An ISR preempting a task that accesses the subscribers queues its
+    /// message, which the task delivers when it finishes the access.
+    inner: RefCellSchedSafe<SoftLock<Inner<T>>>,
+    /// The number of subscribers, readable without the access.
+    subscriber_count: AtomicUsize,
+    next_id: AtomicUsize,
+    published: AtomicU32,
+}
+
+impl<T> Topic<T>
+where
+    T: Clone + Send + 'static,
+{
+    /// Create a topic without any subscriber.
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            inner: RefCellSchedSafe::new(SoftLock::new(Inner {
+                subscribers: Spin::new(Vec::new()),
+                pending: MpMcQueue::new(),
+                dropped: AtomicU32::new(0),
+            })),
+            subscriber_count: AtomicUsize::new(0),
+            next_id: AtomicUsize::new(0),
+            published: AtomicU32::new(0),
+        }
+    }
+
+    /// The name of the topic.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Subscribe to the topic with a queue holding up to `N` messages and
+    /// the [`Backpressure`](LagPolicy::Backpressure) lag policy. The
+    /// subscription ends when the returned [`Subscriber`] is dropped.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn subscribe<const N: usize>(&'static self) -> Subscriber<T, N> {
+        self.subscribe_with_policy(LagPolicy::Backpressure)
+    }
+
+    /// Subscribe to the topic with a queue holding up to `N` messages and
+    /// the given lag policy. The subscription ends when the returned
+    /// [`Subscriber`] is dropped.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn subscribe_with_policy<const N: usize>(
+        &'static self,
+        policy: LagPolicy,
+    ) -> Subscriber<T, N> {
+        let (producer, consumer) = create_channel::<T, N>();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let dropped = Arc::new(AtomicU32::new(0));
+        let entry = Entry {
+            id,
+            sink: Box::new(Queue {
+                producer,
+                consumer: consumer.clone(),
+            }),
+            policy,
+            dropped: dropped.clone(),
+        };
+
+        // Should always grant full access to a task.
+        self.inner.with_suspended_scheduler(|inner, _| {
+            inner.must_with_full_access(|full_access| {
+                let mut subscribers = full_access.subscribers.lock_now_or_die();
+                subscribers.push(entry);
+                self.subscriber_count
+                    .store(subscribers.len(), Ordering::SeqCst);
+            })
+        });
+
+        Subscriber {
+            topic: self,
+            id,
+            consumer,
+            dropped,
+            seen_dropped: AtomicU32::new(0),
+        }
+    }
+
+    /// Publish a message to all subscribers. For each subscriber whose queue
+    /// is full, block until there is room if its lag policy is
+    /// [`Backpressure`](LagPolicy::Backpressure), so a slow subscriber slows
+    /// down the publisher, or drop its oldest message if the policy is
+    /// [`DropOldest`](LagPolicy::DropOldest). Return the number of
+    /// subscribers that received the message.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn publish(&self, msg: T) -> usize {
+        // Take a snapshot of the subscribers with backpressure so that the
+        // lock is not held while blocking.
+        let mut delivered = 0;
+        let mut blocking = Vec::new();
+        // Should always grant full access to a task.
+        self.inner.with_suspended_scheduler(|inner, _| {
+            inner.must_with_full_access(|full_access| {
+                for entry in full_access.subscribers.lock_now_or_die().iter() {
+                    match entry.policy {
+                        LagPolicy::Backpressure => blocking.push(entry.sink.boxed_clone()),
+                        LagPolicy::DropOldest => {
+                            delivered +=
+                                entry.deliver_allow_isr(msg.clone(), full_access.dropped) as usize
+                        }
+                    }
+                }
+            })
+        });
+
+        self.published.fetch_add(1, Ordering::Relaxed);
+        for sink in blocking.iter() {
+            sink.send(msg.clone());
+        }
+        delivered + blocking.len()
+    }
+
+    /// Publish a message to all subscribers without blocking. For each
+    /// subscriber whose queue is full, a message is dropped and counted in
+    /// the dropped count of both the subscriber and the topic. The dropped
+    /// message is the new one if the lag policy of the subscriber is
+    /// [`Backpressure`](LagPolicy::Backpressure), or the oldest one in the
+    /// queue if the policy is [`DropOldest`](LagPolicy::DropOldest). Return
+    /// the number of subscribers that received the message.
+    ///
+    /// If an ISR preempts a task accessing the subscribers, the message is
+    /// queued and delivered by the task when it finishes the access, and `0`
+    /// is returned. Up to 4 messages can be queued this way. The message is
+    /// dropped and counted in the dropped count of the topic if the queue is
+    /// full.
+    ///
+    /// Calling this method in ISR context is allowed, as long as cloning and
+    /// dropping the message do not use the heap.
+    pub fn publish_allow_isr(&self, msg: T) -> usize {
+        self.published.fetch_add(1, Ordering::Relaxed);
+        self.inner.with_suspended_scheduler(|inner, _| {
+            inner.with_access(|access| match access {
+                Access::Full { full_access } => full_access.deliver_allow_isr(msg),
+                Access::PendOnly { pend_access } => {
+                    if pend_access.pending.enqueue(msg).is_err() {
+                        pend_access.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    0
+                }
+            })
+        })
+    }
+
+    /// The current number of subscribers.
+    pub fn subscriber_count(&self) -> usize {
+        self.subscriber_count.load(Ordering::SeqCst)
+    }
+
+    /// The number of messages published to the topic.
+    pub fn published_count(&self) -> u32 {
+        self.published.load(Ordering::Relaxed)
+    }
+
+    /// The total number of messages dropped because a subscriber queue was
+    /// full.
+    pub fn dropped_count(&self) -> u32 {
+        self.inner.with_suspended_scheduler(|inner, _| {
+            inner.with_access(|access| match access {
+                Access::Full { full_access } => full_access.dropped.load(Ordering::Relaxed),
+                Access::PendOnly { pend_access } => pend_access.dropped.load(Ordering::Relaxed),
+            })
+        })
+    }
+
+    /// Remove the subscriber with the given ID.
+    fn unsubscribe(&self, id: usize) {
+        // Drop the entry after releasing the access.
+        let entry = self.inner.with_suspended_scheduler(|inner, _| {
+            inner.must_with_full_access(|full_access| {
+                let mut subscribers = full_access.subscribers.lock_now_or_die();
+                let entry = subscribers
+                    .iter()
+                    .position(|entry| entry.id == id)
+                    .map(|idx| subscribers.swap_remove(idx));
+                self.subscriber_count
+                    .store(subscribers.len(), Ordering::SeqCst);
+                entry
+            })
+        });
+        drop(entry);
+    }
+}
+
+/// A subscription to a [`Topic`] with a queue holding up to `N` messages.
+/// Dropping the subscriber ends the subscription.
+pub struct Subscriber<T, const N: usize>
+where
+    T: Clone + Send + 'static,
+{
+    topic: &'static Topic<T>,
+    id: usize,
+    consumer: Consumer<T, N>,
+    dropped: Arc<AtomicU32>,
+    /// The dropped count when [`take_lag`](Subscriber::take_lag) was last
+    /// called.
+    seen_dropped: AtomicU32,
+}
+
+impl<T, const N: usize> Subscriber<T, N>
+where
+    T: Clone + Send + 'static,
+{
+    /// Receive the next message. If the queue is empty, block until a
+    /// message is published.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn recv(&self) -> T {
+        self.consumer.consume()
+    }
+
+    /// Receive the next message if there is one.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn try_recv_allow_isr(&self) -> Option<T> {
+        self.consumer.try_consume_allow_isr()
+    }
+
+    /// The topic subscribed to.
+    pub fn topic(&self) -> &'static Topic<T> {
+        self.topic
+    }
+
+    /// The number of messages dropped because the queue of this subscriber
+    /// was full.
+    pub fn dropped_count(&self) -> u32 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// The number of messages dropped because the queue of this subscriber
+    /// was full since the last call, e.g., to resynchronize the state
+    /// tracked from the messages after falling behind.
+    pub fn take_lag(&self) -> u32 {
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        dropped.wrapping_sub(self.seen_dropped.swap(dropped, Ordering::Relaxed))
+    }
+}
+
+impl<T, const N: usize> Drop for Subscriber<T, N>
+where
+    T: Clone + Send + 'static,
+{
+    fn drop(&mut self) {
+        self.topic.unsubscribe(self.id);
+    }
+}
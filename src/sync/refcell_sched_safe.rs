@@ -1,8 +1,10 @@
 use crate::schedule::scheduler::{SchedSuspendGuard, Scheduler};
 
 /// A lock type that grants access to the contained data when the scheduler
-/// is suspended.
-pub(crate) struct RefCellSchedSafe<T>
+/// is suspended. Wrapping a [`SoftLock`](super::SoftLock) with it ensures
+/// that only ISRs, but not other tasks, can preempt the owner of the full
+/// access.
+pub struct RefCellSchedSafe<T>
 where
     T: ?Sized,
 {
@@ -11,7 +13,7 @@ where
 
 impl<T> RefCellSchedSafe<T> {
     /// Create a new [`RefCellSchedSafe`] instance wrapping the given value.
-    pub(crate) const fn new(val: T) -> Self {
+    pub const fn new(val: T) -> Self {
         Self { val }
     }
 
@@ -19,7 +21,7 @@ impl<T> RefCellSchedSafe<T> {
     /// take two arguments: `&T` and [`&SchedSuspendGuard`](SchedSuspendGuard).
     /// The granted access is not `mut` because even when the scheduler is
     /// suspended, an interrupt handler can still concurrently access the data.
-    pub(crate) fn with_suspended_scheduler<F, R>(&self, op: F) -> R
+    pub fn with_suspended_scheduler<F, R>(&self, op: F) -> R
     where
         F: FnOnce(&T, &SchedSuspendGuard) -> R,
     {
@@ -0,0 +1,65 @@
+use crate::config;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// The maximum number of retries of a single loop counted so far.
+static MAX_RETRIES: AtomicU32 = AtomicU32::new(0);
+
+/// Count the retries of a lock-free loop on the path of an `_allow_isr`
+/// operation. Such a loop retries only when a higher priority ISR modifies
+/// the same primitive in between, so it should retry at most
+/// [`ISR_RETRY_LIMIT`](config::ISR_RETRY_LIMIT) times. Debug builds assert
+/// the bound, which catches an IRQ storm or a context modifying the primitive
+/// in a loop. Custom ISR-safe primitives can use it for their own lock-free
+/// loops.
+///
+/// # Example
+/// ```rust
+/// fn increment_allow_isr(counter: &AtomicUsize) {
+///     let mut retries = RetryCounter::new();
+///     loop {
+///         let cur = counter.load(Ordering::SeqCst);
+///         if counter
+///             .compare_exchange(cur, cur + 1, Ordering::SeqCst, Ordering::SeqCst)
+///             .is_ok()
+///         {
+///             return;
+///         }
+///         // A higher priority ISR changed the counter in between.
+///         retries.retry();
+///     }
+/// }
+/// ```
+pub struct RetryCounter {
+    count: u32,
+}
+
+impl RetryCounter {
+    /// Create a counter for a new run of a loop.
+    pub const fn new() -> Self {
+        Self { count: 0 }
+    }
+
+    /// Record a retry.
+    #[inline]
+    pub fn retry(&mut self) {
+        self.count += 1;
+        MAX_RETRIES.fetch_max(self.count, Ordering::Relaxed);
+        debug_assert!(
+            self.count <= config::ISR_RETRY_LIMIT,
+            "unbounded retries on an ISR path"
+        );
+    }
+
+    /// Return the number of retries recorded.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+/// Return the maximum number of times a lock-free loop on the path of an
+/// `_allow_isr` operation has retried since boot. A value close to
+/// [`ISR_RETRY_LIMIT`](config::ISR_RETRY_LIMIT) indicates a primitive
+/// modified by ISRs at a high rate.
+pub fn max_isr_retries() -> u32 {
+    MAX_RETRIES.load(Ordering::Relaxed)
+}
@@ -0,0 +1,206 @@
+//! Typed request/response calls between tasks over a channel.
+//!
+//! [`create`] returns a [`Client`] and a [`Server`] sharing a channel of
+//! requests. A client task [`call`](Client::call)s the server with a request
+//! and blocks until the server responds, optionally with a timeout. The
+//! server receives each request along with a [`Responder`] through which it
+//! sends back the response. A [`Responder`] dropped without responding, e.g.,
+//! when the server task panics and is unwound, fails the call with
+//! [`RpcError::NoResponse`] instead of blocking the client forever.
+//!
+//! ISRs and tasks that do not need a response can
+//! [`send`](Client::try_send_allow_isr) a request without blocking. Its
+//! responder discards the response.
+//!
+//! # Example
+//! ```rust
+//! let (client, server) = rpc::create::<u32, u32, 4>();
+//!
+//! task::build()
+//!     .set_entry(move || loop {
+//!         let (request, responder) = server.recv();
+//!         responder.respond(request * 2);
+//!     })
+//!     .spawn()
+//!     .unwrap();
+//!
+//! assert_eq!(client.call(21), Ok(42));
+//! ```
+
+use super::{Consumer, Mailbox, Producer, SpinIrqSafe};
+use crate::interrupt::mask::AllIrqExceptSvc;
+use alloc::sync::Arc;
+
+/// Enumeration of errors of the RPC API.
+#[derive(Debug, PartialEq)]
+pub enum RpcError {
+    /// No response arrived before the timeout.
+    Timeout,
+    /// The server dropped the responder without responding.
+    NoResponse,
+}
+
+/// The place where the server puts the response of a call.
+struct Slot<Resp> {
+    response: SpinIrqSafe<Option<Resp>, AllIrqExceptSvc>,
+    /// Notified when the responder responds or is dropped.
+    mailbox: Mailbox,
+}
+
+/// A request and where to put its response.
+struct Envelope<Req, Resp> {
+    request: Req,
+    /// `None` for a request sent without waiting for a response.
+    slot: Option<Arc<Slot<Resp>>>,
+}
+
+/// The client side of an RPC channel. It can be cloned.
+pub struct Client<Req, Resp, const N: usize> {
+    producer: Producer<Envelope<Req, Resp>, N>,
+}
+
+impl<Req, Resp, const N: usize> Clone for Client<Req, Resp, N> {
+    fn clone(&self) -> Self {
+        Self {
+            producer: self.producer.clone(),
+        }
+    }
+}
+
+/// The server side of an RPC channel. It can be cloned, so that multiple
+/// server tasks take turns to serve the requests.
+pub struct Server<Req, Resp, const N: usize> {
+    consumer: Consumer<Envelope<Req, Resp>, N>,
+}
+
+impl<Req, Resp, const N: usize> Clone for Server<Req, Resp, N> {
+    fn clone(&self) -> Self {
+        Self {
+            consumer: self.consumer.clone(),
+        }
+    }
+}
+
+/// The means for the server to respond to a request. Dropping it without
+/// responding fails the call with [`RpcError::NoResponse`].
+#[must_use = "the call fails if the responder is dropped without responding"]
+pub struct Responder<Resp> {
+    slot: Option<Arc<Slot<Resp>>>,
+}
+
+impl<Req, Resp, const N: usize> Client<Req, Resp, N> {
+    /// Send the request to the server and block until the server responds.
+    /// If the channel is full, block until there is an empty slot.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn call(&self, request: Req) -> Result<Resp, RpcError> {
+        let slot = self.send(request);
+        slot.mailbox.wait();
+        slot.response.lock().take().ok_or(RpcError::NoResponse)
+    }
+
+    /// Send the request to the server and block until the server responds or
+    /// the timeout elapses. If the channel is full, block until there is an
+    /// empty slot, which does not count towards the timeout. A response
+    /// arriving after the timeout is discarded.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn call_until_timeout(&self, request: Req, timeout_ms: u32) -> Result<Resp, RpcError> {
+        let slot = self.send(request);
+        let notified = slot.mailbox.wait_until_timeout(timeout_ms);
+        // The response may have arrived right after the timeout.
+        match slot.response.lock().take() {
+            Some(response) => Ok(response),
+            None if notified => Err(RpcError::NoResponse),
+            None => Err(RpcError::Timeout),
+        }
+    }
+
+    /// Send the request to the server without waiting for a response. If the
+    /// channel is full, return the request with `Err`.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn try_send_allow_isr(&self, request: Req) -> Result<(), Req> {
+        let envelope = Envelope {
+            request,
+            slot: None,
+        };
+        self.producer
+            .try_produce_allow_isr(envelope)
+            .map_err(|envelope| envelope.request)
+    }
+
+    /// Send the request with a new slot for its response.
+    fn send(&self, request: Req) -> Arc<Slot<Resp>> {
+        let slot = Arc::new(Slot {
+            response: SpinIrqSafe::new(None),
+            mailbox: Mailbox::new(),
+        });
+        self.producer.produce(Envelope {
+            request,
+            slot: Some(slot.clone()),
+        });
+        slot
+    }
+}
+
+impl<Req, Resp, const N: usize> Server<Req, Resp, N> {
+    /// Receive a request and its responder. If there is no request, block
+    /// until there is one.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn recv(&self) -> (Req, Responder<Resp>) {
+        Self::open(self.consumer.consume())
+    }
+
+    /// Receive a request and its responder. If there is no request, return
+    /// `None`.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn try_recv_allow_isr(&self) -> Option<(Req, Responder<Resp>)> {
+        self.consumer.try_consume_allow_isr().map(Self::open)
+    }
+
+    fn open(envelope: Envelope<Req, Resp>) -> (Req, Responder<Resp>) {
+        let responder = Responder {
+            slot: envelope.slot,
+        };
+        (envelope.request, responder)
+    }
+}
+
+impl<Resp> Responder<Resp> {
+    /// Send the response to the client. The response is discarded if the
+    /// request was sent without waiting for a response or if the call has
+    /// timed out.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn respond(mut self, response: Resp) {
+        if let Some(slot) = self.slot.take() {
+            *slot.response.lock() = Some(response);
+            slot.mailbox.notify_allow_isr();
+        }
+    }
+
+    /// Return if the request was sent by a call rather than without waiting
+    /// for a response.
+    pub fn expects_response(&self) -> bool {
+        self.slot.is_some()
+    }
+}
+
+impl<Resp> Drop for Responder<Resp> {
+    fn drop(&mut self) {
+        // Wake up the client without a response.
+        if let Some(slot) = self.slot.take() {
+            slot.mailbox.notify_allow_isr();
+        }
+    }
+}
+
+/// Create an RPC channel buffering up to `N` requests. Return the client and
+/// the server sides of the channel.
+pub fn create<Req, Resp, const N: usize>() -> (Client<Req, Resp, N>, Server<Req, Resp, N>) {
+    let (producer, consumer) = super::create_channel();
+    (Client { producer }, Server { consumer })
+}
@@ -0,0 +1,256 @@
+use super::{RetryCounter, SpinSchedSafe, UnwindGuard, WaitQueue};
+use crate::{schedule::current, task::Task};
+use alloc::sync::Arc;
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use stable_deref_trait::StableDeref;
+
+/// The lock state value when a writer holds the lock. Other values count the
+/// readers holding the lock.
+const WRITE_LOCKED: usize = usize::MAX;
+
+/// A lock type protecting the contained data, allowing either multiple
+/// readers or a single writer to access the data at any time. Failure to
+/// acquire the lock will cause the calling task to block until the lock can
+/// be acquired.
+///
+/// Waiting writers are preferred over new readers, so that a stream of
+/// readers cannot starve a writer. A task blocked on a lock held by a writer
+/// lends its priority to the writer, in the same way as with a
+/// [`Mutex`](super::Mutex). Readers do not inherit priorities.
+pub struct RwLock<T: ?Sized> {
+    /// Either [`WRITE_LOCKED`] or the number of readers holding the lock.
+    state: AtomicUsize,
+    /// The number of tasks blocked waiting to write.
+    writers_waiting: AtomicUsize,
+    /// The wait queue for tasks blocked waiting to read.
+    read_queue: WaitQueue,
+    /// The wait queue for tasks blocked waiting to write.
+    write_queue: WaitQueue,
+    /// The task that is currently holding the lock for writing.
+    writer: SpinSchedSafe<Option<Arc<Task>>>,
+    /// If the lock is released by a writer in an unwinding path, this
+    /// variable will be set to `true`. This is just additional information to
+    /// application code.
+    poisoned: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+/// The guard type that provides shared access to the data being protected.
+/// The guard will be returned after successfully acquiring the lock for
+/// reading.
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+/// The guard type that provides mutable access to the data being protected.
+/// The guard will be returned after successfully acquiring the lock for
+/// writing.
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> RwLock<T> {
+    /// Create a new reader-writer lock instance protecting the given `data`.
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            writers_waiting: AtomicUsize::new(0),
+            read_queue: WaitQueue::new(),
+            write_queue: WaitQueue::new(),
+            writer: SpinSchedSafe::new(None),
+            poisoned: AtomicBool::new(false),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Discard the lock and get back the contained data.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Return if the lock was acquired for writing by a task when the task
+    /// is being unwound.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::SeqCst)
+    }
+
+    /// Try to acquire the lock for reading. Return `None` if a writer holds
+    /// the lock, or if `respect_writers` is `true` and a writer is waiting.
+    fn try_read_inner(&self, respect_writers: bool) -> Option<RwLockReadGuard<'_, T>> {
+        let mut retries = RetryCounter::new();
+        loop {
+            let cur_state = self.state.load(Ordering::SeqCst);
+            if cur_state == WRITE_LOCKED {
+                return None;
+            }
+            if respect_writers && self.writers_waiting.load(Ordering::SeqCst) > 0 {
+                return None;
+            }
+
+            if self
+                .state
+                .compare_exchange(cur_state, cur_state + 1, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                return Some(RwLockReadGuard { lock: self });
+            }
+
+            // Another context changed the reader count in between.
+            retries.retry();
+        }
+    }
+
+    /// If no writer holds the lock, acquire it for reading and return the
+    /// guard within `Some`. Otherwise, return `None`. Waiting writers are not
+    /// preferred, because an ISR cannot wait for them.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn try_read_allow_isr(&self) -> Option<RwLockReadGuard<'_, T>> {
+        self.try_read_inner(false)
+    }
+
+    /// Acquire the lock for reading. If a writer holds the lock or is waiting
+    /// for it, block until the current task can acquire the lock.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        // Fast path for no contention.
+        if let Some(guard) = self.try_read_inner(true) {
+            return guard;
+        }
+
+        self.ceil_writer_priority();
+        let guard = self.read_queue.wait_until(|| self.try_read_inner(true));
+        #[cfg(feature = "starvation_monitor")]
+        current::with_cur_task(|cur_task| cur_task.set_waits_for(None));
+
+        // Other readers may be waiting for the same writer to finish. Pass on
+        // the notification.
+        self.read_queue.notify_one_allow_isr();
+        guard
+    }
+
+    /// If the lock is not held, acquire it for writing and return the guard
+    /// within `Some`. Otherwise, return `None`.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn try_write_allow_isr(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.state
+            .compare_exchange(0, WRITE_LOCKED, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()?;
+
+        // Like `Mutex`, do not record the owner in ISR context. The ISR will
+        // always release the lock before any task can block on it.
+        if !current::is_in_isr_context() {
+            current::with_cur_task_arc(|cur_task| {
+                #[cfg(feature = "starvation_monitor")]
+                cur_task.set_waits_for(None);
+                self.writer.lock_now_or_die().replace(cur_task)
+            });
+        }
+        Some(RwLockWriteGuard { lock: self })
+    }
+
+    /// Acquire the lock for writing. If the lock is held, block until the
+    /// current task can acquire the lock.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        // Fast path for no contention.
+        if let Some(guard) = self.try_write_allow_isr() {
+            return guard;
+        }
+
+        self.ceil_writer_priority();
+        self.writers_waiting.fetch_add(1, Ordering::SeqCst);
+        // Do not leave the task counted if it is unwound while waiting.
+        let waiting = UnwindGuard::new(|| {
+            self.writers_waiting.fetch_sub(1, Ordering::SeqCst);
+        });
+        let guard = self.write_queue.wait_until(|| self.try_write_allow_isr());
+        waiting.disarm();
+        self.writers_waiting.fetch_sub(1, Ordering::SeqCst);
+        guard
+    }
+
+    /// Priority inheritance. Let the writer holding the lock, if any, run
+    /// with the priority of the current task if it is higher.
+    fn ceil_writer_priority(&self) {
+        current::with_cur_task(|cur_task| {
+            if let Some(writer) = self.writer.lock_now_or_die().as_ref() {
+                writer.ceil_priority_from(cur_task);
+                // Let the starvation monitor follow the chain of lock owners.
+                #[cfg(feature = "starvation_monitor")]
+                {
+                    cur_task.set_waits_for(Some(writer.get_uid()));
+                    cur_task.start_waiting();
+                }
+            }
+        });
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        // The last reader lets a waiting writer in.
+        if self.lock.state.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.lock.write_queue.notify_one_allow_isr();
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        if let Some(cur_task) = self.lock.writer.lock_now_or_die().take() {
+            cur_task.restore_intrinsic_priority();
+        }
+
+        #[cfg(feature = "unwind")]
+        if crate::unwind::unwind::is_unwinding() {
+            self.lock.poisoned.store(true, Ordering::SeqCst);
+        }
+
+        // Release the lock before notifying other tasks, so that the woken up
+        // tasks can acquire it.
+        self.lock.state.store(0, Ordering::SeqCst);
+
+        // Wake up both a writer and a reader. The reader fails to acquire the
+        // lock if the writer is waiting, and otherwise wakes up the next reader.
+        self.lock.write_queue.notify_one_allow_isr();
+        self.lock.read_queue.notify_one_allow_isr();
+    }
+}
+
+unsafe impl<'a, T: ?Sized> StableDeref for RwLockReadGuard<'a, T> {}
+unsafe impl<'a, T: ?Sized> StableDeref for RwLockWriteGuard<'a, T> {}
@@ -0,0 +1,298 @@
+use super::{
+    Access, AllowPendOp, CancellationToken, Consumer, Mailbox, RefCellSchedSafe, RunPendedOp,
+    Semaphore, SoftLock, Spin,
+};
+use crate::time;
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+mod private {
+    use super::*;
+
+    /// The mailboxes of the [`WaitSet`]s a primitive belongs to, notified
+    /// each time the primitive may have become ready.
+    pub struct Observers {
+        pub(super) inner: RefCellSchedSafe<SoftLock<Inner>>,
+        /// The number of mailboxes, so that notifying a primitive that
+        /// belongs to no wait set does not suspend the scheduler.
+        pub(super) count: AtomicUsize,
+    }
+
+    pub struct Inner {
+        /// The spin lock around it is only for sanity check. This field
+        /// should not be accessed concurrently.
+        pub(super) mailboxes: Spin<Vec<Arc<Mailbox>>>,
+    }
+
+    /// Implemented by the primitives that a [`WaitSet`] can wait on.
+    pub trait Sealed {
+        fn observers(&self) -> &Observers;
+    }
+}
+
+use private::Inner;
+pub(super) use private::Observers;
+
+/// Representing full access to all fields of the [`Observers`].
+struct InnerFullAccessor<'a> {
+    mailboxes: &'a Spin<Vec<Arc<Mailbox>>>,
+}
+
+/// Representing pend-only access to the [`Observers`]. Only notifying the
+/// mailboxes is pended, which needs no field.
+struct InnerPendAccessor;
+
+/// Bind the accessor types.
+impl<'a> AllowPendOp<'a> for Inner {
+    type FullAccessor = InnerFullAccessor<'a>;
+    type PendOnlyAccessor = InnerPendAccessor;
+    fn full_access(&'a self) -> Self::FullAccessor {
+        Self::FullAccessor {
+            mailboxes: &self.mailboxes,
+        }
+    }
+
+    fn pend_only_access(&'a self) -> Self::PendOnlyAccessor {
+        InnerPendAccessor
+    }
+}
+
+/// A pended operation is always a notification. Notify all mailboxes.
+impl<'a> RunPendedOp for InnerFullAccessor<'a> {
+    fn run_pended_op(&mut self) {
+        self.notify_all();
+    }
+}
+
+impl<'a> InnerFullAccessor<'a> {
+    fn notify_all(&self) {
+        for mailbox in self.mailboxes.lock_now_or_die().iter() {
+            mailbox.notify_allow_isr();
+        }
+    }
+}
+
+impl Observers {
+    pub(super) const fn new() -> Self {
+        Self {
+            inner: RefCellSchedSafe::new(SoftLock::new(Inner {
+                mailboxes: Spin::new(Vec::new()),
+            })),
+            count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Important: *must not* call this method in ISR context.
+    fn add(&self, mailbox: Arc<Mailbox>) {
+        // Should always grant full access to a task.
+        self.inner.with_suspended_scheduler(|inner, _| {
+            inner.must_with_full_access(|full_access| {
+                let mut mailboxes = full_access.mailboxes.lock_now_or_die();
+                mailboxes.push(mailbox);
+                self.count.store(mailboxes.len(), Ordering::SeqCst);
+            })
+        });
+    }
+
+    /// Important: *must not* call this method in ISR context.
+    fn remove(&self, mailbox: &Arc<Mailbox>) {
+        // Should always grant full access to a task.
+        self.inner.with_suspended_scheduler(|inner, _| {
+            inner.must_with_full_access(|full_access| {
+                let mut mailboxes = full_access.mailboxes.lock_now_or_die();
+                mailboxes.retain(|observer| !Arc::ptr_eq(observer, mailbox));
+                self.count.store(mailboxes.len(), Ordering::SeqCst);
+            })
+        });
+    }
+
+    /// Wake up the tasks waiting on the wait sets. If it preempts a task
+    /// accessing the mailboxes, the task notifies them on its behalf.
+    ///
+    /// This method is allowed in ISR context.
+    pub(super) fn notify_allow_isr(&self) {
+        if self.count.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        self.inner.with_suspended_scheduler(|inner, _| {
+            inner.with_access(|access| match access {
+                Access::Full { full_access } => full_access.notify_all(),
+                // The full access owner will notify the mailboxes on our
+                // behalf.
+                Access::PendOnly { .. } => {}
+            })
+        });
+    }
+}
+
+/// A synchronization primitive that a [`WaitSet`] can wait on.
+pub trait Selectable: private::Sealed {
+    /// Return if an operation on the primitive can proceed without blocking,
+    /// e.g., consuming from a channel. Note that the read value may become
+    /// stale immediately after it is read.
+    fn is_ready(&self) -> bool;
+}
+
+/// A channel consumer is ready when the channel is not empty.
+impl<T, const N: usize> Selectable for Consumer<T, N> {
+    fn is_ready(&self) -> bool {
+        self.occupied_semaphore().count() > 0
+    }
+}
+
+impl<T, const N: usize> private::Sealed for Consumer<T, N> {
+    fn observers(&self) -> &Observers {
+        self.occupied_semaphore().observers()
+    }
+}
+
+/// A semaphore is ready when its counter is positive.
+impl Selectable for Semaphore {
+    fn is_ready(&self) -> bool {
+        self.count() > 0
+    }
+}
+
+impl private::Sealed for Semaphore {
+    fn observers(&self) -> &Observers {
+        &self.observers
+    }
+}
+
+/// A mailbox is ready when it has received a notification not yet taken by
+/// a waiting task.
+impl Selectable for Mailbox {
+    fn is_ready(&self) -> bool {
+        self.has_notification()
+    }
+}
+
+impl private::Sealed for Mailbox {
+    fn observers(&self) -> &Observers {
+        &self.observers
+    }
+}
+
+/// A cancellation token is ready when it is cancelled.
+impl Selectable for CancellationToken {
+    fn is_ready(&self) -> bool {
+        self.is_cancelled()
+    }
+}
+
+impl private::Sealed for CancellationToken {
+    fn observers(&self) -> &Observers {
+        &self.observers
+    }
+}
+
+/// A set of synchronization primitives that a task waits on at once. The
+/// task blocks until any of them becomes ready and learns which one.
+///
+/// Waiting on a wait set does not perform the operation, e.g., consuming
+/// from a channel. After [`wait`](Self::wait) returns, the task performs it
+/// with the non-blocking method of the primitive, e.g.,
+/// [`Consumer::try_consume_allow_isr`] or [`Mailbox::try_wait_allow_isr`],
+/// which may still fail if another task performed it in between.
+///
+/// Ready primitives are reported in turns, so a busy primitive cannot starve
+/// the others.
+///
+/// # Example
+/// ```rust
+/// let mut set = WaitSet::new();
+/// let commands = set.add(&command_consumer);
+/// let config = set.add(&config_consumer);
+/// let reset = set.add(&RESET_MAILBOX);
+///
+/// loop {
+///     let ready = set.wait();
+///     if ready == commands {
+///         if let Some(cmd) = command_consumer.try_consume_allow_isr() {
+///             handle(cmd);
+///         }
+///     } else if ready == config {
+///         // ...
+///     } else if ready == reset && RESET_MAILBOX.try_wait_allow_isr() {
+///         // ...
+///     }
+/// }
+/// ```
+pub struct WaitSet<'a> {
+    /// The mailbox notified when any primitive may have become ready.
+    mailbox: Arc<Mailbox>,
+    sources: Vec<&'a dyn Selectable>,
+    /// The index of the primitive checked first by the next wait.
+    next: usize,
+}
+
+impl<'a> WaitSet<'a> {
+    /// Create an empty wait set.
+    pub fn new() -> Self {
+        Self {
+            mailbox: Arc::new(Mailbox::new()),
+            sources: Vec::new(),
+            next: 0,
+        }
+    }
+
+    /// Add the primitive to the set. Return its index, which
+    /// [`wait`](Self::wait) returns when the primitive is ready.
+    pub fn add(&mut self, source: &'a dyn Selectable) -> usize {
+        source.observers().add(self.mailbox.clone());
+        self.sources.push(source);
+        self.sources.len() - 1
+    }
+
+    /// Return the index of a ready primitive, if any.
+    fn poll(&mut self) -> Option<usize> {
+        let len = self.sources.len();
+        let ready = (0..len)
+            .map(|offset| (self.next + offset) % len)
+            .find(|&index| self.sources[index].is_ready())?;
+        self.next = (ready + 1) % len;
+        Some(ready)
+    }
+
+    /// Block until any primitive in the set is ready and return its index.
+    /// Return immediately if one is already ready.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn wait(&mut self) -> usize {
+        loop {
+            // A primitive becoming ready after the poll notifies the mailbox,
+            // so the task does not miss it.
+            if let Some(ready) = self.poll() {
+                return ready;
+            }
+            self.mailbox.wait();
+        }
+    }
+
+    /// Block until any primitive in the set is ready or the elapsed waiting
+    /// time reaches timeout. Return the index of the ready primitive within
+    /// `Some`, or `None` if timed out.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn wait_timeout(&mut self, timeout_ms: u32) -> Option<usize> {
+        let start = time::get_tick();
+        loop {
+            if let Some(ready) = self.poll() {
+                return Some(ready);
+            }
+            let elapsed = time::get_tick().wrapping_sub(start);
+            if elapsed >= timeout_ms {
+                return None;
+            }
+            self.mailbox.wait_until_timeout(timeout_ms - elapsed);
+        }
+    }
+}
+
+impl Drop for WaitSet<'_> {
+    fn drop(&mut self) {
+        for source in self.sources.iter() {
+            source.observers().remove(&self.mailbox);
+        }
+    }
+}
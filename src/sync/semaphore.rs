@@ -1,4 +1,7 @@
-use super::CondVar;
+use super::{
+    CancellationToken, Cancelled, CondVar, Observers, RetryCounter, UnwindGuard, WakeupOrder,
+};
+use crate::{schedule::current, time, unrecoverable::Lethal};
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 /// A semaphore that has the classic semantic. A counter is associated with
@@ -20,16 +23,33 @@ pub struct Semaphore {
     cv_incremented: CondVar,
     /// Condition variable to wait for a decrease on the counter.
     cv_decremented: CondVar,
+    /// The number of tasks waiting to decrement the counter by more than 1.
+    multi_waiters: AtomicUsize,
+    /// The wait sets to notify on an increase on the counter.
+    pub(super) observers: Observers,
 }
 
 impl Semaphore {
     /// Create a new semaphore.
     pub const fn new(max_count: usize, init_count: usize) -> Self {
+        Self::with_wakeup_order(max_count, init_count, WakeupOrder::Priority)
+    }
+
+    /// Create a new semaphore waking up the blocked tasks in the given order.
+    /// With [`WakeupOrder::Fifo`], tasks blocked on `.down()` or `.up()` are
+    /// served in the order they started waiting, regardless of priority.
+    pub const fn with_wakeup_order(
+        max_count: usize,
+        init_count: usize,
+        order: WakeupOrder,
+    ) -> Self {
         Self {
             count: AtomicUsize::new(init_count),
             max_count,
-            cv_incremented: CondVar::new(),
-            cv_decremented: CondVar::new(),
+            cv_incremented: CondVar::with_wakeup_order(order),
+            cv_decremented: CondVar::with_wakeup_order(order),
+            multi_waiters: AtomicUsize::new(0),
+            observers: Observers::new(),
         }
     }
 
@@ -69,7 +89,8 @@ impl Semaphore {
                 .is_ok()
             {
                 // If we successfully incremented the counter, signal the condition variable.
-                self.cv_incremented.notify_one_allow_isr();
+                self.notify_incremented(1);
+                self.observers.notify_allow_isr();
                 return;
             }
 
@@ -77,10 +98,49 @@ impl Semaphore {
         }
     }
 
+    /// Increment the counter value by 1. Block if the counter value is already
+    /// at the maximum until it is decremented by someone else or the elapsed
+    /// waiting time reaches timeout. Return `Ok(())` if succeeded, or
+    /// `Err(())` if timed out.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn up_timeout(&self, timeout_ms: u32) -> Result<(), ()> {
+        let start = time::get_tick();
+        loop {
+            let cur_cnt = self.count.load(Ordering::SeqCst);
+
+            // If the counter is already at the maximum, wait until it is not.
+            if cur_cnt == self.max_count {
+                let elapsed = time::get_tick().wrapping_sub(start);
+                if elapsed >= timeout_ms
+                    || !self.cv_decremented.wait_without_lock_until_timeout(
+                        || self.count.load(Ordering::SeqCst) < self.max_count,
+                        timeout_ms - elapsed,
+                    )
+                {
+                    return Err(());
+                }
+                continue;
+            }
+
+            // Atomically increment the counter. Fail if others have changed the counter.
+            if self
+                .count
+                .compare_exchange(cur_cnt, cur_cnt + 1, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                self.notify_incremented(1);
+                self.observers.notify_allow_isr();
+                return Ok(());
+            }
+        }
+    }
+
     /// Try to increment the counter value by 1. Return `Err(())` if the counter value
     /// is already at the maximum. Return `Ok(())` if succeeded. Calling this method in
     /// ISR context is allowed.
     pub fn try_up_allow_isr(&self) -> Result<(), ()> {
+        let mut retries = RetryCounter::new();
         loop {
             let cur_cnt = self.count.load(Ordering::SeqCst);
             if cur_cnt == self.max_count {
@@ -92,9 +152,43 @@ impl Semaphore {
                 .compare_exchange(cur_cnt, cur_cnt + 1, Ordering::SeqCst, Ordering::SeqCst)
                 .is_ok()
             {
-                self.cv_incremented.notify_one_allow_isr();
+                self.notify_incremented(1);
+                self.observers.notify_allow_isr();
+                return Ok(());
+            }
+
+            // A higher priority ISR changed the counter in between.
+            retries.retry();
+        }
+    }
+
+    /// Try to increment the counter value by `n` atomically. Return `Err(())`
+    /// if the counter value would exceed the maximum, in which case the
+    /// counter is left unchanged. Return `Ok(())` if succeeded. Equivalent to
+    /// calling [`try_up_allow_isr`](Self::try_up_allow_isr) `n` times, but
+    /// the waiting tasks are woken up at once, e.g., when a DMA-complete ISR
+    /// releases a permit for each transferred byte. Calling this method in
+    /// ISR context is allowed.
+    pub fn up_n_allow_isr(&self, n: usize) -> Result<(), ()> {
+        let mut retries = RetryCounter::new();
+        loop {
+            let cur_cnt = self.count.load(Ordering::SeqCst);
+            if n > self.max_count - cur_cnt {
+                return Err(());
+            }
+
+            if self
+                .count
+                .compare_exchange(cur_cnt, cur_cnt + n, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                self.notify_incremented(n);
+                self.observers.notify_allow_isr();
                 return Ok(());
             }
+
+            // A higher priority ISR changed the counter in between.
+            retries.retry();
         }
     }
 
@@ -102,6 +196,7 @@ impl Semaphore {
     /// is already zero. Return `Ok(())` if succeeded. Calling this method in ISR context
     /// is allowed.
     pub fn try_down_allow_isr(&self) -> Result<(), ()> {
+        let mut retries = RetryCounter::new();
         loop {
             let cur_cnt = self.count.load(Ordering::SeqCst);
             if cur_cnt == 0 {
@@ -116,6 +211,9 @@ impl Semaphore {
                 self.cv_decremented.notify_one_allow_isr();
                 return Ok(());
             }
+
+            // A higher priority ISR changed the counter in between.
+            retries.retry();
         }
     }
 
@@ -151,4 +249,229 @@ impl Semaphore {
             // Otherwise, the increment operation has failed. Try again from the beginning.
         }
     }
+
+    /// Decrement the counter value by 1. Block if the counter value is already
+    /// zero until it is incremented by someone else or the elapsed waiting
+    /// time reaches timeout. Return `Ok(())` if succeeded, or `Err(())` if
+    /// timed out.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn down_timeout(&self, timeout_ms: u32) -> Result<(), ()> {
+        let start = time::get_tick();
+        loop {
+            let cur_cnt = self.count.load(Ordering::SeqCst);
+
+            // If the counter is already at the zero, wait until it is not.
+            if cur_cnt == 0 {
+                let elapsed = time::get_tick().wrapping_sub(start);
+                if elapsed >= timeout_ms
+                    || !self.cv_incremented.wait_without_lock_until_timeout(
+                        || self.count.load(Ordering::SeqCst) > 0,
+                        timeout_ms - elapsed,
+                    )
+                {
+                    return Err(());
+                }
+                continue;
+            }
+
+            // Atomically decrement the counter. Fail if others have changed the counter.
+            if self
+                .count
+                .compare_exchange(cur_cnt, cur_cnt - 1, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                self.cv_decremented.notify_one_allow_isr();
+                return Ok(());
+            }
+        }
+    }
+
+    /// Decrement the counter value by 1. Block if the counter value is already
+    /// zero until it is incremented by someone else or the token is
+    /// cancelled. Return `Ok(())` if succeeded, or `Err(Cancelled)` if
+    /// cancelled.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn down_cancellable(&self, token: &CancellationToken) -> Result<(), Cancelled> {
+        token.wait_for(self, || self.try_down_allow_isr().ok())
+    }
+
+    /// Decrement the counter value by `n` atomically. Block if the counter
+    /// value is less than `n` until it is incremented enough by others. Return
+    /// `Err(())` without blocking if `n` exceeds the maximum counter value,
+    /// or `Ok(())` if succeeded.
+    ///
+    /// No permit is taken until all `n` permits can be taken at once, so
+    /// tasks taking permits one by one may decrement the counter before this
+    /// task does.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn down_multiple(&self, n: usize) -> Result<(), ()> {
+        if n > self.max_count {
+            return Err(());
+        }
+
+        self.multi_waiters.fetch_add(1, Ordering::SeqCst);
+        // Do not leave the task counted if it is unwound while waiting.
+        let waiting = UnwindGuard::new(|| {
+            self.multi_waiters.fetch_sub(1, Ordering::SeqCst);
+        });
+        loop {
+            // If the counter is less than `n`, wait until it is not.
+            self.cv_incremented
+                .wait_without_lock_until(|| self.count.load(Ordering::SeqCst) >= n);
+
+            // Get the latest count.
+            let cur_cnt = self.count.load(Ordering::SeqCst);
+
+            // If now it is less than `n` again, we should continue to wait.
+            if cur_cnt < n {
+                continue;
+            }
+
+            // Atomically decrement the counter. Fail if others have changed the counter.
+            if self
+                .count
+                .compare_exchange(cur_cnt, cur_cnt - n, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                break;
+            }
+
+            // Otherwise, the decrement operation has failed. Try again from the beginning.
+        }
+        waiting.disarm();
+        self.multi_waiters.fetch_sub(1, Ordering::SeqCst);
+
+        self.notify_decremented(n);
+        Ok(())
+    }
+
+    /// Try to decrement the counter value by `n` atomically. Return `Err(())` if the
+    /// counter value is less than `n`. Return `Ok(())` if succeeded. Calling this
+    /// method in ISR context is allowed.
+    pub fn try_down_multiple_allow_isr(&self, n: usize) -> Result<(), ()> {
+        let mut retries = RetryCounter::new();
+        loop {
+            let cur_cnt = self.count.load(Ordering::SeqCst);
+            if cur_cnt < n {
+                return Err(());
+            }
+
+            if self
+                .count
+                .compare_exchange(cur_cnt, cur_cnt - n, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                self.notify_decremented(n);
+                return Ok(());
+            }
+
+            // A higher priority ISR changed the counter in between.
+            retries.retry();
+        }
+    }
+
+    /// Return a guard that increments the counter back if the task is unwound
+    /// before the guard is disarmed. A primitive taking a slot from the
+    /// semaphore, e.g., a channel, holds the guard until it has used the
+    /// slot, so that the slot is not leaked.
+    pub(super) fn give_back_on_unwind(&self) -> UnwindGuard<impl FnOnce() + '_> {
+        UnwindGuard::new(move || {
+            let _ = self.try_up_allow_isr();
+        })
+    }
+
+    /// Wake up the tasks waiting for an increase on the counter. Each of them
+    /// decrements the counter by 1, so all of them are woken up if the
+    /// counter is increased by more than 1. A task waiting to decrement the
+    /// counter by more than 1 may not be satisfied by the increase, and it
+    /// would discard the notification. So all waiting tasks are also woken up
+    /// if such a task exists.
+    fn notify_incremented(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        if n > 1 || self.multi_waiters.load(Ordering::SeqCst) > 0 {
+            self.cv_incremented.notify_all_allow_isr();
+        } else {
+            self.cv_incremented.notify_one_allow_isr();
+        }
+    }
+
+    /// Wake up the tasks waiting for a decrease on the counter. Each of them
+    /// increments the counter by 1, so all of them are woken up if the
+    /// counter is decreased by more than 1.
+    fn notify_decremented(&self, n: usize) {
+        match n {
+            0 => {}
+            1 => self.cv_decremented.notify_one_allow_isr(),
+            _ => self.cv_decremented.notify_all_allow_isr(),
+        }
+    }
+
+    /// Decrement the counter value by 1 and return a permit that increments
+    /// it back when dropped. Block if the counter value is already zero until
+    /// it is incremented by someone else.
+    ///
+    /// Since the permit is also dropped when a panicking task is unwound, the
+    /// count is not leaked by a panic between the decrement and the
+    /// increment.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn acquire(&self) -> SemaphorePermit<'_> {
+        self.down();
+        SemaphorePermit { semaphore: self }
+    }
+
+    /// Try to decrement the counter value by 1. Return a permit that increments
+    /// it back when dropped within `Some` if succeeded, or `None` if the
+    /// counter value is already zero. Calling this method in ISR context is
+    /// allowed.
+    pub fn try_acquire_allow_isr(&self) -> Option<SemaphorePermit<'_>> {
+        self.try_down_allow_isr()
+            .ok()
+            .map(|_| SemaphorePermit { semaphore: self })
+    }
+}
+
+/// A decrement of a [`Semaphore`] counter, returned by
+/// [`Semaphore::acquire`]. The counter is incremented back when the permit is
+/// dropped, including when the owning task is unwound.
+#[must_use]
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl SemaphorePermit<'_> {
+    /// Consume the permit without incrementing the counter back, so that the
+    /// decrement becomes permanent.
+    pub fn forget(self) {
+        core::mem::forget(self)
+    }
+}
+
+/// Increment the counter back. The permit may be dropped in ISR context if it
+/// was acquired there, in which case the counter cannot be at the maximum.
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if current::is_in_isr_context() {
+            self.semaphore.try_up_allow_isr().unwrap_or_die();
+        } else {
+            self.semaphore.up();
+        }
+    }
+}
+
+#[cfg(feature = "soft_lock_stats")]
+impl Semaphore {
+    /// Return the contention statistics of the semaphore, merged from those
+    /// waiting for an increase and a decrease. See
+    /// [`soft_lock_stats`](crate::debug::soft_lock_stats).
+    pub fn soft_lock_stats(&self) -> crate::debug::soft_lock_stats::SoftLockStats {
+        self.cv_incremented
+            .soft_lock_stats()
+            .merge(self.cv_decremented.soft_lock_stats())
+    }
 }
@@ -1,3 +1,8 @@
+use super::RetryCounter;
+#[cfg(feature = "soft_lock_stats")]
+use crate::debug::soft_lock_stats::SoftLockStats;
+#[cfg(feature = "soft_lock_stats")]
+use core::sync::atomic::AtomicU32;
 use core::sync::atomic::{AtomicBool, Ordering};
 
 /// Indicate that certain operation on the struct can be pended and get executed
@@ -14,17 +19,31 @@ use core::sync::atomic::{AtomicBool, Ordering};
 ///
 /// The two required methods should respectively return the two accessor types
 /// given the `&self` reference.
-pub(crate) trait AllowPendOp<'a> {
+///
+/// The fields of the protected content are shared between the two accessors
+/// and accessed concurrently by the task and ISRs, so they should be atomics
+/// or cells that tolerate it, e.g., [`Spin`](super::Spin) for fields that are
+/// only ever accessed with the full access.
+pub trait AllowPendOp<'a> {
+    /// The accessor granting access to all fields.
     type FullAccessor: RunPendedOp + 'a;
+    /// The accessor granting access to the fields needed to pend an
+    /// operation.
     type PendOnlyAccessor: 'a;
 
+    /// Return the accessor granting access to all fields.
     fn full_access(&'a self) -> Self::FullAccessor;
+    /// Return the accessor granting access to the fields needed to pend an
+    /// operation.
     fn pend_only_access(&'a self) -> Self::PendOnlyAccessor;
 }
 
 /// The `FullAccessor` should check whether there is any pended operation and run
 /// it if exists.
-pub(crate) trait RunPendedOp {
+pub trait RunPendedOp {
+    /// Run the operations pended while the full access was held. It is called
+    /// when the full access is released, possibly more than once if ISRs keep
+    /// pending operations in between.
     fn run_pended_op(&mut self);
 }
 
@@ -46,8 +65,60 @@ pub(crate) trait RunPendedOp {
 ///
 /// Important Note: The wrapper assumes that the preemting thread of execution always
 /// finishes before returning to the prreempted thread of execution, which is the case
-/// for ISRs, but not the case for task context switching in general.
-pub(crate) struct SoftLock<T>
+/// for ISRs, but not the case for task context switching in general. Wrap it with
+/// [`RefCellSchedSafe`](super::RefCellSchedSafe) and access it only with the
+/// scheduler suspended, so that tasks never preempt each other while accessing it.
+///
+/// # Example
+/// A counter of events that an ISR records and a task drains. When the task is
+/// draining, the ISR pends its event, and the task takes it on releasing the
+/// full access.
+/// ```rust
+/// struct Inner {
+///     total: Spin<u32>,
+///     pended: AtomicU32,
+/// }
+///
+/// struct FullAccessor<'a>(&'a Inner);
+/// struct PendAccessor<'a>(&'a AtomicU32);
+///
+/// impl<'a> AllowPendOp<'a> for Inner {
+///     type FullAccessor = FullAccessor<'a>;
+///     type PendOnlyAccessor = PendAccessor<'a>;
+///     fn full_access(&'a self) -> Self::FullAccessor {
+///         FullAccessor(self)
+///     }
+///     fn pend_only_access(&'a self) -> Self::PendOnlyAccessor {
+///         PendAccessor(&self.pended)
+///     }
+/// }
+///
+/// impl RunPendedOp for FullAccessor<'_> {
+///     fn run_pended_op(&mut self) {
+///         *self.0.total.lock() += self.0.pended.swap(0, Ordering::SeqCst);
+///     }
+/// }
+///
+/// static EVENTS: RefCellSchedSafe<SoftLock<Inner>> = RefCellSchedSafe::new(SoftLock::new(
+///     Inner { total: Spin::new(0), pended: AtomicU32::new(0) },
+/// ));
+///
+/// // In an IRQ handler.
+/// EVENTS.with_suspended_scheduler(|events, _| {
+///     events.with_access(|access| match access {
+///         Access::Full { full_access } => *full_access.0.total.lock() += 1,
+///         Access::PendOnly { pend_access } => {
+///             pend_access.0.fetch_add(1, Ordering::SeqCst);
+///         }
+///     })
+/// });
+///
+/// // In a task.
+/// let total = EVENTS.with_suspended_scheduler(|events, _| {
+///     events.must_with_full_access(|full_access| *full_access.0.total.lock())
+/// });
+/// ```
+pub struct SoftLock<T>
 where
     for<'b> T: AllowPendOp<'b>,
 {
@@ -57,24 +128,67 @@ where
     pending: AtomicBool,
     /// Whether a `FullAccessor` is active.
     locked: AtomicBool,
+    /// Counters of the accesses granted.
+    #[cfg(feature = "soft_lock_stats")]
+    stats: Stats,
+}
+
+/// Counters of the accesses granted by a [`SoftLock`].
+#[cfg(feature = "soft_lock_stats")]
+struct Stats {
+    /// The number of times `Full` access was granted.
+    full: AtomicU32,
+    /// The number of times `PendOnly` access was granted.
+    pend_only: AtomicU32,
+    /// The number of operations pended since the pended operations were last
+    /// run.
+    pended: AtomicU32,
+    /// The maximum number of pended operations run by a single release of
+    /// `Full` access.
+    max_pended: AtomicU32,
+}
+
+#[cfg(feature = "soft_lock_stats")]
+impl Stats {
+    const fn new() -> Self {
+        Self {
+            full: AtomicU32::new(0),
+            pend_only: AtomicU32::new(0),
+            pended: AtomicU32::new(0),
+            max_pended: AtomicU32::new(0),
+        }
+    }
 }
 
 impl<T> SoftLock<T>
 where
     for<'b> T: AllowPendOp<'b>,
 {
-    pub(crate) const fn new(val: T) -> Self {
+    /// Create a new soft lock protecting the given content.
+    pub const fn new(val: T) -> Self {
         Self {
             content: val,
             pending: AtomicBool::new(false),
             locked: AtomicBool::new(false),
+            #[cfg(feature = "soft_lock_stats")]
+            stats: Stats::new(),
+        }
+    }
+
+    /// Return the counters of the accesses granted so far.
+    #[cfg(feature = "soft_lock_stats")]
+    pub fn stats(&self) -> SoftLockStats {
+        SoftLockStats {
+            full: self.stats.full.load(Ordering::Relaxed),
+            pend_only: self.stats.pend_only.load(Ordering::Relaxed),
+            max_pended: self.stats.max_pended.load(Ordering::Relaxed),
         }
     }
 
     /// Get access to the protected content and execute the operation. The closure
     /// should take an `Access` enum type as the argument. The operation should run
     /// based on the `Access` variant. It may be either `Full` or `PendOnly`.
-    pub(crate) fn with_access<'a, F, R>(&'a self, op: F) -> R
+    pub fn with_access<'a, F, R>(&'a self, op: F) -> R
     where
         F: FnOnce(
             Access<<T as AllowPendOp>::FullAccessor, <T as AllowPendOp>::PendOnlyAccessor>,
@@ -88,7 +202,10 @@ where
     /// Get the full access to the protected content and execute the operation. If
     /// `Full` access cannot be granted, the code spins. Otherwise, the closure is
     /// invoked with `FullAccessor` variant as the argument.
-    pub(crate) fn must_with_full_access<'a, F, R>(&'a self, op: F) -> R
+    ///
+    /// NOTE: The code spins forever if it preempts the owner of the full access,
+    /// so *must not* call this method in ISR context.
+    pub fn must_with_full_access<'a, F, R>(&'a self, op: F) -> R
     where
         F: FnOnce(<T as AllowPendOp>::FullAccessor) -> R,
     {
@@ -100,11 +217,14 @@ where
 
 /// Access to the protected contents can be either `Full` or `PendOnly`, yielding
 /// the `FullAccessor` or `PendOnlyAccessor`, respectively.
-pub(crate) enum Access<FullAccessor, PendOnlyAccessor>
+pub enum Access<FullAccessor, PendOnlyAccessor>
 where
     FullAccessor: RunPendedOp,
 {
+    /// No one else is accessing the protected content.
     Full { full_access: FullAccessor },
+    /// The access preempts the owner of the full access, which will run the
+    /// pended operation.
     PendOnly { pend_access: PendOnlyAccessor },
 }
 
@@ -128,11 +248,19 @@ where
     /// the returned guard will assume full access by setting the `locked` bit.
     /// Otherwise, the guard will assume pend-only access.
     fn guard(soft_lock: &'a SoftLock<T>) -> Self {
+        let lock_held = soft_lock
+            .locked
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok();
+
+        #[cfg(feature = "soft_lock_stats")]
+        match lock_held {
+            true => soft_lock.stats.full.fetch_add(1, Ordering::Relaxed),
+            false => soft_lock.stats.pend_only.fetch_add(1, Ordering::Relaxed),
+        };
+
         Self {
-            lock_held: soft_lock
-                .locked
-                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
-                .is_ok(),
+            lock_held,
             soft_lock,
         }
     }
@@ -176,12 +304,19 @@ where
                 // While we are performing the pended operation, we may be preempted by an
                 // ISR that again adds more pended operations. We keep checking until we are
                 // certain that we miss no operation.
+                let mut retries = RetryCounter::new();
+                #[cfg(feature = "soft_lock_stats")]
+                let mut pended = 0;
                 loop {
                     // Check if we have pended operation and clear the pending flag.
                     let prev_pending = self.soft_lock.pending.swap(false, Ordering::SeqCst);
 
                     // If some tasks are pended to be added to the ready queue, add them in.
                     if prev_pending {
+                        #[cfg(feature = "soft_lock_stats")]
+                        {
+                            pended += self.soft_lock.stats.pended.swap(0, Ordering::Relaxed);
+                        }
                         full_access.run_pended_op();
                     }
 
@@ -199,6 +334,7 @@ where
                     // `locked`, but we use `compare_exchange` here for sanity checking that
                     // the previous value is `false`.
                     } else {
+                        retries.retry();
                         let res = self.soft_lock.locked.compare_exchange(
                             false,
                             true,
@@ -210,11 +346,19 @@ where
                         }
                     }
                 }
+
+                #[cfg(feature = "soft_lock_stats")]
+                self.soft_lock
+                    .stats
+                    .max_pended
+                    .fetch_max(pended, Ordering::Relaxed);
             }
             // If the guard assumes pend-only access, we should set the pending flag
             // to true so that the owner having full access will later run the pended
             // operation.
             false => {
+                #[cfg(feature = "soft_lock_stats")]
+                self.soft_lock.stats.pended.fetch_add(1, Ordering::Relaxed);
                 self.soft_lock.pending.store(true, Ordering::SeqCst);
             }
         }
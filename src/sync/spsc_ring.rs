@@ -0,0 +1,196 @@
+use super::Mailbox;
+use crate::{time, unrecoverable};
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// A lock-free ring buffer streaming elements from a single producer, e.g.,
+/// a UART receive ISR, to a single consumer task. Both pushing and popping
+/// are wait-free, and IRQs are never masked.
+///
+/// The consumer task can block until an element is available. The producer
+/// notifies the consumer through a [`Mailbox`] only when it pushes into an
+/// empty ring, so streaming a burst costs one notification.
+///
+/// At most one context may push and at most one task may pop at any time.
+/// A push concurrent with another push fails as if the ring were full, and a
+/// pop concurrent with another pop fails as if the ring were empty.
+///
+/// # Example
+/// ```rust
+/// static RX: SpscRing<u8, 64> = SpscRing::new();
+///
+/// // In the UART IRQ handler.
+/// let _ = RX.push_allow_isr(uart.read_byte());
+///
+/// // In a task.
+/// loop {
+///     let byte = RX.pop();
+///     handle(byte);
+/// }
+/// ```
+pub struct SpscRing<T, const N: usize> {
+    buffer: [UnsafeCell<MaybeUninit<T>>; N],
+    /// The number of elements popped so far, wrapping around on overflow.
+    /// Only the consumer modifies it.
+    head: AtomicUsize,
+    /// The number of elements pushed so far, wrapping around on overflow.
+    /// Only the producer modifies it.
+    tail: AtomicUsize,
+    /// Whether a context is pushing.
+    producing: AtomicBool,
+    /// Whether a context is popping.
+    consuming: AtomicBool,
+    /// The consumer task blocks on the mailbox when the ring is empty.
+    mailbox: Mailbox,
+}
+
+unsafe impl<T: Send, const N: usize> Sync for SpscRing<T, N> {}
+
+/// Clear the flag of the producer or the consumer when dropped.
+struct SideGuard<'a>(&'a AtomicBool);
+
+impl<'a> SideGuard<'a> {
+    /// Set the flag. Return `None` if it is already set by another context.
+    fn acquire(flag: &'a AtomicBool) -> Option<Self> {
+        (!flag.swap(true, Ordering::SeqCst)).then(|| Self(flag))
+    }
+}
+
+impl Drop for SideGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl<T, const N: usize> SpscRing<T, N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY_SLOT: UnsafeCell<MaybeUninit<T>> = UnsafeCell::new(MaybeUninit::uninit());
+
+    /// Create a new empty ring buffer holding at most `N` elements.
+    pub const fn new() -> Self {
+        Self {
+            buffer: [Self::EMPTY_SLOT; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            producing: AtomicBool::new(false),
+            consuming: AtomicBool::new(false),
+            mailbox: Mailbox::new(),
+        }
+    }
+
+    /// Return the maximum number of elements the ring can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Return the number of elements in the ring. Note that the read value may
+    /// become stale immediately after it is read.
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::SeqCst);
+        let tail = self.tail.load(Ordering::SeqCst);
+        tail.wrapping_sub(head)
+    }
+
+    /// Return if the ring is empty. Note that the read value may become stale
+    /// immediately after it is read.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Push an element into the ring and wake up the consumer task if it is
+    /// waiting. If the ring is already full or another context is pushing,
+    /// return the element with `Err`. Otherwise, return `Ok`.
+    ///
+    /// This method is allowed in ISR context.
+    pub fn push_allow_isr(&self, data: T) -> Result<(), T> {
+        let Some(_guard) = SideGuard::acquire(&self.producing) else {
+            return Err(data);
+        };
+
+        let head = self.head.load(Ordering::SeqCst);
+        let tail = self.tail.load(Ordering::SeqCst);
+        if tail.wrapping_sub(head) == N {
+            return Err(data);
+        }
+
+        // The consumer does not read the slot until `tail` is published.
+        unsafe { (*self.buffer[tail % N].get()).write(data) };
+        self.tail.store(tail.wrapping_add(1), Ordering::SeqCst);
+
+        // The consumer blocks only after observing an empty ring. If the ring
+        // was empty right before the push, including when the consumer popped
+        // the remaining elements in between, notify the consumer.
+        if self.head.load(Ordering::SeqCst) == tail {
+            self.mailbox.notify_allow_isr();
+        }
+        Ok(())
+    }
+
+    /// Try to pop an element from the ring. If the ring is empty or another
+    /// context is popping, return `None`. Otherwise, return the element with
+    /// `Some`.
+    ///
+    /// This method is allowed in ISR context.
+    pub fn try_pop_allow_isr(&self) -> Option<T> {
+        let _guard = SideGuard::acquire(&self.consuming)?;
+
+        let head = self.head.load(Ordering::SeqCst);
+        let tail = self.tail.load(Ordering::SeqCst);
+        if head == tail {
+            return None;
+        }
+
+        // The producer does not overwrite the slot until `head` is published.
+        let data = unsafe { (*self.buffer[head % N].get()).assume_init_read() };
+        self.head.store(head.wrapping_add(1), Ordering::SeqCst);
+        Some(data)
+    }
+
+    /// Pop an element from the ring. If the ring is empty, block until there
+    /// is an element.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn pop(&self) -> T {
+        unrecoverable::die_if_in_isr();
+
+        loop {
+            if let Some(data) = self.try_pop_allow_isr() {
+                return data;
+            }
+            // A notification left from an earlier push may wake the task up
+            // with the ring still empty. Just check again.
+            self.mailbox.wait();
+        }
+    }
+
+    /// Pop an element from the ring. If the ring is empty, block until there
+    /// is an element or the elapsed waiting time reaches timeout. Return the
+    /// element within `Some`, or `None` if timed out.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn pop_timeout(&self, timeout_ms: u32) -> Option<T> {
+        unrecoverable::die_if_in_isr();
+
+        let start = time::get_tick();
+        loop {
+            if let Some(data) = self.try_pop_allow_isr() {
+                return Some(data);
+            }
+            let elapsed = time::get_tick().wrapping_sub(start);
+            if elapsed >= timeout_ms {
+                return None;
+            }
+            self.mailbox.wait_until_timeout(timeout_ms - elapsed);
+        }
+    }
+}
+
+/// Drop the elements left in the ring.
+impl<T, const N: usize> Drop for SpscRing<T, N> {
+    fn drop(&mut self) {
+        while self.try_pop_allow_isr().is_some() {}
+    }
+}
@@ -0,0 +1,33 @@
+/// Run a cleanup closure when dropped, unless disarmed first. A primitive
+/// performing an operation in multiple steps creates a guard right after a
+/// step that must be reverted if the task is unwound before the operation
+/// completes, e.g., giving back a slot taken from a [`Semaphore`], and
+/// disarms it once the operation completes.
+///
+/// [`Semaphore`]: super::Semaphore
+pub(super) struct UnwindGuard<F: FnOnce()> {
+    cleanup: Option<F>,
+}
+
+impl<F: FnOnce()> UnwindGuard<F> {
+    /// Arm the guard with the cleanup closure.
+    pub(super) fn new(cleanup: F) -> Self {
+        Self {
+            cleanup: Some(cleanup),
+        }
+    }
+
+    /// The operation completes. Drop the guard without running the cleanup.
+    pub(super) fn disarm(mut self) {
+        self.cleanup = None;
+    }
+}
+
+/// Reaching here with the guard armed means the task is being unwound.
+impl<F: FnOnce()> Drop for UnwindGuard<F> {
+    fn drop(&mut self) {
+        if let Some(cleanup) = self.cleanup.take() {
+            cleanup();
+        }
+    }
+}
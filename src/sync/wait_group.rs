@@ -0,0 +1,245 @@
+use super::{
+    Access, AllowPendOp, Mailbox, RefCellSchedSafe, RetryCounter, RunPendedOp, SoftLock, Spin,
+};
+use crate::unrecoverable;
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A counter of outstanding work items, similar to Go's `sync.WaitGroup`. A
+/// coordinator task [`add`](Self::add)s the number of work items, workers
+/// call [`done_allow_isr`](Self::done_allow_isr) when finishing each of
+/// them, and the coordinator blocks in [`wait`](Self::wait) until the count
+/// reaches zero.
+///
+/// Like the [`Mailbox`], a [`WaitGroup`] never masks IRQs. If an ISR
+/// finishes the last work item while a task is modifying the list of waiting
+/// tasks, the task wakes up the waiting tasks on behalf of the ISR.
+///
+/// A restartable worker that panics before finishing its work item should
+/// call [`done_allow_isr`](Self::done_allow_isr) in its restarted instance,
+/// otherwise the count never reaches zero.
+///
+/// # Example
+/// ```rust
+/// static PENDING: WaitGroup = WaitGroup::new();
+///
+/// // In the coordinator task.
+/// PENDING.add(CHUNKS);
+/// for chunk in 0..CHUNKS {
+///     WORK.produce(chunk);
+/// }
+/// PENDING.wait();
+///
+/// // In each worker task.
+/// let chunk = WORK.consume();
+/// process(chunk);
+/// PENDING.done_allow_isr().unwrap();
+/// ```
+pub struct WaitGroup {
+    inner: RefCellSchedSafe<SoftLock<Inner>>,
+}
+
+struct Inner {
+    /// The number of outstanding work items. It is updated without the full
+    /// access, so that an ISR never needs to pend an update of the count.
+    count: AtomicUsize,
+    /// The mailboxes of the tasks waiting for the count to reach zero. The
+    /// spin lock around it is only for sanity check. This field should not
+    /// be accessed concurrently.
+    waiters: Spin<Vec<Arc<Mailbox>>>,
+}
+
+/// Representing full access to all fields of the [`WaitGroup`].
+struct InnerFullAccessor<'a> {
+    count: &'a AtomicUsize,
+    waiters: &'a Spin<Vec<Arc<Mailbox>>>,
+}
+
+/// Representing pend-only access to the [`WaitGroup`]. Only the count can be
+/// updated, and the full access owner wakes up the waiting tasks later.
+struct InnerPendAccessor<'a> {
+    count: &'a AtomicUsize,
+}
+
+/// Bind the accessor types.
+impl<'a> AllowPendOp<'a> for Inner {
+    type FullAccessor = InnerFullAccessor<'a>;
+    type PendOnlyAccessor = InnerPendAccessor<'a>;
+    fn full_access(&'a self) -> Self::FullAccessor {
+        Self::FullAccessor {
+            count: &self.count,
+            waiters: &self.waiters,
+        }
+    }
+
+    fn pend_only_access(&'a self) -> Self::PendOnlyAccessor {
+        Self::PendOnlyAccessor { count: &self.count }
+    }
+}
+
+/// A pended operation is always finishing the last work item. Wake up all
+/// waiting tasks.
+impl<'a> RunPendedOp for InnerFullAccessor<'a> {
+    fn run_pended_op(&mut self) {
+        self.wake_all();
+    }
+}
+
+impl<'a> InnerFullAccessor<'a> {
+    /// Wake up and remove all waiting tasks if the count is zero.
+    fn wake_all(&self) {
+        if self.count.load(Ordering::SeqCst) != 0 {
+            return;
+        }
+        for mailbox in self.waiters.lock_now_or_die().drain(..) {
+            mailbox.notify_allow_isr();
+        }
+    }
+}
+
+/// Decrement the count by 1 unless it is already zero.
+fn decrement(count: &AtomicUsize) -> Result<(), ()> {
+    let mut retries = RetryCounter::new();
+    loop {
+        let cur_cnt = count.load(Ordering::SeqCst);
+        if cur_cnt == 0 {
+            return Err(());
+        }
+
+        if count
+            .compare_exchange(cur_cnt, cur_cnt - 1, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            return Ok(());
+        }
+
+        // A higher priority ISR changed the count in between.
+        retries.retry();
+    }
+}
+
+impl WaitGroup {
+    /// Create a new [`WaitGroup`] without outstanding work items.
+    pub const fn new() -> Self {
+        Self {
+            inner: RefCellSchedSafe::new(SoftLock::new(Inner {
+                count: AtomicUsize::new(0),
+                waiters: Spin::new(Vec::new()),
+            })),
+        }
+    }
+
+    /// Return the number of outstanding work items. Note that the read value
+    /// may become stale immediately after it is read.
+    pub fn count(&self) -> usize {
+        self.inner.with_suspended_scheduler(|wait_group, _| {
+            wait_group.with_access(|access| match access {
+                Access::Full { full_access } => full_access.count.load(Ordering::SeqCst),
+                Access::PendOnly { pend_access } => pend_access.count.load(Ordering::SeqCst),
+            })
+        })
+    }
+
+    /// Add `n` outstanding work items.
+    pub fn add(&self, n: usize) {
+        self.inner.with_suspended_scheduler(|wait_group, _| {
+            wait_group.with_access(|access| match access {
+                Access::Full { full_access } => full_access.count.fetch_add(n, Ordering::SeqCst),
+                Access::PendOnly { pend_access } => {
+                    pend_access.count.fetch_add(n, Ordering::SeqCst)
+                }
+            })
+        });
+    }
+
+    /// Finish one outstanding work item. Wake up all waiting tasks if it is
+    /// the last one. Return `Err(())` if there is no outstanding work item,
+    /// or `Ok(())` otherwise.
+    ///
+    /// This method is allowed in ISR context.
+    pub fn done_allow_isr(&self) -> Result<(), ()> {
+        self.inner.with_suspended_scheduler(|wait_group, _| {
+            wait_group.with_access(|access| match access {
+                // If we have full access to the inner fields, we directly wake
+                // up the waiting tasks.
+                Access::Full { full_access } => {
+                    decrement(full_access.count)?;
+                    full_access.wake_all();
+                    Ok(())
+                }
+                // If other context is running with the full access and we
+                // preempt it, we get pend-only access. We only decrement the
+                // count, and the full access owner will wake up the waiting
+                // tasks on our behalf.
+                Access::PendOnly { pend_access } => decrement(pend_access.count),
+            })
+        })
+    }
+
+    /// Block the calling task until the count reaches zero.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn wait(&self) {
+        // Cannot time out without a timeout.
+        self.wait_inner(None);
+    }
+
+    /// Block the calling task until the count reaches zero or the elapsed
+    /// waiting time reaches timeout. Return `true` if the count reaches zero,
+    /// or `false` if timed out.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn wait_timeout(&self, timeout_ms: u32) -> bool {
+        self.wait_inner(Some(timeout_ms))
+    }
+
+    /// Block the calling task until the count reaches zero or it times out.
+    /// Return whether the count reaches zero.
+    fn wait_inner(&self, timeout_ms: Option<u32>) -> bool {
+        unrecoverable::die_if_in_isr();
+
+        let mailbox = Arc::new(Mailbox::new());
+
+        // Suspend scheduling and acquire full access to the fields. If the
+        // count has not reached zero yet, register the mailbox. The last work
+        // item finished in between by an ISR is pended and wakes up the
+        // registered mailbox.
+        let finished = self.inner.with_suspended_scheduler(|wait_group, _| {
+            wait_group.must_with_full_access(|full_access| {
+                if full_access.count.load(Ordering::SeqCst) == 0 {
+                    return true;
+                }
+                full_access.waiters.lock_now_or_die().push(mailbox.clone());
+                false
+            })
+        });
+        if finished {
+            return true;
+        }
+
+        // The mailbox counts the notification if the last work item is
+        // finished before the task blocks on it.
+        let timeout_ms = match timeout_ms {
+            Some(timeout_ms) => timeout_ms,
+            None => {
+                mailbox.wait();
+                return true;
+            }
+        };
+        if mailbox.wait_until_timeout(timeout_ms) {
+            return true;
+        }
+
+        // Timed out. Unregister the mailbox if it is still registered. The
+        // last work item may have been finished right after the timeout.
+        let registered = self.inner.with_suspended_scheduler(|wait_group, _| {
+            wait_group.must_with_full_access(|full_access| {
+                let mut waiters = full_access.waiters.lock_now_or_die();
+                let len = waiters.len();
+                waiters.retain(|registered| !Arc::ptr_eq(registered, &mailbox));
+                waiters.len() != len
+            })
+        });
+        !registered
+    }
+}
@@ -1,18 +1,49 @@
 use super::{
-    Access, AllowPendOp, Lockable, RefCellSchedSafe, RunPendedOp, SoftLock, Spin, UnlockableGuard,
+    Access, AllowPendOp, Lockable, Mailbox, RefCellSchedSafe, RunPendedOp, SoftLock, Spin,
+    UnlockableGuard, UnwindGuard,
 };
 use crate::{
     interrupt::context_switch,
     schedule::{current, scheduler::Scheduler},
-    task::{TaskListAdapter, TaskListInterfaces, TaskState},
-    unrecoverable,
+    task::{Task, TaskListAdapter, TaskListInterfaces, TaskState},
+    time,
+    unrecoverable::{self, Lethal},
 };
-use core::sync::atomic::{AtomicUsize, Ordering};
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use intrusive_collections::LinkedList;
 
-/// Queue for blocked tasks waiting for notification.
+/// The order in which the tasks blocked on a [`Semaphore`](super::Semaphore)
+/// or a [`Mutex`](super::Mutex) are woken up.
+///
+/// A woken task may find its condition unmet when it resumes, e.g., another
+/// task locked the released mutex first. With [`Priority`](Self::Priority),
+/// such a task waits again behind the other tasks, so a task of the same
+/// priority as its competitors may starve. The other orders let it keep its
+/// place.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum WakeupOrder {
+    /// Wake up the task with the highest priority first, and among them the
+    /// one that has waited the longest. A woken task whose condition is not
+    /// met waits again behind the other tasks of its priority.
+    #[default]
+    Priority,
+    /// Wake up the task with the highest priority first, and among them the
+    /// one that has waited the longest. A woken task whose condition is not
+    /// met keeps its place in front of the other tasks of its priority.
+    PriorityFifo,
+    /// Wake up the task that has waited the longest first, regardless of
+    /// priority. A woken task whose condition is not met keeps its place in
+    /// front of the other tasks.
+    Fifo,
+}
+
+/// Queue for blocked tasks waiting for notification. Tasks waiting with a
+/// timeout and those waiting without one are woken up in the same order.
 pub(super) struct WaitQueue {
     inner: RefCellSchedSafe<SoftLock<Inner>>,
+    /// The order in which the tasks are woken up.
+    order: WakeupOrder,
 }
 
 /// The inner content of a wait queue.
@@ -21,23 +52,51 @@ struct Inner {
     /// be sorted based on task priority when popping out tasks. The spin lock
     /// around it is only for sanity check.
     queue: Spin<LinkedList<TaskListAdapter>>,
+    /// The tasks waiting with a timeout. Such a task blocks on a mailbox of
+    /// its own, which puts it into the sleeping queue rather than `queue`.
+    /// The spin lock around it is only for sanity check.
+    timed: Spin<Vec<TimedWaiter>>,
+    /// The sequence number given to the next task starting to wait. It orders
+    /// the tasks in `queue` against those in `timed`.
+    next_seq: AtomicU32,
     /// When an ISR is trying to dequeue a task when the queue is already locked,
     /// it increments the notification counter, so that the lock holder can later
     /// dequeue the task on behalf of the ISR.
     notify_cnt: AtomicUsize,
+    /// When an ISR is trying to dequeue all tasks when the queue is already
+    /// locked, it sets this flag, so that the lock holder can later dequeue
+    /// the tasks on behalf of the ISR.
+    notify_all: AtomicBool,
+    /// The order in which the tasks are woken up.
+    order: WakeupOrder,
+}
+
+/// A task waiting with a timeout.
+struct TimedWaiter {
+    /// The mailbox the task blocks on.
+    mailbox: Arc<Mailbox>,
+    /// The waiting task.
+    task: Arc<Task>,
+    /// See [`Inner::next_seq`].
+    seq: u32,
 }
 
 /// Representing full access to the queue.
 struct InnerFullAccessor<'a> {
     queue: &'a Spin<LinkedList<TaskListAdapter>>,
+    timed: &'a Spin<Vec<TimedWaiter>>,
+    next_seq: &'a AtomicU32,
     notify_cnt: &'a AtomicUsize,
+    notify_all: &'a AtomicBool,
+    order: WakeupOrder,
 }
 
 /// Representing pend-only access to the queue. Using this accessor one can only
 /// increment the notification counter indicating that one more task needs to be
-/// notified.
+/// notified, or set the flag indicating that all tasks need to be notified.
 struct InnerPendAccessor<'a> {
     notify_cnt: &'a AtomicUsize,
+    notify_all: &'a AtomicBool,
 }
 
 /// Bind the accessor types.
@@ -47,46 +106,169 @@ impl<'a> AllowPendOp<'a> for Inner {
     fn full_access(&'a self) -> InnerFullAccessor<'a> {
         InnerFullAccessor {
             queue: &self.queue,
+            timed: &self.timed,
+            next_seq: &self.next_seq,
             notify_cnt: &self.notify_cnt,
+            notify_all: &self.notify_all,
+            order: self.order,
         }
     }
     fn pend_only_access(&'a self) -> InnerPendAccessor<'a> {
         InnerPendAccessor {
             notify_cnt: &self.notify_cnt,
+            notify_all: &self.notify_all,
         }
     }
 }
 
 /// If the notification counter is non-zero, we should notify tasks as many times
-/// as indicated by the counter.
+/// as indicated by the counter. If the flag to notify all tasks is set, we should
+/// notify all tasks.
 impl<'a> RunPendedOp for InnerFullAccessor<'a> {
     fn run_pended_op(&mut self) {
-        let mut locked_queue = self.queue.lock_now_or_die();
+        if self.notify_all.swap(false, Ordering::SeqCst) {
+            self.notify_cnt.store(0, Ordering::SeqCst);
+            self.notify_all_waiters();
+            return;
+        }
         let cnt = self.notify_cnt.swap(0, Ordering::SeqCst);
         for _ in 0..cnt {
-            if let Some(task) = locked_queue.pop_highest_priority() {
-                Scheduler::accept_task(task);
-            } else {
+            if !self.notify_next_waiter() {
                 break;
             }
         }
     }
 }
 
+impl<'a> InnerFullAccessor<'a> {
+    /// Put the task into the queue. A task that was woken up but did not have
+    /// its condition met, i.e., `requeued` is true, keeps its place unless
+    /// the order is [`WakeupOrder::Priority`].
+    fn enqueue(&self, queue: &mut LinkedList<TaskListAdapter>, task: Arc<Task>, requeued: bool) {
+        if requeued && self.order != WakeupOrder::Priority {
+            queue.push_front(task);
+        } else {
+            task.set_wait_seq(self.next_seq.fetch_add(1, Ordering::SeqCst));
+            queue.push_back(task);
+        }
+    }
+
+    /// Wake up the task to be woken up next in the order of the queue, if
+    /// there exists. Return whether a task was woken up.
+    fn notify_next_waiter(&self) -> bool {
+        let mut locked_queue = self.queue.lock_now_or_die();
+        let mut locked_timed = self.timed.lock_now_or_die();
+
+        let timed_pos = (0..locked_timed.len()).reduce(|best, pos| {
+            let (cand, cur) = (&locked_timed[pos], &locked_timed[best]);
+            if wakes_before(&cand.task, cand.seq, &cur.task, cur.seq, self.order) {
+                pos
+            } else {
+                best
+            }
+        });
+        let wake_timed = match (peek_next(&locked_queue, self.order), timed_pos) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(task), Some(pos)) => {
+                let timed = &locked_timed[pos];
+                wakes_before(
+                    &timed.task,
+                    timed.seq,
+                    task,
+                    task.get_wait_seq(),
+                    self.order,
+                )
+            }
+        };
+
+        match timed_pos {
+            Some(pos) if wake_timed => {
+                locked_timed.swap_remove(pos).mailbox.notify_allow_isr();
+                true
+            }
+            _ => match pop_next(&mut locked_queue, self.order) {
+                Some(task) => {
+                    Scheduler::accept_task(task);
+                    true
+                }
+                None => false,
+            },
+        }
+    }
+
+    /// Wake up all tasks in the queue.
+    fn notify_all_waiters(&self) {
+        let mut locked_queue = self.queue.lock_now_or_die();
+        while let Some(task) = pop_next(&mut locked_queue, self.order) {
+            Scheduler::accept_task(task);
+        }
+        for timed in self.timed.lock_now_or_die().drain(..) {
+            timed.mailbox.notify_allow_isr();
+        }
+    }
+}
+
 impl Inner {
-    const fn new() -> Self {
+    const fn new(order: WakeupOrder) -> Self {
         Self {
             queue: Spin::new(LinkedList::new(TaskListAdapter::NEW)),
+            timed: Spin::new(Vec::new()),
+            next_seq: AtomicU32::new(0),
             notify_cnt: AtomicUsize::new(0),
+            notify_all: AtomicBool::new(false),
+            order,
         }
     }
 }
 
+/// Pop out the task to be woken up next in the given order.
+fn pop_next(queue: &mut LinkedList<TaskListAdapter>, order: WakeupOrder) -> Option<Arc<Task>> {
+    match order {
+        WakeupOrder::Priority | WakeupOrder::PriorityFifo => queue.pop_highest_priority(),
+        WakeupOrder::Fifo => queue.pop_front(),
+    }
+}
+
+/// Return the task [`pop_next`] would pop out, without popping it.
+fn peek_next(queue: &LinkedList<TaskListAdapter>, order: WakeupOrder) -> Option<&Task> {
+    match order {
+        WakeupOrder::Priority | WakeupOrder::PriorityFifo => {
+            queue.iter().fold(None, |next, task| match next {
+                Some(next) if !task.precedes(next) => Some(next),
+                _ => Some(task),
+            })
+        }
+        WakeupOrder::Fifo => queue.front().get(),
+    }
+}
+
+/// Return whether the waiting task `a` should be woken up before `b`, given
+/// the sequence numbers they got when starting to wait.
+fn wakes_before(a: &Task, a_seq: u32, b: &Task, b_seq: u32, order: WakeupOrder) -> bool {
+    if order != WakeupOrder::Fifo {
+        if a.precedes(b) {
+            return true;
+        }
+        if b.precedes(a) {
+            return false;
+        }
+    }
+    // Compare in a wrapping manner, as the sequence number may overflow.
+    (a_seq.wrapping_sub(b_seq) as i32) < 0
+}
+
 impl WaitQueue {
     /// Create a new empty wait queue.
     pub(super) const fn new() -> Self {
+        Self::with_order(WakeupOrder::Priority)
+    }
+
+    /// Create a new empty wait queue waking up the tasks in the given order.
+    pub(super) const fn with_order(order: WakeupOrder) -> Self {
         Self {
-            inner: RefCellSchedSafe::new(SoftLock::<Inner>::new(Inner::new())),
+            inner: RefCellSchedSafe::new(SoftLock::<Inner>::new(Inner::new(order))),
+            order,
         }
     }
 
@@ -115,7 +297,7 @@ impl WaitQueue {
                     current::with_cur_task_arc_explicit_sched_suspend(sched_guard, |cur_task| {
                         cur_task.set_state(TaskState::Blocked);
                         let mut locked_queue = full_access.queue.lock_now_or_die();
-                        locked_queue.push_back(cur_task);
+                        full_access.enqueue(&mut locked_queue, cur_task, false);
                     });
                 })
             });
@@ -143,8 +325,9 @@ impl WaitQueue {
         unrecoverable::die_if_in_isr();
 
         // Keep blocking until the predicate is satisfied.
+        let mut requeued = false;
         loop {
-            let res = add_cur_task_to_block_queue_with_condition(self, &mut condition);
+            let res = add_cur_task_to_block_queue_with_condition(self, &mut condition, requeued);
 
             // If the condition is met, we return.
             if let Some(ret) = res {
@@ -154,6 +337,7 @@ impl WaitQueue {
             // Otherwise, we have put the current task to the wait queue.
             // Tell the scheduler to run another task.
             context_switch::yield_current_task();
+            requeued = true;
         }
 
         // Outline the logic to reduce the stack frame size of `.wait_until()`.
@@ -161,6 +345,7 @@ impl WaitQueue {
         fn add_cur_task_to_block_queue_with_condition<F, R>(
             wq: &WaitQueue,
             condition: &mut F,
+            requeued: bool,
         ) -> Option<R>
         where
             F: FnMut() -> Option<R>,
@@ -181,7 +366,7 @@ impl WaitQueue {
                     // Otherwise, put the current task into the queue.
                     current::with_cur_task_arc_explicit_sched_suspend(sched_guard, |cur_task| {
                         cur_task.set_state(TaskState::Blocked);
-                        locked_queue.push_back(cur_task);
+                        full_access.enqueue(&mut locked_queue, cur_task, requeued);
                     });
 
                     None
@@ -219,8 +404,9 @@ impl WaitQueue {
         unrecoverable::die_if_in_isr();
 
         // Keep blocking until the predicate is satisfied.
+        let mut requeued = false;
         loop {
-            let res = add_cur_task_to_block_queue_and_unlock(self, guard, &mut condition);
+            let res = add_cur_task_to_block_queue_and_unlock(self, guard, &mut condition, requeued);
 
             match res {
                 // If the condition is met, we return the lock guard and condition
@@ -232,6 +418,7 @@ impl WaitQueue {
                 Ok(mutex) => {
                     context_switch::yield_current_task();
                     guard = mutex.lock_and_get_guard();
+                    requeued = true;
                 }
             }
         }
@@ -242,6 +429,7 @@ impl WaitQueue {
             wq: &WaitQueue,
             mut guard: G,
             condition: &mut F,
+            requeued: bool,
         ) -> Result<&'a L, (G, R)>
         where
             F: FnMut(&mut G) -> Option<R>,
@@ -267,7 +455,7 @@ impl WaitQueue {
                     // Put the current task into the queue.
                     current::with_cur_task_arc_explicit_sched_suspend(sched_guard, |cur_task| {
                         cur_task.set_state(TaskState::Blocked);
-                        locked_queue.push_back(cur_task);
+                        full_access.enqueue(&mut locked_queue, cur_task, requeued);
                     });
 
                     Ok(mutex)
@@ -276,9 +464,152 @@ impl WaitQueue {
         }
     }
 
+    /// Like [`wait_until`](Self::wait_until), but stop waiting once the
+    /// elapsed waiting time reaches the timeout. Return the value returned
+    /// by `condition` within `Some`, or `None` if timed out.
+    ///
+    /// The task waits in the same order as the tasks waiting without a
+    /// timeout. It blocks on a mailbox of its own while waiting.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub(super) fn wait_until_timeout<F, R>(&self, mut condition: F, timeout_ms: u32) -> Option<R>
+    where
+        F: FnMut() -> Option<R>,
+    {
+        unrecoverable::die_if_in_isr();
+
+        let start = time::get_tick();
+        let mut seq = None;
+        loop {
+            let (_, mailbox, waiter_seq) =
+                match self.add_cur_task_as_timed_waiter(|| condition().ok_or(()), seq) {
+                    Ok(ret) => return Some(ret),
+                    Err(registered) => registered,
+                };
+            // A task woken up but not having its condition met keeps its
+            // place unless the order is `WakeupOrder::Priority`.
+            if self.order != WakeupOrder::Priority {
+                seq = Some(waiter_seq);
+            }
+
+            if !self.wait_on_timed_mailbox(&mailbox, start, timeout_ms) {
+                return condition();
+            }
+        }
+    }
+
+    /// Put the current task into the queue and block it. Wait until some other
+    /// task notifies it or the timeout elapses. The lock guard passed in will
+    /// be released atomically when the task blocks and get locked again when
+    /// the task resumes. Return the lock guard and whether the task was
+    /// notified.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub(super) fn wait_with_lock_timeout<'a, G, L>(&self, guard: G, timeout_ms: u32) -> (G, bool)
+    where
+        G: UnlockableGuard<'a, LockType = L>,
+        L: Lockable<GuardType<'a> = G> + 'a,
+    {
+        unrecoverable::die_if_in_isr();
+
+        let start = time::get_tick();
+        // The condition is never met. Release the lock guard instead.
+        let (lock, mailbox, _) = self
+            .add_cur_task_as_timed_waiter(|| Err::<(), _>(guard.unlock_and_into_lock_ref()), None)
+            .err()
+            .unwrap_or_die();
+
+        let notified = self.wait_on_timed_mailbox(&mailbox, start, timeout_ms);
+        (lock.lock_and_get_guard(), notified)
+    }
+
+    /// Put the current task into the queue of the tasks waiting with a timeout,
+    /// unless `condition` returns `Ok`. The task gets the given sequence
+    /// number if any, or a new one otherwise. Return the value returned by
+    /// `condition` within `Err`, the mailbox the task should block on, and the
+    /// sequence number of the task.
+    fn add_cur_task_as_timed_waiter<F, R, U>(
+        &self,
+        condition: F,
+        seq: Option<u32>,
+    ) -> Result<R, (U, Arc<Mailbox>, u32)>
+    where
+        F: FnOnce() -> Result<R, U>,
+    {
+        // Should always grant full access to a task.
+        self.inner.with_suspended_scheduler(|queue, sched_guard| {
+            queue.must_with_full_access(|full_access| {
+                // Must lock the queue here before evaluating the condition to
+                // prevent deadlock.
+                let _locked_queue = full_access.queue.lock_now_or_die();
+
+                let unmet = condition()?;
+
+                // Register the mailbox before the task blocks, so that a
+                // notification before then is not missed. The mailbox counts
+                // the notification if the task has not blocked yet.
+                let mailbox = Arc::new(Mailbox::new());
+                let seq =
+                    seq.unwrap_or_else(|| full_access.next_seq.fetch_add(1, Ordering::SeqCst));
+                current::with_cur_task_arc_explicit_sched_suspend(sched_guard, |task| {
+                    full_access.timed.lock_now_or_die().push(TimedWaiter {
+                        mailbox: mailbox.clone(),
+                        task,
+                        seq,
+                    });
+                });
+
+                Err((unmet, mailbox, seq))
+            })
+        })
+    }
+
+    /// Remove the mailbox from the tasks waiting with a timeout. Return whether
+    /// it was still registered, i.e., it has not been notified.
+    fn remove_timed_waiter(&self, mailbox: &Arc<Mailbox>) -> bool {
+        // Should always grant full access to a task.
+        self.inner.with_suspended_scheduler(|queue, _| {
+            queue.must_with_full_access(|full_access| {
+                let mut locked_timed = full_access.timed.lock_now_or_die();
+                match locked_timed
+                    .iter()
+                    .position(|waiter| Arc::ptr_eq(&waiter.mailbox, mailbox))
+                {
+                    Some(pos) => {
+                        locked_timed.swap_remove(pos);
+                        true
+                    }
+                    None => false,
+                }
+            })
+        })
+    }
+
+    /// Block on the mailbox registered as a task waiting with a timeout, for
+    /// the time remaining since `start`. Return whether the task was notified.
+    /// The mailbox is no longer registered upon return.
+    fn wait_on_timed_mailbox(&self, mailbox: &Arc<Mailbox>, start: u32, timeout_ms: u32) -> bool {
+        // If the task is unwound, unregister the mailbox, or pass on the
+        // notification it has taken to another task.
+        let registered = UnwindGuard::new(|| {
+            if !self.remove_timed_waiter(mailbox) {
+                self.notify_one_allow_isr();
+            }
+        });
+
+        let elapsed = time::get_tick().wrapping_sub(start);
+        let notified = elapsed < timeout_ms && mailbox.wait_until_timeout(timeout_ms - elapsed);
+        registered.disarm();
+
+        // A notification may have taken the mailbox right after the timeout.
+        // Do not discard it.
+        notified || !self.remove_timed_waiter(mailbox)
+    }
+
     /// Pop a task (if exists) from the queue and mark its state as ready.
     /// This method is allowed in ISR context. The popped the task is the one
-    /// with the highest priority (smallest numerical value) in the queue.
+    /// with the highest priority (smallest numerical value) in the queue,
+    /// unless the queue wakes up the tasks in [`WakeupOrder::Fifo`] order.
     ///
     /// Important: the tasks waiting for nofitication are ordered with respect to
     /// their priorities. If the highest priority task gets notified but does not
@@ -291,10 +622,7 @@ impl WaitQueue {
                 // If we have full access to the inner components, we directly operate
                 // on the queue to make the popped task ready.
                 Access::Full { full_access } => {
-                    let mut locked_queue = full_access.queue.lock_now_or_die();
-                    if let Some(task) = locked_queue.pop_highest_priority() {
-                        Scheduler::accept_task(task);
-                    }
+                    full_access.notify_next_waiter();
                 }
                 // If other context is running with the full access and we preempt it,
                 // we get pend-only access. We increment the counter so that the full
@@ -305,4 +633,33 @@ impl WaitQueue {
             })
         });
     }
+
+    /// Pop all tasks from the queue and mark their states as ready. This
+    /// method is allowed in ISR context.
+    pub(super) fn notify_all_allow_isr(&self) {
+        self.inner.with_suspended_scheduler(|queue, _| {
+            queue.with_access(|access| match access {
+                // If we have full access to the inner components, we directly operate
+                // on the queue to make all tasks ready.
+                Access::Full { full_access } => {
+                    full_access.notify_all_waiters();
+                }
+                // If other context is running with the full access and we preempt it,
+                // we get pend-only access. We set the flag so that the full access
+                // owner can later pop out all tasks on our behalf.
+                Access::PendOnly { pend_access } => {
+                    pend_access.notify_all.store(true, Ordering::SeqCst);
+                }
+            })
+        });
+    }
+}
+
+#[cfg(feature = "soft_lock_stats")]
+impl WaitQueue {
+    /// Return the contention statistics of the wait queue.
+    pub(super) fn soft_lock_stats(&self) -> crate::debug::soft_lock_stats::SoftLockStats {
+        self.inner
+            .with_suspended_scheduler(|inner, _| inner.stats())
+    }
 }
@@ -0,0 +1,365 @@
+use super::{Access, AllowPendOp, Mailbox, RefCellSchedSafe, RunPendedOp, SoftLock, Spin};
+use crate::unrecoverable::{self, Lethal};
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU32, Ordering};
+use heapless::mpmc::MpMcQueue;
+
+/// The number of values sent by ISRs that can wait for the preempted context
+/// to store them. Only the latest one is stored, so an ISR finding the queue
+/// full discards the oldest one.
+const PENDING_CAPACITY: usize = 2;
+
+/// A channel holding only the latest value, e.g., the latest reading of a
+/// sensor. Producers overwrite the value, and consumers block until the
+/// value changes. Intermediate values that no consumer has read are lost.
+///
+/// Each value sent is numbered by a version counter. A [`Watcher`] remembers
+/// the version it has seen last, so that it blocks only when no newer value
+/// has been sent since.
+///
+/// The value is cloned with the scheduler suspended, so `T` should be cheap
+/// to clone, e.g., a small `Copy` struct.
+///
+/// # Example
+/// ```rust
+/// static TEMPERATURE: Watch<i16> = Watch::new();
+///
+/// // In an IRQ handler.
+/// TEMPERATURE.send_allow_isr(read_adc());
+///
+/// // In a task.
+/// let mut watcher = TEMPERATURE.watcher();
+/// loop {
+///     let celsius = watcher.changed();
+///     update_display(celsius);
+/// }
+/// ```
+pub struct Watch<T>
+where
+    T: Clone + 'static,
+{
+    /// An ISR preempting a task that accesses the value queues the value it
+    /// sends, which the task stores when it finishes the access.
+    inner: RefCellSchedSafe<SoftLock<Inner<T>>>,
+}
+
+struct Inner<T> {
+    /// The latest value. The spin lock around it is only for sanity check.
+    /// This field should not be accessed concurrently.
+    value: Spin<Option<T>>,
+    /// The number of values sent, wrapping around on overflow. Only modified
+    /// with the full access, but may be read with either access.
+    version: AtomicU32,
+    /// The mailboxes of the tasks waiting for a change. The spin lock around
+    /// it is only for sanity check. This field should not be accessed
+    /// concurrently.
+    waiters: Spin<Vec<Arc<Mailbox>>>,
+    /// The values sent by ISRs preempting the owner of the full access.
+    pending: MpMcQueue<T, PENDING_CAPACITY>,
+    /// The number of values sent by ISRs preempting the owner of the full
+    /// access, including the discarded ones.
+    pending_sends: AtomicU32,
+}
+
+/// Representing full access to all fields of the [`Watch`].
+struct InnerFullAccessor<'a, T> {
+    value: &'a Spin<Option<T>>,
+    version: &'a AtomicU32,
+    waiters: &'a Spin<Vec<Arc<Mailbox>>>,
+    pending: &'a MpMcQueue<T, PENDING_CAPACITY>,
+    pending_sends: &'a AtomicU32,
+}
+
+/// Representing pend-only access to the [`Watch`]. Only a value to be
+/// stored later can be queued, and the version can be read.
+struct InnerPendAccessor<'a, T> {
+    version: &'a AtomicU32,
+    pending: &'a MpMcQueue<T, PENDING_CAPACITY>,
+    pending_sends: &'a AtomicU32,
+}
+
+/// Bind the accessor types.
+impl<'a, T> AllowPendOp<'a> for Inner<T>
+where
+    T: Clone + 'static,
+{
+    type FullAccessor = InnerFullAccessor<'a, T>;
+    type PendOnlyAccessor = InnerPendAccessor<'a, T>;
+    fn full_access(&'a self) -> Self::FullAccessor {
+        Self::FullAccessor {
+            value: &self.value,
+            version: &self.version,
+            waiters: &self.waiters,
+            pending: &self.pending,
+            pending_sends: &self.pending_sends,
+        }
+    }
+
+    fn pend_only_access(&'a self) -> Self::PendOnlyAccessor {
+        Self::PendOnlyAccessor {
+            version: &self.version,
+            pending: &self.pending,
+            pending_sends: &self.pending_sends,
+        }
+    }
+}
+
+/// A pended operation is always sending a value. Store the latest pended
+/// value and wake up all waiting tasks.
+impl<'a, T> RunPendedOp for InnerFullAccessor<'a, T>
+where
+    T: Clone + 'static,
+{
+    fn run_pended_op(&mut self) {
+        // An ISR may pend another value after the count is taken, in which
+        // case the value is stored now and counted by the next run.
+        let sends = self.pending_sends.swap(0, Ordering::SeqCst);
+        let mut latest = None;
+        while let Some(value) = self.pending.dequeue() {
+            latest = Some(value);
+        }
+        if let Some(value) = latest {
+            drop(self.value.lock_now_or_die().replace(value));
+        }
+        self.version.fetch_add(sends, Ordering::SeqCst);
+        self.wake_all();
+    }
+}
+
+impl<'a, T> InnerFullAccessor<'a, T> {
+    /// Wake up and remove all waiting tasks.
+    fn wake_all(&self) {
+        for mailbox in self.waiters.lock_now_or_die().drain(..) {
+            mailbox.notify_allow_isr();
+        }
+    }
+}
+
+/// The ring buffer does not drop the values left in it, so drop them here.
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        while self.pending.dequeue().is_some() {}
+    }
+}
+
+impl<T> InnerPendAccessor<'_, T> {
+    /// Queue the value for the owner of the full access to store.
+    fn pend(&self, mut value: T) {
+        // Only the latest value matters, so discard the oldest one to make
+        // room. Another ISR may fill the queue again in between.
+        while let Err(rejected) = self.pending.enqueue(value) {
+            value = rejected;
+            drop(self.pending.dequeue());
+        }
+        self.pending_sends.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl<T> Watch<T>
+where
+    T: Clone + 'static,
+{
+    /// Create a new [`Watch`] without a value.
+    pub const fn new() -> Self {
+        Self {
+            inner: RefCellSchedSafe::new(SoftLock::new(Inner {
+                value: Spin::new(None),
+                version: AtomicU32::new(0),
+                waiters: Spin::new(Vec::new()),
+                pending: MpMcQueue::new(),
+                pending_sends: AtomicU32::new(0),
+            })),
+        }
+    }
+
+    /// Overwrite the value and wake up all tasks waiting for a change. If it
+    /// preempts a task accessing the value, the value is stored and the
+    /// waiting tasks are woken up when the task finishes the access.
+    ///
+    /// This method is allowed in ISR context.
+    pub fn send_allow_isr(&self, value: T) {
+        // Drop the previous value after releasing the access.
+        let prev = self.inner.with_suspended_scheduler(|watch, _| {
+            watch.with_access(|access| match access {
+                Access::Full { full_access } => {
+                    let prev = full_access.value.lock_now_or_die().replace(value);
+                    full_access.version.fetch_add(1, Ordering::SeqCst);
+                    full_access.wake_all();
+                    prev
+                }
+                Access::PendOnly { pend_access } => {
+                    pend_access.pend(value);
+                    None
+                }
+            })
+        });
+        drop(prev);
+    }
+
+    /// Return a clone of the latest value, or `None` if no value has been
+    /// sent.
+    ///
+    /// This method is allowed in ISR context. If it preempts a task
+    /// accessing the value, it returns `None`.
+    pub fn get_allow_isr(&self) -> Option<T> {
+        self.inner.with_suspended_scheduler(|watch, _| {
+            watch.with_access(|access| match access {
+                Access::Full { full_access } => full_access.value.lock_now_or_die().clone(),
+                Access::PendOnly { .. } => None,
+            })
+        })
+    }
+
+    /// Return a [`Watcher`] that has seen the current value, so that it
+    /// waits for the next one.
+    pub fn watcher(&self) -> Watcher<'_, T> {
+        Watcher {
+            watch: self,
+            seen: self.version(),
+        }
+    }
+
+    /// Return the latest value and its version.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    fn latest(&self) -> (Option<T>, u32) {
+        // Should always grant full access to a task.
+        self.inner.with_suspended_scheduler(|watch, _| {
+            watch.must_with_full_access(|full_access| {
+                (
+                    full_access.value.lock_now_or_die().clone(),
+                    full_access.version.load(Ordering::SeqCst),
+                )
+            })
+        })
+    }
+
+    /// Return the number of values sent. Values pended by ISRs are counted
+    /// once the owner of the full access stores them.
+    fn version(&self) -> u32 {
+        self.inner.with_suspended_scheduler(|watch, _| {
+            watch.with_access(|access| match access {
+                Access::Full { full_access } => full_access.version.load(Ordering::SeqCst),
+                Access::PendOnly { pend_access } => pend_access.version.load(Ordering::SeqCst),
+            })
+        })
+    }
+
+    /// Block the calling task until the version differs from `seen` or it
+    /// times out. Return whether the version differs.
+    fn wait_changed(&self, seen: u32, timeout_ms: Option<u32>) -> bool {
+        unrecoverable::die_if_in_isr();
+
+        let mailbox = Arc::new(Mailbox::new());
+
+        // Suspend scheduling and acquire full access to the fields. If the
+        // value has not changed yet, register the mailbox. A value sent in
+        // between by an ISR is pended and wakes up the registered mailbox.
+        let changed = self.inner.with_suspended_scheduler(|watch, _| {
+            watch.must_with_full_access(|full_access| {
+                if full_access.version.load(Ordering::SeqCst) != seen {
+                    return true;
+                }
+                full_access.waiters.lock_now_or_die().push(mailbox.clone());
+                false
+            })
+        });
+        if changed {
+            return true;
+        }
+
+        // The mailbox counts the notification if a value is sent before the
+        // task blocks on it.
+        let timeout_ms = match timeout_ms {
+            Some(timeout_ms) => timeout_ms,
+            None => {
+                mailbox.wait();
+                return true;
+            }
+        };
+        if mailbox.wait_until_timeout(timeout_ms) {
+            return true;
+        }
+
+        // Timed out. Unregister the mailbox if it is still registered. A value
+        // may have been sent right after the timeout.
+        self.inner.with_suspended_scheduler(|watch, _| {
+            watch.must_with_full_access(|full_access| {
+                full_access
+                    .waiters
+                    .lock_now_or_die()
+                    .retain(|registered| !Arc::ptr_eq(registered, &mailbox));
+            })
+        });
+        self.version() != seen
+    }
+}
+
+/// A handle of a consumer of a [`Watch`], remembering the version of the
+/// value it has seen last.
+pub struct Watcher<'a, T>
+where
+    T: Clone + 'static,
+{
+    watch: &'a Watch<T>,
+    seen: u32,
+}
+
+impl<'a, T> Watcher<'a, T>
+where
+    T: Clone + 'static,
+{
+    /// Return whether a value has been sent since the value was last seen.
+    pub fn has_changed(&self) -> bool {
+        self.watch.version() != self.seen
+    }
+
+    /// Block the calling task until a value has been sent since the value
+    /// was last seen, and return the latest value. The returned value is
+    /// marked as seen.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn changed(&mut self) -> T {
+        // Cannot time out without a timeout.
+        self.watch.wait_changed(self.seen, None);
+        // A value exists once the version has changed.
+        self.get().unwrap_or_die()
+    }
+
+    /// Block the calling task until a value has been sent since the value
+    /// was last seen or the elapsed waiting time reaches timeout.
+    ///
+    /// Return the latest value within `Some` and mark it as seen, or `None`
+    /// if timed out.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn changed_timeout(&mut self, timeout_ms: u32) -> Option<T> {
+        if !self.watch.wait_changed(self.seen, Some(timeout_ms)) {
+            return None;
+        }
+        self.get()
+    }
+
+    /// Return the latest value and mark it as seen, or `None` if no value
+    /// has been sent.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn get(&mut self) -> Option<T> {
+        let (value, version) = self.watch.latest();
+        self.seen = version;
+        value
+    }
+}
+
+#[cfg(feature = "soft_lock_stats")]
+impl<T> Watch<T>
+where
+    T: Clone + 'static,
+{
+    /// Return the contention statistics of the watch. See
+    /// [`soft_lock_stats`](crate::debug::soft_lock_stats).
+    pub fn soft_lock_stats(&self) -> crate::debug::soft_lock_stats::SoftLockStats {
+        self.inner
+            .with_suspended_scheduler(|inner, _| inner.stats())
+    }
+}
@@ -1,24 +1,165 @@
-use crate::{config, sync::Semaphore};
-
-/// The semaphore controlling the concurrency of all breathing tasks. Each
-/// breathing task acquires (`.down()`) the semaphore before proceeding to the
-/// `work` function, and will release (`.up()`) the semaphore after the work
-/// function finishes.
-static CONCUR_CTRL_SEM: Semaphore =
-    Semaphore::new(config::BREATHING_CONCURRENCY, config::BREATHING_CONCURRENCY);
-
-/// To prevent a panicked task from not releasing the concurrency control
-/// semaphore, we create a guard after acquiring the semaphore. In this way
-/// the drop handler will release the semaphore in both the normal and
-/// unwinding path.
-struct SemGuard;
-
-impl Drop for SemGuard {
+//! Concurrency control of breathing tasks.
+//!
+//! To smooth out the stack memory usage among tasks and avoid high peaks,
+//! only a limited number of breathing tasks, see
+//! [`build_breathing`](super::build_breathing), can run their `work`
+//! closures at the same time. The limit starts at
+//! [`BREATHING_CONCURRENCY`](config::BREATHING_CONCURRENCY) and can be
+//! adjusted at runtime with [`set_concurrency_limit`].
+//!
+//! The limit can also follow the memory pressure, i.e., the percentage of
+//! the heap not available for stack extension, see
+//! [`get_stack_headroom`](crate::debug::segmented_stack::get_stack_headroom).
+//! With a target set by [`set_target_memory_pressure`], a breathing task
+//! starts its `work` only if the memory pressure is below the target, or if
+//! no other breathing task is working, so that at least one always makes
+//! progress. A delayed task checks the memory pressure again every
+//! [`BREATHING_PRESSURE_POLL_MS`](config::BREATHING_PRESSURE_POLL_MS)
+//! milliseconds, and whenever another breathing task finishes its work.
+//!
+//! # Example
+//! ```rust
+//! // Start delaying work items when 80% of the heap is taken.
+//! breathing::set_target_memory_pressure(80);
+//!
+//! // Later, inspect how often work items were delayed.
+//! let delayed = breathing::get_memory_delay_count();
+//! ```
+
+use super::segmented_stack;
+use crate::{allocator, config, sync::CondVar, unrecoverable};
+use core::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+/// The maximum number of breathing tasks running their `work` closures.
+static CONCURRENCY_LIMIT: AtomicUsize = AtomicUsize::new(config::BREATHING_CONCURRENCY);
+
+/// The number of breathing tasks running their `work` closures.
+static INFLIGHT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The memory pressure in percent at or above which work items are delayed.
+/// 100 or above disables the memory pressure control.
+static TARGET_MEMORY_PRESSURE: AtomicU8 = AtomicU8::new(100);
+
+/// The number of work items delayed because of the memory pressure.
+static MEMORY_DELAY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Notified when a breathing task finishes its work or when the controls are
+/// changed.
+static ADMISSION: CondVar = CondVar::new();
+
+/// Set the maximum number of breathing tasks running their `work` closures
+/// at the same time. Lowering the limit does not interrupt the work in
+/// progress, but no more work starts until the number of working tasks
+/// drops below the new limit. Zero pauses all breathing tasks before their
+/// next work.
+pub fn set_concurrency_limit(limit: usize) {
+    CONCURRENCY_LIMIT.store(limit, Ordering::SeqCst);
+    ADMISSION.notify_all_allow_isr();
+}
+
+/// Return the maximum number of breathing tasks running their `work`
+/// closures at the same time.
+pub fn get_concurrency_limit() -> usize {
+    CONCURRENCY_LIMIT.load(Ordering::SeqCst)
+}
+
+/// Delay the work items of breathing tasks while the memory pressure, in
+/// percent, is at or above `percent`, unless no other breathing task is
+/// working. 100 or above disables the memory pressure control, which is the
+/// default.
+pub fn set_target_memory_pressure(percent: u8) {
+    TARGET_MEMORY_PRESSURE.store(percent, Ordering::SeqCst);
+    ADMISSION.notify_all_allow_isr();
+}
+
+/// Return the current memory pressure, i.e., the percentage of the heap not
+/// available for stack extension.
+pub fn get_memory_pressure() -> u8 {
+    let size = allocator::heap_size() as usize;
+    let headroom = segmented_stack::get_stack_headroom().min(size);
+    (100 - headroom * 100 / size) as u8
+}
+
+/// Return the number of breathing tasks running their `work` closures.
+pub fn get_inflight_count() -> usize {
+    INFLIGHT_COUNT.load(Ordering::SeqCst)
+}
+
+/// Return the number of work items delayed because of the memory pressure
+/// since system boot. Each work item is counted at most once, however long
+/// it is delayed. The counter will wrap around back to zero after reaching
+/// `usize::MAX`.
+pub fn get_memory_delay_count() -> usize {
+    MEMORY_DELAY_COUNT.load(Ordering::Relaxed)
+}
+
+/// The reason why a breathing task cannot start its work.
+enum Refusal {
+    ConcurrencyLimit,
+    MemoryPressure,
+}
+
+/// Count the calling breathing task as working if the controls allow.
+fn try_admit() -> Result<(), Refusal> {
+    let mut inflight = INFLIGHT_COUNT.load(Ordering::SeqCst);
+    loop {
+        if inflight >= CONCURRENCY_LIMIT.load(Ordering::SeqCst) {
+            return Err(Refusal::ConcurrencyLimit);
+        }
+        let target = TARGET_MEMORY_PRESSURE.load(Ordering::SeqCst);
+        if inflight > 0 && target < 100 && get_memory_pressure() >= target {
+            return Err(Refusal::MemoryPressure);
+        }
+        match INFLIGHT_COUNT.compare_exchange(
+            inflight,
+            inflight + 1,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ) {
+            Ok(_) => return Ok(()),
+            Err(updated) => inflight = updated,
+        }
+    }
+}
+
+/// Block the calling breathing task until it may start its work. Return a
+/// guard counting the task as working until it is dropped.
+fn admit() -> WorkGuard {
+    unrecoverable::die_if_in_isr();
+
+    let mut delayed_by_memory = false;
+    loop {
+        let admitted = ADMISSION.wait_without_lock_until_timeout(
+            || match try_admit() {
+                Ok(()) => true,
+                Err(Refusal::MemoryPressure) => {
+                    delayed_by_memory = true;
+                    false
+                }
+                Err(Refusal::ConcurrencyLimit) => false,
+            },
+            config::BREATHING_PRESSURE_POLL_MS,
+        );
+        if admitted {
+            break;
+        }
+    }
+
+    if delayed_by_memory {
+        MEMORY_DELAY_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+    WorkGuard
+}
+
+/// To prevent a panicked task from staying counted as working, the count is
+/// decremented by the drop handler of the guard, which runs in both the
+/// normal and the unwinding path.
+struct WorkGuard;
+
+impl Drop for WorkGuard {
     fn drop(&mut self) {
-        // `.up()` should suffice, but we just do not want to block in a drop
-        // handler. Logically, the semaphore should never block on `.up()`, but
-        // here we just try to be extra careful in case we have some other bugs.
-        let _ = CONCUR_CTRL_SEM.try_up_allow_isr();
+        INFLIGHT_COUNT.fetch_sub(1, Ordering::SeqCst);
+        ADMISSION.notify_all_allow_isr();
     }
 }
 
@@ -32,19 +173,20 @@ impl Drop for SemGuard {
 ///     let mut state = init());
 ///     loop {
 ///         let item = wait(&mut state);
-///         CONCUR_CTRL_SEM.down();
+///         admit();
 ///         work(&mut state, item);
-///         CONCUR_CTRL_SEM.up();
+///         release();
 ///     }
 /// }
 /// ```
 ///
 /// Technically, however, the `init`, `wait`, and `work` closure are outlined,
-/// so that when the task blocks on [`CONCUR_CTRL_SEM`] the task's stack usage
-/// will be low.
+/// so that when the task blocks in [`admit`] the task's stack usage will be
+/// low.
 ///
-/// Also, the `.up()` part is guarded by [`SemGuard`], so even if the `work`
-/// closure throws a panic, the semaphore can still be released.
+/// Also, the release part is done by dropping the [`WorkGuard`], so even if
+/// the `work` closure throws a panic, the task is no longer counted as
+/// working.
 macro_rules! define_breathing_task_entry_constructor {
     (
         $fn_name:ident,
@@ -83,8 +225,7 @@ macro_rules! define_breathing_task_entry_constructor {
                 let mut state = call_init(init);
                 loop {
                     let item = wait(&mut state);
-                    CONCUR_CTRL_SEM.down();
-                    let _guard = SemGuard;
+                    let _guard = admit();
                     call_work(&mut state, item, &mut work);
                 }
             }
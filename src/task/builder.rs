@@ -1,8 +1,19 @@
-use super::{breathing, StackConfig, Task};
-use crate::{config, schedule::scheduler::Scheduler, unrecoverable::Lethal};
+use super::{
+    breathing, join, registry, segmented_stack, static_task::StackSlot, JoinHandle, PausedTask,
+    StackConfig, StaticTask, Task, TaskId,
+};
+use crate::{
+    allocator::{self, Arena, ArenaError},
+    config,
+    schedule::scheduler::{Scheduler, TaskQuota},
+    unrecoverable::Lethal,
+};
 use alloc::sync::Arc;
 use core::num::NonZeroUsize;
 
+#[cfg(feature = "unwind")]
+use super::{supervisor::MemberExit, RestartPolicy};
+
 /// Enumeration of errors during task creation.
 #[derive(Debug, PartialEq)]
 pub enum TaskBuildError {
@@ -15,19 +26,36 @@ pub enum TaskBuildError {
     NoEntry,
     /// The priority level is not an allowed value.
     PriorityNotAllowed,
+    /// The heap does not have enough headroom for the stack reservation
+    /// beyond the reservations of other tasks.
+    NoStackReserve,
+    /// The [`StaticTask`] is used by another task, or the task is spawned
+    /// as restartable, which is not supported in static memory.
+    StaticTaskUnavailable,
+    /// The task struct and the entry closure do not fit in the
+    /// [`StaticTask`].
+    StaticTaskTooSmall,
 }
 
 /// Supporting the builder pattern to create a new task.
-pub struct TaskBuilder<F>
+#[derive(Clone)]
+pub struct TaskBuilder<F, T = ()>
 where
-    F: FnOnce() + Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
 {
     entry_closure: Option<F>,
     stack_limit: Option<usize>,
     stack_init_size: Option<usize>,
+    stack_reserve: usize,
     stack_is_dynamic: bool,
+    static_task: Option<(StackSlot, Arena)>,
     priority: Option<u8>,
     id: Option<u8>,
+    name: Option<&'static str>,
+    cooperative: bool,
+    deadline_ms: Option<u32>,
+    #[cfg(feature = "unwind")]
+    restart_policy: RestartPolicy,
 }
 
 pub struct BreathingTaskBuilder<F, G, H, S, I>
@@ -41,8 +69,13 @@ where
     work: Option<H>,
     stack_limit: Option<usize>,
     stack_init_size: Option<usize>,
+    stack_reserve: usize,
     priority: Option<u8>,
     id: Option<u8>,
+    name: Option<&'static str>,
+    cooperative: bool,
+    #[cfg(feature = "unwind")]
+    restart_policy: RestartPolicy,
 }
 
 macro_rules! define_common_set_methods {
@@ -57,6 +90,16 @@ macro_rules! define_common_set_methods {
             self
         }
 
+        /// Set a name for the task.
+        ///
+        /// Like the numerical ID, the name is only for diagnosing bugs, e.g.,
+        /// in panic reports, and need not be unique among tasks. The task can
+        /// be looked up by its name with [`find_by_name`](super::find_by_name).
+        pub fn set_name(mut self, name: &'static str) -> Self {
+            self.name.replace(name);
+            self
+        }
+
         /// Set the size limit of the stack in bytes. If the task exceeds the
         /// limit, it will be terminated with its stack forcefully unwound to
         /// reclaim resources. The task will be restarted if restartable.
@@ -73,19 +116,101 @@ macro_rules! define_common_set_methods {
             self
         }
 
+        /// Reserve the given number of bytes of the heap for the stack
+        /// extension of the task. Only meaningful when dynamic stack extension
+        /// is enabled.
+        ///
+        /// The stack extensions of other tasks fail as if their stack limits
+        /// were exceeded if they would take the reserved bytes not yet used by
+        /// the task. This guarantees that a critical task can extend its
+        /// stack up to the reserved size, even if other tasks recurse deeply.
+        /// Like the stack limit, the reservation counts the stack frames
+        /// excluding the overhead of each stacklet, so it should include some
+        /// margin. The reservation does not restrict other heap allocations.
+        ///
+        /// Spawning the task fails with [`TaskBuildError::NoStackReserve`] if
+        /// the heap does not have enough headroom, see
+        /// [`get_stack_headroom`](crate::debug::segmented_stack::get_stack_headroom).
+        pub fn set_stack_reserve(mut self, size: usize) -> Self {
+            self.stack_reserve = size;
+            self
+        }
+
         /// Set the priority to a task. If not explicitly set, the task will
         /// have the [`DEFAULT_TASK_PRIORITY`](config::DEFAULT_TASK_PRIORITY).
         pub fn set_priority(mut self, prio: u8) -> Self {
             self.priority.replace(prio);
             self
         }
+
+        /// Make the task cooperative, so that it gives up the CPU to the
+        /// other tasks of its priority only when it yields, blocks or sleeps.
+        ///
+        /// A cooperative task is not time sliced, see
+        /// [`ROUND_ROBIN_QUANTUM_TICKS`](config::ROUND_ROBIN_QUANTUM_TICKS).
+        /// A task preempted by a higher priority task is normally put behind
+        /// the other ready tasks of its priority, which then run before it
+        /// resumes. A cooperative task instead resumes first once no higher
+        /// priority task is ready. It is still preempted by higher priority
+        /// tasks. To also hold off those for a short code region, use
+        /// [`run_uninterrupted`](super::run_uninterrupted).
+        pub fn set_cooperative(mut self, cooperative: bool) -> Self {
+            self.cooperative = cooperative;
+            self
+        }
+
+        /// Set the maximum number of times the task is restarted after
+        /// panicking. Only meaningful when the task is spawned with
+        /// `spawn_restartable`. If not set, the task is restarted without
+        /// limit.
+        ///
+        /// When the task panics again after being restarted `n` times, it
+        /// terminates once its stack is unwound, and the callback set with
+        /// [`on_restart_exhausted`](Self::on_restart_exhausted) is called.
+        #[cfg(feature = "unwind")]
+        pub fn max_restarts(mut self, n: u32) -> Self {
+            self.restart_policy.max_restarts = Some(n);
+            self
+        }
+
+        /// Set the callback to run when the task panics after being restarted
+        /// the maximum number of times, e.g., to reset the system or to notify
+        /// a supervisor task. The callback is given the ID of the task and
+        /// runs in the context of the task after its stack is unwound.
+        ///
+        /// See [`max_restarts`](Self::max_restarts).
+        #[cfg(feature = "unwind")]
+        pub fn on_restart_exhausted(mut self, callback: fn(TaskId)) -> Self {
+            self.restart_policy.on_exhausted = Some(callback);
+            self
+        }
+
+        /// Set the delay in milliseconds before the task is restarted after
+        /// panicking, e.g., to wait for a transient external condition to
+        /// clear, such as a sensor not yet powered. Only meaningful when the
+        /// task is spawned with `spawn_restartable`. The restarted task
+        /// sleeps for the delay, so that other tasks can use the CPU.
+        #[cfg(feature = "unwind")]
+        pub fn restart_delay_ms(mut self, ms: u32) -> Self {
+            self.restart_policy.delay_ms = ms;
+            self
+        }
+
+        /// Double the [restart delay](Self::restart_delay_ms) each time the
+        /// task is restarted again, up to `max_ms` milliseconds.
+        #[cfg(feature = "unwind")]
+        pub fn restart_backoff(mut self, max_ms: u32) -> Self {
+            self.restart_policy.backoff_max_ms = Some(max_ms);
+            self
+        }
     };
 }
 
 macro_rules! define_task_spawn {
     (
         $method_name:ident,
-        $builder_fn:ident
+        $builder_fn:ident,
+        $wrap:expr
     ) => {
         /// Start the task. A spawned task is always detached. If a panic
         /// occurs while running the task, the task's stack will be unwound.
@@ -95,20 +220,7 @@ macro_rules! define_task_spawn {
         /// [`spawn_restartable`](Self::spawn_restartable), the task will be
         /// restarted again from the given entry closure.
         pub fn $method_name(self) -> Result<(), TaskBuildError> {
-            let stack_config = self.parse_stack_config()?;
-
-            let entry_closure = self.entry_closure.ok_or(TaskBuildError::NoEntry)?;
-            let id = self.id.unwrap_or(config::DEFAULT_TASK_ID);
-            let prio = self.priority.unwrap_or(config::DEFAULT_TASK_PRIORITY);
-
-            // Get a quota from the scheduler to ensure that the maximum number of
-            // tasks has not been reached yet.
-            let quota = Scheduler::request_task_quota().map_err(|_| TaskBuildError::NoMoreTask)?;
-
-            let new_task = Task::$builder_fn(quota, id, entry_closure, stack_config, prio)?;
-            Scheduler::accept_task(Arc::new(new_task));
-
-            Ok(())
+            self.spawn_with($wrap, Task::$builder_fn, false).map(|_| ())
         }
     };
 }
@@ -145,18 +257,41 @@ macro_rules! define_breathing_task_spawn {
                 Some(limit) => NonZeroUsize::new(limit),
                 None => None,
             };
-            let stack_config = StackConfig::Dynamic { initial, limit };
+            let stack_config = StackConfig::Dynamic {
+                initial,
+                limit,
+                reserve: self.stack_reserve,
+            };
+            check_stack_reserve(&stack_config)?;
 
             let entry = breathing::$entry_constr_fn(init, wait, work);
 
-            let new_task = Task::$builder_fn(quota, id, entry, stack_config, prio)?;
-            Scheduler::accept_task(Arc::new(new_task));
+            let mut new_task = Task::$builder_fn(quota, id, entry, stack_config, prio)?;
+            new_task.set_name(self.name);
+            new_task.set_cooperative(self.cooperative);
+            #[cfg(feature = "unwind")]
+            new_task.set_restart_policy(self.restart_policy);
+            let new_task = Arc::new(new_task);
+            registry::register(&new_task);
+            Scheduler::accept_task(new_task);
 
             Ok(())
         }
     };
 }
 
+/// Check that the heap has enough headroom for the stack reservation.
+fn check_stack_reserve(stack_config: &StackConfig) -> Result<(), TaskBuildError> {
+    match stack_config {
+        StackConfig::Dynamic { reserve, .. }
+            if *reserve > segmented_stack::get_stack_headroom() =>
+        {
+            Err(TaskBuildError::NoStackReserve)
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Build a new task with the task builder.
 ///
 /// # Example
@@ -168,28 +303,110 @@ macro_rules! define_breathing_task_spawn {
 ///
 /// fn foo() {}
 /// ```
-pub fn build<F>() -> TaskBuilder<F>
+pub fn build<F, T>() -> TaskBuilder<F, T>
 where
-    F: FnOnce() + Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
 {
     TaskBuilder::new()
 }
 
-impl<F> TaskBuilder<F>
+/// Build a new task whose task struct, entry closure and stack are in the
+/// given static memory, rather than allocated from the heap. The stack size
+/// is fixed, so the stack settings of the builder are ignored. See
+/// [`StaticTask`].
+///
+/// Spawning the task fails with [`TaskBuildError::StaticTaskUnavailable`]
+/// if the memory is used by another task, or if the task is spawned as
+/// restartable. It fails with [`TaskBuildError::StaticTaskTooSmall`] if the
+/// task struct and the entry closure do not fit in the memory.
+///
+/// # Example
+/// ```rust
+/// static TASK: StaticTask<2048> = StaticTask::new();
+///
+/// task::build_static(&TASK)
+///     .set_entry(foo)
+///     .spawn()
+///     .unwrap();
+///
+/// fn foo() {}
+/// ```
+pub fn build_static<F, T, const N: usize, const M: usize>(
+    task: &'static StaticTask<N, M>,
+) -> TaskBuilder<F, T>
+where
+    F: FnOnce() -> T + Send + 'static,
+{
+    let mut builder = TaskBuilder::new();
+    builder.static_task = Some((task.stack_slot(), task.tcb_arena()));
+    builder
+}
+
+impl<F, T> TaskBuilder<F, T>
 where
-    F: FnOnce() + Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
 {
     define_common_set_methods!();
-    define_task_spawn!(spawn, build);
+    // The value returned by the entry closure is dropped.
+    define_task_spawn!(spawn, build, |entry_closure| move || {
+        entry_closure();
+    });
+
+    /// Start the task and return a [`JoinHandle`] to collect the value
+    /// returned by the entry closure. Otherwise the same as
+    /// [`spawn`](Self::spawn). The task will not be restarted if it panics,
+    /// in which case joining it returns [`JoinError::Panicked`].
+    ///
+    /// [`JoinError::Panicked`]: super::JoinError::Panicked
+    pub fn spawn_joinable(self) -> Result<JoinHandle<T>, TaskBuildError>
+    where
+        T: Send + 'static,
+    {
+        let (handle, completion) = join::new_join_pair();
+        self.spawn_with(
+            |entry_closure| move || completion.complete(entry_closure()),
+            Task::build,
+            false,
+        )?;
+        Ok(handle)
+    }
+
+    /// Create the task without starting it, and return a [`PausedTask`] to
+    /// start it later. Otherwise the same as [`spawn`](Self::spawn).
+    ///
+    /// This allows creating a set of tasks and connecting them, e.g., by
+    /// passing them the [`TaskId`]s of each other, before any of them runs.
+    /// The task is kept suspended until it is
+    /// [`start`](PausedTask::start)ed or resumed through its
+    /// [`TaskHandle`](super::TaskHandle).
+    pub fn spawn_paused(self) -> Result<PausedTask, TaskBuildError> {
+        let id = self.spawn_with(
+            |entry_closure| {
+                move || {
+                    entry_closure();
+                }
+            },
+            Task::build,
+            true,
+        )?;
+        Ok(PausedTask::new(id))
+    }
 
     const fn new() -> Self {
         Self {
             entry_closure: None,
             stack_limit: None,
             stack_init_size: None,
+            stack_reserve: 0,
             stack_is_dynamic: true,
+            static_task: None,
             priority: None,
             id: None,
+            name: None,
+            cooperative: false,
+            deadline_ms: None,
+            #[cfg(feature = "unwind")]
+            restart_policy: RestartPolicy::new(),
         }
     }
 
@@ -215,10 +432,101 @@ where
         self
     }
 
+    /// Schedule the task earliest deadline first (EDF) with the given
+    /// relative deadline in milliseconds. The task releases a job when it is
+    /// started and each time it calls [`next_job`](super::next_job), and the
+    /// deadline of the job is `ms` milliseconds from then. A job keeps its
+    /// deadline while blocking or sleeping, so that waking up does not push
+    /// the deadline back.
+    ///
+    /// The task runs at the [`EDF_TASK_PRIORITY`](config::EDF_TASK_PRIORITY),
+    /// which overrides [`set_priority`](Self::set_priority). Among the ready
+    /// EDF tasks, the one with the earliest deadline runs, preempting the
+    /// others. A missed deadline is not reported, and the late job keeps
+    /// running with its earliest deadline.
+    pub fn set_deadline_ms(mut self, ms: u32) -> Self {
+        self.deadline_ms = Some(ms);
+        self
+    }
+
+    /// Start the task as a member of a [`Supervisor`](super::Supervisor),
+    /// which is notified through `exit` when the task returns or is unwound.
+    /// Return the ID of the new task.
+    #[cfg(feature = "unwind")]
+    pub(super) fn spawn_supervised(self, exit: MemberExit) -> Result<TaskId, TaskBuildError> {
+        self.spawn_with(
+            |entry_closure| {
+                move || {
+                    entry_closure();
+                    exit.returned();
+                }
+            },
+            Task::build,
+            false,
+        )
+    }
+
+    /// Build the task struct with `builder_fn` from the entry closure wrapped
+    /// by `wrap`, and hand the task to the scheduler. The task is kept
+    /// suspended if `paused` is set. Return the ID of the new task.
+    fn spawn_with<W>(
+        self,
+        wrap: impl FnOnce(F) -> W,
+        builder_fn: impl FnOnce(TaskQuota, u8, W, StackConfig, u8) -> Result<Task, TaskBuildError>,
+        paused: bool,
+    ) -> Result<TaskId, TaskBuildError> {
+        let stack_config = self.parse_stack_config()?;
+        check_stack_reserve(&stack_config)?;
+
+        let entry_closure = self.entry_closure.ok_or(TaskBuildError::NoEntry)?;
+        let id = self.id.unwrap_or(config::DEFAULT_TASK_ID);
+        let prio = match self.deadline_ms {
+            Some(_) => config::EDF_TASK_PRIORITY,
+            None => self.priority.unwrap_or(config::DEFAULT_TASK_PRIORITY),
+        };
+
+        // Get a quota from the scheduler to ensure that the maximum number of
+        // tasks has not been reached yet.
+        let quota = Scheduler::request_task_quota().map_err(|_| TaskBuildError::NoMoreTask)?;
+
+        let build = || -> Result<Arc<Task>, TaskBuildError> {
+            let mut new_task = builder_fn(quota, id, wrap(entry_closure), stack_config, prio)?;
+            new_task.set_name(self.name);
+            new_task.set_cooperative(self.cooperative);
+            new_task.set_suspended(paused);
+            new_task.set_relative_deadline(self.deadline_ms);
+            #[cfg(feature = "unwind")]
+            new_task.set_restart_policy(self.restart_policy);
+            Ok(Arc::new(new_task))
+        };
+
+        let new_task = match self.static_task {
+            // Allocate the task struct and the entry closure from the static
+            // memory. A terminated task may still be referred to by the
+            // registry, so forget such tasks first to free their memory.
+            Some((_, arena)) => {
+                registry::prune();
+                allocator::with_arena(&arena, build).map_err(|e| match e {
+                    ArenaError::InUse => TaskBuildError::StaticTaskUnavailable,
+                    ArenaError::TooSmall => TaskBuildError::StaticTaskTooSmall,
+                })??
+            }
+            None => build()?,
+        };
+        let task_id = new_task.get_uid();
+        new_task.start_job();
+        registry::register(&new_task);
+        Scheduler::accept_task(new_task);
+
+        Ok(task_id)
+    }
+
     /// Check the configuration of the stack and generate a [`StackConfig`]
     /// instance representing a valid configuration.
     fn parse_stack_config(&self) -> Result<StackConfig, TaskBuildError> {
-        if self.stack_is_dynamic {
+        if let Some((stack, _)) = self.static_task {
+            Ok(StackConfig::Provided { stack })
+        } else if self.stack_is_dynamic {
             let initial = match self.stack_init_size {
                 Some(initial) => NonZeroUsize::new(initial),
                 None => None,
@@ -227,7 +535,11 @@ where
                 Some(limit) => NonZeroUsize::new(limit),
                 None => None,
             };
-            Ok(StackConfig::Dynamic { initial, limit })
+            Ok(StackConfig::Dynamic {
+                initial,
+                limit,
+                reserve: self.stack_reserve,
+            })
         } else {
             let limit = match self.stack_limit {
                 Some(0) => return Err(TaskBuildError::NoStack),
@@ -244,7 +556,50 @@ impl<F> TaskBuilder<F>
 where
     F: FnOnce() + Send + Sync + Clone + 'static,
 {
-    define_task_spawn!(spawn_restartable, build_restartable);
+    define_task_spawn!(spawn_restartable, build_restartable, |entry_closure| {
+        entry_closure
+    });
+}
+
+impl TaskBuilder<fn()> {
+    /// Set the entry function for the task together with its argument. The
+    /// task starts by calling `entry` with `arg`. Unlike capturing the
+    /// argument in a closure passed to [`set_entry`](TaskBuilder::set_entry),
+    /// the argument can be shared, e.g., a driver state also used by other
+    /// tasks. A task spawned with `spawn_restartable` gets the same argument
+    /// each time it is restarted.
+    ///
+    /// # Example
+    /// ```rust
+    /// let uart = Arc::new(UartState::new());
+    /// task::build()
+    ///     .set_entry_with_arg(uart_rx, uart.clone())
+    ///     .spawn_restartable()
+    ///     .unwrap();
+    ///
+    /// fn uart_rx(uart: Arc<UartState>) {}
+    /// ```
+    pub fn set_entry_with_arg<A>(
+        self,
+        entry: fn(Arc<A>),
+        arg: Arc<A>,
+    ) -> TaskBuilder<impl FnOnce() + Send + Sync + Clone + 'static>
+    where
+        A: Send + Sync + 'static,
+    {
+        TaskBuilder {
+            entry_closure: Some(move || entry(arg)),
+            stack_limit: self.stack_limit,
+            stack_init_size: self.stack_init_size,
+            stack_reserve: self.stack_reserve,
+            stack_is_dynamic: self.stack_is_dynamic,
+            priority: self.priority,
+            id: self.id,
+            name: self.name,
+            #[cfg(feature = "unwind")]
+            restart_policy: self.restart_policy,
+        }
+    }
 }
 
 /// Start a new task from a previously failed task.
@@ -254,8 +609,10 @@ pub(crate) fn try_spawn_restarted(prev_task: Arc<Task>) -> Result<(), ()> {
     // tasks has not been reached yet.
     let quota = Scheduler::request_task_quota()?;
 
-    let restarted_task = Task::build_restarted(quota, prev_task);
-    Scheduler::accept_task(Arc::new(restarted_task));
+    let restarted_task = Arc::new(Task::build_restarted(quota, prev_task));
+    restarted_task.start_job();
+    registry::register(&restarted_task);
+    Scheduler::accept_task(restarted_task);
     Ok(())
 }
 
@@ -277,7 +634,9 @@ pub(crate) fn try_spawn_restarted(prev_task: Arc<Task>) -> Result<(), ()> {
 /// But more precisely, to smooth out stack memory usage among tasks and
 /// avoid high peaks, the concurrency among breathing tasks is constrained.
 /// Only a number of breathing tasks can run in the `work` function,
-/// controlled by the `hopter::config::BREATHING_CONCURRENCY` parameter.
+/// controlled by the `hopter::config::BREATHING_CONCURRENCY` parameter, which
+/// can be adjusted at runtime along with the memory pressure. See the
+/// [`breathing`](super::breathing) module.
 ///
 /// # Example
 /// ```rust
@@ -320,8 +679,13 @@ where
             work: None,
             stack_limit: None,
             stack_init_size: None,
+            stack_reserve: 0,
             priority: None,
             id: None,
+            name: None,
+            cooperative: false,
+            #[cfg(feature = "unwind")]
+            restart_policy: RestartPolicy::new(),
         }
     }
 
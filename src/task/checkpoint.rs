@@ -0,0 +1,77 @@
+//! Cooperative checkpoints for long-running computations.
+//!
+//! A task running a long computation without blocking calls [`checkpoint`]
+//! periodically, e.g., once per loop iteration. At each checkpoint the
+//! kernel:
+//!
+//! - returns [`Cancelled`] if the computation should stop, i.e., when the
+//!   system is [shutting down](crate::power::shutdown), so that the task can
+//!   wind down at a point where its state is consistent;
+//! - unwinds the task if it has been
+//!   [terminated](super::TaskHandle::terminate) or has missed its
+//!   [watchdog](super::watchdog_register) deadline;
+//! - records the progress of the task, which serves as its heartbeat for
+//!   diagnostics and supervision, see [`progress`];
+//! - yields the CPU to other ready tasks of the same priority, at most once
+//!   every [`CHECKPOINT_YIELD_PERIOD_MS`](config::CHECKPOINT_YIELD_PERIOD_MS)
+//!   milliseconds.
+//!
+//! # Example
+//! ```rust
+//! fn crunch(blocks: &mut [Block]) -> Result<(), Cancelled> {
+//!     for block in blocks {
+//!         block.transform();
+//!         task::checkpoint()?;
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use super::current::yield_current;
+use crate::{config, power, schedule::current, time, unrecoverable};
+
+/// The error returned by [`checkpoint`] when the computation should stop.
+#[derive(Debug, PartialEq)]
+pub struct Cancelled;
+
+/// The progress of a task recorded at its checkpoints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// The number of checkpoints the task has passed.
+    pub count: u32,
+    /// The tick number when the task last passed a checkpoint, or zero if it
+    /// has not passed any.
+    pub last_tick: u32,
+}
+
+/// Pass a checkpoint in a long-running computation. Return [`Cancelled`] if
+/// the computation should stop. See the [module-level documentation](self)
+/// for what the kernel does at a checkpoint.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn checkpoint() -> Result<(), Cancelled> {
+    unrecoverable::die_if_in_isr();
+
+    #[cfg(feature = "unwind")]
+    crate::unwind::forced::unwind_if_requested();
+
+    let tick = time::get_tick();
+    let should_yield = current::with_cur_task(|cur_task| {
+        cur_task.record_checkpoint(tick, config::CHECKPOINT_YIELD_PERIOD_MS)
+    });
+
+    if power::is_shutting_down() {
+        return Err(Cancelled);
+    }
+
+    if should_yield {
+        yield_current();
+    }
+    Ok(())
+}
+
+/// Return the progress of the current task recorded at its checkpoints.
+pub fn progress() -> Progress {
+    let (count, last_tick) = current::with_cur_task(|cur_task| cur_task.get_checkpoint_progress());
+    Progress { count, last_tick }
+}
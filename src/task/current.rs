@@ -1,7 +1,9 @@
+use super::TaskId;
 use crate::{
     config,
     interrupt::context_switch,
-    schedule::{current, scheduler::Scheduler},
+    schedule::{self, current, scheduler::Scheduler},
+    unrecoverable,
 };
 
 /// Switch the current task out of the CPU and let the scheduler pick the next
@@ -10,9 +12,50 @@ pub fn yield_current() {
     // Yield only if the scheduler is not suspended.
     if !Scheduler::is_suspended() {
         context_switch::yield_current_task();
+
+        // The task may have been requested to terminate meanwhile.
+        #[cfg(feature = "unwind")]
+        crate::unwind::forced::unwind_if_requested();
     }
 }
 
+/// Release the next job of the calling EDF task, whose deadline is the
+/// [relative deadline](super::TaskBuilder::set_deadline_ms) from now. A
+/// periodic task calls it at the start of each period, typically after
+/// sleeping until the period begins. Do nothing if the task is not an EDF
+/// task.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn next_job() {
+    unrecoverable::die_if_in_isr();
+
+    current::with_cur_task(|cur_task| cur_task.start_job());
+
+    // The earlier deadline of a ready EDF task may now precede the new one.
+    yield_current();
+}
+
+/// Run the closure without being switched out of the CPU, and return its
+/// result. No other task runs until the closure returns, but interrupts are
+/// still served. A task woken up meanwhile, e.g., by an interrupt handler,
+/// preempts the calling task after the closure returns if it has a higher
+/// priority.
+///
+/// The region should be short, since it delays all other tasks. Unlike
+/// [`set_cooperative`](super::TaskBuilder::set_cooperative), it also holds
+/// off higher priority tasks. See also
+/// [`preemption_lock`](crate::schedule::preemption_lock).
+///
+/// NOTE: The closure *must not* block, sleep or yield. *Must not* call this
+/// function in ISR context.
+pub fn run_uninterrupted<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let _lock = schedule::preemption_lock();
+    f()
+}
+
 /// Change the priority of the currently running task. Return `Ok(())` if the
 /// priority is successfully changed. Return `Err(())` if the given new
 /// priority is not allowed by the configuration settings.
@@ -34,3 +77,14 @@ pub fn change_current_priority(prio: u8) -> Result<(), ()> {
 pub fn get_current_id() -> u8 {
     current::with_cur_task(|cur_task| cur_task.get_id())
 }
+
+/// Return the unique ID of the current task. See [`TaskId`].
+pub fn current_id() -> TaskId {
+    current::with_cur_task(|cur_task| cur_task.get_uid())
+}
+
+/// Return the name of the current task, or `None` if the task is not named.
+/// The name is set with [`set_name`](super::TaskBuilder::set_name).
+pub fn current_name() -> Option<&'static str> {
+    current::with_cur_task(|cur_task| cur_task.get_name())
+}
@@ -0,0 +1,208 @@
+//! Run `async` code on Hopter.
+//!
+//! Futures spawned with [`spawn_async`] are distributed among a pool of
+//! [`ASYNC_WORKER_NUMBER`](config::ASYNC_WORKER_NUMBER) kernel tasks, called
+//! workers. Each worker owns a run queue and a [`Mailbox`]. Waking a future
+//! puts it back into the run queue of its worker and notifies the mailbox,
+//! so a worker blocks like any other task when none of its futures can make
+//! progress. Waking is allowed from ISRs, which lets interrupt-driven drivers
+//! complete futures directly.
+//!
+//! Timer futures created by [`sleep_ms_async`](crate::time::sleep_ms_async)
+//! are tracked by the `time` module. An idle worker waits on its mailbox with
+//! a timeout equal to the earliest timer deadline, which puts the worker into
+//! the sleep queue until the timer expires.
+
+use super::TaskBuildError;
+use crate::{config, sync::Mailbox, time, unrecoverable::Lethal};
+use alloc::{boxed::Box, sync::Arc, task::Wake};
+use core::{
+    cell::UnsafeCell,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    task::{Context, Waker},
+};
+use heapless::mpmc::MpMcQueue;
+
+type BoxedFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Enumeration of errors when spawning a future.
+#[derive(Debug, PartialEq)]
+pub enum SpawnAsyncError {
+    /// The chosen worker already runs
+    /// [`ASYNC_RUN_QUEUE_CAPACITY`](config::ASYNC_RUN_QUEUE_CAPACITY) futures.
+    TooManyFutures,
+    /// The worker tasks cannot be spawned.
+    Worker(TaskBuildError),
+}
+
+/// A kernel task polling futures.
+struct Worker {
+    /// Futures that have been woken up and should be polled.
+    run_queue: MpMcQueue<Arc<AsyncTask>, { config::ASYNC_RUN_QUEUE_CAPACITY }>,
+    /// Notified whenever a future is put into the run queue.
+    mailbox: Mailbox,
+    /// The number of unfinished futures assigned to the worker. It never
+    /// exceeds the capacity of the run queue, so that a woken future can
+    /// always be enqueued.
+    load: AtomicUsize,
+}
+
+impl Worker {
+    const fn new() -> Self {
+        Self {
+            run_queue: MpMcQueue::new(),
+            mailbox: Mailbox::new(),
+            load: AtomicUsize::new(0),
+        }
+    }
+
+    /// Reserve a slot for a new future. Return `false` if the worker is full.
+    fn try_reserve(&self) -> bool {
+        self.load
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |load| {
+                (load < config::ASYNC_RUN_QUEUE_CAPACITY).then_some(load + 1)
+            })
+            .is_ok()
+    }
+
+    /// The body of a worker task.
+    fn run(&'static self) {
+        loop {
+            while let Some(task) = self.run_queue.dequeue() {
+                task.queued.store(false, Ordering::SeqCst);
+                task.poll();
+            }
+
+            time::wake_expired_async_timers();
+
+            match time::next_async_timer_timeout_ms() {
+                Some(timeout_ms) => {
+                    self.mailbox.wait_until_timeout(timeout_ms);
+                }
+                None => self.mailbox.wait(),
+            }
+        }
+    }
+}
+
+/// A spawned future together with the bookkeeping to wake it.
+struct AsyncTask {
+    /// The future to be polled. It becomes `None` once completed.
+    future: UnsafeCell<Option<BoxedFuture>>,
+    /// The worker that polls the future.
+    worker: &'static Worker,
+    /// Whether the task is currently in the run queue. It prevents the same
+    /// task from occupying more than one slot in the run queue.
+    queued: AtomicBool,
+}
+
+/// Safety: The future is only accessed by its worker when the task is dequeued
+/// from the run queue. A task is enqueued at most once at a time, so there is
+/// never concurrent access to the future.
+unsafe impl Sync for AsyncTask {}
+
+impl AsyncTask {
+    fn poll(self: &Arc<Self>) {
+        // Safety: See `unsafe impl Sync for AsyncTask`.
+        let slot = unsafe { &mut *self.future.get() };
+
+        if let Some(future) = slot {
+            let waker = Waker::from(Arc::clone(self));
+            let mut cx = Context::from_waker(&waker);
+            if future.as_mut().poll(&mut cx).is_ready() {
+                *slot = None;
+                self.worker.load.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn schedule(self: Arc<Self>) {
+        if self.queued.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let worker = self.worker;
+        worker.run_queue.enqueue(self).ok().unwrap_or_die();
+        worker.mailbox.notify_allow_isr();
+    }
+}
+
+/// Waking a future is allowed in ISR context. However, an ISR must not drop
+/// the last [`Waker`] of a future, because memory cannot be freed in ISR
+/// context.
+impl Wake for AsyncTask {
+    fn wake(self: Arc<Self>) {
+        self.schedule();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        Arc::clone(self).schedule();
+    }
+}
+
+const WORKER_INIT: Worker = Worker::new();
+
+static WORKERS: [Worker; config::ASYNC_WORKER_NUMBER] = [WORKER_INIT; config::ASYNC_WORKER_NUMBER];
+
+/// Whether the worker tasks have been spawned.
+static WORKERS_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// The worker index to try first for the next spawned future.
+static NEXT_WORKER: AtomicUsize = AtomicUsize::new(0);
+
+/// Spawn the worker tasks if they have not been spawned.
+fn start_workers() -> Result<(), TaskBuildError> {
+    if WORKERS_STARTED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    // If spawning fails halfway, the workers already spawned keep running.
+    // The flag stays set so that they will not be spawned twice.
+    for worker in WORKERS.iter() {
+        super::build()
+            .set_id(config::ASYNC_WORKER_TASK_ID)
+            .set_name("async_worker")
+            .set_priority(config::ASYNC_WORKER_PRIORITY)
+            .set_entry(move || worker.run())
+            .spawn()?;
+    }
+
+    Ok(())
+}
+
+/// Run the future to completion on the worker task pool. The future starts
+/// running when a worker picks it up, which may happen before this function
+/// returns.
+///
+/// # Example
+/// ```rust
+/// task::spawn_async(async {
+///     time::sleep_ms_async(100).unwrap().await;
+///     dbg_println!("100 ms later");
+/// })
+/// .unwrap();
+/// ```
+pub fn spawn_async<F>(future: F) -> Result<(), SpawnAsyncError>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    start_workers().map_err(SpawnAsyncError::Worker)?;
+
+    // Pick the first worker in round-robin order that still has room.
+    let first = NEXT_WORKER.fetch_add(1, Ordering::SeqCst);
+    let worker = (0..config::ASYNC_WORKER_NUMBER)
+        .map(|offset| &WORKERS[(first + offset) % config::ASYNC_WORKER_NUMBER])
+        .find(|worker| worker.try_reserve())
+        .ok_or(SpawnAsyncError::TooManyFutures)?;
+
+    let task = Arc::new(AsyncTask {
+        future: UnsafeCell::new(Some(Box::pin(future))),
+        worker,
+        queued: AtomicBool::new(false),
+    });
+    task.schedule();
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use crate::{schedule::current, unrecoverable};
+use alloc::boxed::Box;
+
+#[cfg(feature = "unwind")]
+use crate::unwind::unw_catch;
+
+/// Register a hook to run when the calling task returns from its entry
+/// closure or is unwound, e.g., to release a hardware resource not modeled
+/// as a type with a drop handler. The hooks run in the reverse order of
+/// registration, in the context of the task after the unwinding finishes,
+/// and before the task struct and its stack are reclaimed.
+///
+/// The hooks belong to a single run of the task. A restartable task runs the
+/// hooks registered by a run before it is restarted, and the restarted run
+/// must register them again. A panic inside a hook is caught, and the
+/// remaining hooks still run.
+///
+/// # Example
+/// ```rust
+/// let dma = take_dma_channel();
+/// task::at_exit(move || dma.disable());
+/// ```
+///
+/// NOTE: *must not* call this function in ISR context.
+pub fn at_exit<F>(hook: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    unrecoverable::die_if_in_isr();
+
+    let hook = Box::new(hook);
+    current::with_cur_task(|cur_task| cur_task.push_exit_hook(hook));
+}
+
+/// Run the hooks registered by the current task in the reverse order of
+/// registration. Called by the entry trampolines.
+pub(super) fn run_exit_hooks() {
+    // Each hook is taken out before it runs, so that it is not run twice and
+    // may itself register more hooks.
+    while let Some(hook) = current::with_cur_task(|cur_task| cur_task.pop_exit_hook()) {
+        #[cfg(feature = "unwind")]
+        let _ = unw_catch::catch_unwind(hook);
+
+        #[cfg(not(feature = "unwind"))]
+        hook();
+    }
+}
@@ -0,0 +1,336 @@
+use super::{registry, TaskId, TaskState};
+use crate::{
+    config,
+    schedule::{current, scheduler::Scheduler},
+    unrecoverable,
+};
+#[cfg(feature = "unwind")]
+use crate::{time, unwind::forced};
+use alloc::sync::Arc;
+
+/// A handle to a task, used by another task to control it, e.g., a
+/// supervisor task freezing a misbehaving worker without terminating it.
+///
+/// The handle refers to the task by its [`TaskId`], so it keeps referring to
+/// the task after the task panics and is restarted.
+///
+/// # Example
+/// ```rust
+/// let worker = task::TaskHandle::from_id(task::find_by_name("worker").unwrap());
+///
+/// // The worker stops running until it is resumed.
+/// worker.suspend().unwrap();
+/// inspect_shared_state();
+/// worker.resume().unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskHandle {
+    id: TaskId,
+}
+
+/// The stack statistics of a task, returned by
+/// [`TaskHandle::stack_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackStats {
+    peak_usage: usize,
+    stacklet_count: usize,
+}
+
+impl StackStats {
+    /// The maximum number of bytes of stack the task has ever used, counted
+    /// in the same way as the usage compared against the stack limit set
+    /// with [`set_stack_limit`](super::TaskBuilder::set_stack_limit), i.e.,
+    /// excluding the initial stacklet and the overhead of each stacklet. A
+    /// restarted task keeps the peak usage of the panicked instance. For a
+    /// task without dynamic stack extension, the whole stack allocated
+    /// upfront is counted.
+    pub fn peak_usage(&self) -> usize {
+        self.peak_usage
+    }
+
+    /// The number of stacklets the task currently has, including the
+    /// initial stacklet. A task without dynamic stack extension always has
+    /// one.
+    pub fn stacklet_count(&self) -> usize {
+        self.stacklet_count
+    }
+}
+
+impl TaskHandle {
+    /// Create a handle to the task with the given ID.
+    pub const fn from_id(id: TaskId) -> Self {
+        Self { id }
+    }
+
+    /// Return the ID of the task.
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Suspend the task. A suspended task does not run until it is
+    /// [`resume`](Self::resume)d. Return `Err(())` if the task has
+    /// terminated or is the idle task.
+    ///
+    /// A ready task stops running immediately. A blocked task is still woken
+    /// up by the event it waits for, e.g., a notification or a timeout, but
+    /// only runs after it is resumed. Suspending a suspended task has no
+    /// effect. Suspending the calling task is the same as
+    /// [`suspend_current`].
+    ///
+    /// The suspended task keeps the locks it holds, so other tasks waiting
+    /// for them will also stop making progress.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn suspend(&self) -> Result<(), ()> {
+        unrecoverable::die_if_in_isr();
+
+        let task = registry::find_task(self.id).ok_or(())?;
+        if task.is_idle() {
+            return Err(());
+        }
+        Scheduler::suspend_task(&task);
+        Ok(())
+    }
+
+    /// Resume the task if it is suspended. It will run again once it is
+    /// ready. Return `Err(())` if the task has terminated.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn resume(&self) -> Result<(), ()> {
+        unrecoverable::die_if_in_isr();
+
+        let task = registry::find_task(self.id).ok_or(())?;
+        Scheduler::resume_task(&task);
+        Ok(())
+    }
+
+    /// Terminate the task by unwinding its stack, which runs the drop
+    /// handlers of its live objects and frees its stacklets. The task is not
+    /// restarted even if it was spawned as restartable. Return `Err(())` if
+    /// the task has already terminated or is the idle task.
+    ///
+    /// The task is unwound at its next preemption point, i.e., when it
+    /// resumes after sleeping, yielding, being suspended or waiting on a
+    /// [`Mailbox`](crate::sync::Mailbox), or when it passes a
+    /// [`checkpoint`](super::checkpoint). A sleeping or suspended task, or a
+    /// task waiting on a mailbox with a timeout, is woken up to be unwound.
+    /// A task blocked on another synchronization primitive keeps waiting,
+    /// and is unwound at its next preemption point after it acquires the
+    /// primitive, so that the primitive is left consistent. Terminating the
+    /// calling task unwinds it immediately.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    #[cfg(feature = "unwind")]
+    pub fn terminate(&self) -> Result<(), ()> {
+        unrecoverable::die_if_in_isr();
+
+        let task = registry::find_task(self.id).ok_or(())?;
+        if task.is_idle() {
+            return Err(());
+        }
+        task.set_terminating();
+
+        if self.id == super::current_id() {
+            drop(task);
+            forced::unwind_if_requested();
+            return Ok(());
+        }
+
+        Scheduler::resume_task(&task);
+        time::remove_task_from_sleep_queue_allow_isr(task);
+        Ok(())
+    }
+
+    /// Change the priority of the task, e.g., to deprioritize a background
+    /// task during a latency-critical phase. Return `Err(())` if the task has
+    /// terminated, is the idle task, or if the priority is not allowed by the
+    /// configuration settings.
+    ///
+    /// The new priority has taken effect when the method returns. If the task
+    /// now has a higher priority than the calling task, it will have
+    /// preempted the calling task. Changing the priority of the calling task
+    /// is the same as [`change_current_priority`](super::change_current_priority).
+    /// A task restarted after panicking keeps the changed priority.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn set_priority(&self, prio: u8) -> Result<(), ()> {
+        unrecoverable::die_if_in_isr();
+
+        if prio >= config::TASK_PRIORITY_LEVELS - 1 {
+            return Err(());
+        }
+        let task = registry::find_task(self.id).ok_or(())?;
+        if task.is_idle() {
+            return Err(());
+        }
+        task.change_intrinsic_priority(prio);
+
+        // Let the scheduler pick the next task if the priority of the calling
+        // task was changed, or if the task should now preempt it.
+        let should_yield = current::with_cur_task(|cur_task| {
+            core::ptr::eq(cur_task, Arc::as_ptr(&task))
+                || (task.get_state() == TaskState::Ready && task.should_preempt(cur_task))
+        });
+        drop(task);
+        if should_yield {
+            super::yield_current();
+        }
+        Ok(())
+    }
+
+    /// Change the priority of the task like [`set_priority`](Self::set_priority),
+    /// e.g., to boost the task handling an urgent event signaled by an
+    /// interrupt. Return `Err(())` in the same cases, and also if the task
+    /// list is being accessed by the preempted code, in which case the
+    /// priority is unchanged and the caller may retry later.
+    ///
+    /// If the task now has a higher priority than the current task, the
+    /// current task is preempted once the ISR returns.
+    ///
+    /// Calling this method in ISR context is allowed.
+    pub fn set_priority_allow_isr(&self, prio: u8) -> Result<(), ()> {
+        if prio >= config::TASK_PRIORITY_LEVELS - 1 {
+            return Err(());
+        }
+        let task = registry::try_find_task(self.id)?.ok_or(())?;
+        if task.is_idle() {
+            return Err(());
+        }
+        task.change_intrinsic_priority(prio);
+        Ok(())
+    }
+
+    /// Return the priority of the task set when it was spawned or changed
+    /// with [`set_priority`](Self::set_priority), excluding the priority
+    /// inherited from other tasks. Return `None` if the task has terminated.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn priority(&self) -> Option<u8> {
+        unrecoverable::die_if_in_isr();
+
+        registry::find_task(self.id).map(|task| task.get_priority().intrinsic_priority())
+    }
+
+    /// Return the stack statistics of the task, e.g., to tune its stack
+    /// limit by observing the peak usage under a realistic workload. Return
+    /// `None` if the task has terminated.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn stack_stats(&self) -> Option<StackStats> {
+        unrecoverable::die_if_in_isr();
+
+        let task = registry::find_task(self.id)?;
+        let stats = task
+            .with_stack_ctrl_block(|scb| StackStats {
+                peak_usage: scb.get_peak_usage() as usize,
+                stacklet_count: scb.get_stacklet_count() as usize,
+            })
+            .unwrap_or_else(|| StackStats {
+                peak_usage: task.get_stack_limit().unwrap_or(0),
+                stacklet_count: 1,
+            });
+        Some(stats)
+    }
+
+    /// Return if the task is suspended. Return `false` if the task has
+    /// terminated.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn is_suspended(&self) -> bool {
+        unrecoverable::die_if_in_isr();
+
+        registry::find_task(self.id).is_some_and(|task| task.is_suspended())
+    }
+}
+
+/// A task created with [`spawn_paused`](super::TaskBuilder::spawn_paused)
+/// that has not been started yet.
+///
+/// Dropping it does not start the task, which can still be resumed through
+/// its [`TaskHandle`].
+///
+/// # Example
+/// ```rust
+/// let consumer = task::build().set_entry(consume).spawn_paused().unwrap();
+/// let producer = task::build().set_entry(produce).spawn_paused().unwrap();
+///
+/// // Start both tasks at once, regardless of their priorities.
+/// task::run_uninterrupted(|| {
+///     consumer.start().unwrap();
+///     producer.start().unwrap();
+/// });
+/// ```
+#[derive(Debug)]
+pub struct PausedTask {
+    handle: TaskHandle,
+}
+
+impl PausedTask {
+    pub(super) fn new(id: TaskId) -> Self {
+        Self {
+            handle: TaskHandle::from_id(id),
+        }
+    }
+
+    /// Return the handle to the task.
+    pub fn handle(&self) -> TaskHandle {
+        self.handle
+    }
+
+    /// Start the task. It runs immediately if it has a higher priority than
+    /// the calling task. An EDF task releases its first job now. Return
+    /// `Err(())` if the task has terminated, e.g., because it was terminated
+    /// before being started.
+    ///
+    /// Important: *must not* call this method in ISR context.
+    pub fn start(self) -> Result<(), ()> {
+        unrecoverable::die_if_in_isr();
+
+        let task = registry::find_task(self.handle.id).ok_or(())?;
+        task.start_job();
+        Scheduler::resume_task(&task);
+        Ok(())
+    }
+}
+
+/// Return a handle to the calling task.
+pub fn current_handle() -> TaskHandle {
+    TaskHandle::from_id(super::current_id())
+}
+
+/// Switch the calling task out of the CPU like
+/// [`yield_current`](super::yield_current), but if the given task is ready
+/// and has the same priority as the calling task, run it next, ahead of the
+/// other ready tasks of that priority. Return `Err(())` if the task has
+/// terminated.
+///
+/// This shortens the latency between two tightly coupled tasks of the same
+/// priority, e.g., a producer handing over to its consumer. A ready task of
+/// a higher priority still runs first. Among EDF tasks, the one with the
+/// earliest deadline runs first regardless.
+///
+/// NOTE: *must not* call this function in ISR context.
+pub fn yield_to(target: TaskHandle) -> Result<(), ()> {
+    unrecoverable::die_if_in_isr();
+
+    let task = registry::find_task(target.id).ok_or(())?;
+    Scheduler::move_to_front_if_peer(&task);
+    drop(task);
+    super::yield_current();
+    Ok(())
+}
+
+/// Suspend the calling task until another task
+/// [`resume`](TaskHandle::resume)s it.
+///
+/// NOTE: *must not* call this function in ISR context.
+pub fn suspend_current() {
+    unrecoverable::die_if_in_isr();
+
+    // The context switch happens when the scheduler is no longer suspended.
+    current::with_cur_task(Scheduler::suspend_task);
+
+    // The task may have been resumed to be terminated.
+    #[cfg(feature = "unwind")]
+    forced::unwind_if_requested();
+}
@@ -0,0 +1,128 @@
+use crate::{
+    sync::{Mailbox, SpinSchedSafe},
+    time, unrecoverable,
+};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A handle to a task spawned with
+/// [`spawn_joinable`](super::TaskBuilder::spawn_joinable), used to collect
+/// the value returned by its entry closure.
+///
+/// Dropping the handle detaches the task. The task keeps running and its
+/// return value is dropped when it finishes.
+///
+/// # Example
+/// ```rust
+/// let handle = task::build()
+///     .set_entry(|| compute_checksum(&BLOCK))
+///     .spawn_joinable()
+///     .unwrap();
+///
+/// // Do something else in between.
+///
+/// let checksum = handle.join().unwrap();
+/// ```
+pub struct JoinHandle<T> {
+    state: Arc<JoinState<T>>,
+}
+
+/// The error returned by joining a task that ended without returning a
+/// value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinError {
+    /// The task panicked, or was [terminated](super::TaskHandle::terminate),
+    /// and its stack was unwound before the entry closure returned.
+    Panicked,
+}
+
+/// The state shared between the joinable task and its handle.
+struct JoinState<T> {
+    /// The value returned by the entry closure.
+    value: SpinSchedSafe<Option<T>>,
+    /// Whether the task has finished, with or without a value.
+    finished: AtomicBool,
+    /// Notified once when the task finishes.
+    done: Mailbox,
+}
+
+/// Held by the joinable task while it runs. Dropping it, including when the
+/// task panics and is unwound, marks the task as finished and wakes up the
+/// joining task.
+pub(super) struct JoinCompletion<T> {
+    state: Arc<JoinState<T>>,
+}
+
+/// Create a handle and the completion held by the task it refers to.
+pub(super) fn new_join_pair<T>() -> (JoinHandle<T>, JoinCompletion<T>) {
+    let state = Arc::new(JoinState {
+        value: SpinSchedSafe::new(None),
+        finished: AtomicBool::new(false),
+        done: Mailbox::new(),
+    });
+    let handle = JoinHandle {
+        state: state.clone(),
+    };
+    (handle, JoinCompletion { state })
+}
+
+impl<T> JoinCompletion<T> {
+    /// Deliver the value returned by the entry closure.
+    pub(super) fn complete(self, value: T) {
+        *self.state.value.lock() = Some(value);
+        // The joining task is notified when `self` is dropped.
+    }
+}
+
+impl<T> Drop for JoinCompletion<T> {
+    fn drop(&mut self) {
+        self.state.finished.store(true, Ordering::SeqCst);
+        self.state.done.notify_allow_isr();
+    }
+}
+
+impl<T> JoinHandle<T> {
+    /// Return if the task has finished, either by returning from its entry
+    /// closure or by panicking.
+    pub fn is_finished(&self) -> bool {
+        self.state.finished.load(Ordering::SeqCst)
+    }
+
+    /// Block the calling task until the task finishes. Return the value
+    /// returned by its entry closure with `Ok`, or `Err` if the task panicked.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn join(self) -> Result<T, JoinError> {
+        unrecoverable::die_if_in_isr();
+
+        while !self.is_finished() {
+            self.state.done.wait();
+        }
+        self.take_value()
+    }
+
+    /// Block the calling task until the task finishes or the elapsed waiting
+    /// time reaches timeout. Return the result of [`join`](Self::join) with
+    /// `Ok`, or give back the handle with `Err` if timed out, so that the
+    /// task can be joined again later.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn join_timeout(self, timeout_ms: u32) -> Result<Result<T, JoinError>, Self> {
+        unrecoverable::die_if_in_isr();
+
+        let start = time::get_tick();
+        while !self.is_finished() {
+            let elapsed = time::get_tick().wrapping_sub(start);
+            if elapsed >= timeout_ms {
+                return Err(self);
+            }
+            self.state.done.wait_until_timeout(timeout_ms - elapsed);
+        }
+        Ok(self.take_value())
+    }
+
+    /// Take the value out of a finished task.
+    fn take_value(&self) -> Result<T, JoinError> {
+        self.state.value.lock().take().ok_or(JoinError::Panicked)
+    }
+}
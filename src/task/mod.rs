@@ -1,16 +1,51 @@
-mod breathing;
+pub mod breathing;
 mod builder;
+mod checkpoint;
 mod current;
+mod executor;
+mod exit_hook;
+mod handle;
+mod join;
+#[cfg(feature = "unwind")]
+mod panic_observer;
 mod priority;
+#[cfg(feature = "reaper")]
+pub(crate) mod reaper;
+mod registry;
 pub(crate) mod segmented_stack;
+#[cfg(feature = "starvation_monitor")]
+pub(crate) mod starvation;
+mod static_task;
+#[cfg(feature = "unwind")]
+mod supervisor;
 mod task_list;
 mod task_struct;
 mod trampoline;
+mod watchdog;
 
+#[cfg(feature = "unwind")]
+pub(crate) use panic_observer::{has_panic_observer, record_panic, record_watchdog_expiry};
+pub(crate) use registry::register;
 pub(crate) use segmented_stack::*;
 pub(crate) use task_list::*;
 pub(crate) use task_struct::*;
+pub(crate) use watchdog::check_deadlines_allow_isr;
 
+pub use crate::schedule::idle::{get_idle_time_ms, set_idle_hook};
 pub use builder::*;
+pub use checkpoint::*;
 pub use current::*;
+pub use executor::*;
+pub use exit_hook::at_exit;
+pub use handle::*;
 pub use hopter_proc_macro::main;
+pub use join::*;
+#[cfg(feature = "unwind")]
+pub use panic_observer::{set_panic_observer, PanicObserver, PanicRecord};
+#[cfg(feature = "reaper")]
+pub use reaper::get_pending_reclamation_count;
+pub use registry::{find_by_name, for_each_task, TaskId, TaskInfo, TaskStatus};
+pub use static_task::StaticTask;
+#[cfg(feature = "unwind")]
+pub use supervisor::{RestartStrategy, Supervisor};
+pub use watchdog::{watchdog_feed, watchdog_register, watchdog_unregister, WatchdogAction};
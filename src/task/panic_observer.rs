@@ -0,0 +1,147 @@
+//! Observing the panics of tasks.
+//!
+//! The application can register a panic observer with
+//! [`set_panic_observer`]. When a task panics, the panic message is recorded
+//! by the panic handler, and the observer is called with the recorded
+//! [`PanicRecord`] after the stack of the task is unwound, before the task
+//! is restarted. The observer decides whether a restartable task should be
+//! restarted, e.g., to give up after counting too many crashes of the task.
+//!
+//! The observer is also called when a task is forcefully unwound because it
+//! exceeds its stack limit or misses its [watchdog](super::watchdog_register)
+//! deadline, but not when it is [terminated](super::TaskHandle::terminate).
+//!
+//! # Example
+//! ```rust
+//! task::set_panic_observer(|info, record| {
+//!     let crashes = CRASHES.fetch_add(1, Ordering::SeqCst) + 1;
+//!     log::warn!("{:?} crashed: {}", info.name(), record.text());
+//!     crashes < 10
+//! });
+//! ```
+
+use super::registry::TaskInfo;
+use crate::{schedule::current, sync::AtomicCell, time};
+use alloc::boxed::Box;
+use core::{
+    fmt::{self, Write},
+    panic::PanicInfo,
+};
+
+/// The maximum length in bytes of the text recorded for a panic. Longer
+/// text is truncated.
+const PANIC_TEXT_LEN: usize = 128;
+
+/// The callback observing the panics of tasks. It returns whether the
+/// panicked task should be restarted if it is restartable.
+pub type PanicObserver = fn(&TaskInfo, &PanicRecord) -> bool;
+
+/// What was recorded about a panic, passed to the [`PanicObserver`]. The
+/// [`PanicInfo`] passed to the panic handler does not outlive the
+/// unwinding, so its text is recorded instead.
+pub struct PanicRecord {
+    text: [u8; PANIC_TEXT_LEN],
+    len: usize,
+    tick: u32,
+}
+
+impl PanicRecord {
+    fn new(tick: u32) -> Self {
+        Self {
+            text: [0; PANIC_TEXT_LEN],
+            len: 0,
+            tick,
+        }
+    }
+
+    /// The text describing the panic, i.e., the location and the message,
+    /// truncated to 128 bytes. For a task forcefully unwound because it
+    /// exceeds its stack limit, the text is `stack limit exceeded`, and
+    /// because it misses its watchdog deadline, `watchdog expired`.
+    pub fn text(&self) -> &str {
+        // Only whole characters are copied into the buffer.
+        core::str::from_utf8(&self.text[..self.len]).unwrap_or("")
+    }
+
+    /// The tick when the panic occurred.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+}
+
+/// Copy the written text into the buffer, dropping what does not fit.
+impl Write for PanicRecord {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut end = s.len().min(PANIC_TEXT_LEN - self.len);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.text[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+        Ok(())
+    }
+}
+
+static OBSERVER: AtomicCell<Option<PanicObserver>> = AtomicCell::new(None);
+
+/// Register the observer called after a panicked task is unwound,
+/// replacing the previous observer. See the [module-level
+/// documentation](self).
+///
+/// While an observer is registered, a panicked restartable task is restarted
+/// only after its stack is unwound, rather than concurrently with the
+/// unwinding, so that the observer can decide whether to restart it.
+pub fn set_panic_observer(observer: PanicObserver) {
+    OBSERVER.store(Some(observer));
+}
+
+/// Return if a panic observer is registered.
+pub(crate) fn has_panic_observer() -> bool {
+    OBSERVER.load().is_some()
+}
+
+/// Record the panic for the observer if the current task panicked. Called
+/// from the panic handler.
+pub(crate) fn record_panic(info: &PanicInfo) {
+    if current::is_in_isr_context() || !has_panic_observer() {
+        return;
+    }
+
+    let mut record = Box::new(PanicRecord::new(time::get_tick()));
+    // Writing into the record never fails.
+    let _ = write!(record, "{}", info);
+    current::with_cur_task(|cur_task| cur_task.set_panic_record(record));
+}
+
+/// Record that the current task is forcefully unwound because it missed its
+/// watchdog deadline.
+pub(crate) fn record_watchdog_expiry() {
+    if !has_panic_observer() {
+        return;
+    }
+
+    let mut record = Box::new(PanicRecord::new(time::get_tick()));
+    let _ = record.write_str("watchdog expired");
+    current::with_cur_task(|cur_task| cur_task.set_panic_record(record));
+}
+
+/// Call the observer after the current task is unwound. Return whether the
+/// task should be restarted if it is restartable.
+pub(super) fn observe_panic() -> bool {
+    let Some(observer) = OBSERVER.load() else {
+        return true;
+    };
+
+    let (info, record) = current::with_cur_task(|cur_task| {
+        // A terminated task did not crash.
+        let info = TaskInfo::of(cur_task).filter(|_| !cur_task.is_terminating());
+        (info, cur_task.take_panic_record())
+    });
+    // A task forcefully unwound did not go through the panic handler.
+    let record = record.unwrap_or_else(|| {
+        let mut record = Box::new(PanicRecord::new(time::get_tick()));
+        let _ = record.write_str("stack limit exceeded");
+        record
+    });
+    info.map_or(false, |info| observer(&info, &record))
+}
@@ -0,0 +1,76 @@
+//! Deferred reclamation of terminated tasks, enabled by the `reaper`
+//! feature.
+//!
+//! Without the feature, the task struct and the initial stacklet of a task
+//! are freed by the context switch that removes the terminated task from the
+//! CPU, delaying whichever task is switched on, possibly a high priority one.
+//! With the feature, the context switch instead hands the task struct to a
+//! reaper task, which frees it at
+//! [`REAPER_TASK_PRIORITY`](config::REAPER_TASK_PRIORITY).
+//!
+//! A task remains counted towards
+//! [`MAX_TASK_NUMBER`](config::MAX_TASK_NUMBER) until it is reclaimed, so
+//! spawning a task right after another one terminated may fail with
+//! [`NoMoreTask`](super::TaskBuildError::NoMoreTask) while the reaper task
+//! is kept off the CPU. The reaper task itself also takes one task slot.
+
+use super::Task;
+use crate::{config, sync::Mailbox};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use heapless::mpmc::MpMcQueue;
+
+/// Terminated tasks waiting to be reclaimed. Every task holds a task quota
+/// until it is reclaimed, so the queue never overflows.
+static TERMINATED: MpMcQueue<Arc<Task>, { config::MAX_TASK_NUMBER }> = MpMcQueue::new();
+
+/// The number of tasks terminated but not yet reclaimed.
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+/// Notified once for each terminated task.
+static REAPER: Mailbox = Mailbox::new();
+
+/// Return the number of terminated tasks whose resources are not yet
+/// reclaimed by the reaper task.
+pub fn get_pending_reclamation_count() -> usize {
+    PENDING.load(Ordering::SeqCst)
+}
+
+/// Spawn the reaper task. Called once when booting.
+pub(crate) fn spawn() -> Result<(), super::TaskBuildError> {
+    super::build()
+        .set_id(config::REAPER_TASK_ID)
+        .set_name("reaper")
+        .set_priority(config::REAPER_TASK_PRIORITY)
+        .set_entry(reaper)
+        .spawn()
+}
+
+/// Wake up the reaper task for the current task which is terminating. Called
+/// in SVC context before the context switch that hands over the task.
+pub(crate) fn notify_termination() {
+    PENDING.fetch_add(1, Ordering::SeqCst);
+    REAPER.notify_allow_isr();
+}
+
+/// Hand over the terminated task to the reaper task. Called in PendSV
+/// context when the task is switched out of the CPU for the last time.
+pub(crate) fn defer_reclamation(task: Arc<Task>) {
+    // Drop the task right away in the impossible case of a full queue.
+    let _ = TERMINATED.enqueue(task);
+}
+
+/// The body of the reaper task.
+fn reaper() {
+    loop {
+        REAPER.wait();
+
+        // The task is always queued by the context switch following the
+        // notification, and the context switch completes before the reaper
+        // task can run.
+        while let Some(task) = TERMINATED.dequeue() {
+            drop(task);
+            PENDING.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use super::{Task, TaskState};
+use crate::{
+    sync::SpinSchedSafe,
+    time::{self, tick_cmp},
+    unrecoverable,
+};
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::{
+    cmp::Ordering as CmpOrdering,
+    fmt,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// The unique ID assigned to a task when it is spawned. Unlike the
+/// diagnostic ID set with [`set_id`](super::TaskBuilder::set_id), no two
+/// tasks share the same ID. A restarted instance of a panicked task keeps
+/// the ID of the panicked task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(u32);
+
+impl TaskId {
+    /// Allocate a new ID. IDs are never reused.
+    pub(crate) fn next() -> Self {
+        static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::SeqCst))
+    }
+
+    /// Return the numerical value of the ID.
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Return the ID with the numerical value returned by [`get`](Self::get).
+    pub(crate) const fn from_raw(id: u32) -> Self {
+        Self(id)
+    }
+}
+
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// The scheduling state of a task reported in a [`TaskInfo`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// The task is running on the CPU, i.e., it is the task calling
+    /// [`for_each_task`].
+    Running,
+    /// The task is ready to run.
+    Ready,
+    /// The task is waiting for an event without a timeout.
+    Blocked,
+    /// The task is sleeping, or waiting for an event with a timeout.
+    Sleeping,
+    /// The task is suspended. See [`TaskHandle::suspend`](super::TaskHandle::suspend).
+    Suspended,
+}
+
+/// A snapshot of a live task, passed to the closure of [`for_each_task`].
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    id: TaskId,
+    name: Option<&'static str>,
+    priority: u8,
+    status: TaskStatus,
+    stack_usage: usize,
+}
+
+impl TaskInfo {
+    /// Take a snapshot of the task. Return `None` if the task is being
+    /// created or destroyed.
+    pub(super) fn of(task: &Task) -> Option<Self> {
+        let status = match task.get_state() {
+            TaskState::Initializing | TaskState::Destructing => return None,
+            // A blocked task is suspended as soon as it is asked to.
+            _ if task.is_suspended() => TaskStatus::Suspended,
+            TaskState::Suspended => TaskStatus::Suspended,
+            TaskState::Running => TaskStatus::Running,
+            TaskState::Ready => TaskStatus::Ready,
+            TaskState::Blocked => match tick_cmp(time::get_tick(), task.get_wake_tick()) {
+                CmpOrdering::Less => TaskStatus::Sleeping,
+                _ => TaskStatus::Blocked,
+            },
+        };
+        // A static stack is allocated entirely when the task is spawned.
+        let stack_usage = task
+            .with_stack_ctrl_block(|scb| scb.get_usage() as usize)
+            .or_else(|| task.get_stack_limit())
+            .unwrap_or(0);
+        Some(Self {
+            id: task.get_uid(),
+            name: task.get_name(),
+            priority: task.get_priority().effective_priority(),
+            status,
+            stack_usage,
+        })
+    }
+
+    /// The unique ID of the task.
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// The name of the task, or `None` if the task is not named.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    /// The effective priority of the task, including the priority inherited
+    /// from other tasks.
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// The scheduling state of the task.
+    pub fn status(&self) -> TaskStatus {
+        self.status
+    }
+
+    /// The number of bytes of stack used by the task, excluding the overhead
+    /// of each stacklet. For a task without dynamic stack extension, the
+    /// whole stack allocated upfront is counted.
+    pub fn stack_usage(&self) -> usize {
+        self.stack_usage
+    }
+}
+
+/// The spawned tasks. Terminated tasks are pruned lazily when a new task is
+/// registered, because the task struct is dropped in the context switch
+/// where the lock cannot be taken.
+static TASKS: SpinSchedSafe<Vec<Weak<Task>>> = SpinSchedSafe::new(Vec::new());
+
+/// Record a newly spawned task before handing it to the scheduler.
+pub(crate) fn register(task: &Arc<Task>) {
+    let mut tasks = TASKS.lock();
+    tasks.retain(|task| task.strong_count() > 0);
+    tasks.push(Arc::downgrade(task));
+}
+
+/// Forget the terminated tasks, so that their task structs are freed.
+pub(super) fn prune() {
+    TASKS.lock().retain(|task| task.strong_count() > 0);
+}
+
+/// Return the newest instance of the task with the given ID, or `None` if
+/// the task has terminated.
+pub(super) fn find_task(id: TaskId) -> Option<Arc<Task>> {
+    TASKS
+        .lock()
+        .iter()
+        .rev()
+        .filter_map(Weak::upgrade)
+        .find(|task| task.get_state() != TaskState::Destructing && task.get_uid() == id)
+}
+
+/// Like [`find_task`], but return `Err(())` instead of waiting if the task
+/// list is being accessed by the preempted context.
+pub(super) fn try_find_task(id: TaskId) -> Result<Option<Arc<Task>>, ()> {
+    let tasks = TASKS.try_lock().ok_or(())?;
+    Ok(tasks
+        .iter()
+        .rev()
+        .filter_map(Weak::upgrade)
+        .find(|task| task.get_state() != TaskState::Destructing && task.get_uid() == id))
+}
+
+/// Return the live tasks, including the idle task, in the order they were
+/// spawned.
+#[cfg(feature = "starvation_monitor")]
+pub(super) fn live_tasks() -> Vec<Arc<Task>> {
+    TASKS
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter(|task| task.get_state() != TaskState::Destructing)
+        .collect()
+}
+
+/// Return the ID of a task with the given name, or `None` if no running task
+/// has the name. If several tasks share the name, return the earliest
+/// spawned one.
+///
+/// NOTE: *must not* call this function in ISR context.
+pub fn find_by_name(name: &str) -> Option<TaskId> {
+    unrecoverable::die_if_in_isr();
+
+    TASKS
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .find(|task| task.get_state() != TaskState::Destructing && task.get_name() == Some(name))
+        .map(|task| task.get_uid())
+}
+
+/// Call the closure with a snapshot of each live task, including the idle
+/// task, in the order the tasks were spawned.
+///
+/// The snapshots of all tasks are taken first with the scheduler suspended,
+/// so the closure may block, e.g., to print the task table.
+///
+/// # Example
+/// ```rust
+/// task::for_each_task(|info| {
+///     dbg_println!(
+///         "{} {:?} prio {} {:?} stack {}",
+///         info.id(),
+///         info.name(),
+///         info.priority(),
+///         info.status(),
+///         info.stack_usage()
+///     );
+/// });
+/// ```
+///
+/// NOTE: *must not* call this function in ISR context.
+pub fn for_each_task<F>(op: F)
+where
+    F: FnMut(&TaskInfo),
+{
+    unrecoverable::die_if_in_isr();
+
+    let infos: Vec<TaskInfo> = TASKS
+        .lock()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter_map(|task| TaskInfo::of(&task))
+        .collect();
+    infos.iter().for_each(op);
+}
@@ -51,9 +51,16 @@
 //!
 //! [`unwind_land`] handles the corner case when the unwinder invokes a landing
 //! pad.
+//!
+//! With [`ENABLE_STACKLET_CACHE`](config::ENABLE_STACKLET_CACHE), each task
+//! keeps the stacklet it freed most recently in its [`StackCtrlBlock`]
+//! instead of returning it to the heap, and [`more_stack`] reuses it if it is
+//! large enough. This makes the hot-split pattern, where a loop repeatedly
+//! calls a function that does not fit in the current stacklet, cost no heap
+//! operation in the steady state.
 
 use crate::{
-    config,
+    allocator, config,
     interrupt::{
         svc,
         svc_handler::TaskSVCCtxt,
@@ -65,7 +72,7 @@ use crate::{
 use core::{
     alloc::Layout,
     arch::asm,
-    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+    sync::atomic::{AtomicPtr, AtomicU32, AtomicUsize, Ordering},
 };
 
 #[cfg(feature = "unwind")]
@@ -89,6 +96,46 @@ pub fn get_active_stacklet_count() -> usize {
     ACTIVE_STACKLET_COUNT.load(Ordering::Relaxed)
 }
 
+static STACKLET_CACHE_HIT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Return the number of stack extensions that reused the stacklet cached by
+/// the task since system boot. The counter will wrap around back to zero
+/// after reaching `usize::MAX`.
+pub fn get_stacklet_cache_hit_count() -> usize {
+    STACKLET_CACHE_HIT_COUNT.load(Ordering::Relaxed)
+}
+
+static STACKLET_CACHE_MISS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Return the number of stack extensions that allocated a new stacklet from
+/// the heap because the task had no cached stacklet large enough, since
+/// system boot. The counter will wrap around back to zero after reaching
+/// `usize::MAX`.
+pub fn get_stacklet_cache_miss_count() -> usize {
+    STACKLET_CACHE_MISS_COUNT.load(Ordering::Relaxed)
+}
+
+/// The sum of the stack reservations of all tasks that are not yet used by
+/// the reserving tasks, in bytes.
+static RESERVED_STACK_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Return the number of bytes reserved for the stack extension of tasks
+/// spawned with [`set_stack_reserve`](crate::task::TaskBuilder::set_stack_reserve)
+/// and not yet used by them.
+pub fn get_reserved_stack_size() -> usize {
+    RESERVED_STACK_SIZE.load(Ordering::SeqCst)
+}
+
+/// Return the number of free heap bytes available for stack extension
+/// beyond the reservations, i.e., to tasks exceeding or not having a
+/// reservation. Heap fragmentation is not taken into account, so an
+/// allocation of this size may still fail.
+pub fn get_stack_headroom() -> usize {
+    let (used, _) = allocator::heap_usage();
+    let free = allocator::heap_size().saturating_sub(used) as usize;
+    free.saturating_sub(get_reserved_stack_size())
+}
+
 #[derive(PartialEq)]
 pub(crate) enum MoreStackReason {
     Normal,
@@ -131,13 +178,135 @@ pub(crate) struct StackCtrlBlock {
     round_robin_idx: AtomicUsize,
     /// Cumulative size of all stacklets allocated for a task, which does not
     /// count the overhead size in each stacklet but only application requested
-    /// size.
-    pub(crate) cumulated_size: AtomicU32,
+    /// size. Use [`add_usage`](Self::add_usage) and
+    /// [`sub_usage`](Self::sub_usage) to update it.
+    cumulated_size: AtomicU32,
+    /// The maximum value [`cumulated_size`](Self::cumulated_size) has ever
+    /// reached.
+    peak_size: AtomicU32,
+    /// The number of stacklets allocated for the task in addition to the
+    /// initial stacklet.
+    extra_stklet_cnt: AtomicU32,
+    /// The size reserved for the stack extension of the task. The part not
+    /// yet used is counted in [`RESERVED_STACK_SIZE`].
+    reserve: u32,
+    /// The stacklet freed most recently, kept for reuse, or null.
+    cached_stklet: AtomicPtr<u8>,
+    /// The usable size of the cached stacklet.
+    cached_size: AtomicUsize,
+}
+
+impl StackCtrlBlock {
+    /// Create a stack control block reserving the given size for the stack
+    /// extension of the task.
+    pub(crate) fn new(reserve: usize) -> Self {
+        RESERVED_STACK_SIZE.fetch_add(reserve, Ordering::SeqCst);
+        Self {
+            reserve: reserve as u32,
+            ..Default::default()
+        }
+    }
+
+    /// Return the part of the reservation not used when the stack usage is
+    /// `used`.
+    fn unused_reserve(&self, used: u32) -> u32 {
+        self.reserve.saturating_sub(used)
+    }
+
+    /// Return whether extending the stack by `size` bytes stays within the
+    /// reservation of the task.
+    fn is_within_reserve(&self, size: u32) -> bool {
+        self.cumulated_size.load(Ordering::SeqCst) + size <= self.reserve
+    }
+
+    /// Return the cumulative size of all stacklets allocated for the task.
+    pub(crate) fn get_usage(&self) -> u32 {
+        self.cumulated_size.load(Ordering::SeqCst)
+    }
+
+    /// Return the maximum cumulative size of all stacklets allocated for the
+    /// task at any time.
+    pub(crate) fn get_peak_usage(&self) -> u32 {
+        self.peak_size.load(Ordering::SeqCst)
+    }
+
+    /// Raise the peak usage to `size` if it is lower.
+    pub(crate) fn record_peak_usage(&self, size: u32) {
+        self.peak_size.fetch_max(size, Ordering::SeqCst);
+    }
+
+    /// Return the number of stacklets the task currently has, including the
+    /// initial stacklet.
+    pub(crate) fn get_stacklet_count(&self) -> u32 {
+        self.extra_stklet_cnt.load(Ordering::SeqCst) + 1
+    }
+
+    /// Count a stacklet allocated for the task.
+    fn count_stacklet_alloc(&self) {
+        self.extra_stklet_cnt.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Count a stacklet of the task that is freed.
+    pub(crate) fn count_stacklet_free(&self) {
+        self.extra_stklet_cnt.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Count `size` more bytes of stack usage. Return the updated usage.
+    pub(crate) fn add_usage(&self, size: u32) -> u32 {
+        let prev = self.cumulated_size.fetch_add(size, Ordering::SeqCst);
+        let updated = prev + size;
+        self.record_peak_usage(updated);
+        let used = self.unused_reserve(prev) - self.unused_reserve(updated);
+        RESERVED_STACK_SIZE.fetch_sub(used as usize, Ordering::SeqCst);
+        updated
+    }
+
+    /// Count `size` bytes less of stack usage.
+    pub(crate) fn sub_usage(&self, size: u32) {
+        let prev = self.cumulated_size.fetch_sub(size, Ordering::SeqCst);
+        let updated = prev - size;
+        let freed = self.unused_reserve(updated) - self.unused_reserve(prev);
+        RESERVED_STACK_SIZE.fetch_add(freed as usize, Ordering::SeqCst);
+    }
+
+    /// Take the cached stacklet if it has at least `size` usable bytes.
+    fn take_cached_stacklet(&self, size: usize) -> Option<*mut u8> {
+        if self.cached_size.load(Ordering::SeqCst) < size {
+            return None;
+        }
+        let stklet_ptr = self
+            .cached_stklet
+            .swap(core::ptr::null_mut(), Ordering::SeqCst);
+        (!stklet_ptr.is_null()).then_some(stklet_ptr)
+    }
+
+    /// Cache the freed stacklet. Return the stacklet cached before, or null.
+    ///
+    /// Safety: `stklet_ptr` must point to a stacklet allocated from the heap
+    /// that is no longer in use.
+    unsafe fn cache_stacklet(&self, stklet_ptr: *mut u8) -> *mut u8 {
+        self.cached_size
+            .store(allocator::usable_size(stklet_ptr), Ordering::SeqCst);
+        self.cached_stklet.swap(stklet_ptr, Ordering::SeqCst)
+    }
+}
+
+impl Drop for StackCtrlBlock {
+    fn drop(&mut self) {
+        let unused = self.unused_reserve(*self.cumulated_size.get_mut());
+        RESERVED_STACK_SIZE.fetch_sub(unused as usize, Ordering::SeqCst);
+
+        let stklet_ptr = *self.cached_stklet.get_mut();
+        if !stklet_ptr.is_null() {
+            // Safety: The cached stacklet is no longer in use.
+            unsafe { alloc::alloc::dealloc(stklet_ptr, Layout::new::<u8>()) };
+        }
+    }
 }
 
 /// Calculate the overhead size according to the stacklet layout. See the
 /// layout graph for details.
-const OVERHEAD_SIZE: usize = core::mem::size_of::<StackletMeta>()
+pub(super) const OVERHEAD_SIZE: usize = core::mem::size_of::<StackletMeta>()
     + TRAP_FRAME_PAD_SIZE
     + TRAP_FRAME_SIZE
     + R12_PRESERVE_SIZE;
@@ -184,14 +353,22 @@ pub(super) fn alloc_initial_stacklet(free_size: usize) -> (*mut u8, *mut u8) {
     // Currently, it is an unrecoverable error if the allocation fails.
     unrecoverable::die_if(|| stklet_ptr.is_null());
 
+    init_initial_stacklet(stklet_ptr, total_size)
+}
+
+/// Initialize the memory chunk of `total_size` bytes starting at `stklet_ptr`
+/// as the initial stacklet for a task. Return a pair of pointers pointing to
+/// the start and end of the memory chunk. The size must be larger than the
+/// stacklet overhead.
+pub(super) fn init_initial_stacklet(stklet_ptr: *mut u8, total_size: usize) -> (*mut u8, *mut u8) {
     // The stacklet metadata region is placed at the lower address
     // boundary of each stacklet. Initialize the metadata region to its
     // default values. Specifically, the fields `prev_stklet_bound` and
     // `prev_sp` are set to zero so that the stack unwinder can identify
     // the initial stacklet as the last one to unwind.
     //
-    // Safety: The stack memory is just allocated, so the current code
-    // has exclusive access to the memory.
+    // Safety: The stack memory is just allocated or claimed, so the current
+    // code has exclusive access to the memory.
     let meta_ptr = stklet_ptr as *mut StackletMeta;
     unsafe {
         meta_ptr.write(StackletMeta::default());
@@ -200,8 +377,8 @@ pub(super) fn alloc_initial_stacklet(free_size: usize) -> (*mut u8, *mut u8) {
     // Check for the non-overflow safety condition required below.
     unrecoverable::die_if(|| total_size > isize::MAX as usize);
 
-    // Safety: The allocation size was `total_size`, so the pointer after
-    // offset must point into the same allocation. The `total_size` does not
+    // Safety: The memory chunk size is `total_size`, so the pointer after
+    // offset must point into the same chunk. The `total_size` does not
     // overflow `isize` as checked above.
     unsafe { (stklet_ptr, stklet_ptr.add(total_size)) }
 }
@@ -321,17 +498,25 @@ pub(crate) fn more_stack(tf: &mut TrapFrame, ctxt: &mut TaskSVCCtxt, reason: Mor
                     &mut cur_meta.extend_cnt,
                 );
 
+                // A stack extension beyond the task's reservation must not
+                // take the heap reserved by other tasks, unless the cached
+                // stacklet can be reused.
+                let size = total_stacklet_size(stk_frame_size, stk_arg_size);
+                let exceed_headroom = !scb.is_within_reserve(stk_frame_size)
+                    && scb.cached_size.load(Ordering::SeqCst) < size
+                    && size > get_stack_headroom();
+
                 // Count stack usage.
-                let prev_size = scb
-                    .cumulated_size
-                    .fetch_add(stk_frame_size, Ordering::SeqCst);
-                let updated_size = prev_size + stk_frame_size;
+                let updated_size = scb.add_usage(stk_frame_size);
 
                 // Check if stack limit is reached.
-                if let Some(limit) = cur_task.get_stack_limit() {
-                    if updated_size > limit as u32 {
-                        handle_limit_exceed(tf);
-                    }
+                let exceed_limit = match cur_task.get_stack_limit() {
+                    Some(limit) => updated_size > limit as u32,
+                    None => false,
+                };
+
+                if exceed_limit || exceed_headroom {
+                    handle_limit_exceed(tf);
                 }
             })
             // Otherwise, the task does not enable dynamic stack extension. A
@@ -344,17 +529,15 @@ pub(crate) fn more_stack(tf: &mut TrapFrame, ctxt: &mut TaskSVCCtxt, reason: Mor
         return;
     }
 
-    // Total chunk size to request from malloc.
-    // The overhead includes the trap frame, its padding, and the metadata block.
-    let total_size = stk_frame_size as usize
-        + stk_arg_size as usize
-        + OVERHEAD_SIZE
-        + config::STACKLET_ADDITION_ALLOC_SIZE;
+    let total_size = total_stacklet_size(stk_frame_size, stk_arg_size);
 
     unsafe {
-        // Pointer to the new stacklet.
-        let stacklet_ptr =
-            alloc::alloc::alloc(Layout::from_size_align(total_size, 4).unwrap_or_die());
+        // Pointer to the new stacklet. Reuse the stacklet cached by the task
+        // if it is large enough.
+        let stacklet_ptr = match take_cached_stacklet(total_size) {
+            Some(stacklet_ptr) => stacklet_ptr,
+            None => alloc::alloc::alloc(Layout::from_size_align(total_size, 4).unwrap_or_die()),
+        };
 
         // Currently, it is an unrecoverable error if the allocation fails.
         unrecoverable::die_if(|| stacklet_ptr.is_null());
@@ -422,9 +605,54 @@ pub(crate) fn more_stack(tf: &mut TrapFrame, ctxt: &mut TaskSVCCtxt, reason: Mor
         new_tf.gp_regs.lr = svc::svc_less_stack as u32;
     }
 
+    current::with_cur_task(|cur_task| {
+        cur_task.with_stack_ctrl_block(StackCtrlBlock::count_stacklet_alloc)
+    });
     ACTIVE_STACKLET_COUNT.fetch_add(1, Ordering::Relaxed);
 }
 
+/// Return the total chunk size to request from malloc for a new stacklet.
+/// The overhead includes the trap frame, its padding, and the metadata block.
+fn total_stacklet_size(stk_frame_size: u32, stk_arg_size: u32) -> usize {
+    stk_frame_size as usize
+        + stk_arg_size as usize
+        + OVERHEAD_SIZE
+        + config::STACKLET_ADDITION_ALLOC_SIZE
+}
+
+/// Take the stacklet cached by the currently running task if it has at least
+/// `size` usable bytes, and count the cache hit or miss.
+fn take_cached_stacklet(size: usize) -> Option<*mut u8> {
+    if !config::ENABLE_STACKLET_CACHE {
+        return None;
+    }
+    let cached = current::with_cur_task(|cur_task| {
+        cur_task.with_stack_ctrl_block(|scb| scb.take_cached_stacklet(size))
+    })
+    .flatten();
+    match cached {
+        Some(_) => STACKLET_CACHE_HIT_COUNT.fetch_add(1, Ordering::Relaxed),
+        None => STACKLET_CACHE_MISS_COUNT.fetch_add(1, Ordering::Relaxed),
+    };
+    cached
+}
+
+/// Keep the freed stacklet in the cache of the currently running task.
+/// Return the stacklet that should be freed instead, which is the one cached
+/// before, or the given one if it cannot be cached.
+///
+/// Safety: `stklet_ptr` must point to a stacklet allocated from the heap that
+/// is no longer in use.
+unsafe fn cache_stacklet(stklet_ptr: *mut u8) -> *mut u8 {
+    if !config::ENABLE_STACKLET_CACHE {
+        return stklet_ptr;
+    }
+    current::with_cur_task(|cur_task| {
+        cur_task.with_stack_ctrl_block(|scb| scb.cache_stacklet(stklet_ptr))
+    })
+    .unwrap_or(stklet_ptr)
+}
+
 /// Free the current stacklet of the currently running task. Let the task return to the
 /// function running with the previous stacklet.
 pub(crate) fn less_stack(tf: &TrapFrame, ctxt: &mut TaskSVCCtxt) {
@@ -462,17 +690,21 @@ pub(crate) fn less_stack(tf: &TrapFrame, ctxt: &mut TaskSVCCtxt) {
                 svc_less_stack_anti_hot_split(prev_tf, scb);
 
                 // Update stack size usage.
-                scb.cumulated_size
-                    .fetch_sub(meta.count_size, Ordering::SeqCst);
+                scb.sub_usage(meta.count_size);
+                scb.count_stacklet_free();
             });
         });
 
         // The stacklet starts with the metadata.
         let stacklet_ptr = meta_ptr as *mut u8;
 
-        // Free the current stacklet.
+        // Free the current stacklet, or keep it in the task's cache and free
+        // the stacklet cached before.
         // Layout is not used in the current dealloc implementation.
-        alloc::alloc::dealloc(stacklet_ptr, Layout::new::<u8>());
+        let to_free_ptr = cache_stacklet(stacklet_ptr);
+        if !to_free_ptr.is_null() {
+            alloc::alloc::dealloc(to_free_ptr, Layout::new::<u8>());
+        }
     }
 
     ACTIVE_STACKLET_COUNT.fetch_sub(1, Ordering::Relaxed);
@@ -540,6 +772,9 @@ pub fn unwind_land(tf: &TrapFrame, ctxt: &mut TaskSVCCtxt) {
     unsafe {
         alloc::alloc::dealloc(unwinder_stklet_ptr, Layout::new::<u8>());
     }
+    current::with_cur_task(|cur_task| {
+        cur_task.with_stack_ctrl_block(StackCtrlBlock::count_stacklet_free)
+    });
 }
 
 fn svc_less_stack_anti_hot_split(tf: &TrapFrame, scb: &StackCtrlBlock) {
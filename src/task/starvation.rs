@@ -0,0 +1,202 @@
+//! Detection of starved tasks, enabled by the `starvation_monitor` feature.
+//!
+//! A task is considered starved when it has been waiting for the CPU for
+//! longer than [`STARVATION_THRESHOLD_MS`](config::STARVATION_THRESHOLD_MS)
+//! while tasks of lower priority ran in the meantime. A task waits for the
+//! CPU when it is ready, or when it is blocked on a
+//! [`Mutex`](crate::sync::Mutex) or an [`RwLock`](crate::sync::RwLock)
+//! held by another task. Tasks blocked on anything else, e.g., sleeping or
+//! waiting on a mailbox, are not waiting for the CPU.
+//!
+//! Priority inheritance should keep a lock owner running at the priority of
+//! the tasks blocked on the lock, so a starved task points at a problem such
+//! as a lock owner blocked on a semaphore or suspended, a chain of lock
+//! owners too long to inherit through in time, or a cooperative task or a
+//! preemption lock keeping the CPU.
+//!
+//! A monitor task at
+//! [`STARVATION_MONITOR_PRIORITY`](config::STARVATION_MONITOR_PRIORITY)
+//! checks all tasks every
+//! [`STARVATION_CHECK_PERIOD_MS`](config::STARVATION_CHECK_PERIOD_MS), and
+//! logs a warning for each starved task once per wait. The warning lists the
+//! chain of lock owners the task is blocked on, ending with the state of the
+//! last task in the chain, e.g.:
+//!
+//! ```text
+//! starvation: task 5 waited 230 ms while lower priority tasks ran; chain: 5 -> 7 -> 3 (blocked)
+//! ```
+//!
+//! The monitor task itself takes one task slot.
+
+use super::{registry, Task, TaskId, TaskState};
+use crate::{
+    config,
+    debug::log::log_warn,
+    time::{self, tick_cmp},
+};
+use core::{
+    cmp::Ordering as CmpOrdering,
+    fmt,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+/// The maximum number of tasks listed in a chain of lock owners.
+const MAX_CHAIN_LENGTH: usize = 8;
+
+const NEVER_RAN: AtomicU32 = AtomicU32::new(0);
+
+/// The tick when a task of each priority level was last switched out of the
+/// CPU, excluding the idle task.
+static LEVEL_LAST_RAN: [AtomicU32; config::TASK_PRIORITY_LEVELS as usize] =
+    [NEVER_RAN; config::TASK_PRIORITY_LEVELS as usize];
+
+/// Spawn the monitor task. Called once when booting.
+pub(crate) fn spawn() -> Result<(), super::TaskBuildError> {
+    super::build()
+        .set_id(config::STARVATION_MONITOR_TASK_ID)
+        .set_name("starvation")
+        .set_priority(config::STARVATION_MONITOR_PRIORITY)
+        .set_entry(monitor)
+        .spawn()
+}
+
+/// Record that the task ran until now. Called in PendSV context when the
+/// task is switched out of the CPU.
+pub(crate) fn record_switch_out(task: &Task) {
+    if task.is_idle() {
+        return;
+    }
+
+    let level = task.get_priority().effective_priority();
+    LEVEL_LAST_RAN[level as usize].store(time::get_tick(), Ordering::SeqCst);
+
+    // A preempted task goes back to the ready queue and waits from now on.
+    if task.get_state() == TaskState::Running && !task.is_suspended() {
+        task.start_waiting();
+    }
+}
+
+/// The body of the monitor task.
+fn monitor() {
+    loop {
+        // Waking up preempts the running task, which records that its
+        // priority level ran.
+        let _ = time::sleep_ms(config::STARVATION_CHECK_PERIOD_MS);
+
+        let now = time::get_tick();
+        for task in registry::live_tasks() {
+            if is_starved(&task, now) && task.mark_starvation_reported() {
+                log_warn!(
+                    "starvation: task {} waited {} ms while lower priority tasks ran; chain: {}",
+                    task.get_uid(),
+                    now.wrapping_sub(task.get_waiting_since()),
+                    Chain::of(&task)
+                );
+            }
+        }
+    }
+}
+
+/// Return if the task has been waiting for the CPU for longer than the
+/// threshold, while a task of lower priority was switched out since the task
+/// started waiting.
+fn is_starved(task: &Task, now: u32) -> bool {
+    let waiting = match task.get_state() {
+        TaskState::Ready => !task.is_suspended(),
+        TaskState::Blocked => task.get_waits_for().is_some(),
+        _ => false,
+    };
+    if !waiting {
+        return false;
+    }
+
+    // The task may have started waiting after `now` was read, e.g., woken up
+    // by an ISR, in which case the difference is negative.
+    let since = task.get_waiting_since();
+    if (now.wrapping_sub(since) as i32) <= config::STARVATION_THRESHOLD_MS as i32 {
+        return false;
+    }
+
+    let level = task.get_priority().effective_priority();
+    (level + 1..config::TASK_PRIORITY_LEVELS)
+        .filter(|&lower| lower != config::IDLE_TASK_PRIORITY)
+        .any(|lower| {
+            let last_ran = LEVEL_LAST_RAN[lower as usize].load(Ordering::SeqCst);
+            tick_cmp(last_ran, since) == CmpOrdering::Greater
+        })
+}
+
+/// The chain of lock owners a task is blocked on, starting with the task
+/// itself.
+struct Chain {
+    ids: [TaskId; MAX_CHAIN_LENGTH],
+    len: usize,
+    end: ChainEnd,
+}
+
+/// How the chain of lock owners ends.
+enum ChainEnd {
+    /// The last task is not blocked on a lock. It is in the given state.
+    State(TaskState),
+    /// The last task is blocked on a lock owned by a task that has
+    /// terminated.
+    Terminated,
+    /// The last task is blocked on a lock owned by a task earlier in the
+    /// chain.
+    Deadlock,
+    /// The chain is longer than [`MAX_CHAIN_LENGTH`].
+    Truncated,
+}
+
+impl Chain {
+    fn of(task: &Task) -> Self {
+        let mut chain = Self {
+            ids: [task.get_uid(); MAX_CHAIN_LENGTH],
+            len: 1,
+            end: ChainEnd::State(task.get_state()),
+        };
+
+        let mut owner_id = task.get_waits_for();
+        while let Some(id) = owner_id {
+            if chain.ids[..chain.len].contains(&id) {
+                chain.end = ChainEnd::Deadlock;
+                break;
+            }
+            if chain.len == MAX_CHAIN_LENGTH {
+                chain.end = ChainEnd::Truncated;
+                break;
+            }
+            let Some(owner) = registry::find_task(id) else {
+                chain.end = ChainEnd::Terminated;
+                break;
+            };
+            chain.ids[chain.len] = id;
+            chain.len += 1;
+            chain.end = ChainEnd::State(owner.get_state());
+            owner_id = owner.get_waits_for();
+        }
+
+        chain
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, id) in self.ids[..self.len].iter().enumerate() {
+            if i > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{}", id)?;
+        }
+        match self.end {
+            ChainEnd::State(TaskState::Ready) => write!(f, " (ready)"),
+            ChainEnd::State(TaskState::Running) => write!(f, " (running)"),
+            ChainEnd::State(TaskState::Blocked) => write!(f, " (blocked)"),
+            ChainEnd::State(TaskState::Suspended) => write!(f, " (suspended)"),
+            ChainEnd::State(_) => Ok(()),
+            ChainEnd::Terminated => write!(f, " -> terminated"),
+            ChainEnd::Deadlock => write!(f, " -> deadlock"),
+            ChainEnd::Truncated => write!(f, " -> ..."),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Tasks in static memory.
+//!
+//! A task spawned from [`build_static`](super::build_static) keeps its task
+//! struct, entry closure and parker in the memory of a [`StaticTask`]
+//! declared as a `static`, and runs on a stack in the same memory. Spawning
+//! the task allocates nothing from the heap, so spawning all tasks from
+//! static memory during initialization leaves the heap to the allocations
+//! made by the tasks themselves.
+//!
+//! The task struct is still referred to by reference-counted pointers from
+//! the scheduler and the wait queues. The memory becomes available to a new
+//! task only after the last of them is dropped, i.e., after the task has
+//! terminated and its task struct is freed. Values the task creates later,
+//! e.g., the closures registered with [`at_exit`](super::at_exit), are
+//! allocated from the heap.
+//!
+//! # Example
+//! ```rust
+//! static SENSOR_TASK: StaticTask<2048> = StaticTask::new();
+//!
+//! task::build_static(&SENSOR_TASK)
+//!     .set_entry(sensor_loop)
+//!     .spawn()
+//!     .unwrap();
+//! ```
+
+use super::segmented_stack;
+use crate::allocator::{Arena, ArenaState};
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Memory for one task, with a stack of `N` bytes including the overhead of
+/// the stacklet metadata, and `M` bytes for the task struct, the entry
+/// closure and the parker. `N` must be a multiple of 8.
+///
+/// The default `M` fits the task struct with an entry closure capturing a
+/// few words. Spawning the task fails with
+/// [`TaskBuildError::StaticTaskTooSmall`](super::TaskBuildError::StaticTaskTooSmall)
+/// if they do not fit.
+///
+/// The memory can be used by one task at a time. It becomes available again
+/// after the task using it has terminated and its task struct is freed.
+#[repr(C, align(8))]
+pub struct StaticTask<const N: usize, const M: usize = 512> {
+    stack: UnsafeCell<MaybeUninit<[u8; N]>>,
+    tcb: UnsafeCell<MaybeUninit<[u8; M]>>,
+    stack_in_use: AtomicBool,
+    /// The bookkeeping of the allocations in `tcb`.
+    tcb_state: ArenaState,
+}
+
+/// The memory is only accessed by the task that claimed it.
+unsafe impl<const N: usize, const M: usize> Sync for StaticTask<N, M> {}
+
+impl<const N: usize, const M: usize> StaticTask<N, M> {
+    /// Create the task memory. Fail to compile if `N` is not a multiple of 8
+    /// or cannot hold the stacklet overhead.
+    pub const fn new() -> Self {
+        assert!(N % 8 == 0, "the stack size must be a multiple of 8");
+        assert!(
+            N > segmented_stack::OVERHEAD_SIZE,
+            "the stack is too small to hold the stacklet overhead"
+        );
+        Self {
+            stack: UnsafeCell::new(MaybeUninit::uninit()),
+            tcb: UnsafeCell::new(MaybeUninit::uninit()),
+            stack_in_use: AtomicBool::new(false),
+            tcb_state: ArenaState::new(),
+        }
+    }
+
+    /// Return the type-erased reference to the stack.
+    pub(super) fn stack_slot(&'static self) -> StackSlot {
+        StackSlot {
+            begin: self.stack.get().cast(),
+            size: N,
+            in_use: &self.stack_in_use,
+        }
+    }
+
+    /// Return the arena serving the allocations of the task struct.
+    pub(super) fn tcb_arena(&'static self) -> Arena {
+        // Safety: The memory is a `static` and only accessed through the
+        // arena.
+        unsafe { Arena::new(self.tcb.get().cast(), M, &self.tcb_state) }
+    }
+}
+
+/// A reference to the stack of a [`StaticTask`] with its size erased.
+#[derive(Clone, Copy)]
+pub(crate) struct StackSlot {
+    begin: *mut u8,
+    size: usize,
+    in_use: &'static AtomicBool,
+}
+
+/// The slot only refers to a `static`, which is `Sync`.
+unsafe impl Send for StackSlot {}
+unsafe impl Sync for StackSlot {}
+
+impl StackSlot {
+    /// Claim the stack for a new task. Return the pointer to its beginning,
+    /// or `None` if another task is using it.
+    pub(super) fn claim(&self) -> Option<*mut u8> {
+        self.in_use
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| self.begin)
+    }
+
+    /// Make the stack available again after the task using it is freed.
+    pub(super) fn release(&self) {
+        self.in_use.store(false, Ordering::SeqCst);
+    }
+
+    /// The size of the stack including the stacklet overhead.
+    pub(super) fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The size of the stack available to the task.
+    pub(super) fn free_size(&self) -> usize {
+        self.size - segmented_stack::OVERHEAD_SIZE
+    }
+}
@@ -0,0 +1,266 @@
+//! Supervisors restarting groups of tasks.
+//!
+//! A [`Supervisor`] spawns a group of member tasks and restarts them when
+//! they panic, according to its [`RestartStrategy`]. Unlike
+//! [`spawn_restartable`](super::TaskBuilder::spawn_restartable), which
+//! restarts a single task, a supervisor can restart all tasks of a pipeline
+//! together when one of them fails, so that no task keeps running with a
+//! peer that lost its state.
+//!
+//! The supervisor runs in its own task, see [`Supervisor::into_entry`]. A
+//! member restarted by the supervisor is a new task with a new [`TaskId`].
+//! The supervisor finishes when all members have returned.
+//!
+//! If the members are restarted more than [`max_restarts`] times, the
+//! supervisor terminates all members and panics. Supervisors can form a
+//! tree: when the supervisor task is itself a member of another supervisor,
+//! the parent then restarts it along with a fresh group of members.
+//!
+//! [`max_restarts`]: Supervisor::max_restarts
+//!
+//! # Example
+//! ```rust
+//! let pipeline = task::Supervisor::new(RestartStrategy::OneForAll)
+//!     .max_restarts(5)
+//!     .add(task::build().set_name("rx").set_entry(rx_loop))
+//!     .add(task::build().set_name("decode").set_entry(decode_loop));
+//!
+//! task::build()
+//!     .set_name("pipeline")
+//!     .set_entry(pipeline.into_entry())
+//!     .spawn()
+//!     .unwrap();
+//! ```
+
+use super::{TaskBuildError, TaskBuilder, TaskHandle, TaskId};
+use crate::{
+    sync::{Mailbox, SpinSchedSafe},
+    unrecoverable,
+};
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec, vec::Vec};
+
+/// How a [`Supervisor`] reacts when a member task panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartStrategy {
+    /// Restart only the panicked member.
+    OneForOne,
+    /// Terminate all other running members, wait until they are unwound,
+    /// and then restart them along with the panicked member. Members that
+    /// have returned are not restarted.
+    OneForAll,
+}
+
+/// Spawn a member task, which reports its exit through the given
+/// [`MemberExit`].
+type Spawner = Box<dyn Fn(MemberExit) -> Result<TaskId, TaskBuildError> + Send + Sync>;
+
+/// A group of tasks restarted together. See the [module-level
+/// documentation](self).
+pub struct Supervisor {
+    strategy: RestartStrategy,
+    max_restarts: Option<u32>,
+    members: Vec<Spawner>,
+}
+
+/// The exit of a member task reported to the supervisor.
+struct Exit {
+    idx: usize,
+    id: TaskId,
+    panicked: bool,
+}
+
+/// The exits reported by the members of a running supervisor.
+struct Exits {
+    queue: SpinSchedSafe<VecDeque<Exit>>,
+    /// Notified when an exit is reported.
+    reported: Mailbox,
+}
+
+/// Held by a member task while it runs. Dropping it, including when the task
+/// panics and is unwound, reports the exit to the supervisor.
+pub(super) struct MemberExit {
+    exits: Arc<Exits>,
+    idx: usize,
+    returned: bool,
+}
+
+impl MemberExit {
+    /// Mark the entry closure of the member as returned.
+    pub(super) fn returned(mut self) {
+        self.returned = true;
+        // The supervisor is notified when `self` is dropped.
+    }
+}
+
+impl Drop for MemberExit {
+    fn drop(&mut self) {
+        self.exits.queue.lock().push_back(Exit {
+            idx: self.idx,
+            id: super::current_id(),
+            panicked: !self.returned,
+        });
+        self.exits.reported.notify_allow_isr();
+    }
+}
+
+impl Supervisor {
+    /// Create a supervisor without members, restarting them with the given
+    /// strategy.
+    pub fn new(strategy: RestartStrategy) -> Self {
+        Self {
+            strategy,
+            max_restarts: None,
+            members: Vec::new(),
+        }
+    }
+
+    /// Limit the number of restarts, counting each time a member panics.
+    /// Without a limit, the members are restarted indefinitely.
+    pub fn max_restarts(mut self, max: u32) -> Self {
+        self.max_restarts = Some(max);
+        self
+    }
+
+    /// Add a member task built by `builder`. The member is spawned as a
+    /// non-restartable task when the supervisor starts, and spawned again
+    /// from a copy of the builder on each restart, so the restart settings
+    /// of the builder are ignored.
+    pub fn add<F>(mut self, builder: TaskBuilder<F>) -> Self
+    where
+        F: FnOnce() + Send + Sync + Clone + 'static,
+    {
+        self.members
+            .push(Box::new(move |exit| builder.clone().spawn_supervised(exit)));
+        self
+    }
+
+    /// Return the entry closure of the supervisor task, which runs
+    /// [`run`](Self::run). The closure can be cloned, so that the supervisor
+    /// task can itself be restarted, e.g., as a member of another
+    /// supervisor.
+    pub fn into_entry(self) -> impl FnOnce() + Send + Sync + Clone + 'static {
+        let supervisor = Arc::new(self);
+        move || supervisor.run()
+    }
+
+    /// Spawn the members and supervise them in the calling task until all of
+    /// them have returned. Panic if a member cannot be spawned or if the
+    /// members are restarted more than the maximum number of times, after
+    /// terminating all running members.
+    ///
+    /// If the calling task is terminated or unwound, the running members are
+    /// terminated as well.
+    ///
+    /// NOTE: *must not* call this method in ISR context.
+    pub fn run(&self) {
+        unrecoverable::die_if_in_isr();
+
+        let mut group = Group {
+            supervisor: self,
+            exits: Arc::new(Exits {
+                queue: SpinSchedSafe::new(VecDeque::new()),
+                reported: Mailbox::new(),
+            }),
+            running: vec![None; self.members.len()],
+        };
+
+        for idx in 0..self.members.len() {
+            group.spawn(idx);
+        }
+
+        let mut restarts = 0;
+        while group.running.iter().any(Option::is_some) {
+            let exit = group.next_exit();
+            if group.running[exit.idx] != Some(exit.id) {
+                continue;
+            }
+            group.running[exit.idx] = None;
+            if !exit.panicked {
+                continue;
+            }
+
+            if self.max_restarts.is_some_and(|max| restarts >= max) {
+                group.tear_down();
+                panic!("supervised tasks restarted too many times");
+            }
+            restarts += 1;
+
+            match self.strategy {
+                RestartStrategy::OneForOne => group.spawn(exit.idx),
+                RestartStrategy::OneForAll => {
+                    let mut to_restart = group.tear_down();
+                    to_restart.push(exit.idx);
+                    to_restart.sort_unstable();
+                    for idx in to_restart {
+                        group.spawn(idx);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The members of a running supervisor. Dropping it, e.g., when the
+/// supervisor task is unwound, terminates the running members.
+struct Group<'a> {
+    supervisor: &'a Supervisor,
+    exits: Arc<Exits>,
+    /// The ID of the running task of each member, or `None` if the member is
+    /// not running.
+    running: Vec<Option<TaskId>>,
+}
+
+impl Group<'_> {
+    /// Spawn the member. Panic after terminating all running members if it
+    /// cannot be spawned.
+    fn spawn(&mut self, idx: usize) {
+        let exit = MemberExit {
+            exits: self.exits.clone(),
+            idx,
+            returned: false,
+        };
+        match (self.supervisor.members[idx])(exit) {
+            Ok(id) => self.running[idx] = Some(id),
+            Err(_) => {
+                self.tear_down();
+                panic!("failed to spawn supervised task");
+            }
+        }
+    }
+
+    /// Block until a member reports its exit.
+    fn next_exit(&self) -> Exit {
+        loop {
+            if let Some(exit) = self.exits.queue.lock().pop_front() {
+                return exit;
+            }
+            self.exits.reported.wait();
+        }
+    }
+
+    /// Terminate all running members and wait until they exit. Return the
+    /// indices of the terminated members.
+    fn tear_down(&mut self) -> Vec<usize> {
+        let torn: Vec<usize> = (0..self.running.len())
+            .filter(|&idx| self.running[idx].is_some())
+            .collect();
+        for id in self.running.iter().flatten() {
+            let _ = TaskHandle::from_id(*id).terminate();
+        }
+        while self.running.iter().any(Option::is_some) {
+            let exit = self.next_exit();
+            if self.running[exit.idx] == Some(exit.id) {
+                self.running[exit.idx] = None;
+            }
+        }
+        torn
+    }
+}
+
+impl Drop for Group<'_> {
+    fn drop(&mut self) {
+        for id in self.running.iter().flatten() {
+            let _ = TaskHandle::from_id(*id).terminate();
+        }
+    }
+}
@@ -46,15 +46,16 @@ impl TaskListInterfaces for LinkedList<TaskListAdapter> {
         cursor_mut.insert_before(new_task);
     }
 
-    /// Pop out the task with the highest priority in the linked list. If
-    /// there are multiple tasks having the highest priority, the one in the
-    /// front will be popped out. Return `None` if the list is empty.
+    /// Pop out the task with the highest priority in the linked list. Among
+    /// EDF tasks of the same priority, the one with the earliest deadline is
+    /// chosen. If there are multiple such tasks, the one in the front will be
+    /// popped out. Return `None` if the list is empty.
     fn pop_highest_priority(&mut self) -> Option<Arc<Task>> {
         let mut cursor = self.front();
 
-        // Get the priority of the first task in the list.
-        let mut max_prio = match cursor.get() {
-            Some(task) => task.get_priority(),
+        // Start with the first task in the list.
+        let mut max_task = match cursor.get() {
+            Some(task) => task,
             None => return None,
         };
         let mut max_pos = 0usize;
@@ -65,16 +66,12 @@ impl TaskListInterfaces for LinkedList<TaskListAdapter> {
         // Record the position of the cursor.
         let mut cur_pos = 1usize;
 
-        // Scan through the linked list. Whenever we see a task with higher
-        // priority than all scanned tasks, update the position.
+        // Scan through the linked list. Whenever we see a task that should
+        // run before all scanned tasks, update the position.
         while let Some(task) = cursor.get() {
-            let cur_prio = task.get_priority();
-
-            // Update the position if we see higher priority.
-            // Smaller numerical value represents higher priority.
-            if cur_prio < max_prio {
+            if task.precedes(max_task) {
                 max_pos = cur_pos;
-                max_prio = cur_prio;
+                max_task = task;
             }
 
             cursor.move_next();
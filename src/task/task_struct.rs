@@ -1,24 +1,29 @@
 use super::{
     priority::TaskPriority,
     segmented_stack::{self, StackCtrlBlock},
-    trampoline, TaskBuildError,
+    static_task::StackSlot,
+    trampoline, TaskBuildError, TaskId,
 };
 use crate::{
     config,
     interrupt::{svc, trap_frame::TrapFrame},
-    schedule::scheduler::TaskQuota,
-    sync::{AtomicCell, Spin},
+    schedule::scheduler::{Scheduler, TaskQuota},
+    sync::{AtomicCell, Parker, Spin},
+    time,
     unrecoverable::{self, Lethal},
 };
-use alloc::{boxed::Box, sync::Arc};
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
 use core::{
     alloc::Layout,
+    cmp::Ordering as CmpOrdering,
     num::NonZeroUsize,
     sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU8, Ordering},
 };
 use intrusive_collections::{intrusive_adapter, LinkedListAtomicLink};
 use static_assertions::const_assert;
 
+#[cfg(feature = "unwind")]
+use super::PanicRecord;
 #[cfg(feature = "unwind")]
 use alloc::sync::Weak;
 #[cfg(feature = "unwind")]
@@ -37,6 +42,8 @@ pub(crate) enum TaskState {
     Ready,
     /// The task is running on the CPU.
     Running,
+    /// The task is suspended and will not run until it is resumed.
+    Suspended,
     /// The task is under destruction.
     Destructing,
 }
@@ -55,6 +62,7 @@ struct CalleeSavedGPRegs {
     r11: u32,
 }
 
+#[cfg(feature = "fp_context")]
 #[repr(C)]
 #[derive(Default)]
 /// Callee-saved floating point registers on Cortex-M.
@@ -111,6 +119,7 @@ pub(crate) struct TaskCtxt {
     /// Preserved callee-saved general purpose registers.
     gp_regs: CalleeSavedGPRegs,
     /// Preserved callee-saved floating point registers.
+    #[cfg(feature = "fp_context")]
     fp_regs: CalleeSavedFPRegs,
 }
 
@@ -128,9 +137,48 @@ pub(crate) enum StackConfig {
         initial: Option<NonZeroUsize>,
         /// The maximum size of all stacklets, excluding stacklet overhead.
         limit: Option<NonZeroUsize>,
+        /// The size reserved for stack extension, excluding stacklet
+        /// overhead.
+        reserve: usize,
+    },
+    /// Use the memory of a [`StaticTask`](super::StaticTask) as a single
+    /// contiguous chunk.
+    Provided {
+        /// The stack memory.
+        stack: StackSlot,
     },
 }
 
+/// Representing how a restartable task is restarted after panicking.
+#[cfg(feature = "unwind")]
+#[derive(Clone, Copy)]
+pub(crate) struct RestartPolicy {
+    /// The maximum number of restarts, or `None` if the task is restarted
+    /// without limit.
+    pub(crate) max_restarts: Option<u32>,
+    /// Called when the task panics after being restarted `max_restarts`
+    /// times.
+    pub(crate) on_exhausted: Option<fn(TaskId)>,
+    /// The delay before the task is restarted in milliseconds.
+    pub(crate) delay_ms: u32,
+    /// If set, the delay doubles on each further restart, up to the given
+    /// milliseconds.
+    pub(crate) backoff_max_ms: Option<u32>,
+}
+
+#[cfg(feature = "unwind")]
+impl RestartPolicy {
+    /// Restart without limit and without delay.
+    pub(crate) const fn new() -> Self {
+        Self {
+            max_restarts: None,
+            on_exhausted: None,
+            delay_ms: 0,
+            backoff_max_ms: None,
+        }
+    }
+}
+
 /// The struct representing a task.
 pub(crate) struct Task {
     /// When dropped it will decrement the number of existing tasks by 1.
@@ -156,10 +204,26 @@ pub(crate) struct Task {
     /// A numerical task ID that does not have functional purpose. It is
     /// only for diagnostic purpose.
     id: AtomicU8,
+    /// The unique ID assigned when the task struct is created. See
+    /// [`TaskId`].
+    uid: TaskId,
+    /// The name of the task. It is only for diagnostic purpose.
+    name: Option<&'static str>,
     /// Whether the task is the idle task.
     is_idle: bool,
     /// See [`TaskState`].
     state: AtomicCell<TaskState>,
+    /// Set when the task is suspended. A suspended task is kept off the
+    /// ready queue until it is resumed, including when it is woken up from
+    /// blocking.
+    suspended: AtomicBool,
+    /// Set when the task is cooperative. When preempted by a higher priority
+    /// task, a cooperative task resumes before the other ready tasks of its
+    /// priority.
+    cooperative: bool,
+    /// The hooks registered with [`at_exit`](super::at_exit), run in reverse
+    /// order when the task returns or is unwound.
+    exit_hooks: Spin<Vec<Box<dyn FnOnce() + Send>>>,
 
     /*** Fields for unwinding. ***/
     /// Set only when the task is unwinding.
@@ -169,6 +233,23 @@ pub(crate) struct Task {
     /// context.
     #[cfg(feature = "unwind")]
     has_restarted: AtomicBool,
+    /// Set when another task requests to terminate the task. The task is
+    /// unwound at its next preemption point and is not restarted.
+    #[cfg(feature = "unwind")]
+    terminating: AtomicBool,
+    /// Set when the task misses its deadline registered with the
+    /// [watchdog](super::watchdog). The task is unwound at its next
+    /// preemption point and may be restarted.
+    #[cfg(feature = "unwind")]
+    watchdog_expired: AtomicBool,
+    /// The intrinsic priority of the task before it was reduced for
+    /// unwinding. Restored when the task restarts reusing the task struct.
+    #[cfg(feature = "unwind")]
+    priority_before_unwind: AtomicU8,
+    /// The panic recorded for the panic observer. See
+    /// [`set_panic_observer`](super::set_panic_observer).
+    #[cfg(feature = "unwind")]
+    panic_record: Spin<Option<Box<PanicRecord>>>,
 
     /*** Fields present only for restartable tasks. ***/
     /// An `Arc` pointing to the bundled struct containing the task entry
@@ -189,6 +270,13 @@ pub(crate) struct Task {
     /// being created.
     #[cfg(feature = "unwind")]
     restart_entry_trampoline: Option<extern "C" fn(*const u8)>,
+    /// The number of times the task has been restarted after panicking,
+    /// counted across all its instances.
+    #[cfg(feature = "unwind")]
+    restart_count: AtomicU32,
+    /// See [`RestartPolicy`].
+    #[cfg(feature = "unwind")]
+    restart_policy: RestartPolicy,
 
     /*** Fields for segmented stack control. ***/
     /// The recorded information used to control segmented stack growth and
@@ -206,6 +294,46 @@ pub(crate) struct Task {
     /// The tick number when a sleeping task should be woken up. This field is
     /// meaningful only the task is sleeping.
     wake_at_tick: AtomicU32,
+    /// The relative deadline in ticks of an EDF task, or `None` if the task
+    /// is scheduled only by its priority.
+    relative_deadline: Option<u32>,
+    /// The absolute deadline of the current job of an EDF task, i.e., the
+    /// tick when the job was released plus the relative deadline.
+    abs_deadline: AtomicU32,
+    /// The priority level of the scheduler's ready list the task is linked
+    /// in, or [`NOT_IN_READY_LIST`] if it is not in one.
+    ready_level: AtomicU8,
+    /// The sequence number the task got when it started waiting in a wait
+    /// queue, ordering it against the other tasks waiting in the same queue.
+    /// This field is meaningful only when the task is waiting in a queue.
+    wait_seq: AtomicU32,
+
+    /*** Fields for starvation monitoring. ***/
+    /// The tick when the task last started waiting for the CPU, i.e., when it
+    /// became ready, was preempted, or blocked on a lock.
+    #[cfg(feature = "starvation_monitor")]
+    waiting_since: AtomicU32,
+    /// The raw ID of the task owning the lock the task is blocked on, or
+    /// [`NOT_WAITING_FOR_TASK`] if it is not blocked on a lock.
+    #[cfg(feature = "starvation_monitor")]
+    waits_for: AtomicU32,
+    /// Whether the monitor has reported the task for its current wait.
+    #[cfg(feature = "starvation_monitor")]
+    starvation_reported: AtomicBool,
+
+    /*** Fields for checkpointing. ***/
+    /// The number of checkpoints the task has passed.
+    checkpoint_count: AtomicU32,
+    /// The tick number when the task last passed a checkpoint.
+    checkpoint_tick: AtomicU32,
+    /// The tick number when the task last yielded at a checkpoint.
+    checkpoint_yield_tick: AtomicU32,
+
+    /*** Fields for parking. ***/
+    /// The wake-up token and the notification word of the task. See
+    /// [`park_current_task`](crate::sync::park_current_task) and
+    /// [`wait_notification`](crate::sync::wait_notification).
+    parker: Arc<Parker>,
 
     /*** Fields for task linked list. ***/
     /// The link field for this struct to form an intrusive linked list.
@@ -220,6 +348,13 @@ pub(crate) struct Task {
 const_assert!(AtomicCell::<TaskState>::is_lock_free());
 const_assert!(AtomicCell::<TaskPriority>::is_lock_free());
 
+/// The value of [`Task::ready_level`] when the task is not in a ready list.
+const NOT_IN_READY_LIST: u8 = u8::MAX;
+
+/// The value of [`Task::waits_for`] when the task is not blocked on a lock.
+#[cfg(feature = "starvation_monitor")]
+const NOT_WAITING_FOR_TASK: u32 = u32::MAX;
+
 /// Task struct builder functions.
 impl Task {
     /// Build a new task struct. Return `Ok(())` if successful, otherwise
@@ -272,6 +407,12 @@ impl Task {
     where
         F: FnOnce() + Send + Sync + Clone + 'static,
     {
+        // The restarted instance would need the static stack while the
+        // panicked instance is still unwinding on it.
+        if let StackConfig::Provided { .. } = stack_config {
+            return Err(TaskBuildError::StaticTaskUnavailable);
+        }
+
         let mut task = Self::new(quota, false);
         task.initialize_restartable(id, entry_closure, stack_config, priority)?;
         Ok(task)
@@ -296,10 +437,12 @@ impl Task {
         unrecoverable::die_if(|| created);
 
         let mut idle_task = Self::new(quota, true);
+        idle_task.name = Some("idle");
 
         let stack_config = StackConfig::Dynamic {
             initial: None,
             limit: None,
+            reserve: 0,
         };
 
         // Create the idle task. The closure passed in `.initialize()` is
@@ -328,24 +471,42 @@ impl Task {
             _quota: quota,
             ctxt: Spin::new(TaskCtxt::default()),
             id: AtomicU8::new(0),
+            uid: TaskId::next(),
+            name: None,
             is_idle,
             state: AtomicCell::new(TaskState::Initializing),
+            suspended: AtomicBool::new(false),
+            cooperative: false,
+            exit_hooks: Spin::new(Vec::new()),
             initial_stklet: AtomicPtr::new(core::ptr::null_mut()),
             #[cfg(feature = "unwind")]
             is_unwinding: AtomicBool::new(false),
             #[cfg(feature = "unwind")]
             has_restarted: AtomicBool::new(false),
             #[cfg(feature = "unwind")]
+            terminating: AtomicBool::new(false),
+            #[cfg(feature = "unwind")]
+            watchdog_expired: AtomicBool::new(false),
+            #[cfg(feature = "unwind")]
+            priority_before_unwind: AtomicU8::new(0),
+            #[cfg(feature = "unwind")]
+            panic_record: Spin::new(None),
+            #[cfg(feature = "unwind")]
             entry_closure: None,
             #[cfg(feature = "unwind")]
             downcast_func: None,
             #[cfg(feature = "unwind")]
             restart_entry_trampoline: None,
             #[cfg(feature = "unwind")]
+            restart_count: AtomicU32::new(0),
+            #[cfg(feature = "unwind")]
+            restart_policy: RestartPolicy::new(),
+            #[cfg(feature = "unwind")]
             restarted_from: None,
             stack_config: StackConfig::Dynamic {
                 initial: None,
                 limit: None,
+                reserve: 0,
             },
             scb: None,
             priority: AtomicCell::new(TaskPriority::new_intrinsic(
@@ -353,6 +514,20 @@ impl Task {
             )),
             linked_list_link: LinkedListAtomicLink::new(),
             wake_at_tick: AtomicU32::new(u32::MAX),
+            relative_deadline: None,
+            abs_deadline: AtomicU32::new(0),
+            ready_level: AtomicU8::new(NOT_IN_READY_LIST),
+            wait_seq: AtomicU32::new(0),
+            #[cfg(feature = "starvation_monitor")]
+            waiting_since: AtomicU32::new(0),
+            #[cfg(feature = "starvation_monitor")]
+            waits_for: AtomicU32::new(NOT_WAITING_FOR_TASK),
+            #[cfg(feature = "starvation_monitor")]
+            starvation_reported: AtomicBool::new(false),
+            checkpoint_count: AtomicU32::new(0),
+            checkpoint_tick: AtomicU32::new(0),
+            checkpoint_yield_tick: AtomicU32::new(0),
+            parker: Arc::new(Parker::new()),
         }
     }
 
@@ -388,18 +563,30 @@ impl Task {
             }
             // For dynamic stack, just allocate the initial stacklet. Also
             // create a stack control block.
-            StackConfig::Dynamic { initial, .. } => {
-                self.scb.replace(Box::new(StackCtrlBlock::default()));
+            StackConfig::Dynamic {
+                initial, reserve, ..
+            } => {
+                self.scb.replace(Box::new(StackCtrlBlock::new(reserve)));
                 stack_alloc_size = initial.map(|size| size.get()).unwrap_or(0);
             }
+            // A provided stack is not allocated.
+            StackConfig::Provided { .. } => {
+                stack_alloc_size = 0;
+            }
         }
 
-        // Allocate the initial stacklet. `stklet_begin` points to the
-        // beginning of the allocated memory chunk, and can be used to call
-        // `alloc::alloc::dealloc()` to free the memory. `stklet_end` points to
-        // the ending of the memory chunk. The allocated memory chunk is *not*
-        // zero-initialized.
-        let (stklet_begin, stklet_end) = segmented_stack::alloc_initial_stacklet(stack_alloc_size);
+        // Allocate the initial stacklet, or claim the provided stack.
+        // `stklet_begin` points to the beginning of the memory chunk, and can
+        // be used to call `alloc::alloc::dealloc()` to free allocated memory.
+        // `stklet_end` points to the ending of the memory chunk. The memory
+        // chunk is *not* zero-initialized.
+        let (stklet_begin, stklet_end) = match &stack_config {
+            StackConfig::Provided { stack } => {
+                let begin = stack.claim().ok_or(TaskBuildError::StaticTaskUnavailable)?;
+                segmented_stack::init_initial_stacklet(begin, stack.size())
+            }
+            _ => segmented_stack::alloc_initial_stacklet(stack_alloc_size),
+        };
 
         // Store stacklet to the task struct.
         self.initial_stklet.store(stklet_begin, Ordering::SeqCst);
@@ -567,13 +754,21 @@ impl Task {
     /// - `prev_task`: The panicked task.
     #[cfg(feature = "unwind")]
     fn restart_from(&mut self, prev_task: Arc<Task>) {
-        // The task ID is kept the same as the panicked task.
+        // The task IDs and the name are kept the same as the panicked task.
         let id = prev_task.id.load(Ordering::SeqCst);
+        self.uid = prev_task.uid;
+        self.name = prev_task.name;
+        self.cooperative = prev_task.cooperative;
+        self.relative_deadline = prev_task.relative_deadline;
 
         // Clone restart relevant fields from the panicked task struct.
         self.downcast_func = prev_task.downcast_func.clone();
         self.entry_closure = prev_task.entry_closure.clone();
         self.restart_entry_trampoline = prev_task.restart_entry_trampoline.clone();
+        self.restart_policy = prev_task.restart_policy;
+        let restart_count = prev_task.restart_count.load(Ordering::SeqCst);
+        self.restart_count
+            .store(restart_count + 1, Ordering::SeqCst);
 
         // Unwrap the downcast function and the entry closure and. Get the raw
         // pointer to the closure using the downcast function.
@@ -604,7 +799,13 @@ impl Task {
             prev_task.stack_config.clone(),
             priority,
         )
-        .unwrap_or_die()
+        .unwrap_or_die();
+
+        // Keep the peak stack usage of the panicked task, which may have
+        // panicked because of a deep call.
+        if let Some(peak) = prev_task.with_stack_ctrl_block(StackCtrlBlock::get_peak_usage) {
+            self.with_stack_ctrl_block(|scb| scb.record_peak_usage(peak));
+        }
     }
 }
 
@@ -630,10 +831,119 @@ impl Task {
         self.id.load(Ordering::SeqCst)
     }
 
+    pub(crate) fn get_uid(&self) -> TaskId {
+        self.uid
+    }
+
+    pub(crate) fn get_name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    pub(crate) fn set_name(&mut self, name: Option<&'static str>) {
+        self.name = name;
+    }
+
+    pub(crate) fn is_cooperative(&self) -> bool {
+        self.cooperative
+    }
+
+    pub(crate) fn set_cooperative(&mut self, cooperative: bool) {
+        self.cooperative = cooperative;
+    }
+
+    pub(crate) fn set_relative_deadline(&mut self, deadline: Option<u32>) {
+        self.relative_deadline = deadline;
+    }
+
+    /// Start a new job of an EDF task, whose absolute deadline is the relative
+    /// deadline from now. Called when the job is released, i.e., when the
+    /// task is spawned, started, restarted or calls
+    /// [`next_job`](super::next_job), but not when it wakes up from blocking
+    /// or sleeping in the middle of a job.
+    pub(crate) fn start_job(&self) {
+        if let Some(deadline) = self.relative_deadline {
+            let abs_deadline = time::get_tick().wrapping_add(deadline);
+            self.abs_deadline.store(abs_deadline, Ordering::SeqCst);
+        }
+    }
+
+    pub(crate) fn get_parker(&self) -> Arc<Parker> {
+        self.parker.clone()
+    }
+
     pub(crate) fn is_idle(&self) -> bool {
         self.is_idle
     }
 
+    pub(crate) fn is_suspended(&self) -> bool {
+        self.suspended.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_suspended(&self, suspended: bool) {
+        self.suspended.store(suspended, Ordering::SeqCst);
+    }
+
+    /// Return the priority level of the ready list the task is linked in, or
+    /// `None` if the task is not in a ready list.
+    pub(crate) fn get_ready_level(&self) -> Option<u8> {
+        match self.ready_level.load(Ordering::SeqCst) {
+            NOT_IN_READY_LIST => None,
+            level => Some(level),
+        }
+    }
+
+    pub(crate) fn set_ready_level(&self, level: Option<u8>) {
+        self.ready_level
+            .store(level.unwrap_or(NOT_IN_READY_LIST), Ordering::SeqCst);
+    }
+
+    /// Record that the task starts waiting for the CPU from now on.
+    #[cfg(feature = "starvation_monitor")]
+    pub(crate) fn start_waiting(&self) {
+        self.waiting_since
+            .store(crate::time::get_tick(), Ordering::SeqCst);
+        self.starvation_reported.store(false, Ordering::SeqCst);
+    }
+
+    #[cfg(feature = "starvation_monitor")]
+    pub(crate) fn get_waiting_since(&self) -> u32 {
+        self.waiting_since.load(Ordering::SeqCst)
+    }
+
+    /// Return the ID of the task owning the lock the task is blocked on, or
+    /// `None` if it is not blocked on a lock.
+    #[cfg(feature = "starvation_monitor")]
+    pub(crate) fn get_waits_for(&self) -> Option<TaskId> {
+        match self.waits_for.load(Ordering::SeqCst) {
+            NOT_WAITING_FOR_TASK => None,
+            raw => Some(TaskId::from_raw(raw)),
+        }
+    }
+
+    #[cfg(feature = "starvation_monitor")]
+    pub(crate) fn set_waits_for(&self, owner: Option<TaskId>) {
+        self.waits_for.store(
+            owner.map_or(NOT_WAITING_FOR_TASK, TaskId::get),
+            Ordering::SeqCst,
+        );
+    }
+
+    /// Mark the current wait of the task as reported. Return `true` if it
+    /// was not reported before.
+    #[cfg(feature = "starvation_monitor")]
+    pub(crate) fn mark_starvation_reported(&self) -> bool {
+        !self.starvation_reported.swap(true, Ordering::SeqCst)
+    }
+
+    pub(crate) fn push_exit_hook(&self, hook: Box<dyn FnOnce() + Send>) {
+        self.exit_hooks.lock().push(hook);
+    }
+
+    /// Take the hook registered most recently.
+    pub(crate) fn pop_exit_hook(&self) -> Option<Box<dyn FnOnce() + Send>> {
+        self.exit_hooks.lock().pop()
+    }
+
     #[cfg(feature = "unwind")]
     pub(crate) fn set_unwind_flag(&self, val: bool) {
         self.is_unwinding.store(val, Ordering::SeqCst);
@@ -657,6 +967,37 @@ impl Task {
         self.wake_at_tick.store(tick, Ordering::SeqCst);
     }
 
+    pub(crate) fn get_wait_seq(&self) -> u32 {
+        self.wait_seq.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_wait_seq(&self, seq: u32) {
+        self.wait_seq.store(seq, Ordering::SeqCst);
+    }
+
+    /// Record that the task passes a checkpoint at the given tick. Return
+    /// whether the task should yield, i.e., whether at least `yield_period`
+    /// ticks have passed since it last yielded at a checkpoint.
+    pub(crate) fn record_checkpoint(&self, tick: u32, yield_period: u32) -> bool {
+        self.checkpoint_count.fetch_add(1, Ordering::SeqCst);
+        self.checkpoint_tick.store(tick, Ordering::SeqCst);
+        let last_yield = self.checkpoint_yield_tick.load(Ordering::SeqCst);
+        if tick.wrapping_sub(last_yield) < yield_period {
+            return false;
+        }
+        self.checkpoint_yield_tick.store(tick, Ordering::SeqCst);
+        true
+    }
+
+    /// Return the number of checkpoints the task has passed and the tick
+    /// number when it last passed one.
+    pub(crate) fn get_checkpoint_progress(&self) -> (u32, u32) {
+        (
+            self.checkpoint_count.load(Ordering::SeqCst),
+            self.checkpoint_tick.load(Ordering::SeqCst),
+        )
+    }
+
     #[cfg(feature = "unwind")]
     pub(crate) fn has_restarted(&self) -> bool {
         self.has_restarted.load(Ordering::SeqCst)
@@ -667,6 +1008,96 @@ impl Task {
         self.entry_closure.is_some()
     }
 
+    #[cfg(feature = "unwind")]
+    pub(crate) fn set_restart_policy(&mut self, policy: RestartPolicy) {
+        self.restart_policy = policy;
+    }
+
+    /// Return if the task has been restarted the maximum number of times and
+    /// should not be restarted again.
+    #[cfg(feature = "unwind")]
+    pub(crate) fn is_restart_exhausted(&self) -> bool {
+        self.restart_policy
+            .max_restarts
+            .is_some_and(|max| self.restart_count.load(Ordering::SeqCst) >= max)
+    }
+
+    /// Return the milliseconds to wait before the task runs its entry
+    /// closure again. Return zero if the task has not been restarted.
+    #[cfg(feature = "unwind")]
+    pub(crate) fn get_restart_delay_ms(&self) -> u32 {
+        let count = self.restart_count.load(Ordering::SeqCst);
+        let policy = &self.restart_policy;
+        match (count, policy.backoff_max_ms) {
+            (0, _) => 0,
+            (_, None) => policy.delay_ms,
+            (_, Some(max)) => {
+                let delay = (policy.delay_ms as u64) << (count - 1).min(32);
+                delay.min(max as u64) as u32
+            }
+        }
+    }
+
+    /// Count a restart of the task reusing the current task struct.
+    #[cfg(feature = "unwind")]
+    pub(crate) fn count_restart(&self) {
+        self.restart_count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[cfg(feature = "unwind")]
+    pub(crate) fn get_restart_exhausted_callback(&self) -> Option<fn(TaskId)> {
+        self.restart_policy.on_exhausted
+    }
+
+    #[cfg(feature = "unwind")]
+    pub(crate) fn is_terminating(&self) -> bool {
+        self.terminating.load(Ordering::SeqCst)
+    }
+
+    #[cfg(feature = "unwind")]
+    pub(crate) fn set_terminating(&self) {
+        self.terminating.store(true, Ordering::SeqCst);
+    }
+
+    /// Clear the flag set when the task misses its watchdog deadline. Return
+    /// whether it was set.
+    #[cfg(feature = "unwind")]
+    pub(crate) fn take_watchdog_expired(&self) -> bool {
+        self.watchdog_expired.swap(false, Ordering::SeqCst)
+    }
+
+    #[cfg(feature = "unwind")]
+    pub(crate) fn set_watchdog_expired(&self, val: bool) {
+        self.watchdog_expired.store(val, Ordering::SeqCst);
+    }
+
+    #[cfg(feature = "unwind")]
+    pub(crate) fn set_panic_record(&self, record: Box<PanicRecord>) {
+        self.panic_record.lock().replace(record);
+    }
+
+    #[cfg(feature = "unwind")]
+    pub(crate) fn take_panic_record(&self) -> Option<Box<PanicRecord>> {
+        self.panic_record.lock().take()
+    }
+
+    /// Reduce the priority of the task to
+    /// [`UNWIND_PRIORITY`](config::UNWIND_PRIORITY) when it starts
+    /// unwinding, so that the unwinding uses only otherwise idle CPU time.
+    #[cfg(feature = "unwind")]
+    pub(crate) fn reduce_priority_for_unwinding(&self) {
+        let prio = self.priority.load().intrinsic_priority();
+        self.priority_before_unwind.store(prio, Ordering::SeqCst);
+        self.change_intrinsic_priority(config::UNWIND_PRIORITY);
+    }
+
+    /// Restore the priority reduced by
+    /// [`reduce_priority_for_unwinding`](Self::reduce_priority_for_unwinding).
+    #[cfg(feature = "unwind")]
+    pub(crate) fn restore_priority_after_unwinding(&self) {
+        self.change_intrinsic_priority(self.priority_before_unwind.load(Ordering::SeqCst));
+    }
+
     /// Lock the task context and return the mutable raw pointer to the
     /// context. The pointer is used by the context switch assembly sequence
     /// in [`context_switch`](crate::interrupt::context_switch).
@@ -702,6 +1133,7 @@ impl Task {
         match self.stack_config {
             StackConfig::Static { limit } => Some(limit.get()),
             StackConfig::Dynamic { limit, .. } => limit.map(|size| size.get()),
+            StackConfig::Provided { stack } => Some(stack.free_size()),
         }
     }
 }
@@ -715,7 +1147,7 @@ impl Task {
 
     pub(crate) fn change_intrinsic_priority(&self, prio: u8) {
         let new_prio = TaskPriority::change_intrinsic(&self.priority.load(), prio);
-        self.priority.store(new_prio);
+        self.store_priority(new_prio);
     }
 
     /// If the other given task has higher priority, inherit it. Otherwise,
@@ -727,7 +1159,19 @@ impl Task {
         let self_prio = self.priority.load();
         let other_prio = other.priority.load();
         if let Ok(inherited_prio) = TaskPriority::try_inherit_from(&self_prio, &other_prio) {
-            self.priority.store(inherited_prio);
+            self.store_priority(inherited_prio);
+        }
+    }
+
+    /// If the given priority is higher than that of the task, raise the task
+    /// to it. Otherwise, keep the current priority. Like
+    /// [`ceil_priority_from`](Self::ceil_priority_from), the intrinsic
+    /// priority is kept and can be restored.
+    pub(crate) fn ceil_priority_to(&self, prio: u8) {
+        let self_prio = self.priority.load();
+        let ceiling = TaskPriority::new_intrinsic(prio);
+        if let Ok(raised_prio) = TaskPriority::try_inherit_from(&self_prio, &ceiling) {
+            self.store_priority(raised_prio);
         }
     }
 
@@ -735,18 +1179,50 @@ impl Task {
     /// at task creation time.
     pub(crate) fn restore_intrinsic_priority(&self) {
         let intrinsic_prio = TaskPriority::restore_intrinsic(&self.priority.load());
-        self.priority.store(intrinsic_prio);
+        self.store_priority(intrinsic_prio);
+    }
+
+    /// Store the new priority. If the task is in a ready list, move it to the
+    /// list of the new priority, or pend the move if the ready queue is under
+    /// contention.
+    fn store_priority(&self, prio: TaskPriority) {
+        self.priority.store(prio);
+        if self.get_ready_level().is_some() {
+            Scheduler::requeue_task(self);
+        }
     }
 
     /// Return true if and only if this task has higher priority than the other
     /// task.
     pub(crate) fn should_preempt(&self, other: &Self) -> bool {
         if config::ALLOW_TASK_PREEMPTION {
-            self.priority.load() < other.priority.load()
+            self.precedes(other)
         } else {
             false
         }
     }
+
+    /// Return true if and only if this task should run before the other task,
+    /// i.e., it has higher priority, or they have the same priority and this
+    /// task is an EDF task with an earlier deadline. An EDF task runs before a
+    /// task of the same priority without a deadline.
+    pub(crate) fn precedes(&self, other: &Self) -> bool {
+        let prio = self.priority.load();
+        let other_prio = other.priority.load();
+        if prio != other_prio {
+            return prio < other_prio;
+        }
+
+        match (self.relative_deadline, other.relative_deadline) {
+            (Some(_), Some(_)) => {
+                let deadline = self.abs_deadline.load(Ordering::SeqCst);
+                let other_deadline = other.abs_deadline.load(Ordering::SeqCst);
+                time::tick_cmp(deadline, other_deadline) == CmpOrdering::Less
+            }
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
 }
 
 // Create the adapter for the intrusive linked list of task structs.
@@ -756,10 +1232,18 @@ intrusive_adapter!(
 );
 
 impl Drop for Task {
-    /// When dropping a task struct, we should free the initial stacklet.
+    /// When dropping a task struct, we should free the initial stacklet, or
+    /// release the provided stack.
     fn drop(&mut self) {
         let stklet_ptr = self.initial_stklet.load(Ordering::SeqCst);
 
+        if let StackConfig::Provided { stack } = &self.stack_config {
+            if !stklet_ptr.is_null() {
+                stack.release();
+            }
+            return;
+        }
+
         if !stklet_ptr.is_null() {
             // Safety: Semantically, `initial_stklet` owns the memory it points
             // to. The memory was dynamically allocated. We must free it to
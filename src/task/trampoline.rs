@@ -1,5 +1,8 @@
+use super::{exit_hook, watchdog};
 use alloc::boxed::Box;
 
+#[cfg(feature = "unwind")]
+use super::panic_observer;
 #[cfg(feature = "unwind")]
 use crate::unwind::unw_catch;
 
@@ -22,14 +25,25 @@ where
     // silently return the entry trampoline function so that the current task
     // struct will be released.
     #[cfg(feature = "unwind")]
-    let _ = unw_catch::catch_unwind(*closure);
+    let catch_result = unw_catch::catch_unwind(*closure);
 
     #[cfg(not(feature = "unwind"))]
     (*closure)();
+
+    // The task is no longer watched once it returns.
+    watchdog::watchdog_unregister();
+    exit_hook::run_exit_hooks();
+
+    // The task is not restarted anyway, so the decision of the observer is
+    // ignored.
+    #[cfg(feature = "unwind")]
+    if catch_result.is_err() {
+        let _ = unw_catch::catch_unwind(panic_observer::observe_panic);
+    }
 }
 
 #[cfg(feature = "unwind")]
-use crate::{schedule::current, unrecoverable};
+use crate::{schedule::current, time, unrecoverable};
 #[cfg(feature = "unwind")]
 use core::any::Any;
 
@@ -50,12 +64,24 @@ where
     let closure = unsafe { &*(closure_ptr as *const F) };
 
     loop {
+        // Wait before running the entry closure again if the task has been
+        // restarted with a delay. The task may be terminated while sleeping.
+        let delay_ms = current::with_cur_task(|cur_task| cur_task.get_restart_delay_ms());
+        if delay_ms > 0 && unw_catch::catch_unwind(|| time::sleep_ms(delay_ms)).is_err() {
+            break;
+        }
+
         // Execute the task entry closure. If the task entry
         // closure returns normally, the `catch_result` will be `Ok(())`.
         // Otherwise, if the entry closure returns due to a panic,
         // `catch_result` will be `Err(())`.
         let catch_result = unw_catch::catch_unwind(closure.clone());
 
+        // The task must register with the watchdog again if restarted, and
+        // release the resources of the run before it terminates or restarts.
+        watchdog::watchdog_unregister();
+        exit_hook::run_exit_hooks();
+
         // When the task entry closure returns the execution normally, break
         // the loop so that the task can terminate.
         if let Ok(_) = catch_result {
@@ -64,8 +90,10 @@ where
 
         // If the task panicked, check if it has already been restarted with
         // another task struct. If yes, we break the loop to let the current
-        // task struct terminates.
-        if current::with_cur_task(|cur_task| cur_task.has_restarted()) {
+        // task struct terminates. A task unwound because it was terminated
+        // also terminates.
+        if current::with_cur_task(|cur_task| cur_task.has_restarted() || cur_task.is_terminating())
+        {
             break;
         }
 
@@ -91,6 +119,34 @@ where
             break;
         }
 
+        // Let the panic observer decide whether to restart the task. Do not
+        // restart the task if the observer panics.
+        if !unw_catch::catch_unwind(panic_observer::observe_panic).unwrap_or(false) {
+            break;
+        }
+
+        // If the task has been restarted the maximum number of times, give up
+        // restarting it and run the callback to escalate the failure.
+        // Otherwise, count the restart that reuses the current task struct,
+        // and restore the priority reduced for unwinding.
+        let exhausted = current::with_cur_task(|cur_task| {
+            if cur_task.is_restart_exhausted() {
+                return Some(cur_task.get_restart_exhausted_callback());
+            }
+            cur_task.count_restart();
+            cur_task.restore_priority_after_unwinding();
+            None
+        });
+        if let Some(callback) = exhausted {
+            if let Some(callback) = callback {
+                let id = current::with_cur_task(|cur_task| cur_task.get_uid());
+                // The task is terminating anyway, so a panic inside the
+                // callback is ignored.
+                let _ = unw_catch::catch_unwind(move || callback(id));
+            }
+            break;
+        }
+
         // Let the loop run over again so that the task can restart execution
         // with the entry closure again.
     }
@@ -0,0 +1,261 @@
+//! Software watchdog for tasks.
+//!
+//! A task registers with the watchdog by calling [`watchdog_register`] with a
+//! period, and afterwards calls [`watchdog_feed`] at least once every period.
+//! The SysTick handler checks the deadlines of the registered tasks every
+//! tick. If a task misses its deadline, the kernel takes the
+//! [`WatchdogAction`] chosen at the registration:
+//!
+//! - [`Restart`](WatchdogAction::Restart) unwinds the stalled task, and
+//!   restarts it if it is restartable. Like a task being
+//!   [terminated](super::TaskHandle::terminate), the task is unwound at its
+//!   next preemption point, i.e., when it next yields, blocks, sleeps or
+//!   passes a [`checkpoint`](super::checkpoint). A sleeping task is woken up
+//!   to be unwound. A task spinning without any preemption point is never
+//!   unwound, so use [`Reset`](WatchdogAction::Reset) for code that may hang
+//!   in a busy loop.
+//! - [`Reset`](WatchdogAction::Reset) records
+//!   [`RebootReason::Watchdog`](crate::debug::breadcrumb::RebootReason::Watchdog)
+//!   in the breadcrumbs and resets the system immediately, without the
+//!   orderly [shutdown](crate::power::shutdown).
+//!
+//! The action is taken once. The task must register again to be watched
+//! again. The registration ends when the task returns or is unwound, so a
+//! restarted task is not watched until it registers. A hardware watchdog,
+//! e.g., the IWDG, can complement the software watchdog by being fed from
+//! the [idle hook](super::set_idle_hook), which only runs if no task hogs
+//! the CPU.
+//!
+//! # Example
+//! ```rust
+//! fn control_loop() {
+//!     task::watchdog_register(tunable::watchdog_period_ms(), WatchdogAction::Restart);
+//!     loop {
+//!         let sample = SENSOR.read();
+//!         ACTUATOR.write(compute(sample));
+//!         task::watchdog_feed();
+//!         time::sleep_ms(10).unwrap();
+//!     }
+//! }
+//! ```
+
+use super::Task;
+use crate::{
+    debug::breadcrumb::RebootReason,
+    power,
+    schedule::current,
+    sync::{Access, AllowPendOp, RefCellSchedSafe, RunPendedOp, SoftLock, Spin},
+    time::{self, tick_cmp},
+    unrecoverable,
+};
+use alloc::{sync::Arc, vec::Vec};
+use core::{
+    cmp::Ordering as CmpOrdering,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The action taken when a registered task misses its watchdog deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogAction {
+    /// Unwind the stalled task, and restart it if it is restartable.
+    #[cfg(feature = "unwind")]
+    Restart,
+    /// Reset the system.
+    Reset,
+}
+
+/// A task registered with the watchdog.
+struct Watched {
+    task: Arc<Task>,
+    period_ms: u32,
+    deadline: u32,
+    action: WatchdogAction,
+    /// Set when the action has been taken.
+    expired: bool,
+}
+
+struct Inner {
+    /// The registered tasks. The spin lock around it is only for sanity
+    /// check. This field should not be accessed concurrently.
+    watched: Spin<Vec<Watched>>,
+    /// Whether SysTick fired while another context had the full access.
+    time_to_check: AtomicBool,
+}
+
+/// Representing full access to all fields of the watchdog.
+struct InnerFullAccessor<'a> {
+    watched: &'a Spin<Vec<Watched>>,
+    time_to_check: &'a AtomicBool,
+}
+
+/// Representing pend-only access to the watchdog.
+struct InnerPendAccessor<'a> {
+    time_to_check: &'a AtomicBool,
+}
+
+/// Bind the accessor types.
+impl<'a> AllowPendOp<'a> for Inner {
+    type FullAccessor = InnerFullAccessor<'a>;
+    type PendOnlyAccessor = InnerPendAccessor<'a>;
+    fn full_access(&'a self) -> InnerFullAccessor<'a> {
+        InnerFullAccessor {
+            watched: &self.watched,
+            time_to_check: &self.time_to_check,
+        }
+    }
+    fn pend_only_access(&'a self) -> InnerPendAccessor<'a> {
+        InnerPendAccessor {
+            time_to_check: &self.time_to_check,
+        }
+    }
+}
+
+/// Check the deadlines if SysTick fired while the registered tasks were
+/// being changed.
+impl<'a> RunPendedOp for InnerFullAccessor<'a> {
+    fn run_pended_op(&mut self) {
+        if self.time_to_check.swap(false, Ordering::SeqCst) {
+            self.check_deadlines();
+        }
+    }
+}
+
+impl<'a> InnerFullAccessor<'a> {
+    /// Take the action for the registered tasks that missed their deadlines.
+    fn check_deadlines(&self) {
+        let now = time::get_tick();
+        let mut reset = false;
+
+        for entry in self.watched.lock_now_or_die().iter_mut() {
+            if entry.expired || tick_cmp(now, entry.deadline) == CmpOrdering::Less {
+                continue;
+            }
+            entry.expired = true;
+            match entry.action {
+                #[cfg(feature = "unwind")]
+                WatchdogAction::Restart => {
+                    entry.task.set_watchdog_expired(true);
+                    time::remove_task_from_sleep_queue_allow_isr(entry.task.clone());
+                }
+                WatchdogAction::Reset => reset = true,
+            }
+        }
+
+        if reset {
+            power::reset(RebootReason::Watchdog);
+        }
+    }
+}
+
+impl Inner {
+    const fn new() -> Self {
+        Self {
+            watched: Spin::new(Vec::new()),
+            time_to_check: AtomicBool::new(false),
+        }
+    }
+}
+
+/// The registered tasks. The SysTick handler checks their deadlines if no
+/// task is changing them, or otherwise pends the check to the task.
+static WATCHDOG: RefCellSchedSafe<SoftLock<Inner>> =
+    RefCellSchedSafe::new(SoftLock::new(Inner::new()));
+
+/// Run `op` on the registered tasks. Should always grant full access to a
+/// task.
+fn with_watched<R>(op: impl FnOnce(&mut Vec<Watched>) -> R) -> R {
+    WATCHDOG.with_suspended_scheduler(|watchdog, _| {
+        watchdog.must_with_full_access(|full_access| op(&mut full_access.watched.lock_now_or_die()))
+    })
+}
+
+/// Register the calling task with the watchdog. The task must then call
+/// [`watchdog_feed`] at least once every `period_ms` milliseconds, or the
+/// `action` is taken. Registering again replaces the period and the action
+/// and feeds the watchdog. See the [module-level documentation](self).
+///
+/// Important: *must not* call this function in ISR context.
+pub fn watchdog_register(period_ms: u32, action: WatchdogAction) {
+    unrecoverable::die_if_in_isr();
+
+    let task = current::with_cur_task_arc(|cur_task| cur_task);
+    let deadline = time::get_tick().wrapping_add(period_ms);
+    // Drop the task struct reference of an existing registration after
+    // releasing the access.
+    let task = with_watched(|watched| {
+        let existing = watched.iter_mut().find(|w| Arc::ptr_eq(&w.task, &task));
+        match existing {
+            Some(entry) => {
+                entry.period_ms = period_ms;
+                entry.deadline = deadline;
+                entry.action = action;
+                entry.expired = false;
+                Some(task)
+            }
+            None => {
+                watched.push(Watched {
+                    task,
+                    period_ms,
+                    deadline,
+                    action,
+                    expired: false,
+                });
+                None
+            }
+        }
+    });
+    drop(task);
+}
+
+/// Feed the watchdog, extending the deadline of the calling task to one
+/// period from now. Do nothing if the task is not registered.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn watchdog_feed() {
+    unrecoverable::die_if_in_isr();
+
+    let now = time::get_tick();
+    current::with_cur_task(|cur_task| {
+        with_watched(|watched| {
+            if let Some(entry) = watched
+                .iter_mut()
+                .find(|w| core::ptr::eq(Arc::as_ptr(&w.task), cur_task))
+            {
+                entry.deadline = now.wrapping_add(entry.period_ms);
+            }
+        })
+    });
+}
+
+/// Unregister the calling task from the watchdog. Do nothing if the task is
+/// not registered.
+///
+/// Important: *must not* call this function in ISR context.
+pub fn watchdog_unregister() {
+    unrecoverable::die_if_in_isr();
+
+    let removed = current::with_cur_task(|cur_task| {
+        with_watched(|watched| {
+            let idx = watched
+                .iter()
+                .position(|w| core::ptr::eq(Arc::as_ptr(&w.task), cur_task));
+            idx.map(|idx| watched.swap_remove(idx))
+        })
+    });
+    // Drop the task struct reference without holding the access.
+    drop(removed);
+}
+
+/// Take the action for the registered tasks that missed their deadlines.
+/// Called by the SysTick handler. If a task is changing the registered
+/// tasks, the check is pended to the task.
+pub(crate) fn check_deadlines_allow_isr() {
+    WATCHDOG.with_suspended_scheduler(|watchdog, _| {
+        watchdog.with_access(|access| match access {
+            Access::Full { full_access } => full_access.check_deadlines(),
+            Access::PendOnly { pend_access } => {
+                pend_access.time_to_check.store(true, Ordering::SeqCst)
+            }
+        })
+    });
+}
@@ -0,0 +1,104 @@
+use super::{get_tick, sleep_ms_unchecked, tick_cmp, SleepError};
+use core::cmp::Ordering as CmpOrdering;
+
+/// A delay provider that blocks the calling task on the sleep queue instead
+/// of busy waiting. The resolution is one SysTick, i.e., one millisecond, and
+/// shorter delays are rounded up.
+///
+/// With the `embedded-hal` feature, [`Delay`] implements
+/// `embedded_hal::delay::DelayNs`, so that third-party driver crates can use
+/// it directly.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Delay;
+
+impl Delay {
+    /// Create a new delay provider.
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /// Block the calling task for at least the given number of microseconds.
+    pub fn delay_us(&mut self, us: u32) {
+        self.delay_ms(us.div_ceil(1000));
+    }
+
+    /// Block the calling task for at least the given number of milliseconds.
+    pub fn delay_ms(&mut self, ms: u32) {
+        // Split long delays so that each sleep stays within the range allowed
+        // by `tick_cmp`.
+        let mut remaining = ms;
+        while remaining > 0 {
+            let chunk = remaining.min(i32::MAX as u32);
+            sleep_ms_unchecked(chunk);
+            remaining -= chunk;
+        }
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl embedded_hal::delay::DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        Delay::delay_ms(self, ns.div_ceil(1_000_000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        Delay::delay_us(self, us);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        Delay::delay_ms(self, ms);
+    }
+}
+
+/// A one-shot countdown timer driven by SysTick. It replaces the
+/// `CountDown` trait from `embedded-hal` 0.2 for drivers that poll for a
+/// timeout while doing other work.
+#[derive(Debug, Default)]
+pub struct Countdown {
+    /// The tick when the countdown expires, or `None` if not started.
+    expire_at_tick: Option<u32>,
+}
+
+impl Countdown {
+    /// Create a new countdown timer that is not started.
+    pub const fn new() -> Self {
+        Self {
+            expire_at_tick: None,
+        }
+    }
+
+    /// Start or restart the countdown to expire after the given number of
+    /// milliseconds.
+    pub fn start(&mut self, ms: u32) -> Result<(), SleepError> {
+        // See `tick_cmp` for the reason of limitation.
+        if ms > i32::MAX as u32 {
+            return Err(SleepError::TooLong);
+        }
+        self.expire_at_tick = Some(get_tick().wrapping_add(ms));
+        Ok(())
+    }
+
+    /// Stop the countdown. A stopped countdown never expires.
+    pub fn cancel(&mut self) {
+        self.expire_at_tick = None;
+    }
+
+    /// Return whether the countdown has been started and has expired.
+    pub fn is_expired(&self) -> bool {
+        match self.expire_at_tick {
+            Some(tick) => !matches!(tick_cmp(get_tick(), tick), CmpOrdering::Less),
+            None => false,
+        }
+    }
+
+    /// Block the calling task until the countdown expires. Return immediately
+    /// if the countdown is not started.
+    pub fn wait(&mut self) {
+        if let Some(tick) = self.expire_at_tick {
+            let remaining = tick.wrapping_sub(get_tick()) as i32;
+            if remaining > 0 {
+                sleep_ms_unchecked(remaining as u32);
+            }
+        }
+    }
+}
@@ -2,18 +2,27 @@ use crate::{
     config,
     interrupt::context_switch,
     schedule::{current, scheduler::Scheduler},
-    sync::{Access, AllowPendOp, RefCellSchedSafe, RunPendedOp, SoftLock, Spin},
+    sync::{Access, AllowPendOp, RefCellSchedSafe, RunPendedOp, SoftLock, Spin, SpinSchedSafe},
     task::{Task, TaskListAdapter, TaskListInterfaces, TaskState},
     unrecoverable::Lethal,
 };
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use core::{
     cmp::Ordering as CmpOrdering,
+    future::Future,
+    pin::Pin,
     sync::atomic::{AtomicBool, AtomicU32, Ordering},
+    task::{Context, Poll, Waker},
 };
+use cortex_m::peripheral::SYST;
 use heapless::mpmc::MpMcQueue;
 use intrusive_collections::LinkedList;
 
+mod delay;
+pub mod timer;
+
+pub use delay::*;
+
 struct Inner {
     time_sorted_queue: Spin<LinkedList<TaskListAdapter>>,
     delete_buffer: DeleteBuffer,
@@ -83,6 +92,7 @@ impl<'a> InnerFullAccessor<'a> {
 
         while let Some(task) = self.delete_buffer.dequeue() {
             if let Some(task) = locked_queue.remove_task(&task) {
+                task.set_wake_tick(cur_tick);
                 Scheduler::accept_task(task);
             }
         }
@@ -100,6 +110,12 @@ impl<'a> RunPendedOp for InnerFullAccessor<'a> {
 
 static SLEEP_TASK_QUEUE: SleepQueue = RefCellSchedSafe::new(SoftLock::new(Inner::new()));
 
+/// Return the contention statistics of the sleeping task queue.
+#[cfg(feature = "soft_lock_stats")]
+pub(crate) fn sleep_queue_soft_lock_stats() -> crate::debug::soft_lock_stats::SoftLockStats {
+    SLEEP_TASK_QUEUE.with_suspended_scheduler(|queue, _| queue.stats())
+}
+
 /// The tick number of SysTick.
 static TICKS: AtomicU32 = AtomicU32::new(0);
 
@@ -114,6 +130,35 @@ pub fn get_tick() -> u32 {
     TICKS.load(Ordering::SeqCst)
 }
 
+/// Return a timestamp in CPU cycles. The SysTick counter counts down from the
+/// reload value once per tick, so the cycles are the tick count times the
+/// period plus the cycles elapsed in the current tick. The timestamp wraps
+/// around `u32::MAX`.
+pub(crate) fn cycle_stamp() -> u32 {
+    let (tick, cycles) = tick_and_cycles();
+    tick.wrapping_mul(cycles_per_tick()).wrapping_add(cycles)
+}
+
+/// Return the tick count and the CPU cycles elapsed in the current tick,
+/// read consistently with each other.
+pub(crate) fn tick_and_cycles() -> (u32, u32) {
+    let period = cycles_per_tick();
+    loop {
+        let tick = get_tick();
+        let current = SYST::get_current();
+
+        // Read again if SysTick fired in between.
+        if get_tick() == tick {
+            return (tick, period - 1 - current);
+        }
+    }
+}
+
+/// Return the number of CPU cycles in a tick.
+pub(crate) fn cycles_per_tick() -> u32 {
+    SYST::get_reload() + 1
+}
+
 /// Wake up those sleeping tasks that have their sleeping time expired.
 pub(crate) fn wake_sleeping_tasks() {
     SLEEP_TASK_QUEUE.with_suspended_scheduler(|queue, _| {
@@ -151,6 +196,10 @@ fn sleep_ms_unchecked(ms: u32) {
         // Yield from the current task. Even if the current task has already
         // been woken up, yielding from it will not introduce deadlock.
         context_switch::yield_current_task();
+
+        // The task may have been woken up to be terminated.
+        #[cfg(feature = "unwind")]
+        crate::unwind::forced::unwind_if_requested();
     }
 
     // Outline the logic to reduce the stack frame size of `sleep_ms`.
@@ -179,6 +228,8 @@ pub(crate) fn remove_task_from_sleep_queue_allow_isr(task: Arc<Task>) {
             Access::Full { full_access } => {
                 let mut locked_queue = full_access.time_sorted_queue.lock_now_or_die();
                 if let Some(task) = locked_queue.remove_task(&task) {
+                    // Woken up early. Do not report the task as sleeping.
+                    task.set_wake_tick(get_tick());
                     Scheduler::accept_task(task);
                 }
             }
@@ -189,6 +240,83 @@ pub(crate) fn remove_task_from_sleep_queue_allow_isr(task: Arc<Task>) {
     });
 }
 
+/// Wakers of pending [`SleepFuture`]s paired with their wake up tick.
+static ASYNC_TIMERS: SpinSchedSafe<Vec<(u32, Waker)>> = SpinSchedSafe::new(Vec::new());
+
+/// A future that becomes ready after the given number of milliseconds.
+/// Created by [`sleep_ms_async`].
+pub struct SleepFuture {
+    wake_at_tick: u32,
+}
+
+impl Future for SleepFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if let CmpOrdering::Less = tick_cmp(get_tick(), self.wake_at_tick) {
+            let mut timers = ASYNC_TIMERS.lock();
+            let registered = timers
+                .iter()
+                .any(|(tick, waker)| *tick == self.wake_at_tick && waker.will_wake(cx.waker()));
+            if !registered {
+                timers.push((self.wake_at_tick, cx.waker().clone()));
+            }
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
+
+/// Create a future that becomes ready after the given number of milliseconds.
+/// The future is intended to be run with [`spawn_async`](crate::task::spawn_async).
+pub fn sleep_ms_async(ms: u32) -> Result<SleepFuture, SleepError> {
+    // See `tick_cmp` for the reason of limitation.
+    if ms > i32::MAX as u32 {
+        return Err(SleepError::TooLong);
+    }
+
+    Ok(SleepFuture {
+        wake_at_tick: get_tick().wrapping_add(ms),
+    })
+}
+
+/// Wake up the [`SleepFuture`]s that have their sleeping time expired.
+pub(crate) fn wake_expired_async_timers() {
+    let cur_tick = get_tick();
+    let mut expired = Vec::new();
+
+    {
+        let mut timers = ASYNC_TIMERS.lock();
+        let mut idx = 0;
+        while idx < timers.len() {
+            if let CmpOrdering::Greater = tick_cmp(timers[idx].0, cur_tick) {
+                idx += 1;
+            } else {
+                expired.push(timers.swap_remove(idx).1);
+            }
+        }
+    }
+
+    // Wake up the futures after releasing the lock, because waking may
+    // register new timers.
+    for waker in expired {
+        waker.wake();
+    }
+}
+
+/// Return the number of milliseconds until the earliest pending
+/// [`SleepFuture`] expires, or `None` if there is no pending one.
+pub(crate) fn next_async_timer_timeout_ms() -> Option<u32> {
+    let cur_tick = get_tick();
+    ASYNC_TIMERS
+        .lock()
+        .iter()
+        .map(|(tick, _)| tick.wrapping_sub(cur_tick) as i32)
+        .min()
+        .map(|ticks| ticks.max(0) as u32)
+}
+
 /// A time-based task barrier that allow a task to proceed at a given interval.
 pub struct IntervalBarrier {
     /// The interval in milliseconds.
@@ -0,0 +1,248 @@
+//! One-shot timers armed from task or ISR context.
+//!
+//! An interrupt handler often needs to do follow-up work after a delay,
+//! e.g., de-asserting a line 5 milliseconds after asserting it, but cannot
+//! sleep and should not own a hardware timer for each such delay. Instead,
+//! it arms a kernel timer with [`arm_allow_isr`], and the SysTick handler
+//! notifies the given [`Mailbox`] when the timer expires. A task waiting on
+//! the mailbox then does the follow-up work.
+//!
+//! At most [`TIMER_QUEUE_LENGTH`](config::TIMER_QUEUE_LENGTH) timers can be
+//! armed at the same time. They are kept in a bounded priority queue, so
+//! arming a timer and firing an expired one take `O(log N)` time.
+//!
+//! # Example
+//! ```rust
+//! static DEASSERT: Mailbox = Mailbox::new();
+//!
+//! #[handler(EXTI0)]
+//! fn exti0_handler() {
+//!     assert_line();
+//!     timer::arm_allow_isr(5, &DEASSERT).unwrap();
+//! }
+//!
+//! // In a task.
+//! loop {
+//!     DEASSERT.wait();
+//!     deassert_line();
+//! }
+//! ```
+
+use super::{get_tick, tick_cmp};
+use crate::{
+    config,
+    sync::{Access, AllowPendOp, Mailbox, RefCellSchedSafe, RunPendedOp, SoftLock, Spin},
+    unrecoverable::Lethal,
+};
+use core::{
+    cmp::Ordering as CmpOrdering,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use heapless::{
+    binary_heap::{BinaryHeap, Max},
+    mpmc::MpMcQueue,
+};
+
+/// Enumeration of errors when arming a timer.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TimerError {
+    /// The given delay is too long.
+    TooLong,
+    /// [`TIMER_QUEUE_LENGTH`](config::TIMER_QUEUE_LENGTH) timers are
+    /// already armed.
+    QueueFull,
+}
+
+/// An armed timer.
+struct Timer {
+    fire_at_tick: u32,
+    mailbox: &'static Mailbox,
+}
+
+/// A timer is greater if it expires earlier, so that the earliest one is at
+/// the top of the max-heap. All armed timers expire within `i32::MAX`
+/// ticks, so [`tick_cmp`] orders them consistently.
+impl Ord for Timer {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        tick_cmp(other.fire_at_tick, self.fire_at_tick)
+    }
+}
+
+impl PartialOrd for Timer {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Timer {
+    fn eq(&self, other: &Self) -> bool {
+        self.fire_at_tick == other.fire_at_tick
+    }
+}
+
+impl Eq for Timer {}
+
+type TimerHeap = BinaryHeap<Timer, Max, { config::TIMER_QUEUE_LENGTH }>;
+type PendingBuffer = MpMcQueue<Timer, { config::TIMER_QUEUE_LENGTH }>;
+
+struct Inner {
+    /// The armed timers. The spin lock around it is only for sanity check.
+    /// This field should not be accessed concurrently.
+    heap: Spin<TimerHeap>,
+    /// The timers armed by a context that preempted the full access owner.
+    pending: PendingBuffer,
+    /// Whether SysTick fired while another context had the full access.
+    time_to_fire: AtomicBool,
+}
+
+/// Representing full access to all fields of the timer queue.
+struct InnerFullAccessor<'a> {
+    heap: &'a Spin<TimerHeap>,
+    pending: &'a PendingBuffer,
+    time_to_fire: &'a AtomicBool,
+}
+
+/// Representing pend-only access to the timer queue.
+struct InnerPendAccessor<'a> {
+    pending: &'a PendingBuffer,
+    time_to_fire: &'a AtomicBool,
+}
+
+/// Bind the accessor types.
+impl<'a> AllowPendOp<'a> for Inner {
+    type FullAccessor = InnerFullAccessor<'a>;
+    type PendOnlyAccessor = InnerPendAccessor<'a>;
+    fn full_access(&'a self) -> InnerFullAccessor<'a> {
+        InnerFullAccessor {
+            heap: &self.heap,
+            pending: &self.pending,
+            time_to_fire: &self.time_to_fire,
+        }
+    }
+    fn pend_only_access(&'a self) -> InnerPendAccessor<'a> {
+        InnerPendAccessor {
+            pending: &self.pending,
+            time_to_fire: &self.time_to_fire,
+        }
+    }
+}
+
+/// Move the pended timers into the heap, and fire the expired timers if
+/// SysTick fired in between.
+impl<'a> RunPendedOp for InnerFullAccessor<'a> {
+    fn run_pended_op(&mut self) {
+        {
+            let mut heap = self.heap.lock_now_or_die();
+            while let Some(timer) = self.pending.dequeue() {
+                heap.push(timer).ok().unwrap_or_die();
+            }
+        }
+        if self.time_to_fire.swap(false, Ordering::SeqCst) {
+            self.fire_expired();
+        }
+    }
+}
+
+impl<'a> InnerFullAccessor<'a> {
+    /// Notify the mailboxes of the expired timers and remove them.
+    fn fire_expired(&self) {
+        let cur_tick = get_tick();
+        let mut heap = self.heap.lock_now_or_die();
+        while let Some(timer) = heap.peek() {
+            if let CmpOrdering::Greater = tick_cmp(timer.fire_at_tick, cur_tick) {
+                break;
+            }
+            let timer = heap.pop().unwrap_or_die();
+            timer.mailbox.notify_allow_isr();
+            ARMED_COUNT.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Inner {
+    const fn new() -> Self {
+        Self {
+            heap: Spin::new(BinaryHeap::new()),
+            pending: PendingBuffer::new(),
+            time_to_fire: AtomicBool::new(false),
+        }
+    }
+}
+
+static TIMER_QUEUE: RefCellSchedSafe<SoftLock<Inner>> =
+    RefCellSchedSafe::new(SoftLock::new(Inner::new()));
+
+/// Return the contention statistics of the timer queue.
+#[cfg(feature = "soft_lock_stats")]
+pub(crate) fn timer_queue_soft_lock_stats() -> crate::debug::soft_lock_stats::SoftLockStats {
+    TIMER_QUEUE.with_suspended_scheduler(|queue, _| queue.stats())
+}
+
+/// The number of armed timers, including the pended ones. Reserving a slot
+/// before arming guarantees that neither the heap nor the pending buffer
+/// overflows.
+static ARMED_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Arm a one-shot timer notifying the mailbox after the given number of
+/// milliseconds. The mailbox counts the notification even if no task is
+/// waiting on it yet.
+///
+/// This function is allowed in ISR context.
+pub fn arm_allow_isr(ms: u32, mailbox: &'static Mailbox) -> Result<(), TimerError> {
+    // See `tick_cmp` for the reason of limitation.
+    if ms > i32::MAX as u32 {
+        return Err(TimerError::TooLong);
+    }
+
+    ARMED_COUNT
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+            (count < config::TIMER_QUEUE_LENGTH).then_some(count + 1)
+        })
+        .map_err(|_| TimerError::QueueFull)?;
+
+    let timer = Timer {
+        fire_at_tick: get_tick().wrapping_add(ms),
+        mailbox,
+    };
+
+    TIMER_QUEUE.with_suspended_scheduler(|queue, _| {
+        queue.with_access(|access| match access {
+            Access::Full { full_access } => {
+                full_access
+                    .heap
+                    .lock_now_or_die()
+                    .push(timer)
+                    .ok()
+                    .unwrap_or_die();
+            }
+            // The full access owner will move the timer into the heap.
+            Access::PendOnly { pend_access } => {
+                pend_access.pending.enqueue(timer).ok().unwrap_or_die();
+            }
+        })
+    });
+
+    Ok(())
+}
+
+/// Return the number of armed timers that have not fired.
+pub fn armed_count() -> usize {
+    ARMED_COUNT.load(Ordering::SeqCst)
+}
+
+/// Fire the expired timers. Called by the SysTick handler.
+pub(crate) fn fire_expired_timers() {
+    // Avoid touching the queue on every tick when no timer is armed.
+    if ARMED_COUNT.load(Ordering::SeqCst) == 0 {
+        return;
+    }
+
+    TIMER_QUEUE.with_suspended_scheduler(|queue, _| {
+        queue.with_access(|access| match access {
+            Access::Full { full_access } => full_access.fire_expired(),
+            Access::PendOnly { pend_access } => {
+                pend_access.time_to_fire.store(true, Ordering::SeqCst)
+            }
+        })
+    });
+}
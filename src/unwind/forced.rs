@@ -1,9 +1,11 @@
 //! Forced unwinding is used to forcefully terminate a task while reclaiming
-//! its allocated resources. The only current use of forced unwinding is to
-//! terminate a task whose stack is going to overflow but does not enable
-//! dynamic stack extension. In this case, the segmented stack runtime
+//! its allocated resources. Forced unwinding is used to terminate a task
+//! whose stack is going to overflow but does not enable dynamic stack
+//! extension. In this case, the segmented stack runtime
 //! [`crate::task::more_stack`] will divert the original function call to
 //! [`diverted_unwind`] instead, which further starts the unwinding process.
+//! It is also used to terminate a task on the request of another task, or
+//! when the task misses its watchdog deadline, see [`unwind_if_requested`].
 //!
 //! A notable caveat to forced unwinding is that it is an undefined behavior
 //! if we initiate unwinding from inside a drop handler function. Thus, if an
@@ -50,8 +52,12 @@
 //!    in [boot::reset](crate::boot::reset) and remains constant.
 
 use super::unwind;
-use crate::config;
-use core::arch::asm;
+use crate::{
+    config,
+    schedule::current,
+    task::{self, TaskLocalStorage},
+};
+use core::{arch::asm, ptr::addr_of_mut};
 
 /// Just jump to the entry point to start unwinding.
 #[naked]
@@ -83,3 +89,46 @@ pub(crate) extern "C" fn deferred_unwind() {
         )
     }
 }
+
+/// Start unwinding the current task if another task has requested to
+/// terminate it with [`TaskHandle::terminate`](crate::task::TaskHandle::terminate),
+/// or if it has missed its [watchdog](crate::task::watchdog_register)
+/// deadline.
+///
+/// Called only where the task resumes with no kernel state left half-updated
+/// on its behalf, i.e., after sleeping, yielding, being suspended, waiting on
+/// a mailbox, and at checkpoints. The task must not be unwound right after
+/// any context switch, e.g., while a synchronization primitive still records
+/// it as the waiting task.
+///
+/// Like a forced unwinding on stack overflow, the unwinding is deferred if
+/// the task is running a drop handler.
+#[inline(never)]
+pub(crate) fn unwind_if_requested() {
+    let (terminating, watchdog_expired) = current::with_cur_task(|cur_task| {
+        if cur_task.is_unwinding() {
+            return (false, false);
+        }
+        // The watchdog flag is cleared, so that a task restarted reusing
+        // the task struct is not unwound again.
+        (cur_task.is_terminating(), cur_task.take_watchdog_expired())
+    });
+    if !terminating && !watchdog_expired {
+        return;
+    }
+    if watchdog_expired {
+        task::record_watchdog_expiry();
+    }
+
+    // Safety: The TLS area at the fixed address holds the fields of the
+    // currently running task.
+    unsafe {
+        let tls = config::__TLS_MEM_ADDR as *mut TaskLocalStorage;
+        if addr_of_mut!((*tls).nested_drop_cnt).read_volatile() > 0 {
+            addr_of_mut!((*tls).unwind_pending).write_volatile(1);
+            return;
+        }
+    }
+
+    diverted_unwind();
+}
@@ -1,8 +1,14 @@
-use crate::unrecoverable;
+use crate::{debug::breadcrumb, unrecoverable};
 use core::panic::PanicInfo;
 
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
+fn panic(info: &PanicInfo) -> ! {
+    // Without unwinding, every panic halts the system.
+    breadcrumb::record_panic(info);
+    breadcrumb::commit_panic();
+    #[cfg(feature = "crash_log")]
+    crate::debug::crash_log::record_panic(info);
+    crate::debug::panic_report::report(info);
     unrecoverable::die();
 }
 
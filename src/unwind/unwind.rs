@@ -38,6 +38,7 @@ use super::{
 };
 use crate::{
     config,
+    debug::breadcrumb,
     interrupt::{
         context_switch, svc,
         svc_handler::SVCNum,
@@ -406,6 +407,13 @@ fn try_concurrent_restart() {
             }
         }
 
+        // Do not restart the task if it has been restarted the maximum number
+        // of times. The task entry trampoline will run the callback after the
+        // unwinding finishes.
+        if cur_task.is_restart_exhausted() {
+            return;
+        }
+
         // Otherwise, we try to concurrently restart the panicked task. It is
         // fine if we are not able to start a new instance running concurrently,
         // probably because the maximum number of tasks has been reached. In
@@ -470,6 +478,14 @@ impl UnwindState<'static> {
         // Mark that we are now unwinding.
         set_unwinding(true);
 
+        #[cfg(feature = "sched_events")]
+        {
+            use crate::debug::sched_events::{self, SchedEventKind};
+            let task = (!current::is_in_isr_context())
+                .then(|| current::with_cur_task(|cur_task| cur_task.get_uid()));
+            sched_events::record(SchedEventKind::Unwind, task);
+        }
+
         // Allocate memory on the heap first and manually initialize the fields.
         // This is to avoid increasing the stack memory footprint.
         // See Rust issue #53827: https://github.com/rust-lang/rust/issues/53827
@@ -511,14 +527,20 @@ impl UnwindState<'static> {
         // handler but do not touch the task.
         if !current::is_in_isr_context() {
             current::with_cur_task(|cur_task| {
-                if cur_task.is_restartable() {
+                // A terminated task is not restarted. With a panic observer,
+                // the task is restarted only after the unwinding finishes, if
+                // the observer allows.
+                if cur_task.is_restartable()
+                    && !cur_task.is_terminating()
+                    && !task::has_panic_observer()
+                {
                     try_concurrent_restart();
                 }
 
                 // Reduce the priority of the previously panicked task, so that
                 // the unwinding procedure of the panicked task uses only
                 // otherwise idle CPU time.
-                cur_task.change_intrinsic_priority(config::UNWIND_PRIORITY);
+                cur_task.reduce_priority_for_unwinding();
             });
 
             // Let the scheduler re-schedule so the above priority reduction
@@ -721,8 +743,8 @@ impl<'a> UnwindState<'a> {
             // Update the stack usage.
             current::with_cur_task(|cur_task| {
                 cur_task.with_stack_ctrl_block(|scb| {
-                    scb.cumulated_size
-                        .fetch_sub(stklet_meta.count_size, Ordering::SeqCst)
+                    scb.sub_usage(stklet_meta.count_size);
+                    scb.count_stacklet_free();
                 })
             });
 
@@ -827,8 +849,13 @@ pub extern "C" fn start_unwind_entry() {
                                             // boundary of the unwinder's stacklet
                                             // into `r3`.
 
+            ".if    {fp_ctxt}",
             "vpush  {{d8-d15}}",            // Push `d8-d15` onto the stack. They will
                                             // become part of the `UnwindInitContext`.
+            ".else",
+            "sub    sp, #64",               // Without floating point context, the
+                                            // slots for `d8-d15` are left unused.
+            ".endif",
             "push   {{r0-r3}}",             // Push `r0-r3` onto the stack. They will
                                             // become part of the `UnwindInitContext`.
             "push   {{r4-r11}}",            // Save callee-saved registers. They will
@@ -851,7 +878,9 @@ pub extern "C" fn start_unwind_entry() {
             "pop    {{r0-r3}}",             // Get the 4 fields of `LandInfo` into `r0-r3`.
             "add    r2, #4",                // Let `r2` point to `gp_regs[4]`.
             "ldmia  r2, {{r4-r11}}",        // Restore `r4` to `r11`.
+            ".if    {fp_ctxt}",
             "vldmia r3, {{d8-d15}}",        // Restore `d8` to `d15`.
+            ".endif",
 
             // If we are in an ISR, skip the following SVC, because we need not free any
             // stacklet since the code is already running in the contiguous stack. Also,
@@ -876,6 +905,7 @@ pub extern "C" fn start_unwind_entry() {
             tls_mem_addr = const config::__TLS_MEM_ADDR,
             task_unwind_prep = const(SVCNum::TaskUnwindPrepare as u8),
             task_unwind_land = const(SVCNum::TaskUnwindLand as u8),
+            fp_ctxt = const config::FP_CONTEXT_SAVE as u32,
             options(noreturn)
         )
     }
@@ -920,6 +950,13 @@ pub fn is_unwinding() -> bool {
     }
 }
 
+/// Halt the system when the panic being unwound cannot be recovered, leaving
+/// a breadcrumb of the panic for the next boot.
+fn die_unwinding(arg: &'static str) -> ! {
+    breadcrumb::commit_panic();
+    unrecoverable::die_with_arg(arg)
+}
+
 pub fn set_unwinding(val: bool) {
     if current::is_in_isr_context() {
         set_isr_unwinding(val)
@@ -940,12 +977,15 @@ pub fn unwind_next_function(unw_state_ptr: *mut UnwindState) -> Option<u32> {
     // Otherwise, we just came back after unwinding the function represented
     // by the state. We step the state to its caller function.
     } else {
-        unw_state.step().unwrap_or_die();
+        unw_state.step().unwrap_or_else(die_unwinding);
     }
 
     #[cfg(feature = "unwind_print_trace")]
     dbg_println!("unwinding at PC: {:#010x}", unw_state.gp_regs[ARMGPReg::PC]);
 
+    #[cfg(feature = "crash_log")]
+    crate::debug::crash_log::record_frame(unw_state.gp_regs[ARMGPReg::PC]);
+
     #[cfg(feature = "unwind_debug")]
     {
         dbg_println!("unwind_next_function: current state");
@@ -954,9 +994,7 @@ pub fn unwind_next_function(unw_state_ptr: *mut UnwindState) -> Option<u32> {
 
     // Get unwind information.
     let unw_info = match &unw_state.unw_ability {
-        UnwindAbility::CantUnwind => {
-            unrecoverable::die_with_arg("unwind_next_function: can't unwind.")
-        }
+        UnwindAbility::CantUnwind => die_unwinding("unwind_next_function: can't unwind."),
         UnwindAbility::CanUnwind(unw_info) => unw_info,
     };
 
@@ -1081,7 +1119,9 @@ unsafe extern "C" fn unwind_resume_entry(unw_state_ptr: *mut UnwindState) -> ! {
             "pop    {{r0-r3}}",           // Get the 4 fields of `LandInfo` into `r0-r3`.
             "add    r2, #4",              // Let `r2` point to `gp_regs[4]`.
             "ldmia  r2, {{r4-r11}}",      // Restore `r4` to `r11`.
+            ".if    {fp_ctxt}",
             "vldmia r3, {{d8-d15}}",      // Restore `d8` to `d15`.
+            ".endif",
 
             // If we are in an ISR, skip the following SVC, because we need not free any
             // stacklet since the code is already running in the contiguous stack. Also,
@@ -1104,6 +1144,7 @@ unsafe extern "C" fn unwind_resume_entry(unw_state_ptr: *mut UnwindState) -> ! {
             resume_unwind = sym resume_unwind,
             task_unwind_prep = const(SVCNum::TaskUnwindPrepare as u8),
             task_unwind_land = const(SVCNum::TaskUnwindLand as u8),
+            fp_ctxt = const config::FP_CONTEXT_SAVE as u32,
             options(noreturn)
         )
     }
@@ -1114,7 +1155,17 @@ unsafe extern "C" fn unwind_resume_entry(unw_state_ptr: *mut UnwindState) -> ! {
 /// The function is marked `unsafe` because it should not be invoked
 /// by any programmer's code.
 #[panic_handler]
-unsafe fn panic(_info: &PanicInfo) -> ! {
+unsafe fn panic(info: &PanicInfo) -> ! {
+    breadcrumb::record_panic(info);
+    // A panic while already unwinding cannot be recovered, and the system
+    // halts when the unwinder starts. Leave a breadcrumb of it.
+    if is_unwinding() {
+        breadcrumb::commit_panic();
+    }
+    #[cfg(feature = "crash_log")]
+    crate::debug::crash_log::record_panic(info);
+    crate::debug::panic_report::report(info);
+    task::record_panic(info);
     start_unwind_entry();
 
     // Should not reach here.